@@ -0,0 +1,188 @@
+//! Every sound effect played with `play_sound_at` or `play_sound_effect` is recorded as a
+//! `SoundEvent`, while the sound event log is recording, along with the simulation tick it was
+//! played on and the variation and volume that was picked for it. UI sounds are not recorded, as
+//! they are not part of the match.
+//!
+//! The recorded events are saved with the input recording of a match, and when the recording is
+//! played back, the events are played back with `start_sound_playback`, in stead of the sounds
+//! played by the simulation, so that the replay has the same audio as the match.
+//!
+//! Recording is started at the start of every match and the log must be advanced with
+//! `advance_sound_event_log`, once every simulation tick, which also plays back any events that
+//! are due, while playing back.
+
+use macroquad::{experimental::collections::storage, prelude::*};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{json, Resources};
+
+use super::{sound::play_sound_raw, AudioBus};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SoundEvent {
+    /// The simulation tick, counted from the start of the recording, that the sound was played on
+    pub tick: u64,
+    #[serde(rename = "sound_effect")]
+    pub sound_effect_id: String,
+    /// The index of the variation of the sound effect that was picked
+    #[serde(default)]
+    pub variation: usize,
+    /// The volume the sound was played at, with jitter applied, but without bus volume and
+    /// positional attenuation, as these depend on the settings and the camera of the viewer
+    pub volume: f32,
+    pub bus: AudioBus,
+    /// The position the sound was played at, if it was played in the world
+    #[serde(
+        default,
+        with = "json::vec2_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub position: Option<Vec2>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum SoundEventLogState {
+    Stopped,
+    Recording,
+    /// The events of the log are played back, and the sounds played by the simulation are muted.
+    /// This holds the index of the next event to play.
+    PlayingBack(usize),
+}
+
+struct SoundEventLog {
+    state: SoundEventLogState,
+    tick: u64,
+    events: Vec<SoundEvent>,
+}
+
+static mut SOUND_EVENT_LOG: Option<SoundEventLog> = None;
+
+unsafe fn get_sound_event_log() -> &'static mut SoundEventLog {
+    SOUND_EVENT_LOG.get_or_insert(SoundEventLog {
+        state: SoundEventLogState::Stopped,
+        tick: 0,
+        events: Vec::new(),
+    })
+}
+
+/// This clears the log and starts recording sound events
+pub fn start_sound_recording() {
+    let log = unsafe { get_sound_event_log() };
+
+    log.state = SoundEventLogState::Recording;
+    log.tick = 0;
+    log.events.clear();
+}
+
+/// This starts playing back `events`, as their ticks are reached. If there are no events, like
+/// for a recording that was made without sound, the sounds played by the simulation are recorded
+/// in stead, so that the replay is not silent.
+pub fn start_sound_playback(events: Vec<SoundEvent>) {
+    if events.is_empty() {
+        start_sound_recording();
+        return;
+    }
+
+    let log = unsafe { get_sound_event_log() };
+
+    log.state = SoundEventLogState::PlayingBack(0);
+    log.tick = 0;
+    log.events = events;
+}
+
+/// This stops recording, or playing back, sound events and clears the log
+pub fn stop_sound_event_log() {
+    let log = unsafe { get_sound_event_log() };
+
+    log.state = SoundEventLogState::Stopped;
+    log.events.clear();
+}
+
+/// Returns the events that have been recorded since the recording was started
+pub fn get_recorded_sound_events() -> Vec<SoundEvent> {
+    let log = unsafe { get_sound_event_log() };
+
+    if log.state == SoundEventLogState::Recording {
+        log.events.clone()
+    } else {
+        Vec::new()
+    }
+}
+
+/// Returns `true` if recorded events are played back, in which case sounds played by the
+/// simulation should be muted
+pub(super) fn is_playing_back_sound_events() -> bool {
+    let log = unsafe { get_sound_event_log() };
+    matches!(log.state, SoundEventLogState::PlayingBack(_))
+}
+
+/// This advances the tick that recorded events are stamped with, or, while playing back, plays
+/// the events of the current tick. It should be called once every simulation tick.
+pub fn advance_sound_event_log() {
+    let due_events = {
+        let log = unsafe { get_sound_event_log() };
+
+        match log.state {
+            SoundEventLogState::Stopped => return,
+            SoundEventLogState::Recording => Vec::new(),
+            SoundEventLogState::PlayingBack(next_event) => {
+                let tick = log.tick;
+
+                let due_cnt = log.events[next_event..]
+                    .iter()
+                    .take_while(|event| event.tick <= tick)
+                    .count();
+
+                log.state = SoundEventLogState::PlayingBack(next_event + due_cnt);
+                log.events[next_event..next_event + due_cnt].to_vec()
+            }
+        }
+    };
+
+    for event in &due_events {
+        play_sound_event(event);
+    }
+
+    unsafe { get_sound_event_log() }.tick += 1;
+}
+
+pub(super) fn record_sound_event(
+    sound_effect_id: &str,
+    variation: usize,
+    volume: f32,
+    bus: AudioBus,
+    position: Option<Vec2>,
+) {
+    let log = unsafe { get_sound_event_log() };
+
+    if log.state == SoundEventLogState::Recording && bus != AudioBus::Ui {
+        log.events.push(SoundEvent {
+            tick: log.tick,
+            sound_effect_id: sound_effect_id.to_string(),
+            variation,
+            volume,
+            bus,
+            position,
+        });
+    }
+}
+
+/// This plays a recorded sound event, using the same variation and volume as when it was recorded.
+/// Positional attenuation and bus volumes are applied for the current listener and settings.
+fn play_sound_event(event: &SoundEvent) {
+    let resources = storage::get::<Resources>();
+
+    match resources.sounds.get(&event.sound_effect_id) {
+        Some(sound) => play_sound_raw(
+            sound.get_variation(event.variation),
+            event.volume,
+            event.bus,
+            event.position,
+        ),
+        None => println!(
+            "WARNING: Invalid sound effect id '{}' in sound event",
+            &event.sound_effect_id
+        ),
+    }
+}
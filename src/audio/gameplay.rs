@@ -0,0 +1,26 @@
+//! Sound effects that are played in response to gameplay events, in stead of by the gameplay code
+//! that causes them.
+
+use macroquad::experimental::collections::storage;
+
+use crate::{game::GameEvent, Resources};
+
+use super::play_sound_at;
+
+pub struct GameplaySounds;
+
+impl GameplaySounds {
+    pub const LISTENER_ID: &'static str = "gameplay_sounds";
+
+    const PICKUP_SOUND_ID: &'static str = "pickup";
+
+    pub fn on_game_event(event: &GameEvent) {
+        if let GameEvent::ItemPickedUp { position, .. } = *event {
+            let resources = storage::get::<Resources>();
+
+            if let Some(sound) = resources.sounds.get(Self::PICKUP_SOUND_ID) {
+                play_sound_at(sound, position);
+            }
+        }
+    }
+}
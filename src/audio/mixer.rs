@@ -0,0 +1,125 @@
+use macroquad::experimental::collections::storage;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{localization::tr, Config};
+
+/// All playback is routed through one of these buses, the volume of which is set by the user, in
+/// the `volume` section of the config. The volume of every bus is multiplied by the `Master` bus.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioBus {
+    Master,
+    Music,
+    Sfx,
+    Ui,
+    Voice,
+}
+
+impl AudioBus {
+    pub const ALL: [AudioBus; 5] = [
+        AudioBus::Master,
+        AudioBus::Music,
+        AudioBus::Sfx,
+        AudioBus::Ui,
+        AudioBus::Voice,
+    ];
+
+    /// Returns the title of the bus, in the current language
+    pub fn get_title(&self) -> String {
+        let key = match self {
+            Self::Master => "audio_bus.master",
+            Self::Music => "audio_bus.music",
+            Self::Sfx => "audio_bus.sfx",
+            Self::Ui => "audio_bus.ui",
+            Self::Voice => "audio_bus.voice",
+        };
+
+        tr(key)
+    }
+
+    /// Returns the volume of the bus, as set in the config, without the master volume applied
+    pub fn get_bus_volume(&self) -> f32 {
+        storage::try_get::<Config>()
+            .map(|config| config.volume.get(*self))
+            .unwrap_or(1.0)
+    }
+
+    /// Returns the volume that playback on this bus should be scaled by, with the master volume
+    /// applied
+    pub fn get_volume(&self) -> f32 {
+        let volume = self.get_bus_volume();
+
+        if *self == Self::Master {
+            volume
+        } else {
+            volume * Self::Master.get_bus_volume()
+        }
+    }
+}
+
+/// The volume of each audio bus, between `0.0` and `1.0`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VolumeConfig {
+    #[serde(default = "VolumeConfig::default_volume")]
+    pub master: f32,
+    #[serde(default = "VolumeConfig::default_volume")]
+    pub music: f32,
+    #[serde(default = "VolumeConfig::default_volume")]
+    pub sfx: f32,
+    #[serde(default = "VolumeConfig::default_volume")]
+    pub ui: f32,
+    #[serde(default = "VolumeConfig::default_volume")]
+    pub voice: f32,
+}
+
+impl VolumeConfig {
+    pub fn default_volume() -> f32 {
+        1.0
+    }
+
+    pub fn get(&self, bus: AudioBus) -> f32 {
+        match bus {
+            AudioBus::Master => self.master,
+            AudioBus::Music => self.music,
+            AudioBus::Sfx => self.sfx,
+            AudioBus::Ui => self.ui,
+            AudioBus::Voice => self.voice,
+        }
+    }
+
+    pub fn set(&mut self, bus: AudioBus, volume: f32) {
+        let volume = volume.clamp(0.0, 1.0);
+
+        match bus {
+            AudioBus::Master => self.master = volume,
+            AudioBus::Music => self.music = volume,
+            AudioBus::Sfx => self.sfx = volume,
+            AudioBus::Ui => self.ui = volume,
+            AudioBus::Voice => self.voice = volume,
+        }
+    }
+
+    /// Returns a copy with all volumes clamped to the valid range
+    pub fn clamped(&self) -> Self {
+        let mut res = self.clone();
+
+        for bus in AudioBus::ALL {
+            res.set(bus, self.get(bus));
+        }
+
+        res
+    }
+}
+
+impl Default for VolumeConfig {
+    fn default() -> Self {
+        VolumeConfig {
+            master: Self::default_volume(),
+            music: Self::default_volume(),
+            sfx: Self::default_volume(),
+            ui: Self::default_volume(),
+            voice: Self::default_volume(),
+        }
+    }
+}
@@ -0,0 +1,22 @@
+//! Sound playback for the game. Gameplay sounds should be played at their position in the world,
+//! with `play_sound_at`, so that they are attenuated by their distance from the listener.
+
+mod events;
+mod gameplay;
+mod mixer;
+mod music;
+mod positional;
+mod sound;
+
+pub use events::{
+    advance_sound_event_log, get_recorded_sound_events, start_sound_playback,
+    start_sound_recording, stop_sound_event_log, SoundEvent,
+};
+pub use gameplay::GameplaySounds;
+pub use mixer::{AudioBus, VolumeConfig};
+pub use music::{
+    duck_music, set_music_intensity, start_match_music, start_music, start_overtime_music,
+    stop_music, update_music, MusicStem, MusicTrack, DEFAULT_MATCH_MUSIC_ID, MENU_MUSIC_ID,
+};
+pub use positional::{get_positional_params, play_sound_at, play_sound_at_ex, PositionalParams};
+pub use sound::{play_sound_effect, SoundGroup};
@@ -0,0 +1,351 @@
+//! Music playback. Changing the track will crossfade from the current track to the new one, and
+//! tracks that have an intro section will play the intro once, before looping the rest of the
+//! track. The music can be ducked, briefly lowering its volume, so that big moments, like large
+//! explosions, stand out.
+//!
+//! Tracks can have additional stems, layered on top of the main loop, that fade in and out as the
+//! music intensity, set with `set_music_intensity`, crosses their thresholds.
+//!
+//! `update_music` must be called every frame, for fades and intros to progress.
+
+use macroquad::{
+    audio::{play_sound, set_sound_volume, stop_sound, PlaySoundParams, Sound},
+    experimental::collections::storage,
+    prelude::*,
+};
+
+use crate::{resources::MapMetadata, Resources};
+
+use super::AudioBus;
+
+/// A layer of a music track that is looped along with the main loop, and which is only audible
+/// while the music intensity is at or above `intensity`
+#[derive(Debug, Copy, Clone)]
+pub struct MusicStem {
+    pub sound: Sound,
+    pub intensity: f32,
+}
+
+/// A music track, as it is stored in `Resources`
+#[derive(Debug, Clone)]
+pub struct MusicTrack {
+    pub sound: Sound,
+    /// An intro section that is played once, before `sound` is looped, and its duration, in
+    /// seconds. The audio backend can not tell when a sound has ended, so the duration has to be
+    /// specified in the track metadata.
+    pub intro: Option<(Sound, f32)>,
+    /// Stems that are started along with the main loop, for dynamic intensity.
+    /// These should have the same length as the main loop, so that they stay in sync.
+    pub stems: Vec<MusicStem>,
+}
+
+struct PlayingTrack {
+    id: String,
+    track: MusicTrack,
+    /// The current fade volume, between `0.0` and `1.0`
+    fade: f32,
+    /// The time since the track was started, used to start the loop after the intro
+    time: f32,
+    is_in_intro: bool,
+    /// The current fade volume of each stem, between `0.0` and `1.0`
+    stem_fades: Vec<f32>,
+}
+
+impl PlayingTrack {
+    fn start(id: &str, track: MusicTrack, fade: f32) -> Self {
+        let is_in_intro = track.intro.is_some();
+        let stem_fades = vec![0.0; track.stems.len()];
+
+        let res = PlayingTrack {
+            id: id.to_string(),
+            track,
+            fade,
+            time: 0.0,
+            is_in_intro,
+            stem_fades,
+        };
+
+        match res.track.intro {
+            Some((intro, _)) => play_sound(
+                intro,
+                PlaySoundParams {
+                    looped: false,
+                    volume: get_volume(fade, 1.0),
+                },
+            ),
+            None => res.start_loop(1.0),
+        }
+
+        res
+    }
+
+    /// This starts the main loop and all stems, so that they are in sync. Stems are started at
+    /// zero volume, and faded in by `update_music`.
+    fn start_loop(&self, duck: f32) {
+        play_sound(
+            self.track.sound,
+            PlaySoundParams {
+                looped: true,
+                volume: get_volume(self.fade, duck),
+            },
+        );
+
+        for (stem, fade) in self.track.stems.iter().zip(&self.stem_fades) {
+            play_sound(
+                stem.sound,
+                PlaySoundParams {
+                    looped: true,
+                    volume: get_volume(self.fade * fade, duck),
+                },
+            );
+        }
+    }
+
+    fn set_volume(&self, duck: f32) {
+        set_sound_volume(self.get_current_sound(), get_volume(self.fade, duck));
+
+        if !self.is_in_intro {
+            for (stem, fade) in self.track.stems.iter().zip(&self.stem_fades) {
+                set_sound_volume(stem.sound, get_volume(self.fade * fade, duck));
+            }
+        }
+    }
+
+    fn get_current_sound(&self) -> Sound {
+        match self.track.intro {
+            Some((intro, _)) if self.is_in_intro => intro,
+            _ => self.track.sound,
+        }
+    }
+
+    fn stop(&self) {
+        stop_sound(self.get_current_sound());
+
+        if !self.is_in_intro {
+            for stem in &self.track.stems {
+                stop_sound(stem.sound);
+            }
+        }
+    }
+}
+
+struct MusicState {
+    current: Option<PlayingTrack>,
+    fading_out: Vec<PlayingTrack>,
+    /// The track that will be played when `start_overtime_music` is called
+    overtime_id: Option<String>,
+    duck_amount: f32,
+    duck_timer: f32,
+    duck_duration: f32,
+    /// The current music intensity, between `0.0` and `1.0`
+    intensity: f32,
+    /// While this is `true`, the music is kept at its maximum intensity
+    is_overtime: bool,
+}
+
+static mut MUSIC_STATE: Option<MusicState> = None;
+
+unsafe fn get_music_state() -> &'static mut MusicState {
+    MUSIC_STATE.get_or_insert(MusicState {
+        current: None,
+        fading_out: Vec::new(),
+        overtime_id: None,
+        duck_amount: 0.0,
+        duck_timer: 0.0,
+        duck_duration: 0.0,
+        intensity: 0.0,
+        is_overtime: false,
+    })
+}
+
+const MUSIC_VOLUME: f32 = 0.6;
+
+/// Returns the playback volume of a track, on the `Music` bus
+fn get_volume(fade: f32, duck: f32) -> f32 {
+    fade * duck * MUSIC_VOLUME * AudioBus::Music.get_volume()
+}
+
+/// The duration of a crossfade between two tracks, in seconds
+const CROSSFADE_DURATION: f32 = 1.5;
+
+/// The duration of a stem fading in or out, in seconds
+const STEM_FADE_DURATION: f32 = 2.0;
+
+/// The part of a duck that is spent fading back in
+const DUCK_RELEASE_FACTOR: f32 = 0.5;
+
+pub const MENU_MUSIC_ID: &str = "fishsticks";
+pub const DEFAULT_MATCH_MUSIC_ID: &str = "fish_tide";
+
+/// This crossfades from the current track to the track with `id`. If the track is already
+/// playing, this does nothing.
+pub fn start_music(id: &str) {
+    let state = unsafe { get_music_state() };
+
+    if state
+        .current
+        .as_ref()
+        .map(|current| current.id == id)
+        .unwrap_or(false)
+    {
+        return;
+    }
+
+    if let Some(current) = state.current.take() {
+        state.fading_out.push(current);
+    }
+
+    // A track that is fading out is faded back in, in stead of being restarted
+    if let Some(i) = state.fading_out.iter().position(|track| track.id == id) {
+        state.current = Some(state.fading_out.remove(i));
+        return;
+    }
+
+    let track = {
+        let resources = storage::get::<Resources>();
+        resources.music.get(id).cloned()
+    };
+
+    match track {
+        Some(track) => {
+            let fade = if state.fading_out.is_empty() {
+                1.0
+            } else {
+                0.0
+            };
+            state.current = Some(PlayingTrack::start(id, track, fade));
+        }
+        None => println!("WARNING: Invalid music track id '{}'", id),
+    }
+}
+
+/// This crossfades to the track of the map described by `meta`, or the default match track, if
+/// the map does not specify a track
+pub fn start_match_music(meta: &MapMetadata) {
+    let state = unsafe { get_music_state() };
+
+    state.overtime_id = meta.overtime_music.clone();
+    state.intensity = 0.0;
+    state.is_overtime = false;
+
+    start_music(meta.music.as_deref().unwrap_or(DEFAULT_MATCH_MUSIC_ID));
+}
+
+/// This crossfades to the overtime track of the current map, if it has one, and keeps the music
+/// intensity at its maximum, until the next match starts
+#[allow(dead_code)]
+pub fn start_overtime_music() {
+    let state = unsafe { get_music_state() };
+
+    state.is_overtime = true;
+
+    if let Some(id) = state.overtime_id.clone() {
+        start_music(&id);
+    }
+}
+
+/// This sets the music intensity, between `0.0` and `1.0`. Stems of the current track with an
+/// intensity threshold at or below this will fade in, while the rest will fade out.
+pub fn set_music_intensity(intensity: f32) {
+    unsafe { get_music_state() }.intensity = intensity.clamp(0.0, 1.0);
+}
+
+/// This stops all music immediately
+#[allow(dead_code)]
+pub fn stop_music() {
+    let state = unsafe { get_music_state() };
+
+    if let Some(current) = state.current.take() {
+        current.stop();
+    }
+
+    for track in state.fading_out.drain(..) {
+        track.stop();
+    }
+}
+
+/// This lowers the volume of the music by `amount`, between `0.0` and `1.0`, for `duration`,
+/// after which it fades back in. A duck will not replace a stronger duck in progress.
+pub fn duck_music(amount: f32, duration: f32) {
+    let state = unsafe { get_music_state() };
+
+    let amount = amount.clamp(0.0, 1.0);
+
+    if amount >= state.duck_amount * get_duck_factor(state) {
+        state.duck_amount = amount;
+        state.duck_timer = 0.0;
+        state.duck_duration = duration;
+    }
+}
+
+/// Returns the part of the duck amount currently in effect, as the end of a duck fades out
+fn get_duck_factor(state: &MusicState) -> f32 {
+    if state.duck_timer >= state.duck_duration {
+        return 0.0;
+    }
+
+    let release_duration = state.duck_duration * DUCK_RELEASE_FACTOR;
+    let remaining = state.duck_duration - state.duck_timer;
+
+    if remaining < release_duration {
+        remaining / release_duration
+    } else {
+        1.0
+    }
+}
+
+/// This advances fades, ducking and intros and should be called once every frame
+pub fn update_music() {
+    let dt = get_frame_time();
+    let state = unsafe { get_music_state() };
+
+    if state.duck_timer < state.duck_duration {
+        state.duck_timer += dt;
+    }
+
+    let duck = 1.0 - state.duck_amount * get_duck_factor(state);
+    let fade_step = dt / CROSSFADE_DURATION;
+    let stem_fade_step = dt / STEM_FADE_DURATION;
+    let intensity = if state.is_overtime {
+        1.0
+    } else {
+        state.intensity
+    };
+
+    if let Some(current) = &mut state.current {
+        current.fade = (current.fade + fade_step).min(1.0);
+        current.time += dt;
+
+        for (stem, fade) in current.track.stems.iter().zip(&mut current.stem_fades) {
+            if intensity >= stem.intensity {
+                *fade = (*fade + stem_fade_step).min(1.0);
+            } else {
+                *fade = (*fade - stem_fade_step).max(0.0);
+            }
+        }
+
+        if let Some((intro, duration)) = current.track.intro {
+            if current.is_in_intro && current.time >= duration {
+                current.is_in_intro = false;
+
+                stop_sound(intro);
+
+                current.start_loop(duck);
+            }
+        }
+
+        current.set_volume(duck);
+    }
+
+    for track in &mut state.fading_out {
+        track.fade = (track.fade - fade_step).max(0.0);
+
+        if track.fade <= 0.0 {
+            track.stop();
+        } else {
+            track.set_volume(duck);
+        }
+    }
+
+    state.fading_out.retain(|track| track.fade > 0.0);
+}
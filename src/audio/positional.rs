@@ -0,0 +1,139 @@
+use macroquad::{
+    experimental::{collections::storage, scene},
+    prelude::*,
+};
+
+use crate::{
+    physics::{raycast, QueryMask},
+    GameCamera, GameWorld,
+};
+
+use super::{
+    events::{is_playing_back_sound_events, record_sound_event},
+    sound::play_sound_raw,
+    AudioBus, SoundGroup,
+};
+
+/// The volume and stereo pan of a sound, relative to the listener
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PositionalParams {
+    pub volume: f32,
+    /// The stereo pan of the sound, from `-1.0` (left) to `1.0` (right).
+    /// This is not applied yet, as the audio backend of macroquad has no support for panning.
+    #[allow(dead_code)]
+    pub pan: f32,
+    /// This is `true` if solid tiles block the line between the sound and the listener. The
+    /// attenuation of occluded sounds is included in `volume`, but they should also be muffled
+    /// with a low-pass filter, which is not applied yet, as the audio backend of macroquad has no
+    /// support for filters.
+    #[allow(dead_code)]
+    pub is_occluded: bool,
+}
+
+impl Default for PositionalParams {
+    fn default() -> Self {
+        PositionalParams {
+            volume: 1.0,
+            pan: 0.0,
+            is_occluded: false,
+        }
+    }
+}
+
+/// The listener is the center of the game camera, and its range is half the size of the view of
+/// the camera. Sounds within the view are played at full volume, while sounds outside of it fall
+/// off with distance.
+struct Listener {
+    position: Vec2,
+    range: Vec2,
+}
+
+impl Listener {
+    fn get() -> Option<Self> {
+        let camera = scene::find_node_by_type::<GameCamera>()?.get_macroquad_camera();
+
+        if camera.zoom.x == 0.0 || camera.zoom.y == 0.0 {
+            return None;
+        }
+
+        Some(Listener {
+            position: camera.target,
+            range: vec2(1.0 / camera.zoom.x.abs(), 1.0 / camera.zoom.y.abs()),
+        })
+    }
+}
+
+/// The distance, in multiples of the range of the listener, at which sounds become inaudible
+const MAX_DISTANCE: f32 = 4.0;
+
+/// The volume factor applied to sounds that are occluded by solid tiles
+const OCCLUSION_VOLUME: f32 = 0.35;
+
+/// Returns `true` if solid tiles block the straight line from `position` to the listener
+fn is_occluded(position: Vec2, listener: &Listener) -> bool {
+    if storage::try_get::<GameWorld>().is_none() {
+        return false;
+    }
+
+    raycast(position, listener.position - position, QueryMask::TILES).is_some()
+}
+
+/// Returns the volume and pan of a sound at `position`. If there is no listener, like when
+/// not in a game, the sound will be played at full volume and centered.
+///
+/// Sounds outside of the view of the listener are attenuated further, if they are occluded by
+/// solid tiles, so that fights in other parts of the map sound distant. Sounds within the view are
+/// never occluded, as the camera center will often be inside of solid tiles.
+pub fn get_positional_params(position: Vec2) -> PositionalParams {
+    let listener = match Listener::get() {
+        Some(listener) => listener,
+        None => return PositionalParams::default(),
+    };
+
+    let offset = (position - listener.position) / listener.range;
+    let distance = offset.x.abs().max(offset.y.abs());
+
+    let mut volume = if distance <= 1.0 {
+        1.0
+    } else {
+        (1.0 - (distance - 1.0) / (MAX_DISTANCE - 1.0)).clamp(0.0, 1.0)
+    };
+
+    let is_occluded = distance > 1.0 && volume > 0.0 && is_occluded(position, &listener);
+
+    if is_occluded {
+        volume *= OCCLUSION_VOLUME;
+    }
+
+    PositionalParams {
+        volume: volume * volume,
+        pan: offset.x.clamp(-1.0, 1.0),
+        is_occluded,
+    }
+}
+
+/// This plays a random variation of `sound` at `position` in the world, on the `Sfx` bus,
+/// attenuated by its distance from the listener
+pub fn play_sound_at(sound: &SoundGroup, position: Vec2) {
+    play_sound_at_ex(sound, position, 1.0);
+}
+
+/// This plays a random variation of `sound` at `position` in the world, at `volume`, attenuated
+/// by its distance from the listener
+pub fn play_sound_at_ex(sound: &SoundGroup, position: Vec2, volume: f32) {
+    let (i, jitter) = sound.pick();
+    let volume = volume * jitter;
+
+    record_sound_event(&sound.id, i, volume, AudioBus::Sfx, Some(position));
+
+    if is_playing_back_sound_events() {
+        return;
+    }
+
+    play_sound_raw(
+        sound.get_variation(i),
+        volume,
+        AudioBus::Sfx,
+        Some(position),
+    );
+}
@@ -0,0 +1,108 @@
+use macroquad::{
+    audio::{play_sound, PlaySoundParams, Sound},
+    prelude::*,
+};
+
+use super::{
+    events::{is_playing_back_sound_events, record_sound_event},
+    get_positional_params, AudioBus,
+};
+
+use crate::cosmetic_rand;
+
+/// A sound effect, as it is stored in `Resources`. A sound effect can have several variations,
+/// of which a random one is picked every time it is played, and its volume and pitch can be
+/// randomized, so that sounds that are played often, like footsteps and gunshots, do not sound
+/// identical every time.
+#[derive(Debug, Clone)]
+pub struct SoundGroup {
+    /// The id of the sound effect in `Resources`, used when recording sound events
+    pub id: String,
+    variations: Vec<Sound>,
+    /// The maximum amount the volume is randomly lowered by, between `0.0` and `1.0`
+    volume_jitter: f32,
+    /// The maximum amount the pitch is randomly changed by, in either direction, as a factor of
+    /// the normal pitch.
+    /// This is not applied yet, as the audio backend of macroquad has no support for pitch.
+    #[allow(dead_code)]
+    pitch_jitter: f32,
+}
+
+impl SoundGroup {
+    pub fn new(id: &str, variations: Vec<Sound>, volume_jitter: f32, pitch_jitter: f32) -> Self {
+        assert!(
+            !variations.is_empty(),
+            "SoundGroup: A sound group must have at least one variation"
+        );
+
+        SoundGroup {
+            id: id.to_string(),
+            variations,
+            volume_jitter: volume_jitter.clamp(0.0, 1.0),
+            pitch_jitter: pitch_jitter.max(0.0),
+        }
+    }
+
+    /// Returns the index of a random variation and a random volume factor, within the jitter
+    /// range
+    pub fn pick(&self) -> (usize, f32) {
+        let i = if self.variations.len() > 1 {
+            cosmetic_rand::gen_range(0, self.variations.len())
+        } else {
+            0
+        };
+
+        let volume = if self.volume_jitter > 0.0 {
+            1.0 - cosmetic_rand::gen_range(0.0, self.volume_jitter)
+        } else {
+            1.0
+        };
+
+        (i, volume)
+    }
+
+    /// Returns the variation at index `i`, or the first variation, if `i` is out of bounds
+    pub fn get_variation(&self, i: usize) -> Sound {
+        self.variations
+            .get(i)
+            .copied()
+            .unwrap_or(self.variations[0])
+    }
+}
+
+/// This plays `sound` at `volume` on `bus`. If `position` is specified, the sound is attenuated
+/// by its distance from the listener. This does not record a sound event, so it should only be
+/// used by functions that do.
+pub(super) fn play_sound_raw(sound: Sound, volume: f32, bus: AudioBus, position: Option<Vec2>) {
+    let attenuation = position
+        .map(|position| get_positional_params(position).volume)
+        .unwrap_or(1.0);
+
+    let volume = volume * attenuation * bus.get_volume();
+
+    if volume > 0.0 {
+        play_sound(
+            sound,
+            PlaySoundParams {
+                looped: false,
+                volume,
+            },
+        );
+    }
+}
+
+/// This plays a random variation of `sound` on `bus`, without any positional attenuation. This
+/// should be used for sounds that do not originate from a position in the world, like UI sounds.
+pub fn play_sound_effect(sound: &SoundGroup, bus: AudioBus, volume: f32) {
+    let (i, jitter) = sound.pick();
+    let volume = volume * jitter;
+
+    record_sound_event(&sound.id, i, volume, bus, None);
+
+    // The recorded sounds are played in stead, while playing back a recording
+    if bus != AudioBus::Ui && is_playing_back_sound_events() {
+        return;
+    }
+
+    play_sound_raw(sound.get_variation(i), volume, bus, None);
+}
@@ -9,22 +9,48 @@ use macroquad::{
 
 use serde::{Deserialize, Serialize};
 
-use crate::{json, Resources};
+use crate::{audio::play_sound_at, game::SurfaceSound, json, symbol::Symbol, Resources};
+
+/// A hitbox that is active on a specific frame of an animation, used for things like the arc of a
+/// melee weapon swing. The rect is relative to the upper left corner of the frame, in unscaled
+/// pixels, and it will be mirrored along with the frame, when the animation is drawn flipped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrameHitbox {
+    pub frame: u32,
+    #[serde(with = "json::RectDef")]
+    pub rect: Rect,
+}
+
+/// A sound effect that is played when a specific frame of an animation is reached, like the
+/// footsteps of a run animation. The sound is played at the position passed to
+/// `AnimationPlayer::play_frame_sounds`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrameSound {
+    pub frame: u32,
+    #[serde(rename = "sound_effect")]
+    pub sound_effect_id: String,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Animation {
-    pub id: String,
+    pub id: Symbol,
     pub row: u32,
     pub frames: u32,
     pub fps: u32,
     #[serde(default, skip_serializing_if = "json::is_false")]
     pub is_looping: bool,
+    /// Hitboxes that are active on specific frames of the animation
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hitboxes: Vec<FrameHitbox>,
+    /// Sound effects that are played when specific frames of the animation are reached
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sounds: Vec<FrameSound>,
 }
 
 impl From<Animation> for MQAnimation {
     fn from(a: Animation) -> Self {
         MQAnimation {
-            name: a.id,
+            name: a.id.to_string(),
             row: a.row,
             frames: a.frames,
             fps: a.fps,
@@ -102,6 +128,8 @@ pub struct AnimationPlayer {
     animations: Vec<Animation>,
     time: f32,
     current_frame: u32,
+    /// The animation index and frame that frame sounds were last checked for
+    sound_frame: Option<(usize, u32)>,
     pub is_deactivated: bool,
 }
 
@@ -146,7 +174,7 @@ impl AnimationPlayer {
                         "AnimationPlayer: Invalid animation id '{}' (duplicate)",
                         &a.id
                     );
-                    ids.push(a.id.clone());
+                    ids.push(a.id);
 
                     let res: MQAnimation = a.into();
                     res
@@ -176,10 +204,14 @@ impl AnimationPlayer {
             is_deactivated: params.is_deactivated,
             time: 0.0,
             current_frame: 0,
+            sound_frame: None,
         }
     }
 
-    pub fn update(&mut self) {
+    /// This advances the animation by `dt`. Animations that are part of the simulation, like
+    /// those of players and weapons, must be advanced by `SIMULATION_DELTA_TIME`, as the hitboxes
+    /// of attacks depend on the current frame.
+    pub fn update(&mut self, dt: f32) {
         let animation = &self.animations[self.sprite.current_animation()];
         let is_last_frame = self.current_frame == animation.frames - 1;
 
@@ -190,7 +222,7 @@ impl AnimationPlayer {
         }
 
         if self.sprite.playing {
-            self.time += get_frame_time();
+            self.time += dt;
             if self.time > 1. / animation.fps as f32 {
                 self.current_frame += 1;
                 self.time = 0.0;
@@ -201,6 +233,44 @@ impl AnimationPlayer {
         self.set_frame(self.current_frame as usize);
     }
 
+    /// This plays the sound effects of the current frame, if the frame has changed since the last
+    /// time this was called. This should be called after `update`, with the position of the
+    /// entity that the animation belongs to.
+    pub fn play_frame_sounds(&mut self, position: Vec2) {
+        self.play_frame_sounds_on(position, SurfaceSound::Default);
+    }
+
+    /// This plays the sound effects of the current frame, like `play_frame_sounds`, using the
+    /// variants of the sounds for `surface`, if they exist. This is used for things like
+    /// footsteps, by entities that walk on the ground.
+    pub fn play_frame_sounds_on(&mut self, position: Vec2, surface: SurfaceSound) {
+        let key = (self.sprite.current_animation(), self.current_frame);
+
+        if self.is_deactivated || self.sound_frame == Some(key) {
+            return;
+        }
+
+        self.sound_frame = Some(key);
+
+        let animation = &self.animations[key.0];
+
+        if animation.sounds.is_empty() {
+            return;
+        }
+
+        let resources = storage::get::<Resources>();
+
+        for sound in animation.sounds.iter().filter(|s| s.frame == key.1) {
+            match surface.get_sound(&resources.sounds, &sound.sound_effect_id) {
+                Some(sound) => play_sound_at(sound, position),
+                None => println!(
+                    "WARNING: AnimationPlayer: Invalid sound effect id '{}'",
+                    &sound.sound_effect_id
+                ),
+            }
+        }
+    }
+
     pub fn draw(&self, position: Vec2, rotation: f32, flip_x: bool, flip_y: bool) {
         if !self.is_deactivated {
             let source_rect = self.sprite.frame().source_rect;
@@ -251,13 +321,67 @@ impl AnimationPlayer {
         self.scale = scale;
     }
 
-    pub fn get_animation(&self, id: &str) -> Option<&Animation> {
+    /// Returns the hitboxes that are active on the current frame, in world space. `position`
+    /// and flip should be the same as what is passed to `draw`.
+    pub fn get_frame_hitboxes(&self, position: Vec2, flip_x: bool, flip_y: bool) -> Vec<Rect> {
+        let animation = &self.animations[self.sprite.current_animation()];
+        let size = self.get_size();
+
+        animation
+            .hitboxes
+            .iter()
+            .filter(|hitbox| hitbox.frame == self.current_frame)
+            .map(|hitbox| {
+                let mut rect = Rect::new(
+                    hitbox.rect.x * self.scale,
+                    hitbox.rect.y * self.scale,
+                    hitbox.rect.w * self.scale,
+                    hitbox.rect.h * self.scale,
+                );
+
+                if flip_x {
+                    rect.x = size.x - rect.x - rect.w;
+                }
+
+                if flip_y {
+                    rect.y = size.y - rect.y - rect.h;
+                }
+
+                rect.x += position.x + self.offset.x;
+                rect.y += position.y + self.offset.y;
+
+                rect
+            })
+            .collect()
+    }
+
+    pub fn get_animation<K: Into<Symbol>>(&self, id: K) -> Option<&Animation> {
+        let id = id.into();
         self.animations.iter().find(|a| a.id == id)
     }
 
+    pub fn get_tint(&self) -> Color {
+        self.tint
+    }
+
+    pub fn set_tint(&mut self, tint: Color) {
+        self.tint = tint;
+    }
+
+    // Returns the id of the current animation
+    pub fn get_current_animation_id(&self) -> Symbol {
+        self.animations[self.sprite.current_animation()].id
+    }
+
+    // Returns the current frame of the current animation
+    pub fn get_current_frame(&self) -> u32 {
+        self.current_frame
+    }
+
     // Set the current animation, using the animations id.
     // Will return a reference to the animation or `None`, if it doesn't exist
-    pub fn set_animation(&mut self, id: &str) -> Option<&Animation> {
+    pub fn set_animation<K: Into<Symbol>>(&mut self, id: K) -> Option<&Animation> {
+        let id = id.into();
         let res = self.animations.iter().enumerate().find(|(_, a)| a.id == id);
 
         if let Some((i, animation)) = res {
@@ -270,6 +394,7 @@ impl AnimationPlayer {
 
     // Set the frame of the current animation
     pub fn set_frame(&mut self, frame: usize) {
+        self.current_frame = frame as u32;
         self.sprite.set_frame(frame as u32);
     }
 
@@ -23,10 +23,10 @@ pub trait EruptedItem {
         // Controls the Actor as long as is erupting,
         // afterwards it informs the actor update to stop calling this function
 
-        body.position.y += PhysicsBody::GRAVITY * get_frame_time().powi(2) / 2.
+        body.position.y += PhysicsBody::get_gravity() * get_frame_time().powi(2) / 2.
             + body.velocity.y * get_frame_time();
         body.position.x += body.velocity.x * get_frame_time();
-        body.velocity.y += PhysicsBody::GRAVITY * get_frame_time();
+        body.velocity.y += PhysicsBody::get_gravity() * get_frame_time();
 
         if body.position.y < enable_at_y || body.velocity.y < 0. {
             return false;
@@ -3,9 +3,11 @@ mod erupted_item;
 mod particle_controller;
 mod physics_body;
 mod sprite;
+mod trail;
 
 pub use animation_player::{Animation, AnimationParams, AnimationPlayer};
 pub use erupted_item::EruptedItem;
 pub use particle_controller::{ParticleController, ParticleControllerParams};
 pub use physics_body::PhysicsBody;
 pub use sprite::{Sprite, SpriteParams};
+pub use trail::{Trail, TrailParams};
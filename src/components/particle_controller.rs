@@ -35,6 +35,13 @@ pub struct ParticleControllerParams {
     /// If this is set to `true` the `ParticleController` will start to emit automatically
     #[serde(default, skip_serializing_if = "bool::is_false")]
     pub should_autostart: bool,
+    /// An optional color that the colors of the particle effect will be multiplied by
+    #[serde(
+        default,
+        with = "json::color_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub color: Option<Color>,
 }
 
 impl Default for ParticleControllerParams {
@@ -47,6 +54,7 @@ impl Default for ParticleControllerParams {
             interval: 0.0,
             animations: None,
             should_autostart: false,
+            color: None,
         }
     }
 }
@@ -65,6 +73,7 @@ pub struct ParticleController {
     emissions: Option<u32>,
     interval: f32,
     animations: Option<AnimationPlayer>,
+    color: Option<Color>,
     delay_timer: f32,
     interval_timer: f32,
     emission_cnt: u32,
@@ -96,6 +105,7 @@ impl ParticleController {
             interval: params.interval,
             emissions: params.emissions,
             animations,
+            color: params.color,
             delay_timer: 0.0,
             interval_timer: params.interval,
             emission_cnt: 0,
@@ -130,6 +140,15 @@ impl ParticleController {
         self.is_active = true;
     }
 
+    pub fn deactivate(&mut self) {
+        if let Some(animations) = &mut self.animations {
+            animations.is_deactivated = true;
+            animations.stop();
+        }
+
+        self.is_active = false;
+    }
+
     pub fn update(&mut self, dt: f32) {
         if self.is_active {
             self.delay_timer += dt;
@@ -140,7 +159,7 @@ impl ParticleController {
 
             if let Some(position) = self.position {
                 if let Some(animations) = &mut self.animations {
-                    animations.update();
+                    animations.update(dt);
                 }
 
                 if self.delay_timer >= self.delay && self.interval_timer >= self.interval {
@@ -148,7 +167,11 @@ impl ParticleController {
 
                     {
                         let mut particles = scene::find_node_by_type::<ParticleEmitters>().unwrap();
-                        particles.spawn(&self.particle_effect_id, position);
+                        if let Some(color) = self.color {
+                            particles.spawn_tinted(&self.particle_effect_id, position, color);
+                        } else {
+                            particles.spawn(&self.particle_effect_id, position);
+                        }
                     }
 
                     if let Some(emissions) = self.emissions {
@@ -1,29 +1,139 @@
 use macroquad::{color, experimental::collections::storage, prelude::*};
 
-use macroquad_platformer::{Actor, World as CollisionWorld};
+use macroquad_platformer::{Actor, Tile, World as CollisionWorld};
 
-use crate::GameWorld;
+use crate::{
+    cvars::{self, CVarFlags, CVarValue},
+    game::{SimulationTimer, TileSurface, SIMULATION_DELTA_TIME},
+    map::TileSlope,
+    math::IsZero,
+    mutators::get_mutator_rules,
+    physics::{CollisionLayers, ForceVolumes},
+    profiler::ProfileScope,
+    GameWorld,
+};
 
+#[cfg(debug_assertions)]
+use crate::physics::PhysicsDebugOverlay;
+
+/// This is the physics body shared by all moving world objects, like players, items, triggered
+/// effects and deployables. All movement and collision resolution is done in `update`, so that
+/// everything that uses a body behaves the same way, with the differences between the various
+/// objects being described by the parameters of the body.
 pub struct PhysicsBody {
     pub collider: Actor,
     pub position: Vec2,
+    /// The position at the start of the last tick, used to interpolate the rendered position
+    pub last_position: Vec2,
     pub size: Vec2,
     pub velocity: Vec2,
     pub is_facing_right: bool,
     pub is_upside_down: bool,
     pub rotation: f32,
-    pub has_friction: bool,
     pub is_on_ground: bool,
     pub was_on_ground_last_frame: bool,
     pub has_gravity: bool,
+    /// The factor of the gravity applied to the body, when `has_gravity` is `true`
+    pub gravity_scale: f32,
+    /// The fraction of horizontal velocity lost each frame, while on the ground
+    pub friction: f32,
+    /// The fraction of horizontal velocity lost each frame, while in the air
+    pub air_friction: f32,
     pub bouncyness: f32,
+    /// The mass of the body, which divides impulses and knockback applied to it, so that heavier
+    /// bodies are pushed around less
+    pub mass: f32,
     pub can_rotate: bool,
+    /// If this is `false` the body will fall through platforms
+    pub is_colliding_with_platforms: bool,
+    /// The collision layer of the body, which is what kind of object it is
+    pub collision_layer: CollisionLayers,
+    /// The layers that the body interacts with. If this does not contain the platforms layer, the
+    /// body will fall through platforms.
+    pub collision_mask: CollisionLayers,
     /// This is the offset between the collider and the body's position
     pub collider_offset: Vec2,
+    /// The slope the body is standing on, if any
+    pub slope: Option<TileSlope>,
+    /// The horizontal velocity imparted by force volumes. This is kept separate from `velocity`,
+    /// so that it is not lost when the horizontal velocity is set directly, like it is by player
+    /// movement, and it will decay once the body leaves the volume.
+    pub drift_velocity: f32,
+    /// The surface of the tile the body is standing on. The conveyor speed of the surface is added
+    /// to the movement of the body, in stead of its velocity, so that it stacks with player input,
+    /// and it is carried over to `drift_velocity` when the body leaves the ground.
+    pub surface: TileSurface,
+    /// This is set to `true` when the body overlaps a solid, like a door that has closed on it,
+    /// and could not be pushed out of it, within `MAX_PUSH_OUT_DISTANCE`. It is up to the owner
+    /// of the body to resolve the crush, like a player dying from it.
+    pub is_crushed: bool,
+    /// The factor of gravity that pushes the body up, when it is submerged in a water volume. A
+    /// body with a buoyancy above one will float, while a body without buoyancy will sink.
+    pub buoyancy: f32,
+    /// This is `true` while a buoyant body is in a water volume
+    pub is_floating: bool,
+    /// If this is `true` the rendered position of the body will bob up and down while it is on
+    /// the ground, like a pickup waiting to be picked up. Floating bodies always bob.
+    pub is_bobbing: bool,
 }
 
 impl PhysicsBody {
-    pub const GRAVITY: f32 = 1800.0;
+    pub const GRAVITY_CVAR: &'static str = "physics.gravity";
+    const DEFAULT_GRAVITY: f32 = 1800.0;
+
+    /// The friction applied to bodies created with `has_friction` set to `true`
+    pub const DEFAULT_FRICTION: f32 = 0.04;
+
+    pub const DEFAULT_MASS: f32 = 1.0;
+
+    /// When knockback is applied to a body on the ground, it will be lifted at least by this
+    /// fraction of the horizontal knockback, so that it is not immediately lost to ground friction
+    const GROUNDED_KNOCKBACK_LIFT: f32 = 0.25;
+
+    /// The gravity scale of bodies that are thrown, like grenades
+    pub const THROWN_GRAVITY_SCALE: f32 = 2.0;
+
+    /// A bounce that is slower than this will bring the body to rest
+    const MIN_BOUNCE_SPEED: f32 = 60.0;
+
+    /// Horizontal velocity below this will be set to zero, when friction is applied
+    const MIN_SPEED: f32 = 1.0;
+
+    /// The fraction of `drift_velocity` lost each frame
+    const DRIFT_DAMPING: f32 = 0.05;
+
+    /// The fraction of velocity lost each frame by a body floating in water
+    const WATER_DRAG: f32 = 0.05;
+
+    /// The amplitude, in pixels, of the visual bobbing of floating and bobbing bodies
+    const BOB_AMPLITUDE: f32 = 3.0;
+    /// The speed of the visual bobbing, in radians per second
+    const BOB_FREQUENCY: f32 = 3.0;
+    /// The phase of the bobbing is offset by the horizontal position of a body, multiplied by this,
+    /// so that bodies next to each other do not bob in unison
+    const BOB_PHASE_FACTOR: f32 = 0.05;
+
+    /// The maximum distance a body that overlaps a solid will be pushed, to get it out of the solid.
+    /// If it can not be pushed out within this distance, it is crushed.
+    const MAX_PUSH_OUT_DISTANCE: f32 = 8.0;
+
+    /// The distance a body that was on the ground in the previous frame will be snapped down to
+    /// the surface of a slope, so that it stays grounded when moving down it
+    const SLOPE_SNAP_DISTANCE: f32 = 8.0;
+
+    pub fn register_cvars() {
+        cvars::register(
+            Self::GRAVITY_CVAR,
+            CVarValue::Float(Self::DEFAULT_GRAVITY),
+            CVarFlags::CHEAT | CVarFlags::REPLICATED,
+            "The downward acceleration of physics bodies, in pixels per second squared",
+        );
+    }
+
+    /// Returns the downward acceleration of physics bodies, before any gravity scale is applied
+    pub fn get_gravity() -> f32 {
+        cvars::get_float(Self::GRAVITY_CVAR)
+    }
 
     pub fn new<O: Into<Option<Vec2>>>(
         collision_world: &mut CollisionWorld,
@@ -38,24 +148,51 @@ impl PhysicsBody {
 
         let collider = collision_world.add_actor(position, size.x as _, size.y as _);
 
+        let friction = if has_friction {
+            Self::DEFAULT_FRICTION
+        } else {
+            0.0
+        };
+
         PhysicsBody {
             position,
+            last_position: position,
             size,
             is_facing_right: true,
             is_upside_down: false,
             velocity: vec2(0.0, 0.0),
             rotation: angle,
-            has_friction,
             collider,
             was_on_ground_last_frame: false,
             is_on_ground: false,
             has_gravity: true,
+            gravity_scale: 1.0,
+            friction,
+            air_friction: 0.0,
             bouncyness: 0.0,
+            mass: Self::DEFAULT_MASS,
             can_rotate,
+            is_colliding_with_platforms: true,
+            collision_layer: CollisionLayers::NONE,
+            collision_mask: CollisionLayers::ALL,
             collider_offset,
+            slope: None,
+            drift_velocity: 0.0,
+            surface: TileSurface::default(),
+            is_crushed: false,
+            buoyancy: 0.0,
+            is_floating: false,
+            is_bobbing: false,
         }
     }
 
+    /// This sets the parameters used for bodies that are thrown, like grenades, that fall faster
+    /// and lose speed while in the air
+    pub fn set_thrown(&mut self) {
+        self.gravity_scale = Self::THROWN_GRAVITY_SCALE;
+        self.air_friction = Self::DEFAULT_FRICTION;
+    }
+
     pub fn facing_dir(&self) -> Vec2 {
         if self.is_facing_right {
             vec2(1.0, 0.0)
@@ -64,6 +201,109 @@ impl PhysicsBody {
         }
     }
 
+    /// Returns the position that the body should be drawn at, which is interpolated between the
+    /// position at the start of the last tick and the current position, so that movement is
+    /// smooth regardless of the difference between the render frame rate and the tick rate.
+    pub fn get_render_position(&self) -> Vec2 {
+        self.last_position
+            .lerp(self.position, SimulationTimer::get_interpolation())
+            + self.get_bob_offset()
+    }
+
+    /// Returns the offset of the visual bobbing of the body, which is added to the rendered
+    /// position. Bodies resting on the ground only bob upwards, so that they do not sink into it.
+    pub fn get_bob_offset(&self) -> Vec2 {
+        let is_resting = self.is_bobbing && self.is_on_ground;
+
+        if !self.is_floating && !is_resting {
+            return Vec2::ZERO;
+        }
+
+        let phase =
+            get_time() as f32 * Self::BOB_FREQUENCY + self.position.x * Self::BOB_PHASE_FACTOR;
+
+        let offset = if self.is_floating {
+            phase.sin()
+        } else {
+            (phase.sin() - 1.0) / 2.0
+        };
+
+        vec2(0.0, offset * Self::BOB_AMPLITUDE)
+    }
+
+    /// This moves the body, without interpolating the rendered position from the previous
+    /// position, which should be used when a body is teleported, like when a player respawns
+    pub fn set_position(&mut self, position: Vec2) {
+        self.position = position;
+        self.last_position = position;
+
+        let mut world = storage::get_mut::<GameWorld>();
+        world
+            .collision_world
+            .set_actor_position(self.collider, position + self.collider_offset);
+    }
+
+    /// This moves the body by `offset`, stopping at any solids in the collision world
+    pub fn translate(&mut self, offset: Vec2) {
+        let mut world = storage::get_mut::<GameWorld>();
+
+        world.collision_world.move_h(self.collider, offset.x);
+        world.collision_world.move_v(self.collider, offset.y);
+
+        self.position = world.collision_world.actor_pos(self.collider) - self.collider_offset;
+    }
+
+    /// Returns `true` if the layer and mask of this body and `other` allow them to interact
+    pub fn can_collide_with(&self, other: &PhysicsBody) -> bool {
+        CollisionLayers::can_collide(
+            self.collision_layer,
+            self.collision_mask,
+            other.collision_layer,
+            other.collision_mask,
+        )
+    }
+
+    /// This changes the velocity of the body by `impulse`, divided by the mass of the body
+    pub fn apply_impulse(&mut self, impulse: Vec2) {
+        if self.mass > 0.0 {
+            self.velocity += impulse / self.mass;
+        }
+    }
+
+    /// This applies `knockback` as an impulse, after removing any velocity the body has against
+    /// its direction, so that moving towards the source of the knockback will not cancel it out.
+    /// If the body is on the ground, knockback into the ground is ignored, and the body will be
+    /// lifted slightly off it.
+    pub fn apply_knockback(&mut self, knockback: Vec2) {
+        let mut knockback = knockback;
+
+        if self.is_on_ground {
+            knockback.y = knockback
+                .y
+                .min(-knockback.x.abs() * Self::GROUNDED_KNOCKBACK_LIFT);
+        }
+
+        let direction = knockback.normalize_or_zero();
+        let opposing_speed = self.velocity.dot(direction);
+        if opposing_speed < 0.0 {
+            self.velocity -= direction * opposing_speed;
+        }
+
+        self.apply_impulse(knockback);
+    }
+
+    /// Returns the fraction, between zero and one, of the difference between its current and its
+    /// desired horizontal velocity, that a body moving under its own power, like a walking player,
+    /// can make up in a single tick. This is lower than one on slippery surfaces, like ice.
+    pub fn get_traction(&self) -> f32 {
+        match self.surface.friction {
+            Some(friction) if self.is_on_ground => {
+                (friction / Self::DEFAULT_FRICTION).clamp(0.0, 1.0)
+            }
+            _ => 1.0,
+        }
+    }
+
     pub fn get_collider_rect(&self) -> Rect {
         let position = self.position + self.collider_offset;
 
@@ -75,43 +315,154 @@ impl PhysicsBody {
         world.collision_world.descent(self.collider);
     }
 
+    /// This moves the body by its velocity and resolves collisions with the collision world and
+    /// with slopes, as well as applying gravity, friction and rotation.
     pub fn update(&mut self) {
-        let dt = get_frame_time();
+        let _scope = ProfileScope::new("physics");
+
+        self.last_position = self.position;
+
+        let dt = SIMULATION_DELTA_TIME;
         let mut world = storage::get_mut::<GameWorld>();
 
+        if !self.is_colliding_with_platforms
+            || !self.collision_mask.contains(CollisionLayers::PLATFORMS)
+        {
+            world.collision_world.descent(self.collider);
+        }
+
         // Don't use offset position for ground check
         let position = world.collision_world.actor_pos(self.collider);
 
         self.was_on_ground_last_frame = self.is_on_ground;
         self.is_on_ground = world
             .collision_world
-            .collide_check(self.collider, position + vec2(0.0, 1.0));
+            .collide_check(self.collider, position + vec2(0.0, 1.0))
+            || (self.velocity.y >= 0.0
+                && world
+                    .get_slope_ground(&self.get_actor_rect(position), 1.0)
+                    .is_some());
+
+        let surface = if self.is_on_ground {
+            world.get_surface_under(&self.get_actor_rect(position))
+        } else {
+            TileSurface::default()
+        };
+
+        if !self.is_on_ground && !self.surface.conveyor_speed.is_zero() {
+            self.drift_velocity += self.surface.conveyor_speed;
+        }
+
+        self.surface = surface;
 
         if !self.is_on_ground && self.has_gravity {
-            self.velocity.y += Self::GRAVITY * dt;
+            let gravity = Self::get_gravity() * get_mutator_rules().gravity_scale;
+            self.velocity.y += gravity * self.gravity_scale * dt;
         }
 
-        if !world
-            .collision_world
-            .move_h(self.collider, self.velocity.x * dt)
-        {
+        // Vertical force is applied to the velocity, so that it counters gravity, while horizontal
+        // force is applied to the drift velocity
+        if self.has_gravity {
+            let center = position + self.size / 2.0;
+            let force = ForceVolumes::get_force(center);
+
+            self.velocity.y += force.y * dt;
+            self.drift_velocity += force.x * dt;
+        }
+
+        self.update_buoyancy(position);
+
+        self.drift_velocity *= 1.0 - Self::DRIFT_DAMPING;
+        if self.drift_velocity.abs() <= Self::MIN_SPEED {
+            self.drift_velocity = 0.0;
+        }
+
+        let mut dx = (self.velocity.x * (1.0 - self.surface.stickiness)
+            + self.drift_velocity
+            + self.surface.conveyor_speed)
+            * dt;
+
+        // Walking up a slope is slower, as movement is along the surface of the slope
+        if let Some(slope) = self.slope {
+            let tile_size = world.map.tile_size;
+            let gradient = slope.get_gradient() * tile_size.y / tile_size.x;
+            if gradient.signum() == dx.signum() {
+                dx *= gradient.atan().cos();
+            }
+        }
+
+        if !world.collision_world.move_h(self.collider, dx) {
+            #[cfg(debug_assertions)]
+            {
+                let rect = self.get_actor_rect(world.collision_world.actor_pos(self.collider));
+                let (x, normal) = if dx > 0.0 {
+                    (rect.x + rect.w, vec2(-1.0, 0.0))
+                } else {
+                    (rect.x, vec2(1.0, 0.0))
+                };
+
+                PhysicsDebugOverlay::add_contact(vec2(x, rect.y + rect.h / 2.0), normal);
+            }
+
             self.velocity.x *= -self.bouncyness;
+            self.drift_velocity = 0.0;
         }
 
         if !world
             .collision_world
             .move_v(self.collider, self.velocity.y * dt)
         {
-            self.velocity.y *= -self.bouncyness;
+            #[cfg(debug_assertions)]
+            {
+                let rect = self.get_actor_rect(world.collision_world.actor_pos(self.collider));
+                let (y, normal) = if self.velocity.y > 0.0 {
+                    (rect.y + rect.h, vec2(0.0, -1.0))
+                } else {
+                    (rect.y, vec2(0.0, 1.0))
+                };
+
+                PhysicsDebugOverlay::add_contact(vec2(rect.x + rect.w / 2.0, y), normal);
+            }
+
+            // Landing on a bouncy surface will use the bounciness of the surface, in stead of the
+            // bouncyness of the body
+            let mut bouncyness = self.bouncyness;
+            if self.velocity.y > 0.0 {
+                let position = world.collision_world.actor_pos(self.collider);
+                let surface = world.get_surface_under(&self.get_actor_rect(position));
+                if surface.bounciness > 0.0 {
+                    bouncyness = surface.bounciness;
+                }
+            }
+
+            self.velocity.y *= -bouncyness;
+
+            if self.velocity.y.abs() < Self::MIN_BOUNCE_SPEED {
+                self.velocity.y = 0.0;
+            }
         }
 
+        self.update_slope(&mut world);
+
+        self.update_crush(&mut world);
+
         if self.can_rotate {
-            // TODO: Rotation
+            self.update_rotation();
         }
 
-        if self.is_on_ground && self.has_friction {
-            self.velocity.x *= 0.96;
-            if self.velocity.x.abs() <= 1.0 {
+        // A slippery surface can only lower the friction of a body, not raise it
+        let friction = if self.is_on_ground {
+            self.surface
+                .friction
+                .map(|friction| friction.min(self.friction))
+                .unwrap_or(self.friction)
+        } else {
+            self.air_friction
+        };
+
+        if friction > 0.0 {
+            self.velocity.x *= 1.0 - friction;
+            if self.velocity.x.abs() <= Self::MIN_SPEED {
                 self.velocity.x = 0.0;
             }
         }
@@ -119,11 +470,136 @@ impl PhysicsBody {
         self.position = world.collision_world.actor_pos(self.collider) - self.collider_offset;
     }
 
-    pub fn update_throw(&mut self) {
+    fn get_actor_rect(&self, actor_position: Vec2) -> Rect {
+        Rect::new(actor_position.x, actor_position.y, self.size.x, self.size.y)
+    }
+
+    /// This resolves collisions with slope tiles, which are not part of the collision world, by
+    /// moving the body up onto the surface of any slope it has sunk into, or, if it was on the
+    /// ground in the previous frame, down onto the surface of a slope right below it.
+    fn update_slope(&mut self, world: &mut GameWorld) {
+        self.slope = None;
+
+        if self.velocity.y < 0.0 {
+            return;
+        }
+
+        let position = world.collision_world.actor_pos(self.collider);
+
+        let max_distance = if self.was_on_ground_last_frame {
+            Self::SLOPE_SNAP_DISTANCE
+        } else {
+            0.0
+        };
+
+        if let Some((surface_y, slope)) =
+            world.get_slope_ground(&self.get_actor_rect(position), max_distance)
+        {
+            world
+                .collision_world
+                .set_actor_position(self.collider, vec2(position.x, surface_y - self.size.y));
+
+            #[cfg(debug_assertions)]
+            PhysicsDebugOverlay::add_contact(
+                vec2(position.x + self.size.x / 2.0, surface_y),
+                slope.get_normal(world.map.tile_size),
+            );
+
+            if self.bouncyness > 0.0 && !self.was_on_ground_last_frame {
+                let normal = slope.get_normal(world.map.tile_size);
+                let velocity = self.velocity;
+                self.velocity = (velocity - 2.0 * velocity.dot(normal) * normal) * self.bouncyness;
+
+                if self.velocity.length() < Self::MIN_BOUNCE_SPEED {
+                    self.velocity.y = 0.0;
+                }
+            } else {
+                self.velocity.y = 0.0;
+            }
+
+            self.is_on_ground = true;
+            self.slope = Some(slope);
+        }
+    }
+
+    /// This pushes a buoyant body up, when its center is below the surface of a water volume, by a
+    /// force that increases with the depth of the center, up to half the height of the body. The
+    /// body will settle where the buoyancy counters gravity, bobbing around that point until the
+    /// water drag has slowed it down.
+    fn update_buoyancy(&mut self, actor_position: Vec2) {
+        self.is_floating = false;
+
+        if !self.has_gravity || self.buoyancy <= 0.0 {
+            return;
+        }
+
+        let center = actor_position + self.size / 2.0;
+
+        if let Some(surface_y) = ForceVolumes::get_water_surface(center) {
+            self.is_floating = true;
+
+            let depth = if self.size.y > 0.0 {
+                ((center.y - surface_y) / (self.size.y / 2.0)).clamp(0.0, 1.0)
+            } else {
+                1.0
+            };
+
+            let dt = SIMULATION_DELTA_TIME;
+            self.velocity.y -=
+                Self::get_gravity() * self.gravity_scale * self.buoyancy * depth * dt;
+            self.velocity *= 1.0 - Self::WATER_DRAG;
+        }
+    }
+
+    /// This checks if the body overlaps a solid, which can happen when a moving solid, like a door,
+    /// pushes it into another solid, as the collision world does not stop solids from moving into
+    /// actors. If it does, the body is pushed out to the closest free position, horizontally or
+    /// upwards, or, if there is none within `MAX_PUSH_OUT_DISTANCE`, it is marked as crushed.
+    fn update_crush(&mut self, world: &mut GameWorld) {
+        let position = world.collision_world.actor_pos(self.collider);
+
+        self.is_crushed = false;
+
+        if !self.is_overlapping_solid(&world.collision_world, position) {
+            return;
+        }
+
+        let mut distance = 1.0;
+        while distance <= Self::MAX_PUSH_OUT_DISTANCE {
+            for offset in [
+                vec2(-distance, 0.0),
+                vec2(distance, 0.0),
+                vec2(0.0, -distance),
+            ] {
+                if !self.is_overlapping_solid(&world.collision_world, position + offset) {
+                    world
+                        .collision_world
+                        .set_actor_position(self.collider, position + offset);
+
+                    return;
+                }
+            }
+
+            distance += 1.0;
+        }
+
+        self.is_crushed = true;
+    }
+
+    /// Returns `true` if the collider of the body would overlap a solid, at `actor_position`.
+    /// Platforms are ignored, as bodies are allowed to pass through them from below.
+    fn is_overlapping_solid(&self, collision_world: &CollisionWorld, actor_position: Vec2) -> bool {
+        let tile =
+            collision_world.collide_solids(actor_position, self.size.x as i32, self.size.y as i32);
+
+        tile != Tile::Empty && tile != Tile::JumpThrough
+    }
+
+    /// This spins the body while it is in the air, and turns it to rest on one of its sides, when
+    /// it is on the ground
+    fn update_rotation(&mut self) {
         if !self.is_on_ground {
             self.rotation += self.velocity.x.abs() * 0.00045 + self.velocity.y.abs() * 0.00015;
-
-            self.velocity.y += Self::GRAVITY * get_frame_time();
         } else {
             self.rotation %= std::f32::consts::PI * 2.;
             let goal = if self.rotation <= std::f32::consts::PI {
@@ -137,16 +613,13 @@ impl PhysicsBody {
                 self.rotation += (rest * 0.1).max(0.1);
             }
         }
-
-        self.velocity.x *= 0.96;
-        if self.velocity.x.abs() <= 1. {
-            self.velocity.x = 0.0;
-        }
     }
 
     #[cfg(debug_assertions)]
     pub fn debug_draw(&self) {
-        if crate::debug::is_debug_draw_enabled() {
+        let is_overlay_enabled = PhysicsDebugOverlay::is_enabled();
+
+        if crate::debug::is_debug_draw_enabled() || is_overlay_enabled {
             let collider = self.get_collider_rect();
 
             draw_rectangle_lines(
@@ -158,5 +631,14 @@ impl PhysicsBody {
                 color::RED,
             )
         }
+
+        // The velocity is drawn as the distance the body will move over the next tenth of a second
+        if is_overlay_enabled {
+            let collider = self.get_collider_rect();
+            let center = collider.point() + collider.size() / 2.0;
+            let end = center + (self.velocity + vec2(self.drift_velocity, 0.0)) * 0.1;
+
+            draw_line(center.x, center.y, end.x, end.y, 1.0, color::GREEN);
+        }
     }
 }
@@ -175,6 +175,18 @@ impl Sprite {
         self.source_rect.size() * self.scale
     }
 
+    /// Returns the rect that the sprite is drawn in, at `position`, before it is rotated
+    pub fn get_rect(&self, position: Vec2) -> Rect {
+        let size = self.get_size();
+
+        Rect::new(
+            position.x + self.offset.x,
+            position.y + self.offset.y,
+            size.x,
+            size.y,
+        )
+    }
+
     pub fn set_scale(&mut self, scale: f32) {
         self.scale = scale;
     }
@@ -0,0 +1,202 @@
+use std::collections::VecDeque;
+
+use macroquad::{
+    experimental::collections::storage,
+    models::{draw_mesh, Mesh, Vertex},
+    prelude::*,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    frame_alloc::{self, frame_vec},
+    json,
+    math::IsZero,
+    symbol::Symbol,
+    Resources,
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TrailParams {
+    /// The maximum amount of points kept in the history of the trail
+    #[serde(default = "TrailParams::default_length")]
+    pub length: usize,
+    /// A new point is only added to the trail when it has moved at least this far from the last
+    /// point that was added
+    #[serde(default = "TrailParams::default_min_distance")]
+    pub min_distance: f32,
+    /// The width of the trail at its head
+    #[serde(default = "TrailParams::default_width")]
+    pub width: f32,
+    /// The width of the trail at its tail
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    pub end_width: f32,
+    #[serde(default = "TrailParams::default_color", with = "json::ColorDef")]
+    pub color: Color,
+    /// The alpha of the trail at its tail, which is multiplied by the alpha of `color`
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    pub end_alpha: f32,
+    /// The id of a texture that will be stretched along the trail, from head to tail. If this is
+    /// not specified, the trail is drawn in `color`.
+    #[serde(default, rename = "texture", skip_serializing_if = "Option::is_none")]
+    pub texture_id: Option<Symbol>,
+}
+
+impl TrailParams {
+    pub fn default_length() -> usize {
+        12
+    }
+
+    pub fn default_min_distance() -> f32 {
+        4.0
+    }
+
+    pub fn default_width() -> f32 {
+        4.0
+    }
+
+    pub fn default_color() -> Color {
+        Color::new(1.0, 1.0, 1.0, 0.6)
+    }
+}
+
+impl Default for TrailParams {
+    fn default() -> Self {
+        TrailParams {
+            length: Self::default_length(),
+            min_distance: Self::default_min_distance(),
+            width: Self::default_width(),
+            end_width: 0.0,
+            color: Self::default_color(),
+            end_alpha: 0.0,
+            texture_id: None,
+        }
+    }
+}
+
+impl From<TrailParams> for Trail {
+    fn from(params: TrailParams) -> Self {
+        Trail::new(params)
+    }
+}
+
+/// A ribbon drawn along the recent positions of something that moves, like a projectile or a
+/// sliding player. The width and alpha of the trail fall off from its head, at the most recent
+/// point, to its tail.
+#[derive(Clone)]
+pub struct Trail {
+    params: TrailParams,
+    points: VecDeque<Vec2>,
+}
+
+impl Trail {
+    pub fn new(params: TrailParams) -> Self {
+        let points = VecDeque::with_capacity(params.length);
+
+        Trail { params, points }
+    }
+
+    /// This adds `position` to the head of the trail, if it is far enough from the last point
+    /// that was added, removing the oldest point, if the trail is at its full length
+    pub fn add_point(&mut self, position: Vec2) {
+        let is_far_enough = self
+            .points
+            .front()
+            .map(|last| last.distance(position) >= self.params.min_distance)
+            .unwrap_or(true);
+
+        if is_far_enough {
+            self.points.push_front(position);
+            self.points.truncate(self.params.length);
+        }
+    }
+
+    /// This removes the oldest point of the trail, so that a trail that is no longer being added
+    /// to shrinks away, instead of disappearing at once
+    pub fn shrink(&mut self) {
+        self.points.pop_back();
+    }
+
+    pub fn clear(&mut self) {
+        self.points.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// This draws the trail, from `head`, which should be the current render position of whatever
+    /// the trail is attached to, through all the points in its history
+    pub fn draw(&self, head: Vec2) {
+        let mut points = frame_vec::<Vec2>();
+        points.push(head);
+        points.extend(self.points.iter().copied());
+
+        if points.len() < 2 {
+            return;
+        }
+
+        let texture = self.params.texture_id.as_ref().map(|texture_id| {
+            let resources = storage::get::<Resources>();
+            resources
+                .textures
+                .get(texture_id)
+                .map(|res| res.texture)
+                .unwrap_or_else(|| panic!("Trail: Invalid texture ID '{}'", texture_id))
+        });
+
+        let last = (points.len() - 1) as f32;
+
+        let mut vertices = frame_vec::<Vertex>();
+        let mut indices = frame_vec::<u16>();
+
+        for (i, point) in points.iter().enumerate() {
+            let t = i as f32 / last;
+
+            // The direction of the trail at this point, averaged between its neighbors
+            let previous = points[i.saturating_sub(1)];
+            let next = points[(i + 1).min(points.len() - 1)];
+            let direction = (next - previous).normalize_or_zero();
+            let normal = vec2(-direction.y, direction.x);
+
+            let width = self.params.width + (self.params.end_width - self.params.width) * t;
+            let alpha = self.params.color.a * (1.0 + (self.params.end_alpha - 1.0) * t);
+            let color = Color::new(
+                self.params.color.r,
+                self.params.color.g,
+                self.params.color.b,
+                alpha,
+            );
+
+            let offset = normal * width / 2.0;
+
+            vertices.push(Vertex {
+                position: (*point + offset).extend(0.0),
+                uv: vec2(t, 0.0),
+                color,
+            });
+
+            vertices.push(Vertex {
+                position: (*point - offset).extend(0.0),
+                uv: vec2(t, 1.0),
+                color,
+            });
+
+            if i > 0 {
+                let i = (i * 2) as u16;
+                indices.extend_from_slice(&[i - 2, i - 1, i, i - 1, i + 1, i]);
+            }
+        }
+
+        let mesh = Mesh {
+            vertices: vertices.into_vec(),
+            indices: indices.into_vec(),
+            texture,
+        };
+
+        draw_mesh(&mesh);
+
+        frame_alloc::recycle(mesh.vertices);
+        frame_alloc::recycle(mesh.indices);
+    }
+}
@@ -1,27 +1,188 @@
+use crate::audio::VolumeConfig;
 use crate::error::Error;
+use crate::game::{InputBindings, KillFeedConfig, TouchControlsConfig};
+use crate::json;
+use crate::localization::DEFAULT_LANGUAGE_ID;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub fullscreen: bool,
+    /// This is stored so that it can be applied when the window is created, but the version of
+    /// the windowing backend that is currently used will always present with vsync enabled.
+    #[serde(default = "Config::default_vsync")]
+    pub vsync: bool,
     pub high_dpi: bool,
     pub resolution: Resolution,
+    /// A factor that the window resolution is multiplied by, when not in fullscreen, between
+    /// `Config::MIN_RESOLUTION_SCALE` and `Config::MAX_RESOLUTION_SCALE`
+    #[serde(default = "Config::default_resolution_scale")]
+    pub resolution_scale: f32,
+    /// The time, in seconds, a player can go without giving any input, before being considered
+    /// AFK. In local games this will pause the game and in network games the player will be
+    /// protected from damage until input is given. Set this to zero to disable AFK detection.
+    #[serde(default = "Config::default_afk_timeout")]
+    pub afk_timeout: f32,
+    /// A factor that all camera shake is multiplied by, between `0.0` and `1.0`. This can be
+    /// reduced, or set to zero to disable camera shake entirely, for motion-sensitive players.
+    #[serde(default = "Config::default_camera_shake")]
+    pub camera_shake: f32,
+    /// If this is `true`, hit flashes and light flashes, like those of explosions and muzzle
+    /// flashes, are not drawn, for photosensitive players
+    #[serde(default, skip_serializing_if = "json::is_false")]
+    pub reduce_flashing: bool,
+    /// If this is `true`, arrows are drawn at the edge of the screen, pointing towards players
+    /// that are outside of the view of the camera
+    #[serde(default = "Config::default_offscreen_indicators")]
+    pub offscreen_indicators: bool,
+    /// If this is `true`, a minimap of the whole map is drawn in the corner of the screen
+    #[serde(default, skip_serializing_if = "json::is_false")]
+    pub minimap: bool,
+    /// If this is `true`, the name of every player is drawn above their head, in matches of more
+    /// than two players
+    #[serde(default, skip_serializing_if = "json::is_false")]
+    pub nameplates: bool,
+    /// The part of the range of the analog sticks of gamepads, around the center, that is
+    /// ignored, between `0.0` and `Config::MAX_GAMEPAD_DEAD_ZONE`. This can be increased for
+    /// worn gamepads whose sticks don't return all the way to the center.
+    #[serde(default = "Config::default_gamepad_dead_zone")]
+    pub gamepad_dead_zone: f32,
+    /// The id of the language that is used for all text in the game, from the `localization`
+    /// resource file
+    #[serde(default = "Config::default_language")]
+    pub language: String,
+    /// The volume of each of the audio buses
+    #[serde(default)]
+    pub volume: VolumeConfig,
+    #[serde(default)]
+    pub kill_feed: KillFeedConfig,
+    /// The key and button bindings of each input scheme
+    #[serde(default)]
+    pub input_bindings: InputBindings,
+    /// The size and opacity of the touch controls, on web and mobile targets
+    #[serde(default)]
+    pub touch_controls: TouchControlsConfig,
+    /// The ids of the mods that are enabled. The order of this does not matter, as the load
+    /// order of mods is resolved from their dependencies (see `mods`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub enabled_mods: Vec<String>,
+    /// The values of the cvars that have the `ARCHIVE` flag and have been changed from their
+    /// default values, by cvar name (see `cvars`)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub cvars: HashMap<String, String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            fullscreen: false,
+            vsync: true,
+            high_dpi: false,
+            resolution: Resolution::default(),
+            resolution_scale: Self::DEFAULT_RESOLUTION_SCALE,
+            afk_timeout: Self::DEFAULT_AFK_TIMEOUT,
+            camera_shake: Self::DEFAULT_CAMERA_SHAKE,
+            reduce_flashing: false,
+            offscreen_indicators: true,
+            minimap: false,
+            nameplates: false,
+            gamepad_dead_zone: Self::DEFAULT_GAMEPAD_DEAD_ZONE,
+            language: Self::default_language(),
+            volume: VolumeConfig::default(),
+            kill_feed: KillFeedConfig::default(),
+            input_bindings: InputBindings::default(),
+            touch_controls: TouchControlsConfig::default(),
+            enabled_mods: Vec::new(),
+            cvars: HashMap::new(),
+        }
+    }
 }
 
 impl Config {
+    const DEFAULT_AFK_TIMEOUT: f32 = 60.0;
+    const DEFAULT_CAMERA_SHAKE: f32 = 1.0;
+    const DEFAULT_RESOLUTION_SCALE: f32 = 1.0;
+    const DEFAULT_GAMEPAD_DEAD_ZONE: f32 = 0.2;
+
+    pub const MIN_RESOLUTION_SCALE: f32 = 0.5;
+    pub const MAX_RESOLUTION_SCALE: f32 = 2.0;
+
+    pub const MAX_GAMEPAD_DEAD_ZONE: f32 = 0.9;
+
+    pub fn default_vsync() -> bool {
+        true
+    }
+
+    pub fn default_offscreen_indicators() -> bool {
+        true
+    }
+
+    pub fn default_language() -> String {
+        DEFAULT_LANGUAGE_ID.to_string()
+    }
+
+    pub fn default_resolution_scale() -> f32 {
+        Self::DEFAULT_RESOLUTION_SCALE
+    }
+
+    pub fn default_afk_timeout() -> f32 {
+        Self::DEFAULT_AFK_TIMEOUT
+    }
+
+    pub fn default_camera_shake() -> f32 {
+        Self::DEFAULT_CAMERA_SHAKE
+    }
+
+    pub fn default_gamepad_dead_zone() -> f32 {
+        Self::DEFAULT_GAMEPAD_DEAD_ZONE
+    }
+
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
         let path = path.as_ref();
 
         let res = if path.exists() {
             let file_contents = fs::read_to_string(path)?;
-            serde_json::from_str(&file_contents)?
+            serde_json::from_str::<Config>(&file_contents)?
         } else {
             Config::default()
         };
 
-        Ok(res)
+        Ok(Config {
+            resolution_scale: res
+                .resolution_scale
+                .clamp(Self::MIN_RESOLUTION_SCALE, Self::MAX_RESOLUTION_SCALE),
+            camera_shake: res.camera_shake.clamp(0.0, 1.0),
+            gamepad_dead_zone: res
+                .gamepad_dead_zone
+                .clamp(0.0, Self::MAX_GAMEPAD_DEAD_ZONE),
+            volume: res.volume.clamped(),
+            touch_controls: res.touch_controls.clamped(),
+            ..res
+        })
+    }
+
+    /// Returns the size of the window, with `resolution_scale` applied
+    pub fn get_window_size(&self) -> (i32, i32) {
+        let width = self.resolution.width as f32 * self.resolution_scale;
+        let height = self.resolution.height as f32 * self.resolution_scale;
+
+        (width.round() as i32, height.round() as i32)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let str = serde_json::to_string_pretty(self)?;
+        fs::write(path, str)?;
+
+        Ok(())
     }
 }
 
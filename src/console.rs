@@ -0,0 +1,309 @@
+//! The developer console is a drop-down console, toggled with the key below Escape, that runs
+//! commands that have been registered by the various subsystems of the game. Commands are
+//! registered with `register` and they are parsed as a name, followed by arguments separated by
+//! whitespace.
+//!
+//! The console keeps a history of the commands that have been run, which can be navigated with the
+//! up and down arrow keys, and command names are completed with Tab.
+//!
+//! Any implementations of commands must be registered in the `init_console_commands` function.
+
+use macroquad::{
+    color,
+    experimental::scene::{self, Node, RefMut},
+    prelude::*,
+};
+
+use crate::{
+    text::{draw_aligned_text, HorizontalAlignment, VerticalAlignment},
+    Result,
+};
+
+/// This is implemented by console commands. It is called with the arguments that were given to
+/// the command and it returns the output of the command, if it succeeded.
+pub type ConsoleCommandHandler = fn(args: &[&str]) -> Result<String>;
+
+static mut CONSOLE_COMMANDS: Option<Vec<(String, ConsoleCommandHandler)>> = None;
+
+unsafe fn get_console_commands() -> &'static mut Vec<(String, ConsoleCommandHandler)> {
+    CONSOLE_COMMANDS.get_or_insert(Vec::new())
+}
+
+/// This will register a command, replacing any existing command with the same name
+pub fn register(name: &str, handler: ConsoleCommandHandler) {
+    let commands = unsafe { get_console_commands() };
+
+    if let Some(entry) = commands.iter_mut().find(|(other, _)| other == name) {
+        entry.1 = handler;
+    } else {
+        commands.push((name.to_string(), handler));
+        commands.sort_by(|(a, _), (b, _)| a.cmp(b));
+    }
+}
+
+fn get_command_names() -> Vec<String> {
+    unsafe { get_console_commands() }
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect()
+}
+
+fn help(_: &[&str]) -> Result<String> {
+    Ok(format!("Commands: {}", get_command_names().join(", ")))
+}
+
+pub fn init_console_commands() {
+    register("help", help);
+
+    crate::cvars::register_console_commands();
+    crate::debug::register_console_commands();
+    crate::items::register_console_commands();
+    crate::game::MatchStats::register_console_commands();
+    crate::profiler::register_console_commands();
+    crate::register_console_commands();
+}
+
+/// Returns `true` if the console is open. Game input should be ignored while it is.
+pub fn is_console_open() -> bool {
+    scene::find_node_by_type::<Console>()
+        .map(|console| console.is_open)
+        .unwrap_or(false)
+}
+
+pub struct Console {
+    is_open: bool,
+    input: String,
+    /// The lines of output, with the most recent last
+    output: Vec<String>,
+    /// The commands that have been run, with the most recent last
+    history: Vec<String>,
+    /// The position in `history` of the command that is currently in the input line, if the
+    /// history is being navigated
+    history_position: Option<usize>,
+}
+
+impl Console {
+    const TOGGLE_KEY: KeyCode = KeyCode::GraveAccent;
+
+    const OUTPUT_CAPACITY: usize = 128;
+    const HISTORY_CAPACITY: usize = 32;
+
+    /// The share of the screen height covered by the console, when it is open
+    const HEIGHT_FACTOR: f32 = 0.4;
+    const FONT_SIZE: u16 = 18;
+    const LINE_HEIGHT: f32 = 20.0;
+    const MARGIN: f32 = 8.0;
+
+    const BACKGROUND_COLOR: Color = Color {
+        r: 0.0,
+        g: 0.0,
+        b: 0.0,
+        a: 0.8,
+    };
+
+    pub fn new() -> Self {
+        Console {
+            is_open: false,
+            input: String::new(),
+            output: Vec::new(),
+            history: Vec::new(),
+            history_position: None,
+        }
+    }
+
+    fn print(&mut self, line: &str) {
+        for line in line.lines() {
+            self.output.push(line.to_string());
+        }
+
+        if self.output.len() > Self::OUTPUT_CAPACITY {
+            let excess = self.output.len() - Self::OUTPUT_CAPACITY;
+            self.output.drain(..excess);
+        }
+    }
+
+    fn run(&mut self, line: &str) {
+        self.print(&format!("> {}", line));
+
+        if self.history.last().map(|last| last != line).unwrap_or(true) {
+            self.history.push(line.to_string());
+
+            if self.history.len() > Self::HISTORY_CAPACITY {
+                self.history.remove(0);
+            }
+        }
+
+        let mut words = line.split_whitespace();
+
+        if let Some(name) = words.next() {
+            let args = words.collect::<Vec<_>>();
+
+            let handler = unsafe { get_console_commands() }
+                .iter()
+                .find(|(other, _)| other == name)
+                .map(|(_, handler)| *handler);
+
+            match handler {
+                Some(handler) => match handler(&args) {
+                    Ok(output) => {
+                        if !output.is_empty() {
+                            self.print(&output);
+                        }
+                    }
+                    Err(err) => self.print(&format!("Error: {}", err)),
+                },
+                None => self.print(&format!("Unknown command '{}'", name)),
+            }
+        }
+    }
+
+    /// This completes the command name in the input line, as far as it is unambiguous, and prints
+    /// the matching commands, if there are more than one
+    fn complete(&mut self) {
+        if self.input.contains(char::is_whitespace) {
+            return;
+        }
+
+        let matches = get_command_names()
+            .into_iter()
+            .filter(|name| name.starts_with(&self.input))
+            .collect::<Vec<_>>();
+
+        match matches.len() {
+            0 => {}
+            1 => self.input = format!("{} ", matches[0]),
+            _ => {
+                let mut prefix = matches[0].clone();
+                for name in &matches[1..] {
+                    while !name.starts_with(&prefix) {
+                        prefix.pop();
+                    }
+                }
+
+                self.input = prefix;
+                self.print(&matches.join("  "));
+            }
+        }
+    }
+
+    fn navigate_history(&mut self, is_backwards: bool) {
+        if self.history.is_empty() {
+            return;
+        }
+
+        let position = match (self.history_position, is_backwards) {
+            (None, true) => Some(self.history.len() - 1),
+            (None, false) => None,
+            (Some(position), true) => Some(position.saturating_sub(1)),
+            (Some(position), false) => {
+                if position + 1 < self.history.len() {
+                    Some(position + 1)
+                } else {
+                    None
+                }
+            }
+        };
+
+        self.history_position = position;
+        self.input = position
+            .map(|position| self.history[position].clone())
+            .unwrap_or_default();
+    }
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Node for Console {
+    fn update(mut node: RefMut<Self>) {
+        if is_key_pressed(Self::TOGGLE_KEY) {
+            node.is_open = !node.is_open;
+        }
+
+        if !node.is_open {
+            // Characters are queued while the console is closed, so they are discarded here
+            while get_char_pressed().is_some() {}
+            return;
+        }
+
+        while let Some(ch) = get_char_pressed() {
+            if !ch.is_control() && ch != '`' && ch != '~' {
+                node.input.push(ch);
+            }
+        }
+
+        if is_key_pressed(KeyCode::Backspace) {
+            node.input.pop();
+        }
+
+        if is_key_pressed(KeyCode::Tab) {
+            node.complete();
+        }
+
+        if is_key_pressed(KeyCode::Up) {
+            node.navigate_history(true);
+        } else if is_key_pressed(KeyCode::Down) {
+            node.navigate_history(false);
+        }
+
+        if is_key_pressed(KeyCode::Enter) {
+            let line = std::mem::take(&mut node.input);
+            node.history_position = None;
+
+            if !line.trim().is_empty() {
+                node.run(line.trim());
+            }
+        }
+    }
+
+    fn draw(node: RefMut<Self>) {
+        if !node.is_open {
+            return;
+        }
+
+        let height = screen_height() * Self::HEIGHT_FACTOR;
+
+        push_camera_state();
+        set_default_camera();
+
+        draw_rectangle(0.0, 0.0, screen_width(), height, Self::BACKGROUND_COLOR);
+
+        let params = TextParams {
+            font_size: Self::FONT_SIZE,
+            color: color::WHITE,
+            ..Default::default()
+        };
+
+        let input_y = height - Self::MARGIN - Self::LINE_HEIGHT;
+
+        draw_aligned_text(
+            &format!("> {}_", node.input),
+            vec2(Self::MARGIN, input_y),
+            HorizontalAlignment::Left,
+            VerticalAlignment::Top,
+            params,
+        );
+
+        let line_cnt = ((input_y - Self::MARGIN) / Self::LINE_HEIGHT) as usize;
+
+        for (i, line) in node.output.iter().rev().take(line_cnt).enumerate() {
+            let position = vec2(Self::MARGIN, input_y - (i + 1) as f32 * Self::LINE_HEIGHT);
+
+            draw_aligned_text(
+                line,
+                position,
+                HorizontalAlignment::Left,
+                VerticalAlignment::Top,
+                TextParams {
+                    color: color::LIGHTGRAY,
+                    ..params
+                },
+            );
+        }
+
+        pop_camera_state();
+    }
+}
@@ -0,0 +1,89 @@
+//! The cosmetic random number generator is used for randomness that has no effect on the
+//! simulation, like the spread of particles, the variations of sounds and the drift of weather.
+//!
+//! The global RNG of macroquad is seeded at the start of a match, and the simulation is
+//! deterministic for as long as it is the only thing that draws from it. Cosmetic code often runs
+//! at frame time, so the amount of numbers it draws depends on the frame rate, or on settings that
+//! do not affect the simulation, like the volume. If it drew from the global RNG, the simulation
+//! would diverge when an input recording is played back at a different frame rate, so it must
+//! use this generator in stead.
+//!
+//! The generator is a SplitMix64 over an atomic state, so it can also be used by the workers of
+//! `jobs`.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static STATE: AtomicU64 = AtomicU64::new(0x853c_49e6_748f_ea9b);
+
+const GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// Returns the next random 64 bit value
+pub fn rand() -> u64 {
+    let mut z = STATE
+        .fetch_add(GAMMA, Ordering::Relaxed)
+        .wrapping_add(GAMMA);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// This is implemented for the types that `gen_range` can generate
+pub trait RandomRange {
+    fn gen_range(low: Self, high: Self) -> Self;
+}
+
+impl RandomRange for f32 {
+    fn gen_range(low: Self, high: Self) -> Self {
+        // The upper 24 bits fill the mantissa, so that the result is uniform in [0, 1)
+        let t = (rand() >> 40) as f32 / (1u64 << 24) as f32;
+        low + (high - low) * t
+    }
+}
+
+impl RandomRange for usize {
+    fn gen_range(low: Self, high: Self) -> Self {
+        if high <= low {
+            return low;
+        }
+
+        low + (rand() % (high - low) as u64) as usize
+    }
+}
+
+impl RandomRange for i32 {
+    fn gen_range(low: Self, high: Self) -> Self {
+        if high <= low {
+            return low;
+        }
+
+        low + (rand() % (high as i64 - low as i64) as u64) as i32
+    }
+}
+
+/// Returns a random value in the range from `low` to `high`, where `high` is excluded. This
+/// works like `rand::gen_range`, but it does not draw from the global RNG.
+pub fn gen_range<T: RandomRange>(low: T, high: T) -> T {
+    T::gen_range(low, high)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gen_range_bounds() {
+        for _ in 0..1000 {
+            let value = gen_range(-2.0, 3.0);
+            assert!((-2.0..3.0).contains(&value));
+
+            let value = gen_range(3usize, 7);
+            assert!((3..7).contains(&value));
+
+            let value = gen_range(-5, 5);
+            assert!((-5..5).contains(&value));
+        }
+
+        assert_eq!(gen_range(4usize, 4), 4);
+        assert_eq!(gen_range(1.0, 1.0), 1.0);
+    }
+}
@@ -0,0 +1,339 @@
+//! Console variables, or cvars, are named, typed values that back tunable constants, like gravity
+//! or the knockback of weapon clashes, so that they can be changed from the developer console,
+//! without rebuilding the game. A cvar is registered, with its default value, by the subsystem
+//! that uses it, and it is read with the getter of its type, like `get_float`.
+//!
+//! Cvars are changed with the `set` console command, and `cvars` lists all of them. The values of
+//! cvars with the `ARCHIVE` flag are saved to the config, when they are changed, and they are
+//! restored when the cvar is registered.
+//!
+//! Any subsystems that register cvars must be added to the `init_cvars` function.
+
+use std::{fmt, ops::BitOr};
+
+use macroquad::experimental::collections::storage;
+
+use crate::{console, formaterr, Config, Result};
+
+/// The cvar that must be enabled for cvars with the `CHEAT` flag to be changed
+pub const CHEATS_CVAR: &str = "cheats";
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct CVarFlags(u8);
+
+impl CVarFlags {
+    pub const NONE: Self = Self(0);
+    /// The cvar can only be changed while the `cheats` cvar is enabled
+    pub const CHEAT: Self = Self(1);
+    /// The cvar affects the simulation, so all peers of a network match must agree on its value.
+    /// This is not enforced yet, as network play is not implemented.
+    pub const REPLICATED: Self = Self(1 << 1);
+    /// The value of the cvar is saved to the config, when it is changed
+    pub const ARCHIVE: Self = Self(1 << 2);
+
+    const NAMES: &'static [(&'static str, Self)] = &[
+        ("cheat", Self::CHEAT),
+        ("replicated", Self::REPLICATED),
+        ("archive", Self::ARCHIVE),
+    ];
+
+    /// Returns `true` if all the flags in `other` are also in `self`
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for CVarFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl fmt::Display for CVarFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = Self::NAMES
+            .iter()
+            .filter(|(_, flag)| self.contains(*flag))
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>();
+
+        write!(f, "{}", names.join(", "))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CVarValue {
+    Bool(bool),
+    Int(i32),
+    Float(f32),
+    String(String),
+}
+
+impl CVarValue {
+    pub fn get_type_name(&self) -> &'static str {
+        match self {
+            CVarValue::Bool(_) => "bool",
+            CVarValue::Int(_) => "int",
+            CVarValue::Float(_) => "float",
+            CVarValue::String(_) => "string",
+        }
+    }
+
+    /// Parses `value` as a value of the same type as `self`
+    fn parse_as_same_type(&self, value: &str) -> Option<CVarValue> {
+        match self {
+            CVarValue::Bool(_) => match value {
+                "1" | "true" | "on" => Some(CVarValue::Bool(true)),
+                "0" | "false" | "off" => Some(CVarValue::Bool(false)),
+                _ => None,
+            },
+            CVarValue::Int(_) => value.parse().ok().map(CVarValue::Int),
+            CVarValue::Float(_) => value.parse().ok().map(CVarValue::Float),
+            CVarValue::String(_) => Some(CVarValue::String(value.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for CVarValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CVarValue::Bool(value) => write!(f, "{}", value),
+            CVarValue::Int(value) => write!(f, "{}", value),
+            CVarValue::Float(value) => write!(f, "{}", value),
+            CVarValue::String(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CVar {
+    pub name: String,
+    pub description: String,
+    pub value: CVarValue,
+    pub default_value: CVarValue,
+    pub flags: CVarFlags,
+}
+
+static mut CVARS: Option<Vec<CVar>> = None;
+
+unsafe fn get_cvars() -> &'static mut Vec<CVar> {
+    CVARS.get_or_insert(Vec::new())
+}
+
+fn find_cvar(name: &str) -> Option<&'static mut CVar> {
+    unsafe { get_cvars() }
+        .iter_mut()
+        .find(|cvar| cvar.name == name)
+}
+
+/// This will register a cvar, replacing any existing cvar with the same name. If the cvar has
+/// the `ARCHIVE` flag and a value has been saved to the config, the saved value is restored.
+pub fn register(name: &str, default_value: CVarValue, flags: CVarFlags, description: &str) {
+    let mut value = default_value.clone();
+
+    if flags.contains(CVarFlags::ARCHIVE) {
+        if let Some(saved) = storage::get::<Config>().cvars.get(name) {
+            match default_value.parse_as_same_type(saved) {
+                Some(saved) => value = saved,
+                None => println!("WARNING: Config: Invalid value of cvar '{}'", name),
+            }
+        }
+    }
+
+    let cvar = CVar {
+        name: name.to_string(),
+        description: description.to_string(),
+        value,
+        default_value,
+        flags,
+    };
+
+    let cvars = unsafe { get_cvars() };
+
+    if let Some(existing) = cvars.iter_mut().find(|other| other.name == name) {
+        *existing = cvar;
+    } else {
+        cvars.push(cvar);
+        cvars.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+}
+
+/// This registers all the cvars of the game
+pub fn init_cvars() {
+    register(
+        CHEATS_CVAR,
+        CVarValue::Bool(false),
+        CVarFlags::NONE,
+        "Allows cvars that are flagged as cheats to be changed",
+    );
+
+    crate::components::PhysicsBody::register_cvars();
+    crate::Player::register_cvars();
+    crate::Weapon::register_cvars();
+    crate::Lighting::register_cvars();
+    crate::jobs::register_cvars();
+    crate::Resources::register_cvars();
+}
+
+/// Returns the value of the cvar `name`. This will panic if there is no such cvar, as that would
+/// mean that it is used before it is registered.
+pub fn get(name: &str) -> &'static CVarValue {
+    match find_cvar(name) {
+        Some(cvar) => &cvar.value,
+        None => panic!("CVars: The cvar '{}' is not registered", name),
+    }
+}
+
+pub fn get_bool(name: &str) -> bool {
+    match get(name) {
+        CVarValue::Bool(value) => *value,
+        value => panic!(
+            "CVars: The cvar '{}' is not a bool but {}",
+            name,
+            value.get_type_name()
+        ),
+    }
+}
+
+pub fn get_int(name: &str) -> i32 {
+    match get(name) {
+        CVarValue::Int(value) => *value,
+        value => panic!(
+            "CVars: The cvar '{}' is not an int but {}",
+            name,
+            value.get_type_name()
+        ),
+    }
+}
+
+pub fn get_float(name: &str) -> f32 {
+    match get(name) {
+        CVarValue::Float(value) => *value,
+        value => panic!(
+            "CVars: The cvar '{}' is not a float but {}",
+            name,
+            value.get_type_name()
+        ),
+    }
+}
+
+#[allow(dead_code)]
+pub fn get_string(name: &str) -> &'static str {
+    match get(name) {
+        CVarValue::String(value) => value,
+        value => panic!(
+            "CVars: The cvar '{}' is not a string but {}",
+            name,
+            value.get_type_name()
+        ),
+    }
+}
+
+/// This parses `value` as the type of the cvar `name` and sets it. Cvars flagged as cheats can
+/// only be set while the `cheats` cvar is enabled.
+pub fn set(name: &str, value: &str) -> Result<()> {
+    let (flags, current) = find_cvar(name)
+        .map(|cvar| (cvar.flags, cvar.value.clone()))
+        .ok_or_else(|| formaterr!("Unknown cvar '{}'", name))?;
+
+    if flags.contains(CVarFlags::CHEAT) && !get_bool(CHEATS_CVAR) {
+        return Err(formaterr!(
+            "The cvar '{}' is a cheat. Set '{}' to true to change it",
+            name,
+            CHEATS_CVAR
+        ));
+    }
+
+    let value = current.parse_as_same_type(value).ok_or_else(|| {
+        formaterr!(
+            "Invalid value '{}' for the cvar '{}', which is a {}",
+            value,
+            name,
+            current.get_type_name()
+        )
+    })?;
+
+    let cvar = find_cvar(name).unwrap();
+    cvar.value = value;
+
+    if cvar.flags.contains(CVarFlags::ARCHIVE) {
+        let mut config = storage::get_mut::<Config>();
+
+        if cvar.value == cvar.default_value {
+            config.cvars.remove(name);
+        } else {
+            config
+                .cvars
+                .insert(name.to_string(), cvar.value.to_string());
+        }
+
+        config.save(crate::get_config_path())?;
+    }
+
+    Ok(())
+}
+
+fn describe(cvar: &CVar) -> String {
+    let mut res = format!(
+        "{} = {} ({}",
+        &cvar.name,
+        &cvar.value,
+        cvar.value.get_type_name()
+    );
+
+    if cvar.flags != CVarFlags::NONE {
+        res = format!("{}, {}", res, cvar.flags);
+    }
+
+    format!(
+        "{}, default {}): {}",
+        res, &cvar.default_value, &cvar.description
+    )
+}
+
+pub fn register_console_commands() {
+    console::register("set", |args| {
+        if args.len() < 2 {
+            return Err(formaterr!("Usage: set <cvar> <value>"));
+        }
+
+        set(args[0], &args[1..].join(" "))?;
+
+        Ok(describe(find_cvar(args[0]).unwrap()))
+    });
+
+    console::register("get", |args| {
+        let name = args
+            .first()
+            .ok_or_else(|| formaterr!("Usage: get <cvar>"))?;
+
+        let cvar = find_cvar(name).ok_or_else(|| formaterr!("Unknown cvar '{}'", name))?;
+
+        Ok(describe(cvar))
+    });
+
+    console::register("reset", |args| {
+        let name = args
+            .first()
+            .ok_or_else(|| formaterr!("Usage: reset <cvar>"))?;
+
+        let default_value = find_cvar(name)
+            .map(|cvar| cvar.default_value.to_string())
+            .ok_or_else(|| formaterr!("Unknown cvar '{}'", name))?;
+
+        set(name, &default_value)?;
+
+        Ok(describe(find_cvar(name).unwrap()))
+    });
+
+    console::register("cvars", |_| {
+        let lines = unsafe { get_cvars() }
+            .iter()
+            .map(describe)
+            .collect::<Vec<_>>();
+
+        Ok(lines.join("\n"))
+    });
+}
@@ -1,4 +1,7 @@
+use crate::{console, formaterr};
+
 static mut IS_DEBUG_DRAW_ENABLED: bool = true;
+static mut IS_PHYSICS_DEBUG_DRAW_ENABLED: bool = false;
 
 pub fn is_debug_draw_enabled() -> bool {
     unsafe { IS_DEBUG_DRAW_ENABLED }
@@ -15,3 +18,30 @@ pub fn disable_debug_draw() {
 pub fn toggle_debug_draw() {
     unsafe { IS_DEBUG_DRAW_ENABLED = !IS_DEBUG_DRAW_ENABLED }
 }
+
+/// The physics debug overlay is drawn separately from the rest of the debug draw, as it is a lot
+/// more cluttered
+pub fn is_physics_debug_draw_enabled() -> bool {
+    unsafe { IS_PHYSICS_DEBUG_DRAW_ENABLED }
+}
+
+pub fn toggle_physics_debug_draw() {
+    unsafe { IS_PHYSICS_DEBUG_DRAW_ENABLED = !IS_PHYSICS_DEBUG_DRAW_ENABLED }
+}
+
+pub fn register_console_commands() {
+    console::register("debug_draw", |args| match args {
+        [] => {
+            toggle_debug_draw();
+            Ok(format!("Debug draw: {}", is_debug_draw_enabled()))
+        }
+        ["physics"] => {
+            toggle_physics_debug_draw();
+            Ok(format!(
+                "Physics debug draw: {}",
+                is_physics_debug_draw_enabled()
+            ))
+        }
+        _ => Err(formaterr!("Usage: debug_draw [physics]")),
+    });
+}
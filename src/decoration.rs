@@ -7,7 +7,7 @@ use macroquad::{
     prelude::*,
 };
 
-use crate::Resources;
+use crate::{GameCamera, Resources};
 
 pub struct Decoration {
     pos: Vec2,
@@ -52,6 +52,13 @@ impl scene::Node for Decoration {
     }
 
     fn draw(node: RefMut<Self>) {
+        let frame = node.sprite.frame();
+
+        let rect = Rect::new(node.pos.x, node.pos.y, frame.dest_size.x, frame.dest_size.y);
+        if !GameCamera::is_in_view(&rect) {
+            return;
+        }
+
         let resources = storage::get::<Resources>();
         let texture_entry = resources.textures.get("default_decorations").unwrap();
 
@@ -61,8 +68,8 @@ impl scene::Node for Decoration {
             node.pos.y,
             WHITE,
             DrawTextureParams {
-                source: Some(node.sprite.frame().source_rect),
-                dest_size: Some(node.sprite.frame().dest_size),
+                source: Some(frame.source_rect),
+                dest_size: Some(frame.dest_size),
                 ..Default::default()
             },
         );
@@ -1,10 +1,12 @@
 use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::{any::TypeId, result};
 
 use crate::editor::gui::windows::Window;
-use crate::map::{MapBackgroundLayer, MapObject, MapObjectKind};
+use crate::map::{MapBackgroundLayer, MapObject, MapObjectKind, MapProperty};
 use crate::{
     map::{Map, MapLayer, MapLayerKind, MapTile, MapTileset},
+    symbol::Symbol,
     Resources,
 };
 use macroquad::{experimental::collections::storage, prelude::*};
@@ -94,12 +96,14 @@ pub enum EditorAction {
         index: usize,
         layer_id: String,
     },
+    /// If `properties` is `None`, the object's existing properties will be kept
     UpdateObject {
         layer_id: String,
         index: usize,
         id: String,
         kind: MapObjectKind,
         position: Vec2,
+        properties: Option<HashMap<String, MapProperty>>,
     },
     CreateSpawnPoint(Vec2),
     DeleteSpawnPoint(usize),
@@ -591,6 +595,7 @@ impl UndoableAction for ImportAction {
                 tile_subdivisions: tileset.tile_subdivisions,
                 autotile_mask: tileset.autotile_mask.clone(),
                 tile_attributes: tileset.tile_attributes.clone(),
+                tile_animations: tileset.tile_animations.clone(),
                 properties: tileset.properties.clone(),
             };
 
@@ -875,6 +880,7 @@ pub struct UpdateObjectAction {
     id: String,
     kind: MapObjectKind,
     position: Vec2,
+    properties: Option<HashMap<String, MapProperty>>,
     object: Option<MapObject>,
 }
 
@@ -885,6 +891,7 @@ impl UpdateObjectAction {
         id: String,
         kind: MapObjectKind,
         position: Vec2,
+        properties: Option<HashMap<String, MapProperty>>,
     ) -> Self {
         UpdateObjectAction {
             layer_id,
@@ -892,6 +899,7 @@ impl UpdateObjectAction {
             id,
             kind,
             position,
+            properties,
             object: None,
         }
     }
@@ -906,6 +914,10 @@ impl UndoableAction for UpdateObjectAction {
                 object.id = self.id.clone();
                 object.kind = self.kind;
                 object.position = self.position;
+
+                if let Some(properties) = &self.properties {
+                    object.properties = properties.clone();
+                }
             } else {
                 return Err(&"UpdateObjectAction: The specified object index does not exist");
             }
@@ -1060,15 +1072,13 @@ impl PlaceTileAction {
 impl UndoableAction for PlaceTileAction {
     fn apply(&mut self, map: &mut Map) -> Result {
         if let Some(tileset) = map.tilesets.get(&self.tileset_id) {
-            let texture_id = tileset.texture_id.clone();
+            let texture_id = Symbol::new(&tileset.texture_id);
             let texture_coords = tileset.get_texture_coords(self.id);
 
             let i = map.to_index(self.coords);
 
             if let Some(layer) = map.layers.get_mut(&self.layer_id) {
                 if let MapLayerKind::TileLayer = layer.kind {
-                    self.replaced_tile = layer.tiles.remove(i as usize);
-
                     let tile = MapTile {
                         tile_id: self.id,
                         tileset_id: self.tileset_id.clone(),
@@ -1077,7 +1087,7 @@ impl UndoableAction for PlaceTileAction {
                         attributes: vec![],
                     };
 
-                    layer.tiles.insert(i as usize, Some(tile));
+                    self.replaced_tile = layer.tiles.replace(i as usize, Some(tile));
                 } else {
                     return Err(&"PlaceTileAction: The specified layer is not a tile layer");
                 }
@@ -1150,10 +1160,8 @@ impl UndoableAction for RemoveTileAction {
 
         if let Some(layer) = map.layers.get_mut(&self.layer_id) {
             if let MapLayerKind::TileLayer = layer.kind {
-                if let Some(tile) = layer.tiles.remove(i as usize) {
+                if let Some(tile) = layer.tiles.replace(i as usize, None) {
                     self.tile = Some(tile);
-
-                    layer.tiles.insert(i, None);
                 } else {
                     return Err(&"RemoveTileAction: No tile at the specified coords, in the specified layer. Undo was probably called on an action that was never applied");
                 }
@@ -5,7 +5,7 @@ use macroquad::{
 };
 
 use crate::map::MapBackgroundLayer;
-use crate::{map::Map, Resources};
+use crate::{map::Map, symbol::Symbol, Resources};
 
 use crate::gui::{GuiResources, ELEMENT_MARGIN, LIST_BOX_ENTRY_HEIGHT};
 use crate::resources::TextureKind;
@@ -161,12 +161,12 @@ impl Window for BackgroundPropertiesWindow {
                                     self.layer_depth = 0.0;
                                 } else {
                                     self.selected_layer = Some(i);
-                                    self.layer_texture_id = Some(layer.texture_id.clone());
+                                    self.layer_texture_id = Some(layer.texture_id.to_string());
                                     self.layer_depth = layer.depth;
                                 }
                             }
 
-                            ui.label(vec2(0.0, 0.0), &layer.texture_id);
+                            ui.label(vec2(0.0, 0.0), &layer.texture_id.to_string());
 
                             if is_selected {
                                 ui.pop_skin();
@@ -239,7 +239,7 @@ impl Window for BackgroundPropertiesWindow {
             if let Some(mut index) = self.selected_layer {
                 {
                     let layer = self.layers.get_mut(index).unwrap();
-                    layer.texture_id = self.layer_texture_id.clone().unwrap();
+                    layer.texture_id = Symbol::new(self.layer_texture_id.as_ref().unwrap());
                     layer.depth = self.layer_depth;
                 }
 
@@ -293,7 +293,7 @@ impl Window for BackgroundPropertiesWindow {
                     self.layer_depth = 0.0;
 
                     self.layers.push(MapBackgroundLayer {
-                        texture_id,
+                        texture_id: Symbol::new(&texture_id),
                         depth,
                         offset: Vec2::ZERO,
                     });
@@ -8,7 +8,8 @@ use crate::editor::gui::combobox::ComboBoxVec;
 use crate::{
     editor::gui::{ComboBoxBuilder, ComboBoxValue},
     map::{Map, MapObjectKind},
-    Resources,
+    physics::ForceVolumes,
+    InteractiveObjects, Lighting, Resources, Sproinger,
 };
 
 use super::{ButtonParams, EditorAction, EditorContext, Window, WindowParams};
@@ -133,7 +134,11 @@ impl Window for CreateObjectWindow {
                 .map(|item| item.id.as_str())
                 .collect::<Vec<&str>>(),
             MapObjectKind::Environment => {
-                vec!["sproinger"]
+                let mut ids = vec![Sproinger::OBJECT_ID];
+                ids.extend_from_slice(InteractiveObjects::OBJECT_IDS);
+                ids.push(ForceVolumes::OBJECT_ID);
+                ids.push(Lighting::LIGHT_OBJECT_ID);
+                ids
             }
             MapObjectKind::Decoration => {
                 vec!["pot", "seaweed"]
@@ -28,11 +28,11 @@ impl CreateTilesetWindow {
         let resources = storage::get::<Resources>();
         let mut textures = resources
             .textures
-            .iter()
-            .filter_map(|(k, v)| {
+            .values()
+            .filter_map(|v| {
                 if let Some(kind) = v.meta.kind {
                     if kind == TextureKind::Tileset {
-                        return Some(k.clone());
+                        return Some(v.meta.id.clone());
                     }
                 }
 
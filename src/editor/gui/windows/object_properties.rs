@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use macroquad::{
     experimental::collections::storage,
     prelude::*,
@@ -5,27 +7,223 @@ use macroquad::{
 };
 
 use crate::editor::gui::combobox::ComboBoxVec;
-use crate::map::MapObject;
+use crate::items::ItemSpawnerParams;
+use crate::map::{MapObject, MapProperty};
 use crate::{
     editor::gui::{ComboBoxBuilder, ComboBoxValue},
+    gui::Checkbox,
     map::{Map, MapObjectKind},
-    Resources,
+    physics::ForceVolumes,
+    InteractiveObjects, Lighting, Resources, Sproinger,
 };
 
 use super::{ButtonParams, EditorAction, EditorContext, Window, WindowParams};
 
+/// This holds the text input values of the item spawner configuration. Items are entered as a
+/// comma separated list of ids, each optionally followed by a colon and a weight, like
+/// `"sword:2, grenades"`.
+struct ItemSpawnerInput {
+    items: String,
+    loot_table: String,
+    initial_delay: String,
+    respawn_interval: String,
+    max_items: String,
+}
+
+impl ItemSpawnerInput {
+    fn from_params(params: &ItemSpawnerParams) -> Self {
+        let items = params
+            .items
+            .iter()
+            .map(|(id, weight)| format!("{}:{}", id, weight))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        ItemSpawnerInput {
+            items,
+            loot_table: params.loot_table.clone().unwrap_or_default(),
+            initial_delay: params.initial_delay.to_string(),
+            respawn_interval: params
+                .respawn_interval
+                .map(|interval| interval.to_string())
+                .unwrap_or_default(),
+            max_items: params.max_items.to_string(),
+        }
+    }
+
+    fn to_params(&self) -> ItemSpawnerParams {
+        let items = self
+            .items
+            .split(',')
+            .filter_map(|entry| {
+                let mut split = entry.split(':');
+
+                let id = split.next()?.trim();
+                let weight = split
+                    .next()
+                    .and_then(|weight| weight.trim().parse::<f32>().ok())
+                    .unwrap_or(1.0);
+
+                if id.is_empty() {
+                    None
+                } else {
+                    Some((id.to_string(), weight))
+                }
+            })
+            .collect();
+
+        let loot_table = Some(self.loot_table.trim().to_string()).filter(|id| !id.is_empty());
+
+        ItemSpawnerParams {
+            items,
+            loot_table,
+            initial_delay: self.initial_delay.trim().parse().unwrap_or(0.0),
+            respawn_interval: self.respawn_interval.trim().parse().ok(),
+            max_items: self.max_items.trim().parse().unwrap_or(1).max(1),
+        }
+    }
+}
+
+/// This holds the text input values of the links of an interactive environment object. Targets are
+/// entered as a comma separated list of link ids.
+struct InteractiveObjectInput {
+    link_id: String,
+    targets: String,
+}
+
+impl InteractiveObjectInput {
+    fn from_object(object: &MapObject) -> Self {
+        let get_property = |id: &str| {
+            object
+                .properties
+                .get(id)
+                .and_then(|param| param.get_value::<String>())
+                .cloned()
+                .unwrap_or_default()
+        };
+
+        InteractiveObjectInput {
+            link_id: get_property(InteractiveObjects::LINK_ID_PROPERTY),
+            targets: get_property(InteractiveObjects::TARGETS_PROPERTY),
+        }
+    }
+
+    fn to_properties(&self, properties: &mut HashMap<String, MapProperty>) {
+        let inputs = [
+            (InteractiveObjects::LINK_ID_PROPERTY, &self.link_id),
+            (InteractiveObjects::TARGETS_PROPERTY, &self.targets),
+        ];
+
+        for (id, value) in inputs.iter() {
+            let value = value.trim();
+
+            if value.is_empty() {
+                properties.remove(*id);
+            } else {
+                properties.insert(id.to_string(), MapProperty::String(value.to_string()));
+            }
+        }
+    }
+}
+
+/// This holds the text input values of the configuration of a force volume. Vectors are entered
+/// as two comma separated numbers, like `"0, -2400"`.
+struct ForceVolumeInput {
+    size: String,
+    force: String,
+    falloff: String,
+    is_water: bool,
+}
+
+impl ForceVolumeInput {
+    fn from_object(object: &MapObject) -> Self {
+        let get_vec2_property = |id: &str| {
+            object
+                .properties
+                .get(id)
+                .and_then(|param| param.get_value::<Vec2>())
+                .map(|value| format!("{}, {}", value.x, value.y))
+                .unwrap_or_default()
+        };
+
+        let falloff = object
+            .properties
+            .get(ForceVolumes::FALLOFF_PROPERTY)
+            .and_then(|param| param.get_value::<String>())
+            .cloned()
+            .unwrap_or_default();
+
+        let is_water = object
+            .properties
+            .get(ForceVolumes::WATER_PROPERTY)
+            .and_then(|param| param.get_value::<bool>())
+            .copied()
+            .unwrap_or(false);
+
+        ForceVolumeInput {
+            size: get_vec2_property(ForceVolumes::SIZE_PROPERTY),
+            force: get_vec2_property(ForceVolumes::FORCE_PROPERTY),
+            falloff,
+            is_water,
+        }
+    }
+
+    fn to_properties(&self, properties: &mut HashMap<String, MapProperty>) {
+        let inputs = [
+            (ForceVolumes::SIZE_PROPERTY, &self.size),
+            (ForceVolumes::FORCE_PROPERTY, &self.force),
+        ];
+
+        for (id, value) in inputs.iter() {
+            let mut split = value.split(',');
+
+            let x = split.next().and_then(|x| x.trim().parse::<f32>().ok());
+            let y = split.next().and_then(|y| y.trim().parse::<f32>().ok());
+
+            if let (Some(x), Some(y)) = (x, y) {
+                properties.insert(id.to_string(), MapProperty::Vec2(vec2(x, y)));
+            } else {
+                properties.remove(*id);
+            }
+        }
+
+        let falloff = self.falloff.trim();
+
+        if falloff.is_empty() {
+            properties.remove(ForceVolumes::FALLOFF_PROPERTY);
+        } else {
+            properties.insert(
+                ForceVolumes::FALLOFF_PROPERTY.to_string(),
+                MapProperty::String(falloff.to_string()),
+            );
+        }
+
+        if self.is_water {
+            properties.insert(
+                ForceVolumes::WATER_PROPERTY.to_string(),
+                MapProperty::Bool(true),
+            );
+        } else {
+            properties.remove(ForceVolumes::WATER_PROPERTY);
+        }
+    }
+}
+
 pub struct ObjectPropertiesWindow {
     params: WindowParams,
     layer_id: String,
     index: usize,
     object: Option<MapObject>,
+    spawner_input: Option<ItemSpawnerInput>,
+    interactive_object_input: Option<InteractiveObjectInput>,
+    force_volume_input: Option<ForceVolumeInput>,
 }
 
 impl ObjectPropertiesWindow {
     pub fn new(layer_id: String, index: usize) -> Self {
         let params = WindowParams {
             title: Some("Object Properties".to_string()),
-            size: vec2(300.0, 300.0),
+            size: vec2(300.0, 450.0),
             ..Default::default()
         };
 
@@ -34,6 +232,9 @@ impl ObjectPropertiesWindow {
             layer_id,
             index,
             object: None,
+            spawner_input: None,
+            interactive_object_input: None,
+            force_volume_input: None,
         }
     }
 }
@@ -49,12 +250,38 @@ impl Window for ObjectPropertiesWindow {
         let mut action = None;
 
         if let Some(object) = &self.object {
+            let mut properties = object.properties.clone();
+
+            if let Some(spawner_input) = &self.spawner_input {
+                if object.kind == MapObjectKind::Item {
+                    spawner_input
+                        .to_params()
+                        .to_properties(&object.id, &mut properties);
+                }
+            }
+
+            if let Some(interactive_object_input) = &self.interactive_object_input {
+                if object.kind == MapObjectKind::Environment
+                    && InteractiveObjects::is_interactive_object(&object.id)
+                {
+                    interactive_object_input.to_properties(&mut properties);
+                }
+            }
+
+            if let Some(force_volume_input) = &self.force_volume_input {
+                if object.kind == MapObjectKind::Environment && object.id == ForceVolumes::OBJECT_ID
+                {
+                    force_volume_input.to_properties(&mut properties);
+                }
+            }
+
             let batch = self.get_close_action().then(EditorAction::UpdateObject {
                 layer_id: self.layer_id.clone(),
                 index: self.index,
                 id: object.id.clone(),
                 kind: object.kind,
                 position: object.position,
+                properties: Some(properties),
             });
 
             action = Some(batch);
@@ -147,7 +374,11 @@ impl Window for ObjectPropertiesWindow {
                 .map(|item| item.id.as_str())
                 .collect::<Vec<&str>>(),
             MapObjectKind::Environment => {
-                vec!["sproinger"]
+                let mut ids = vec![Sproinger::OBJECT_ID];
+                ids.extend_from_slice(InteractiveObjects::OBJECT_IDS);
+                ids.push(ForceVolumes::OBJECT_ID);
+                ids.push(Lighting::LIGHT_OBJECT_ID);
+                ids
             }
             MapObjectKind::Decoration => {
                 vec!["pot", "seaweed"]
@@ -171,6 +402,107 @@ impl Window for ObjectPropertiesWindow {
 
         object.id = item_id_value.get_value();
 
+        if object.kind == MapObjectKind::Item {
+            let spawner_input = self.spawner_input.get_or_insert_with(|| {
+                ItemSpawnerInput::from_params(&ItemSpawnerParams::from_object(&object))
+            });
+
+            ui.separator();
+            ui.separator();
+
+            ui.label(None, "Spawner");
+
+            let size = vec2(275.0, 25.0);
+
+            widgets::InputText::new(hash!(id, "spawner_items_input"))
+                .size(size)
+                .ratio(0.6)
+                .label("Items (id:weight)")
+                .ui(ui, &mut spawner_input.items);
+
+            widgets::InputText::new(hash!(id, "spawner_loot_table_input"))
+                .size(size)
+                .ratio(0.6)
+                .label("Loot table")
+                .ui(ui, &mut spawner_input.loot_table);
+
+            widgets::InputText::new(hash!(id, "spawner_initial_delay_input"))
+                .size(size)
+                .ratio(0.6)
+                .label("Initial delay")
+                .ui(ui, &mut spawner_input.initial_delay);
+
+            widgets::InputText::new(hash!(id, "spawner_respawn_interval_input"))
+                .size(size)
+                .ratio(0.6)
+                .label("Respawn interval")
+                .ui(ui, &mut spawner_input.respawn_interval);
+
+            widgets::InputText::new(hash!(id, "spawner_max_items_input"))
+                .size(size)
+                .ratio(0.6)
+                .label("Max items")
+                .ui(ui, &mut spawner_input.max_items);
+        } else if object.kind == MapObjectKind::Environment
+            && InteractiveObjects::is_interactive_object(&object.id)
+        {
+            let interactive_object_input = self
+                .interactive_object_input
+                .get_or_insert_with(|| InteractiveObjectInput::from_object(&object));
+
+            ui.separator();
+            ui.separator();
+
+            ui.label(None, "Links");
+
+            let size = vec2(275.0, 25.0);
+
+            widgets::InputText::new(hash!(id, "interactive_link_id_input"))
+                .size(size)
+                .ratio(0.6)
+                .label("Link id")
+                .ui(ui, &mut interactive_object_input.link_id);
+
+            widgets::InputText::new(hash!(id, "interactive_targets_input"))
+                .size(size)
+                .ratio(0.6)
+                .label("Targets")
+                .ui(ui, &mut interactive_object_input.targets);
+        } else if object.kind == MapObjectKind::Environment && object.id == ForceVolumes::OBJECT_ID
+        {
+            let force_volume_input = self
+                .force_volume_input
+                .get_or_insert_with(|| ForceVolumeInput::from_object(&object));
+
+            ui.separator();
+            ui.separator();
+
+            ui.label(None, "Force volume");
+
+            let size = vec2(275.0, 25.0);
+
+            widgets::InputText::new(hash!(id, "force_volume_size_input"))
+                .size(size)
+                .ratio(0.6)
+                .label("Size")
+                .ui(ui, &mut force_volume_input.size);
+
+            widgets::InputText::new(hash!(id, "force_volume_force_input"))
+                .size(size)
+                .ratio(0.6)
+                .label("Force")
+                .ui(ui, &mut force_volume_input.force);
+
+            widgets::InputText::new(hash!(id, "force_volume_falloff_input"))
+                .size(size)
+                .ratio(0.6)
+                .label("Falloff")
+                .ui(ui, &mut force_volume_input.falloff);
+
+            Checkbox::new(hash!(id, "force_volume_water_input"), None, "Water")
+                .ui(ui, &mut force_volume_input.is_water);
+        }
+
         self.object = Some(object);
 
         None
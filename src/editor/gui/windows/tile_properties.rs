@@ -3,14 +3,23 @@ use macroquad::{
     ui::{hash, Ui},
 };
 
-use crate::map::Map;
+use crate::map::{Map, TileSlope};
 
+use crate::editor::gui::{combobox::ComboBoxVec, ComboBoxBuilder, ComboBoxValue};
 use crate::gui::Checkbox;
 
 use super::{ButtonParams, EditorAction, EditorContext, Window, WindowParams};
 
 const JUMPTHROUGH_ATTRIBUTE: &str = "jumpthrough";
 
+const NO_SLOPE_OPTION: &str = "none";
+
+const NO_CONVEYOR_OPTION: &str = "none";
+
+const NO_MATERIAL_OPTION: &str = "none";
+
+const NO_SOUND_OPTION: &str = "default";
+
 pub struct TilePropertiesWindow {
     params: WindowParams,
     layer_id: String,
@@ -22,7 +31,7 @@ impl TilePropertiesWindow {
     pub fn new(layer_id: String, index: usize) -> Self {
         let params = WindowParams {
             title: Some("Tile Properties".to_string()),
-            size: vec2(300.0, 200.0),
+            size: vec2(300.0, 300.0),
             ..Default::default()
         };
 
@@ -103,6 +112,102 @@ impl Window for TilePropertiesWindow {
             } else if !is_jumpthrough && was_jumpthrough {
                 attributes.retain(|s| s != JUMPTHROUGH_ATTRIBUTE);
             }
+
+            let mut options = vec![NO_SLOPE_OPTION];
+            options.extend_from_slice(TileSlope::ATTRIBUTES);
+
+            let index = options
+                .iter()
+                .position(|option| attributes.iter().any(|s| s == option))
+                .unwrap_or_default();
+
+            let mut slope_value = ComboBoxVec::new(index, &options);
+
+            ComboBoxBuilder::new(hash!(id, "slope_input"))
+                .with_ratio(0.6)
+                .with_label("Slope")
+                .build(ui, &mut slope_value);
+
+            let slope = slope_value.get_value();
+            if slope_value.get_index() != index {
+                attributes.retain(|s| !TileSlope::ATTRIBUTES.contains(&s.as_str()));
+
+                if slope != NO_SLOPE_OPTION {
+                    attributes.push(slope);
+                }
+            }
+
+            let mut options = vec![NO_CONVEYOR_OPTION];
+            options.extend_from_slice(Map::CONVEYOR_TILE_ATTRIBUTES);
+
+            let index = options
+                .iter()
+                .position(|option| attributes.iter().any(|s| s == option))
+                .unwrap_or_default();
+
+            let mut conveyor_value = ComboBoxVec::new(index, &options);
+
+            ComboBoxBuilder::new(hash!(id, "conveyor_input"))
+                .with_ratio(0.6)
+                .with_label("Conveyor")
+                .build(ui, &mut conveyor_value);
+
+            let conveyor = conveyor_value.get_value();
+            if conveyor_value.get_index() != index {
+                attributes.retain(|s| !Map::CONVEYOR_TILE_ATTRIBUTES.contains(&s.as_str()));
+
+                if conveyor != NO_CONVEYOR_OPTION {
+                    attributes.push(conveyor);
+                }
+            }
+
+            let mut options = vec![NO_MATERIAL_OPTION];
+            options.extend_from_slice(Map::MATERIAL_TILE_ATTRIBUTES);
+
+            let index = options
+                .iter()
+                .position(|option| attributes.iter().any(|s| s == option))
+                .unwrap_or_default();
+
+            let mut material_value = ComboBoxVec::new(index, &options);
+
+            ComboBoxBuilder::new(hash!(id, "material_input"))
+                .with_ratio(0.6)
+                .with_label("Material")
+                .build(ui, &mut material_value);
+
+            let material = material_value.get_value();
+            if material_value.get_index() != index {
+                attributes.retain(|s| !Map::MATERIAL_TILE_ATTRIBUTES.contains(&s.as_str()));
+
+                if material != NO_MATERIAL_OPTION {
+                    attributes.push(material);
+                }
+            }
+
+            let mut options = vec![NO_SOUND_OPTION];
+            options.extend_from_slice(Map::SOUND_TILE_ATTRIBUTES);
+
+            let index = options
+                .iter()
+                .position(|option| attributes.iter().any(|s| s == option))
+                .unwrap_or_default();
+
+            let mut sound_value = ComboBoxVec::new(index, &options);
+
+            ComboBoxBuilder::new(hash!(id, "sound_input"))
+                .with_ratio(0.6)
+                .with_label("Sound")
+                .build(ui, &mut sound_value);
+
+            let sound = sound_value.get_value();
+            if sound_value.get_index() != index {
+                attributes.retain(|s| !Map::SOUND_TILE_ATTRIBUTES.contains(&s.as_str()));
+
+                if sound != NO_SOUND_OPTION {
+                    attributes.push(sound);
+                }
+            }
         }
 
         None
@@ -31,11 +31,11 @@ impl TilesetPropertiesWindow {
         let resources = storage::get::<Resources>();
         let texture_ids = resources
             .textures
-            .iter()
-            .filter_map(|(k, v)| {
+            .values()
+            .filter_map(|v| {
                 if let Some(kind) = v.meta.kind {
                     if kind == TextureKind::Tileset {
-                        return Some(k.as_str());
+                        return Some(v.meta.id.as_str());
                     }
                 }
 
@@ -49,7 +49,8 @@ use crate::editor::gui::windows::{
 };
 use crate::editor::input::{collect_editor_input, EditorInput};
 use crate::editor::tools::SpawnPointPlacementTool;
-use crate::gui::SELECTION_HIGHLIGHT_COLOR;
+use crate::gui::{show_toast, ToastPriority, SELECTION_HIGHLIGHT_COLOR};
+use crate::localization::tr_args;
 use crate::map::{MapObject, MapObjectKind};
 use macroquad::{
     color,
@@ -181,6 +182,8 @@ impl Editor {
 
         let selected_tool = None;
 
+        storage::get_mut::<Resources>().prepare_editor_textures();
+
         let selected_layer = map_resource.map.draw_order.first().cloned();
 
         let cursor_position = match input_scheme {
@@ -522,8 +525,10 @@ impl Editor {
                 id,
                 kind,
                 position,
+                properties,
             } => {
-                let action = UpdateObjectAction::new(layer_id, index, id, kind, position);
+                let action =
+                    UpdateObjectAction::new(layer_id, index, id, kind, position, properties);
                 res = self
                     .history
                     .apply(Box::new(action), &mut self.map_resource.map);
@@ -599,9 +604,11 @@ impl Editor {
                 gui.add_window(CreateMapWindow::new());
             }
             EditorAction::OpenMap(index) => {
-                let resources = storage::get::<Resources>();
+                let mut resources = storage::get_mut::<Resources>();
                 let map_resource = resources.maps.get(index).cloned().unwrap();
 
+                resources.prepare_editor_textures();
+
                 self.map_resource = map_resource;
                 self.history.clear();
                 self.clear_context();
@@ -627,7 +634,17 @@ impl Editor {
 
                 let mut resources = storage::get_mut::<Resources>();
                 if resources.save_map(&map_resource).is_ok() {
+                    show_toast(
+                        &tr_args("toast.map_saved", &[&map_resource.meta.name]),
+                        ToastPriority::Normal,
+                    );
+
                     self.map_resource = map_resource;
+                } else {
+                    show_toast(
+                        &tr_args("toast.map_save_failed", &[&map_resource.meta.name]),
+                        ToastPriority::High,
+                    );
                 }
             }
             EditorAction::OpenSaveMapWindow => {
@@ -1062,6 +1079,7 @@ impl Node for Editor {
                         index,
                         layer_id,
                         position,
+                        properties: None,
                     };
 
                     node.apply_action(action);
@@ -0,0 +1,286 @@
+use macroquad::{
+    experimental::scene::{Handle, HandleUntyped, Node, RefMut},
+    prelude::*,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    capabilities::NetworkReplicate,
+    cosmetic_rand,
+    effects::PassiveEffectParams,
+    game::SIMULATION_DELTA_TIME,
+    json::{self, GenericParam},
+    map::Map,
+    math::IsZero,
+    player::DamageParams,
+    ParticleEmitters, Player,
+};
+
+/// This holds the parameters of a lingering area effect, like a poison cloud, a fire patch or a
+/// slime trail. Area effects are volumes that live for a set amount of time, damaging and/or
+/// applying a status effect to all players inside them, on a regular interval.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AreaEffectParams {
+    /// The size of the volume. The volume will be centered on the origin of the effect.
+    #[serde(with = "json::vec2_def")]
+    pub size: Vec2,
+    /// The time, in seconds, before the area effect expires
+    pub lifetime: f32,
+    /// The time between each tick of the area effect, where damage and status is applied
+    #[serde(default = "AreaEffectParams::default_tick_interval")]
+    pub tick_interval: f32,
+    /// The damage dealt to each player inside the volume, on every tick
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    pub tick_damage: f32,
+    /// A passive effect that will be applied to each player inside the volume, on every tick.
+    /// As passive effects are stored by id, this will refresh the duration of the status, if
+    /// the player already has it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<PassiveEffectParams>,
+    /// If this is `true` the player that instantiated the effect will also be affected by it
+    #[serde(default, skip_serializing_if = "json::is_false")]
+    pub is_owner_affected: bool,
+    /// The velocity of the volume, in addition to any wind
+    #[serde(default, with = "json::vec2_def")]
+    pub velocity: Vec2,
+    /// The factor of the map's wind that is applied to the volume. A fire patch would typically
+    /// have this set to zero, while a gas cloud would drift with the wind.
+    #[serde(default = "AreaEffectParams::default_wind_factor")]
+    pub wind_factor: f32,
+    /// The particle effect that will be emitted at random positions inside the volume
+    #[serde(
+        default,
+        rename = "particle_effect",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub particle_effect_id: Option<String>,
+    /// The amount of particle effects emitted per second
+    #[serde(default = "AreaEffectParams::default_particle_rate")]
+    pub particle_rate: f32,
+    /// This can specify a color that the colors of the particle effect will be multiplied by
+    #[serde(
+        default,
+        with = "json::color_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub particle_color: Option<Color>,
+    /// If this is specified the volume will be drawn as a filled rectangle of this color, that
+    /// fades out as the effect expires
+    #[serde(
+        default,
+        with = "json::color_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub color: Option<Color>,
+}
+
+impl AreaEffectParams {
+    pub fn default_tick_interval() -> f32 {
+        0.5
+    }
+
+    pub fn default_wind_factor() -> f32 {
+        1.0
+    }
+
+    pub fn default_particle_rate() -> f32 {
+        8.0
+    }
+}
+
+struct AreaEffect {
+    owner: Handle<Player>,
+    position: Vec2,
+    params: AreaEffectParams,
+    lifetime_timer: f32,
+    tick_timer: f32,
+    particle_timer: f32,
+    /// The id of the weapon that kills by the area effect are credited to
+    weapon_id: Option<String>,
+}
+
+impl AreaEffect {
+    fn get_rect(&self) -> Rect {
+        Rect::new(
+            self.position.x,
+            self.position.y,
+            self.params.size.x,
+            self.params.size.y,
+        )
+    }
+}
+
+#[derive(Default)]
+pub struct AreaEffects {
+    active: Vec<AreaEffect>,
+    /// The wind of the current map, that area effects will drift with, according to their
+    /// `wind_factor`
+    pub wind: Vec2,
+}
+
+impl AreaEffects {
+    pub const WIND_MAP_PROPERTY: &'static str = "wind";
+
+    /// The fraction of the lifetime, at the end, where the volume fades out when drawn
+    const FADE_OUT_FACTOR: f32 = 0.25;
+
+    pub fn new(wind: Vec2) -> Self {
+        AreaEffects {
+            active: Vec::new(),
+            wind,
+        }
+    }
+
+    /// Returns the wind set in the properties of `map`, if any
+    pub fn get_wind(map: &Map) -> Vec2 {
+        if let Some(GenericParam::Vec2(wind)) = map.properties.get(Self::WIND_MAP_PROPERTY) {
+            *wind
+        } else {
+            Vec2::ZERO
+        }
+    }
+
+    pub fn spawn(
+        &mut self,
+        owner: Handle<Player>,
+        origin: Vec2,
+        is_facing_right: bool,
+        mut params: AreaEffectParams,
+        weapon_id: Option<String>,
+    ) {
+        if !is_facing_right {
+            params.velocity.x = -params.velocity.x;
+        }
+
+        self.active.push(AreaEffect {
+            owner,
+            position: origin - params.size / 2.0,
+            params,
+            lifetime_timer: 0.0,
+            tick_timer: 0.0,
+            particle_timer: 0.0,
+            weapon_id,
+        });
+    }
+
+    fn network_update(mut node: RefMut<Self>) {
+        let dt = SIMULATION_DELTA_TIME;
+        let wind = node.wind;
+
+        node.active
+            .retain(|effect| effect.lifetime_timer < effect.params.lifetime);
+
+        for effect in &mut node.active {
+            effect.lifetime_timer += dt;
+            effect.position += (effect.params.velocity + wind * effect.params.wind_factor) * dt;
+
+            effect.tick_timer += dt;
+            if effect.tick_timer < effect.params.tick_interval {
+                continue;
+            }
+
+            effect.tick_timer = 0.0;
+
+            let rect = effect.get_rect();
+
+            // Borrow owner so that it is excluded from the following iteration
+            let _owner = if effect.params.is_owner_affected {
+                None
+            } else {
+                scene::try_get_node(effect.owner)
+            };
+
+            for mut player in scene::find_nodes_by_type::<Player>() {
+                if player.is_dead || !rect.overlaps(&player.get_collider_rect()) {
+                    continue;
+                }
+
+                if let Some(params) = &effect.params.status {
+                    player.add_passive_effect(None, params.clone());
+                }
+
+                if effect.params.tick_damage > 0.0 {
+                    let is_from_right = rect.x + rect.w / 2.0 > player.body.position.x;
+                    Player::on_receive_damage_ex(
+                        player.handle(),
+                        is_from_right,
+                        Some(effect.owner),
+                        effect.params.tick_damage,
+                        DamageParams {
+                            weapon_id: effect.weapon_id.clone(),
+                            ..Default::default()
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    fn network_capabilities() -> NetworkReplicate {
+        fn network_update(handle: HandleUntyped) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<AreaEffects>();
+            AreaEffects::network_update(node);
+        }
+
+        NetworkReplicate { network_update }
+    }
+}
+
+impl Node for AreaEffects {
+    fn ready(mut node: RefMut<Self>) {
+        node.provides(Self::network_capabilities());
+    }
+
+    fn update(mut node: RefMut<Self>) {
+        let dt = get_frame_time();
+
+        let mut particles = scene::find_node_by_type::<ParticleEmitters>().unwrap();
+
+        for effect in &mut node.active {
+            if let Some(particle_effect_id) = &effect.params.particle_effect_id {
+                effect.particle_timer += dt;
+
+                let interval = 1.0 / effect.params.particle_rate.max(0.01);
+                while effect.particle_timer >= interval {
+                    effect.particle_timer -= interval;
+
+                    let position = effect.position
+                        + vec2(
+                            cosmetic_rand::gen_range(0.0, effect.params.size.x),
+                            cosmetic_rand::gen_range(0.0, effect.params.size.y),
+                        );
+
+                    if let Some(color) = effect.params.particle_color {
+                        particles.spawn_tinted(particle_effect_id, position, color);
+                    } else {
+                        particles.spawn(particle_effect_id, position);
+                    }
+                }
+            }
+        }
+    }
+
+    fn draw(node: RefMut<Self>) {
+        for effect in &node.active {
+            if let Some(mut color) = effect.params.color {
+                let fade_out_time = effect.params.lifetime * Self::FADE_OUT_FACTOR;
+                let time_left = effect.params.lifetime - effect.lifetime_timer;
+                if fade_out_time > 0.0 && time_left < fade_out_time {
+                    color.a *= (time_left / fade_out_time).max(0.0);
+                }
+
+                let rect = effect.get_rect();
+                draw_rectangle(rect.x, rect.y, rect.w, rect.h, color);
+            }
+
+            #[cfg(debug_assertions)]
+            {
+                let rect = effect.get_rect();
+                draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 2.0, GREEN);
+            }
+        }
+    }
+}
@@ -0,0 +1,489 @@
+//! Deployables are world objects, placed or thrown by a player, through a `Deployable` effect.
+//! They fall to the ground, and, if their placement is valid, they will deploy and perform their
+//! behavior until they run out of health or their lifetime expires. The player that placed a
+//! deployable owns it, so it will not be targeted or blocked by it.
+
+use macroquad::{
+    experimental::{
+        collections::storage,
+        scene::{Handle, HandleUntyped, Node, RefMut},
+    },
+    prelude::*,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    instantiate_active_effect, instantiate_directed_active_effect, ActiveEffectParams,
+    ExplosionFalloff,
+};
+
+use crate::{
+    audio::play_sound_at,
+    capabilities::NetworkReplicate,
+    components::{AnimationParams, AnimationPlayer, PhysicsBody},
+    game::SIMULATION_DELTA_TIME,
+    json,
+    physics::CollisionLayers,
+    GameWorld, ParticleEmitters, Player, Resources,
+};
+
+/// The behaviors of deployables
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "behavior", rename_all = "snake_case")]
+pub enum DeployableBehavior {
+    /// Instantiate `effects` at an interval, facing the closest enemy within `range`
+    Turret {
+        range: f32,
+        interval: f32,
+        #[serde(alias = "effect")]
+        effects: Vec<ActiveEffectParams>,
+        /// The offset from the center of the deployable to the origin of the effects, when facing
+        /// right
+        #[serde(default, with = "json::vec2_def")]
+        effect_offset: Vec2,
+    },
+    /// Block projectiles fired by enemies, taking damage from them
+    Shield,
+    /// Launch players that land on top of the deployable upwards
+    Trampoline { force: f32 },
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DeployableParams {
+    #[serde(flatten)]
+    pub behavior: DeployableBehavior,
+    #[serde(with = "json::vec2_def")]
+    pub size: Vec2,
+    /// The velocity of the deployable when it is instantiated. If this is zero, the deployable
+    /// will be placed at the origin of the effect and fall to the ground from there.
+    #[serde(default, with = "json::vec2_def")]
+    pub velocity: Vec2,
+    /// If this is specified, the deployable can be damaged by enemy projectiles and explosions and
+    /// it will be destroyed when it runs out of health.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health: Option<f32>,
+    /// If this is specified, the deployable will be destroyed after `lifetime` seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lifetime: Option<f32>,
+    /// If this is `true`, the deployable can only be deployed on top of solid ground
+    #[serde(default = "DeployableParams::default_requires_ground")]
+    pub requires_ground: bool,
+    /// This can be used to add an animated sprite to the deployable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub animation: Option<AnimationParams>,
+    /// This can specify the id of a sound effect that is played when the deployable is deployed
+    #[serde(
+        default,
+        rename = "deploy_sound_effect",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub deploy_sound_effect_id: Option<String>,
+    /// Effects that will be instantiated at the center of the deployable when it is destroyed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on_destroyed_effects: Vec<ActiveEffectParams>,
+}
+
+impl DeployableParams {
+    pub fn default_requires_ground() -> bool {
+        true
+    }
+}
+
+/// Returns `true` if a deployable with the collider `rect` can be deployed. This is the case if
+/// no solid tiles overlap the collider and, if `requires_ground` is `true`, there is solid
+/// ground below the collider.
+pub fn is_valid_placement(rect: &Rect, requires_ground: bool) -> bool {
+    let world = storage::get::<GameWorld>();
+
+    let step = world.map.tile_size / 2.0;
+
+    let cols = (rect.w / step.x).ceil() as u32;
+    let rows = (rect.h / step.y).ceil() as u32;
+
+    for y in 0..=rows {
+        for x in 0..=cols {
+            let point = vec2(
+                (rect.x + x as f32 * step.x).min(rect.x + rect.w - 1.0),
+                (rect.y + y as f32 * step.y).min(rect.y + rect.h - 1.0),
+            );
+
+            if world.is_solid_at(point) {
+                return false;
+            }
+        }
+    }
+
+    if requires_ground {
+        let bottom = rect.y + rect.h + 1.0;
+
+        return [rect.x, rect.x + rect.w / 2.0, rect.x + rect.w - 1.0]
+            .iter()
+            .all(|x| world.is_solid_at(vec2(*x, bottom)));
+    }
+
+    true
+}
+
+struct Deployable {
+    owner: Handle<Player>,
+    /// The id of the player that owns the deployable, if the player still exists
+    owner_id: Option<u8>,
+    behavior: DeployableBehavior,
+    body: PhysicsBody,
+    animation_player: Option<AnimationPlayer>,
+    deploy_sound_effect_id: Option<String>,
+    on_destroyed_effects: Vec<ActiveEffectParams>,
+    requires_ground: bool,
+    is_deployed: bool,
+    is_facing_right: bool,
+    health: Option<f32>,
+    max_health: Option<f32>,
+    lifetime: Option<f32>,
+    lifetime_timer: f32,
+    interval_timer: f32,
+}
+
+impl Deployable {
+    fn get_center(&self) -> Vec2 {
+        self.body.position + self.body.size / 2.0
+    }
+
+    fn is_owned_by(&self, player_id: Option<u8>) -> bool {
+        player_id.is_some() && self.owner_id == player_id
+    }
+
+    fn apply_damage(&mut self, damage: f32) {
+        if let Some(health) = &mut self.health {
+            *health -= damage;
+        }
+    }
+
+    fn is_destroyed(&self) -> bool {
+        let is_out_of_health = self.health.map(|health| health <= 0.0).unwrap_or(false);
+        let is_expired = self
+            .lifetime
+            .map(|lifetime| self.lifetime_timer >= lifetime)
+            .unwrap_or(false);
+
+        is_out_of_health || is_expired
+    }
+}
+
+#[derive(Default)]
+pub struct Deployables {
+    active: Vec<Deployable>,
+}
+
+impl Deployables {
+    const HEALTH_BAR_HEIGHT: f32 = 3.0;
+    const HEALTH_BAR_Y_OFFSET: f32 = -6.0;
+
+    /// The size of the area on top of a trampoline that will launch players
+    const TRAMPOLINE_TRIGGER_HEIGHT: f32 = 8.0;
+
+    const INVALID_PLACEMENT_PARTICLE_EFFECT_ID: &'static str = "hit";
+
+    pub fn new() -> Self {
+        Deployables { active: Vec::new() }
+    }
+
+    pub fn spawn(
+        &mut self,
+        owner: Handle<Player>,
+        position: Vec2,
+        is_facing_right: bool,
+        params: DeployableParams,
+    ) {
+        let mut body = {
+            let mut world = storage::get_mut::<GameWorld>();
+            PhysicsBody::new(
+                &mut world.collision_world,
+                position - params.size / 2.0,
+                0.0,
+                params.size,
+                false,
+                true,
+                None,
+            )
+        };
+
+        body.collision_layer = CollisionLayers::HAZARDS;
+
+        body.velocity = params.velocity;
+        if !is_facing_right {
+            body.velocity.x = -body.velocity.x;
+        }
+
+        let animation_player = params.animation.map(AnimationPlayer::new);
+
+        let owner_id = scene::try_get_node(owner).map(|player| player.id);
+
+        self.active.push(Deployable {
+            owner,
+            owner_id,
+            behavior: params.behavior,
+            body,
+            animation_player,
+            deploy_sound_effect_id: params.deploy_sound_effect_id,
+            on_destroyed_effects: params.on_destroyed_effects,
+            requires_ground: params.requires_ground,
+            is_deployed: false,
+            is_facing_right,
+            health: params.health,
+            max_health: params.health,
+            lifetime: params.lifetime,
+            lifetime_timer: 0.0,
+            interval_timer: 0.0,
+        });
+    }
+
+    /// This checks for a projectile hit at `point`. Deployables that can be damaged will take
+    /// damage from enemy projectiles and shields will block them. Returns `true` if the
+    /// projectile was stopped by a deployable.
+    pub fn check_projectile_hit(&mut self, point: Vec2, owner_id: Option<u8>, damage: f32) -> bool {
+        for deployable in &mut self.active {
+            if deployable.is_deployed
+                && !deployable.is_owned_by(owner_id)
+                && deployable.body.get_collider_rect().contains(point)
+            {
+                let is_shield = matches!(deployable.behavior, DeployableBehavior::Shield);
+
+                if is_shield || deployable.health.is_some() {
+                    deployable.apply_damage(damage);
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    /// This applies the damage of an explosion to all deployables within its radius, regardless
+    /// of ownership
+    pub fn apply_explosion_damage(
+        &mut self,
+        circle: &Circle,
+        damage: f32,
+        falloff: ExplosionFalloff,
+    ) {
+        for deployable in &mut self.active {
+            if deployable.is_deployed && circle.overlaps_rect(&deployable.body.get_collider_rect())
+            {
+                let distance = if circle.r > 0.0 {
+                    circle.point().distance(deployable.get_center()) / circle.r
+                } else {
+                    0.0
+                };
+
+                deployable.apply_damage(damage * falloff.get_factor(distance));
+            }
+        }
+    }
+
+    fn update_turret(deployable: &mut Deployable, dt: f32) {
+        if let DeployableBehavior::Turret {
+            range,
+            interval,
+            effects,
+            effect_offset,
+        } = &deployable.behavior
+        {
+            deployable.interval_timer += dt;
+
+            let center = deployable.get_center();
+
+            let target = scene::find_nodes_by_type::<Player>()
+                .filter(|player| !player.is_dead && !deployable.is_owned_by(Some(player.id)))
+                .map(|player| player.body.position + player.body.size / 2.0)
+                .filter(|position| position.distance(center) <= *range)
+                .min_by(|a, b| {
+                    a.distance(center)
+                        .partial_cmp(&b.distance(center))
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                });
+
+            if let Some(target) = target {
+                deployable.is_facing_right = target.x >= center.x;
+
+                if deployable.interval_timer >= *interval {
+                    deployable.interval_timer = 0.0;
+
+                    let mut offset = *effect_offset;
+                    if !deployable.is_facing_right {
+                        offset.x = -offset.x;
+                    }
+
+                    for params in effects.clone() {
+                        instantiate_directed_active_effect(
+                            deployable.owner,
+                            center + offset,
+                            deployable.is_facing_right,
+                            params,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn update_trampoline(deployable: &Deployable) {
+        if let DeployableBehavior::Trampoline { force } = &deployable.behavior {
+            let collider = deployable.body.get_collider_rect();
+            let trigger = Rect::new(
+                collider.x,
+                collider.y - Self::TRAMPOLINE_TRIGGER_HEIGHT / 2.0,
+                collider.w,
+                Self::TRAMPOLINE_TRIGGER_HEIGHT,
+            );
+
+            for mut player in scene::find_nodes_by_type::<Player>() {
+                if player.body.velocity.y > 0.0
+                    && deployable.body.can_collide_with(&player.body)
+                    && trigger.overlaps(&player.get_collider_rect())
+                {
+                    player.body.velocity.y = -force;
+
+                    let resources = storage::get::<Resources>();
+                    play_sound_at(&resources.sounds["jump"], player.body.position);
+                }
+            }
+        }
+    }
+
+    fn network_update(mut node: RefMut<Self>) {
+        let dt = SIMULATION_DELTA_TIME;
+
+        let mut i = 0;
+        while i < node.active.len() {
+            let mut is_removed = false;
+
+            {
+                let deployable = &mut node.active[i];
+
+                if !deployable.is_deployed {
+                    deployable.body.update();
+
+                    if deployable.body.is_on_ground
+                        || (!deployable.requires_ground && deployable.body.velocity == Vec2::ZERO)
+                    {
+                        let collider = deployable.body.get_collider_rect();
+
+                        if is_valid_placement(&collider, deployable.requires_ground) {
+                            deployable.is_deployed = true;
+                            deployable.body.velocity = Vec2::ZERO;
+                            deployable.body.has_gravity = false;
+
+                            if let Some(sound_effect_id) = &deployable.deploy_sound_effect_id {
+                                let resources = storage::get::<Resources>();
+                                play_sound_at(
+                                    &resources.sounds[sound_effect_id],
+                                    deployable.body.position,
+                                );
+                            }
+                        } else {
+                            let mut particles =
+                                scene::find_node_by_type::<ParticleEmitters>().unwrap();
+                            particles.spawn(
+                                Self::INVALID_PLACEMENT_PARTICLE_EFFECT_ID,
+                                deployable.get_center(),
+                            );
+
+                            deployable.on_destroyed_effects.clear();
+                            is_removed = true;
+                        }
+                    }
+                } else {
+                    deployable.lifetime_timer += dt;
+
+                    match deployable.behavior {
+                        DeployableBehavior::Turret { .. } => Self::update_turret(deployable, dt),
+                        DeployableBehavior::Trampoline { .. } => {
+                            Self::update_trampoline(deployable)
+                        }
+                        DeployableBehavior::Shield => {}
+                    }
+
+                    is_removed = deployable.is_destroyed();
+                }
+            }
+
+            if is_removed {
+                let deployable = node.active.remove(i);
+
+                let center = deployable.get_center();
+
+                for params in deployable.on_destroyed_effects {
+                    instantiate_active_effect(deployable.owner, center, params);
+                }
+
+                continue;
+            }
+
+            i += 1;
+        }
+    }
+
+    fn network_capabilities() -> NetworkReplicate {
+        fn network_update(handle: HandleUntyped) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Deployables>();
+            Deployables::network_update(node);
+        }
+
+        NetworkReplicate { network_update }
+    }
+}
+
+impl Node for Deployables {
+    fn ready(mut node: RefMut<Self>) {
+        node.provides(Self::network_capabilities());
+    }
+
+    fn update(mut node: RefMut<Self>) {
+        let dt = get_frame_time();
+
+        for deployable in &mut node.active {
+            if let Some(animation_player) = deployable.animation_player.as_mut() {
+                animation_player.update(dt);
+                animation_player.play_frame_sounds(deployable.body.position);
+            }
+        }
+    }
+
+    fn draw(node: RefMut<Self>) {
+        for deployable in &node.active {
+            let center = deployable.body.get_render_position() + deployable.body.size / 2.0;
+
+            if let Some(animation_player) = &deployable.animation_player {
+                animation_player.draw(
+                    center - animation_player.get_size() / 2.0,
+                    0.0,
+                    !deployable.is_facing_right,
+                    false,
+                );
+            }
+
+            if let (Some(health), Some(max_health)) = (deployable.health, deployable.max_health) {
+                if health < max_health {
+                    let collider = deployable.body.get_collider_rect();
+                    let y = collider.y + Self::HEALTH_BAR_Y_OFFSET;
+                    let width = collider.w * (health / max_health).max(0.0);
+
+                    draw_rectangle(
+                        collider.x,
+                        y,
+                        collider.w,
+                        Self::HEALTH_BAR_HEIGHT,
+                        Color::new(0.0, 0.0, 0.0, 0.6),
+                    );
+                    draw_rectangle(collider.x, y, width, Self::HEALTH_BAR_HEIGHT, RED);
+                }
+            }
+
+            #[cfg(debug_assertions)]
+            deployable.body.debug_draw();
+        }
+    }
+}
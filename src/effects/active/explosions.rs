@@ -0,0 +1,244 @@
+//! Explosions are shared by all explosive weapons and items. An explosion will damage and knock
+//! back all players within its radius, including the player that caused it, scaled by the
+//! distance from the origin, as well as set off any triggered effects with the `explosion`
+//! trigger and remove any destructible tiles within its radius.
+
+use macroquad::{
+    experimental::{
+        collections::storage,
+        scene::{self, Handle},
+    },
+    prelude::*,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Deployables, TriggeredEffectTrigger, TriggeredEffects};
+
+use crate::{
+    audio::duck_music,
+    game::GameEvent,
+    math::IsZero,
+    physics::{self, EntityIndex, QueryMask},
+    player::DamageParams,
+    GameCamera, GameWorld, InteractiveObjects, Lighting, ParticleEmitters, Player,
+};
+
+/// This specifies how a value falls off from the origin of an explosion, to its radius.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExplosionFalloff {
+    /// The full value is applied everywhere within the radius
+    None,
+    /// The value falls off linearly with distance
+    Linear,
+    /// The value falls off slowly close to the origin and quickly towards the radius
+    Quadratic,
+    /// The value falls off quickly close to the origin and slowly towards the radius
+    InverseQuadratic,
+}
+
+impl ExplosionFalloff {
+    /// Returns the factor to apply to a value, at `distance` from the origin, where `distance` is
+    /// a fraction of the explosion radius, between `0.0` and `1.0`.
+    pub fn get_factor(&self, distance: f32) -> f32 {
+        let distance = distance.clamp(0.0, 1.0);
+
+        match self {
+            Self::None => 1.0,
+            Self::Linear => 1.0 - distance,
+            Self::Quadratic => 1.0 - distance * distance,
+            Self::InverseQuadratic => (1.0 - distance) * (1.0 - distance),
+        }
+    }
+}
+
+impl Default for ExplosionFalloff {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ExplosionParams {
+    pub radius: f32,
+    /// This specifies how damage falls off with distance from the origin
+    #[serde(default)]
+    pub damage_falloff: ExplosionFalloff,
+    /// The velocity applied to players at the origin of the explosion, away from the origin
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    pub knockback: f32,
+    /// This specifies how knockback falls off with distance from the origin
+    #[serde(default = "ExplosionParams::default_knockback_falloff")]
+    pub knockback_falloff: ExplosionFalloff,
+    /// If this is `true`, players will be hit even if there are solid tiles between them and the
+    /// origin of the explosion
+    #[serde(default, skip_serializing_if = "crate::json::is_false")]
+    pub ignores_line_of_sight: bool,
+    /// The amount of trauma, between `0.0` and `1.0`, added to the camera shake by the explosion
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    pub camera_shake: f32,
+    /// This can specify the id of a particle effect that will be spawned at the origin
+    #[serde(
+        default,
+        rename = "particle_effect",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub particle_effect_id: Option<String>,
+    /// This can specify the id of a particle effect that will be spawned at the position of each
+    /// destroyed tile
+    #[serde(
+        default,
+        rename = "debris_particle_effect",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub debris_particle_effect_id: Option<String>,
+}
+
+impl ExplosionParams {
+    pub fn default_knockback_falloff() -> ExplosionFalloff {
+        ExplosionFalloff::Linear
+    }
+}
+
+/// The light emitted by explosions, on maps with darkness, where the radius of the light is
+/// relative to the radius of the explosion
+const EXPLOSION_LIGHT_RADIUS_FACTOR: f32 = 3.0;
+const EXPLOSION_LIGHT_DURATION: f32 = 0.4;
+const EXPLOSION_LIGHT_COLOR: Color = Color {
+    r: 1.0,
+    g: 0.7,
+    b: 0.3,
+    a: 1.0,
+};
+
+/// Explosions with at least this much camera shake will duck the music, so that they stand out
+const BIG_EXPLOSION_CAMERA_SHAKE: f32 = 0.5;
+const EXPLOSION_MUSIC_DUCK_AMOUNT: f32 = 0.6;
+const EXPLOSION_MUSIC_DUCK_DURATION: f32 = 1.0;
+
+/// Returns `true` if there are no solid tiles on the line between `from` and `to`
+fn is_line_of_sight(from: Vec2, to: Vec2) -> bool {
+    physics::raycast(from, to - from, QueryMask::TILES).is_none()
+}
+
+/// This will instantiate an explosion at `origin`. `damage` is the damage dealt at the origin.
+pub fn explode(owner: Handle<Player>, origin: Vec2, damage: f32, params: &ExplosionParams) {
+    explode_ex(owner, origin, damage, params, DamageParams::default())
+}
+
+/// This is the same as `explode`, except the damage dealt to players is given `damage_params`
+pub fn explode_ex(
+    owner: Handle<Player>,
+    origin: Vec2,
+    damage: f32,
+    params: &ExplosionParams,
+    damage_params: DamageParams,
+) {
+    let circle = Circle::new(origin.x, origin.y, params.radius);
+
+    let rect = Rect::new(
+        origin.x - params.radius,
+        origin.y - params.radius,
+        params.radius * 2.0,
+        params.radius * 2.0,
+    );
+
+    for mut player in EntityIndex::get_players(&rect)
+        .into_iter()
+        .filter_map(scene::try_get_node)
+    {
+        let collider = player.get_collider_rect();
+
+        if !circle.overlaps_rect(&collider) {
+            continue;
+        }
+
+        let center = vec2(collider.x + collider.w / 2.0, collider.y + collider.h / 2.0);
+
+        if !params.ignores_line_of_sight && !is_line_of_sight(origin, center) {
+            continue;
+        }
+
+        let distance = if params.radius > 0.0 {
+            origin.distance(center) / params.radius
+        } else {
+            0.0
+        };
+
+        if params.knockback > 0.0 {
+            let direction = (center - origin).normalize_or_zero();
+            let knockback = params.knockback * params.knockback_falloff.get_factor(distance);
+
+            player.body.apply_knockback(direction * knockback);
+        }
+
+        let is_from_right = origin.x > player.body.position.x;
+
+        Player::on_receive_damage_ex(
+            player.handle(),
+            is_from_right,
+            Some(owner),
+            damage * params.damage_falloff.get_factor(distance),
+            damage_params.clone(),
+        );
+    }
+
+    {
+        let mut triggered_effects = scene::find_node_by_type::<TriggeredEffects>().unwrap();
+        triggered_effects.check_triggers_circle(TriggeredEffectTrigger::Explosion, &circle, None);
+    }
+
+    {
+        let mut deployables = scene::find_node_by_type::<Deployables>().unwrap();
+        deployables.apply_explosion_damage(&circle, damage, params.damage_falloff);
+    }
+
+    {
+        let mut interactive_objects = scene::find_node_by_type::<InteractiveObjects>().unwrap();
+        interactive_objects.apply_explosion_damage(&circle, damage, params.damage_falloff);
+    }
+
+    let destroyed_tiles = {
+        let mut world = storage::get_mut::<GameWorld>();
+        world.map.destroy_tiles(&circle)
+    };
+
+    for position in &destroyed_tiles {
+        GameEvent::TileDestroyed {
+            position: *position,
+        }
+        .dispatch();
+    }
+
+    {
+        let mut particles = scene::find_node_by_type::<ParticleEmitters>().unwrap();
+
+        if let Some(particle_effect_id) = &params.particle_effect_id {
+            particles.spawn(particle_effect_id, origin);
+        }
+
+        if let Some(particle_effect_id) = &params.debris_particle_effect_id {
+            for position in destroyed_tiles {
+                particles.spawn(particle_effect_id, position);
+            }
+        }
+    }
+
+    Lighting::add_flash(
+        origin,
+        params.radius * EXPLOSION_LIGHT_RADIUS_FACTOR,
+        EXPLOSION_LIGHT_COLOR,
+        EXPLOSION_LIGHT_DURATION,
+    );
+
+    if params.camera_shake > 0.0 {
+        if let Some(mut camera) = scene::find_node_by_type::<GameCamera>() {
+            camera.add_trauma(params.camera_shake);
+        }
+    }
+
+    if params.camera_shake >= BIG_EXPLOSION_CAMERA_SHAKE {
+        duck_music(EXPLOSION_MUSIC_DUCK_AMOUNT, EXPLOSION_MUSIC_DUCK_DURATION);
+    }
+}
@@ -0,0 +1,123 @@
+//! A grappling hook is fired at an angle, in the direction the player is facing, and attaches to
+//! the first solid tile it hits, within its range. While attached, the player will swing from the
+//! rope, and can reel it in, by holding jump, or out, by holding down. Firing the hook again, or
+//! dying, will release the rope.
+
+use macroquad::{experimental::collections::storage, prelude::*};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    components::PhysicsBody,
+    math::{deg_to_rad, IsZero},
+    physics::{self, QueryMask, Rope},
+    symbol::Symbol,
+    GameInput, Resources,
+};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GrapplingHookParams {
+    /// The maximum distance the hook can reach, which is also the maximum length of the rope
+    pub range: f32,
+    /// The angle, in degrees, above the horizontal, that the hook is fired at
+    #[serde(default = "GrapplingHookParams::default_angle")]
+    pub angle: f32,
+    /// The rope can not be reeled in further than this
+    #[serde(default = "GrapplingHookParams::default_min_length")]
+    pub min_length: f32,
+    /// The speed at which the rope is reeled in or out
+    #[serde(default = "GrapplingHookParams::default_reel_speed")]
+    pub reel_speed: f32,
+    /// The horizontal acceleration applied while swinging and holding left or right
+    #[serde(default = "GrapplingHookParams::default_swing_force")]
+    pub swing_force: f32,
+    /// The id of a texture that will be tiled along the rope. If this is not specified, the rope
+    /// will be drawn as a line.
+    #[serde(default, rename = "texture", skip_serializing_if = "Option::is_none")]
+    pub texture_id: Option<Symbol>,
+}
+
+impl GrapplingHookParams {
+    pub fn default_angle() -> f32 {
+        45.0
+    }
+
+    pub fn default_min_length() -> f32 {
+        32.0
+    }
+
+    pub fn default_reel_speed() -> f32 {
+        240.0
+    }
+
+    pub fn default_swing_force() -> f32 {
+        900.0
+    }
+}
+
+/// An attached grappling hook, held by a player
+pub struct GrapplingHook {
+    pub rope: Rope,
+    pub params: GrapplingHookParams,
+}
+
+impl GrapplingHook {
+    const ROPE_COLOR: Color = Color {
+        r: 0.55,
+        g: 0.4,
+        b: 0.25,
+        a: 1.0,
+    };
+
+    /// This fires a hook from `origin` and returns a `GrapplingHook`, if it hits a solid tile
+    /// within range
+    pub fn fire(origin: Vec2, is_facing_right: bool, params: GrapplingHookParams) -> Option<Self> {
+        let angle = deg_to_rad(params.angle);
+
+        let mut direction = vec2(angle.cos(), -angle.sin());
+        if !is_facing_right {
+            direction.x = -direction.x;
+        }
+
+        physics::raycast(origin, direction * params.range, QueryMask::TILES).map(|hit| {
+            let length = hit.point.distance(origin);
+            let rope = Rope::new(hit.point, length, params.min_length, params.range);
+
+            GrapplingHook { rope, params }
+        })
+    }
+
+    /// This reels the rope in or out, and applies swing acceleration, according to `input`. This
+    /// should be called before the body is updated, while the hook is attached.
+    pub fn update(&mut self, body: &mut PhysicsBody, input: &GameInput, dt: f32) {
+        if input.jump {
+            self.rope.reel(-self.params.reel_speed * dt);
+        } else if input.down {
+            self.rope.reel(self.params.reel_speed * dt);
+        }
+
+        if !body.is_on_ground && !self.params.swing_force.is_zero() {
+            let collider = body.get_collider_rect();
+            if self.rope.is_taut(collider.point() + collider.size() / 2.0) {
+                if input.right {
+                    body.velocity.x += self.params.swing_force * dt;
+                } else if input.left {
+                    body.velocity.x -= self.params.swing_force * dt;
+                }
+            }
+        }
+    }
+
+    pub fn draw(&self, from: Vec2) {
+        let texture = self.params.texture_id.as_ref().map(|texture_id| {
+            let resources = storage::get::<Resources>();
+            resources
+                .textures
+                .get(texture_id)
+                .map(|res| res.texture)
+                .unwrap_or_else(|| panic!("GrapplingHook: Invalid texture ID '{}'", texture_id))
+        });
+
+        self.rope.draw(from, texture, Self::ROPE_COLOR);
+    }
+}
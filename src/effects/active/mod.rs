@@ -1,24 +1,29 @@
 use std::collections::HashMap;
 
 use macroquad::{
-    experimental::{
-        coroutines::{start_coroutine, wait_seconds, Coroutine},
-        scene::Handle,
-    },
+    experimental::{collections::storage, scene::Handle},
     prelude::*,
 };
 
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    components::ParticleControllerParams,
-    json::{self, GenericParam},
+    audio::play_sound_at,
+    components::{ParticleControllerParams, TrailParams},
+    effects::PassiveEffectParams,
+    game::{Rumble, SIMULATION_DELTA_TIME},
+    json::{self, GenericParam, OneOrMany},
     math::{deg_to_rad, rotate_vector, IsZero},
-    Player,
+    player::{DamageParams, PlayerCharacterParams},
+    GameCamera, ParticleEmitters, Player, Resources,
 };
 
 use super::AnyEffectParams;
 
+pub mod area_effects;
+pub mod deployables;
+pub mod explosions;
+pub mod grappling_hook;
 pub mod projectiles;
 pub mod triggered;
 
@@ -30,7 +35,11 @@ pub use coroutines::{
     add_active_effect_coroutine, get_active_effect_coroutine, ActiveEffectCoroutine,
 };
 
-pub use projectiles::{ProjectileKind, Projectiles};
+pub use area_effects::{AreaEffectParams, AreaEffects};
+pub use deployables::{DeployableBehavior, DeployableParams, Deployables};
+pub use explosions::{explode, explode_ex, ExplosionFalloff, ExplosionParams};
+pub use grappling_hook::{GrapplingHook, GrapplingHookParams};
+pub use projectiles::{ProjectileBallistics, ProjectileKind, Projectiles};
 
 /// This holds all the common parameters, available to all implementations, as well as specialized
 /// parameters, in the `ActiveEffectKind`.
@@ -53,6 +62,78 @@ pub struct ActiveEffectParams {
     /// `TriggeredEffect`, with a `timed_trigger` value, in stead of using this.
     #[serde(default)]
     pub delay: f32,
+    /// The amount of damage dealt to players hit by the effect. The default value is equal to
+    /// the default max health of player characters.
+    /// If the effect is instantiated by a weapon that has a `damage` value, that will be used
+    /// in stead of this.
+    #[serde(default = "ActiveEffectParams::default_damage")]
+    pub damage: f32,
+    /// The knockback applied to players that are damaged by the effect and survive, away from
+    /// the effect. If the effect is instantiated by a weapon that has a `knockback` value, that
+    /// will be used in stead of this.
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    pub knockback: f32,
+    /// The id of the weapon that instantiated the effect, if any, which kills by the effect, and
+    /// by any effects that it instantiates, are credited to. This is set with `set_weapon_id`.
+    #[serde(skip)]
+    pub weapon_id: Option<String>,
+}
+
+impl ActiveEffectParams {
+    pub fn default_damage() -> f32 {
+        PlayerCharacterParams::DEFAULT_MAX_HEALTH
+    }
+
+    /// This sets the weapon that kills by the effect are credited to, on the effect and on all the
+    /// effects that it will instantiate, like the effects of projectiles and deployables
+    pub fn set_weapon_id(&mut self, weapon_id: &str) {
+        self.weapon_id = Some(weapon_id.to_string());
+
+        let nested_effects: Vec<&mut ActiveEffectParams> = match &mut *self.kind {
+            ActiveEffectKind::CircleCollider { on_hit_effects, .. }
+            | ActiveEffectKind::RectCollider { on_hit_effects, .. } => {
+                on_hit_effects.iter_mut().collect()
+            }
+            ActiveEffectKind::TriggeredEffect { params } => {
+                let effects = match &mut params.effects {
+                    OneOrMany::One(effect) => std::slice::from_mut(effect),
+                    OneOrMany::Many(effects) => effects.as_mut_slice(),
+                };
+
+                effects
+                    .iter_mut()
+                    .filter_map(|effect| match effect {
+                        AnyEffectParams::Active(params) => Some(params),
+                        AnyEffectParams::Passive(_) => None,
+                    })
+                    .collect()
+            }
+            ActiveEffectKind::Projectile { ballistics, .. } => ballistics
+                .on_hit_effects
+                .iter_mut()
+                .chain(ballistics.on_expire_effects.iter_mut())
+                .collect(),
+            ActiveEffectKind::Deployable { params } => {
+                let mut effects: Vec<&mut ActiveEffectParams> =
+                    params.on_destroyed_effects.iter_mut().collect();
+
+                if let DeployableBehavior::Turret {
+                    effects: turret_effects,
+                    ..
+                } = &mut params.behavior
+                {
+                    effects.extend(turret_effects.iter_mut());
+                }
+
+                effects
+            }
+            _ => Vec::new(),
+        };
+
+        for params in nested_effects {
+            params.set_weapon_id(weapon_id);
+        }
+    }
 }
 
 /// This should hold implementations of the commonly used weapon effects, that see usage spanning
@@ -63,6 +144,24 @@ pub struct ActiveEffectParams {
 ///
 /// The effects that have the `Collider` suffix denote effects that do an immediate collider check,
 /// upon attack, using the weapons `effect_offset` as origin.
+///
+/// Some of the variants, like `PlaySound`, `CameraShake`, `ParticleEffect` and `ApplyStatus`, are
+/// primitives that do not do much on their own, but which can be combined, in a list of effects,
+/// with other effects, like `on_fire` on a weapon or `on_hit` on a collider or a projectile. This
+/// way most new items can be described entirely in JSON, by composing existing effects, like this:
+///
+/// ```json
+/// "on_fire": [
+///   { "type": "projectile", "projectile": { ... }, "speed": 800.0, "range": 600.0,
+///     "on_hit": [
+///       { "type": "apply_status", "radius": 16.0, "status": { ... } },
+///       { "type": "particle_effect", "particle_effect": "hit" }
+///     ]
+///   },
+///   { "type": "play_sound", "sound_effect": "shoot" },
+///   { "type": "camera_shake", "magnitude": 6.0 }
+/// ]
+/// ```
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ActiveEffectKind {
@@ -88,11 +187,41 @@ pub enum ActiveEffectKind {
             skip_serializing_if = "Option::is_none"
         )]
         segment: Option<IVec2>,
-        #[serde(default, skip_serializing_if = "json::is_false")]
-        is_explosion: bool,
+        /// Effects that will be instantiated at the position of each player that is hit
+        #[serde(default, alias = "on_hit", skip_serializing_if = "Vec::is_empty")]
+        on_hit_effects: Vec<ActiveEffectParams>,
+    },
+    /// Instantiate an explosion, damaging and knocking back all players within its radius,
+    /// including the player that caused it. This should be used by all explosive weapons.
+    Explosion {
+        #[serde(flatten)]
+        params: ExplosionParams,
+    },
+    /// Spawn a lingering area effect, like a poison cloud, a fire patch or a slime trail, that
+    /// damages and/or applies a status to players inside it, for the duration of its lifetime
+    AreaEffect {
+        #[serde(flatten)]
+        params: Box<AreaEffectParams>,
+    },
+    /// Fire a grappling hook that attaches to the first solid tile it hits, letting the player
+    /// swing from it. If the player already has a hook attached, it will be released, in stead.
+    GrapplingHook {
+        #[serde(flatten)]
+        params: Box<GrapplingHookParams>,
+    },
+    /// Place, or throw, a deployable world object, like a turret, a shield or a trampoline
+    Deployable {
+        #[serde(flatten)]
+        params: Box<DeployableParams>,
     },
     /// Check for hits with a `Rect` collider
-    RectCollider { width: f32, height: f32 },
+    RectCollider {
+        width: f32,
+        height: f32,
+        /// Effects that will be instantiated at the position of each player that is hit
+        #[serde(default, alias = "on_hit", skip_serializing_if = "Vec::is_empty")]
+        on_hit_effects: Vec<ActiveEffectParams>,
+    },
     /// Spawn a trigger that will set of another effect if its trigger conditions are met.
     TriggeredEffect {
         #[serde(flatten)]
@@ -110,145 +239,386 @@ pub enum ActiveEffectKind {
         /// Particle effects that will be attached to the projectile
         #[serde(default, skip_serializing_if = "Vec::is_empty")]
         particles: Vec<ParticleControllerParams>,
+        /// A trail that will be drawn behind the projectile
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        trail: Option<TrailParams>,
+        /// This holds the parameters that control things like gravity, bounces and piercing, as
+        /// well as effects that will be instantiated on hit and on expiry. It is flattened into
+        /// this variant in JSON.
+        #[serde(flatten)]
+        ballistics: Box<ProjectileBallistics>,
+    },
+    /// Only play the sound effect specified by `sound_effect`, in the effect parameters.
+    /// This is useful for delaying a sound, or for playing a sound on hit.
+    PlaySound,
+    /// Shake the camera
+    CameraShake {
+        magnitude: f32,
+        /// The length of the shake, in frames
+        #[serde(default = "ActiveEffectKind::default_camera_shake_length")]
+        length: i32,
+        #[serde(default = "ActiveEffectKind::default_camera_shake_frequency")]
+        frequency: f32,
+    },
+    /// Spawn a particle effect at the origin of the effect
+    ParticleEffect {
+        #[serde(rename = "particle_effect")]
+        particle_effect_id: String,
+    },
+    /// Apply a passive effect, like a status effect, to all players within `radius` of the origin
+    /// of the effect. The player that instantiated the effect will not be affected, unless
+    /// `is_self_applied` is `true`.
+    ApplyStatus {
+        radius: f32,
+        #[serde(rename = "status")]
+        params: Box<PassiveEffectParams>,
+        #[serde(default, skip_serializing_if = "json::is_false")]
+        is_self_applied: bool,
     },
 }
 
-pub fn active_effect_coroutine(
+impl ActiveEffectKind {
+    pub fn default_camera_shake_length() -> i32 {
+        10
+    }
+
+    pub fn default_camera_shake_frequency() -> f32 {
+        0.5
+    }
+}
+
+/// An effect that has been instantiated, but not yet applied. Effects are applied by
+/// `update_active_effects`, in the simulation tick, once their delay has passed.
+struct PendingActiveEffect {
+    player_handle: Handle<Player>,
+    origin: Vec2,
+    is_facing_right: Option<bool>,
+    params: ActiveEffectParams,
+    /// The time left until the effect is applied
+    delay_timer: f32,
+}
+
+static mut PENDING_ACTIVE_EFFECTS: Option<Vec<PendingActiveEffect>> = None;
+
+unsafe fn get_pending_active_effects() -> &'static mut Vec<PendingActiveEffect> {
+    PENDING_ACTIVE_EFFECTS.get_or_insert_with(Vec::new)
+}
+
+/// This instantiates an effect, that will be applied in the current tick, or when its delay has
+/// passed, counted in ticks
+pub fn instantiate_active_effect(
     player_handle: Handle<Player>,
     origin: Vec2,
     params: ActiveEffectParams,
-) -> Coroutine {
-    let coroutine = async move {
-        wait_seconds(params.delay).await;
+) {
+    queue_active_effect(player_handle, origin, None, params);
+}
 
-        let mut is_facing_right = false;
-        if let Some(player) = scene::try_get_node(player_handle) {
-            is_facing_right = player.body.is_facing_right;
+/// This is the same as `instantiate_active_effect`, except the direction of the effect is
+/// specified by `is_facing_right`, in stead of by the direction the player is facing. This is
+/// used for effects instantiated by things other than the player, like deployables.
+pub fn instantiate_directed_active_effect(
+    player_handle: Handle<Player>,
+    origin: Vec2,
+    is_facing_right: bool,
+    params: ActiveEffectParams,
+) {
+    queue_active_effect(player_handle, origin, Some(is_facing_right), params);
+}
+
+fn queue_active_effect(
+    player_handle: Handle<Player>,
+    origin: Vec2,
+    is_facing_right: Option<bool>,
+    params: ActiveEffectParams,
+) {
+    let delay_timer = params.delay;
+
+    unsafe { get_pending_active_effects() }.push(PendingActiveEffect {
+        player_handle,
+        origin,
+        is_facing_right,
+        params,
+        delay_timer,
+    });
+}
+
+/// This advances the delays of instantiated effects by one tick and applies the effects that are
+/// due. Effects are instantiated by nodes while they are updated, so they are queued and applied
+/// here, after all nodes have been updated, when no node is borrowed. Effects instantiated by
+/// other effects, like `on_hit_effects`, are applied in the same tick, if they have no delay.
+pub fn update_active_effects() {
+    for effect in unsafe { get_pending_active_effects() }.iter_mut() {
+        effect.delay_timer -= SIMULATION_DELTA_TIME;
+    }
+
+    loop {
+        let due = {
+            let pending = unsafe { get_pending_active_effects() };
+
+            let (due, waiting): (Vec<_>, Vec<_>) = pending
+                .drain(..)
+                .partition(|effect| effect.delay_timer <= 0.0);
+
+            *pending = waiting;
+
+            due
+        };
+
+        if due.is_empty() {
+            break;
         }
 
-        match *params.kind {
-            ActiveEffectKind::Custom { id, params } => {
-                let f = get_active_effect_coroutine(&id);
-                f(player_handle, params);
-            }
-            ActiveEffectKind::CircleCollider {
-                radius,
-                segment,
-                is_explosion,
-            } => {
-                // borrow player so that it is excluded from hit check below
-                let mut _player = None;
-                if !is_explosion {
-                    _player = scene::try_get_node(player_handle)
-                }
+        for effect in due {
+            apply_active_effect(
+                effect.player_handle,
+                effect.origin,
+                effect.is_facing_right,
+                effect.params,
+            );
+        }
+    }
+}
 
-                let circle = Circle::new(origin.x, origin.y, radius);
-                for player in scene::find_nodes_by_type::<Player>() {
-                    let collider = player.get_collider_rect();
-                    if circle.overlaps_rect(&collider) {
-                        let mut is_killed = false;
-
-                        if let Some(mut segment) = segment {
-                            if !is_facing_right {
-                                segment.x = -segment.x;
-                            }
-
-                            if segment.x == 1 {
-                                is_killed = collider.x + collider.w >= circle.point().x;
-                            } else if segment.x == -1 {
-                                is_killed = collider.x <= circle.point().x;
-                            }
-
-                            if segment.y == 1 {
-                                is_killed =
-                                    is_killed && collider.y + collider.h <= circle.point().y;
-                            } else if segment.y == -1 {
-                                is_killed = is_killed && collider.y >= circle.point().y;
-                            }
-                        } else {
-                            is_killed = true;
-                        }
+/// This removes all instantiated effects that have not been applied yet. It should be called
+/// whenever a new game scene is created.
+pub fn clear_active_effects() {
+    unsafe { get_pending_active_effects() }.clear();
+}
 
-                        if is_killed {
-                            let is_from_right = origin.x > player.body.position.x;
-                            Player::on_receive_damage(
-                                player.handle(),
-                                is_from_right,
-                                Some(player_handle),
-                            );
+fn apply_active_effect(
+    player_handle: Handle<Player>,
+    origin: Vec2,
+    is_facing_right: Option<bool>,
+    params: ActiveEffectParams,
+) {
+    if let Some(sound_effect_id) = &params.sound_effect_id {
+        let resources = storage::get::<Resources>();
+        play_sound_at(&resources.sounds[sound_effect_id], origin);
+    }
+
+    let damage = params.damage;
+
+    let damage_params = DamageParams {
+        knockback: params.knockback,
+        weapon_id: params.weapon_id.clone(),
+        ..Default::default()
+    };
+
+    // Effects that are directed by something other than the player are not aimed
+    let aim = if is_facing_right.is_none() {
+        scene::try_get_node(player_handle).and_then(|player| player.get_aim_direction())
+    } else {
+        None
+    };
+
+    let is_facing_right = is_facing_right.unwrap_or_else(|| {
+        scene::try_get_node(player_handle)
+            .map(|player| player.body.is_facing_right)
+            .unwrap_or(false)
+    });
+
+    match *params.kind {
+        ActiveEffectKind::Custom { id, params } => {
+            let f = get_active_effect_coroutine(&id);
+            f(player_handle, params);
+        }
+        ActiveEffectKind::CircleCollider {
+            radius,
+            segment,
+            on_hit_effects,
+        } => {
+            // borrow player so that it is excluded from hit check below
+            let _player = scene::try_get_node(player_handle);
+
+            let circle = Circle::new(origin.x, origin.y, radius);
+            for player in scene::find_nodes_by_type::<Player>() {
+                let collider = player.get_collider_rect();
+                if circle.overlaps_rect(&collider) {
+                    let mut is_killed = false;
+
+                    if let Some(mut segment) = segment {
+                        if !is_facing_right {
+                            segment.x = -segment.x;
                         }
-                    }
-                }
 
-                if is_explosion {
-                    let mut triggered_effects =
-                        scene::find_node_by_type::<TriggeredEffects>().unwrap();
-                    triggered_effects.check_triggers_circle(
-                        TriggeredEffectTrigger::Explosion,
-                        &circle,
-                        None,
-                    );
-                }
-            }
-            ActiveEffectKind::RectCollider { width, height } => {
-                // borrow player so that it is excluded from hit check below
-                let _player = scene::try_get_node(player_handle);
+                        if segment.x == 1 {
+                            is_killed = collider.x + collider.w >= circle.point().x;
+                        } else if segment.x == -1 {
+                            is_killed = collider.x <= circle.point().x;
+                        }
 
-                let mut rect = Rect::new(origin.x, origin.y, width, height);
-                if !is_facing_right {
-                    rect.x -= rect.w;
-                }
+                        if segment.y == 1 {
+                            is_killed = is_killed && collider.y + collider.h <= circle.point().y;
+                        } else if segment.y == -1 {
+                            is_killed = is_killed && collider.y >= circle.point().y;
+                        }
+                    } else {
+                        is_killed = true;
+                    }
 
-                for player in scene::find_nodes_by_type::<Player>() {
-                    if rect.overlaps(&player.get_collider_rect()) {
+                    if is_killed {
                         let is_from_right = origin.x > player.body.position.x;
-                        Player::on_receive_damage(
+                        Player::on_receive_damage_ex(
                             player.handle(),
                             is_from_right,
                             Some(player_handle),
+                            damage,
+                            damage_params.clone(),
                         );
+
+                        for params in on_hit_effects.iter().cloned() {
+                            instantiate_active_effect(
+                                player_handle,
+                                collider.point() + collider.size() / 2.0,
+                                params,
+                            );
+                        }
                     }
                 }
             }
-            ActiveEffectKind::TriggeredEffect { mut params } => {
-                let mut triggered_effects = scene::find_node_by_type::<TriggeredEffects>().unwrap();
+        }
+        ActiveEffectKind::Explosion { params } => {
+            explosions::explode_ex(player_handle, origin, damage, &params, damage_params);
+        }
+        ActiveEffectKind::AreaEffect { params } => {
+            let mut area_effects = scene::find_node_by_type::<AreaEffects>().unwrap();
+            area_effects.spawn(
+                player_handle,
+                origin,
+                is_facing_right,
+                *params,
+                damage_params.weapon_id,
+            );
+        }
+        ActiveEffectKind::GrapplingHook { params } => {
+            if let Some(mut player) = scene::try_get_node(player_handle) {
+                if player.grappling_hook.is_some() {
+                    player.grappling_hook = None;
+                } else {
+                    player.grappling_hook = GrapplingHook::fire(origin, is_facing_right, *params);
+                }
+            }
+        }
+        ActiveEffectKind::Deployable { params } => {
+            let mut deployables = scene::find_node_by_type::<Deployables>().unwrap();
+            deployables.spawn(player_handle, origin, is_facing_right, *params);
+        }
+        ActiveEffectKind::RectCollider {
+            width,
+            height,
+            on_hit_effects,
+        } => {
+            // borrow player so that it is excluded from hit check below
+            let _player = scene::try_get_node(player_handle);
+
+            let mut rect = Rect::new(origin.x, origin.y, width, height);
+            if !is_facing_right {
+                rect.x -= rect.w;
+            }
+
+            for player in scene::find_nodes_by_type::<Player>() {
+                let collider = player.get_collider_rect();
+                if rect.overlaps(&collider) {
+                    let is_from_right = origin.x > player.body.position.x;
+                    Player::on_receive_damage_ex(
+                        player.handle(),
+                        is_from_right,
+                        Some(player_handle),
+                        damage,
+                        damage_params.clone(),
+                    );
 
-                if !is_facing_right {
-                    params.velocity.x = -params.velocity.x;
+                    for params in on_hit_effects.iter().cloned() {
+                        instantiate_active_effect(
+                            player_handle,
+                            collider.point() + collider.size() / 2.0,
+                            params,
+                        );
+                    }
                 }
+            }
+        }
+        ActiveEffectKind::TriggeredEffect { mut params } => {
+            let mut triggered_effects = scene::find_node_by_type::<TriggeredEffects>().unwrap();
 
-                triggered_effects.spawn(player_handle, origin - params.size / 2.0, *params)
+            if !is_facing_right {
+                params.velocity.x = -params.velocity.x;
             }
-            ActiveEffectKind::Projectile {
+
+            triggered_effects.spawn(player_handle, origin - params.size / 2.0, *params)
+        }
+        ActiveEffectKind::Projectile {
+            kind,
+            speed,
+            range,
+            spread,
+            particles,
+            trail,
+            ballistics,
+        } => {
+            let rad = deg_to_rad(spread);
+            let spread = rand::gen_range(-rad, rad);
+
+            let mut velocity = Vec2::ZERO;
+            if let Some(aim) = aim {
+                velocity = aim * speed
+            } else if is_facing_right {
+                velocity.x = speed
+            } else {
+                velocity.x = -speed
+            }
+
+            let mut projectiles = scene::find_node_by_type::<Projectiles>().unwrap();
+
+            projectiles.spawn(
+                player_handle,
                 kind,
-                speed,
+                origin,
+                rotate_vector(velocity, spread),
                 range,
-                spread,
+                damage,
+                damage_params.knockback,
+                *ballistics,
                 particles,
-            } => {
-                let rad = deg_to_rad(spread);
-                let spread = rand::gen_range(-rad, rad);
-
-                let mut velocity = Vec2::ZERO;
-                if is_facing_right {
-                    velocity.x = speed
-                } else {
-                    velocity.x = -speed
-                }
+                trail,
+                damage_params.weapon_id,
+            );
+        }
+        ActiveEffectKind::PlaySound => {}
+        ActiveEffectKind::CameraShake {
+            magnitude,
+            length,
+            frequency,
+        } => {
+            if let Some(mut camera) = scene::find_node_by_type::<GameCamera>() {
+                camera.shake_noise(magnitude, length, frequency);
+            }
 
-                let mut projectiles = scene::find_node_by_type::<Projectiles>().unwrap();
+            Rumble::add_shake(magnitude);
+        }
+        ActiveEffectKind::ParticleEffect { particle_effect_id } => {
+            let mut particles = scene::find_node_by_type::<ParticleEmitters>().unwrap();
+            particles.spawn(&particle_effect_id, origin);
+        }
+        ActiveEffectKind::ApplyStatus {
+            radius,
+            params,
+            is_self_applied,
+        } => {
+            // borrow player so that it is excluded from the check below, unless self applied
+            let _player = if is_self_applied {
+                None
+            } else {
+                scene::try_get_node(player_handle)
+            };
 
-                projectiles.spawn(
-                    player_handle,
-                    kind,
-                    origin,
-                    rotate_vector(velocity, spread),
-                    range,
-                    particles,
-                );
+            let circle = Circle::new(origin.x, origin.y, radius);
+            for mut player in scene::find_nodes_by_type::<Player>() {
+                if circle.overlaps_rect(&player.get_collider_rect()) {
+                    player.add_passive_effect(None, (*params).clone());
+                }
             }
         }
-    };
-
-    start_coroutine(coroutine)
+    }
 }
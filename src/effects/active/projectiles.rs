@@ -10,12 +10,24 @@ use macroquad::{
 
 use serde::{Deserialize, Serialize};
 
-use super::{TriggeredEffectTrigger, TriggeredEffects};
+use super::{
+    instantiate_active_effect, ActiveEffectParams, Deployables, TriggeredEffectTrigger,
+    TriggeredEffects,
+};
 
 use crate::{
     capabilities::NetworkReplicate,
-    components::{ParticleController, ParticleControllerParams, Sprite, SpriteParams},
-    json, GameWorld, ParticleEmitters, Player,
+    components::{
+        ParticleController, ParticleControllerParams, PhysicsBody, Sprite, SpriteParams, Trail,
+        TrailParams,
+    },
+    game::{SimulationTimer, SIMULATION_DELTA_TIME},
+    json,
+    math::IsZero,
+    physics::{CollisionLayers, EntityIndex},
+    player::DamageParams,
+    pool::Pool,
+    GameWorld, InteractiveObjects, ParticleEmitters, Player,
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,27 +54,128 @@ pub enum ProjectileKind {
     },
 }
 
+/// This holds the parameters that control how a projectile moves and what happens when it hits
+/// something. All members are optional, so, by default, a projectile will travel in a straight
+/// line until it hits something or has traveled its full range.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ProjectileBallistics {
+    /// The factor of gravity applied to the projectile. If this is zero, the projectile will
+    /// travel in a straight line.
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    pub gravity_scale: f32,
+    /// The amount of velocity lost per second, as a factor of current velocity
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    pub drag: f32,
+    /// The amount of times the projectile will bounce off solid tiles, before it is destroyed
+    #[serde(default, skip_serializing_if = "u32::is_zero")]
+    pub bounces: u32,
+    /// The amount of players the projectile will pass through, before it is destroyed
+    #[serde(default, skip_serializing_if = "u32::is_zero")]
+    pub pierce: u32,
+    /// The layers that the projectile interacts with. A projectile without `solids` will pass
+    /// through walls, one without `players` will not hit players and one without `hazards` will
+    /// not hit deployables or set off triggered effects.
+    #[serde(default, skip_serializing_if = "CollisionLayers::is_all")]
+    pub collision_mask: CollisionLayers,
+    /// If this is specified, the projectile will expire after `lifetime` seconds, even if it
+    /// has not traveled its full range
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lifetime: Option<f32>,
+    /// Effects that will be instantiated at the projectile's position when it hits a player
+    #[serde(default, alias = "on_hit", skip_serializing_if = "Vec::is_empty")]
+    pub on_hit_effects: Vec<ActiveEffectParams>,
+    /// Effects that will be instantiated at the projectile's position when it expires, either
+    /// from hitting a solid tile or by exceeding its range or lifetime
+    #[serde(default, alias = "on_expire", skip_serializing_if = "Vec::is_empty")]
+    pub on_expire_effects: Vec<ActiveEffectParams>,
+}
+
 // TODO: Performance test this and reduce complexity as needed
 struct Projectile {
     owner: Handle<Player>,
     kind: ProjectileKind,
     origin: Vec2,
     position: Vec2,
+    /// The position at the start of the last tick, used to interpolate the rendered position
+    last_position: Vec2,
     velocity: Vec2,
     range: f32,
+    damage: f32,
+    knockback: f32,
+    ballistics: ProjectileBallistics,
     sprite: Option<Sprite>,
     sprite_draw_angle: f32,
     particles: Vec<ParticleController>,
+    trail: Option<Trail>,
+    /// The ids of the players that have already been hit by a piercing projectile
+    hit_players: Vec<u8>,
+    bounces_left: u32,
+    pierce_left: u32,
+    lifetime_timer: f32,
+    /// The id of the weapon that kills by the projectile are credited to
+    weapon_id: Option<String>,
+}
+
+impl Projectile {
+    /// This is called when the projectile is destroyed, before it is despawned from the pool
+    fn on_destroyed(&mut self, is_expired: bool) {
+        if is_expired {
+            for params in self.ballistics.on_expire_effects.drain(..) {
+                instantiate_active_effect(self.owner, self.position, params);
+            }
+        }
+
+        self.particles.clear();
+        self.trail = None;
+    }
+}
+
+/// Projectiles are only default constructed to fill the slots of the pool, which are reset by
+/// `Projectiles::spawn` before they are used
+impl Default for Projectile {
+    fn default() -> Self {
+        Projectile {
+            owner: Handle::null(),
+            kind: ProjectileKind::Circle {
+                radius: 0.0,
+                color: BLANK,
+            },
+            origin: Vec2::ZERO,
+            position: Vec2::ZERO,
+            last_position: Vec2::ZERO,
+            velocity: Vec2::ZERO,
+            range: 0.0,
+            damage: 0.0,
+            knockback: 0.0,
+            ballistics: ProjectileBallistics::default(),
+            sprite: None,
+            sprite_draw_angle: 0.0,
+            particles: Vec::new(),
+            trail: None,
+            hit_players: Vec::new(),
+            bounces_left: 0,
+            pierce_left: 0,
+            lifetime_timer: 0.0,
+            weapon_id: None,
+        }
+    }
 }
 
+/// All the active projectiles. These are pooled, so that the buffers of destroyed projectiles are
+/// reused by the next projectiles that are spawned, and firing does not allocate once the pool
+/// has grown to the amount of projectiles in flight in a heavy firefight.
 #[derive(Default)]
 pub struct Projectiles {
-    active: Vec<Projectile>,
+    active: Pool<Projectile>,
 }
 
 impl Projectiles {
+    const INITIAL_POOL_SIZE: usize = 64;
+
     pub fn new() -> Self {
-        Projectiles { active: Vec::new() }
+        Projectiles {
+            active: Pool::with_capacity(Self::INITIAL_POOL_SIZE),
+        }
     }
 
     pub fn spawn(
@@ -72,7 +185,12 @@ impl Projectiles {
         origin: Vec2,
         velocity: Vec2,
         range: f32,
+        damage: f32,
+        knockback: f32,
+        ballistics: ProjectileBallistics,
         particles: Vec<ParticleControllerParams>,
+        trail: Option<TrailParams>,
+        weapon_id: Option<String>,
     ) {
         let mut sprite = None;
 
@@ -100,41 +218,67 @@ impl Projectiles {
             }
         }
 
-        let mut particles = particles
-            .into_iter()
-            .map(ParticleController::new)
-            .collect::<Vec<ParticleController>>();
-
-        particles.iter_mut().for_each(|p| p.activate());
-
-        self.active.push(Projectile {
-            owner,
-            kind,
-            origin,
-            position: origin,
-            velocity,
-            range,
-            sprite,
-            sprite_draw_angle,
-            particles,
+        // The buffers of the last projectile in the slot are cleared and reused
+        self.active.spawn_with(|projectile| {
+            projectile.particles.clear();
+            projectile
+                .particles
+                .extend(particles.into_iter().map(ParticleController::new));
+            projectile.particles.iter_mut().for_each(|p| p.activate());
+
+            projectile.hit_players.clear();
+
+            projectile.owner = owner;
+            projectile.kind = kind;
+            projectile.origin = origin;
+            projectile.position = origin;
+            projectile.last_position = origin;
+            projectile.velocity = velocity;
+            projectile.range = range;
+            projectile.damage = damage;
+            projectile.knockback = knockback;
+            projectile.bounces_left = ballistics.bounces;
+            projectile.pierce_left = ballistics.pierce;
+            projectile.ballistics = ballistics;
+            projectile.sprite = sprite;
+            projectile.sprite_draw_angle = sprite_draw_angle;
+            projectile.trail = trail.map(Trail::new);
+            projectile.lifetime_timer = 0.0;
+            projectile.weapon_id = weapon_id;
         });
     }
 
     fn network_update(mut node: RefMut<Self>) {
-        let dt = get_frame_time();
+        let dt = SIMULATION_DELTA_TIME;
+
+        node.active.retain(|projectile| {
+            projectile.velocity.y +=
+                PhysicsBody::get_gravity() * projectile.ballistics.gravity_scale * dt * dt;
+
+            if projectile.ballistics.drag > 0.0 {
+                projectile.velocity *= (1.0 - projectile.ballistics.drag * dt).max(0.0);
+            }
 
-        let mut i = 0;
-        while i < node.active.len() {
-            let projectile = &mut node.active[i];
+            let previous_position = projectile.position;
+            projectile.last_position = previous_position;
             projectile.position += projectile.velocity;
 
+            if let Some(trail) = &mut projectile.trail {
+                trail.add_point(previous_position);
+            }
+
+            projectile.lifetime_timer += dt;
+
             for particles in &mut projectile.particles {
                 particles.update(dt);
             }
 
+            let mut is_expired = false;
             let mut is_hit = false;
 
-            {
+            let mask = projectile.ballistics.collision_mask;
+
+            if mask.contains(CollisionLayers::HAZARDS) {
                 let mut triggered_effects = scene::find_node_by_type::<TriggeredEffects>().unwrap();
                 triggered_effects.check_triggers_point(
                     TriggeredEffectTrigger::Projectile,
@@ -146,21 +290,85 @@ impl Projectiles {
             {
                 let distance = projectile.position.distance(projectile.origin);
                 if distance > projectile.range {
-                    is_hit = true;
+                    is_expired = true;
                 }
 
-                if !is_hit {
+                if let Some(lifetime) = projectile.ballistics.lifetime {
+                    if projectile.lifetime_timer >= lifetime {
+                        is_expired = true;
+                    }
+                }
+
+                if !is_expired && mask.contains(CollisionLayers::SOLIDS) {
                     let world = storage::get::<GameWorld>();
 
-                    is_hit = world.collision_world.solid_at(projectile.position);
+                    if world.is_solid_at(projectile.position) {
+                        if projectile.bounces_left > 0 {
+                            projectile.bounces_left -= 1;
+
+                            let velocity = projectile.velocity;
+
+                            if let Some(normal) = world.get_slope_normal(projectile.position) {
+                                // Reflect off of the surface of the slope
+                                projectile.velocity =
+                                    velocity - 2.0 * velocity.dot(normal) * normal;
+                            } else {
+                                if world.is_solid_at(previous_position + vec2(velocity.x, 0.0)) {
+                                    projectile.velocity.x = -velocity.x;
+                                }
+
+                                if world.is_solid_at(previous_position + vec2(0.0, velocity.y)) {
+                                    projectile.velocity.y = -velocity.y;
+                                }
+                            }
+
+                            projectile.position = previous_position;
+                        } else {
+                            is_expired = true;
+                        }
+                    }
+                }
+            }
+
+            if !is_expired && mask.contains(CollisionLayers::HAZARDS) {
+                let owner_id = scene::try_get_node(projectile.owner).map(|player| player.id);
+
+                let mut deployables = scene::find_node_by_type::<Deployables>().unwrap();
+                if deployables.check_projectile_hit(
+                    projectile.position,
+                    owner_id,
+                    projectile.damage,
+                ) {
+                    is_expired = true;
+                }
+            }
+
+            if !is_expired {
+                let mut interactive_objects =
+                    scene::find_node_by_type::<InteractiveObjects>().unwrap();
+                if interactive_objects.check_projectile_hit(projectile.position, projectile.damage)
+                {
+                    is_expired = true;
                 }
             }
 
-            if !is_hit {
+            if !is_expired && mask.contains(CollisionLayers::PLAYERS) {
                 // Borrow owner so that it is excluded from the following iteration and hit check
                 let _player = scene::try_get_node(projectile.owner);
 
-                for player in scene::find_nodes_by_type::<Player>() {
+                for player in EntityIndex::get_players_at(projectile.position)
+                    .into_iter()
+                    .filter_map(scene::try_get_node)
+                {
+                    if projectile.hit_players.contains(&player.id)
+                        || !player
+                            .body
+                            .collision_mask
+                            .contains(CollisionLayers::PROJECTILES)
+                    {
+                        continue;
+                    }
+
                     let hitbox = player.get_collider_rect();
 
                     if hitbox.contains(projectile.position) {
@@ -168,25 +376,45 @@ impl Projectiles {
                         particles.spawn("hit", projectile.position);
 
                         let is_from_right = projectile.position.x > player.body.position.x;
-                        Player::on_receive_damage(
+                        Player::on_receive_damage_ex(
                             player.handle(),
                             is_from_right,
                             Some(projectile.owner),
+                            projectile.damage,
+                            DamageParams {
+                                knockback: projectile.knockback,
+                                weapon_id: projectile.weapon_id.clone(),
+                                ..Default::default()
+                            },
                         );
 
-                        is_hit = true;
-                        break;
+                        for params in projectile.ballistics.on_hit_effects.clone() {
+                            instantiate_active_effect(
+                                projectile.owner,
+                                projectile.position,
+                                params,
+                            );
+                        }
+
+                        projectile.hit_players.push(player.id);
+
+                        if projectile.pierce_left > 0 {
+                            projectile.pierce_left -= 1;
+                        } else {
+                            is_hit = true;
+                            break;
+                        }
                     }
                 }
             }
 
-            if is_hit {
-                node.active.remove(i);
-                continue;
+            if is_hit || is_expired {
+                projectile.on_destroyed(is_expired);
+                return false;
             }
 
-            i += 1;
-        }
+            true
+        });
     }
 
     fn network_capabilities() -> NetworkReplicate {
@@ -207,37 +435,36 @@ impl Node for Projectiles {
     }
 
     fn draw(mut node: RefMut<Self>) {
-        for projectile in &mut node.active {
+        let interpolation = SimulationTimer::get_interpolation();
+
+        for projectile in node.active.iter_mut() {
+            let position = projectile
+                .last_position
+                .lerp(projectile.position, interpolation);
+
             let flip_x = projectile.velocity.x < 0.0;
 
+            if let Some(trail) = &projectile.trail {
+                trail.draw(position);
+            }
+
             match projectile.kind.clone() {
                 ProjectileKind::Circle { radius, color } => {
-                    draw_circle(projectile.position.x, projectile.position.y, radius, color)
+                    draw_circle(position.x, position.y, radius, color)
                 }
                 ProjectileKind::Rect {
                     width,
                     height,
                     color,
-                } => draw_rectangle(
-                    projectile.position.x,
-                    projectile.position.y,
-                    width,
-                    height,
-                    color,
-                ),
+                } => draw_rectangle(position.x, position.y, width, height, color),
                 ProjectileKind::Sprite { .. } => {
                     let sprite = projectile.sprite.as_ref().unwrap();
-                    sprite.draw(
-                        projectile.position,
-                        projectile.sprite_draw_angle,
-                        flip_x,
-                        false,
-                    );
+                    sprite.draw(position, projectile.sprite_draw_angle, flip_x, false);
                 }
             }
 
             for particles in &mut projectile.particles {
-                particles.draw(projectile.position, flip_x, false);
+                particles.draw(position, flip_x, false);
             }
         }
     }
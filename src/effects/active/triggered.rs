@@ -13,10 +13,14 @@ use crate::json::OneOrMany;
 use crate::{
     capabilities::NetworkReplicate,
     components::{AnimationParams, AnimationPlayer, PhysicsBody},
-    json, GameWorld, Player,
+    game::SIMULATION_DELTA_TIME,
+    json,
+    math::IsZero,
+    physics::CollisionLayers,
+    GameWorld, Player,
 };
 
-use super::{active_effect_coroutine, AnyEffectParams};
+use super::{instantiate_active_effect, AnyEffectParams};
 
 /// This contains commonly used groups of triggers
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
@@ -152,6 +156,19 @@ pub struct TriggeredEffectParams {
     /// The angle of rotation with which the triggered physics body will spawn.
     #[serde(default)]
     pub spawn_angle: f32,
+    /// The amount of velocity retained, in the opposite direction, when the triggered physics body
+    /// collides with a wall, the ground or a ceiling.
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    pub bouncyness: f32,
+    /// If this is `true` the triggered physics body will stick to the first surface or player
+    /// that it hits, after activation. If it sticks to a player, it will follow that player until
+    /// it is triggered or the player dies.
+    #[serde(default, skip_serializing_if = "json::is_false")]
+    pub is_sticky: bool,
+    /// The layers that the triggered physics body interacts with. If this does not contain
+    /// `players`, it can not be triggered, kicked or stuck to by players.
+    #[serde(default, skip_serializing_if = "CollisionLayers::is_all")]
+    pub collision_mask: CollisionLayers,
 }
 
 impl Default for TriggeredEffectParams {
@@ -170,6 +187,9 @@ impl Default for TriggeredEffectParams {
             should_collide_with_platforms: false,
             is_rotates: false,
             spawn_angle: 0.0,
+            bouncyness: 0.0,
+            is_sticky: false,
+            collision_mask: CollisionLayers::ALL,
         }
     }
 }
@@ -190,7 +210,12 @@ struct TriggeredEffect {
     /// This can be used to trigger the effect immediately, ignoring delay timers.
     /// Also requires `is_triggered` to be set to `true`, for this to work.
     pub should_override_delay: bool,
-    should_collide_with_platforms: bool,
+    is_sticky: bool,
+    /// This is `true` if the effect has stuck to a surface
+    is_stuck: bool,
+    /// This holds a handle to the player the effect has stuck to, if any, as well as the offset
+    /// from the player's position
+    stuck_to: Option<(Handle<Player>, Vec2)>,
     /// This holds a handle to the player that triggered the effect, if applicable.
     triggered_by: Option<Handle<Player>>,
     kick_delay_timer: f32,
@@ -260,6 +285,14 @@ impl TriggeredEffects {
         };
 
         body.velocity = params.velocity;
+        body.bouncyness = params.bouncyness;
+        body.is_colliding_with_platforms = params.should_collide_with_platforms;
+        body.collision_layer = CollisionLayers::HAZARDS;
+        body.collision_mask = params.collision_mask;
+
+        if params.is_rotates {
+            body.set_thrown();
+        }
 
         self.active.push(TriggeredEffect {
             owner,
@@ -279,7 +312,9 @@ impl TriggeredEffects {
             kick_delay_timer: 0.0,
             is_triggered: false,
             should_override_delay: false,
-            should_collide_with_platforms: params.should_collide_with_platforms,
+            is_sticky: params.is_sticky,
+            is_stuck: false,
+            stuck_to: None,
             triggered_by: None,
         })
     }
@@ -337,19 +372,75 @@ impl TriggeredEffects {
         while i < node.active.len() {
             let trigger = &mut node.active[i];
 
-            let dt = get_frame_time();
+            let dt = SIMULATION_DELTA_TIME;
 
             for particles in &mut trigger.particles {
                 particles.update(dt);
             }
 
-            if !trigger.should_collide_with_platforms {
-                trigger.body.descent();
-            }
+            if let Some((player_handle, offset)) = trigger.stuck_to {
+                let position = scene::try_get_node(player_handle)
+                    .filter(|player| !player.is_dead)
+                    .map(|player| player.body.position + offset);
+
+                if let Some(position) = position {
+                    let mut game_world = storage::get_mut::<GameWorld>();
+                    game_world
+                        .collision_world
+                        .set_actor_position(trigger.body.collider, position);
+
+                    trigger.body.position = position;
+                } else {
+                    // The player died, so the effect falls off
+                    trigger.stuck_to = None;
+                    trigger.is_stuck = false;
+                    trigger.body.has_gravity = true;
+                }
+            } else if !trigger.is_stuck {
+                let velocity = trigger.body.velocity;
+
+                trigger.body.update();
+
+                if trigger.is_sticky && trigger.activation_timer >= trigger.activation_delay {
+                    let game_world = storage::get::<GameWorld>();
+                    let collider = trigger.body.collider;
+                    let position = game_world.collision_world.actor_pos(collider);
+
+                    let is_hitting_surface = trigger.body.is_on_ground
+                        || game_world
+                            .collision_world
+                            .collide_check(collider, position + vec2(velocity.x.signum(), 0.0))
+                        || game_world
+                            .collision_world
+                            .collide_check(collider, position - vec2(0.0, 1.0));
+
+                    if is_hitting_surface {
+                        trigger.is_stuck = true;
+                    } else {
+                        let collider = trigger.body.get_collider_rect();
+
+                        // Borrow owner so that it is excluded from the following iteration
+                        let _owner = scene::try_get_node(trigger.owner);
+
+                        for player in scene::find_nodes_by_type::<Player>() {
+                            if !player.is_dead
+                                && trigger.body.can_collide_with(&player.body)
+                                && collider.overlaps(&player.get_collider_rect())
+                            {
+                                let offset = trigger.body.position - player.body.position;
+                                trigger.stuck_to = Some((player.handle(), offset));
+                                trigger.is_stuck = true;
+
+                                break;
+                            }
+                        }
+                    }
 
-            trigger.body.update();
-            if trigger.body.can_rotate {
-                trigger.body.update_throw();
+                    if trigger.is_stuck {
+                        trigger.body.velocity = Vec2::ZERO;
+                        trigger.body.has_gravity = false;
+                    }
+                }
             }
 
             if let Some(timed_trigger) = trigger.timed_trigger {
@@ -395,7 +486,9 @@ impl TriggeredEffects {
                     }
 
                     for player in scene::find_nodes_by_type::<Player>() {
-                        if collider.overlaps(&player.get_collider_rect()) {
+                        if trigger.body.can_collide_with(&player.body)
+                            && collider.overlaps(&player.get_collider_rect())
+                        {
                             if trigger.is_kickable {
                                 if !player.body.is_facing_right
                                     && trigger.body.position.x
@@ -435,7 +528,7 @@ impl TriggeredEffects {
                 for params in trigger.effects.drain(0..) {
                     match params {
                         AnyEffectParams::Active(params) => {
-                            active_effect_coroutine(
+                            instantiate_active_effect(
                                 trigger.owner,
                                 trigger.body.position + trigger.body.size / 2.0,
                                 params,
@@ -477,9 +570,12 @@ impl Node for TriggeredEffects {
     }
 
     fn update(mut node: RefMut<Self>) {
+        let dt = get_frame_time();
+
         for trigger in &mut node.active {
             if let Some(animation_player) = trigger.animation_player.as_mut() {
-                animation_player.update();
+                animation_player.update(dt);
+                animation_player.play_frame_sounds(trigger.body.position);
             }
         }
     }
@@ -488,7 +584,7 @@ impl Node for TriggeredEffects {
         for trigger in &mut node.active {
             let flip_x = trigger.body.velocity.x < 0.0;
 
-            let trigger_center = trigger.body.position + trigger.body.size / 2.0;
+            let trigger_center = trigger.body.get_render_position() + trigger.body.size / 2.0;
 
             if let Some(animation_player) = &trigger.animation_player {
                 animation_player.draw(
@@ -6,8 +6,9 @@ pub mod passive;
 pub use passive::{PassiveEffectInstance, PassiveEffectParams};
 
 pub use active::{
-    active_effect_coroutine, add_active_effect_coroutine, get_active_effect_coroutine,
-    ActiveEffectCoroutine, ActiveEffectKind, ActiveEffectParams, Projectiles,
+    add_active_effect_coroutine, clear_active_effects, get_active_effect_coroutine,
+    instantiate_active_effect, update_active_effects, ActiveEffectCoroutine, ActiveEffectKind,
+    ActiveEffectParams, AreaEffects, Deployables, GrapplingHook, Projectiles,
     TriggeredEffectTrigger, TriggeredEffects,
 };
 
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use macroquad::experimental::{coroutines::Coroutine, scene::Handle};
+use macroquad::experimental::scene::Handle;
 
 use crate::{Player, PlayerEventParams};
 
@@ -30,6 +30,8 @@ pub fn get_passive_effect_coroutine(id: &str) -> &PassiveEffectCoroutine {
 /// and access the instance in the `passive_effects` map, using the `instance_id` parameter.
 /// Likewise, the `EquippedItem` the effect belongs to, can be accessed in the player's
 /// `equipped_items` map, by using `item_id`, if one is provided.
+/// Calls are queued when the event occurs and run later in the same simulation tick, by
+/// `Player::apply_pending_interactions`, so the player is never borrowed when this is called.
 ///
 /// Any implementations must also be added to `init_passive_effects` function.
 pub type PassiveEffectCoroutine = fn(
@@ -37,4 +39,4 @@ pub type PassiveEffectCoroutine = fn(
     item_id: Option<&str>,
     player_handle: Handle<Player>,
     event_params: PlayerEventParams,
-) -> Coroutine;
+);
@@ -1,7 +1,4 @@
-use macroquad::{
-    experimental::{coroutines::Coroutine, scene::Handle},
-    prelude::*,
-};
+use macroquad::{experimental::scene::Handle, prelude::*};
 
 use serde::{Deserialize, Serialize};
 
@@ -11,9 +8,7 @@ mod turtle_shell;
 
 pub use coroutines::PassiveEffectCoroutine;
 
-use crate::effects::passive::coroutines::{
-    add_passive_effect_coroutine, get_passive_effect_coroutine,
-};
+use crate::effects::passive::coroutines::add_passive_effect_coroutine;
 use crate::json::OneOrMany;
 use crate::{player::PlayerEvent, ParticleEmitters, Player, PlayerEventParams};
 
@@ -112,7 +107,7 @@ impl PassiveEffectInstance {
         player_handle: Handle<Player>,
         position: Vec2,
         params: PlayerEventParams,
-    ) -> Option<Coroutine> {
+    ) {
         if self.events.contains(&PlayerEvent::from(&params)) {
             let coroutine_id = self.coroutine_id.as_ref().unwrap_or(&self.id);
 
@@ -125,13 +120,14 @@ impl PassiveEffectInstance {
                 particle_emitters.spawn(particle_effect_id, position);
             }
 
-            let coroutine = get_passive_effect_coroutine(coroutine_id);
-            let res = coroutine(&self.id, self.item_id.as_deref(), player_handle, params);
-
-            return Some(res);
+            Player::on_passive_effect_event(
+                coroutine_id,
+                &self.id,
+                self.item_id.as_deref(),
+                player_handle,
+                params,
+            );
         }
-
-        None
     }
 
     pub fn default_events() -> Vec<PlayerEvent> {
@@ -192,6 +192,12 @@ impl From<serde_json::Error> for Error {
     }
 }
 
+impl From<rhai::ParseError> for Error {
+    fn from(err: rhai::ParseError) -> Self {
+        Error::new_message(ErrorKind::Parsing, &err.to_string())
+    }
+}
+
 impl From<crate::json::Error> for Error {
     fn from(err: crate::json::Error) -> Self {
         Error::new(ErrorKind::Parsing, err)
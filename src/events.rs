@@ -22,6 +22,11 @@ pub fn iter_events() -> ApplicationEventIterator {
 pub enum ApplicationEvent {
     /// Reload resources
     ReloadResources,
+    /// Restart the current local match, with the same map and players
+    RestartMatch,
+    /// Start a new local match on the map with the given index in `Resources::maps`, with the
+    /// same players
+    NextMap(usize),
     /// Exit to main menu
     MainMenu,
     /// Quit to desktop
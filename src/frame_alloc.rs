@@ -0,0 +1,274 @@
+//! The frame allocator hands out scratch buffers for transient data that only lives for part of a
+//! frame, like the vertices of a trail mesh or the positions of the nameplates of the world UI.
+//! Buffers are kept in a pool, per element type, when they are released, so that they can be
+//! reused, with the capacity that they have grown to, in stead of being freed and allocated again
+//! every frame. Once the buffers have grown to the peak size of the data they hold, drawing does
+//! not allocate at all, which removes the heap churn that shows up as spikes in frame times.
+//!
+//! This is a pool of typed buffers, not a bump allocator that is reset every frame, as the
+//! collections of the standard library can not allocate from an arena on stable Rust. The effect
+//! on the heap is the same, as long as buffers are released before the end of the frame.
+//!
+//! A buffer is taken with `frame_vec`, which returns a `FrameVec` that is returned to the pool
+//! when it is dropped. Buffers that have to be moved into something that takes a `Vec`, like a
+//! `Mesh`, can be taken out with `FrameVec::into_vec` and returned with `recycle`, when done.
+//!
+//! `end_frame` is called once per frame, from the main loop. It frees the buffers of types that
+//! have not been used for `TRIM_FRAME_CNT` frames, so that a one-off spike, like a screen full of
+//! trails, does not hold on to memory for the rest of the game.
+//!
+//! The pool is not thread safe, so buffers are only available on the main thread, and a
+//! `FrameVec` can not be sent to the workers of `jobs`. Only `'static` element types can be
+//! pooled, so lists of borrows, like the particle batches that are handed to `jobs`, are still
+//! allocated every frame.
+//!
+//! The current users are trails, the world UI and the scoreboard. The particles of particle
+//! emitters keep their own buffers, which are reused between updates, and the tile meshes of maps
+//! are built once, when a chunk changes, so neither of them use the frame allocator.
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    marker::PhantomData,
+    mem,
+    ops::{Deref, DerefMut},
+};
+
+/// The amount of frames that the buffers of a type can go unused before they are freed
+const TRIM_FRAME_CNT: u32 = 600;
+
+struct BufferPool {
+    /// The released buffers of the type of the pool, as a `Vec<Vec<T>>`
+    buffers: Box<dyn Any>,
+    /// The total capacity, in bytes, of the released buffers
+    capacity: usize,
+    unused_frame_cnt: u32,
+}
+
+#[derive(Default)]
+struct FrameAllocator {
+    pools: HashMap<TypeId, BufferPool>,
+    /// The amount of buffers that were taken during the current frame
+    taken_cnt: usize,
+    last_frame_stats: FrameAllocatorStats,
+}
+
+impl FrameAllocator {
+    /// Returns an empty buffer, with the capacity of a buffer that was released earlier, if there
+    /// is one. The pool of the type counts as used, even if all of its buffers are taken, so that
+    /// it is not trimmed while its buffers are in use.
+    fn take<T: 'static>(&mut self) -> Vec<T> {
+        self.taken_cnt += 1;
+
+        let pool = match self.pools.get_mut(&TypeId::of::<T>()) {
+            Some(pool) => pool,
+            None => return Vec::new(),
+        };
+
+        pool.unused_frame_cnt = 0;
+
+        let vec = pool
+            .buffers
+            .downcast_mut::<Vec<Vec<T>>>()
+            .unwrap()
+            .pop()
+            .unwrap_or_default();
+
+        pool.capacity -= vec.capacity() * mem::size_of::<T>();
+
+        vec
+    }
+
+    fn recycle<T: 'static>(&mut self, mut vec: Vec<T>) {
+        if vec.capacity() == 0 {
+            return;
+        }
+
+        vec.clear();
+
+        let pool = self
+            .pools
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| BufferPool {
+                buffers: Box::new(Vec::<Vec<T>>::new()),
+                capacity: 0,
+                unused_frame_cnt: 0,
+            });
+
+        pool.capacity += vec.capacity() * mem::size_of::<T>();
+
+        pool.buffers
+            .downcast_mut::<Vec<Vec<T>>>()
+            .unwrap()
+            .push(vec);
+    }
+
+    fn end_frame(&mut self) {
+        self.pools.retain(|_, pool| {
+            pool.unused_frame_cnt += 1;
+            pool.unused_frame_cnt < TRIM_FRAME_CNT
+        });
+
+        self.last_frame_stats = FrameAllocatorStats {
+            taken_cnt: self.taken_cnt,
+            pooled_capacity: self.pools.values().map(|pool| pool.capacity).sum(),
+        };
+
+        self.taken_cnt = 0;
+    }
+}
+
+static mut FRAME_ALLOCATOR: Option<FrameAllocator> = None;
+
+unsafe fn get_frame_allocator() -> &'static mut FrameAllocator {
+    FRAME_ALLOCATOR.get_or_insert_with(FrameAllocator::default)
+}
+
+/// The stats of the frame allocator for the last frame
+#[derive(Debug, Default, Copy, Clone)]
+pub struct FrameAllocatorStats {
+    /// The amount of buffers that were taken during the frame
+    pub taken_cnt: usize,
+    /// The total capacity, in bytes, of all pooled buffers
+    pub pooled_capacity: usize,
+}
+
+/// A scratch buffer that is returned to the pool of the frame allocator when it is dropped. It
+/// can be used like a `Vec`, which it dereferences to.
+pub struct FrameVec<T: 'static> {
+    vec: Vec<T>,
+    /// This makes the buffer `!Send`, as the pool is not thread safe
+    _marker: PhantomData<*const ()>,
+}
+
+impl<T: 'static> FrameVec<T> {
+    /// This takes the buffer out of the frame allocator. It will not be returned to the pool
+    /// unless it is passed to `recycle`.
+    pub fn into_vec(mut self) -> Vec<T> {
+        mem::take(&mut self.vec)
+    }
+}
+
+impl<T: 'static> Deref for FrameVec<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.vec
+    }
+}
+
+impl<T: 'static> DerefMut for FrameVec<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.vec
+    }
+}
+
+impl<T: 'static> Drop for FrameVec<T> {
+    fn drop(&mut self) {
+        recycle(mem::take(&mut self.vec));
+    }
+}
+
+/// Returns an empty scratch buffer, with the capacity of a buffer that was released earlier, if
+/// there is one
+pub fn frame_vec<T: 'static>() -> FrameVec<T> {
+    FrameVec {
+        vec: unsafe { get_frame_allocator() }.take(),
+        _marker: PhantomData,
+    }
+}
+
+/// This returns `vec` to the pool of the frame allocator, so that its buffer can be reused. It
+/// should be used for buffers that were taken out of a `FrameVec` with `into_vec`.
+pub fn recycle<T: 'static>(vec: Vec<T>) {
+    unsafe { get_frame_allocator() }.recycle(vec);
+}
+
+/// This ends the current frame of the frame allocator. It should be called once per frame, from
+/// the main loop.
+pub fn end_frame() {
+    unsafe { get_frame_allocator() }.end_frame();
+}
+
+pub fn get_last_frame_stats() -> FrameAllocatorStats {
+    unsafe { get_frame_allocator() }.last_frame_stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffer_reuse() {
+        let mut allocator = FrameAllocator::default();
+
+        let mut vec = allocator.take::<u32>();
+        assert_eq!(vec.capacity(), 0);
+
+        vec.extend(0..100);
+        let capacity = vec.capacity();
+        let ptr = vec.as_ptr();
+
+        allocator.recycle(vec);
+
+        // Buffers are pooled by element type
+        assert_eq!(allocator.take::<u64>().capacity(), 0);
+
+        let vec = allocator.take::<u32>();
+        assert!(vec.is_empty());
+        assert_eq!(vec.capacity(), capacity);
+        assert_eq!(vec.as_ptr(), ptr);
+
+        // Empty buffers are not pooled
+        allocator.recycle(Vec::<u32>::new());
+        assert_eq!(allocator.take::<u32>().capacity(), 0);
+
+        allocator.recycle(vec);
+        allocator.end_frame();
+
+        let stats = allocator.last_frame_stats;
+        assert_eq!(stats.taken_cnt, 4);
+        assert_eq!(stats.pooled_capacity, capacity * mem::size_of::<u32>());
+    }
+
+    #[test]
+    fn test_buffer_trimming() {
+        let mut allocator = FrameAllocator::default();
+
+        let mut vec = allocator.take::<u8>();
+        vec.push(1);
+        allocator.recycle(vec);
+
+        let mut vec = allocator.take::<u16>();
+        vec.push(1);
+        allocator.recycle(vec);
+
+        let mut vec = allocator.take::<u32>();
+        vec.push(1);
+        allocator.recycle(vec);
+
+        // The only buffer of this pool is held, so the pool is empty when it is taken from below
+        let held = allocator.take::<u32>();
+
+        for _ in 0..TRIM_FRAME_CNT - 1 {
+            // Taking a buffer resets the unused frame count of its pool
+            let vec = allocator.take::<u16>();
+            allocator.recycle(vec);
+
+            allocator.take::<u32>();
+
+            allocator.end_frame();
+        }
+
+        assert!(allocator.pools.contains_key(&TypeId::of::<u8>()));
+
+        allocator.end_frame();
+
+        assert!(!allocator.pools.contains_key(&TypeId::of::<u8>()));
+        assert!(allocator.pools.contains_key(&TypeId::of::<u16>()));
+        assert!(allocator.pools.contains_key(&TypeId::of::<u32>()));
+
+        allocator.recycle(held);
+        assert_eq!(allocator.last_frame_stats.taken_cnt, 0);
+    }
+}
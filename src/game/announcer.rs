@@ -0,0 +1,242 @@
+//! The announcer plays voice lines, on the `Voice` bus, in reaction to moments in a match, like a
+//! player scoring a double kill. Which lines are played for which moments is decided by the rules
+//! in the `announcer` resource file, each of which references a sound effect by its id.
+//!
+//! Only one line is played at a time. A line that is triggered while another is playing will be
+//! queued, if it has a higher priority than the playing line, or dropped, if it does not. Every
+//! rule also has a cooldown, so that the same line is not repeated too often.
+
+use std::collections::HashMap;
+
+use macroquad::{
+    experimental::{
+        collections::storage,
+        scene::{self, Node, RefMut},
+    },
+    prelude::*,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    audio::{duck_music, play_sound_effect, AudioBus},
+    game::GameEvent,
+    Player, Resources,
+};
+
+/// The moments in a match that the announcer can react to
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnnouncerTrigger {
+    RoundStart,
+    DoubleKill,
+    LastPlayerStanding,
+    Overtime,
+    RoundEnd,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnnouncerRule {
+    pub trigger: AnnouncerTrigger,
+    /// The id of the sound effect holding the voice line
+    #[serde(rename = "sound_effect")]
+    pub sound_effect_id: String,
+    /// If several rules are triggered at once, or while a line is playing, the one with the
+    /// highest priority is played
+    #[serde(default)]
+    pub priority: u32,
+    /// The minimum time between two plays of the line, in seconds
+    #[serde(default = "AnnouncerRule::default_cooldown")]
+    pub cooldown: f32,
+    /// The duration of the line, in seconds. The audio backend can not tell when a sound has
+    /// ended, so this is used to keep lines from overlapping.
+    #[serde(default = "AnnouncerRule::default_duration")]
+    pub duration: f32,
+}
+
+impl AnnouncerRule {
+    pub fn default_cooldown() -> f32 {
+        10.0
+    }
+
+    pub fn default_duration() -> f32 {
+        1.5
+    }
+}
+
+struct QueuedLine {
+    rule: usize,
+    timer: f32,
+}
+
+pub struct Announcer {
+    rules: Vec<AnnouncerRule>,
+    /// The remaining cooldown of each rule
+    cooldowns: Vec<f32>,
+    /// The rule of the line that is currently playing and the time remaining of it
+    current: Option<(usize, f32)>,
+    queued: Option<QueuedLine>,
+    /// The time since the last kill of each player, by player id, used to detect double kills
+    kill_timers: HashMap<u8, f32>,
+}
+
+impl Announcer {
+    pub const LISTENER_ID: &'static str = "announcer";
+
+    /// The maximum time between two kills by the same player, for them to count as a double kill
+    const DOUBLE_KILL_WINDOW: f32 = 2.5;
+    /// The maximum time a line will wait in the queue, before it is dropped
+    const QUEUE_TIMEOUT: f32 = 1.0;
+    const MUSIC_DUCK_AMOUNT: f32 = 0.3;
+
+    pub fn new() -> Self {
+        let rules = storage::get::<Resources>().announcer_rules.clone();
+        let cooldowns = vec![0.0; rules.len()];
+
+        Announcer {
+            rules,
+            cooldowns,
+            current: None,
+            queued: None,
+            kill_timers: HashMap::new(),
+        }
+    }
+
+    pub fn on_game_event(event: &GameEvent) {
+        if let Some(mut node) = scene::find_node_by_type::<Announcer>() {
+            node.handle_event(event);
+        }
+    }
+
+    fn handle_event(&mut self, event: &GameEvent) {
+        if let GameEvent::RoundEnded { .. } = event {
+            self.announce(AnnouncerTrigger::RoundEnd);
+        }
+
+        if let GameEvent::PlayerKilled {
+            player_id,
+            killed_by,
+            ..
+        } = *event
+        {
+            self.kill_timers.remove(&player_id);
+
+            if let Some(killed_by) = killed_by {
+                if self.kill_timers.insert(killed_by, 0.0).is_some() {
+                    self.announce(AnnouncerTrigger::DoubleKill);
+                }
+            }
+
+            let alive_cnt = scene::find_nodes_by_type::<Player>()
+                .filter(|player| player.id != player_id && !player.is_dead)
+                .count();
+
+            if alive_cnt == 1 {
+                self.announce(AnnouncerTrigger::LastPlayerStanding);
+            }
+        }
+    }
+
+    /// This plays the line of the highest priority rule for `trigger`, that is not on cooldown,
+    /// if any. If another line is playing, the line is queued or dropped, depending on priority.
+    pub fn announce(&mut self, trigger: AnnouncerTrigger) {
+        let rule = self
+            .rules
+            .iter()
+            .enumerate()
+            .filter(|(i, rule)| rule.trigger == trigger && self.cooldowns[*i] <= 0.0)
+            .max_by_key(|(_, rule)| rule.priority)
+            .map(|(i, _)| i);
+
+        if let Some(rule) = rule {
+            let priority = self.rules[rule].priority;
+
+            match self.current {
+                Some((current, _)) => {
+                    let is_queued = priority > self.rules[current].priority
+                        && self
+                            .queued
+                            .as_ref()
+                            .map(|queued| priority > self.rules[queued.rule].priority)
+                            .unwrap_or(true);
+
+                    if is_queued {
+                        self.queued = Some(QueuedLine {
+                            rule,
+                            timer: Self::QUEUE_TIMEOUT,
+                        });
+                    }
+                }
+                None => self.play(rule),
+            }
+        }
+    }
+
+    fn play(&mut self, rule: usize) {
+        let rule_params = &self.rules[rule];
+
+        {
+            let resources = storage::get::<Resources>();
+
+            match resources.sounds.get(&rule_params.sound_effect_id) {
+                Some(sound) => play_sound_effect(sound, AudioBus::Voice, 1.0),
+                None => {
+                    println!(
+                        "WARNING: Announcer: Invalid sound effect id '{}'",
+                        &rule_params.sound_effect_id
+                    );
+
+                    return;
+                }
+            }
+        }
+
+        duck_music(Self::MUSIC_DUCK_AMOUNT, rule_params.duration);
+
+        self.cooldowns[rule] = rule_params.cooldown;
+        self.current = Some((rule, rule_params.duration));
+    }
+}
+
+impl Node for Announcer {
+    fn ready(mut node: RefMut<Self>) {
+        node.announce(AnnouncerTrigger::RoundStart);
+    }
+
+    fn update(mut node: RefMut<Self>) {
+        let dt = get_frame_time();
+
+        for cooldown in &mut node.cooldowns {
+            *cooldown -= dt;
+        }
+
+        for timer in node.kill_timers.values_mut() {
+            *timer += dt;
+        }
+
+        node.kill_timers
+            .retain(|_, timer| *timer < Self::DOUBLE_KILL_WINDOW);
+
+        if let Some((_, timer)) = &mut node.current {
+            *timer -= dt;
+
+            if *timer <= 0.0 {
+                node.current = None;
+            }
+        }
+
+        if let Some(queued) = &mut node.queued {
+            queued.timer -= dt;
+
+            if queued.timer <= 0.0 {
+                node.queued = None;
+            }
+        }
+
+        if node.current.is_none() {
+            if let Some(queued) = node.queued.take() {
+                node.play(queued.rule);
+            }
+        }
+    }
+}
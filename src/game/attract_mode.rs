@@ -0,0 +1,114 @@
+//! The attract mode is started when the title screen has been left idle for a while. It shows a
+//! local match that is played back from the input recording bundled with the assets, if there is
+//! one, or played by bots on a random map, and returns to the main menu on any input.
+//!
+//! No recording is bundled with the base assets, so the bot match is what is normally shown, and
+//! this is intended, as every attract mode session is a different match. A recording can be added
+//! by mods or by packagers, at `ATTRACT_MODE_RECORDING_PATH`, to show a specific match.
+
+use macroquad::{
+    color,
+    experimental::scene::{self, Node, RefMut},
+    prelude::*,
+};
+
+use crate::{
+    exit_to_main_menu,
+    game::MatchStats,
+    gui,
+    localization::tr,
+    text::{draw_aligned_text, HorizontalAlignment, VerticalAlignment},
+};
+
+/// The time, in seconds, that the title screen must be idle before the attract mode is started
+pub const ATTRACT_MODE_DELAY: f32 = 30.0;
+
+/// The path of the input recording that is played back in attract mode, relative to the assets
+/// directory. This is not bundled with the base assets, and if it is missing, a bot match is
+/// shown in stead.
+pub const ATTRACT_MODE_RECORDING_PATH: &str = "recordings/attract_mode.json";
+
+pub struct AttractMode {
+    timer: f32,
+    /// This is set when the return to the main menu has been requested, so that it is only
+    /// requested once
+    is_exiting: bool,
+}
+
+impl AttractMode {
+    /// The time after which the attract mode returns to the main menu, on its own
+    const DURATION: f32 = 90.0;
+
+    const FONT_SIZE: u16 = 28;
+    /// The distance of the prompt from the bottom of the screen
+    const PROMPT_MARGIN: f32 = 48.0;
+    /// The time, in seconds, of a single fade in and out of the prompt
+    const PROMPT_BLINK_INTERVAL: f32 = 1.5;
+
+    pub fn new() -> Self {
+        AttractMode {
+            timer: 0.0,
+            is_exiting: false,
+        }
+    }
+
+    fn is_match_over() -> bool {
+        scene::find_node_by_type::<MatchStats>()
+            .map(|match_stats| match_stats.is_match_over())
+            .unwrap_or(false)
+    }
+}
+
+impl Default for AttractMode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Node for AttractMode {
+    fn update(mut node: RefMut<Self>) {
+        node.timer += get_frame_time();
+
+        if node.is_exiting {
+            return;
+        }
+
+        // The match is paused while the game menu is open, so attract mode is ended if anything
+        // opens it
+        let should_exit = gui::is_any_input_pressed()
+            || node.timer >= Self::DURATION
+            || Self::is_match_over()
+            || gui::is_game_menu_open();
+
+        if should_exit {
+            gui::close_game_menu();
+
+            node.is_exiting = true;
+            exit_to_main_menu();
+        }
+    }
+
+    fn draw(node: RefMut<Self>) {
+        let phase = (node.timer / Self::PROMPT_BLINK_INTERVAL).fract();
+
+        let mut color = color::WHITE;
+        color.a = 1.0 - (phase * 2.0 - 1.0).abs();
+
+        push_camera_state();
+        set_default_camera();
+
+        draw_aligned_text(
+            &tr("attract_mode.press_any_button"),
+            vec2(screen_width() / 2.0, screen_height() - Self::PROMPT_MARGIN),
+            HorizontalAlignment::Center,
+            VerticalAlignment::Center,
+            TextParams {
+                font_size: Self::FONT_SIZE,
+                color,
+                ..Default::default()
+            },
+        );
+
+        pop_camera_state();
+    }
+}
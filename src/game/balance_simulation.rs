@@ -0,0 +1,278 @@
+//! The balance simulation runs a number of matches between bots, as fast as possible, and writes
+//! aggregate stats of all the matches to a JSON file, so that the effect of balance changes can be
+//! evaluated without playing. It is started, in stead of the main menu, if the path of a file with
+//! the parameters of the simulation is given by the `FISHFIGHT_SIMULATE` env var, and the game
+//! quits when it is done.
+//!
+//! Macroquad always opens a window, so the simulation is not truly headless, but it is decoupled
+//! from rendering: `TICKS_PER_FRAME` simulation ticks are run for every frame that is drawn, and
+//! all audio is muted. This gives the same results as running one tick per frame, as everything
+//! that affects the outcome of a match, including damage, respawns, effect delays and animation
+//! frames, is advanced by the tick, and nothing by the frame.
+
+use std::{collections::HashMap, fs};
+
+use macroquad::{
+    experimental::{collections::storage, scene},
+    prelude::*,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    formaterr,
+    game::{
+        add_game_event_listener, clear_game_events, create_game_scene, remove_game_event_listener,
+        run_simulation_tick, GameEvent, MatchStats, Ruleset, SIMULATION_DELTA_TIME,
+    },
+    Config, Resources, Result,
+};
+
+pub const SIMULATE_ENV_VAR: &str = "FISHFIGHT_SIMULATE";
+
+/// The amount of simulation ticks that are run for every frame that is drawn
+const TICKS_PER_FRAME: u32 = 120;
+
+/// The key, in the weapon kill counts, of kills that were made without a weapon, or that can not
+/// be attributed to another player, like falling into a hazard
+const NO_WEAPON_KEY: &str = "none";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BalanceSimulationParams {
+    /// The amount of matches to simulate
+    pub match_cnt: u32,
+    /// The ruleset of the matches. If this is not specified, the ruleset in resources is used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ruleset: Option<Ruleset>,
+    /// The names of the maps to pick from, at random, for every match. If this is empty, all maps
+    /// are used.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub maps: Vec<String>,
+    /// The ids of the player characters to pick from, at random, for every player. If this is
+    /// empty, all player characters are used.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub player_characters: Vec<String>,
+    /// The maximum duration, in simulated seconds, of a match. Matches that are not decided
+    /// within this are stopped and not counted towards the round length.
+    #[serde(default = "BalanceSimulationParams::default_time_limit")]
+    pub time_limit: f32,
+    /// The seed of the first match. Every match after that uses the next seed.
+    #[serde(default)]
+    pub seed: u64,
+    /// The path that the results are written to
+    pub output_path: String,
+}
+
+impl BalanceSimulationParams {
+    pub fn default_time_limit() -> f32 {
+        300.0
+    }
+
+    pub fn load(path: &str) -> Result<Self> {
+        let file_contents = fs::read_to_string(path)?;
+        let res = serde_json::from_str(&file_contents)?;
+        Ok(res)
+    }
+}
+
+/// The aggregate stats of all the matches of a balance simulation
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct BalanceSimulationResults {
+    pub match_cnt: u32,
+    /// The amount of matches that were decided within the time limit
+    pub decided_match_cnt: u32,
+    /// The average duration, in simulated seconds, of the matches that were decided
+    pub average_round_length: f32,
+    pub total_kills: u32,
+    /// The amount of kills made with each weapon, by item id
+    pub kills_by_weapon: HashMap<String, u32>,
+    /// The share of all kills made with each weapon, by item id, between `0.0` and `1.0`
+    pub weapon_kill_shares: HashMap<String, f32>,
+    /// The amount of matches won by each player character, by character id
+    pub wins_by_character: HashMap<String, u32>,
+}
+
+/// The kills of the match that is currently simulated, by weapon id
+static mut KILLS_BY_WEAPON: Option<HashMap<String, u32>> = None;
+
+unsafe fn get_kills_by_weapon() -> &'static mut HashMap<String, u32> {
+    KILLS_BY_WEAPON.get_or_insert(HashMap::new())
+}
+
+const LISTENER_ID: &str = "balance_simulation";
+
+fn on_game_event(event: &GameEvent) {
+    if let GameEvent::PlayerKilled {
+        player_id,
+        killed_by,
+        ref weapon_id,
+        ..
+    } = *event
+    {
+        let weapon_id = match (killed_by, weapon_id) {
+            (Some(killed_by), Some(weapon_id)) if killed_by != player_id => weapon_id.clone(),
+            _ => NO_WEAPON_KEY.to_string(),
+        };
+
+        *unsafe { get_kills_by_weapon() }
+            .entry(weapon_id)
+            .or_insert(0) += 1;
+    }
+}
+
+fn pick<T: Clone>(options: &[T]) -> T {
+    options[rand::gen_range(0, options.len())].clone()
+}
+
+/// This runs the balance simulation that is described by `params` and writes the results to the
+/// output path of `params`
+pub async fn run_balance_simulation(params: &BalanceSimulationParams) -> Result<()> {
+    let (maps, player_characters) = {
+        let mut resources = storage::get_mut::<Resources>();
+
+        if let Some(ruleset) = &params.ruleset {
+            resources.ruleset = ruleset.clone();
+        }
+
+        let mut maps = Vec::new();
+        for name in &params.maps {
+            let map = resources
+                .maps
+                .iter()
+                .find(|map| map.meta.name == *name)
+                .cloned()
+                .ok_or_else(|| formaterr!("Balance simulation: Invalid map '{}'", name))?;
+
+            maps.push(map);
+        }
+
+        let mut player_characters = Vec::new();
+        for id in &params.player_characters {
+            let character = resources
+                .player_characters
+                .iter()
+                .find(|character| character.id == *id)
+                .cloned()
+                .ok_or_else(|| {
+                    formaterr!("Balance simulation: Invalid player character '{}'", id)
+                })?;
+
+            player_characters.push(character);
+        }
+
+        if maps.is_empty() {
+            maps = resources.maps.clone();
+        }
+
+        if player_characters.is_empty() {
+            player_characters = resources.player_characters.clone();
+        }
+
+        (maps, player_characters)
+    };
+
+    if maps.is_empty() || player_characters.is_empty() {
+        return Err(formaterr!(
+            "Balance simulation: There must be at least one map and one player character"
+        ));
+    }
+
+    storage::get_mut::<Config>().volume.master = 0.0;
+
+    add_game_event_listener(LISTENER_ID, on_game_event);
+
+    let mut results = BalanceSimulationResults {
+        match_cnt: params.match_cnt,
+        ..Default::default()
+    };
+
+    let mut total_round_length = 0.0;
+
+    let max_tick_cnt = (params.time_limit / SIMULATION_DELTA_TIME) as u32;
+
+    for i in 0..params.match_cnt {
+        rand::srand(params.seed + i as u64);
+
+        let map = pick(&maps);
+        let characters = vec![pick(&player_characters), pick(&player_characters)];
+
+        unsafe { get_kills_by_weapon() }.clear();
+
+        let players = create_game_scene(map.map.clone(), characters.clone(), true);
+
+        for player in &players {
+            scene::get_node(*player).set_ai_enabled(true);
+        }
+
+        // The scene is readied when the first frame is drawn
+        next_frame().await;
+
+        let mut tick_cnt = 0;
+        let mut winner = None;
+
+        while winner.is_none() && tick_cnt < max_tick_cnt {
+            for _ in 0..TICKS_PER_FRAME {
+                run_simulation_tick();
+
+                tick_cnt += 1;
+
+                winner = scene::find_node_by_type::<MatchStats>()
+                    .and_then(|match_stats| match_stats.get_winner());
+
+                if winner.is_some() || tick_cnt >= max_tick_cnt {
+                    break;
+                }
+            }
+
+            next_frame().await;
+        }
+
+        if let Some(winner) = winner {
+            results.decided_match_cnt += 1;
+            total_round_length += tick_cnt as f32 * SIMULATION_DELTA_TIME;
+
+            let character_id = characters[winner as usize].id.clone();
+            *results.wins_by_character.entry(character_id).or_insert(0) += 1;
+        }
+
+        for (weapon_id, kill_cnt) in unsafe { get_kills_by_weapon() }.drain() {
+            results.total_kills += kill_cnt;
+            *results.kills_by_weapon.entry(weapon_id).or_insert(0) += kill_cnt;
+        }
+
+        println!(
+            "Balance simulation: Match {}/{} on '{}' {} after {:.1} seconds",
+            i + 1,
+            params.match_cnt,
+            &map.meta.name,
+            if winner.is_some() {
+                "was decided"
+            } else {
+                "timed out"
+            },
+            tick_cnt as f32 * SIMULATION_DELTA_TIME,
+        );
+
+        scene::clear();
+        clear_game_events();
+    }
+
+    remove_game_event_listener(LISTENER_ID);
+
+    if results.decided_match_cnt > 0 {
+        results.average_round_length = total_round_length / results.decided_match_cnt as f32;
+    }
+
+    if results.total_kills > 0 {
+        results.weapon_kill_shares = results
+            .kills_by_weapon
+            .iter()
+            .map(|(id, kill_cnt)| (id.clone(), *kill_cnt as f32 / results.total_kills as f32))
+            .collect();
+    }
+
+    let json = serde_json::to_string_pretty(&results)?;
+    fs::write(&params.output_path, json)?;
+
+    Ok(())
+}
@@ -1,10 +1,12 @@
 use macroquad::{
-    experimental::scene::{self, RefMut},
+    experimental::{
+        collections::storage,
+        scene::{self, RefMut},
+    },
     prelude::*,
-    rand::gen_range,
 };
 
-use crate::{noise::NoiseGenerator, Player};
+use crate::{cosmetic_rand, game::SIMULATION_DELTA_TIME, noise::NoiseGenerator, Config, Player};
 
 struct Shake {
     direction: (f32, f32),
@@ -27,8 +29,14 @@ pub struct GameCamera {
     bounds: Rect,
     follow_buffer: Vec<(Vec2, f32)>,
     shake: Vec<Shake>,
+    /// The trauma of the camera, between `0.0` and `1.0`. The camera shakes with a magnitude of
+    /// the square of its trauma, so that small amounts of trauma are barely noticeable, while
+    /// the trauma decays linearly over time.
+    trauma: f32,
     noisegen: NoiseGenerator,
     noisegen_position: f32,
+    /// The camera that was set in the last update
+    macroquad_camera: Camera2D,
 
     pub manual: Option<(Vec2, f32)>,
 }
@@ -36,20 +44,80 @@ pub struct GameCamera {
 impl GameCamera {
     const BUFFER_CAPACITY: usize = 20;
 
+    /// The amount of trauma removed per second
+    const TRAUMA_DECAY: f32 = 1.2;
+    /// The offset and rotation, in degrees, of the camera at full trauma
+    const MAX_TRAUMA_OFFSET: f32 = 24.0;
+    const MAX_TRAUMA_ROTATION: f32 = 2.0;
+    const TRAUMA_FREQUENCY: f32 = 0.6;
+
+    /// The margin that is added to the view rect, on every side, so that rotated sprites, which
+    /// may extend beyond the bounds that they are culled by, do not pop in at the edges
+    const CULLING_MARGIN: f32 = 32.0;
+
     pub fn new(bounds: Rect) -> GameCamera {
         GameCamera {
             bounds,
             follow_buffer: vec![],
             shake: vec![],
+            trauma: 0.0,
             manual: None,
             noisegen: NoiseGenerator::new(5),
             noisegen_position: 5.0,
+            macroquad_camera: Camera2D::default(),
+        }
+    }
+
+    /// Returns the area of the world that is in view, in world space, grown by a margin. If the
+    /// camera is rotated, this is the bounding box of the rotated view.
+    pub fn get_view_rect(&self) -> Rect {
+        let camera = &self.macroquad_camera;
+
+        if camera.zoom.x == 0.0 || camera.zoom.y == 0.0 {
+            return Rect::new(0.0, 0.0, 0.0, 0.0);
         }
+
+        let size = vec2(2.0 / camera.zoom.x.abs(), 2.0 / camera.zoom.y.abs());
+
+        let (sin, cos) = camera.rotation.to_radians().sin_cos();
+        let size = vec2(
+            size.x * cos.abs() + size.y * sin.abs(),
+            size.x * sin.abs() + size.y * cos.abs(),
+        ) + Vec2::splat(Self::CULLING_MARGIN * 2.0);
+
+        let position = camera.target - size / 2.0;
+
+        Rect::new(position.x, position.y, size.x, size.y)
+    }
+
+    /// Returns `true` if any part of `rect`, in world space, is in view of the game camera. This
+    /// is used to skip drawing things that are off screen, and it will always return `true` if
+    /// there is no game camera, like in the editor.
+    pub fn is_in_view(rect: &Rect) -> bool {
+        scene::find_node_by_type::<GameCamera>()
+            .map(|camera| camera.get_view_rect().overlaps(rect))
+            .unwrap_or(true)
     }
 }
 
 #[allow(dead_code)]
 impl GameCamera {
+    /// This adds `amount` of trauma to the camera, which should be between `0.0` and `1.0`. This
+    /// is the preferred way of shaking the camera, for things like explosions, as the shake of
+    /// several sources will not add up beyond full trauma.
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+    }
+
+    pub fn get_trauma(&self) -> f32 {
+        self.trauma
+    }
+
+    /// Returns the camera that the scene is currently drawn with
+    pub fn get_macroquad_camera(&self) -> Camera2D {
+        self.macroquad_camera
+    }
+
     pub fn shake_noise(&mut self, magnitude: f32, length: i32, frequency: f32) {
         self.shake.push(Shake {
             direction: (1.0, 1.0),
@@ -57,7 +125,7 @@ impl GameCamera {
             magnitude,
             length: length as f32,
             age: 0.0,
-            random_offset: rand::gen_range(1.0, 100.0),
+            random_offset: cosmetic_rand::gen_range(1.0, 100.0),
             frequency,
         });
     }
@@ -75,7 +143,7 @@ impl GameCamera {
             magnitude,
             length: length as f32,
             age: 0.0,
-            random_offset: rand::gen_range(1.0, 100.0),
+            random_offset: cosmetic_rand::gen_range(1.0, 100.0),
             frequency,
         });
     }
@@ -96,7 +164,7 @@ impl GameCamera {
         self.shake.push(Shake {
             direction: (1.0, 1.0),
             kind: ShakeType::Rotational,
-            magnitude: magnitude * (gen_range(0, 2) as f32 - 0.5) * 2.0,
+            magnitude: magnitude * (cosmetic_rand::gen_range(0, 2) as f32 - 0.5) * 2.0,
             length: length as f32,
             age: 0.0,
             random_offset: 0.0,
@@ -158,7 +226,25 @@ impl GameCamera {
         shake_offset.x = (shake_offset.x.abs() + 1.0).log2() * shake_offset.x.signum(); // log2(x+1) is almost linear from 0-1, but then flattens out. Limits the screenshake so if there is lots at the same time, the scene won't fly away
         shake_offset.y = (shake_offset.y.abs() + 1.0).log2() * shake_offset.y.signum();
 
-        (shake_offset, shake_rotation)
+        if self.trauma > 0.0 {
+            let shake = self.trauma * self.trauma;
+            let position = self.noisegen_position * Self::TRAUMA_FREQUENCY;
+
+            // The noise is in the range of +/- 0.5
+            shake_offset.x +=
+                self.noisegen.perlin_2d(position, 11.0) * 2.0 * shake * Self::MAX_TRAUMA_OFFSET;
+            shake_offset.y +=
+                self.noisegen.perlin_2d(position, 13.0) * 2.0 * shake * Self::MAX_TRAUMA_OFFSET;
+            shake_rotation +=
+                self.noisegen.perlin_2d(position, 17.0) * 2.0 * shake * Self::MAX_TRAUMA_ROTATION;
+
+            self.trauma = (self.trauma - Self::TRAUMA_DECAY * SIMULATION_DELTA_TIME).max(0.0);
+        }
+
+        // This allows players to reduce, or disable, camera shake in the config
+        let scale = storage::get::<Config>().camera_shake;
+
+        (shake_offset * scale, shake_rotation * scale)
     }
 }
 
@@ -234,6 +320,8 @@ impl scene::Node for GameCamera {
             ..Camera2D::default()
         };
 
+        node.macroquad_camera = macroquad_camera;
+
         scene::set_camera(0, Some(macroquad_camera));
     }
 }
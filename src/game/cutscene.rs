@@ -0,0 +1,488 @@
+//! Cutscenes are short, scripted sequences, that are defined in data, as a timeline of events.
+//! Events can move the camera, spawn actors, that are animated sprites, move them, play their
+//! animations and show text on the screen. Cutscenes are listed in `cutscenes.json`, in the
+//! assets directory.
+//!
+//! A cutscene with the id `TITLE_INTRO_CUTSCENE_ID` is played before the main menu is shown, when
+//! the game is started, and a map can have a cutscene played before the first round starts, like
+//! a flyover of the map, by setting `intro_cutscene` in its metadata. The simulation of the match
+//! is paused while a cutscene is playing.
+
+use macroquad::{
+    color,
+    experimental::{
+        collections::storage,
+        scene::{self, Node, RefMut},
+    },
+    prelude::*,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    audio::update_music,
+    components::{AnimationParams, AnimationPlayer},
+    gui::{self, draw_main_menu_background},
+    json,
+    localization::tr,
+    symbol::Symbol,
+    text::{draw_aligned_text, HorizontalAlignment, VerticalAlignment},
+    GameCamera, Resources,
+};
+
+/// The id of the cutscene that is played when the game is started, if there is one
+pub const TITLE_INTRO_CUTSCENE_ID: &str = "title_intro";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CutsceneAction {
+    /// Moves the camera to `position`, over `duration` seconds. `zoom` is the height of the area
+    /// of the world that is visible. The first camera move of a cutscene is always instant.
+    CameraMove {
+        #[serde(with = "json::vec2_def")]
+        position: Vec2,
+        zoom: f32,
+        #[serde(default)]
+        duration: f32,
+    },
+    /// Spawns an actor, with the id `actor`, which is used to refer to it in later events. The
+    /// first animation in `animation` is played, until another is played with `PlayAnimation`.
+    SpawnActor {
+        actor: String,
+        animation: AnimationParams,
+        #[serde(with = "json::vec2_def")]
+        position: Vec2,
+        #[serde(default, skip_serializing_if = "json::is_false")]
+        flip_x: bool,
+    },
+    /// Moves the actor with the id `actor` to `position`, over `duration` seconds
+    MoveActor {
+        actor: String,
+        #[serde(with = "json::vec2_def")]
+        position: Vec2,
+        #[serde(default)]
+        duration: f32,
+    },
+    /// Plays the animation with the id `animation` on the actor with the id `actor`. The id is
+    /// interned when the cutscene is loaded, so that it can be printed if it is invalid.
+    PlayAnimation {
+        actor: String,
+        animation: Symbol,
+    },
+    RemoveActor {
+        actor: String,
+    },
+    /// Shows the localized string with the key `text` at the bottom of the screen, for
+    /// `duration` seconds
+    ShowText {
+        text: String,
+        duration: f32,
+    },
+}
+
+impl CutsceneAction {
+    /// Returns the time, in seconds, that the action takes to finish
+    fn get_duration(&self) -> f32 {
+        match self {
+            CutsceneAction::CameraMove { duration, .. }
+            | CutsceneAction::MoveActor { duration, .. }
+            | CutsceneAction::ShowText { duration, .. } => *duration,
+            _ => 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CutsceneEvent {
+    /// The time, in seconds from the start of the cutscene, at which the event starts
+    pub time: f32,
+    #[serde(flatten)]
+    pub action: CutsceneAction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cutscene {
+    /// If this is `true`, the cutscene can be skipped with any input
+    #[serde(default = "Cutscene::default_is_skippable")]
+    pub is_skippable: bool,
+    /// The events of the cutscene. These do not have to be sorted by time.
+    pub events: Vec<CutsceneEvent>,
+}
+
+impl Cutscene {
+    pub fn default_is_skippable() -> bool {
+        true
+    }
+
+    /// Returns the time, in seconds, from the start of the cutscene, until all its events have
+    /// finished
+    pub fn get_duration(&self) -> f32 {
+        self.events
+            .iter()
+            .map(|event| event.time + event.action.get_duration())
+            .fold(0.0, f32::max)
+    }
+}
+
+/// A transition of a value, from `from` to `to`, that started at `start_time`
+#[derive(Debug, Copy, Clone)]
+struct Tween<T> {
+    from: T,
+    to: T,
+    start_time: f32,
+    duration: f32,
+}
+
+impl<T: Copy> Tween<T> {
+    fn new(value: T) -> Self {
+        Tween {
+            from: value,
+            to: value,
+            start_time: 0.0,
+            duration: 0.0,
+        }
+    }
+
+    /// Returns the progress of the tween at `time`, between `0.0` and `1.0`, with smoothing
+    fn get_progress(&self, time: f32) -> f32 {
+        if self.duration <= 0.0 {
+            return 1.0;
+        }
+
+        let t = ((time - self.start_time) / self.duration).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+struct CutsceneActor {
+    id: String,
+    animation_player: AnimationPlayer,
+    position: Tween<Vec2>,
+    flip_x: bool,
+}
+
+impl CutsceneActor {
+    fn get_position(&self, time: f32) -> Vec2 {
+        let progress = self.position.get_progress(time);
+        self.position.from.lerp(self.position.to, progress)
+    }
+}
+
+/// This plays back a cutscene. It is used by the `CutscenePlayer` node during matches, and by
+/// `play_cutscene`, outside of matches.
+struct CutscenePlayback {
+    cutscene: Cutscene,
+    time: f32,
+    duration: f32,
+    /// The index, in the sorted events, of the next event to start
+    next_event: usize,
+    /// The position and zoom of the camera, if a camera move has been started
+    camera: Option<Tween<(Vec2, f32)>>,
+    actors: Vec<CutsceneActor>,
+    /// The texts that are shown, with the time that they are hidden at
+    texts: Vec<(String, f32)>,
+}
+
+impl CutscenePlayback {
+    const FONT_SIZE: u16 = 32;
+    /// The distance of texts from the bottom of the screen
+    const TEXT_MARGIN: f32 = 96.0;
+    const TEXT_LINE_HEIGHT: f32 = 40.0;
+
+    fn new(mut cutscene: Cutscene) -> Self {
+        cutscene
+            .events
+            .sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+
+        let duration = cutscene.get_duration();
+
+        CutscenePlayback {
+            cutscene,
+            time: 0.0,
+            duration,
+            next_event: 0,
+            camera: None,
+            actors: Vec::new(),
+            texts: Vec::new(),
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.time >= self.duration
+    }
+
+    fn skip(&mut self) {
+        if self.cutscene.is_skippable {
+            self.time = self.duration;
+        }
+    }
+
+    fn get_actor(&mut self, id: &str) -> Option<&mut CutsceneActor> {
+        let res = self.actors.iter_mut().find(|actor| actor.id == id);
+
+        if res.is_none() {
+            println!("WARNING: Cutscene: Invalid actor id '{}'", id);
+        }
+
+        res
+    }
+
+    fn start_event(&mut self, action: CutsceneAction) {
+        let time = self.time;
+
+        match action {
+            CutsceneAction::CameraMove {
+                position,
+                zoom,
+                duration,
+            } => {
+                let from = self.get_camera().unwrap_or((position, zoom));
+
+                self.camera = Some(Tween {
+                    from,
+                    to: (position, zoom),
+                    start_time: time,
+                    duration,
+                });
+            }
+            CutsceneAction::SpawnActor {
+                actor,
+                animation,
+                position,
+                flip_x,
+            } => {
+                let is_valid = storage::get::<Resources>()
+                    .textures
+                    .contains_key(&animation.texture_id);
+
+                if !is_valid {
+                    println!(
+                        "WARNING: Cutscene: Invalid texture id '{}'",
+                        &animation.texture_id
+                    );
+                    return;
+                }
+
+                let mut animation_player = AnimationPlayer::new(animation);
+                animation_player.play();
+
+                self.actors.retain(|other| other.id != actor);
+                self.actors.push(CutsceneActor {
+                    id: actor,
+                    animation_player,
+                    position: Tween::new(position),
+                    flip_x,
+                });
+            }
+            CutsceneAction::MoveActor {
+                actor,
+                position,
+                duration,
+            } => {
+                if let Some(actor) = self.get_actor(&actor) {
+                    actor.position = Tween {
+                        from: actor.get_position(time),
+                        to: position,
+                        start_time: time,
+                        duration,
+                    };
+                }
+            }
+            CutsceneAction::PlayAnimation { actor, animation } => {
+                if let Some(actor) = self.get_actor(&actor) {
+                    if actor.animation_player.set_animation(animation).is_none() {
+                        println!("WARNING: Cutscene: Invalid animation id '{}'", animation);
+                    }
+
+                    actor.animation_player.set_frame(0);
+                    actor.animation_player.play();
+                }
+            }
+            CutsceneAction::RemoveActor { actor } => {
+                self.actors.retain(|other| other.id != actor);
+            }
+            CutsceneAction::ShowText { text, duration } => {
+                self.texts.push((tr(&text), time + duration));
+            }
+        }
+    }
+
+    fn update(&mut self, dt: f32) {
+        self.time += dt;
+
+        while let Some(event) = self.cutscene.events.get(self.next_event) {
+            if event.time > self.time {
+                break;
+            }
+
+            let action = event.action.clone();
+            self.next_event += 1;
+
+            self.start_event(action);
+        }
+
+        let time = self.time;
+        self.texts.retain(|(_, hide_time)| *hide_time > time);
+
+        for actor in &mut self.actors {
+            actor.animation_player.update(dt);
+        }
+    }
+
+    /// Returns the current position and zoom of the camera, if the cutscene has moved it
+    fn get_camera(&self) -> Option<(Vec2, f32)> {
+        self.camera.map(|tween| {
+            let progress = tween.get_progress(self.time);
+
+            let position = tween.from.0.lerp(tween.to.0, progress);
+            let zoom = tween.from.1 + (tween.to.1 - tween.from.1) * progress;
+
+            (position, zoom)
+        })
+    }
+
+    /// This draws the actors, with the current camera
+    fn draw_actors(&self) {
+        for actor in &self.actors {
+            let position = actor.get_position(self.time);
+
+            actor
+                .animation_player
+                .draw(position, 0.0, actor.flip_x, false);
+        }
+    }
+
+    /// This draws the texts, in screen space
+    fn draw_texts(&self) {
+        push_camera_state();
+        set_default_camera();
+
+        for (i, (text, _)) in self.texts.iter().rev().enumerate() {
+            let position = vec2(
+                screen_width() / 2.0,
+                screen_height() - Self::TEXT_MARGIN - i as f32 * Self::TEXT_LINE_HEIGHT,
+            );
+
+            draw_aligned_text(
+                text,
+                position,
+                HorizontalAlignment::Center,
+                VerticalAlignment::Center,
+                TextParams {
+                    font_size: Self::FONT_SIZE,
+                    color: color::WHITE,
+                    ..Default::default()
+                },
+            );
+        }
+
+        pop_camera_state();
+    }
+}
+
+/// This plays the cutscene with the id `id`, outside of a match, over the main menu background,
+/// and returns when it is done, or skipped. Cutscenes that move the camera should set it on
+/// their first frame, as the view is centered on the origin, until they do.
+pub async fn play_cutscene(id: &str) {
+    let cutscene = storage::get::<Resources>().cutscenes.get(id).cloned();
+
+    let cutscene = match cutscene {
+        Some(cutscene) => cutscene,
+        None => {
+            println!("WARNING: Cutscene: Invalid cutscene id '{}'", id);
+            return;
+        }
+    };
+
+    let mut playback = CutscenePlayback::new(cutscene);
+
+    // The input that started the game, or that was held while it loaded, should not skip
+    let mut is_first_frame = true;
+
+    while !playback.is_done() {
+        if !is_first_frame && gui::is_any_input_pressed() {
+            playback.skip();
+        }
+
+        is_first_frame = false;
+
+        playback.update(get_frame_time());
+
+        draw_main_menu_background(false);
+
+        let (position, zoom) = playback
+            .get_camera()
+            .unwrap_or((Vec2::ZERO, screen_height()));
+
+        let aspect = screen_width() / screen_height();
+
+        set_camera(&Camera2D {
+            target: position,
+            zoom: vec2(1.0 / aspect, -1.0) / zoom * 2.0,
+            ..Camera2D::default()
+        });
+
+        playback.draw_actors();
+
+        set_default_camera();
+
+        playback.draw_texts();
+
+        update_music();
+
+        next_frame().await;
+    }
+}
+
+/// This plays a cutscene during a match, through the game camera. The simulation of the match
+/// should be paused while `is_cutscene_playing` returns `true`.
+pub struct CutscenePlayer {
+    playback: CutscenePlayback,
+}
+
+impl CutscenePlayer {
+    pub fn new(cutscene: Cutscene) -> Self {
+        CutscenePlayer {
+            playback: CutscenePlayback::new(cutscene),
+        }
+    }
+
+    fn stop(node: RefMut<Self>) {
+        if let Some(mut camera) = scene::find_node_by_type::<GameCamera>() {
+            camera.manual = None;
+        }
+
+        node.delete();
+    }
+}
+
+/// Returns `true` if a cutscene is playing in the current scene
+pub fn is_cutscene_playing() -> bool {
+    scene::find_node_by_type::<CutscenePlayer>().is_some()
+}
+
+impl Node for CutscenePlayer {
+    fn update(mut node: RefMut<Self>) {
+        if gui::is_any_input_pressed() {
+            node.playback.skip();
+        }
+
+        node.playback.update(get_frame_time());
+
+        if node.playback.is_done() {
+            Self::stop(node);
+            return;
+        }
+
+        if let Some(camera_state) = node.playback.get_camera() {
+            if let Some(mut camera) = scene::find_node_by_type::<GameCamera>() {
+                camera.manual = Some(camera_state);
+            }
+        }
+    }
+
+    fn draw(node: RefMut<Self>) {
+        node.playback.draw_actors();
+        node.playback.draw_texts();
+    }
+}
@@ -0,0 +1,181 @@
+//! Gameplay events are dispatched from within the simulation (`network_update`) and they are
+//! queued until the end of the simulation frame, when they are passed on to all the registered
+//! listeners, in the order they were dispatched.
+//! As events are only dispatched by the simulation, and listeners are called in the order they
+//! were registered, any listener will see the exact same sequence of events in local and in
+//! networked play.
+//!
+//! Systems that react to gameplay, like the UI, audio, match stats and scripting, should listen
+//! for events, in stead of being called directly by the gameplay code.
+
+use macroquad::prelude::*;
+
+static mut GAME_EVENT_QUEUE: Option<Vec<GameEvent>> = None;
+
+static mut GAME_EVENT_LISTENERS: Option<Vec<(String, GameEventListener)>> = None;
+
+unsafe fn get_game_event_queue() -> &'static mut Vec<GameEvent> {
+    GAME_EVENT_QUEUE.get_or_insert(Vec::new())
+}
+
+unsafe fn get_game_event_listeners() -> &'static mut Vec<(String, GameEventListener)> {
+    GAME_EVENT_LISTENERS.get_or_insert(Vec::new())
+}
+
+/// This is implemented by anything that should react to gameplay events. Listeners are called
+/// after all nodes have been updated, so it is safe to borrow any node from the scene.
+///
+/// Any implementations must also be added to the `init_game_event_listeners` function.
+pub type GameEventListener = fn(event: &GameEvent);
+
+/// This holds all the gameplay event types
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameEvent {
+    /// A player received damage that was not blocked. `damaged_by` holds the id of the player
+    /// responsible for the damage, if the damage can be attributed to another player, and
+    /// `weapon_id` holds the id of the weapon that dealt the damage, if any. `is_from_script` is
+    /// `true` if the damage was dealt by an item or ability script.
+    PlayerDamaged {
+        player_id: u8,
+        position: Vec2,
+        damage: f32,
+        damaged_by: Option<u8>,
+        weapon_id: Option<String>,
+        is_from_script: bool,
+    },
+    /// A player was killed. `killed_by` holds the id of the player responsible for the kill, if
+    /// the kill can be attributed to another player, and `weapon_id` holds the id of the weapon
+    /// that dealt the killing blow, if it was dealt by a weapon, or by an effect of a weapon.
+    PlayerKilled {
+        player_id: u8,
+        position: Vec2,
+        killed_by: Option<u8>,
+        weapon_id: Option<String>,
+    },
+    /// A player picked up an item, either a weapon or an equipped item
+    ItemPickedUp {
+        player_id: u8,
+        item_id: String,
+        position: Vec2,
+    },
+    /// A player attacked with the weapon with the id `item_id`
+    ItemUsed { player_id: u8, item_id: String },
+    /// The round was decided. `winner` holds the id of the player that won it, if any.
+    RoundEnded { winner: Option<u8> },
+    /// A destructible tile was destroyed. `position` is the center of the tile.
+    TileDestroyed { position: Vec2 },
+}
+
+impl GameEvent {
+    pub fn dispatch(self) {
+        dispatch_game_event(self);
+    }
+}
+
+pub fn dispatch_game_event(event: GameEvent) {
+    unsafe { get_game_event_queue() }.push(event);
+}
+
+/// This will add a listener, replacing any existing listener with the same id.
+pub fn add_game_event_listener(id: &str, f: GameEventListener) {
+    let listeners = unsafe { get_game_event_listeners() };
+
+    if let Some(entry) = listeners.iter_mut().find(|(other_id, _)| other_id == id) {
+        entry.1 = f;
+    } else {
+        listeners.push((id.to_string(), f));
+    }
+}
+
+pub fn remove_game_event_listener(id: &str) {
+    unsafe { get_game_event_listeners() }.retain(|(other_id, _)| other_id != id);
+}
+
+/// This will pass all queued events to the registered listeners and empty the queue.
+/// It should be called once per simulation frame, after all nodes have been updated.
+/// Events that are dispatched by listeners are passed on in the same flush, after the events that
+/// were already queued.
+pub fn flush_game_events() {
+    loop {
+        let events: Vec<GameEvent> = unsafe { get_game_event_queue() }.drain(..).collect();
+
+        if events.is_empty() {
+            break;
+        }
+
+        for event in &events {
+            for (_, f) in unsafe { get_game_event_listeners() }.iter() {
+                f(event);
+            }
+        }
+    }
+}
+
+/// This will discard all queued events, without passing them on to the listeners.
+pub fn clear_game_events() {
+    unsafe { get_game_event_queue() }.clear();
+}
+
+/// This adds all listener implementations to the directory, so that they will receive events.
+pub fn init_game_event_listeners() {
+    add_game_event_listener(
+        crate::player::PlayerEmotes::LISTENER_ID,
+        crate::player::PlayerEmotes::on_game_event,
+    );
+    add_game_event_listener(
+        crate::player::HitFlash::LISTENER_ID,
+        crate::player::HitFlash::on_game_event,
+    );
+    add_game_event_listener(
+        crate::game::KillCam::LISTENER_ID,
+        crate::game::KillCam::on_game_event,
+    );
+    add_game_event_listener(
+        crate::game::MusicIntensity::LISTENER_ID,
+        crate::game::MusicIntensity::on_game_event,
+    );
+    add_game_event_listener(
+        crate::game::Announcer::LISTENER_ID,
+        crate::game::Announcer::on_game_event,
+    );
+    add_game_event_listener(
+        crate::game::Hud::LISTENER_ID,
+        crate::game::Hud::on_game_event,
+    );
+    add_game_event_listener(
+        crate::game::MatchStats::LISTENER_ID,
+        crate::game::MatchStats::on_game_event,
+    );
+    add_game_event_listener(
+        crate::game::ActiveGameMode::LISTENER_ID,
+        crate::game::ActiveGameMode::on_game_event,
+    );
+    add_game_event_listener(
+        crate::game::WorldUi::LISTENER_ID,
+        crate::game::WorldUi::on_game_event,
+    );
+    add_game_event_listener(
+        crate::game::KillFeed::LISTENER_ID,
+        crate::game::KillFeed::on_game_event,
+    );
+    add_game_event_listener(
+        crate::game::DamageReadout::LISTENER_ID,
+        crate::game::DamageReadout::on_game_event,
+    );
+    add_game_event_listener(
+        crate::game::Rumble::LISTENER_ID,
+        crate::game::Rumble::on_game_event,
+    );
+    add_game_event_listener(
+        crate::audio::GameplaySounds::LISTENER_ID,
+        crate::audio::GameplaySounds::on_game_event,
+    );
+    add_game_event_listener(
+        crate::mutators::Mutators::LISTENER_ID,
+        crate::mutators::Mutators::on_game_event,
+    );
+    add_game_event_listener(
+        crate::scripting::LISTENER_ID,
+        crate::scripting::on_game_event,
+    );
+}
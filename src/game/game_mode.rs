@@ -0,0 +1,201 @@
+//! Game modes decide how a match is won. The mode of a match is picked by the `game_mode` of the
+//! `Ruleset` and it is driven by the `ActiveGameMode` node, which passes it the gameplay events,
+//! advances it on every simulation frame and ends the match, through `MatchStats`, when its win
+//! condition is met.
+//!
+//! Modes implemented in Rust are registered with `register_game_mode`, while modes implemented in
+//! scripts are loaded from `game_modes.json`, in the assets directory (see `scripting`). Any
+//! built-in implementations must be registered in the `init_game_modes` function.
+
+use macroquad::{
+    experimental::{
+        collections::storage,
+        scene::{self, HandleUntyped, Node, RefMut},
+    },
+    prelude::*,
+};
+
+use crate::{
+    capabilities::NetworkReplicate,
+    game::{GameEvent, MatchStats, SIMULATION_DELTA_TIME},
+    scripting::ScriptedGameMode,
+    Player, Resources,
+};
+
+/// This is implemented by game modes
+pub trait GameMode {
+    /// This is called when the match is started, with the ids of the players in the match
+    fn setup(&mut self, _player_ids: &[u8]) {}
+
+    /// This is called with every gameplay event, after it has been counted by `MatchStats`
+    fn on_event(&mut self, _event: &GameEvent) {}
+
+    /// This is called on every simulation frame
+    fn update(&mut self, _dt: f32) {}
+
+    /// Returns the id of the player that has won the match, if the win condition has been met
+    fn check_win_condition(&mut self, match_stats: &MatchStats) -> Option<u8>;
+
+    /// This is called when the HUD is drawn, with the default camera, so that a mode can draw
+    /// its own state, like the time each player has held the hill in king of the hill
+    fn hud_overlay(&mut self) {}
+}
+
+pub type GameModeConstructor = fn() -> Box<dyn GameMode>;
+
+static mut GAME_MODES: Option<Vec<(String, GameModeConstructor)>> = None;
+
+unsafe fn get_game_modes() -> &'static mut Vec<(String, GameModeConstructor)> {
+    GAME_MODES.get_or_insert(Vec::new())
+}
+
+/// This will register a game mode, replacing any existing mode with the same id
+pub fn register_game_mode(id: &str, constructor: GameModeConstructor) {
+    let modes = unsafe { get_game_modes() };
+
+    if let Some(entry) = modes.iter_mut().find(|(other_id, _)| other_id == id) {
+        entry.1 = constructor;
+    } else {
+        modes.push((id.to_string(), constructor));
+    }
+}
+
+/// This registers all the built-in game modes
+pub fn init_game_modes() {
+    register_game_mode(Deathmatch::ID, || Box::new(Deathmatch::new()));
+}
+
+/// This creates the game mode with the id `id`, from the registered modes, or from the scripted
+/// modes in resources, if there is no registered mode with that id
+pub fn create_game_mode(id: &str) -> Option<Box<dyn GameMode>> {
+    let constructor = unsafe { get_game_modes() }
+        .iter()
+        .find(|(other_id, _)| other_id == id)
+        .map(|(_, constructor)| *constructor);
+
+    if let Some(constructor) = constructor {
+        return Some(constructor());
+    }
+
+    let resources = storage::get::<Resources>();
+    resources
+        .game_mode_scripts
+        .get(id)
+        .map(|script| Box::new(ScriptedGameMode::new(id, script)) as Box<dyn GameMode>)
+}
+
+/// The default game mode, where the first player to reach the kill limit of the `Ruleset` wins
+pub struct Deathmatch {
+    kill_limit: u32,
+}
+
+impl Deathmatch {
+    pub const ID: &'static str = "deathmatch";
+
+    pub fn new() -> Self {
+        let kill_limit = storage::get::<Resources>().ruleset.kill_limit;
+
+        Deathmatch { kill_limit }
+    }
+}
+
+impl Default for Deathmatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GameMode for Deathmatch {
+    fn check_win_condition(&mut self, match_stats: &MatchStats) -> Option<u8> {
+        // If the kill limit is zero, the match will never end
+        if self.kill_limit == 0 {
+            return None;
+        }
+
+        match_stats
+            .iter_stats()
+            .find(|(_, stats)| stats.kills >= self.kill_limit)
+            .map(|(player_id, _)| player_id)
+    }
+}
+
+pub struct ActiveGameMode {
+    mode: Box<dyn GameMode>,
+}
+
+impl ActiveGameMode {
+    pub const LISTENER_ID: &'static str = "active_game_mode";
+
+    pub fn new() -> Self {
+        let id = storage::get::<Resources>().ruleset.game_mode.clone();
+
+        let mode = create_game_mode(&id).unwrap_or_else(|| {
+            println!("WARNING: Ruleset: Invalid game mode id '{}'", &id);
+            Box::new(Deathmatch::new())
+        });
+
+        ActiveGameMode { mode }
+    }
+
+    fn check_win_condition(&mut self) {
+        if let Some(mut match_stats) = scene::find_node_by_type::<MatchStats>() {
+            if match_stats.is_match_over() {
+                return;
+            }
+
+            if let Some(winner) = self.mode.check_win_condition(&match_stats) {
+                match_stats.set_winner(winner);
+            }
+        }
+    }
+
+    pub fn on_game_event(event: &GameEvent) {
+        if let Some(mut node) = scene::find_node_by_type::<ActiveGameMode>() {
+            node.mode.on_event(event);
+            node.check_win_condition();
+        }
+    }
+
+    fn network_update(mut node: RefMut<Self>) {
+        node.mode.update(SIMULATION_DELTA_TIME);
+        node.check_win_condition();
+    }
+
+    fn network_capabilities() -> NetworkReplicate {
+        fn network_update(handle: HandleUntyped) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<ActiveGameMode>();
+            ActiveGameMode::network_update(node);
+        }
+
+        NetworkReplicate { network_update }
+    }
+}
+
+impl Default for ActiveGameMode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Node for ActiveGameMode {
+    fn ready(mut node: RefMut<Self>) {
+        node.provides(Self::network_capabilities());
+
+        let player_ids = scene::find_nodes_by_type::<Player>()
+            .map(|player| player.id)
+            .collect::<Vec<_>>();
+
+        node.mode.setup(&player_ids);
+    }
+
+    fn draw(mut node: RefMut<Self>) {
+        push_camera_state();
+        set_default_camera();
+
+        node.mode.hud_overlay();
+
+        pop_camera_state();
+    }
+}
@@ -0,0 +1,407 @@
+//! The match HUD shows a panel for every player along the bottom of the screen, holding the
+//! portrait of the player's character, the player's score and health, and the weapon held by the
+//! player, with its remaining ammo. The player with the highest score is marked with a crown.
+//!
+//! The score of a player is the number of kills scored by the player, counted from `GameEvent`s,
+//! so it will be the same in local and networked play.
+
+use std::collections::HashMap;
+
+use macroquad::{
+    color,
+    experimental::{
+        collections::storage,
+        scene::{self, Node, RefMut},
+    },
+    prelude::*,
+};
+
+use crate::{
+    components::{Sprite, SpriteParams},
+    game::GameEvent,
+    items::WeaponSlot,
+    symbol::SymbolMap,
+    text::{draw_aligned_text, HorizontalAlignment, VerticalAlignment},
+    Player, Resources,
+};
+
+/// A cache of the icons of weapons, of a fixed size, that are created from the sprites of the
+/// items, when they are first drawn. This is used by every part of the UI that shows weapons.
+pub struct WeaponIcons {
+    size: f32,
+    /// The icon of each weapon, by item id
+    icons: SymbolMap<Sprite>,
+}
+
+impl WeaponIcons {
+    pub fn new(size: f32) -> Self {
+        WeaponIcons {
+            size,
+            icons: SymbolMap::new(),
+        }
+    }
+
+    /// Returns the icon of the item `item_id`, or `None` if there is no such item
+    pub fn get(&mut self, item_id: &str) -> Option<&Sprite> {
+        if !self.icons.contains_key(item_id) {
+            let params = {
+                let resources = storage::get::<Resources>();
+                resources.items.get(item_id)?.sprite.clone()
+            };
+
+            let icon = Hud::create_icon(params, self.size);
+            self.icons.insert(item_id, icon);
+        }
+
+        self.icons.get(item_id)
+    }
+}
+
+pub struct Hud {
+    /// The score of each player, by player id
+    scores: HashMap<u8, u32>,
+    /// The portrait of each player character, by player character id
+    portraits: HashMap<String, Sprite>,
+    weapon_icons: WeaponIcons,
+}
+
+impl Hud {
+    pub const LISTENER_ID: &'static str = "hud";
+
+    const PANEL_WIDTH: f32 = 200.0;
+    const PANEL_HEIGHT: f32 = 64.0;
+    const PANEL_MARGIN: f32 = 12.0;
+    const PANEL_PADDING: f32 = 8.0;
+    const PANEL_BORDER_WIDTH: f32 = 2.0;
+    const PANEL_COLOR: Color = Color {
+        r: 0.0,
+        g: 0.0,
+        b: 0.0,
+        a: 0.6,
+    };
+    const DEAD_PANEL_COLOR: Color = Color {
+        r: 0.25,
+        g: 0.0,
+        b: 0.0,
+        a: 0.6,
+    };
+
+    const PORTRAIT_SIZE: f32 = 48.0;
+    const ICON_SIZE: f32 = 32.0;
+
+    /// Above this score, the score is shown as a number, in stead of as pips
+    const MAX_SCORE_PIPS: u32 = 8;
+    const PIP_RADIUS: f32 = 4.0;
+    const PIP_SPACING: f32 = 11.0;
+    const SCORE_COLOR: Color = Color {
+        r: 1.0,
+        g: 0.85,
+        b: 0.2,
+        a: 1.0,
+    };
+
+    const HEALTH_BAR_HEIGHT: f32 = 6.0;
+    const HEALTH_COLOR: Color = Color {
+        r: 0.3,
+        g: 0.85,
+        b: 0.35,
+        a: 1.0,
+    };
+    const HEALTH_EMPTY_COLOR: Color = Color {
+        r: 0.3,
+        g: 0.3,
+        b: 0.3,
+        a: 1.0,
+    };
+
+    const FONT_SIZE: u16 = 16;
+
+    const CROWN_WIDTH: f32 = 24.0;
+    const CROWN_HEIGHT: f32 = 14.0;
+    const CROWN_COLOR: Color = Color {
+        r: 1.0,
+        g: 0.8,
+        b: 0.1,
+        a: 1.0,
+    };
+
+    pub fn new() -> Self {
+        Hud {
+            scores: HashMap::new(),
+            portraits: HashMap::new(),
+            weapon_icons: WeaponIcons::new(Self::ICON_SIZE),
+        }
+    }
+
+    pub fn on_game_event(event: &GameEvent) {
+        if let GameEvent::PlayerKilled {
+            killed_by: Some(killed_by),
+            player_id,
+            ..
+        } = *event
+        {
+            if killed_by != player_id {
+                if let Some(mut node) = scene::find_node_by_type::<Hud>() {
+                    *node.scores.entry(killed_by).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+
+    pub fn get_score(&self, player_id: u8) -> u32 {
+        self.scores.get(&player_id).copied().unwrap_or(0)
+    }
+
+    /// Returns the id of the player with the highest score, if any player has scored and no other
+    /// player has the same score
+    pub fn get_leader(&self) -> Option<u8> {
+        let (&leader, &score) = self.scores.iter().max_by_key(|(_, &score)| score)?;
+
+        let is_tied = self
+            .scores
+            .iter()
+            .any(|(&id, &other)| id != leader && other == score);
+
+        if score > 0 && !is_tied {
+            Some(leader)
+        } else {
+            None
+        }
+    }
+
+    /// Creates a sprite, from `params`, that is scaled to fit within a square of `size`
+    pub fn create_icon(params: SpriteParams, size: f32) -> Sprite {
+        let mut sprite = Sprite::new(SpriteParams {
+            scale: 1.0,
+            offset: Vec2::ZERO,
+            pivot: None,
+            ..params
+        });
+
+        let sprite_size = sprite.get_size();
+        sprite.set_scale(size / sprite_size.x.max(sprite_size.y));
+
+        sprite
+    }
+
+    fn get_portrait(&mut self, character_id: &str) -> Option<&Sprite> {
+        if !self.portraits.contains_key(character_id) {
+            let params = {
+                let resources = storage::get::<Resources>();
+                let character = resources
+                    .player_characters
+                    .iter()
+                    .find(|params| params.id == character_id)?;
+
+                match &character.portrait_texture_id {
+                    Some(texture_id) => SpriteParams {
+                        texture_id: texture_id.clone(),
+                        ..Default::default()
+                    },
+                    None => SpriteParams {
+                        texture_id: character.animation.texture_id.clone(),
+                        size: character.animation.frame_size,
+                        tint: character.animation.tint,
+                        ..Default::default()
+                    },
+                }
+            };
+
+            let portrait = Self::create_icon(params, Self::PORTRAIT_SIZE);
+            self.portraits.insert(character_id.to_string(), portrait);
+        }
+
+        self.portraits.get(character_id)
+    }
+
+    fn draw_panel(&mut self, player: &Player, position: Vec2, is_leader: bool) {
+        let panel_color = if player.is_dead {
+            Self::DEAD_PANEL_COLOR
+        } else {
+            Self::PANEL_COLOR
+        };
+
+        draw_rectangle(
+            position.x,
+            position.y,
+            Self::PANEL_WIDTH,
+            Self::PANEL_HEIGHT,
+            panel_color,
+        );
+
+        draw_rectangle_lines(
+            position.x,
+            position.y,
+            Self::PANEL_WIDTH,
+            Self::PANEL_HEIGHT,
+            Self::PANEL_BORDER_WIDTH,
+            player.get_color(),
+        );
+
+        let portrait_position = position + vec2(Self::PANEL_PADDING, Self::PANEL_PADDING);
+
+        if let Some(portrait) = self.get_portrait(&player.character_id) {
+            portrait.draw(portrait_position, 0.0, false, false);
+        }
+
+        if is_leader {
+            Self::draw_crown(portrait_position + vec2(Self::PORTRAIT_SIZE / 2.0, 0.0));
+        }
+
+        let x = portrait_position.x + Self::PORTRAIT_SIZE + Self::PANEL_PADDING;
+
+        self.draw_score(player.id, vec2(x, position.y + Self::PANEL_PADDING));
+
+        let health_width =
+            position.x + Self::PANEL_WIDTH - Self::PANEL_PADDING * 2.0 - Self::ICON_SIZE - x;
+        let health = if player.max_health > 0.0 {
+            (player.health / player.max_health).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        let health_y =
+            position.y + Self::PANEL_HEIGHT - Self::PANEL_PADDING - Self::HEALTH_BAR_HEIGHT;
+
+        draw_rectangle(
+            x,
+            health_y,
+            health_width,
+            Self::HEALTH_BAR_HEIGHT,
+            Self::HEALTH_EMPTY_COLOR,
+        );
+
+        if !player.is_dead {
+            draw_rectangle(
+                x,
+                health_y,
+                health_width * health,
+                Self::HEALTH_BAR_HEIGHT,
+                Self::HEALTH_COLOR,
+            );
+        }
+
+        if let Some(weapon) = player.get_weapon(WeaponSlot::MainHand) {
+            let icon_position = vec2(
+                position.x + Self::PANEL_WIDTH - Self::PANEL_PADDING - Self::ICON_SIZE,
+                position.y + Self::PANEL_PADDING,
+            );
+
+            if let Some(icon) = self.weapon_icons.get(&weapon.id) {
+                icon.draw(icon_position, 0.0, false, false);
+            }
+
+            if let Some((remaining, total)) = weapon.get_ammo() {
+                draw_aligned_text(
+                    &format!("{}/{}", remaining, total),
+                    icon_position + vec2(Self::ICON_SIZE / 2.0, Self::ICON_SIZE),
+                    HorizontalAlignment::Center,
+                    VerticalAlignment::Top,
+                    TextParams {
+                        font_size: Self::FONT_SIZE,
+                        color: color::WHITE,
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+    }
+
+    fn draw_score(&self, player_id: u8, position: Vec2) {
+        let score = self.get_score(player_id);
+
+        if score > Self::MAX_SCORE_PIPS {
+            draw_aligned_text(
+                &format!("x{}", score),
+                position,
+                HorizontalAlignment::Left,
+                VerticalAlignment::Top,
+                TextParams {
+                    font_size: Self::FONT_SIZE,
+                    color: Self::SCORE_COLOR,
+                    ..Default::default()
+                },
+            );
+        } else {
+            for i in 0..Self::MAX_SCORE_PIPS.min(score.max(1) + 2) {
+                let x = position.x + Self::PIP_RADIUS + i as f32 * Self::PIP_SPACING;
+                let y = position.y + Self::PIP_RADIUS + 2.0;
+
+                if i < score {
+                    draw_circle(x, y, Self::PIP_RADIUS, Self::SCORE_COLOR);
+                } else {
+                    draw_circle_lines(x, y, Self::PIP_RADIUS, 1.0, Self::HEALTH_EMPTY_COLOR);
+                }
+            }
+        }
+    }
+
+    /// Draws a crown, centered horizontally on `position`, with its bottom at `position`
+    fn draw_crown(position: Vec2) {
+        let left = position.x - Self::CROWN_WIDTH / 2.0;
+        let right = position.x + Self::CROWN_WIDTH / 2.0;
+        let bottom = position.y;
+        let band = bottom - Self::CROWN_HEIGHT * 0.4;
+        let top = bottom - Self::CROWN_HEIGHT;
+
+        draw_rectangle(
+            left,
+            band,
+            Self::CROWN_WIDTH,
+            bottom - band,
+            Self::CROWN_COLOR,
+        );
+
+        for (x1, x2, x3) in [
+            (left, left + Self::CROWN_WIDTH / 3.0, left),
+            (
+                left + Self::CROWN_WIDTH / 4.0,
+                right - Self::CROWN_WIDTH / 4.0,
+                position.x,
+            ),
+            (right - Self::CROWN_WIDTH / 3.0, right, right),
+        ] {
+            draw_triangle(
+                vec2(x1, band),
+                vec2(x2, band),
+                vec2(x3, top),
+                Self::CROWN_COLOR,
+            );
+        }
+    }
+}
+
+impl Default for Hud {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Node for Hud {
+    fn draw(mut node: RefMut<Self>) {
+        let mut players = scene::find_nodes_by_type::<Player>().collect::<Vec<_>>();
+
+        if players.is_empty() {
+            return;
+        }
+
+        players.sort_by_key(|player| player.id);
+
+        let leader = node.get_leader();
+
+        push_camera_state();
+        set_default_camera();
+
+        // The panels are spread evenly along the bottom of the screen
+        let slot_width = screen_width() / players.len() as f32;
+        let y = screen_height() - Self::PANEL_HEIGHT - Self::PANEL_MARGIN;
+
+        for (i, player) in players.iter().enumerate() {
+            let x = slot_width * i as f32 + (slot_width - Self::PANEL_WIDTH).max(0.0) / 2.0;
+
+            node.draw_panel(player, vec2(x, y), leader == Some(player.id));
+        }
+
+        pop_camera_state();
+    }
+}
@@ -0,0 +1,111 @@
+//! Off-screen indicators are arrows, in the color of a player, drawn at the edge of the screen,
+//! pointing towards players that are outside of the view of the camera, so that players that have
+//! been launched off-camera on large maps can be found. The arrows shrink with the distance to the
+//! player they point at. They can be disabled with the `offscreen_indicators` setting in `Config`.
+
+use macroquad::{
+    experimental::{
+        collections::storage,
+        scene::{self, Node, RefMut},
+    },
+    prelude::*,
+};
+
+use crate::{game::GameCamera, Config, Player};
+
+pub struct OffscreenIndicators {}
+
+impl OffscreenIndicators {
+    /// The distance between the arrows and the edge of the screen
+    const MARGIN: f32 = 24.0;
+    const MAX_SIZE: f32 = 18.0;
+    const MIN_SIZE: f32 = 8.0;
+    /// The distance from the edge of the screen, in screen sizes, at which arrows are smallest
+    const MIN_SIZE_DISTANCE: f32 = 1.5;
+
+    pub fn new() -> Self {
+        OffscreenIndicators {}
+    }
+
+    fn draw_arrow(position: Vec2, direction: Vec2, size: f32, color: Color) {
+        let normal = vec2(-direction.y, direction.x);
+
+        let tip = position + direction * size;
+        let back = position - direction * size * 0.5;
+
+        draw_triangle(
+            tip,
+            back + normal * size * 0.6,
+            back - normal * size * 0.6,
+            color,
+        );
+    }
+}
+
+impl Default for OffscreenIndicators {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Node for OffscreenIndicators {
+    fn draw(_node: RefMut<Self>) {
+        if !storage::get::<Config>().offscreen_indicators {
+            return;
+        }
+
+        let camera = match scene::find_node_by_type::<GameCamera>() {
+            Some(camera) => camera.get_macroquad_camera(),
+            None => return,
+        };
+
+        let screen_size = vec2(screen_width(), screen_height());
+        let center = screen_size / 2.0;
+        let half_extents = center - vec2(Self::MARGIN, Self::MARGIN);
+
+        if half_extents.x <= 0.0 || half_extents.y <= 0.0 {
+            return;
+        }
+
+        push_camera_state();
+        set_default_camera();
+
+        for player in scene::find_nodes_by_type::<Player>() {
+            if player.is_dead {
+                continue;
+            }
+
+            let world_position = player.body.position + player.body.size / 2.0;
+            let screen_position = camera.world_to_screen(world_position);
+
+            let is_on_screen = screen_position.x >= 0.0
+                && screen_position.x <= screen_size.x
+                && screen_position.y >= 0.0
+                && screen_position.y <= screen_size.y;
+
+            if is_on_screen {
+                continue;
+            }
+
+            let offset = screen_position - center;
+            let direction = offset.normalize_or_zero();
+
+            if direction == Vec2::ZERO {
+                continue;
+            }
+
+            // Scale the offset, so that the arrow is placed where the line towards the player
+            // crosses the margin along the edge of the screen
+            let t = (half_extents.x / offset.x.abs()).min(half_extents.y / offset.y.abs());
+            let position = center + offset * t;
+
+            let distance = ((offset.abs() / center).max_element() - 1.0).max(0.0);
+            let size = Self::MAX_SIZE
+                - (Self::MAX_SIZE - Self::MIN_SIZE) * (distance / Self::MIN_SIZE_DISTANCE).min(1.0);
+
+            Self::draw_arrow(position, direction, size, player.get_color());
+        }
+
+        pop_camera_state();
+    }
+}
@@ -1,83 +1,275 @@
 use macroquad::{
-    experimental::collections::storage,
-    input::{is_key_down, KeyCode},
+    experimental::{collections::storage, scene},
+    prelude::*,
 };
 
-use fishsticks::{Axis, Button};
+use fishsticks::Axis;
 
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    game::{GameCamera, InputAction, InputBinding, TouchControls},
+    input::apply_dead_zone,
+    json,
+    localization::{tr, tr_args},
+    Config, GamepadContext, Resources,
+};
+
+/// The device slot that a local player is assigned to, in the lobby
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum GameInputScheme {
-    /// Left side of the keyboard, around WASD
+    /// Right side of the keyboard, around the arrow keys
     KeyboardRight,
-    /// Left side of the keyboard, around Arrows
+    /// Left side of the keyboard, around WASD
     KeyboardLeft,
     /// Gamepad index
     Gamepad(fishsticks::GamepadId),
 }
 
+impl GameInputScheme {
+    /// The keyboard slots, in the order that they are assigned when a player joins with enter
+    pub const KEYBOARD_SLOTS: &'static [GameInputScheme] = &[
+        GameInputScheme::KeyboardLeft,
+        GameInputScheme::KeyboardRight,
+    ];
+
+    /// Returns the localized name of the device slot, like "Keyboard Left" or "Gamepad 2".
+    /// Gamepads are numbered by their order among the connected gamepads.
+    pub fn get_name(&self) -> String {
+        match *self {
+            GameInputScheme::KeyboardLeft => tr("input_scheme.keyboard_left"),
+            GameInputScheme::KeyboardRight => tr("input_scheme.keyboard_right"),
+            GameInputScheme::Gamepad(id) => {
+                let gamepad_context = storage::get::<GamepadContext>();
+                let number = gamepad_context
+                    .gamepads()
+                    .position(|(other, _)| other == id)
+                    .map(|i| (i + 1).to_string())
+                    .unwrap_or_else(|| "?".to_string());
+
+                tr_args("input_scheme.gamepad", &[&number])
+            }
+        }
+    }
+}
+
 #[derive(Default, Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct GameInput {
     pub jump: bool,
     pub pickup: bool,
     pub fire: bool,
+    /// Fires the off-hand weapon, if one is held
+    pub fire_off_hand: bool,
     pub slide: bool,
 
     pub left: bool,
     pub right: bool,
     pub down: bool,
+
+    /// The normalized direction that the player is aiming in, or zero if the player is not
+    /// aiming. This is only set if free aim is enabled in the ruleset.
+    #[serde(default, with = "json::vec2_def")]
+    pub aim: Vec2,
 }
 
-pub fn collect_input(scheme: GameInputScheme) -> GameInput {
-    let mut input = GameInput::default();
+impl GameInput {
+    /// This builds the input of a single frame, where `is_down` returns `true` for any action that
+    /// is currently pressed
+    pub fn from_actions<F: Fn(InputAction) -> bool>(is_down: F) -> Self {
+        GameInput {
+            jump: is_down(InputAction::Jump),
+            pickup: is_down(InputAction::Pickup),
+            fire: is_down(InputAction::Fire),
+            fire_off_hand: is_down(InputAction::FireOffHand),
+            slide: is_down(InputAction::Slide),
+            left: is_down(InputAction::Left),
+            right: is_down(InputAction::Right),
+            down: is_down(InputAction::Down),
+            aim: Vec2::ZERO,
+        }
+    }
+
+    pub fn is_action_down(&self, action: InputAction) -> bool {
+        match action {
+            InputAction::Left => self.left,
+            InputAction::Right => self.right,
+            InputAction::Down => self.down,
+            InputAction::Jump => self.jump,
+            InputAction::Fire => self.fire,
+            InputAction::FireOffHand => self.fire_off_hand,
+            InputAction::Pickup => self.pickup,
+            InputAction::Slide => self.slide,
+        }
+    }
+
+    /// Returns `true` if no buttons are pressed. Aim is not considered, as the mouse will always
+    /// give an aim direction.
+    pub fn is_idle(&self) -> bool {
+        !(self.jump
+            || self.pickup
+            || self.fire
+            || self.fire_off_hand
+            || self.slide
+            || self.left
+            || self.right
+            || self.down)
+    }
+}
+
+/// Returns `true` on the frame that a binding of `action` is first pressed, on the device of
+/// `scheme`. This is used to join and navigate menus with the bindings of each device slot.
+pub fn is_action_pressed(scheme: GameInputScheme, action: InputAction) -> bool {
+    let config = storage::get::<Config>();
+    let profile = config.input_bindings.get_profile(scheme);
 
-    if let GameInputScheme::KeyboardLeft = scheme {
-        input.pickup = is_key_down(KeyCode::C);
-        input.fire = is_key_down(KeyCode::V) || is_key_down(KeyCode::LeftControl);
+    if let GameInputScheme::Gamepad(ix) = scheme {
+        let gamepad_context = storage::get::<GamepadContext>();
+
+        if let Some(gamepad) = gamepad_context.gamepad(ix) {
+            return profile.is_action_down(action, |binding| match *binding {
+                InputBinding::Key(_) => false,
+                InputBinding::Button(button) => gamepad.digital_inputs.just_activated(button),
+                InputBinding::Axis(axis, is_negative) => {
+                    let value = gamepad.analog_inputs.digital_value(axis);
 
-        input.jump = is_key_down(KeyCode::W) || is_key_down(KeyCode::Space);
-        input.left = is_key_down(KeyCode::A);
-        input.down = is_key_down(KeyCode::S);
-        input.right = is_key_down(KeyCode::D);
+                    gamepad.analog_inputs.just_activated_digital(axis).is_some()
+                        && (value < 0.0) == is_negative
+                }
+            });
+        }
 
-        input.slide = is_key_down(KeyCode::C);
+        false
+    } else {
+        profile.is_action_down(action, |binding| match *binding {
+            InputBinding::Key(key) => is_key_pressed(key),
+            _ => false,
+        })
     }
+}
 
-    if let GameInputScheme::KeyboardRight = scheme {
-        input.pickup = is_key_down(KeyCode::K);
-        input.fire = is_key_down(KeyCode::L);
+/// Returns the free aim direction of the player on `scheme`, whose weapon is at `origin`, or zero
+/// if free aim is disabled in the ruleset. Gamepads aim with the right stick and the left keyboard
+/// scheme aims with the mouse.
+pub fn collect_aim(scheme: GameInputScheme, origin: Vec2) -> Vec2 {
+    if !storage::get::<Resources>().ruleset.free_aim {
+        return Vec2::ZERO;
+    }
 
-        input.jump = is_key_down(KeyCode::Up);
-        input.left = is_key_down(KeyCode::Left);
-        input.down = is_key_down(KeyCode::Down);
-        input.right = is_key_down(KeyCode::Right);
+    let aim = match scheme {
+        GameInputScheme::Gamepad(ix) => {
+            let dead_zone = storage::get::<Config>().gamepad_dead_zone;
+            let gamepad_context = storage::get::<GamepadContext>();
 
-        input.slide = is_key_down(KeyCode::RightControl);
+            gamepad_context
+                .gamepad(ix)
+                .map(|gamepad| {
+                    vec2(
+                        apply_dead_zone(gamepad.analog_inputs.value(Axis::RightX), dead_zone),
+                        apply_dead_zone(gamepad.analog_inputs.value(Axis::RightY), dead_zone),
+                    )
+                })
+                .unwrap_or(Vec2::ZERO)
+        }
+        GameInputScheme::KeyboardLeft => scene::find_node_by_type::<GameCamera>()
+            .map(|camera| {
+                let camera = camera.get_macroquad_camera();
+                camera.screen_to_world(mouse_position().into()) - origin
+            })
+            .unwrap_or(Vec2::ZERO),
+        GameInputScheme::KeyboardRight => Vec2::ZERO,
+    };
+
+    if aim.length() > 0.0 {
+        aim.normalize()
+    } else {
+        Vec2::ZERO
     }
+}
+
+pub fn collect_input(scheme: GameInputScheme) -> GameInput {
+    let config = storage::get::<Config>();
+    let profile = config.input_bindings.get_profile(scheme);
 
     if let GameInputScheme::Gamepad(ix) = scheme {
-        let gamepad_system = storage::get_mut::<fishsticks::GamepadContext>();
-        let gamepad = gamepad_system.gamepad(ix);
+        let gamepad_system = storage::get::<fishsticks::GamepadContext>();
 
-        if let Some(gamepad) = gamepad {
-            input.pickup = gamepad.digital_inputs.activated(Button::X);
-            input.fire = gamepad.digital_inputs.activated(Button::B);
+        if let Some(gamepad) = gamepad_system.gamepad(ix) {
+            return profile.collect(|binding| match *binding {
+                InputBinding::Key(_) => false,
+                InputBinding::Button(button) => gamepad.digital_inputs.activated(button),
+                InputBinding::Axis(axis, is_negative) => {
+                    let value = apply_dead_zone(
+                        gamepad.analog_inputs.value(axis),
+                        config.gamepad_dead_zone,
+                    );
 
-            input.jump = gamepad.digital_inputs.activated(Button::A);
+                    if is_negative {
+                        value < 0.0
+                    } else {
+                        value > 0.0
+                    }
+                }
+            });
+        }
 
-            input.left = gamepad.digital_inputs.activated(Button::DPadLeft)
-                || gamepad.analog_inputs.digital_value(Axis::LeftX) < 0.0;
+        GameInput::default()
+    } else {
+        let is_key_binding_down = |binding: &InputBinding| match *binding {
+            InputBinding::Key(key) => is_key_down(key),
+            _ => false,
+        };
 
-            input.right = gamepad.digital_inputs.activated(Button::DPadRight)
-                || gamepad.analog_inputs.digital_value(Axis::LeftX) > 0.0;
+        // On touch devices, the touch controls are used by the player on the left keyboard scheme
+        let touch_actions = if scheme == GameInputScheme::KeyboardLeft {
+            TouchControls::get_active_actions()
+        } else {
+            Vec::new()
+        };
 
-            input.down = gamepad.digital_inputs.activated(Button::DPadDown)
-                || gamepad.analog_inputs.digital_value(Axis::LeftY) > 0.0;
+        GameInput::from_actions(|action| {
+            profile.is_action_down(action, is_key_binding_down) || touch_actions.contains(&action)
+        })
+    }
+}
+
+/// This holds the state of the toggled actions of a single player, as set by
+/// `InputBindings::toggled_actions`. A toggled action is switched on when it is pressed, and
+/// stays on until it is pressed again.
+#[derive(Debug, Default, Clone)]
+pub struct InputToggles {
+    previous: GameInput,
+    active: Vec<InputAction>,
+}
+
+impl InputToggles {
+    /// This applies the toggled actions to `input`, which should be collected once per tick
+    pub fn apply(&mut self, input: GameInput) -> GameInput {
+        let config = storage::get::<Config>();
+        let bindings = &config.input_bindings;
 
-            input.slide = gamepad.digital_inputs.activated(Button::Y)
+        self.active.retain(|action| bindings.is_toggled(*action));
+
+        for action in InputAction::TOGGLEABLE {
+            let is_pressed =
+                input.is_action_down(*action) && !self.previous.is_action_down(*action);
+
+            if is_pressed && bindings.is_toggled(*action) {
+                if let Some(i) = self.active.iter().position(|other| other == action) {
+                    self.active.remove(i);
+                } else {
+                    self.active.push(*action);
+                }
+            }
         }
-    }
 
-    input
+        self.previous = input;
+
+        GameInput::from_actions(|action| {
+            if bindings.is_toggled(action) {
+                self.active.contains(&action)
+            } else {
+                input.is_action_down(action)
+            }
+        })
+    }
 }
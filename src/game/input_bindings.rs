@@ -0,0 +1,424 @@
+//! Input bindings map the keys and gamepad inputs of each player to the actions of `GameInput`.
+//! Local players are identified by the input scheme they play with, so every scheme has its own
+//! binding profile. The profiles are stored in the config file and can be changed in the settings
+//! menu, where the next key or button that is pressed is bound to the selected action.
+//!
+//! Some actions can be set to toggle, in stead of being held, which is applied to the input of
+//! each player by `InputToggles`.
+//!
+//! Bindings are serialized as strings, like `"key:Space"`, `"button:A"` or `"axis:LeftX-"`, so
+//! that the config file can be edited by hand.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use macroquad::input::{get_last_key_pressed, KeyCode};
+
+use fishsticks::{Axis, Button};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{game::GameInput, localization::tr, GameInputScheme, GamepadContext};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputAction {
+    Left,
+    Right,
+    Down,
+    Jump,
+    Fire,
+    FireOffHand,
+    /// Picks up items and throws held items
+    Pickup,
+    Slide,
+}
+
+impl InputAction {
+    pub const ALL: &'static [InputAction] = &[
+        InputAction::Left,
+        InputAction::Right,
+        InputAction::Down,
+        InputAction::Jump,
+        InputAction::Fire,
+        InputAction::FireOffHand,
+        InputAction::Pickup,
+        InputAction::Slide,
+    ];
+
+    /// The actions that can be set to toggle, in stead of being held
+    pub const TOGGLEABLE: &'static [InputAction] = &[
+        InputAction::Down,
+        InputAction::Fire,
+        InputAction::FireOffHand,
+        InputAction::Slide,
+    ];
+
+    pub fn get_title(&self) -> String {
+        let key = match self {
+            InputAction::Left => "controls.move_left",
+            InputAction::Right => "controls.move_right",
+            InputAction::Down => "controls.drop_down",
+            InputAction::Jump => "controls.jump",
+            InputAction::Fire => "controls.fire",
+            InputAction::FireOffHand => "controls.fire_off_hand",
+            InputAction::Pickup => "controls.pick_up",
+            InputAction::Slide => "controls.slide",
+        };
+
+        tr(key)
+    }
+}
+
+/// The keys that can be bound to actions, with the names they are serialized as. Escape is left
+/// out, as it always opens the game menu.
+const BINDABLE_KEYS: &[(KeyCode, &str)] = &[
+    (KeyCode::A, "A"),
+    (KeyCode::B, "B"),
+    (KeyCode::C, "C"),
+    (KeyCode::D, "D"),
+    (KeyCode::E, "E"),
+    (KeyCode::F, "F"),
+    (KeyCode::G, "G"),
+    (KeyCode::H, "H"),
+    (KeyCode::I, "I"),
+    (KeyCode::J, "J"),
+    (KeyCode::K, "K"),
+    (KeyCode::L, "L"),
+    (KeyCode::M, "M"),
+    (KeyCode::N, "N"),
+    (KeyCode::O, "O"),
+    (KeyCode::P, "P"),
+    (KeyCode::Q, "Q"),
+    (KeyCode::R, "R"),
+    (KeyCode::S, "S"),
+    (KeyCode::T, "T"),
+    (KeyCode::U, "U"),
+    (KeyCode::V, "V"),
+    (KeyCode::W, "W"),
+    (KeyCode::X, "X"),
+    (KeyCode::Y, "Y"),
+    (KeyCode::Z, "Z"),
+    (KeyCode::Key0, "0"),
+    (KeyCode::Key1, "1"),
+    (KeyCode::Key2, "2"),
+    (KeyCode::Key3, "3"),
+    (KeyCode::Key4, "4"),
+    (KeyCode::Key5, "5"),
+    (KeyCode::Key6, "6"),
+    (KeyCode::Key7, "7"),
+    (KeyCode::Key8, "8"),
+    (KeyCode::Key9, "9"),
+    (KeyCode::Up, "Up"),
+    (KeyCode::Down, "Down"),
+    (KeyCode::Left, "Left"),
+    (KeyCode::Right, "Right"),
+    (KeyCode::Space, "Space"),
+    (KeyCode::Enter, "Enter"),
+    (KeyCode::Tab, "Tab"),
+    (KeyCode::LeftShift, "LeftShift"),
+    (KeyCode::RightShift, "RightShift"),
+    (KeyCode::LeftControl, "LeftControl"),
+    (KeyCode::RightControl, "RightControl"),
+    (KeyCode::LeftAlt, "LeftAlt"),
+    (KeyCode::RightAlt, "RightAlt"),
+    (KeyCode::Comma, "Comma"),
+    (KeyCode::Period, "Period"),
+    (KeyCode::Slash, "Slash"),
+    (KeyCode::Semicolon, "Semicolon"),
+    (KeyCode::Apostrophe, "Apostrophe"),
+    (KeyCode::LeftBracket, "LeftBracket"),
+    (KeyCode::RightBracket, "RightBracket"),
+    (KeyCode::Backslash, "Backslash"),
+    (KeyCode::Minus, "Minus"),
+    (KeyCode::Equal, "Equal"),
+];
+
+/// The gamepad buttons that can be bound to actions, with the names they are serialized as.
+/// Start is left out, as it always opens the game menu.
+const BINDABLE_BUTTONS: &[(Button, &str)] = &[
+    (Button::A, "A"),
+    (Button::B, "B"),
+    (Button::X, "X"),
+    (Button::Y, "Y"),
+    (Button::LeftShoulder, "LeftShoulder"),
+    (Button::RightShoulder, "RightShoulder"),
+    (Button::DPadUp, "DPadUp"),
+    (Button::DPadDown, "DPadDown"),
+    (Button::DPadLeft, "DPadLeft"),
+    (Button::DPadRight, "DPadRight"),
+];
+
+const BINDABLE_AXES: &[(Axis, &str)] = &[
+    (Axis::LeftX, "LeftX"),
+    (Axis::LeftY, "LeftY"),
+    (Axis::RightX, "RightX"),
+    (Axis::RightY, "RightY"),
+];
+
+/// A single key, button or direction of an analog stick, that an action is bound to
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum InputBinding {
+    Key(KeyCode),
+    Button(Button),
+    /// An analog axis, in the negative direction if the value is `true`
+    Axis(Axis, bool),
+}
+
+impl InputBinding {
+    /// Returns a binding for `key`, if it can be bound
+    fn from_key(key: KeyCode) -> Option<Self> {
+        BINDABLE_KEYS
+            .iter()
+            .find(|(other, _)| *other == key)
+            .map(|_| InputBinding::Key(key))
+    }
+
+    /// Returns a binding for the key that was pressed this frame, if any, and if it can be bound
+    pub fn poll_keyboard() -> Option<Self> {
+        get_last_key_pressed().and_then(Self::from_key)
+    }
+
+    /// Returns a binding for the first button that was pressed this frame, on any gamepad, if any
+    pub fn poll_gamepads(gamepad_context: &GamepadContext) -> Option<Self> {
+        for (_, gamepad) in gamepad_context.gamepads() {
+            for (button, _) in BINDABLE_BUTTONS {
+                if gamepad.digital_inputs.just_activated(*button) {
+                    return Some(InputBinding::Button(*button));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Returns the name of the key, button or axis, without the type prefix
+    pub fn get_name(&self) -> String {
+        match *self {
+            InputBinding::Key(key) => find_name(BINDABLE_KEYS, key).to_string(),
+            InputBinding::Button(button) => find_name(BINDABLE_BUTTONS, button).to_string(),
+            InputBinding::Axis(axis, is_negative) => {
+                let sign = if is_negative { "-" } else { "+" };
+                format!("{}{}", find_name(BINDABLE_AXES, axis), sign)
+            }
+        }
+    }
+}
+
+fn find_name<T: PartialEq + Copy>(table: &[(T, &'static str)], value: T) -> &'static str {
+    table
+        .iter()
+        .find(|(other, _)| *other == value)
+        .map(|(_, name)| *name)
+        .unwrap_or("?")
+}
+
+fn find_value<T: Copy>(table: &[(T, &'static str)], name: &str) -> Option<T> {
+    table
+        .iter()
+        .find(|(_, other)| *other == name)
+        .map(|(value, _)| *value)
+}
+
+impl From<InputBinding> for String {
+    fn from(binding: InputBinding) -> Self {
+        let prefix = match binding {
+            InputBinding::Key(_) => "key",
+            InputBinding::Button(_) => "button",
+            InputBinding::Axis(..) => "axis",
+        };
+
+        format!("{}:{}", prefix, binding.get_name())
+    }
+}
+
+impl TryFrom<String> for InputBinding {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let res = match value.split_once(':') {
+            Some(("key", name)) => find_value(BINDABLE_KEYS, name).map(InputBinding::Key),
+            Some(("button", name)) => find_value(BINDABLE_BUTTONS, name).map(InputBinding::Button),
+            Some(("axis", name)) if name.len() > 1 => {
+                let (name, sign) = name.split_at(name.len() - 1);
+                let is_negative = sign == "-";
+
+                find_value(BINDABLE_AXES, name).map(|axis| InputBinding::Axis(axis, is_negative))
+            }
+            _ => None,
+        };
+
+        res.ok_or_else(|| format!("Invalid input binding '{}'", value))
+    }
+}
+
+/// The bindings of every action, for a single player. Actions that are not in the map are unbound.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct InputProfile {
+    pub bindings: HashMap<InputAction, Vec<InputBinding>>,
+}
+
+impl InputProfile {
+    fn new(bindings: &[(InputAction, &[InputBinding])]) -> Self {
+        InputProfile {
+            bindings: bindings
+                .iter()
+                .map(|(action, bindings)| (*action, bindings.to_vec()))
+                .collect(),
+        }
+    }
+
+    pub fn default_keyboard_left() -> Self {
+        use InputBinding::Key;
+
+        InputProfile::new(&[
+            (InputAction::Left, &[Key(KeyCode::A)]),
+            (InputAction::Right, &[Key(KeyCode::D)]),
+            (InputAction::Down, &[Key(KeyCode::S)]),
+            (InputAction::Jump, &[Key(KeyCode::W), Key(KeyCode::Space)]),
+            (
+                InputAction::Fire,
+                &[Key(KeyCode::V), Key(KeyCode::LeftControl)],
+            ),
+            (InputAction::FireOffHand, &[Key(KeyCode::B)]),
+            (InputAction::Pickup, &[Key(KeyCode::C)]),
+            (InputAction::Slide, &[Key(KeyCode::C)]),
+        ])
+    }
+
+    pub fn default_keyboard_right() -> Self {
+        use InputBinding::Key;
+
+        InputProfile::new(&[
+            (InputAction::Left, &[Key(KeyCode::Left)]),
+            (InputAction::Right, &[Key(KeyCode::Right)]),
+            (InputAction::Down, &[Key(KeyCode::Down)]),
+            (InputAction::Jump, &[Key(KeyCode::Up)]),
+            (InputAction::Fire, &[Key(KeyCode::L)]),
+            (InputAction::FireOffHand, &[Key(KeyCode::Semicolon)]),
+            (InputAction::Pickup, &[Key(KeyCode::K)]),
+            (InputAction::Slide, &[Key(KeyCode::RightControl)]),
+        ])
+    }
+
+    pub fn default_gamepad() -> Self {
+        use InputBinding::{Axis as StickAxis, Button as Btn};
+
+        InputProfile::new(&[
+            (
+                InputAction::Left,
+                &[Btn(Button::DPadLeft), StickAxis(Axis::LeftX, true)],
+            ),
+            (
+                InputAction::Right,
+                &[Btn(Button::DPadRight), StickAxis(Axis::LeftX, false)],
+            ),
+            (
+                InputAction::Down,
+                &[Btn(Button::DPadDown), StickAxis(Axis::LeftY, false)],
+            ),
+            (InputAction::Jump, &[Btn(Button::A)]),
+            (InputAction::Fire, &[Btn(Button::B)]),
+            (InputAction::FireOffHand, &[Btn(Button::RightShoulder)]),
+            (InputAction::Pickup, &[Btn(Button::X)]),
+            (InputAction::Slide, &[Btn(Button::Y)]),
+        ])
+    }
+
+    pub fn get_bindings(&self, action: InputAction) -> &[InputBinding] {
+        self.bindings
+            .get(&action)
+            .map(|bindings| bindings.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Returns the names of the bindings of `action`, separated by slashes
+    pub fn get_binding_names(&self, action: InputAction) -> String {
+        let names = self
+            .get_bindings(action)
+            .iter()
+            .map(|binding| binding.get_name())
+            .collect::<Vec<_>>();
+
+        if names.is_empty() {
+            "-".to_string()
+        } else {
+            names.join(" / ")
+        }
+    }
+
+    /// This replaces all bindings of `action` with `binding`
+    pub fn rebind(&mut self, action: InputAction, binding: InputBinding) {
+        self.bindings.insert(action, vec![binding]);
+    }
+
+    /// Returns `true` if any binding of `action` is pressed, where `is_down` returns `true` for
+    /// any binding that is currently pressed
+    pub fn is_action_down<F: Fn(&InputBinding) -> bool>(
+        &self,
+        action: InputAction,
+        is_down: F,
+    ) -> bool {
+        self.get_bindings(action)
+            .iter()
+            .any(|binding| is_down(binding))
+    }
+
+    /// This builds the input of a single frame, where `is_down` returns `true` for any binding
+    /// that is currently pressed
+    pub fn collect<F: Fn(&InputBinding) -> bool>(&self, is_down: F) -> GameInput {
+        GameInput::from_actions(|action| self.is_action_down(action, &is_down))
+    }
+}
+
+/// The binding profile of each input scheme
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InputBindings {
+    #[serde(default = "InputProfile::default_keyboard_left")]
+    pub keyboard_left: InputProfile,
+    #[serde(default = "InputProfile::default_keyboard_right")]
+    pub keyboard_right: InputProfile,
+    /// This is shared by all gamepads
+    #[serde(default = "InputProfile::default_gamepad")]
+    pub gamepad: InputProfile,
+    /// The actions that are toggled on and off by pressing them, in stead of being held, for all
+    /// players. Only the actions in `InputAction::TOGGLEABLE` can be toggled.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub toggled_actions: Vec<InputAction>,
+}
+
+impl InputBindings {
+    pub fn is_toggled(&self, action: InputAction) -> bool {
+        InputAction::TOGGLEABLE.contains(&action) && self.toggled_actions.contains(&action)
+    }
+
+    /// This switches `action` between being held and being toggled
+    pub fn set_toggled(&mut self, action: InputAction, is_toggled: bool) {
+        self.toggled_actions.retain(|other| *other != action);
+
+        if is_toggled {
+            self.toggled_actions.push(action);
+        }
+    }
+
+    pub fn get_profile(&self, input_scheme: GameInputScheme) -> &InputProfile {
+        match input_scheme {
+            GameInputScheme::KeyboardLeft => &self.keyboard_left,
+            GameInputScheme::KeyboardRight => &self.keyboard_right,
+            GameInputScheme::Gamepad(_) => &self.gamepad,
+        }
+    }
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        InputBindings {
+            keyboard_left: InputProfile::default_keyboard_left(),
+            keyboard_right: InputProfile::default_keyboard_right(),
+            gamepad: InputProfile::default_gamepad(),
+            toggled_actions: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,343 @@
+//! Input recordings hold the input of every player, for every simulation tick of a local match,
+//! along with the random seed, the map and the characters of the players. Playing a recording back
+//! feeds the same input to a new match, started from the same seed, which can be used to reproduce
+//! bugs in movement and weapons, and to watch a replay of the match, with the sounds that were
+//! recorded along with it (see `audio::events`).
+//!
+//! Nothing verifies that the played back match ends in the same state as the recorded one. The
+//! simulation is meant to only depend on the seed and the input, but anything that draws from the
+//! simulation RNG, or changes the simulation, outside of `run_simulation_tick`, will make playback
+//! diverge from the recorded match.
+//!
+//! Local matches are recorded to the file given by the `FISHFIGHT_RECORD_INPUT` env var, if it is
+//! set, and a recording is played back on startup, in stead of showing the main menu, if its path
+//! is given by the `FISHFIGHT_REPLAY` env var.
+
+use std::f32::consts::PI;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use macroquad::prelude::*;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    audio::{get_recorded_sound_events, SoundEvent},
+    GameInput, Result,
+};
+
+/// The path that local matches are recorded to, if set
+pub const RECORD_INPUT_ENV_VAR: &str = "FISHFIGHT_RECORD_INPUT";
+/// The path of a recording that will be played back on startup, if set
+pub const REPLAY_ENV_VAR: &str = "FISHFIGHT_REPLAY";
+
+/// A player that joined the match while it was being recorded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InputRecordingJoin {
+    /// The tick that the player was spawned on
+    pub tick: usize,
+    pub character_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InputRecording {
+    /// The seed that the random number generator was seeded with, before the scene was created
+    pub seed: u64,
+    pub map_path: String,
+    /// The character ids of the players that the match was started with, in order of player id
+    pub player_characters: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub joins: Vec<InputRecordingJoin>,
+    /// The sound effects that were played during the match, which are played back in stead of the
+    /// sounds played by the simulation, when the recording is played back
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sound_events: Vec<SoundEvent>,
+    /// The input of every player, for every tick, packed by `pack_input`, so that every player
+    /// takes up `PACKED_INPUT_SIZE` bytes of a frame
+    pub frames: Vec<Vec<u8>>,
+}
+
+impl InputRecording {
+    pub fn new(seed: u64, map_path: &str, player_characters: &[String]) -> Self {
+        InputRecording {
+            seed,
+            map_path: map_path.to_string(),
+            player_characters: player_characters.to_vec(),
+            joins: Vec::new(),
+            sound_events: Vec::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file_contents = fs::read_to_string(path)?;
+        let res = serde_json::from_str(&file_contents)?;
+        Ok(res)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string(self)?;
+        fs::write(path, json)?;
+        Ok(())
+    }
+}
+
+/// The amount of bytes that the input of a single player is packed into
+const PACKED_INPUT_SIZE: usize = 2;
+
+/// The amount of directions that free aim is quantized to, when packed
+const AIM_DIRECTION_CNT: f32 = 255.0;
+
+/// This packs the buttons of `input` into the bits of the first byte and the aim direction into
+/// the second byte, where zero means that the player is not aiming
+fn pack_input(input: &GameInput) -> [u8; PACKED_INPUT_SIZE] {
+    let buttons = [
+        input.jump,
+        input.pickup,
+        input.fire,
+        input.fire_off_hand,
+        input.slide,
+        input.left,
+        input.right,
+        input.down,
+    ]
+    .iter()
+    .enumerate()
+    .fold(0, |res, (i, is_down)| res | ((*is_down as u8) << i));
+
+    let aim = if input.aim == Vec2::ZERO {
+        0
+    } else {
+        let angle = input.aim.y.atan2(input.aim.x).rem_euclid(2.0 * PI);
+        1 + ((angle / (2.0 * PI) * AIM_DIRECTION_CNT).round() as u8 % AIM_DIRECTION_CNT as u8)
+    };
+
+    [buttons, aim]
+}
+
+fn unpack_input(packed: &[u8]) -> GameInput {
+    let bits = packed.first().copied().unwrap_or(0);
+    let is_down = |i: u8| bits & (1 << i) != 0;
+
+    let aim = match packed.get(1).copied().unwrap_or(0) {
+        0 => Vec2::ZERO,
+        direction => {
+            let angle = (direction - 1) as f32 / AIM_DIRECTION_CNT * 2.0 * PI;
+            vec2(angle.cos(), angle.sin())
+        }
+    };
+
+    GameInput {
+        jump: is_down(0),
+        pickup: is_down(1),
+        fire: is_down(2),
+        fire_off_hand: is_down(3),
+        slide: is_down(4),
+        left: is_down(5),
+        right: is_down(6),
+        down: is_down(7),
+        aim,
+    }
+}
+
+/// This records the input of a local match, and saves it when the match is over, or exited
+pub struct InputRecorder {
+    path: PathBuf,
+    recording: InputRecording,
+}
+
+impl InputRecorder {
+    pub fn new<P: AsRef<Path>>(path: P, recording: InputRecording) -> Self {
+        InputRecorder {
+            path: path.as_ref().to_path_buf(),
+            recording,
+        }
+    }
+
+    /// This adds the input of all players for a single tick
+    pub fn push_frame(&mut self, input: &[GameInput]) {
+        let frame = input.iter().flat_map(pack_input).collect();
+        self.recording.frames.push(frame);
+    }
+
+    /// This records a player joining on the current tick
+    pub fn push_join(&mut self, character_id: &str) {
+        self.recording.joins.push(InputRecordingJoin {
+            tick: self.recording.frames.len(),
+            character_id: character_id.to_string(),
+        });
+    }
+
+    /// This saves the recording, along with the sound events that have been recorded so far
+    pub fn save(&self) {
+        let recording = InputRecording {
+            sound_events: get_recorded_sound_events(),
+            ..self.recording.clone()
+        };
+
+        if let Err(err) = recording.save(&self.path) {
+            println!("WARNING: Failed to save input recording: {}", err);
+        }
+    }
+}
+
+/// This plays back the input of a recording, a tick at a time
+pub struct InputPlayback {
+    recording: InputRecording,
+    tick: usize,
+}
+
+impl InputPlayback {
+    pub fn new(recording: InputRecording) -> Self {
+        InputPlayback { recording, tick: 0 }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.tick >= self.recording.frames.len()
+    }
+
+    /// Returns the character ids of the players that joined on the current tick
+    pub fn get_joins(&self) -> Vec<String> {
+        self.recording
+            .joins
+            .iter()
+            .filter(|join| join.tick == self.tick)
+            .map(|join| join.character_id.clone())
+            .collect()
+    }
+
+    /// Returns the input of `player_cnt` players, for the current tick, and advances to the next
+    /// tick. Players that are missing from the recording, or any ticks past its end, get no input.
+    pub fn next_frame(&mut self, player_cnt: usize) -> Vec<GameInput> {
+        let frame = self.recording.frames.get(self.tick);
+        self.tick += 1;
+
+        (0..player_cnt)
+            .map(|i| {
+                frame
+                    .and_then(|frame| frame.chunks(PACKED_INPUT_SIZE).nth(i))
+                    .map(unpack_input)
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_buttons(input: &GameInput) -> [bool; 8] {
+        [
+            input.jump,
+            input.pickup,
+            input.fire,
+            input.fire_off_hand,
+            input.slide,
+            input.left,
+            input.right,
+            input.down,
+        ]
+    }
+
+    #[test]
+    fn test_pack_input_buttons() {
+        for bits in 0..=u8::MAX {
+            let input = unpack_input(&[bits, 0]);
+            let packed = pack_input(&input);
+
+            assert_eq!(packed, [bits, 0]);
+
+            let repacked = unpack_input(&packed);
+            assert_eq!(get_buttons(&repacked), get_buttons(&input));
+            assert_eq!(repacked.aim, Vec2::ZERO);
+        }
+
+        let input = GameInput {
+            fire_off_hand: true,
+            down: true,
+            ..Default::default()
+        };
+
+        assert_eq!(pack_input(&input)[0], 0b1000_1000);
+    }
+
+    #[test]
+    fn test_pack_input_aim() {
+        let max_error = PI / AIM_DIRECTION_CNT + 0.0001;
+
+        for i in 0..1000 {
+            let angle = i as f32 / 1000.0 * 2.0 * PI;
+            let aim = vec2(angle.cos(), angle.sin());
+
+            let input = GameInput {
+                aim,
+                ..Default::default()
+            };
+
+            let packed = pack_input(&input);
+            assert_ne!(packed[1], 0);
+
+            let unpacked = unpack_input(&packed).aim;
+            assert!((unpacked.length() - 1.0).abs() < 0.0001);
+
+            let diff = (unpacked.y.atan2(unpacked.x) - angle).rem_euclid(2.0 * PI);
+            assert!(diff.min(2.0 * PI - diff) <= max_error);
+        }
+
+        // Unpacked directions are packed into the same byte again, so a recording is stable
+        for direction in 1..=u8::MAX {
+            let input = unpack_input(&[0, direction]);
+            assert_eq!(pack_input(&input)[1], direction);
+        }
+
+        assert_eq!(pack_input(&GameInput::default())[1], 0);
+    }
+
+    #[test]
+    fn test_input_playback() {
+        let recording =
+            InputRecording::new(42, "map.json", &["pescy".to_string(), "sharky".to_string()]);
+
+        let first = GameInput {
+            jump: true,
+            ..Default::default()
+        };
+
+        let second = GameInput {
+            left: true,
+            aim: vec2(1.0, 0.0),
+            ..Default::default()
+        };
+
+        let mut recorder = InputRecorder::new("recording.json", recording);
+        recorder.push_frame(&[first, second]);
+        recorder.push_join("fishy");
+        recorder.push_frame(&[second, first]);
+
+        let mut playback = InputPlayback::new(recorder.recording);
+
+        assert!(!playback.is_finished());
+        assert!(playback.get_joins().is_empty());
+
+        let frame = playback.next_frame(3);
+        assert_eq!(frame.len(), 3);
+        assert_eq!(get_buttons(&frame[0]), get_buttons(&first));
+        assert_eq!(get_buttons(&frame[1]), get_buttons(&second));
+        assert_eq!(frame[1].aim, vec2(1.0, 0.0));
+        assert_eq!(get_buttons(&frame[2]), [false; 8]);
+
+        assert_eq!(playback.get_joins(), vec!["fishy".to_string()]);
+
+        let frame = playback.next_frame(2);
+        assert_eq!(get_buttons(&frame[0]), get_buttons(&second));
+        assert_eq!(get_buttons(&frame[1]), get_buttons(&first));
+
+        assert!(playback.is_finished());
+
+        let frame = playback.next_frame(2);
+        assert!(frame
+            .iter()
+            .all(|input| get_buttons(input) == [false; 8] && input.aim == Vec2::ZERO));
+    }
+}
@@ -0,0 +1,124 @@
+//! When a kill decides the round, the game slows down and the camera zooms in on the killed player,
+//! for a brief moment. The slow motion slows down the rate of simulation ticks, so everything in
+//! the simulation, including respawns and effect delays, is slowed down with it.
+
+use macroquad::{
+    experimental::scene::{self, HandleUntyped, Node, RefMut},
+    prelude::*,
+};
+
+use crate::{
+    audio::duck_music, capabilities::NetworkReplicate, game::GameEvent, GameCamera, TimeScale,
+};
+
+pub struct KillCam {
+    /// The remaining time of the kill cam, in real time
+    timer: f32,
+    /// The position of the last kill in the current tick that was credited to a player. The kill
+    /// cam is focused on this, if the round is decided in the same tick.
+    last_kill_position: Option<Vec2>,
+}
+
+impl KillCam {
+    pub const LISTENER_ID: &'static str = "kill_cam";
+
+    const DURATION: f32 = 1.2;
+    const TIME_SCALE: f32 = 0.25;
+    /// The height of the area of the world that is visible while the kill cam is active
+    const ZOOM: f32 = 320.0;
+    const MUSIC_DUCK_AMOUNT: f32 = 0.5;
+
+    pub fn new() -> Self {
+        KillCam {
+            timer: 0.0,
+            last_kill_position: None,
+        }
+    }
+
+    /// The round is ended by the game mode, when it gets the event of the kill that decides it, so
+    /// the position of every kill is kept until the end of the tick, and the kill cam is started
+    /// on it, if the round ends with a winner in the same tick. Rounds that are decided by
+    /// something other than a kill do not start the kill cam.
+    pub fn on_game_event(event: &GameEvent) {
+        match *event {
+            GameEvent::PlayerKilled {
+                position,
+                killed_by: Some(_),
+                ..
+            } => {
+                if let Some(mut kill_cam) = scene::find_node_by_type::<KillCam>() {
+                    kill_cam.last_kill_position = Some(position);
+                }
+            }
+            GameEvent::RoundEnded { winner: Some(_) } => {
+                let position = scene::find_node_by_type::<KillCam>()
+                    .and_then(|mut kill_cam| kill_cam.last_kill_position.take());
+
+                if let Some(position) = position {
+                    Self::start(position);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// This starts the kill cam, focused on `position`. This can also be used for other dramatic
+    /// moments, like the end of a match.
+    pub fn start(position: Vec2) {
+        if !TimeScale::is_enabled() {
+            return;
+        }
+
+        if let Some(mut kill_cam) = scene::find_node_by_type::<KillCam>() {
+            TimeScale::add_slow_motion(Self::TIME_SCALE, Self::DURATION);
+            duck_music(Self::MUSIC_DUCK_AMOUNT, Self::DURATION);
+
+            if let Some(mut camera) = scene::find_node_by_type::<GameCamera>() {
+                camera.manual = Some((position, Self::ZOOM));
+            }
+
+            kill_cam.timer = Self::DURATION;
+        }
+    }
+}
+
+impl KillCam {
+    fn network_update(mut node: RefMut<Self>) {
+        node.last_kill_position = None;
+    }
+
+    fn network_capabilities() -> NetworkReplicate {
+        fn network_update(handle: HandleUntyped) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<KillCam>();
+            KillCam::network_update(node);
+        }
+
+        NetworkReplicate { network_update }
+    }
+}
+
+impl Default for KillCam {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Node for KillCam {
+    fn ready(mut node: RefMut<Self>) {
+        node.provides(Self::network_capabilities());
+    }
+
+    fn update(mut node: RefMut<Self>) {
+        if node.timer > 0.0 {
+            node.timer -= get_frame_time();
+
+            if node.timer <= 0.0 {
+                if let Some(mut camera) = scene::find_node_by_type::<GameCamera>() {
+                    camera.manual = None;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,324 @@
+//! The kill feed lists the latest kills in the top right corner of the screen, as messages like
+//! "P1 harpooned P2", with the icon of the weapon that dealt the killing blow. It is fed by the
+//! `PlayerKilled` game event, so it is shown both while playing and while spectating, and the
+//! amount of lines and the time each line is shown for can be changed in the config.
+
+use std::collections::VecDeque;
+
+use macroquad::{
+    color,
+    experimental::{
+        collections::storage,
+        scene::{self, Node, RefMut},
+    },
+    prelude::*,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game::{GameEvent, WeaponIcons},
+    items::ItemKind,
+    localization::{tr, tr_args},
+    text::{draw_aligned_text, HorizontalAlignment, VerticalAlignment},
+    Config, Player, Resources,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KillFeedConfig {
+    /// The time, in seconds, that each line is shown for
+    #[serde(default = "KillFeedConfig::default_duration")]
+    pub duration: f32,
+    /// The maximum amount of lines shown at once. Set this to zero to hide the kill feed.
+    #[serde(default = "KillFeedConfig::default_max_lines")]
+    pub max_lines: usize,
+}
+
+impl KillFeedConfig {
+    pub fn default_duration() -> f32 {
+        5.0
+    }
+
+    pub fn default_max_lines() -> usize {
+        5
+    }
+}
+
+impl Default for KillFeedConfig {
+    fn default() -> Self {
+        KillFeedConfig {
+            duration: Self::default_duration(),
+            max_lines: Self::default_max_lines(),
+        }
+    }
+}
+
+struct KillFeedLine {
+    killer: Option<(u8, Color)>,
+    victim: (u8, Color),
+    weapon_id: Option<String>,
+    verb: String,
+    timer: f32,
+}
+
+pub struct KillFeed {
+    lines: VecDeque<KillFeedLine>,
+    weapon_icons: WeaponIcons,
+}
+
+impl KillFeed {
+    pub const LISTENER_ID: &'static str = "kill_feed";
+
+    /// The string keys of the verb used for kills with weapons that do not specify one, and of the
+    /// verb used for deaths that were not caused by another player
+    const DEFAULT_VERB: &'static str = "kill_feed.killed";
+    const SUICIDE_VERB: &'static str = "kill_feed.died";
+
+    const MARGIN: f32 = 12.0;
+    const LINE_HEIGHT: f32 = 28.0;
+    const LINE_PADDING: f32 = 6.0;
+    const ICON_SIZE: f32 = 22.0;
+    const FONT_SIZE: u16 = 16;
+    const BACKGROUND_COLOR: Color = Color {
+        r: 0.0,
+        g: 0.0,
+        b: 0.0,
+        a: 0.5,
+    };
+
+    /// The time it takes for a line to fade out, at the end of its duration
+    const FADE_DURATION: f32 = 0.5;
+
+    pub fn new() -> Self {
+        KillFeed {
+            lines: VecDeque::new(),
+            weapon_icons: WeaponIcons::new(Self::ICON_SIZE),
+        }
+    }
+
+    pub fn on_game_event(event: &GameEvent) {
+        if let GameEvent::PlayerKilled {
+            player_id,
+            killed_by,
+            ref weapon_id,
+            ..
+        } = *event
+        {
+            if let Some(mut node) = scene::find_node_by_type::<KillFeed>() {
+                node.add_line(player_id, killed_by, weapon_id.clone());
+            }
+        }
+    }
+
+    fn add_line(&mut self, player_id: u8, killed_by: Option<u8>, weapon_id: Option<String>) {
+        let max_lines = storage::get::<Config>().kill_feed.max_lines;
+        if max_lines == 0 {
+            return;
+        }
+
+        let mut victim = None;
+        let mut killer = None;
+
+        for player in scene::find_nodes_by_type::<Player>() {
+            if player.id == player_id {
+                victim = Some((player.id, player.get_color()));
+            }
+
+            if Some(player.id) == killed_by {
+                killer = Some((player.id, player.get_color()));
+            }
+        }
+
+        let victim = match victim {
+            Some(victim) => victim,
+            None => return,
+        };
+
+        // Deaths that were not caused by another player are not shown with a weapon
+        let weapon_id = weapon_id.filter(|_| killer.is_some());
+
+        let verb_key = if killer.is_none() {
+            Self::SUICIDE_VERB.to_string()
+        } else {
+            weapon_id
+                .as_ref()
+                .and_then(|weapon_id| {
+                    let resources = storage::get::<Resources>();
+                    match &resources.items.get(weapon_id)?.kind {
+                        ItemKind::Weapon { params } => params.kill_verb.clone(),
+                        _ => None,
+                    }
+                })
+                .unwrap_or_else(|| Self::DEFAULT_VERB.to_string())
+        };
+
+        self.lines.push_back(KillFeedLine {
+            killer,
+            victim,
+            weapon_id,
+            verb: tr(&verb_key),
+            timer: 0.0,
+        });
+
+        while self.lines.len() > max_lines {
+            self.lines.pop_front();
+        }
+    }
+
+    fn get_player_name(player_id: u8) -> String {
+        tr_args("player.short_name", &[&(player_id + 1).to_string()])
+    }
+
+    /// Draws `text` with its left edge at `x` and returns the x of its right edge
+    fn draw_text(text: &str, x: f32, y: f32, color: Color) -> f32 {
+        draw_aligned_text(
+            text,
+            vec2(x, y),
+            HorizontalAlignment::Left,
+            VerticalAlignment::Center,
+            TextParams {
+                font_size: Self::FONT_SIZE,
+                color,
+                ..Default::default()
+            },
+        );
+
+        x + measure_text(text, None, Self::FONT_SIZE, 1.0).width
+    }
+
+    fn get_line_width(line: &KillFeedLine) -> f32 {
+        let mut parts = Vec::new();
+
+        if let Some((killer, _)) = line.killer {
+            parts.push(Self::get_player_name(killer));
+        }
+
+        parts.push(line.verb.clone());
+        parts.push(Self::get_player_name(line.victim.0));
+
+        let text_width: f32 = parts
+            .iter()
+            .map(|part| measure_text(part, None, Self::FONT_SIZE, 1.0).width)
+            .sum();
+
+        let icon_width = if line.weapon_id.is_some() {
+            Self::ICON_SIZE + Self::LINE_PADDING
+        } else {
+            0.0
+        };
+
+        text_width + icon_width + Self::LINE_PADDING * (parts.len() + 1) as f32
+    }
+
+    fn draw_line(&mut self, i: usize, y: f32, duration: f32) {
+        let (width, alpha) = {
+            let line = &self.lines[i];
+            let remaining = duration - line.timer;
+
+            (
+                Self::get_line_width(line),
+                (remaining / Self::FADE_DURATION).clamp(0.0, 1.0),
+            )
+        };
+
+        let fade = |color: Color| Color {
+            a: color.a * alpha,
+            ..color
+        };
+
+        let x = screen_width() - Self::MARGIN - width;
+        let center_y = y + Self::LINE_HEIGHT / 2.0;
+
+        draw_rectangle(x, y, width, Self::LINE_HEIGHT, fade(Self::BACKGROUND_COLOR));
+
+        let (killer, victim, weapon_id, verb) = {
+            let line = &self.lines[i];
+            (
+                line.killer,
+                line.victim,
+                line.weapon_id.clone(),
+                line.verb.clone(),
+            )
+        };
+
+        let mut x = x + Self::LINE_PADDING;
+
+        if let Some((killer, color)) = killer {
+            x = Self::draw_text(&Self::get_player_name(killer), x, center_y, fade(color));
+            x += Self::LINE_PADDING;
+        }
+
+        if let Some(weapon_id) = weapon_id {
+            if let Some(icon) = self.weapon_icons.get(&weapon_id) {
+                let size = icon.get_size();
+                let position = vec2(
+                    x + (Self::ICON_SIZE - size.x) / 2.0,
+                    center_y - size.y / 2.0,
+                );
+
+                icon.draw(position, 0.0, false, false);
+            }
+
+            x += Self::ICON_SIZE + Self::LINE_PADDING;
+        }
+
+        if killer.is_some() {
+            x = Self::draw_text(&verb, x, center_y, fade(color::WHITE));
+            x += Self::LINE_PADDING;
+
+            Self::draw_text(
+                &Self::get_player_name(victim.0),
+                x,
+                center_y,
+                fade(victim.1),
+            );
+        } else {
+            x = Self::draw_text(
+                &Self::get_player_name(victim.0),
+                x,
+                center_y,
+                fade(victim.1),
+            );
+            x += Self::LINE_PADDING;
+
+            Self::draw_text(&verb, x, center_y, fade(color::WHITE));
+        }
+    }
+}
+
+impl Default for KillFeed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Node for KillFeed {
+    fn update(mut node: RefMut<Self>) {
+        let duration = storage::get::<Config>().kill_feed.duration;
+        let dt = get_frame_time();
+
+        for line in &mut node.lines {
+            line.timer += dt;
+        }
+
+        node.lines.retain(|line| line.timer < duration);
+    }
+
+    fn draw(mut node: RefMut<Self>) {
+        if node.lines.is_empty() {
+            return;
+        }
+
+        let duration = storage::get::<Config>().kill_feed.duration;
+
+        push_camera_state();
+        set_default_camera();
+
+        for i in 0..node.lines.len() {
+            let y = Self::MARGIN + i as f32 * (Self::LINE_HEIGHT + Self::LINE_PADDING);
+            node.draw_line(i, y, duration);
+        }
+
+        pop_camera_state();
+    }
+}
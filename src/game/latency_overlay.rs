@@ -0,0 +1,182 @@
+//! The latency overlay is a debug overlay that measures the time from input being received, to
+//! it being applied by a simulation tick (input-to-simulation), and to the first frame that shows
+//! the result being presented (input-to-photon). This is used to tune the fixed timestep and will
+//! be used to evaluate rollback.
+//!
+//! Macroquad does not timestamp input events, so input is timestamped at the start of the frame
+//! that it is first seen in, which is right after the events of the frame have been processed.
+//! Frames are considered presented at the start of the next frame, as that is when `next_frame`
+//! returns, after the buffers have been swapped. This means that the measured latencies do not
+//! include the time spent in the OS and the display, but they do include waiting for vsync.
+//!
+//! The overlay is only added in debug builds, and it is toggled with F3.
+
+use std::collections::VecDeque;
+
+use macroquad::{
+    color,
+    experimental::scene::{self, Node, RefMut},
+    prelude::*,
+};
+
+use crate::{
+    gui,
+    text::{draw_aligned_text, HorizontalAlignment, VerticalAlignment},
+};
+
+/// A running window of latency samples, in seconds
+#[derive(Debug, Default)]
+struct LatencySamples {
+    samples: VecDeque<f64>,
+}
+
+impl LatencySamples {
+    /// The amount of samples that the average and the maximum are calculated from
+    const CAPACITY: usize = 60;
+
+    fn push(&mut self, sample: f64) {
+        if self.samples.len() >= Self::CAPACITY {
+            self.samples.pop_front();
+        }
+
+        self.samples.push_back(sample);
+    }
+
+    fn get_average(&self) -> Option<f64> {
+        if self.samples.is_empty() {
+            None
+        } else {
+            Some(self.samples.iter().sum::<f64>() / self.samples.len() as f64)
+        }
+    }
+
+    fn get_max(&self) -> Option<f64> {
+        self.samples.iter().copied().reduce(f64::max)
+    }
+
+    /// Returns the average and maximum latency, in milliseconds, formatted for the overlay
+    fn format(&self) -> String {
+        match (self.get_average(), self.get_max()) {
+            (Some(average), Some(max)) => {
+                format!("avg {:.1} ms, max {:.1} ms", average * 1000.0, max * 1000.0)
+            }
+            _ => "-".to_string(),
+        }
+    }
+}
+
+pub struct LatencyOverlay {
+    is_enabled: bool,
+    /// The timestamps of input that has been received, but not yet applied by a tick
+    pending_input: Vec<f64>,
+    /// The timestamps of input that has been applied by a tick on this frame, waiting for the
+    /// frame to be presented
+    applied_input: Vec<f64>,
+    input_to_simulation: LatencySamples,
+    input_to_photon: LatencySamples,
+}
+
+impl LatencyOverlay {
+    const TOGGLE_KEY: KeyCode = KeyCode::F3;
+
+    const FONT_SIZE: u16 = 18;
+    const LINE_HEIGHT: f32 = 22.0;
+    const MARGIN: f32 = 12.0;
+
+    pub fn new() -> Self {
+        LatencyOverlay {
+            is_enabled: false,
+            pending_input: Vec::new(),
+            applied_input: Vec::new(),
+            input_to_simulation: LatencySamples::default(),
+            input_to_photon: LatencySamples::default(),
+        }
+    }
+
+    /// This should be called by every simulation tick that applies player input, so that the
+    /// input that is pending is considered applied
+    pub fn on_input_applied() {
+        if let Some(mut node) = scene::find_node_by_type::<LatencyOverlay>() {
+            if !node.is_enabled || node.pending_input.is_empty() {
+                return;
+            }
+
+            let now = get_time();
+
+            let pending_input = std::mem::take(&mut node.pending_input);
+            for timestamp in pending_input {
+                node.input_to_simulation.push(now - timestamp);
+                node.applied_input.push(timestamp);
+            }
+        }
+    }
+}
+
+impl Default for LatencyOverlay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Node for LatencyOverlay {
+    fn update(mut node: RefMut<Self>) {
+        if is_key_pressed(Self::TOGGLE_KEY) {
+            node.is_enabled = !node.is_enabled;
+
+            node.pending_input.clear();
+            node.applied_input.clear();
+        }
+
+        if !node.is_enabled {
+            return;
+        }
+
+        let now = get_time();
+
+        // The previous frame was presented before this frame started
+        let applied_input = std::mem::take(&mut node.applied_input);
+        for timestamp in applied_input {
+            node.input_to_photon.push(now - timestamp);
+        }
+
+        if gui::is_any_input_pressed() {
+            node.pending_input.push(now);
+        }
+    }
+
+    fn draw(node: RefMut<Self>) {
+        if !node.is_enabled {
+            return;
+        }
+
+        let lines = [
+            format!("Input to simulation: {}", node.input_to_simulation.format()),
+            format!("Input to photon: {}", node.input_to_photon.format()),
+            format!("Frame time: {:.1} ms", get_frame_time() * 1000.0),
+        ];
+
+        push_camera_state();
+        set_default_camera();
+
+        for (i, line) in lines.iter().enumerate() {
+            let position = vec2(
+                Self::MARGIN,
+                screen_height() - Self::MARGIN - (lines.len() - i) as f32 * Self::LINE_HEIGHT,
+            );
+
+            draw_aligned_text(
+                line,
+                position,
+                HorizontalAlignment::Left,
+                VerticalAlignment::Top,
+                TextParams {
+                    font_size: Self::FONT_SIZE,
+                    color: color::WHITE,
+                    ..Default::default()
+                },
+            );
+        }
+
+        pop_camera_state();
+    }
+}
@@ -10,70 +10,534 @@ use fishsticks::{Button, GamepadContext};
 use macroquad::{
     experimental::{
         collections::storage,
-        scene::{self, Handle, Node, NodeWith, RefMut},
+        scene::{self, Handle, Node, RefMut},
     },
+    prelude::*,
     ui::root_ui,
 };
 
 use crate::{
-    capabilities::NetworkReplicate,
-    collect_input, exit_to_main_menu,
-    gui::{self, GAME_MENU_RESULT_MAIN_MENU, GAME_MENU_RESULT_QUIT},
-    is_gamepad_btn_pressed, quit_to_desktop, GameInputScheme, Player,
+    collect_input, console, exit_to_main_menu,
+    game::{
+        collect_aim, is_action_pressed, is_cutscene_playing, run_simulation_tick, InputAction,
+        InputPlayback, InputRecorder, InputToggles, LatencyOverlay, MapVoteScreen, MatchStats,
+        ResultsScreen, ResultsVote, SimulationTimer, SIMULATION_DELTA_TIME,
+    },
+    gui::{
+        self, FocusNavigator, ToastPriority, GAME_MENU_RESULT_MAIN_MENU, GAME_MENU_RESULT_QUIT,
+        GAME_MENU_RESULT_RESTART,
+    },
+    localization::{tr, tr_args},
+    next_map,
+    player::PlayerCharacterParams,
+    profiler::ProfileScope,
+    quit_to_desktop, restart_match, GameInputScheme, Player, Resources,
 };
 
+/// This holds the state of a local player that has pressed start mid-match and is picking a
+/// character, or waiting for the next respawn wave to drop into the match.
+struct PendingJoin {
+    input_scheme: GameInputScheme,
+    /// The focus of the navigator is the index of the selected character
+    navigator: FocusNavigator,
+    is_confirmed: bool,
+}
+
+impl PendingJoin {
+    fn new(input_scheme: GameInputScheme) -> Self {
+        PendingJoin {
+            input_scheme,
+            navigator: FocusNavigator::new(Some(0)),
+            is_confirmed: false,
+        }
+    }
+
+    fn get_selection(&self) -> usize {
+        self.navigator.get_focus().unwrap_or(0)
+    }
+}
+
 pub struct LocalGame {
-    player1_input: GameInputScheme,
-    player1: Handle<Player>,
-    player2_input: GameInputScheme,
-    player2: Handle<Player>,
+    players: Vec<(GameInputScheme, Handle<Player>)>,
+    /// The state of the toggled actions of each player, in the same order as `players`
+    input_toggles: Vec<InputToggles>,
+    pending_joins: Vec<PendingJoin>,
+    /// The indices, in `players`, of players whose gamepad has been disconnected. These players
+    /// will get their gamepad back if it is reconnected, or they can be assigned another device
+    /// slot by joining on it.
+    disconnected_players: Vec<usize>,
+    respawn_wave_timer: f32,
+    /// The path of the map that is being played
+    map_path: String,
+    /// This is set when the match is over
+    results: Option<ResultsScreen>,
+    /// This is set when the players have voted for the next map, on the results screen
+    map_vote: Option<MapVoteScreen>,
+    /// This is set when the input of the match is being recorded
+    recorder: Option<InputRecorder>,
+    /// This is set when the input of the players is played back from a recording, in stead of
+    /// being collected from their input devices
+    playback: Option<InputPlayback>,
 }
 
 impl LocalGame {
+    /// The interval between each wave of late-joining players being spawned into the match
+    const RESPAWN_WAVE_INTERVAL: f32 = 5.0;
+
     pub fn new(
         player_input: Vec<GameInputScheme>,
-        player1: Handle<Player>,
-        player2: Handle<Player>,
+        players: Vec<Handle<Player>>,
+        map_path: &str,
     ) -> LocalGame {
+        assert_eq!(
+            player_input.len(),
+            players.len(),
+            "Local Game: The amount of input schemes does not match the amount of players"
+        );
+
+        let input_toggles = vec![InputToggles::default(); players.len()];
+
+        LocalGame {
+            players: player_input.into_iter().zip(players).collect(),
+            input_toggles,
+            pending_joins: Vec::new(),
+            disconnected_players: Vec::new(),
+            respawn_wave_timer: 0.0,
+            map_path: map_path.to_string(),
+            results: None,
+            map_vote: None,
+            recorder: None,
+            playback: None,
+        }
+    }
+
+    pub fn with_recorder(self, recorder: InputRecorder) -> Self {
         LocalGame {
-            player1,
-            player2,
-            player1_input: player_input[0],
-            player2_input: player_input[1],
+            recorder: Some(recorder),
+            ..self
+        }
+    }
+
+    pub fn with_playback(self, playback: InputPlayback) -> Self {
+        LocalGame {
+            playback: Some(playback),
+            ..self
+        }
+    }
+
+    fn save_recording(&self) {
+        if let Some(recorder) = &self.recorder {
+            recorder.save();
+        }
+    }
+
+    fn is_input_scheme_in_use(&self, input_scheme: GameInputScheme) -> bool {
+        self.players.iter().any(|(other, _)| *other == input_scheme)
+            || self
+                .pending_joins
+                .iter()
+                .any(|join| join.input_scheme == input_scheme)
+    }
+
+    fn get_player_name(player: Handle<Player>) -> String {
+        let id = scene::get_node(player).id;
+        tr_args("player.name", &[&(id + 1).to_string()])
+    }
+
+    /// This checks the gamepads of all players and opens the game menu if any of them have been
+    /// disconnected. Players get their gamepad back, when it is reconnected.
+    fn update_disconnected_players(&mut self) {
+        let gamepad_context = storage::get::<GamepadContext>();
+
+        for (i, (input_scheme, player)) in self.players.iter().enumerate() {
+            if let GameInputScheme::Gamepad(ix) = *input_scheme {
+                let is_connected = gamepad_context.gamepad(ix).is_some();
+                let is_disconnected = self.disconnected_players.contains(&i);
+
+                if !is_connected && !is_disconnected {
+                    self.disconnected_players.push(i);
+
+                    let name = Self::get_player_name(*player);
+                    gui::show_toast(
+                        &tr_args("toast.player_gamepad_disconnected", &[&name]),
+                        ToastPriority::High,
+                    );
+
+                    gui::open_game_menu();
+                } else if is_connected && is_disconnected {
+                    self.disconnected_players.retain(|other| *other != i);
+
+                    let name = Self::get_player_name(*player);
+                    gui::show_toast(
+                        &tr_args("toast.player_gamepad_reconnected", &[&name]),
+                        ToastPriority::Normal,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Returns the device slots that join was pressed on this frame. A gamepad joins by pressing
+    /// start, and a keyboard slot by pressing its jump binding, or enter for the first keyboard
+    /// slot that is not in use, like in the lobby.
+    fn get_join_pressed(&self) -> Vec<GameInputScheme> {
+        let mut res = Vec::new();
+
+        let mut is_enter_pressed = is_key_pressed(KeyCode::Enter);
+
+        for slot in GameInputScheme::KEYBOARD_SLOTS {
+            if self.is_input_scheme_in_use(*slot) {
+                continue;
+            }
+
+            if is_enter_pressed || is_action_pressed(*slot, InputAction::Jump) {
+                is_enter_pressed = false;
+                res.push(*slot);
+            }
+        }
+
+        let gamepad_context = storage::get::<GamepadContext>();
+        for (ix, gamepad) in gamepad_context.gamepads() {
+            if gamepad.digital_inputs.just_activated(Button::Start) {
+                res.push(GameInputScheme::Gamepad(ix));
+            }
+        }
+
+        res
+    }
+
+    /// Returns `true` if the game menu should be toggled. Join pressed on a device slot that is
+    /// not in use will assign it to a player whose gamepad has been disconnected or, if there are
+    /// none, start a late join. Start pressed on a gamepad that is in use toggles the menu, as
+    /// does escape.
+    fn check_start_pressed(&mut self) -> bool {
+        let mut should_toggle_menu = is_key_pressed(KeyCode::Escape);
+
+        for input_scheme in self.get_join_pressed() {
+            if self.is_input_scheme_in_use(input_scheme) {
+                if let GameInputScheme::Gamepad(_) = input_scheme {
+                    should_toggle_menu = true;
+                }
+            } else if !self.disconnected_players.is_empty() {
+                let i = self.disconnected_players.remove(0);
+                self.players[i].0 = input_scheme;
+
+                let name = Self::get_player_name(self.players[i].1);
+                gui::show_toast(
+                    &tr_args("toast.player_gamepad_reconnected", &[&name]),
+                    ToastPriority::Normal,
+                );
+            } else if !gui::is_game_menu_open() && self.playback.is_none() {
+                self.pending_joins.push(PendingJoin::new(input_scheme));
+            }
+        }
+
+        should_toggle_menu
+    }
+
+    /// This updates the character selection of all pending joins, with the navigation input of
+    /// their own device slot. A join is cancelled if its gamepad is disconnected.
+    fn update_pending_joins(&mut self) {
+        let character_cnt = {
+            let resources = storage::get::<Resources>();
+            resources.player_characters.len()
+        };
+
+        let mut i = 0;
+        while i < self.pending_joins.len() {
+            let join = &mut self.pending_joins[i];
+
+            if let GameInputScheme::Gamepad(ix) = join.input_scheme {
+                let is_connected = storage::get::<GamepadContext>().gamepad(ix).is_some();
+
+                if !is_connected {
+                    self.pending_joins.remove(i);
+                    continue;
+                }
+            }
+
+            let input = join.navigator.update_scheme_input(join.input_scheme);
+
+            if input.cancel {
+                self.pending_joins.remove(i);
+                continue;
+            }
+
+            if !join.is_confirmed {
+                if input.left {
+                    join.navigator.move_focus(-1, character_cnt, |_| true);
+                } else if input.right {
+                    join.navigator.move_focus(1, character_cnt, |_| true);
+                }
+
+                join.is_confirmed = input.confirm;
+            }
+
+            i += 1;
+        }
+    }
+
+    /// This spawns all players that have confirmed their character selection
+    fn spawn_respawn_wave(&mut self) {
+        let mut i = 0;
+        while i < self.pending_joins.len() {
+            if self.pending_joins[i].is_confirmed {
+                let join = self.pending_joins.remove(i);
+
+                let params = {
+                    let resources = storage::get::<Resources>();
+                    resources.player_characters[join.get_selection()].clone()
+                };
+
+                self.spawn_player(join.input_scheme, params);
+
+                continue;
+            }
+
+            i += 1;
+        }
+    }
+
+    /// This spawns a player that joined the match late
+    fn spawn_player(&mut self, input_scheme: GameInputScheme, params: PlayerCharacterParams) {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.push_join(&params.id);
+        }
+
+        let id = self.players.len() as u8;
+        let player = scene::add_node(Player::new(id, params));
+
+        self.players.push((input_scheme, player));
+        self.input_toggles.push(InputToggles::default());
+
+        let name = Self::get_player_name(player);
+        gui::show_toast(
+            &tr_args("toast.player_joined", &[&name]),
+            ToastPriority::Normal,
+        );
+    }
+
+    /// This spawns the players that joined on the current tick of the recording that is played
+    /// back. They are given a keyboard input scheme, which is only used for the votes at the end
+    /// of the match.
+    fn spawn_playback_joins(&mut self) {
+        let character_ids = match &self.playback {
+            Some(playback) => playback.get_joins(),
+            None => return,
+        };
+
+        for character_id in character_ids {
+            let params = {
+                let resources = storage::get::<Resources>();
+                resources
+                    .player_characters
+                    .iter()
+                    .find(|params| params.id == character_id)
+                    .cloned()
+            };
+
+            match params {
+                Some(params) => self.spawn_player(GameInputScheme::KeyboardLeft, params),
+                None => println!(
+                    "WARNING: Input playback: Invalid player character id '{}'",
+                    character_id
+                ),
+            }
+        }
+    }
+
+    /// Returns the input scheme and id of every player, for the votes at the end of the match
+    fn get_voters(&self) -> Vec<(GameInputScheme, u8)> {
+        self.players
+            .iter()
+            .map(|(input_scheme, player)| (*input_scheme, scene::get_node(*player).id))
+            .collect()
+    }
+
+    fn is_match_over() -> bool {
+        scene::find_node_by_type::<MatchStats>()
+            .map(|match_stats| match_stats.is_match_over())
+            .unwrap_or(false)
+    }
+
+    /// This runs a single tick of the simulation
+    fn tick(&mut self) {
+        let _scope = ProfileScope::new("tick");
+
+        #[cfg(debug_assertions)]
+        crate::physics::PhysicsDebugOverlay::clear();
+
+        self.respawn_wave_timer += SIMULATION_DELTA_TIME;
+        if self.respawn_wave_timer >= Self::RESPAWN_WAVE_INTERVAL {
+            self.respawn_wave_timer = 0.0;
+            self.spawn_respawn_wave();
+        }
+
+        self.spawn_playback_joins();
+
+        let input = if let Some(playback) = &mut self.playback {
+            let was_finished = playback.is_finished();
+            let res = playback.next_frame(self.players.len());
+
+            if playback.is_finished() && !was_finished {
+                gui::show_toast(&tr("toast.playback_finished"), ToastPriority::Normal);
+            }
+
+            res
+        } else {
+            self.players
+                .iter()
+                .zip(&mut self.input_toggles)
+                .map(|((input_scheme, player), toggles)| {
+                    let mut input = toggles.apply(collect_input(*input_scheme));
+
+                    let origin = scene::get_node(*player).body.position;
+                    input.aim = collect_aim(*input_scheme, origin);
+
+                    input
+                })
+                .collect::<Vec<_>>()
+        };
+
+        if let Some(recorder) = &mut self.recorder {
+            recorder.push_frame(&input);
+        }
+
+        for ((_, player), input) in self.players.iter().zip(input) {
+            scene::get_node(*player).apply_input(input);
+        }
+
+        LatencyOverlay::on_input_applied();
+
+        run_simulation_tick();
+
+        // Pause the game if any player has gone AFK. The idle timers are reset, so that the
+        // game will not be paused again, immediately, when it is resumed.
+        let is_any_afk = self
+            .players
+            .iter()
+            .any(|(_, player)| scene::get_node(*player).is_afk);
+
+        if is_any_afk {
+            for (_, player) in &self.players {
+                let mut player = scene::get_node(*player);
+                player.is_afk = false;
+                player.idle_timer = 0.0;
+            }
+
+            gui::open_game_menu();
         }
     }
 }
 
 impl Node for LocalGame {
-    fn fixed_update(node: RefMut<Self>) {
+    fn update(mut node: RefMut<Self>) {
+        if console::is_console_open() || is_cutscene_playing() {
+            return;
+        }
+
         #[cfg(debug_assertions)]
         if macroquad::input::is_key_pressed(macroquad::prelude::KeyCode::U) {
             crate::debug::toggle_debug_draw();
         }
 
-        {
-            let gamepad_context = storage::get::<GamepadContext>();
-            if macroquad::input::is_key_pressed(macroquad::prelude::KeyCode::Escape)
-                || is_gamepad_btn_pressed(Some(&gamepad_context), Button::Start)
-            {
-                gui::toggle_game_menu();
+        #[cfg(debug_assertions)]
+        if macroquad::input::is_key_pressed(macroquad::prelude::KeyCode::I) {
+            crate::debug::toggle_physics_debug_draw();
+        }
+
+        if node.results.is_none() && Self::is_match_over() {
+            gui::close_game_menu();
+
+            node.save_recording();
+
+            let players = node.get_voters();
+            node.results = Some(ResultsScreen::new(&players));
+        }
+
+        if let Some(map_vote) = &mut node.map_vote {
+            if let Some(map_index) = map_vote.update() {
+                next_map(map_index);
+            }
+
+            return;
+        }
+
+        if let Some(results) = &mut node.results {
+            match results.update() {
+                Some(ResultsVote::Rematch) => restart_match(),
+                Some(ResultsVote::NextMap) => {
+                    let players = node.get_voters();
+                    node.map_vote = Some(MapVoteScreen::new(&players, &node.map_path));
+                }
+                None => {}
             }
+
+            return;
+        }
+
+        node.update_disconnected_players();
+
+        // Pending joins are updated before new ones are started, so that the press that starts
+        // a join on a keyboard slot, which is the jump binding, does not also confirm it
+        if !gui::is_game_menu_open() {
+            node.update_pending_joins();
+        }
+
+        if node.check_start_pressed() {
+            gui::toggle_game_menu();
         }
 
+        // The simulation is paused by not running any ticks while the game menu is open. As all
+        // timers of the simulation, like respawns, slides and effect delays, are advanced by the
+        // tick, this freezes all of them, and the simulation timer is not advanced either, so the
+        // time spent in the menu is not caught up on when the game is resumed.
         if !gui::is_game_menu_open() {
-            scene::get_node(node.player1).apply_input(collect_input(node.player1_input));
-            scene::get_node(node.player2).apply_input(collect_input(node.player2_input));
+            for _ in 0..SimulationTimer::advance() {
+                node.tick();
 
-            for NodeWith { node, capability } in scene::find_nodes_with::<NetworkReplicate>() {
-                (capability.network_update)(node);
+                if gui::is_game_menu_open() || Self::is_match_over() {
+                    break;
+                }
             }
         }
     }
 
-    fn draw(_: RefMut<Self>) {
+    fn draw(mut node: RefMut<Self>) {
+        if let Some(map_vote) = &node.map_vote {
+            map_vote.draw();
+            return;
+        }
+
+        if let Some(results) = &mut node.results {
+            results.draw();
+            return;
+        }
+
+        if !node.pending_joins.is_empty() {
+            let resources = storage::get::<Resources>();
+
+            for (i, join) in node.pending_joins.iter().enumerate() {
+                let player_number = node.players.len() + i + 1;
+                let character_name = &resources.player_characters[join.get_selection()].name;
+
+                gui::draw_join_game_panel(
+                    &mut *root_ui(),
+                    i,
+                    player_number,
+                    join.input_scheme,
+                    character_name,
+                    join.is_confirmed,
+                );
+            }
+        }
+
         if gui::is_game_menu_open() {
             if let Some(res) = gui::draw_game_menu(&mut *root_ui()) {
+                node.save_recording();
+
                 match res.into_usize() {
+                    GAME_MENU_RESULT_RESTART => restart_match(),
                     GAME_MENU_RESULT_MAIN_MENU => exit_to_main_menu(),
                     GAME_MENU_RESULT_QUIT => quit_to_desktop(),
                     _ => {}
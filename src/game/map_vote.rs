@@ -0,0 +1,235 @@
+//! The map vote screen is shown by `LocalGame` when the players have voted for the next map on the
+//! results screen. It shows the previews of a few random maps, other than the current one, and a
+//! random option, and lets every player vote with their own input device. When all players have
+//! voted, the option with the most votes wins, with ties broken at random.
+
+use macroquad::{color, experimental::collections::storage, prelude::*};
+
+use crate::{
+    game::Vote,
+    localization::tr,
+    text::{draw_aligned_text, HorizontalAlignment, VerticalAlignment},
+    GameInputScheme, Resources,
+};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum MapVoteOption {
+    /// A map, by its index in `Resources::maps`
+    Map(usize),
+    /// A random map, from all the maps that can be voted for
+    Random,
+}
+
+pub struct MapVoteScreen {
+    vote: Vote,
+    options: Vec<MapVoteOption>,
+    /// The indices of all the maps that can be picked by the random option
+    candidates: Vec<usize>,
+}
+
+impl MapVoteScreen {
+    /// The amount of maps that are offered, in addition to the random option
+    const MAP_OPTION_CNT: usize = 3;
+
+    const PREVIEW_WIDTH: f32 = 192.0;
+    const PREVIEW_HEIGHT: f32 = 120.0;
+    const SPACING: f32 = 24.0;
+    const TITLE_FONT_SIZE: u16 = 32;
+    const FONT_SIZE: u16 = 18;
+    const MARKER_OFFSET: f32 = 40.0;
+
+    const BACKGROUND_COLOR: Color = Color {
+        r: 0.0,
+        g: 0.0,
+        b: 0.0,
+        a: 0.75,
+    };
+    const RANDOM_BACKGROUND_COLOR: Color = Color {
+        r: 0.2,
+        g: 0.2,
+        b: 0.25,
+        a: 1.0,
+    };
+
+    /// `current_map` is the path of the map that was just played, which will not be offered,
+    /// unless it is the only map
+    pub fn new(players: &[(GameInputScheme, u8)], current_map: &str) -> Self {
+        let mut candidates = {
+            let resources = storage::get::<Resources>();
+
+            let mut res = resources
+                .maps
+                .iter()
+                .enumerate()
+                .filter(|(_, map_resource)| map_resource.meta.path != current_map)
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>();
+
+            if res.is_empty() {
+                res = (0..resources.maps.len()).collect();
+            }
+
+            res
+        };
+
+        // Shuffle, so that the first maps are a random pick
+        for i in (1..candidates.len()).rev() {
+            let j = rand::gen_range(0, i + 1);
+            candidates.swap(i, j);
+        }
+
+        let mut options = candidates
+            .iter()
+            .take(Self::MAP_OPTION_CNT)
+            .map(|i| MapVoteOption::Map(*i))
+            .collect::<Vec<_>>();
+
+        options.push(MapVoteOption::Random);
+
+        MapVoteScreen {
+            vote: Vote::new(players, options.len()),
+            options,
+            candidates,
+        }
+    }
+
+    /// This updates the votes of all players and returns the index of the winning map, in
+    /// `Resources::maps`, when all players have voted
+    pub fn update(&mut self) -> Option<usize> {
+        if !self.vote.update() {
+            return None;
+        }
+
+        let counts = self.vote.get_counts();
+        let max = counts.iter().copied().max().unwrap_or(0);
+
+        let winners = self
+            .options
+            .iter()
+            .zip(counts)
+            .filter(|(_, cnt)| *cnt == max)
+            .map(|(option, _)| *option)
+            .collect::<Vec<_>>();
+
+        let winner = winners[rand::gen_range(0, winners.len())];
+
+        match winner {
+            MapVoteOption::Map(i) => Some(i),
+            MapVoteOption::Random => {
+                if self.candidates.is_empty() {
+                    None
+                } else {
+                    Some(self.candidates[rand::gen_range(0, self.candidates.len())])
+                }
+            }
+        }
+    }
+
+    fn draw_text(text: &str, position: Vec2, font_size: u16) {
+        draw_aligned_text(
+            text,
+            position,
+            HorizontalAlignment::Center,
+            VerticalAlignment::Center,
+            TextParams {
+                font_size,
+                color: color::WHITE,
+                ..Default::default()
+            },
+        );
+    }
+
+    pub fn draw(&self) {
+        push_camera_state();
+        set_default_camera();
+
+        draw_rectangle(
+            0.0,
+            0.0,
+            screen_width(),
+            screen_height(),
+            Self::BACKGROUND_COLOR,
+        );
+
+        let option_cnt = self.options.len() as f32;
+        let total_width = Self::PREVIEW_WIDTH * option_cnt + Self::SPACING * (option_cnt - 1.0);
+        let start_x = (screen_width() - total_width) / 2.0;
+        let y = (screen_height() - Self::PREVIEW_HEIGHT) / 2.0;
+
+        Self::draw_text(
+            &tr("map_vote.title"),
+            vec2(screen_width() / 2.0, y - Self::SPACING * 3.0),
+            Self::TITLE_FONT_SIZE,
+        );
+
+        let resources = storage::get::<Resources>();
+
+        for (i, option) in self.options.iter().enumerate() {
+            let x = start_x + i as f32 * (Self::PREVIEW_WIDTH + Self::SPACING);
+            let center_x = x + Self::PREVIEW_WIDTH / 2.0;
+
+            let name = match *option {
+                MapVoteOption::Map(map_index) => {
+                    let map_resource = &resources.maps[map_index];
+
+                    draw_texture_ex(
+                        map_resource.preview,
+                        x,
+                        y,
+                        color::WHITE,
+                        DrawTextureParams {
+                            dest_size: Some(vec2(Self::PREVIEW_WIDTH, Self::PREVIEW_HEIGHT)),
+                            ..Default::default()
+                        },
+                    );
+
+                    map_resource.meta.name.clone()
+                }
+                MapVoteOption::Random => {
+                    draw_rectangle(
+                        x,
+                        y,
+                        Self::PREVIEW_WIDTH,
+                        Self::PREVIEW_HEIGHT,
+                        Self::RANDOM_BACKGROUND_COLOR,
+                    );
+
+                    Self::draw_text(
+                        "?",
+                        vec2(center_x, y + Self::PREVIEW_HEIGHT / 2.0),
+                        Self::TITLE_FONT_SIZE * 2,
+                    );
+
+                    tr("map_vote.random")
+                }
+            };
+
+            draw_rectangle_lines(
+                x,
+                y,
+                Self::PREVIEW_WIDTH,
+                Self::PREVIEW_HEIGHT,
+                2.0,
+                color::WHITE,
+            );
+
+            let name_y = y + Self::PREVIEW_HEIGHT + Self::SPACING / 2.0;
+
+            Self::draw_text(&name, vec2(center_x, name_y), Self::FONT_SIZE);
+
+            self.vote
+                .draw_markers(i, vec2(center_x, name_y + Self::MARKER_OFFSET / 2.0));
+        }
+
+        Self::draw_text(
+            &tr("vote.help"),
+            vec2(
+                screen_width() / 2.0,
+                y + Self::PREVIEW_HEIGHT + Self::SPACING + Self::MARKER_OFFSET * 1.5,
+            ),
+            Self::FONT_SIZE - 4,
+        );
+
+        pop_camera_state();
+    }
+}
@@ -0,0 +1,172 @@
+//! The match stats tracker counts the kills, deaths and damage of every player, from `GameEvent`s.
+//! The match is ended by the active `GameMode`, when its win condition is met, with `set_winner`.
+//!
+//! Round wins are kept across rematches, until a new match is started from the main menu, so a
+//! group of players can see who is ahead over a session.
+
+use std::{cmp::Ordering, collections::HashMap};
+
+use macroquad::experimental::scene::{self, Node};
+
+use crate::{console, formaterr, game::GameEvent};
+
+#[derive(Debug, Default, Clone)]
+pub struct PlayerStats {
+    pub kills: u32,
+    pub deaths: u32,
+    pub damage_dealt: f32,
+    /// The damage dealt with each weapon, by item id
+    damage_by_weapon: HashMap<String, f32>,
+}
+
+impl PlayerStats {
+    /// Returns the id of the weapon that the player has dealt the most damage with, if any
+    pub fn get_favorite_weapon(&self) -> Option<&str> {
+        self.damage_by_weapon
+            .iter()
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+            .map(|(id, _)| id.as_str())
+    }
+}
+
+static mut ROUND_WINS: Option<HashMap<u8, u32>> = None;
+
+unsafe fn get_round_wins() -> &'static mut HashMap<u8, u32> {
+    ROUND_WINS.get_or_insert(HashMap::new())
+}
+
+/// This clears the round wins of all players. It should be called when a new match is started
+/// from the main menu, but not on rematches.
+pub fn clear_round_wins() {
+    unsafe { get_round_wins() }.clear();
+}
+
+pub struct MatchStats {
+    stats: HashMap<u8, PlayerStats>,
+    winner: Option<u8>,
+}
+
+impl MatchStats {
+    pub const LISTENER_ID: &'static str = "match_stats";
+
+    pub fn new() -> Self {
+        MatchStats {
+            stats: HashMap::new(),
+            winner: None,
+        }
+    }
+
+    pub fn on_game_event(event: &GameEvent) {
+        if let Some(mut node) = scene::find_node_by_type::<MatchStats>() {
+            node.handle_event(event);
+        }
+    }
+
+    fn handle_event(&mut self, event: &GameEvent) {
+        // Events that happen after the match has been decided are not counted
+        if self.winner.is_some() {
+            return;
+        }
+
+        match *event {
+            GameEvent::PlayerDamaged {
+                damage,
+                damaged_by: Some(damaged_by),
+                ref weapon_id,
+                ..
+            } => {
+                let stats = self.stats.entry(damaged_by).or_default();
+                stats.damage_dealt += damage;
+
+                if let Some(weapon_id) = weapon_id {
+                    *stats
+                        .damage_by_weapon
+                        .entry(weapon_id.clone())
+                        .or_insert(0.0) += damage;
+                }
+            }
+            GameEvent::PlayerKilled {
+                player_id,
+                killed_by,
+                ..
+            } => {
+                self.stats.entry(player_id).or_default().deaths += 1;
+
+                if let Some(killed_by) = killed_by {
+                    self.stats.entry(killed_by).or_default().kills += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// This ends the match, with `player_id` as the winner. This does nothing if the match is
+    /// already over.
+    pub fn set_winner(&mut self, player_id: u8) {
+        if self.winner.is_some() {
+            return;
+        }
+
+        self.winner = Some(player_id);
+        *unsafe { get_round_wins() }.entry(player_id).or_insert(0) += 1;
+
+        GameEvent::RoundEnded {
+            winner: Some(player_id),
+        }
+        .dispatch();
+    }
+
+    /// Returns the stats of all players that have any, by player id
+    pub fn iter_stats(&self) -> impl Iterator<Item = (u8, &PlayerStats)> {
+        self.stats.iter().map(|(id, stats)| (*id, stats))
+    }
+
+    pub fn get_stats(&self, player_id: u8) -> PlayerStats {
+        self.stats.get(&player_id).cloned().unwrap_or_default()
+    }
+
+    pub fn get_round_wins(&self, player_id: u8) -> u32 {
+        unsafe { get_round_wins() }
+            .get(&player_id)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Returns the id of the player that won the match, if the match is over
+    pub fn get_winner(&self) -> Option<u8> {
+        self.winner
+    }
+
+    pub fn is_match_over(&self) -> bool {
+        self.winner.is_some()
+    }
+
+    pub fn register_console_commands() {
+        console::register("set_score", |args| {
+            let (player_id, kills) = match args {
+                [player_id, kills] => (player_id.parse::<u8>(), kills.parse::<u32>()),
+                _ => return Err(formaterr!("Usage: set_score <player_id> <kills>")),
+            };
+
+            let (player_id, kills) = match (player_id, kills) {
+                (Ok(player_id), Ok(kills)) => (player_id, kills),
+                _ => return Err(formaterr!("Usage: set_score <player_id> <kills>")),
+            };
+
+            let mut node = scene::find_node_by_type::<MatchStats>()
+                .ok_or_else(|| formaterr!("There is no match in progress"))?;
+
+            node.stats.entry(player_id).or_default().kills = kills;
+
+            Ok(format!("Player {} has {} kills", player_id + 1, kills))
+        });
+    }
+}
+
+impl Default for MatchStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Node for MatchStats {}
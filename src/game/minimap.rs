@@ -0,0 +1,215 @@
+//! The minimap is an optional overview of the whole map, drawn in the top left corner of the
+//! screen, with a dot for every player and an outline of the area that is in view of the camera.
+//! It is enabled with the `minimap` setting in `Config`.
+//!
+//! The tiles are drawn to a texture with one pixel per tile, which is redrawn at an interval, so
+//! that destroyed tiles are eventually reflected.
+
+use macroquad::{
+    experimental::{
+        collections::storage,
+        scene::{self, Node, RefMut},
+    },
+    prelude::*,
+};
+
+use crate::{
+    game::{GameCamera, TileCollision},
+    Config, GameWorld, Player,
+};
+
+pub struct Minimap {
+    image: Option<Image>,
+    texture: Option<Texture2D>,
+    refresh_timer: f32,
+}
+
+impl Minimap {
+    const MAX_WIDTH: f32 = 180.0;
+    const MAX_HEIGHT: f32 = 120.0;
+    const MARGIN: f32 = 12.0;
+
+    /// The time between each redraw of the tiles, in seconds
+    const REFRESH_INTERVAL: f32 = 0.5;
+
+    const PLAYER_RADIUS: f32 = 3.0;
+
+    const BACKGROUND_COLOR: Color = Color {
+        r: 0.0,
+        g: 0.0,
+        b: 0.0,
+        a: 0.5,
+    };
+    const SOLID_COLOR: Color = Color {
+        r: 0.8,
+        g: 0.8,
+        b: 0.8,
+        a: 0.8,
+    };
+    const PLATFORM_COLOR: Color = Color {
+        r: 0.6,
+        g: 0.6,
+        b: 0.6,
+        a: 0.5,
+    };
+    const VIEW_COLOR: Color = Color {
+        r: 1.0,
+        g: 1.0,
+        b: 1.0,
+        a: 0.6,
+    };
+
+    pub fn new() -> Self {
+        Minimap {
+            image: None,
+            texture: None,
+            refresh_timer: 0.0,
+        }
+    }
+
+    /// This draws the tiles of the map to the minimap image, with one pixel per tile, and uploads
+    /// it to the texture
+    fn refresh(&mut self) {
+        let world = match storage::try_get::<GameWorld>() {
+            Some(world) => world,
+            None => return,
+        };
+
+        let grid_size = world.map.grid_size;
+
+        if grid_size.x == 0 || grid_size.y == 0 {
+            return;
+        }
+
+        let image = self.image.get_or_insert_with(|| {
+            Image::gen_image_color(grid_size.x as u16, grid_size.y as u16, BLANK)
+        });
+
+        for (i, collision) in world.tile_collisions.iter().enumerate() {
+            let x = i as u32 % grid_size.x;
+            let y = i as u32 / grid_size.x;
+
+            let color = match collision {
+                TileCollision::Empty => BLANK,
+                TileCollision::Platform => Self::PLATFORM_COLOR,
+                TileCollision::Solid | TileCollision::Slope(_) => Self::SOLID_COLOR,
+            };
+
+            image.set_pixel(x, y, color);
+        }
+
+        match &self.texture {
+            Some(texture) => texture.update(image),
+            None => {
+                let texture = Texture2D::from_image(image);
+                texture.set_filter(FilterMode::Nearest);
+
+                self.texture = Some(texture);
+            }
+        }
+    }
+}
+
+impl Default for Minimap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Node for Minimap {
+    fn update(mut node: RefMut<Self>) {
+        if !storage::get::<Config>().minimap {
+            return;
+        }
+
+        node.refresh_timer -= get_frame_time();
+
+        if node.refresh_timer <= 0.0 {
+            node.refresh_timer = Self::REFRESH_INTERVAL;
+            node.refresh();
+        }
+    }
+
+    fn draw(node: RefMut<Self>) {
+        if !storage::get::<Config>().minimap {
+            return;
+        }
+
+        let texture = match node.texture {
+            Some(texture) => texture,
+            None => return,
+        };
+
+        let (map_offset, map_size) = match storage::try_get::<GameWorld>() {
+            Some(world) => (world.map.world_offset, world.map.get_size()),
+            None => return,
+        };
+
+        let scale = (Self::MAX_WIDTH / texture.width()).min(Self::MAX_HEIGHT / texture.height());
+        let size = vec2(texture.width(), texture.height()) * scale;
+        let position = vec2(Self::MARGIN, Self::MARGIN);
+
+        // Converts a position in the world to a position on the minimap
+        let to_minimap =
+            |world_position: Vec2| position + (world_position - map_offset) / map_size * size;
+
+        let view = scene::find_node_by_type::<GameCamera>().map(|camera| {
+            let camera = camera.get_macroquad_camera();
+            let extents = vec2(1.0 / camera.zoom.x.abs(), 1.0 / camera.zoom.y.abs());
+
+            (camera.target - extents, camera.target + extents)
+        });
+
+        push_camera_state();
+        set_default_camera();
+
+        draw_rectangle(
+            position.x,
+            position.y,
+            size.x,
+            size.y,
+            Self::BACKGROUND_COLOR,
+        );
+
+        draw_texture_ex(
+            texture,
+            position.x,
+            position.y,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(size),
+                ..Default::default()
+            },
+        );
+
+        if let Some((min, max)) = view {
+            let min = to_minimap(min).max(position);
+            let max = to_minimap(max).min(position + size);
+
+            if max.x > min.x && max.y > min.y {
+                draw_rectangle_lines(
+                    min.x,
+                    min.y,
+                    max.x - min.x,
+                    max.y - min.y,
+                    1.0,
+                    Self::VIEW_COLOR,
+                );
+            }
+        }
+
+        for player in scene::find_nodes_by_type::<Player>() {
+            if player.is_dead {
+                continue;
+            }
+
+            let center = to_minimap(player.body.position + player.body.size / 2.0)
+                .max(position)
+                .min(position + size);
+
+            draw_circle(center.x, center.y, Self::PLAYER_RADIUS, player.get_color());
+        }
+
+        pop_camera_state();
+    }
+}
@@ -1,17 +1,103 @@
+mod announcer;
+mod attract_mode;
+mod balance_simulation;
 mod camera;
+mod cutscene;
+mod events;
+mod game_mode;
+mod hud;
+mod indicators;
 mod input;
+mod input_bindings;
+mod input_recording;
+mod kill_cam;
+mod kill_feed;
+mod latency_overlay;
 mod local;
-mod music;
+mod map_vote;
+mod match_stats;
+mod minimap;
+mod music_intensity;
+mod results;
+mod ruleset;
+mod rumble;
 mod scene;
+mod scoreboard;
+mod simulation;
+mod time_scale;
+mod touch_controls;
+mod training;
+mod vote;
 mod world;
+mod world_ui;
+
+pub use attract_mode::{AttractMode, ATTRACT_MODE_DELAY, ATTRACT_MODE_RECORDING_PATH};
+
+pub use balance_simulation::{run_balance_simulation, BalanceSimulationParams, SIMULATE_ENV_VAR};
 
 pub use camera::GameCamera;
+pub use cutscene::{
+    is_cutscene_playing, play_cutscene, Cutscene, CutsceneAction, CutsceneEvent, CutscenePlayer,
+    TITLE_INTRO_CUTSCENE_ID,
+};
+pub use events::{
+    add_game_event_listener, clear_game_events, dispatch_game_event, flush_game_events,
+    init_game_event_listeners, remove_game_event_listener, GameEvent, GameEventListener,
+};
+pub use game_mode::{
+    create_game_mode, init_game_modes, register_game_mode, ActiveGameMode, Deathmatch, GameMode,
+    GameModeConstructor,
+};
+pub use hud::{Hud, WeaponIcons};
+pub use indicators::OffscreenIndicators;
 pub use local::LocalGame;
 
 pub use scene::{create_game_scene, GameScene};
 
-pub use world::GameWorld;
+pub use simulation::{run_simulation_tick, SimulationTimer, SIMULATION_DELTA_TIME};
+
+pub use time_scale::{EntityTimeScale, TimeScale};
+
+pub use touch_controls::{TouchControls, TouchControlsConfig};
+
+pub use announcer::{Announcer, AnnouncerRule, AnnouncerTrigger};
+
+pub use kill_cam::KillCam;
+
+pub use kill_feed::{KillFeed, KillFeedConfig};
+
+pub use latency_overlay::LatencyOverlay;
+
+pub use map_vote::MapVoteScreen;
+
+pub use minimap::Minimap;
+
+pub use match_stats::{clear_round_wins, MatchStats, PlayerStats};
+
+pub use results::{ResultsScreen, ResultsVote};
+
+pub use vote::Vote;
+
+pub use ruleset::Ruleset;
+
+pub use rumble::Rumble;
+
+pub use scoreboard::Scoreboard;
+
+pub use training::{DamageReadout, TrainingGame};
+
+pub use music_intensity::MusicIntensity;
+
+pub use world::{GameWorld, SurfaceSound, TileCollision, TileSurface};
+
+pub use world_ui::{WorldUi, WorldUiAnchor};
+
+pub use input::{
+    collect_aim, collect_input, is_action_pressed, GameInput, GameInputScheme, InputToggles,
+};
 
-pub use input::{collect_input, GameInput, GameInputScheme};
+pub use input_bindings::{InputAction, InputBinding, InputBindings, InputProfile};
 
-pub use music::{start_music, stop_music};
+pub use input_recording::{
+    InputPlayback, InputRecorder, InputRecording, RECORD_INPUT_ENV_VAR, REPLAY_ENV_VAR,
+};
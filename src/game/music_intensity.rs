@@ -0,0 +1,70 @@
+//! The music intensity rises with every kill and decays over time, so that layered music tracks
+//! build up during intense fights. The share of players that are currently dead sets a floor for
+//! the intensity, so that the music stays intense while few players are left standing.
+
+use macroquad::{
+    experimental::scene::{self, Node, RefMut},
+    prelude::*,
+};
+
+use crate::{audio::set_music_intensity, game::GameEvent, Player};
+
+pub struct MusicIntensity {
+    intensity: f32,
+}
+
+impl MusicIntensity {
+    pub const LISTENER_ID: &'static str = "music_intensity";
+
+    /// The intensity added by a kill
+    const KILL_INTENSITY: f32 = 0.35;
+    /// The intensity lost per second
+    const DECAY_RATE: f32 = 0.04;
+
+    pub fn new() -> Self {
+        MusicIntensity { intensity: 0.0 }
+    }
+
+    pub fn on_game_event(event: &GameEvent) {
+        if let GameEvent::PlayerKilled { .. } = event {
+            if let Some(mut node) = scene::find_node_by_type::<MusicIntensity>() {
+                node.intensity = (node.intensity + Self::KILL_INTENSITY).min(1.0);
+            }
+        }
+    }
+
+    /// Returns the share of players that are dead, as a value between `0.0`, when all players are
+    /// alive, and `1.0`, when only one player is left
+    fn get_eliminated_share() -> f32 {
+        let mut player_cnt = 0;
+        let mut dead_cnt = 0;
+
+        for player in scene::find_nodes_by_type::<Player>() {
+            player_cnt += 1;
+
+            if player.is_dead {
+                dead_cnt += 1;
+            }
+        }
+
+        if player_cnt > 1 {
+            (dead_cnt as f32 / (player_cnt - 1) as f32).min(1.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Default for MusicIntensity {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Node for MusicIntensity {
+    fn update(mut node: RefMut<Self>) {
+        node.intensity = (node.intensity - Self::DECAY_RATE * get_frame_time()).max(0.0);
+
+        set_music_intensity(node.intensity.max(Self::get_eliminated_share()));
+    }
+}
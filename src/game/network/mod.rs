@@ -1,11 +1,24 @@
 //! Very, very WIP
 //! "Delayed lockstep" networking implementation - first step towards GGPO
+//!
+//! This module is not declared in `game`, so it is not built and network matches can not be
+//! played. It is kept in step with changes to the simulation, like the fixed tick and the entity
+//! index, so that it is closer to working when it is enabled again, but none of it is tested.
 
 use std::sync::mpsc;
 
-use macroquad::experimental::scene::{self, Handle, Node, NodeWith, RefMut};
+use macroquad::{
+    experimental::scene::{self, Handle, Node, RefMut},
+    prelude::*,
+    ui::root_ui,
+};
 
-use crate::{capabilities::NetworkReplicate, collect_input, GameInput, GameInputScheme, Player};
+use crate::{
+    collect_input, exit_to_main_menu,
+    game::{run_simulation_tick, Scoreboard, SimulationTimer},
+    gui::{self, GAME_MENU_RESULT_MAIN_MENU, GAME_MENU_RESULT_QUIT},
+    quit_to_desktop, GameInput, GameInputScheme, Player,
+};
 
 use nanoserde::{DeBin, SerBin};
 
@@ -26,6 +39,14 @@ pub enum NetworkMessage {
         frame: u64,
         input: GameInput,
     },
+    /// Sent periodically to measure the round trip time to the other peer, which will answer
+    /// with a `Pong` holding the same time
+    Ping {
+        time: f64,
+    },
+    Pong {
+        time: f64,
+    },
 }
 
 pub struct NetworkGame {
@@ -43,6 +64,9 @@ pub struct NetworkGame {
     // all the inputs from the beginning of the game
     // will optimize memory later
     frames_buffer: Vec<[Option<GameInput>; 2]>,
+
+    // the time the last ping was sent, for the scoreboard
+    last_ping_time: f64,
 }
 
 // // get a bitmask of received remote inputs out of frames_buffer
@@ -63,6 +87,9 @@ impl NetworkGame {
     /// bitmask type should be changed
     const CONSTANT_DELAY: usize = 8;
 
+    /// The time between pings, in seconds
+    const PING_INTERVAL: f64 = 1.0;
+
     pub fn new(
         id: usize,
         socket: std::net::UdpSocket,
@@ -140,13 +167,16 @@ impl NetworkGame {
             tx,
             rx: rx1,
             frames_buffer,
+            last_ping_time: 0.0,
         }
     }
 }
 
-impl Node for NetworkGame {
-    fn fixed_update(mut node: RefMut<Self>) {
-        let node = &mut *node;
+impl NetworkGame {
+    /// This runs a single tick of the simulation, if the inputs of the remote player has been
+    /// received for the current frame
+    fn tick(&mut self) {
+        let node = self;
 
         let own_input = collect_input(node.input_scheme);
 
@@ -178,14 +208,31 @@ impl Node for NetworkGame {
             }
         }
 
+        let time = get_time();
+
+        if time - node.last_ping_time >= Self::PING_INTERVAL {
+            node.last_ping_time = time;
+            node.tx.send(NetworkMessage::Ping { time }).unwrap();
+        }
+
         // Receive other fish input
         while let Ok(message) = node.rx.try_recv() {
-            if let NetworkMessage::Input { frame, input } = message {
-                // frame from the future, need to wait until will simulate
-                // the game enough to use this data
-                if frame < node.frames_buffer.len() as _ {
-                    node.frames_buffer[frame as usize][remote_id] = Some(input);
+            match message {
+                NetworkMessage::Input { frame, input } => {
+                    // frame from the future, need to wait until will simulate
+                    // the game enough to use this data
+                    if frame < node.frames_buffer.len() as _ {
+                        node.frames_buffer[frame as usize][remote_id] = Some(input);
+                    }
+                }
+                NetworkMessage::Ping { time } => {
+                    node.tx.send(NetworkMessage::Pong { time }).unwrap();
                 }
+                NetworkMessage::Pong { time: sent_time } => {
+                    let ping = ((time - sent_time) * 1000.0).round() as u32;
+                    Scoreboard::set_ping(remote_id as u8, ping);
+                }
+                _ => {}
             }
         }
 
@@ -206,9 +253,11 @@ impl Node for NetworkGame {
             scene::get_node(node.player2).apply_input(p2_input);
 
             // advance the simulation
-            for NodeWith { node, capability } in scene::find_nodes_with::<NetworkReplicate>() {
-                (capability.network_update)(node);
-            }
+            #[cfg(debug_assertions)]
+            crate::physics::PhysicsDebugOverlay::clear();
+
+            // This also flushes the game events of the tick, like `LocalGame::tick`
+            run_simulation_tick();
 
             let mut new_frame = [None, None];
             new_frame[node.self_id] = Some(own_input);
@@ -219,3 +268,32 @@ impl Node for NetworkGame {
         }
     }
 }
+
+impl Node for NetworkGame {
+    fn update(mut node: RefMut<Self>) {
+        // The other peer can not be paused, so the simulation keeps running while the menu is open
+        if is_key_pressed(KeyCode::Escape) {
+            if gui::is_game_menu_open() {
+                gui::toggle_game_menu();
+            } else {
+                gui::open_game_menu_ex(true);
+            }
+        }
+
+        for _ in 0..SimulationTimer::advance() {
+            node.tick();
+        }
+    }
+
+    fn draw(_node: RefMut<Self>) {
+        if gui::is_game_menu_open() {
+            if let Some(res) = gui::draw_game_menu(&mut *root_ui()) {
+                match res.into_usize() {
+                    GAME_MENU_RESULT_MAIN_MENU => exit_to_main_menu(),
+                    GAME_MENU_RESULT_QUIT => quit_to_desktop(),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,260 @@
+//! The results screen is shown by `LocalGame` when the match is over. It lists the stats of every
+//! player, from `MatchStats`, and lets every player vote for a rematch or the next map, with their
+//! own input device. When all players have voted, the option with the most votes is returned, with
+//! ties going to a rematch. If the next map wins, the `MapVoteScreen` is shown.
+
+use macroquad::{color, experimental::scene, prelude::*};
+
+use crate::{
+    game::{MatchStats, Vote, WeaponIcons},
+    localization::{tr, tr_args},
+    text::{draw_aligned_text, HorizontalAlignment, VerticalAlignment},
+    GameInputScheme, Player,
+};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ResultsVote {
+    Rematch,
+    NextMap,
+}
+
+impl ResultsVote {
+    const ALL: [ResultsVote; 2] = [ResultsVote::Rematch, ResultsVote::NextMap];
+
+    fn get_title(&self) -> String {
+        let key = match self {
+            ResultsVote::Rematch => "results.rematch",
+            ResultsVote::NextMap => "results.next_map",
+        };
+
+        tr(key)
+    }
+}
+
+pub struct ResultsScreen {
+    vote: Vote,
+    weapon_icons: WeaponIcons,
+}
+
+impl ResultsScreen {
+    const WIDTH: f32 = 640.0;
+    const ROW_HEIGHT: f32 = 36.0;
+    const PADDING: f32 = 16.0;
+    const TITLE_FONT_SIZE: u16 = 32;
+    const FONT_SIZE: u16 = 18;
+    const ICON_SIZE: f32 = 28.0;
+
+    /// The string keys of the column headers
+    const COLUMNS: [&'static str; 6] = [
+        "",
+        "results.kills",
+        "results.deaths",
+        "results.damage",
+        "results.favorite",
+        "results.wins",
+    ];
+
+    const BACKGROUND_COLOR: Color = Color {
+        r: 0.0,
+        g: 0.0,
+        b: 0.0,
+        a: 0.75,
+    };
+    const OPTION_WIDTH: f32 = 160.0;
+    const OPTION_HEIGHT: f32 = 40.0;
+    const SELECTION_MARKER_SIZE: f32 = 10.0;
+
+    pub fn new(players: &[(GameInputScheme, u8)]) -> Self {
+        ResultsScreen {
+            vote: Vote::new(players, ResultsVote::ALL.len()),
+            weapon_icons: WeaponIcons::new(Self::ICON_SIZE),
+        }
+    }
+
+    /// This updates the votes of all players and returns the result, when all players have voted
+    pub fn update(&mut self) -> Option<ResultsVote> {
+        if self.vote.update() {
+            let votes = self.vote.get_votes();
+
+            let next_map_cnt = votes
+                .iter()
+                .filter(|vote| ResultsVote::ALL[vote.selection] == ResultsVote::NextMap)
+                .count();
+
+            if next_map_cnt * 2 > votes.len() {
+                Some(ResultsVote::NextMap)
+            } else {
+                Some(ResultsVote::Rematch)
+            }
+        } else {
+            None
+        }
+    }
+
+    fn draw_text(text: &str, position: Vec2, font_size: u16, color: Color) {
+        draw_aligned_text(
+            text,
+            position,
+            HorizontalAlignment::Center,
+            VerticalAlignment::Center,
+            TextParams {
+                font_size,
+                color,
+                ..Default::default()
+            },
+        );
+    }
+
+    pub fn draw(&mut self) {
+        let match_stats = match scene::find_node_by_type::<MatchStats>() {
+            Some(match_stats) => match_stats,
+            None => return,
+        };
+
+        let mut players = scene::find_nodes_by_type::<Player>()
+            .map(|player| (player.id, player.get_color()))
+            .collect::<Vec<_>>();
+
+        players.sort_by_key(|(id, _)| *id);
+
+        push_camera_state();
+        set_default_camera();
+
+        draw_rectangle(
+            0.0,
+            0.0,
+            screen_width(),
+            screen_height(),
+            Self::BACKGROUND_COLOR,
+        );
+
+        let x = (screen_width() - Self::WIDTH) / 2.0;
+        let mut y = Self::PADDING * 3.0;
+
+        let title = match match_stats.get_winner() {
+            Some(winner) => tr_args(
+                "results.winner",
+                &[&tr_args("player.short_name", &[&(winner + 1).to_string()])],
+            ),
+            None => tr("results.match_over"),
+        };
+
+        Self::draw_text(
+            &title,
+            vec2(screen_width() / 2.0, y),
+            Self::TITLE_FONT_SIZE,
+            color::WHITE,
+        );
+
+        y += Self::ROW_HEIGHT * 1.5;
+
+        let column_width = Self::WIDTH / Self::COLUMNS.len() as f32;
+        let get_column_x = |i: usize| x + column_width * (i as f32 + 0.5);
+
+        for (i, column) in Self::COLUMNS.iter().enumerate() {
+            if column.is_empty() {
+                continue;
+            }
+
+            Self::draw_text(
+                &tr(column),
+                vec2(get_column_x(i), y),
+                Self::FONT_SIZE,
+                color::LIGHTGRAY,
+            );
+        }
+
+        y += Self::ROW_HEIGHT;
+
+        for (player_id, player_color) in players {
+            let stats = match_stats.get_stats(player_id);
+            let round_wins = match_stats.get_round_wins(player_id);
+
+            let values = [
+                tr_args("player.short_name", &[&(player_id + 1).to_string()]),
+                stats.kills.to_string(),
+                stats.deaths.to_string(),
+                format!("{:.0}", stats.damage_dealt),
+            ];
+
+            for (i, value) in values.iter().enumerate() {
+                let color = if i == 0 { player_color } else { color::WHITE };
+
+                Self::draw_text(value, vec2(get_column_x(i), y), Self::FONT_SIZE, color);
+            }
+
+            match stats.get_favorite_weapon() {
+                Some(weapon_id) => {
+                    let center = vec2(get_column_x(4), y);
+
+                    if let Some(icon) = self.weapon_icons.get(weapon_id) {
+                        icon.draw(center - icon.get_size() / 2.0, 0.0, false, false);
+                    }
+                }
+                None => {
+                    Self::draw_text("-", vec2(get_column_x(4), y), Self::FONT_SIZE, color::WHITE)
+                }
+            }
+
+            Self::draw_text(
+                &round_wins.to_string(),
+                vec2(get_column_x(5), y),
+                Self::FONT_SIZE,
+                color::WHITE,
+            );
+
+            y += Self::ROW_HEIGHT;
+        }
+
+        y += Self::ROW_HEIGHT;
+
+        self.draw_vote_options(y);
+
+        pop_camera_state();
+    }
+
+    fn draw_vote_options(&self, y: f32) {
+        let option_cnt = ResultsVote::ALL.len() as f32;
+        let spacing = Self::PADDING * 2.0;
+        let total_width = Self::OPTION_WIDTH * option_cnt + spacing * (option_cnt - 1.0);
+        let start_x = (screen_width() - total_width) / 2.0;
+
+        for (i, option) in ResultsVote::ALL.iter().enumerate() {
+            let x = start_x + i as f32 * (Self::OPTION_WIDTH + spacing);
+
+            draw_rectangle_lines(
+                x,
+                y,
+                Self::OPTION_WIDTH,
+                Self::OPTION_HEIGHT,
+                2.0,
+                color::WHITE,
+            );
+
+            Self::draw_text(
+                &option.get_title(),
+                vec2(x + Self::OPTION_WIDTH / 2.0, y + Self::OPTION_HEIGHT / 2.0),
+                Self::FONT_SIZE,
+                color::WHITE,
+            );
+
+            self.vote.draw_markers(
+                i,
+                vec2(
+                    x + Self::OPTION_WIDTH / 2.0,
+                    y + Self::OPTION_HEIGHT + Self::SELECTION_MARKER_SIZE * 1.5,
+                ),
+            );
+        }
+
+        Self::draw_text(
+            &tr("vote.help"),
+            vec2(
+                screen_width() / 2.0,
+                y + Self::OPTION_HEIGHT + Self::SELECTION_MARKER_SIZE * 4.0,
+            ),
+            Self::FONT_SIZE - 4,
+            color::LIGHTGRAY,
+        );
+    }
+}
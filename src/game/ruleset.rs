@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{game::Deathmatch, json};
+
+/// The rules of a match, loaded from the `ruleset` resource file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ruleset {
+    /// The id of the game mode, which decides how the match is won
+    #[serde(default = "Ruleset::default_game_mode")]
+    pub game_mode: String,
+    /// The amount of kills a player must score to win the match. If this is zero, the match
+    /// will never end.
+    #[serde(default = "Ruleset::default_kill_limit")]
+    pub kill_limit: u32,
+    /// If this is `true`, the damage dealt by every hit is shown as a number that floats up from
+    /// the player that was hit
+    #[serde(default, skip_serializing_if = "json::is_false")]
+    pub damage_numbers: bool,
+    /// If this is `true`, a health bar is shown above the head of every player
+    #[serde(default, skip_serializing_if = "json::is_false")]
+    pub health_bars: bool,
+    /// If this is `true`, ranged weapons are fired in the direction that the player aims, with
+    /// the mouse or the right stick, in stead of the direction that the player is facing
+    #[serde(default, skip_serializing_if = "json::is_false")]
+    pub free_aim: bool,
+    /// The ids of the mutators that are enabled, in the order that their hooks are called
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mutators: Vec<String>,
+}
+
+impl Ruleset {
+    pub fn default_game_mode() -> String {
+        Deathmatch::ID.to_string()
+    }
+
+    pub fn default_kill_limit() -> u32 {
+        10
+    }
+}
+
+impl Default for Ruleset {
+    fn default() -> Self {
+        Ruleset {
+            game_mode: Self::default_game_mode(),
+            kill_limit: Self::default_kill_limit(),
+            damage_numbers: false,
+            health_bars: false,
+            free_aim: false,
+            mutators: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! Rumble is requested, per player, when a player is damaged and, for all players, on explosions
+//! and other effects that shake the camera. Requests are combined into a single intensity per
+//! player, between `0.0` and `1.0`, that decays over a short duration.
+//!
+//! The version of `fishsticks` that is currently used does not expose force feedback, so the
+//! intensities are not yet sent to the gamepads. When it does, `Rumble::get_intensity` should be
+//! applied to the gamepad of each player.
+
+use std::collections::HashMap;
+
+use macroquad::{
+    experimental::scene::{self, Node, RefMut},
+    prelude::*,
+};
+
+use crate::game::GameEvent;
+
+#[derive(Debug, Default, Copy, Clone)]
+struct RumbleState {
+    intensity: f32,
+    timer: f32,
+    duration: f32,
+}
+
+impl RumbleState {
+    fn get_intensity(&self) -> f32 {
+        if self.duration > 0.0 {
+            self.intensity * (1.0 - self.timer / self.duration).max(0.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// This starts a new rumble, unless the current rumble is stronger
+    fn start(&mut self, intensity: f32, duration: f32) {
+        let intensity = intensity.clamp(0.0, 1.0);
+
+        if intensity >= self.get_intensity() {
+            *self = RumbleState {
+                intensity,
+                timer: 0.0,
+                duration,
+            };
+        }
+    }
+}
+
+pub struct Rumble {
+    players: HashMap<u8, RumbleState>,
+    /// The rumble that applies to all players
+    shake: RumbleState,
+}
+
+impl Rumble {
+    pub const LISTENER_ID: &'static str = "rumble";
+
+    /// The intensity of the rumble of a player that is damaged, per point of damage
+    const DAMAGE_INTENSITY: f32 = 0.02;
+    const DAMAGE_DURATION: f32 = 0.2;
+
+    /// The camera shake magnitude that gives a rumble of full intensity
+    const MAX_SHAKE_MAGNITUDE: f32 = 10.0;
+    const SHAKE_DURATION: f32 = 0.35;
+
+    pub fn new() -> Self {
+        Rumble {
+            players: HashMap::new(),
+            shake: RumbleState::default(),
+        }
+    }
+
+    /// This requests rumble for all players, from an effect that shakes the camera with
+    /// `magnitude`
+    pub fn add_shake(magnitude: f32) {
+        if let Some(mut node) = scene::find_node_by_type::<Rumble>() {
+            node.shake
+                .start(magnitude / Self::MAX_SHAKE_MAGNITUDE, Self::SHAKE_DURATION);
+        }
+    }
+
+    /// Returns the current rumble intensity of a player, between `0.0` and `1.0`
+    #[allow(dead_code)]
+    pub fn get_intensity(&self, player_id: u8) -> f32 {
+        let intensity = self
+            .players
+            .get(&player_id)
+            .map(|state| state.get_intensity())
+            .unwrap_or(0.0);
+
+        intensity.max(self.shake.get_intensity())
+    }
+
+    pub fn on_game_event(event: &GameEvent) {
+        if let GameEvent::PlayerDamaged {
+            player_id, damage, ..
+        } = *event
+        {
+            if let Some(mut node) = scene::find_node_by_type::<Rumble>() {
+                node.players
+                    .entry(player_id)
+                    .or_default()
+                    .start(damage * Self::DAMAGE_INTENSITY, Self::DAMAGE_DURATION);
+            }
+        }
+    }
+}
+
+impl Default for Rumble {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Node for Rumble {
+    fn update(mut node: RefMut<Self>) {
+        let dt = get_frame_time();
+
+        node.shake.timer += dt;
+
+        for state in node.players.values_mut() {
+            state.timer += dt;
+        }
+
+        node.players.retain(|_, state| state.timer < state.duration);
+    }
+}
@@ -6,10 +6,27 @@ use macroquad::{
     prelude::*,
 };
 
-use crate::player::PlayerCharacterParams;
+use crate::console::Console;
+use crate::effects::clear_active_effects;
+#[cfg(debug_assertions)]
+use crate::game::LatencyOverlay;
+use crate::game::{
+    ActiveGameMode, Announcer, Hud, KillCam, KillFeed, MatchStats, Minimap, MusicIntensity,
+    OffscreenIndicators, Rumble, Scoreboard, SimulationTimer, TimeScale, TouchControls, WorldUi,
+};
+use crate::gui::ToastOverlay;
+use crate::items::{ItemSpawnerParams, ItemSpawners, LootTable};
+use crate::mutators::Mutators;
+use crate::particles::DecalLayer;
+#[cfg(debug_assertions)]
+use crate::physics::PhysicsDebugOverlay;
+use crate::physics::{EntityIndex, ForceVolumes};
+use crate::player::{HitFlash, PlayerCharacterParams, PlayerEmotes};
+use crate::profiler::{ProfileScope, ProfilerOverlay};
 use crate::{
-    Decoration, GameCamera, GameWorld, Item, Map, MapLayerKind, MapObjectKind, ParticleEmitters,
-    Player, Projectiles, Resources, Sproinger, TriggeredEffects,
+    AreaEffects, Decoration, Deployables, GameCamera, GameWorld, InteractiveObjects, Lighting, Map,
+    MapLayerKind, MapObjectKind, ParticleEmitters, Player, Projectiles, Resources, Sproinger,
+    TriggeredEffects, Weather,
 };
 
 #[derive(Default)]
@@ -23,8 +40,30 @@ impl GameScene {
 
 impl scene::Node for GameScene {
     fn draw(_: RefMut<Self>) {
-        let world = storage::get::<GameWorld>();
-        world.map.draw(None, true);
+        {
+            let _scope = ProfileScope::new("draw map");
+
+            let world = storage::get::<GameWorld>();
+
+            // Only the tiles that are in view are drawn, which matters on big maps
+            match scene::find_node_by_type::<GameCamera>() {
+                Some(camera) => {
+                    match world.map.get_overlapping_grid_rect(&camera.get_view_rect()) {
+                        Some(rect) => world.map.draw(Some(rect), true),
+                        None => world.map.draw_background(None, false),
+                    }
+                }
+                None => world.map.draw(None, true),
+            }
+        }
+
+        DecalLayer::draw();
+
+        #[cfg(debug_assertions)]
+        ForceVolumes::debug_draw();
+
+        #[cfg(debug_assertions)]
+        PhysicsDebugOverlay::draw();
     }
 }
 
@@ -33,6 +72,12 @@ pub fn create_game_scene(
     player_characters: Vec<PlayerCharacterParams>,
     is_local_game: bool,
 ) -> Vec<Handle<Player>> {
+    storage::get_mut::<Resources>().prepare_map_textures(&map);
+
+    // Effects and interactions that were queued in a previous match must not carry over
+    clear_active_effects();
+    Player::clear_pending_interactions();
+
     let bounds = {
         let w = map.grid_size.x as f32 * map.tile_size.x;
         let h = map.grid_size.y as f32 * map.tile_size.y;
@@ -43,9 +88,7 @@ pub fn create_game_scene(
 
     scene::add_node(GameScene::new());
 
-    let resources = storage::get::<Resources>();
-
-    // Objects are cloned since Item constructor requires `GameWorld` in storage
+    // Objects are cloned since the map is moved into `GameWorld`, below
     let mut map_objects = Vec::new();
     for layer in map.layers.values() {
         if layer.is_visible && layer.kind == MapLayerKind::ObjectLayer {
@@ -53,7 +96,14 @@ pub fn create_game_scene(
         }
     }
 
-    let mut items = Vec::new();
+    let mut item_spawners = ItemSpawners::new(LootTable::get_overrides(&map));
+
+    storage::store(ForceVolumes::new(&map_objects));
+
+    let lighting = Lighting::new(&map, &map_objects);
+    let weather = Weather::new(&map);
+
+    let mut interactive_objects = Vec::new();
 
     for object in map_objects {
         match object.kind {
@@ -63,29 +113,46 @@ pub fn create_game_scene(
             MapObjectKind::Environment => {
                 if object.id == Sproinger::OBJECT_ID {
                     Sproinger::spawn(object.position);
+                } else if InteractiveObjects::is_interactive_object(&object.id) {
+                    interactive_objects.push(object);
+                } else if object.id == ForceVolumes::OBJECT_ID
+                    || object.id == Lighting::LIGHT_OBJECT_ID
+                {
+                    // Force volumes and lights are instantiated from the map objects, separately
                 } else {
                     println!("WARNING: Invalid environment object id '{}'", &object.id);
                 }
             }
             MapObjectKind::Item => {
-                if let Some(params) = resources.items.get(&object.id).cloned() {
-                    if params.is_network_ready || is_local_game {
-                        items.push((object.position, params));
-                    }
-                } else {
-                    println!("WARNING: Invalid item id '{}'", &object.id);
-                }
+                let params = ItemSpawnerParams::from_object(&object);
+                item_spawners.add(object.position, params, is_local_game);
             }
         }
     }
 
+    let wind = AreaEffects::get_wind(&map);
+
     storage::store(GameWorld::new(map));
 
-    for (position, params) in items {
-        scene::add_node(Item::new(position, params));
-    }
+    let map_rect = DecalLayer::get_map_rect(&storage::get::<GameWorld>());
+    storage::store(DecalLayer::new(map_rect));
+
+    storage::store(HitFlash::new());
+
+    storage::store(EntityIndex::new());
+    storage::store(SimulationTimer::new());
+
+    // Global time scaling would break lockstep, unless all peers agree on it, so it is disabled
+    // in network games, until that is part of the protocol
+    storage::store(TimeScale::new(is_local_game));
+
+    #[cfg(debug_assertions)]
+    storage::store(PhysicsDebugOverlay::new());
+
+    // Doors require the collision world, so interactive objects are added after it is stored
+    scene::add_node(InteractiveObjects::new(&interactive_objects));
 
-    drop(resources);
+    scene::add_node(item_spawners);
 
     let players = vec![
         scene::add_node(Player::new(0, player_characters[0].clone())),
@@ -94,7 +161,44 @@ pub fn create_game_scene(
 
     scene::add_node(TriggeredEffects::new());
     scene::add_node(Projectiles::new());
+    scene::add_node(Deployables::new());
+    scene::add_node(AreaEffects::new(wind));
     scene::add_node(ParticleEmitters::new());
+    scene::add_node(PlayerEmotes::new());
+    scene::add_node(KillCam::new());
+    scene::add_node(MusicIntensity::new());
+    scene::add_node(Rumble::new());
+    scene::add_node(Announcer::new());
+    scene::add_node(MatchStats::new());
+    scene::add_node(Mutators::new());
+
+    if let Some(weather) = weather {
+        scene::add_node(weather);
+    }
+
+    // Lighting is drawn on top of everything that was added before it
+    if let Some(lighting) = lighting {
+        scene::add_node(lighting);
+    }
+
+    // The world UI and the HUD are drawn in screen space, on top of everything else
+    scene::add_node(WorldUi::new());
+    scene::add_node(OffscreenIndicators::new());
+    scene::add_node(Minimap::new());
+    scene::add_node(Hud::new());
+    scene::add_node(ActiveGameMode::new());
+    scene::add_node(KillFeed::new());
+    scene::add_node(TouchControls::new());
+
+    scene::add_node(Scoreboard::new());
+
+    scene::add_node(ToastOverlay::new());
+    scene::add_node(Console::new());
+
+    #[cfg(debug_assertions)]
+    scene::add_node(LatencyOverlay::new());
+
+    scene::add_node(ProfilerOverlay::new());
 
     players
 }
@@ -0,0 +1,171 @@
+//! The scoreboard is an overlay that is shown while tab is held during a match. It lists every
+//! player, with their score and deaths, from `MatchStats`, and the round trip time to each remote
+//! peer, if any pings have been reported with `Scoreboard::set_ping`.
+//!
+//! Pings are reported by the lockstep network game, in `game::network`, which is not built as part
+//! of the game yet, so for now the ping column is never shown. Network matches are simulated in
+//! lockstep, so `MatchStats` will be the same on every peer and does not have to be sent.
+//!
+//! There are no teams yet, so there is no team column. When teams are added, players should be
+//! grouped by team, here.
+
+use std::{cmp::Reverse, collections::HashMap};
+
+use macroquad::{
+    color,
+    experimental::scene::{self, Node, RefMut},
+    prelude::*,
+};
+
+use crate::{
+    frame_alloc::frame_vec,
+    game::MatchStats,
+    localization::{tr, tr_args},
+    text::{draw_aligned_text, HorizontalAlignment, VerticalAlignment},
+    Player,
+};
+
+pub struct Scoreboard {
+    /// The round trip time to the peer of each remote player, in milliseconds, by player id
+    pings: HashMap<u8, u32>,
+}
+
+impl Scoreboard {
+    const KEY: KeyCode = KeyCode::Tab;
+
+    const WIDTH: f32 = 420.0;
+    const ROW_HEIGHT: f32 = 30.0;
+    const PADDING: f32 = 12.0;
+    const FONT_SIZE: u16 = 18;
+
+    /// The string keys of the column headers
+    const COLUMNS: [&'static str; 4] = [
+        "scoreboard.player",
+        "scoreboard.score",
+        "scoreboard.deaths",
+        "scoreboard.ping",
+    ];
+
+    const BACKGROUND_COLOR: Color = Color {
+        r: 0.0,
+        g: 0.0,
+        b: 0.0,
+        a: 0.75,
+    };
+
+    pub fn new() -> Self {
+        Scoreboard {
+            pings: HashMap::new(),
+        }
+    }
+
+    /// This sets the round trip time, in milliseconds, to the peer that controls `player_id`. This
+    /// is only called by the network game, which is not built yet.
+    #[allow(dead_code)]
+    pub fn set_ping(player_id: u8, ping: u32) {
+        if let Some(mut node) = scene::find_node_by_type::<Scoreboard>() {
+            node.pings.insert(player_id, ping);
+        }
+    }
+
+    fn draw_text(text: &str, position: Vec2, color: Color) {
+        draw_aligned_text(
+            text,
+            position,
+            HorizontalAlignment::Center,
+            VerticalAlignment::Center,
+            TextParams {
+                font_size: Self::FONT_SIZE,
+                color,
+                ..Default::default()
+            },
+        );
+    }
+}
+
+impl Default for Scoreboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Node for Scoreboard {
+    fn draw(node: RefMut<Self>) {
+        if !is_key_down(Self::KEY) {
+            return;
+        }
+
+        let match_stats = match scene::find_node_by_type::<MatchStats>() {
+            Some(match_stats) => match_stats,
+            None => return,
+        };
+
+        let mut players = frame_vec::<(u8, Color)>();
+        players.extend(
+            scene::find_nodes_by_type::<Player>().map(|player| (player.id, player.get_color())),
+        );
+
+        // Players are listed by score, with the leader on top
+        players.sort_by_key(|(id, _)| (Reverse(match_stats.get_stats(*id).kills), *id));
+
+        let height = Self::ROW_HEIGHT * (players.len() + 1) as f32 + Self::PADDING * 2.0;
+        let position = vec2(
+            (screen_width() - Self::WIDTH) / 2.0,
+            (screen_height() - height) / 2.0,
+        );
+
+        push_camera_state();
+        set_default_camera();
+
+        draw_rectangle(
+            position.x,
+            position.y,
+            Self::WIDTH,
+            height,
+            Self::BACKGROUND_COLOR,
+        );
+
+        // The ping column is only shown if there are remote players
+        let columns = if node.pings.is_empty() {
+            &Self::COLUMNS[..Self::COLUMNS.len() - 1]
+        } else {
+            &Self::COLUMNS[..]
+        };
+
+        let column_width = (Self::WIDTH - Self::PADDING * 2.0) / columns.len() as f32;
+        let get_column_x = |i: usize| position.x + Self::PADDING + column_width * (i as f32 + 0.5);
+
+        let mut y = position.y + Self::PADDING + Self::ROW_HEIGHT / 2.0;
+
+        for (i, column) in columns.iter().enumerate() {
+            Self::draw_text(&tr(column), vec2(get_column_x(i), y), color::LIGHTGRAY);
+        }
+
+        for &(player_id, player_color) in players.iter() {
+            y += Self::ROW_HEIGHT;
+
+            let stats = match_stats.get_stats(player_id);
+
+            let ping = node
+                .pings
+                .get(&player_id)
+                .map(|ping| tr_args("scoreboard.ping_value", &[&ping.to_string()]))
+                .unwrap_or_else(|| "-".to_string());
+
+            let values = [
+                tr_args("player.short_name", &[&(player_id + 1).to_string()]),
+                stats.kills.to_string(),
+                stats.deaths.to_string(),
+                ping,
+            ];
+
+            for (i, value) in values.iter().take(columns.len()).enumerate() {
+                let color = if i == 0 { player_color } else { color::WHITE };
+
+                Self::draw_text(value, vec2(get_column_x(i), y), color);
+            }
+        }
+
+        pop_camera_state();
+    }
+}
@@ -0,0 +1,112 @@
+//! The game simulation is run in ticks of a fixed duration, decoupled from the render frame rate,
+//! so that physics behave the same regardless of frame rate, and so that the simulation of a
+//! given sequence of inputs is deterministic, as is required by the lockstep network game.
+//!
+//! Any time left over, after the ticks of a frame has been run, is carried over to the next frame
+//! and is used to interpolate the rendered positions of physics bodies between the two last ticks.
+//!
+//! The duration of each frame is scaled by the global `TimeScale`, before it is added to the
+//! timer, so slow motion reduces the rate of ticks, in stead of the duration of each tick.
+//!
+//! Everything that affects the simulation, including timers, delays, damage and respawns, must be
+//! advanced in `run_simulation_tick`, and never in coroutines, as those run once per frame, and
+//! keep running while the simulation is paused.
+
+use macroquad::{
+    experimental::{
+        collections::storage,
+        scene::{self, NodeWith},
+    },
+    prelude::*,
+};
+
+use crate::{
+    audio::advance_sound_event_log, capabilities::NetworkReplicate, effects::update_active_effects,
+    physics::EntityIndex, profiler::ProfileScope, Player,
+};
+
+use super::{flush_game_events, TimeScale};
+
+/// The duration of a single simulation tick (60 Hz). Simulation code should use this, in stead of
+/// `get_frame_time`, which will return the duration of the render frame.
+pub const SIMULATION_DELTA_TIME: f32 = 1.0 / 60.0;
+
+/// This runs the updates of a single tick: all replicated nodes are updated, after which the
+/// effects, damage and other interactions that they caused are applied, and the game events of the
+/// tick are delivered. Input should be applied to the players before this is called.
+pub fn run_simulation_tick() {
+    {
+        let _scope = ProfileScope::new("entity index");
+        EntityIndex::rebuild();
+    }
+
+    {
+        let _scope = ProfileScope::new("update");
+
+        for NodeWith { node, capability } in scene::find_nodes_with::<NetworkReplicate>() {
+            (capability.network_update)(node);
+        }
+    }
+
+    {
+        let _scope = ProfileScope::new("interactions");
+        update_active_effects();
+        Player::apply_pending_interactions();
+    }
+
+    {
+        let _scope = ProfileScope::new("events");
+        flush_game_events();
+    }
+
+    advance_sound_event_log();
+}
+
+pub struct SimulationTimer {
+    accumulator: f32,
+}
+
+impl SimulationTimer {
+    /// The maximum amount of ticks run in a single frame. If the frame rate drops so low that
+    /// more ticks than this are due, the simulation will slow down, in stead of trying to catch up,
+    /// which would only make the following frames even slower.
+    const MAX_TICKS_PER_FRAME: u32 = 5;
+
+    pub fn new() -> Self {
+        SimulationTimer { accumulator: 0.0 }
+    }
+
+    /// This adds the duration of the current frame to the timer and returns the amount of ticks
+    /// that should be run this frame
+    pub fn advance() -> u32 {
+        let mut timer = storage::get_mut::<SimulationTimer>();
+
+        let frame_time = get_frame_time();
+
+        timer.accumulator += frame_time * TimeScale::update(frame_time);
+
+        let tick_cnt = (timer.accumulator / SIMULATION_DELTA_TIME) as u32;
+        if tick_cnt > Self::MAX_TICKS_PER_FRAME {
+            timer.accumulator = 0.0;
+            return Self::MAX_TICKS_PER_FRAME;
+        }
+
+        timer.accumulator -= tick_cnt as f32 * SIMULATION_DELTA_TIME;
+
+        tick_cnt
+    }
+
+    /// Returns the fraction of a tick that has passed since the last tick was run. This is used to
+    /// interpolate between the state of the previous tick and the state of the last tick, when
+    /// drawing.
+    pub fn get_interpolation() -> f32 {
+        let timer = storage::get::<SimulationTimer>();
+        (timer.accumulator / SIMULATION_DELTA_TIME).clamp(0.0, 1.0)
+    }
+}
+
+impl Default for SimulationTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
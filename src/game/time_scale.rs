@@ -0,0 +1,200 @@
+//! Time scaling, used for effects like slow motion on the last kill.
+//!
+//! The global time scale does not change the duration of a simulation tick, only the rate at which
+//! ticks are run, so the simulation stays deterministic. As the peers of a network game run in
+//! lockstep, a peer that slows down would stall the others, so global time scaling is disabled
+//! in network games, unless all peers have agreed to enable it.
+//!
+//! The time scale of individual entities is implemented with `EntityTimeScale`, which works like
+//! hitstop, by skipping ticks for the entity, and which is advanced by the simulation itself, so
+//! it is safe to use in network games.
+
+use macroquad::{experimental::collections::storage, prelude::*};
+
+/// A temporary change of the global time scale, which eases back to normal speed over the last
+/// part of its duration
+struct SlowMotion {
+    scale: f32,
+    duration: f32,
+    timer: f32,
+}
+
+impl SlowMotion {
+    /// The part of the duration spent easing back to normal speed
+    const EASE_OUT_FACTOR: f32 = 0.3;
+
+    fn get_scale(&self) -> f32 {
+        let ease_out_duration = self.duration * Self::EASE_OUT_FACTOR;
+        let remaining = self.duration - self.timer;
+
+        if remaining < ease_out_duration {
+            let t = 1.0 - remaining / ease_out_duration;
+            self.scale + (1.0 - self.scale) * t
+        } else {
+            self.scale
+        }
+    }
+}
+
+pub struct TimeScale {
+    is_enabled: bool,
+    scale: f32,
+    slow_motion: Vec<SlowMotion>,
+}
+
+impl TimeScale {
+    /// The lowest global time scale allowed, so that the game will never appear frozen
+    const MIN_SCALE: f32 = 0.1;
+
+    pub fn new(is_enabled: bool) -> Self {
+        TimeScale {
+            is_enabled,
+            scale: 1.0,
+            slow_motion: Vec::new(),
+        }
+    }
+
+    /// This enables or disables global time scaling. Disabling it will cancel any slow motion
+    /// in progress.
+    pub fn set_is_enabled(is_enabled: bool) {
+        let mut time_scale = storage::get_mut::<TimeScale>();
+
+        time_scale.is_enabled = is_enabled;
+
+        if !is_enabled {
+            time_scale.slow_motion.clear();
+            time_scale.scale = 1.0;
+        }
+    }
+
+    pub fn is_enabled() -> bool {
+        storage::try_get::<TimeScale>()
+            .map(|time_scale| time_scale.is_enabled)
+            .unwrap_or(false)
+    }
+
+    /// This slows the game down to `scale` for `duration`, in real time. If several slow motion
+    /// effects overlap, the slowest one will be used. This does nothing if global time scaling is
+    /// disabled.
+    pub fn add_slow_motion(scale: f32, duration: f32) {
+        if let Some(mut time_scale) = storage::try_get_mut::<TimeScale>() {
+            if time_scale.is_enabled && duration > 0.0 {
+                time_scale.slow_motion.push(SlowMotion {
+                    scale: scale.clamp(Self::MIN_SCALE, 1.0),
+                    duration,
+                    timer: 0.0,
+                });
+            }
+        }
+    }
+
+    /// Returns the current global time scale
+    pub fn get() -> f32 {
+        storage::try_get::<TimeScale>()
+            .map(|time_scale| time_scale.scale)
+            .unwrap_or(1.0)
+    }
+
+    /// Returns the duration of the current frame, scaled by the global time scale. Cosmetic
+    /// effects, like particles, that are updated per frame, should use this, in stead of
+    /// `get_frame_time`, so that they slow down with the simulation.
+    pub fn get_frame_time() -> f32 {
+        get_frame_time() * Self::get()
+    }
+
+    /// This advances any slow motion by `dt`, which should be the unscaled duration of the frame,
+    /// and returns the new global time scale
+    pub fn update(dt: f32) -> f32 {
+        let mut time_scale = match storage::try_get_mut::<TimeScale>() {
+            Some(time_scale) => time_scale,
+            None => return 1.0,
+        };
+
+        for slow_motion in &mut time_scale.slow_motion {
+            slow_motion.timer += dt;
+        }
+
+        time_scale
+            .slow_motion
+            .retain(|slow_motion| slow_motion.timer < slow_motion.duration);
+
+        time_scale.scale = time_scale
+            .slow_motion
+            .iter()
+            .map(SlowMotion::get_scale)
+            .fold(1.0, f32::min);
+
+        time_scale.scale
+    }
+}
+
+impl Default for TimeScale {
+    fn default() -> Self {
+        Self::new(true)
+    }
+}
+
+/// The time scale of a single entity. An entity with a time scale below `1.0` will skip ticks,
+/// so that it is only updated on the given fraction of ticks. This should only be advanced from
+/// within the simulation.
+#[derive(Debug, Clone)]
+pub struct EntityTimeScale {
+    scale: f32,
+    accumulator: f32,
+    /// The amount of ticks left before the time scale is reset to `1.0`, if any
+    remaining_ticks: Option<u32>,
+}
+
+impl EntityTimeScale {
+    pub fn new() -> Self {
+        EntityTimeScale {
+            scale: 1.0,
+            accumulator: 0.0,
+            remaining_ticks: None,
+        }
+    }
+
+    pub fn get(&self) -> f32 {
+        self.scale
+    }
+
+    /// This sets the time scale of the entity, between `0.0` and `1.0`. If `duration_ticks` is
+    /// specified, the time scale will be reset after that amount of ticks.
+    pub fn set(&mut self, scale: f32, duration_ticks: Option<u32>) {
+        self.scale = scale.clamp(0.0, 1.0);
+        self.remaining_ticks = duration_ticks;
+    }
+
+    pub fn reset(&mut self) {
+        self.scale = 1.0;
+        self.accumulator = 0.0;
+        self.remaining_ticks = None;
+    }
+
+    /// This should be called once per tick and it will return `true` if the entity should be
+    /// updated on this tick
+    pub fn tick(&mut self) -> bool {
+        if let Some(remaining_ticks) = &mut self.remaining_ticks {
+            if *remaining_ticks == 0 {
+                self.reset();
+            } else {
+                *remaining_ticks -= 1;
+            }
+        }
+
+        self.accumulator += self.scale;
+
+        if self.accumulator >= 1.0 {
+            self.accumulator -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl Default for EntityTimeScale {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,324 @@
+//! On-screen touch controls, with a virtual stick on the left side of the screen and a button for
+//! each of the other actions on the right side. These are only shown on web and mobile targets,
+//! once the screen has been touched, and they feed their actions into the input of the player on
+//! the left keyboard scheme, so that the game is playable on phones.
+//!
+//! The size and opacity of the controls can be changed in the config.
+
+use macroquad::{
+    color,
+    experimental::{
+        collections::storage,
+        scene::{self, Node, RefMut},
+    },
+    prelude::*,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game::InputAction,
+    gui,
+    text::{draw_aligned_text, HorizontalAlignment, VerticalAlignment},
+    Config,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TouchControlsConfig {
+    /// A factor that the size of the controls is multiplied by, between
+    /// `TouchControlsConfig::MIN_SCALE` and `TouchControlsConfig::MAX_SCALE`
+    #[serde(default = "TouchControlsConfig::default_scale")]
+    pub scale: f32,
+    /// The opacity of the controls, between `TouchControlsConfig::MIN_OPACITY` and `1.0`
+    #[serde(default = "TouchControlsConfig::default_opacity")]
+    pub opacity: f32,
+}
+
+impl TouchControlsConfig {
+    pub const MIN_SCALE: f32 = 0.5;
+    pub const MAX_SCALE: f32 = 2.0;
+
+    pub const MIN_OPACITY: f32 = 0.1;
+
+    pub fn default_scale() -> f32 {
+        1.0
+    }
+
+    pub fn default_opacity() -> f32 {
+        0.5
+    }
+
+    pub fn clamped(&self) -> Self {
+        TouchControlsConfig {
+            scale: self.scale.clamp(Self::MIN_SCALE, Self::MAX_SCALE),
+            opacity: self.opacity.clamp(Self::MIN_OPACITY, 1.0),
+        }
+    }
+}
+
+impl Default for TouchControlsConfig {
+    fn default() -> Self {
+        TouchControlsConfig {
+            scale: Self::default_scale(),
+            opacity: Self::default_opacity(),
+        }
+    }
+}
+
+/// A button of the touch controls, with its position relative to the bottom right corner of the
+/// screen, before scaling
+struct TouchButton {
+    action: InputAction,
+    label: &'static str,
+    offset: (f32, f32),
+    radius: f32,
+}
+
+const BUTTONS: &[TouchButton] = &[
+    TouchButton {
+        action: InputAction::Jump,
+        label: "A",
+        offset: (-80.0, -80.0),
+        radius: 44.0,
+    },
+    TouchButton {
+        action: InputAction::Fire,
+        label: "B",
+        offset: (-180.0, -70.0),
+        radius: 36.0,
+    },
+    TouchButton {
+        action: InputAction::Pickup,
+        label: "X",
+        offset: (-90.0, -185.0),
+        radius: 32.0,
+    },
+    TouchButton {
+        action: InputAction::Slide,
+        label: "Y",
+        offset: (-180.0, -170.0),
+        radius: 28.0,
+    },
+    TouchButton {
+        action: InputAction::FireOffHand,
+        label: "R",
+        offset: (-270.0, -60.0),
+        radius: 28.0,
+    },
+];
+
+pub struct TouchControls {
+    /// This is set when the screen has been touched, on targets that have touch controls
+    is_active: bool,
+    /// The position of the stick, between `-1.0` and `1.0` on each axis
+    stick: Vec2,
+    active_actions: Vec<InputAction>,
+}
+
+impl TouchControls {
+    /// The distance of the center of the stick from the bottom left corner of the screen, before
+    /// scaling
+    const STICK_OFFSET: f32 = 130.0;
+    const STICK_RADIUS: f32 = 80.0;
+    const STICK_KNOB_RADIUS: f32 = 32.0;
+    /// The share of the stick radius that the stick must be moved, before it is registered
+    const STICK_THRESHOLD: f32 = 0.35;
+
+    const MENU_BUTTON_OFFSET: f32 = 40.0;
+    const MENU_BUTTON_RADIUS: f32 = 24.0;
+
+    const FONT_SIZE: u16 = 24;
+
+    pub fn new() -> Self {
+        TouchControls {
+            is_active: false,
+            stick: Vec2::ZERO,
+            active_actions: Vec::new(),
+        }
+    }
+
+    /// Returns `true` on targets that should have touch controls
+    fn is_touch_target() -> bool {
+        cfg!(any(
+            target_arch = "wasm32",
+            target_os = "android",
+            target_os = "ios"
+        ))
+    }
+
+    /// Returns the actions that are currently held on the touch controls
+    pub fn get_active_actions() -> Vec<InputAction> {
+        scene::find_node_by_type::<TouchControls>()
+            .map(|node| node.active_actions.clone())
+            .unwrap_or_default()
+    }
+
+    fn get_scale() -> f32 {
+        storage::get::<Config>().touch_controls.scale
+    }
+
+    fn get_stick_center(scale: f32) -> Vec2 {
+        vec2(
+            Self::STICK_OFFSET * scale,
+            screen_height() - Self::STICK_OFFSET * scale,
+        )
+    }
+
+    fn get_button_center(button: &TouchButton, scale: f32) -> Vec2 {
+        vec2(screen_width(), screen_height()) + vec2(button.offset.0, button.offset.1) * scale
+    }
+
+    fn get_menu_button_center(scale: f32) -> Vec2 {
+        vec2(
+            Self::MENU_BUTTON_OFFSET * scale,
+            Self::MENU_BUTTON_OFFSET * scale,
+        )
+    }
+}
+
+impl Default for TouchControls {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Node for TouchControls {
+    fn update(mut node: RefMut<Self>) {
+        let touches = touches();
+
+        if !node.is_active {
+            node.is_active = Self::is_touch_target() && !touches.is_empty();
+        }
+
+        node.stick = Vec2::ZERO;
+        node.active_actions.clear();
+
+        if !node.is_active {
+            return;
+        }
+
+        let scale = Self::get_scale();
+        let stick_center = Self::get_stick_center(scale);
+        let menu_button_center = Self::get_menu_button_center(scale);
+
+        for touch in touches {
+            if let TouchPhase::Ended | TouchPhase::Cancelled = touch.phase {
+                continue;
+            }
+
+            if touch.phase == TouchPhase::Started
+                && touch.position.distance(menu_button_center) <= Self::MENU_BUTTON_RADIUS * scale
+            {
+                gui::toggle_game_menu();
+                continue;
+            }
+
+            // Any touch on the left half of the screen moves the stick
+            if touch.position.x < screen_width() / 2.0 {
+                let offset = (touch.position - stick_center) / (Self::STICK_RADIUS * scale);
+                node.stick = if offset.length() > 1.0 {
+                    offset.normalize()
+                } else {
+                    offset
+                };
+                continue;
+            }
+
+            for button in BUTTONS {
+                let center = Self::get_button_center(button, scale);
+
+                if touch.position.distance(center) <= button.radius * scale {
+                    node.active_actions.push(button.action);
+                }
+            }
+        }
+
+        if node.stick.x < -Self::STICK_THRESHOLD {
+            node.active_actions.push(InputAction::Left);
+        } else if node.stick.x > Self::STICK_THRESHOLD {
+            node.active_actions.push(InputAction::Right);
+        }
+
+        if node.stick.y > Self::STICK_THRESHOLD {
+            node.active_actions.push(InputAction::Down);
+        }
+    }
+
+    fn draw(node: RefMut<Self>) {
+        if !node.is_active {
+            return;
+        }
+
+        let (scale, opacity) = {
+            let config = storage::get::<Config>();
+            (config.touch_controls.scale, config.touch_controls.opacity)
+        };
+
+        let mut color = color::WHITE;
+        color.a = opacity;
+
+        let mut fill_color = color::WHITE;
+        fill_color.a = opacity * 0.5;
+
+        push_camera_state();
+        set_default_camera();
+
+        let stick_center = Self::get_stick_center(scale);
+        draw_circle_lines(
+            stick_center.x,
+            stick_center.y,
+            Self::STICK_RADIUS * scale,
+            2.0,
+            color,
+        );
+
+        let knob = stick_center + node.stick * Self::STICK_RADIUS * scale;
+        draw_circle(knob.x, knob.y, Self::STICK_KNOB_RADIUS * scale, fill_color);
+
+        for button in BUTTONS {
+            let center = Self::get_button_center(button, scale);
+            let radius = button.radius * scale;
+
+            if node.active_actions.contains(&button.action) {
+                draw_circle(center.x, center.y, radius, fill_color);
+            }
+
+            draw_circle_lines(center.x, center.y, radius, 2.0, color);
+
+            draw_aligned_text(
+                button.label,
+                center,
+                HorizontalAlignment::Center,
+                VerticalAlignment::Center,
+                TextParams {
+                    font_size: (Self::FONT_SIZE as f32 * scale) as u16,
+                    color,
+                    ..Default::default()
+                },
+            );
+        }
+
+        let menu_button_center = Self::get_menu_button_center(scale);
+        draw_circle_lines(
+            menu_button_center.x,
+            menu_button_center.y,
+            Self::MENU_BUTTON_RADIUS * scale,
+            2.0,
+            color,
+        );
+
+        draw_aligned_text(
+            "=",
+            menu_button_center,
+            HorizontalAlignment::Center,
+            VerticalAlignment::Center,
+            TextParams {
+                font_size: (Self::FONT_SIZE as f32 * scale) as u16,
+                color,
+                ..Default::default()
+            },
+        );
+
+        pop_camera_state();
+    }
+}
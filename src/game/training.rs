@@ -0,0 +1,496 @@
+//! Training mode is a single player mode, on a dedicated map, for learning weapons and for testing
+//! items. The second player is a training dummy, that never moves, and that will respawn, like
+//! any other player, when it is killed. The match never ends, so respawns are infinite.
+//!
+//! The damage dealt to the dummy is shown above it by `DamageReadout`, and the following cheats
+//! are available, on the keyboard:
+//!
+//! - F1 opens a menu that spawns any item at the position of the player
+//! - F2 toggles drawing of the colliders of players and items
+//! - P pauses the simulation and, while paused, period advances it by a single tick
+
+use fishsticks::{Button, GamepadContext};
+
+use macroquad::{
+    color,
+    experimental::{
+        collections::storage,
+        scene::{self, Handle, Node, RefMut},
+    },
+    prelude::*,
+    ui::root_ui,
+};
+
+use crate::{
+    collect_input, console, exit_to_main_menu,
+    game::{
+        collect_aim, run_simulation_tick, GameEvent, InputToggles, LatencyOverlay, SimulationTimer,
+        WorldUi, SIMULATION_DELTA_TIME,
+    },
+    gui::{
+        self, FocusNavigator, GAME_MENU_RESULT_MAIN_MENU, GAME_MENU_RESULT_QUIT,
+        GAME_MENU_RESULT_RESTART,
+    },
+    localization::{tr, tr_args},
+    profiler::ProfileScope,
+    quit_to_desktop, restart_match,
+    text::{draw_aligned_text, HorizontalAlignment, VerticalAlignment},
+    GameInput, GameInputScheme, Item, Player, Resources,
+};
+
+fn draw_text(text: &str, position: Vec2, horizontal: HorizontalAlignment, color: Color) {
+    draw_aligned_text(
+        text,
+        position,
+        horizontal,
+        VerticalAlignment::Center,
+        TextParams {
+            font_size: 18,
+            color,
+            ..Default::default()
+        },
+    );
+}
+
+const PANEL_BACKGROUND_COLOR: Color = Color {
+    r: 0.0,
+    g: 0.0,
+    b: 0.0,
+    a: 0.75,
+};
+
+pub struct TrainingGame {
+    input_scheme: GameInputScheme,
+    input_toggles: InputToggles,
+    player: Handle<Player>,
+    dummy: Handle<Player>,
+    is_paused: bool,
+    /// This is set when a single tick should be run, while paused
+    should_advance_frame: bool,
+    should_draw_hitboxes: bool,
+    /// The ids of all items, in alphabetical order, as they are listed in the item menu
+    item_ids: Vec<String>,
+    /// This is set while the item menu is open
+    item_menu: Option<FocusNavigator>,
+}
+
+impl TrainingGame {
+    const ITEM_MENU_KEY: KeyCode = KeyCode::F1;
+    const HITBOXES_KEY: KeyCode = KeyCode::F2;
+    const PAUSE_KEY: KeyCode = KeyCode::P;
+    const FRAME_ADVANCE_KEY: KeyCode = KeyCode::Period;
+
+    /// The amount of items that are visible in the item menu, at once
+    const ITEM_MENU_ROW_CNT: usize = 16;
+    const ITEM_MENU_WIDTH: f32 = 220.0;
+    const ROW_HEIGHT: f32 = 24.0;
+    const PADDING: f32 = 12.0;
+
+    /// The height above the player that spawned items are placed at
+    const ITEM_SPAWN_OFFSET: f32 = 32.0;
+
+    const ITEM_HITBOX_COLOR: Color = color::YELLOW;
+
+    pub fn new(
+        input_scheme: GameInputScheme,
+        player: Handle<Player>,
+        dummy: Handle<Player>,
+    ) -> Self {
+        let mut item_ids = {
+            let resources = storage::get::<Resources>();
+            resources
+                .items
+                .values()
+                .map(|params| params.id.clone())
+                .collect::<Vec<_>>()
+        };
+
+        item_ids.sort();
+
+        TrainingGame {
+            input_scheme,
+            input_toggles: InputToggles::default(),
+            player,
+            dummy,
+            is_paused: false,
+            should_advance_frame: false,
+            should_draw_hitboxes: false,
+            item_ids,
+            item_menu: None,
+        }
+    }
+
+    /// Returns `true` if the game menu should be toggled. If the gamepad of the player has been
+    /// disconnected, start pressed on any other gamepad will assign that gamepad to the player.
+    fn check_start_pressed(&mut self) -> bool {
+        if is_key_pressed(KeyCode::Escape) {
+            return true;
+        }
+
+        if let GameInputScheme::Gamepad(ix) = self.input_scheme {
+            let gamepad_context = storage::get::<GamepadContext>();
+            if let Some(gamepad) = gamepad_context.gamepad(ix) {
+                return gamepad.digital_inputs.just_activated(Button::Start);
+            }
+
+            for (other, gamepad) in gamepad_context.gamepads() {
+                if gamepad.digital_inputs.just_activated(Button::Start) {
+                    self.input_scheme = GameInputScheme::Gamepad(other);
+                    break;
+                }
+            }
+        }
+
+        false
+    }
+
+    fn spawn_item(&self, item_id: &str) {
+        let params = {
+            let resources = storage::get::<Resources>();
+            resources.items.get(item_id).cloned()
+        };
+
+        if let Some(params) = params {
+            let position = {
+                let player = scene::get_node(self.player);
+                player.body.position - vec2(0.0, Self::ITEM_SPAWN_OFFSET)
+            };
+
+            scene::add_node(Item::new(position, params));
+        }
+    }
+
+    fn update_item_menu(&mut self) {
+        let item_cnt = self.item_ids.len();
+
+        let mut item_to_spawn = None;
+
+        if let Some(navigator) = &mut self.item_menu {
+            let input = navigator.update_input();
+
+            if input.up {
+                navigator.move_focus(-1, item_cnt, |_| true);
+            } else if input.down {
+                navigator.move_focus(1, item_cnt, |_| true);
+            }
+
+            if input.confirm {
+                item_to_spawn = navigator.get_focus();
+            }
+
+            if input.cancel {
+                self.item_menu = None;
+            }
+        }
+
+        if let Some(i) = item_to_spawn {
+            self.spawn_item(&self.item_ids[i]);
+        }
+    }
+
+    /// This runs a single tick of the simulation
+    fn tick(&mut self) {
+        let _scope = ProfileScope::new("tick");
+
+        #[cfg(debug_assertions)]
+        crate::physics::PhysicsDebugOverlay::clear();
+
+        // Input is not passed on to the player while the item menu is open, as it shares keys
+        // with menu navigation
+        let input = if self.item_menu.is_none() {
+            let mut input = self.input_toggles.apply(collect_input(self.input_scheme));
+
+            let origin = scene::get_node(self.player).body.position;
+            input.aim = collect_aim(self.input_scheme, origin);
+
+            input
+        } else {
+            GameInput::default()
+        };
+
+        scene::get_node(self.player).apply_input(input);
+
+        LatencyOverlay::on_input_applied();
+
+        // Neither player should be considered AFK, as damage is blocked for AFK players
+        for handle in &[self.player, self.dummy] {
+            let mut player = scene::get_node(*handle);
+            player.is_afk = false;
+            player.idle_timer = 0.0;
+        }
+
+        scene::get_node(self.dummy).apply_input(GameInput::default());
+
+        run_simulation_tick();
+
+        if let Some(mut readout) = scene::find_node_by_type::<DamageReadout>() {
+            readout.advance(SIMULATION_DELTA_TIME);
+        }
+    }
+
+    fn draw_hitboxes(&self) {
+        for player in scene::find_nodes_by_type::<Player>() {
+            let rect = player.body.get_collider_rect();
+            draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 1.0, player.get_color());
+        }
+
+        for item in scene::find_nodes_by_type::<Item>() {
+            let rect = item.body.get_collider_rect();
+            draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 1.0, Self::ITEM_HITBOX_COLOR);
+        }
+    }
+
+    fn draw_item_menu(&self, navigator: &FocusNavigator) {
+        let focus = navigator.get_focus();
+
+        // The visible rows are scrolled, so that the focused item is always visible
+        let first_row = focus
+            .map(|i| (i + 1).saturating_sub(Self::ITEM_MENU_ROW_CNT))
+            .unwrap_or(0);
+
+        let row_cnt = self.item_ids.len().min(Self::ITEM_MENU_ROW_CNT);
+        let height = Self::ROW_HEIGHT * (row_cnt + 1) as f32 + Self::PADDING * 2.0;
+        let position = vec2(Self::PADDING, (screen_height() - height) / 2.0);
+
+        draw_rectangle(
+            position.x,
+            position.y,
+            Self::ITEM_MENU_WIDTH,
+            height,
+            PANEL_BACKGROUND_COLOR,
+        );
+
+        let x = position.x + Self::PADDING;
+        let mut y = position.y + Self::PADDING + Self::ROW_HEIGHT / 2.0;
+
+        draw_text(
+            &tr("training.spawn_item"),
+            vec2(x, y),
+            HorizontalAlignment::Left,
+            color::LIGHTGRAY,
+        );
+
+        for (i, item_id) in self
+            .item_ids
+            .iter()
+            .enumerate()
+            .skip(first_row)
+            .take(row_cnt)
+        {
+            y += Self::ROW_HEIGHT;
+
+            let color = if Some(i) == focus {
+                gui::SELECTION_HIGHLIGHT_COLOR
+            } else {
+                color::WHITE
+            };
+
+            draw_text(item_id, vec2(x, y), HorizontalAlignment::Left, color);
+        }
+    }
+}
+
+impl Node for TrainingGame {
+    fn update(mut node: RefMut<Self>) {
+        if console::is_console_open() {
+            return;
+        }
+
+        if node.item_menu.is_none() && node.check_start_pressed() {
+            gui::toggle_game_menu();
+        }
+
+        if gui::is_game_menu_open() {
+            return;
+        }
+
+        if is_key_pressed(Self::ITEM_MENU_KEY) {
+            node.item_menu = match node.item_menu {
+                Some(_) => None,
+                None => Some(FocusNavigator::new(Some(0))),
+            };
+        }
+
+        if is_key_pressed(Self::HITBOXES_KEY) {
+            node.should_draw_hitboxes = !node.should_draw_hitboxes;
+        }
+
+        if is_key_pressed(Self::PAUSE_KEY) {
+            node.is_paused = !node.is_paused;
+        }
+
+        if node.is_paused && is_key_pressed(Self::FRAME_ADVANCE_KEY) {
+            node.should_advance_frame = true;
+        }
+
+        node.update_item_menu();
+
+        if node.is_paused {
+            if node.should_advance_frame {
+                node.should_advance_frame = false;
+                node.tick();
+            }
+        } else {
+            for _ in 0..SimulationTimer::advance() {
+                node.tick();
+
+                if gui::is_game_menu_open() {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn draw(node: RefMut<Self>) {
+        if node.should_draw_hitboxes {
+            node.draw_hitboxes();
+        }
+
+        push_camera_state();
+        set_default_camera();
+
+        if let Some(navigator) = &node.item_menu {
+            node.draw_item_menu(navigator);
+        }
+
+        if node.is_paused {
+            draw_text(
+                &tr("training.paused"),
+                vec2(screen_width() / 2.0, TrainingGame::PADDING * 4.0),
+                HorizontalAlignment::Center,
+                color::WHITE,
+            );
+        }
+
+        draw_text(
+            &tr("training.help"),
+            vec2(
+                screen_width() / 2.0,
+                screen_height() - TrainingGame::PADDING * 2.0,
+            ),
+            HorizontalAlignment::Center,
+            color::LIGHTGRAY,
+        );
+
+        pop_camera_state();
+
+        if gui::is_game_menu_open() {
+            if let Some(res) = gui::draw_game_menu(&mut *root_ui()) {
+                match res.into_usize() {
+                    GAME_MENU_RESULT_RESTART => restart_match(),
+                    GAME_MENU_RESULT_MAIN_MENU => exit_to_main_menu(),
+                    GAME_MENU_RESULT_QUIT => quit_to_desktop(),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// This shows the damage dealt to the training dummy, above it: the damage of the last hit, the
+/// total damage of the current combo and the damage per second over the combo. A combo ends when
+/// the dummy has not been damaged for `DamageReadout::COMBO_TIMEOUT`.
+pub struct DamageReadout {
+    dummy_id: u8,
+    last_hit: f32,
+    combo_damage: f32,
+    /// The time since the first hit of the current combo
+    combo_time: f32,
+    /// The time since the last hit
+    idle_time: f32,
+}
+
+impl DamageReadout {
+    pub const LISTENER_ID: &'static str = "damage_readout";
+
+    const COMBO_TIMEOUT: f32 = 2.0;
+    /// Damage per second is calculated over at least this duration, so that single hits do not
+    /// result in huge values
+    const MIN_DPS_TIME: f32 = 1.0;
+
+    /// The distance between the top of the dummy and the bottom line of the readout, in screen
+    /// pixels
+    const OFFSET: f32 = 24.0;
+
+    pub fn new(dummy_id: u8) -> Self {
+        DamageReadout {
+            dummy_id,
+            last_hit: 0.0,
+            combo_damage: 0.0,
+            combo_time: 0.0,
+            idle_time: Self::COMBO_TIMEOUT,
+        }
+    }
+
+    pub fn on_game_event(event: &GameEvent) {
+        if let GameEvent::PlayerDamaged {
+            player_id, damage, ..
+        } = *event
+        {
+            if let Some(mut node) = scene::find_node_by_type::<DamageReadout>() {
+                if player_id == node.dummy_id {
+                    if node.idle_time >= Self::COMBO_TIMEOUT {
+                        node.combo_damage = 0.0;
+                        node.combo_time = 0.0;
+                    }
+
+                    node.last_hit = damage;
+                    node.combo_damage += damage;
+                    node.idle_time = 0.0;
+                }
+            }
+        }
+    }
+
+    /// This advances the combo timers by `dt`. It is called by `TrainingGame` on every simulation
+    /// tick, so that the timers are paused along with the simulation.
+    fn advance(&mut self, dt: f32) {
+        if self.idle_time < Self::COMBO_TIMEOUT {
+            self.combo_time += dt;
+        }
+
+        self.idle_time += dt;
+    }
+
+    fn get_dps(&self) -> f32 {
+        self.combo_damage / self.combo_time.max(Self::MIN_DPS_TIME)
+    }
+}
+
+impl Node for DamageReadout {
+    fn draw(node: RefMut<Self>) {
+        let position = scene::find_nodes_by_type::<Player>()
+            .find(|player| player.id == node.dummy_id)
+            .and_then(|player| {
+                WorldUi::world_to_screen(WorldUi::get_player_anchor_position(&player))
+            });
+
+        let position = match position {
+            Some(position) => position,
+            None => return,
+        };
+
+        let lines = [
+            tr_args("training.last_hit", &[&format!("{:.0}", node.last_hit)]),
+            tr_args("training.combo", &[&format!("{:.0}", node.combo_damage)]),
+            tr_args("training.dps", &[&format!("{:.1}", node.get_dps())]),
+        ];
+
+        push_camera_state();
+        set_default_camera();
+
+        let mut y = position.y - Self::OFFSET;
+
+        for line in lines.iter().rev() {
+            draw_text(
+                line,
+                vec2(position.x, y),
+                HorizontalAlignment::Center,
+                color::WHITE,
+            );
+            y -= TrainingGame::ROW_HEIGHT;
+        }
+
+        pop_camera_state();
+    }
+}
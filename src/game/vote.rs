@@ -0,0 +1,111 @@
+//! Votes where every local player picks one of a row of options, with their own input device.
+//! Left and right changes the selection, jump or fire confirms the vote and pick up cancels it,
+//! so that a player can change their mind, until every player has voted.
+
+use macroquad::prelude::*;
+
+use crate::{collect_input, GameInput, GameInputScheme, Player};
+
+pub struct PlayerVote {
+    pub player_id: u8,
+    pub selection: usize,
+    pub is_confirmed: bool,
+    input_scheme: GameInputScheme,
+    previous_input: GameInput,
+}
+
+pub struct Vote {
+    option_cnt: usize,
+    votes: Vec<PlayerVote>,
+}
+
+impl Vote {
+    const MARKER_SIZE: f32 = 10.0;
+
+    pub fn new(players: &[(GameInputScheme, u8)], option_cnt: usize) -> Self {
+        let votes = players
+            .iter()
+            .map(|&(input_scheme, player_id)| PlayerVote {
+                player_id,
+                selection: 0,
+                is_confirmed: false,
+                input_scheme,
+                // Inputs that are held when the vote starts should not count as presses
+                previous_input: collect_input(input_scheme),
+            })
+            .collect();
+
+        Vote { option_cnt, votes }
+    }
+
+    /// This updates the votes of all players and returns `true` when all players have voted
+    pub fn update(&mut self) -> bool {
+        let option_cnt = self.option_cnt;
+
+        for vote in &mut self.votes {
+            let input = collect_input(vote.input_scheme);
+            let previous = vote.previous_input;
+            vote.previous_input = input;
+
+            if vote.is_confirmed {
+                if input.pickup && !previous.pickup {
+                    vote.is_confirmed = false;
+                }
+
+                continue;
+            }
+
+            if input.left && !previous.left {
+                vote.selection = (vote.selection + option_cnt - 1) % option_cnt;
+            } else if input.right && !previous.right {
+                vote.selection = (vote.selection + 1) % option_cnt;
+            }
+
+            if (input.jump && !previous.jump) || (input.fire && !previous.fire) {
+                vote.is_confirmed = true;
+            }
+        }
+
+        self.votes.iter().all(|vote| vote.is_confirmed)
+    }
+
+    pub fn get_votes(&self) -> &[PlayerVote] {
+        &self.votes
+    }
+
+    /// Returns the amount of votes for each option
+    pub fn get_counts(&self) -> Vec<usize> {
+        let mut res = vec![0; self.option_cnt];
+
+        for vote in &self.votes {
+            res[vote.selection] += 1;
+        }
+
+        res
+    }
+
+    /// This marks every player that has `option` selected, in a row centered on `position`, with
+    /// a filled marker if they have confirmed their vote
+    pub fn draw_markers(&self, option: usize, position: Vec2) {
+        let voters = self
+            .votes
+            .iter()
+            .filter(|vote| vote.selection == option)
+            .collect::<Vec<_>>();
+
+        let spacing = Self::MARKER_SIZE * 2.0;
+        let width = spacing * voters.len() as f32;
+        let radius = Self::MARKER_SIZE / 2.0;
+
+        for (i, vote) in voters.iter().enumerate() {
+            let x = position.x - width / 2.0 + spacing * (i as f32 + 0.5);
+            let color = Player::get_color_by_id(vote.player_id);
+
+            if vote.is_confirmed {
+                draw_circle(x, position.y, radius, color);
+            } else {
+                draw_circle_lines(x, position.y, radius, 2.0, color);
+            }
+        }
+    }
+}
@@ -1,12 +1,160 @@
+use std::collections::HashMap;
+
 use macroquad::prelude::*;
 
 use macroquad_platformer::{Tile, World as CollisionWorld};
 
-use crate::Map;
+use crate::{
+    audio::SoundGroup,
+    map::{MapTileset, TileSlope},
+    Map,
+};
+
+/// The collision shape of a tile in the layers with collision
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TileCollision {
+    Empty,
+    Solid,
+    Platform,
+    Slope(TileSlope),
+}
+
+/// The material of the surface of a tile, that selects the sounds played by bodies landing or
+/// walking on it
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SurfaceSound {
+    Default,
+    Metal,
+    Wood,
+    Sand,
+    Water,
+}
+
+impl SurfaceSound {
+    pub fn from_attributes(attributes: &[String]) -> Self {
+        let has_attribute = |attribute: &str| attributes.iter().any(|s| s == attribute);
+
+        if has_attribute(Map::METAL_TILE_ATTRIBUTE) {
+            Self::Metal
+        } else if has_attribute(Map::WOOD_TILE_ATTRIBUTE) {
+            Self::Wood
+        } else if has_attribute(Map::SAND_TILE_ATTRIBUTE) {
+            Self::Sand
+        } else if has_attribute(Map::WATER_TILE_ATTRIBUTE) {
+            Self::Water
+        } else {
+            Self::Default
+        }
+    }
+
+    /// Returns the suffix added to sound effect ids for the surface variant, if any
+    fn get_suffix(&self) -> Option<&'static str> {
+        match self {
+            Self::Default => None,
+            Self::Metal => Some(Map::METAL_TILE_ATTRIBUTE),
+            Self::Wood => Some(Map::WOOD_TILE_ATTRIBUTE),
+            Self::Sand => Some(Map::SAND_TILE_ATTRIBUTE),
+            Self::Water => Some(Map::WATER_TILE_ATTRIBUTE),
+        }
+    }
+
+    /// Returns the sound effect with `id`, or its variant for this surface, like `land_metal` for
+    /// `land` on metal, if the variant exists
+    pub fn get_sound<'a>(
+        &self,
+        sounds: &'a HashMap<String, SoundGroup>,
+        id: &str,
+    ) -> Option<&'a SoundGroup> {
+        self.get_suffix()
+            .and_then(|suffix| sounds.get(&format!("{}_{}", id, suffix)))
+            .or_else(|| sounds.get(id))
+    }
+}
+
+impl Default for SurfaceSound {
+    fn default() -> Self {
+        Self::Default
+    }
+}
+
+/// The properties of the surface of a tile in the layers with collision, that affect bodies
+/// standing on it
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct TileSurface {
+    /// The horizontal velocity applied to bodies standing on the tile, where a negative value
+    /// moves them to the left
+    pub conveyor_speed: f32,
+    /// The factor of the vertical velocity of a body landing on the tile that it bounces back
+    /// with. If this is zero, the bouncyness of the body is used.
+    pub bounciness: f32,
+    /// If this is set, it replaces the friction of bodies standing on the tile
+    pub friction: Option<f32>,
+    /// The fraction of horizontal movement and jump force lost by bodies standing on the tile
+    pub stickiness: f32,
+    /// The material that selects the sounds played by bodies on the tile
+    pub sound: SurfaceSound,
+}
+
+impl TileSurface {
+    /// Returns the surface described by `attributes`, using the properties of `tileset`
+    pub fn from_attributes(attributes: &[String], tileset: Option<&MapTileset>) -> Self {
+        let get_property = |id: &str, default: f32| {
+            tileset
+                .map(|tileset| tileset.get_f32_property(id, default))
+                .unwrap_or(default)
+        };
+
+        let has_attribute = |attribute: &str| attributes.iter().any(|s| s == attribute);
+
+        let mut res = TileSurface {
+            sound: SurfaceSound::from_attributes(attributes),
+            ..Default::default()
+        };
+
+        if has_attribute(Map::CONVEYOR_LEFT_TILE_ATTRIBUTE) {
+            res.conveyor_speed = -get_property(
+                Map::CONVEYOR_SPEED_TILESET_PROPERTY,
+                Map::DEFAULT_CONVEYOR_SPEED,
+            );
+        } else if has_attribute(Map::CONVEYOR_RIGHT_TILE_ATTRIBUTE) {
+            res.conveyor_speed = get_property(
+                Map::CONVEYOR_SPEED_TILESET_PROPERTY,
+                Map::DEFAULT_CONVEYOR_SPEED,
+            );
+        }
+
+        if has_attribute(Map::BOUNCY_TILE_ATTRIBUTE) {
+            res.bounciness =
+                get_property(Map::BOUNCINESS_TILESET_PROPERTY, Map::DEFAULT_BOUNCINESS);
+        }
+
+        if has_attribute(Map::ICE_TILE_ATTRIBUTE) {
+            res.friction = Some(get_property(
+                Map::ICE_FRICTION_TILESET_PROPERTY,
+                Map::DEFAULT_ICE_FRICTION,
+            ));
+        }
+
+        if has_attribute(Map::STICKY_TILE_ATTRIBUTE) {
+            res.stickiness =
+                get_property(Map::STICKINESS_TILESET_PROPERTY, Map::DEFAULT_STICKINESS)
+                    .clamp(0.0, 1.0);
+        }
+
+        res
+    }
+}
 
 pub struct GameWorld {
     pub map: Map,
     pub collision_world: CollisionWorld,
+    /// This holds the collision shape of each tile in the layers with collision, indexed the same
+    /// way as the tiles of a map layer. Slope tiles are not added to the collision world, as it
+    /// only supports axis-aligned solids, so they have to be resolved separately.
+    pub tile_collisions: Vec<TileCollision>,
+    /// This holds the surface of each tile in the layers with collision, indexed the same way as
+    /// `tile_collisions`
+    pub tile_surfaces: Vec<TileSurface>,
 }
 
 impl GameWorld {
@@ -17,18 +165,31 @@ impl GameWorld {
             static_colliders.push(Tile::Empty);
         }
 
+        let mut tile_collisions = vec![TileCollision::Empty; tile_cnt];
+        let mut tile_surfaces = vec![TileSurface::default(); tile_cnt];
+
         for layer_id in &map.draw_order {
             let layer = map.layers.get(layer_id).unwrap();
             if layer.has_collision {
                 for (i, (_, _, tile)) in map.get_tiles(layer_id, None).enumerate() {
                     if let Some(tile) = tile {
-                        if tile
+                        tile_surfaces[i] = TileSurface::from_attributes(
+                            &tile.attributes,
+                            map.tilesets.get(&tile.tileset_id),
+                        );
+
+                        if let Some(slope) = TileSlope::from_attributes(&tile.attributes) {
+                            static_colliders[i] = Tile::Empty;
+                            tile_collisions[i] = TileCollision::Slope(slope);
+                        } else if tile
                             .attributes
                             .contains(&Map::PLATFORM_TILE_ATTRIBUTE.to_string())
                         {
                             static_colliders[i] = Tile::JumpThrough;
+                            tile_collisions[i] = TileCollision::Platform;
                         } else {
                             static_colliders[i] = Tile::Solid;
+                            tile_collisions[i] = TileCollision::Solid;
                         }
                     }
                 }
@@ -47,7 +208,140 @@ impl GameWorld {
         GameWorld {
             map,
             collision_world,
+            tile_collisions,
+            tile_surfaces,
+        }
+    }
+
+    /// Returns the collision shape of the tile at the grid coordinates `x` and `y`. Coordinates
+    /// outside of the map will return `TileCollision::Empty`.
+    pub fn get_tile_collision(&self, x: i32, y: i32) -> TileCollision {
+        let grid_size = self.map.grid_size;
+
+        if x < 0 || y < 0 || x as u32 >= grid_size.x || y as u32 >= grid_size.y {
+            return TileCollision::Empty;
+        }
+
+        let i = self.map.to_index(uvec2(x as u32, y as u32));
+        self.tile_collisions[i]
+    }
+
+    /// Returns the collision shape of the tile at `position`. Positions outside of the map will
+    /// return `TileCollision::Empty`.
+    pub fn get_tile_collision_at(&self, position: Vec2) -> TileCollision {
+        let coords = ((position - self.map.world_offset) / self.map.tile_size).floor();
+        self.get_tile_collision(coords.x as i32, coords.y as i32)
+    }
+
+    /// Returns the slope of the tile at `position`, along with the rect of the tile, if the tile
+    /// at `position` is a slope
+    pub fn get_slope_at(&self, position: Vec2) -> Option<(TileSlope, Rect)> {
+        let map_size = self.map.grid_size.as_f32() * self.map.tile_size;
+        let map_rect = Rect::new(
+            self.map.world_offset.x,
+            self.map.world_offset.y,
+            map_size.x,
+            map_size.y,
+        );
+
+        if !map_rect.contains(position) {
+            return None;
+        }
+
+        let coords = self.map.to_coords(position);
+        let i = self.map.to_index(coords);
+
+        let slope = match self.tile_collisions.get(i) {
+            Some(TileCollision::Slope(slope)) => Some(*slope),
+            _ => None,
+        };
+
+        slope.map(|slope| {
+            let tile_position = self.map.to_position(coords);
+            let tile_rect = Rect::new(
+                tile_position.x,
+                tile_position.y,
+                self.map.tile_size.x,
+                self.map.tile_size.y,
+            );
+
+            (slope, tile_rect)
+        })
+    }
+
+    /// Returns the y position of the surface of the slope at `position`, if the tile at
+    /// `position` is a slope
+    pub fn get_slope_surface_y(&self, position: Vec2) -> Option<f32> {
+        self.get_slope_at(position).map(|(slope, tile_rect)| {
+            let x = (position.x - tile_rect.x) / tile_rect.w;
+            tile_rect.y + tile_rect.h - slope.get_surface_height(x) * tile_rect.h
+        })
+    }
+
+    /// Returns the surface normal of the slope at `position`, if the tile at `position` is a slope
+    pub fn get_slope_normal(&self, position: Vec2) -> Option<Vec2> {
+        self.get_slope_at(position)
+            .map(|(slope, _)| slope.get_normal(self.map.tile_size))
+    }
+
+    /// Returns `true` if `position` is inside a solid tile, or below the surface of a slope tile
+    pub fn is_solid_at(&self, position: Vec2) -> bool {
+        self.collision_world.solid_at(position)
+            || self
+                .get_slope_surface_y(position)
+                .map(|surface_y| position.y >= surface_y)
+                .unwrap_or(false)
+    }
+
+    /// This checks for the surface of a slope under the bottom center of `rect`, either
+    /// intersecting it, from at most half a tile above its bottom, or at most `max_distance`
+    /// below it. If one is found, the y position of the surface and the slope is returned.
+    pub fn get_slope_ground(&self, rect: &Rect, max_distance: f32) -> Option<(f32, TileSlope)> {
+        let x = rect.x + rect.w / 2.0;
+        let bottom = rect.y + rect.h;
+
+        let min_y = bottom - self.map.tile_size.y / 2.0;
+        let max_y = bottom + max_distance;
+
+        let mut res: Option<(f32, TileSlope)> = None;
+
+        for y in [min_y, bottom - 1.0, max_y] {
+            let position = vec2(x, y);
+
+            if let Some((slope, _)) = self.get_slope_at(position) {
+                let surface_y = self.get_slope_surface_y(position).unwrap();
+
+                if surface_y >= min_y
+                    && surface_y <= max_y
+                    && res.map(|(res_y, _)| surface_y < res_y).unwrap_or(true)
+                {
+                    res = Some((surface_y, slope));
+                }
+            }
         }
+
+        res
+    }
+
+    /// Returns the surface of the tile right below the bottom center of `rect`, or the default
+    /// surface, if there is no tile there
+    pub fn get_surface_under(&self, rect: &Rect) -> TileSurface {
+        let position = vec2(rect.x + rect.w / 2.0, rect.y + rect.h + 1.0);
+
+        let map_size = self.map.grid_size.as_f32() * self.map.tile_size;
+        let map_rect = Rect::new(
+            self.map.world_offset.x,
+            self.map.world_offset.y,
+            map_size.x,
+            map_size.y,
+        );
+
+        if !map_rect.contains(position) {
+            return TileSurface::default();
+        }
+
+        let i = self.map.to_index(self.map.to_coords(position));
+        self.tile_surfaces.get(i).copied().unwrap_or_default()
     }
 
     pub fn get_random_spawn_point(&self) -> Vec2 {
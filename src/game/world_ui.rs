@@ -0,0 +1,356 @@
+//! The world UI is a lightweight UI layer for elements that are placed in the world, but drawn in
+//! screen space, so that text stays sharp regardless of camera zoom. Elements can be anchored to a
+//! fixed position in the world, or to a player, in which case they follow the player around.
+//!
+//! This draws floating damage numbers and health bars above the heads of players, if they are
+//! enabled in the `Ruleset`, and popups can be added by anything else with `WorldUi::add_popup`.
+//!
+//! Nameplates are drawn above the heads of players, if they are enabled with the `nameplates`
+//! setting in `Config`. They are hidden when there are only two players, as it is obvious who is
+//! who in a 1v1, and they fade out when players are close to each other, so that they do not
+//! cover the action.
+
+use macroquad::{
+    experimental::{
+        collections::storage,
+        scene::{self, Node, RefMut},
+    },
+    prelude::*,
+};
+
+use crate::{
+    cosmetic_rand,
+    frame_alloc::frame_vec,
+    game::{GameCamera, GameEvent},
+    localization::tr_args,
+    pool::Pool,
+    text::{draw_aligned_text, HorizontalAlignment, VerticalAlignment},
+    Config, Player, Resources,
+};
+
+/// What a world UI element is placed relative to
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum WorldUiAnchor {
+    /// A fixed position in the world
+    Position(Vec2),
+    /// The top center of the player with the given id, or its last known position, if the player
+    /// no longer exists
+    Player { player_id: u8, last_position: Vec2 },
+}
+
+impl WorldUiAnchor {
+    pub fn player(player_id: u8, position: Vec2) -> Self {
+        WorldUiAnchor::Player {
+            player_id,
+            last_position: position,
+        }
+    }
+
+    /// This updates the last known position of a player anchor and returns the position of the
+    /// anchor in the world
+    fn update(&mut self) -> Vec2 {
+        match self {
+            WorldUiAnchor::Position(position) => *position,
+            WorldUiAnchor::Player {
+                player_id,
+                last_position,
+            } => {
+                if let Some(player) =
+                    scene::find_nodes_by_type::<Player>().find(|player| player.id == *player_id)
+                {
+                    *last_position = WorldUi::get_player_anchor_position(&player);
+                }
+
+                *last_position
+            }
+        }
+    }
+}
+
+struct Popup {
+    anchor: WorldUiAnchor,
+    text: String,
+    color: Color,
+    duration: f32,
+    timer: f32,
+    /// A random horizontal offset, so that popups added at the same time do not overlap
+    offset_x: f32,
+}
+
+/// Popups are only default constructed to fill the slots of the pool, which are reset by
+/// `WorldUi::add_popup` before they are used
+impl Default for Popup {
+    fn default() -> Self {
+        Popup {
+            anchor: WorldUiAnchor::Position(Vec2::ZERO),
+            text: String::new(),
+            color: WHITE,
+            duration: 0.0,
+            timer: 0.0,
+            offset_x: 0.0,
+        }
+    }
+}
+
+pub struct WorldUi {
+    /// The popups are pooled, so that the text buffers of expired popups are reused
+    popups: Pool<Popup>,
+}
+
+impl WorldUi {
+    pub const LISTENER_ID: &'static str = "world_ui";
+
+    /// The distance, in screen pixels, that popups rise over their duration
+    const POPUP_RISE: f32 = 32.0;
+    const POPUP_SPREAD: f32 = 12.0;
+    const POPUP_FONT_SIZE: u16 = 20;
+    const INITIAL_POPUP_POOL_SIZE: usize = 16;
+    const DAMAGE_NUMBER_DURATION: f32 = 0.8;
+    const DAMAGE_NUMBER_COLOR: Color = Color {
+        r: 1.0,
+        g: 0.85,
+        b: 0.3,
+        a: 1.0,
+    };
+
+    const HEALTH_BAR_WIDTH: f32 = 36.0;
+    const HEALTH_BAR_HEIGHT: f32 = 5.0;
+    /// The distance between the top of a player and its health bar, in screen pixels
+    const HEALTH_BAR_OFFSET: f32 = 10.0;
+    const HEALTH_BAR_BACKGROUND_COLOR: Color = Color {
+        r: 0.0,
+        g: 0.0,
+        b: 0.0,
+        a: 0.6,
+    };
+
+    /// The distance between the top of a player and the bottom of its nameplate, in screen
+    /// pixels, so that it is drawn above the health bar
+    const NAMEPLATE_OFFSET: f32 = 20.0;
+    const NAMEPLATE_FONT_SIZE: u16 = 14;
+    /// Nameplates are hidden if there are no more players than this
+    const NAMEPLATE_MIN_PLAYER_CNT: usize = 3;
+    /// Nameplates start to fade out when another player is closer than this, in world units
+    const NAMEPLATE_FADE_DISTANCE: f32 = 128.0;
+    /// Nameplates are at their lowest opacity when another player is closer than this
+    const NAMEPLATE_MIN_DISTANCE: f32 = 48.0;
+    const NAMEPLATE_MIN_OPACITY: f32 = 0.2;
+
+    pub fn new() -> Self {
+        WorldUi {
+            popups: Pool::with_capacity(Self::INITIAL_POPUP_POOL_SIZE),
+        }
+    }
+
+    pub fn on_game_event(event: &GameEvent) {
+        if let GameEvent::PlayerDamaged {
+            player_id,
+            position,
+            damage,
+            ..
+        } = *event
+        {
+            let is_enabled = storage::get::<Resources>().ruleset.damage_numbers;
+
+            if is_enabled && damage > 0.0 {
+                Self::add_popup(
+                    WorldUiAnchor::player(player_id, position),
+                    &format!("{:.0}", damage),
+                    Self::DAMAGE_NUMBER_COLOR,
+                    Self::DAMAGE_NUMBER_DURATION,
+                );
+            }
+        }
+    }
+
+    /// This adds a line of text at `anchor`, that rises and fades out over `duration`
+    pub fn add_popup(anchor: WorldUiAnchor, text: &str, color: Color, duration: f32) {
+        if let Some(mut node) = scene::find_node_by_type::<WorldUi>() {
+            node.popups.spawn_with(|popup| {
+                popup.anchor = anchor;
+                popup.text.clear();
+                popup.text.push_str(text);
+                popup.color = color;
+                popup.duration = duration;
+                popup.timer = 0.0;
+                popup.offset_x = cosmetic_rand::gen_range(-Self::POPUP_SPREAD, Self::POPUP_SPREAD);
+            });
+        }
+    }
+
+    /// Returns the position in the world that elements anchored to `player` are placed at
+    pub fn get_player_anchor_position(player: &Player) -> Vec2 {
+        player.body.position + vec2(player.body.size.x / 2.0, 0.0)
+    }
+
+    /// Returns the position on the screen of `position` in the world, or `None`, if there is no
+    /// game camera
+    pub fn world_to_screen(position: Vec2) -> Option<Vec2> {
+        let camera = scene::find_node_by_type::<GameCamera>()?.get_macroquad_camera();
+        Some(camera.world_to_screen(position))
+    }
+
+    /// Returns the opacity of the nameplate of the player at `position`, by the distance to the
+    /// closest of `others`
+    fn get_nameplate_opacity(position: Vec2, others: &[Vec2]) -> f32 {
+        let distance = others
+            .iter()
+            .map(|other| other.distance(position))
+            .fold(f32::INFINITY, f32::min);
+
+        let t = (distance - Self::NAMEPLATE_MIN_DISTANCE)
+            / (Self::NAMEPLATE_FADE_DISTANCE - Self::NAMEPLATE_MIN_DISTANCE);
+
+        Self::NAMEPLATE_MIN_OPACITY + (1.0 - Self::NAMEPLATE_MIN_OPACITY) * t.clamp(0.0, 1.0)
+    }
+
+    fn draw_nameplates() {
+        if scene::find_nodes_by_type::<Player>().count() < Self::NAMEPLATE_MIN_PLAYER_CNT {
+            return;
+        }
+
+        let mut players = frame_vec::<(u8, Color, Vec2)>();
+        players.extend(
+            scene::find_nodes_by_type::<Player>()
+                .filter(|player| !player.is_dead)
+                .map(|player| {
+                    (
+                        player.id,
+                        player.get_color(),
+                        Self::get_player_anchor_position(&player),
+                    )
+                }),
+        );
+
+        let mut others = frame_vec::<Vec2>();
+
+        for (i, (player_id, color, anchor)) in players.iter().enumerate() {
+            let position = match Self::world_to_screen(*anchor) {
+                Some(position) => position,
+                None => continue,
+            };
+
+            others.clear();
+            others.extend(
+                players
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .map(|(_, (_, _, other))| *other),
+            );
+
+            let opacity = Self::get_nameplate_opacity(*anchor, &others);
+
+            draw_aligned_text(
+                &tr_args("player.name", &[&(player_id + 1).to_string()]),
+                position - vec2(0.0, Self::NAMEPLATE_OFFSET),
+                HorizontalAlignment::Center,
+                VerticalAlignment::Bottom,
+                TextParams {
+                    font_size: Self::NAMEPLATE_FONT_SIZE,
+                    color: Color {
+                        a: color.a * opacity,
+                        ..*color
+                    },
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
+    fn draw_health_bar(player: &Player) {
+        let position = match Self::world_to_screen(Self::get_player_anchor_position(player)) {
+            Some(position) => position,
+            None => return,
+        };
+
+        let health = if player.max_health > 0.0 {
+            (player.health / player.max_health).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        let x = position.x - Self::HEALTH_BAR_WIDTH / 2.0;
+        let y = position.y - Self::HEALTH_BAR_OFFSET - Self::HEALTH_BAR_HEIGHT;
+
+        draw_rectangle(
+            x - 1.0,
+            y - 1.0,
+            Self::HEALTH_BAR_WIDTH + 2.0,
+            Self::HEALTH_BAR_HEIGHT + 2.0,
+            Self::HEALTH_BAR_BACKGROUND_COLOR,
+        );
+
+        draw_rectangle(
+            x,
+            y,
+            Self::HEALTH_BAR_WIDTH * health,
+            Self::HEALTH_BAR_HEIGHT,
+            player.get_color(),
+        );
+    }
+}
+
+impl Default for WorldUi {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Node for WorldUi {
+    fn update(mut node: RefMut<Self>) {
+        let dt = get_frame_time();
+
+        node.popups.retain(|popup| {
+            popup.timer += dt;
+            popup.timer < popup.duration
+        });
+    }
+
+    fn draw(mut node: RefMut<Self>) {
+        let is_health_bars_enabled = storage::get::<Resources>().ruleset.health_bars;
+        let is_nameplates_enabled = storage::get::<Config>().nameplates;
+
+        push_camera_state();
+        set_default_camera();
+
+        if is_nameplates_enabled {
+            Self::draw_nameplates();
+        }
+
+        if is_health_bars_enabled {
+            for player in scene::find_nodes_by_type::<Player>() {
+                if !player.is_dead {
+                    Self::draw_health_bar(&player);
+                }
+            }
+        }
+
+        for popup in node.popups.iter_mut() {
+            let position = match Self::world_to_screen(popup.anchor.update()) {
+                Some(position) => position,
+                None => continue,
+            };
+
+            let progress = popup.timer / popup.duration;
+            let position = position + vec2(popup.offset_x, -Self::POPUP_RISE * progress);
+
+            draw_aligned_text(
+                &popup.text,
+                position,
+                HorizontalAlignment::Center,
+                VerticalAlignment::Bottom,
+                TextParams {
+                    font_size: Self::POPUP_FONT_SIZE,
+                    color: Color {
+                        a: popup.color.a * (1.0 - progress * progress),
+                        ..popup.color
+                    },
+                    ..Default::default()
+                },
+            );
+        }
+
+        pop_camera_state();
+    }
+}
@@ -6,11 +6,13 @@ use macroquad::{
     ui::{hash, root_ui, widgets},
 };
 
-use super::{GuiResources, Panel};
+use super::{draw_toasts, GuiResources, Panel};
 
 use crate::gui::draw_main_menu_background;
 use crate::{
+    audio::update_music,
     is_gamepad_btn_pressed,
+    localization::tr,
     resources::{map_name_to_filename, MapResource, Resources},
     GamepadContext, Result,
 };
@@ -35,7 +37,7 @@ pub async fn show_create_map_menu() -> Result<Option<MapResource>> {
     let gui_resources = storage::get::<GuiResources>();
     root_ui().push_skin(&gui_resources.skins.menu);
 
-    let mut name = "Unnamed Map".to_string();
+    let mut name = tr("create_map.default_name");
     let mut description = "".to_string();
     let mut grid_width = "100".to_string();
     let mut grid_height = "100".to_string();
@@ -55,7 +57,7 @@ pub async fn show_create_map_menu() -> Result<Option<MapResource>> {
         draw_main_menu_background(true);
 
         Panel::new(hash!(), size, position)
-            .with_title("Create Map", false)
+            .with_title(&tr("create_map.title"), false)
             .ui(&mut *root_ui(), |ui, _| {
                 {
                     let size = vec2(275.0, 25.0);
@@ -103,7 +105,7 @@ pub async fn show_create_map_menu() -> Result<Option<MapResource>> {
                     widgets::InputText::new(hash!())
                         .size(size)
                         .ratio(1.0)
-                        .label("Tile size")
+                        .label(&tr("create_map.tile_size"))
                         .ui(ui, &mut tile_height);
 
                     widgets::InputText::new(hash!())
@@ -117,7 +119,7 @@ pub async fn show_create_map_menu() -> Result<Option<MapResource>> {
                     widgets::InputText::new(hash!())
                         .size(size)
                         .ratio(1.0)
-                        .label("Grid size")
+                        .label(&tr("create_map.grid_size"))
                         .ui(ui, &mut grid_height);
                 }
 
@@ -128,7 +130,7 @@ pub async fn show_create_map_menu() -> Result<Option<MapResource>> {
                 let btn_a = is_gamepad_btn_pressed(Some(&gamepad_system), fishsticks::Button::A);
                 let enter = is_key_pressed(KeyCode::Enter);
 
-                if ui.button(None, "Confirm") || btn_a || enter {
+                if ui.button(None, tr("create_map.confirm").as_str()) || btn_a || enter {
                     // TODO: Validate input
 
                     let tile_size = vec2(
@@ -154,7 +156,7 @@ pub async fn show_create_map_menu() -> Result<Option<MapResource>> {
                 let btn_b = is_gamepad_btn_pressed(Some(&gamepad_system), fishsticks::Button::B);
                 let escape = is_key_pressed(KeyCode::Escape);
 
-                if ui.button(None, "Cancel") || btn_b || escape {
+                if ui.button(None, tr("menu.cancel").as_str()) || btn_b || escape {
                     res = WindowState::Cancel;
                 }
             });
@@ -180,6 +182,10 @@ pub async fn show_create_map_menu() -> Result<Option<MapResource>> {
             _ => {}
         }
 
+        draw_toasts();
+
+        update_music();
+
         next_frame().await;
     }
 }
@@ -0,0 +1,202 @@
+//! The credits screen, which is opened from the main menu. It is rendered from the `credits`
+//! resource file, so that contributors and the attribution and licenses of community assets can
+//! be added without changing any code. The credits scroll up by themselves and any confirm or
+//! cancel input will skip them.
+
+use macroquad::{color, experimental::collections::storage, prelude::*};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    draw_button_prompts, draw_main_menu_background, draw_toasts, FocusNavigator, PromptAction,
+};
+
+use crate::{
+    audio::update_music,
+    input::update_gamepad_context,
+    localization::tr,
+    text::{draw_aligned_text, HorizontalAlignment, VerticalAlignment},
+    Resources,
+};
+
+/// A single line of the credits, like a contributor or an asset
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreditsEntry {
+    pub name: String,
+    /// The role of a contributor, or what an asset is used for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The license of an asset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+impl CreditsEntry {
+    /// Returns the line shown below the name, if any
+    fn get_details(&self) -> Option<String> {
+        let details = [&self.description, &self.license, &self.url]
+            .iter()
+            .filter_map(|detail| detail.as_deref())
+            .collect::<Vec<_>>();
+
+        if details.is_empty() {
+            None
+        } else {
+            Some(details.join(" - "))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreditsSection {
+    /// The title of the section. This is translated, if it is a string key.
+    pub title: String,
+    pub entries: Vec<CreditsEntry>,
+}
+
+/// The credits, loaded from the `credits` resource file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Credits {
+    pub sections: Vec<CreditsSection>,
+}
+
+/// The speed that the credits scroll at, in pixels per second
+const SCROLL_SPEED: f32 = 60.0;
+
+const TITLE_FONT_SIZE: u16 = 36;
+const SECTION_FONT_SIZE: u16 = 26;
+const NAME_FONT_SIZE: u16 = 20;
+const DETAILS_FONT_SIZE: u16 = 14;
+
+const TITLE_HEIGHT: f32 = 80.0;
+const SECTION_HEIGHT: f32 = 56.0;
+const NAME_HEIGHT: f32 = 24.0;
+const DETAILS_HEIGHT: f32 = 20.0;
+const SECTION_SPACING: f32 = 40.0;
+
+enum CreditsLine {
+    Title(String),
+    Section(String),
+    Name(String),
+    Details(String),
+}
+
+impl CreditsLine {
+    fn get_height(&self) -> f32 {
+        match self {
+            CreditsLine::Title(_) => TITLE_HEIGHT,
+            CreditsLine::Section(_) => SECTION_HEIGHT,
+            CreditsLine::Name(_) => NAME_HEIGHT,
+            CreditsLine::Details(_) => DETAILS_HEIGHT,
+        }
+    }
+
+    /// Returns the space above the line
+    fn get_spacing(&self) -> f32 {
+        match self {
+            CreditsLine::Section(_) => SECTION_SPACING,
+            _ => 0.0,
+        }
+    }
+
+    fn draw(&self, y: f32) {
+        let (text, font_size, color) = match self {
+            CreditsLine::Title(text) => (text, TITLE_FONT_SIZE, color::WHITE),
+            CreditsLine::Section(text) => (text, SECTION_FONT_SIZE, color::ORANGE),
+            CreditsLine::Name(text) => (text, NAME_FONT_SIZE, color::WHITE),
+            CreditsLine::Details(text) => (text, DETAILS_FONT_SIZE, color::LIGHTGRAY),
+        };
+
+        draw_aligned_text(
+            text,
+            vec2(screen_width() / 2.0, y),
+            HorizontalAlignment::Center,
+            VerticalAlignment::Top,
+            TextParams {
+                font_size,
+                color,
+                ..Default::default()
+            },
+        );
+    }
+}
+
+fn build_lines(credits: &Credits) -> Vec<CreditsLine> {
+    let mut lines = vec![CreditsLine::Title(tr("credits.title"))];
+
+    for section in &credits.sections {
+        lines.push(CreditsLine::Section(tr(&section.title)));
+
+        for entry in &section.entries {
+            lines.push(CreditsLine::Name(entry.name.clone()));
+
+            if let Some(details) = entry.get_details() {
+                lines.push(CreditsLine::Details(details));
+            }
+        }
+    }
+
+    lines
+}
+
+pub async fn show_credits() {
+    let lines = {
+        let resources = storage::get::<Resources>();
+        build_lines(&resources.credits)
+    };
+
+    let total_height = lines
+        .iter()
+        .map(|line| line.get_spacing() + line.get_height())
+        .sum::<f32>();
+
+    // The credits start just below the bottom of the screen
+    let mut scroll = 0.0;
+
+    let mut navigator = FocusNavigator::default();
+
+    // skip a frame to let Enter be unpressed from the previous screen
+    next_frame().await;
+
+    loop {
+        let _ = update_gamepad_context(None);
+
+        let input = navigator.update_input();
+
+        if input.confirm || input.cancel {
+            break;
+        }
+
+        scroll += SCROLL_SPEED * get_frame_time();
+
+        let mut y = screen_height() - scroll;
+
+        if y + total_height < 0.0 {
+            break;
+        }
+
+        draw_main_menu_background(false);
+
+        for line in &lines {
+            y += line.get_spacing();
+
+            let height = line.get_height();
+
+            if y + height > 0.0 && y < screen_height() {
+                line.draw(y);
+            }
+
+            y += height;
+        }
+
+        draw_button_prompts(&[PromptAction::Cancel]);
+
+        draw_toasts();
+
+        update_music();
+
+        next_frame().await;
+    }
+}
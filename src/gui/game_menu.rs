@@ -1,38 +1,92 @@
+//! The in-game menu, which is opened with escape or start during a match. In local games, the
+//! simulation is paused while the menu is open, but in network games the menu is only an overlay,
+//! as the other peers can not be paused, so it can not be used to restart the match there.
+
 use macroquad::{
     prelude::*,
     ui::{hash, Ui},
 };
 
-use super::{Menu, MenuEntry, MenuResult};
+use super::{draw_button_prompts, Menu, MenuEntry, MenuResult, PromptAction, SettingsMenu};
+
+use crate::localization::tr;
 
 const MENU_WIDTH: f32 = 300.0;
 
+/// The color that the screen is dimmed with, behind the menu
+const DIM_COLOR: Color = Color {
+    r: 0.0,
+    g: 0.0,
+    b: 0.0,
+    a: 0.5,
+};
+
 pub const GAME_MENU_RESULT_MAIN_MENU: usize = 0;
 pub const GAME_MENU_RESULT_QUIT: usize = 1;
+const GAME_MENU_RESULT_RESUME: usize = 2;
+pub const GAME_MENU_RESULT_RESTART: usize = 3;
+const GAME_MENU_RESULT_SETTINGS: usize = 4;
 
-static mut GAME_MENU_INSTANCE: Option<Menu> = None;
+struct GameMenu {
+    menu: Menu,
+    settings: Option<SettingsMenu>,
+    is_network_game: bool,
+}
+
+static mut GAME_MENU_INSTANCE: Option<GameMenu> = None;
 
+fn build_menu(is_network_game: bool) -> Menu {
+    let mut entries = vec![
+        MenuEntry {
+            index: GAME_MENU_RESULT_RESUME,
+            title: tr("game_menu.resume"),
+            ..Default::default()
+        },
+        MenuEntry {
+            index: GAME_MENU_RESULT_SETTINGS,
+            title: tr("game_menu.settings"),
+            ..Default::default()
+        },
+    ];
+
+    if !is_network_game {
+        entries.push(MenuEntry {
+            index: GAME_MENU_RESULT_RESTART,
+            title: tr("game_menu.restart"),
+            ..Default::default()
+        });
+    }
+
+    entries.push(MenuEntry {
+        index: GAME_MENU_RESULT_MAIN_MENU,
+        title: tr("game_menu.main_menu"),
+        ..Default::default()
+    });
+
+    entries.push(MenuEntry {
+        index: GAME_MENU_RESULT_QUIT,
+        title: tr("game_menu.quit"),
+        ..Default::default()
+    });
+
+    Menu::new(hash!(), MENU_WIDTH, &entries)
+}
+
+/// This opens the game menu of a local game, if it is not already open
 pub fn open_game_menu() {
+    open_game_menu_ex(false);
+}
+
+/// This opens the game menu, if it is not already open. If `is_network_game` is `true`, the
+/// option to restart the match is left out.
+pub fn open_game_menu_ex(is_network_game: bool) {
     unsafe {
         if GAME_MENU_INSTANCE.is_none() {
-            let menu = Menu::new(
-                hash!(),
-                MENU_WIDTH,
-                &[
-                    MenuEntry {
-                        index: GAME_MENU_RESULT_MAIN_MENU,
-                        title: "Main Menu".to_string(),
-                        ..Default::default()
-                    },
-                    MenuEntry {
-                        index: GAME_MENU_RESULT_QUIT,
-                        title: "Quit".to_string(),
-                        ..Default::default()
-                    },
-                ],
-            );
-
-            GAME_MENU_INSTANCE = Some(menu);
+            GAME_MENU_INSTANCE = Some(GameMenu {
+                menu: build_menu(is_network_game),
+                settings: None,
+                is_network_game,
+            });
         }
     }
 }
@@ -41,8 +95,11 @@ pub fn close_game_menu() {
     unsafe { GAME_MENU_INSTANCE = None };
 }
 
+/// This dims the screen and draws the game menu, or the settings menu, if it has been opened from
+/// the game menu. Selecting resume will close the menu, while the results that the game has to
+/// act on, like main menu and quit, are returned.
 pub fn draw_game_menu(ui: &mut Ui) -> Option<MenuResult> {
-    let menu = unsafe {
+    let game_menu = unsafe {
         if GAME_MENU_INSTANCE.is_none() {
             open_game_menu();
         }
@@ -50,22 +107,58 @@ pub fn draw_game_menu(ui: &mut Ui) -> Option<MenuResult> {
         GAME_MENU_INSTANCE.as_mut().unwrap()
     };
 
-    let res = menu.ui(ui);
+    push_camera_state();
+    set_default_camera();
 
-    if res.is_some() {
-        close_game_menu();
+    draw_rectangle(0.0, 0.0, screen_width(), screen_height(), DIM_COLOR);
+
+    pop_camera_state();
+
+    if let Some(settings) = &mut game_menu.settings {
+        draw_button_prompts(SettingsMenu::PROMPTS);
+
+        if settings.ui(ui).is_some() {
+            game_menu.settings = None;
+            game_menu.menu = build_menu(game_menu.is_network_game);
+        }
+
+        return None;
     }
 
-    res
+    draw_button_prompts(&[PromptAction::Navigate, PromptAction::Confirm]);
+
+    let res = game_menu.menu.ui(ui);
+
+    match res.map(|res| res.into_usize()) {
+        Some(GAME_MENU_RESULT_SETTINGS) => {
+            game_menu.settings = Some(SettingsMenu::new());
+            None
+        }
+        Some(_) => {
+            close_game_menu();
+            res
+        }
+        None => None,
+    }
 }
 
 pub fn is_game_menu_open() -> bool {
     unsafe { GAME_MENU_INSTANCE.is_some() }
 }
 
-/// Toggle game menu and return state after toggle
+/// Toggle game menu and return state after toggle. While the settings menu is open, this does
+/// nothing, as the settings menu handles going back by itself.
 pub fn toggle_game_menu() -> bool {
-    if is_game_menu_open() {
+    let is_in_settings = unsafe {
+        GAME_MENU_INSTANCE
+            .as_ref()
+            .map(|game_menu| game_menu.settings.is_some())
+            .unwrap_or(false)
+    };
+
+    if is_in_settings {
+        true
+    } else if is_game_menu_open() {
         close_game_menu();
         false
     } else {
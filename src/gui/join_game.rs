@@ -0,0 +1,69 @@
+use macroquad::{
+    experimental::collections::storage,
+    prelude::*,
+    ui::{hash, Ui},
+};
+
+use super::{GuiResources, Panel, WINDOW_BG_COLOR};
+
+use crate::{
+    game::InputAction,
+    localization::{tr, tr_args},
+    Config, GameInputScheme,
+};
+
+const PANEL_WIDTH: f32 = 300.0;
+const PANEL_HEIGHT: f32 = 120.0;
+
+const PANEL_MARGIN: f32 = 16.0;
+
+/// This draws a panel, at the bottom of the screen, for a player that is joining a match that is
+/// already in progress. `slot` is the index of the panel, among the panels currently shown, and
+/// the prompt shows the bindings of the device slot of `input_scheme`.
+pub fn draw_join_game_panel(
+    ui: &mut Ui,
+    slot: usize,
+    player_number: usize,
+    input_scheme: GameInputScheme,
+    character_name: &str,
+    is_confirmed: bool,
+) {
+    let size = vec2(PANEL_WIDTH, PANEL_HEIGHT);
+    let position = vec2(
+        PANEL_MARGIN + (PANEL_WIDTH + PANEL_MARGIN) * slot as f32,
+        screen_height() - PANEL_HEIGHT - PANEL_MARGIN,
+    );
+
+    Panel::new(hash!("join_game", slot), size, position)
+        .with_title(&tr_args("player.name", &[&player_number.to_string()]), true)
+        .with_background_color(WINDOW_BG_COLOR)
+        .ui(ui, |ui, _| {
+            {
+                let gui_resources = storage::get::<GuiResources>();
+                ui.push_skin(&gui_resources.skins.menu);
+            }
+
+            ui.label(vec2(12.0, 12.0), &format!("< {} >", character_name));
+
+            if is_confirmed {
+                ui.label(vec2(12.0, 44.0), &tr("join_game.ready"));
+            } else {
+                let (confirm, cancel) = {
+                    let config = storage::get::<Config>();
+                    let profile = config.input_bindings.get_profile(input_scheme);
+
+                    (
+                        profile.get_binding_names(InputAction::Jump),
+                        profile.get_binding_names(InputAction::Fire),
+                    )
+                };
+
+                ui.label(
+                    vec2(12.0, 44.0),
+                    &tr_args("join_game.prompt", &[&confirm, &cancel]),
+                );
+            }
+
+            ui.pop_skin();
+        });
+}
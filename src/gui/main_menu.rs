@@ -7,24 +7,39 @@ use std::borrow::BorrowMut;
 
 use fishsticks::{Button, GamepadContext};
 
-use super::{draw_main_menu_background, GuiResources, Menu, MenuEntry, MenuResult, Panel};
+use super::{
+    draw_button_prompts, draw_main_menu_background, draw_toasts, is_any_input_pressed,
+    show_credits, GuiResources, Menu, MenuEntry, MenuResult, Panel, PromptAction, SettingsMenu,
+};
 
+use crate::audio::update_music;
 use crate::input::update_gamepad_context;
-use crate::{is_gamepad_btn_pressed, EditorInputScheme, GameInputScheme, Resources};
+use crate::{
+    game::{is_action_pressed, InputAction, ATTRACT_MODE_DELAY},
+    is_gamepad_btn_pressed,
+    localization::{tr, tr_args},
+    mods::ModStatus,
+    Config, EditorInputScheme, GameInputScheme, Resources,
+};
 
 const MENU_WIDTH: f32 = 300.0;
 
 const HEADER_TEXTURE_ID: &str = "main_menu_header";
 
+const MODS_MENU_WIDTH: f32 = 500.0;
+
 const LOCAL_GAME_MENU_WIDTH: f32 = 400.0;
 const LOCAL_GAME_MENU_HEIGHT: f32 = 200.0;
 
 pub enum MainMenuResult {
     LocalGame(Vec<GameInputScheme>),
+    Training(GameInputScheme),
     Editor {
         input_scheme: EditorInputScheme,
         is_new_map: bool,
     },
+    /// The title screen has been idle for `ATTRACT_MODE_DELAY`
+    AttractMode,
     ReloadResources,
     Quit,
 }
@@ -33,16 +48,26 @@ pub enum MainMenuResult {
 enum MainMenuState {
     Root(Menu),
     LocalGame,
+    Training,
     NetworkGame,
-    Settings,
+    Settings(SettingsMenu),
     Editor(Menu),
+    /// The mod manager. `is_changed` is set when a mod is enabled or disabled, so that resources
+    /// are reloaded when the menu is closed.
+    Mods {
+        menu: Menu,
+        is_changed: bool,
+    },
 }
 
 const ROOT_OPTION_LOCAL_GAME: usize = 0;
-const ROOT_OPTION_NETWORK_GAME: usize = 1;
-const ROOT_OPTION_EDITOR: usize = 2;
-const ROOT_OPTION_SETTINGS: usize = 3;
-const ROOT_OPTION_RELOAD_RESOURCES: usize = 4;
+const ROOT_OPTION_TRAINING: usize = 1;
+const ROOT_OPTION_NETWORK_GAME: usize = 2;
+const ROOT_OPTION_EDITOR: usize = 3;
+const ROOT_OPTION_SETTINGS: usize = 4;
+const ROOT_OPTION_CREDITS: usize = 5;
+const ROOT_OPTION_RELOAD_RESOURCES: usize = 6;
+const ROOT_OPTION_MODS: usize = 7;
 
 const LOCAL_GAME_OPTION_SUBMIT: usize = 0;
 
@@ -56,34 +81,48 @@ fn build_main_menu() -> Menu {
         &[
             MenuEntry {
                 index: ROOT_OPTION_LOCAL_GAME,
-                title: "Local Game".to_string(),
+                title: tr("main_menu.local_game"),
+                ..Default::default()
+            },
+            MenuEntry {
+                index: ROOT_OPTION_TRAINING,
+                title: tr("main_menu.training"),
                 ..Default::default()
             },
             MenuEntry {
                 index: ROOT_OPTION_NETWORK_GAME,
-                title: "Network Game".to_string(),
+                title: tr("main_menu.network_game"),
                 is_disabled: true,
                 ..Default::default()
             },
             MenuEntry {
                 index: ROOT_OPTION_EDITOR,
-                title: "Editor".to_string(),
+                title: tr("main_menu.editor"),
                 ..Default::default()
             },
             MenuEntry {
                 index: ROOT_OPTION_SETTINGS,
-                title: "Settings".to_string(),
-                is_disabled: true,
+                title: tr("main_menu.settings"),
+                ..Default::default()
+            },
+            MenuEntry {
+                index: ROOT_OPTION_MODS,
+                title: tr("main_menu.mods"),
+                ..Default::default()
+            },
+            MenuEntry {
+                index: ROOT_OPTION_CREDITS,
+                title: tr("main_menu.credits"),
                 ..Default::default()
             },
             MenuEntry {
                 index: ROOT_OPTION_RELOAD_RESOURCES,
-                title: "Reload Resources".to_string(),
+                title: tr("main_menu.reload_resources"),
                 ..Default::default()
             },
         ],
     )
-    .with_cancel_button(Some("Quit"))
+    .with_cancel_button(Some(&tr("main_menu.quit")))
 }
 
 fn build_editor_menu() -> Menu {
@@ -93,17 +132,89 @@ fn build_editor_menu() -> Menu {
         &[
             MenuEntry {
                 index: EDITOR_OPTION_CREATE,
-                title: "Create Map".to_string(),
+                title: tr("main_menu.create_map"),
                 ..Default::default()
             },
             MenuEntry {
                 index: EDITOR_OPTION_LOAD,
-                title: "Load Map".to_string(),
+                title: tr("main_menu.load_map"),
                 ..Default::default()
             },
         ],
     )
-    .with_cancel_button(Some("Cancel"))
+    .with_cancel_button(Some(&tr("menu.cancel")))
+}
+
+/// Returns the status of the mod at `index` in the mod list of resources, as shown in the mod
+/// manager. Mods that have been enabled, or disabled, since resources were loaded, are shown as
+/// such, even though they will not be loaded, or unloaded, until resources are reloaded.
+fn get_mod_status_label(index: usize) -> String {
+    let resources = storage::get::<Resources>();
+    let info = &resources.mods[index];
+
+    let is_enabled = storage::get::<Config>()
+        .enabled_mods
+        .contains(&info.manifest.id);
+
+    if !is_enabled {
+        return tr("mods.disabled");
+    }
+
+    match &info.status {
+        ModStatus::MissingDependency(id) => tr_args("mods.missing_dependency", &[id]),
+        ModStatus::DependencyCycle => tr("mods.dependency_cycle"),
+        ModStatus::Failed => tr("mods.failed"),
+        ModStatus::Disabled | ModStatus::Loaded(_) => tr("mods.enabled"),
+    }
+}
+
+fn build_mods_menu() -> Menu {
+    let entries = {
+        let resources = storage::get::<Resources>();
+
+        resources
+            .mods
+            .iter()
+            .enumerate()
+            .map(|(i, info)| MenuEntry {
+                index: i,
+                title: format!(
+                    "{} {}: {}",
+                    &info.manifest.name,
+                    &info.manifest.version,
+                    get_mod_status_label(i)
+                ),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let menu = Menu::new(hash!("main_menu", "mods"), MODS_MENU_WIDTH, &entries)
+        .with_cancel_button(Some(&tr("menu.cancel")));
+
+    if entries.is_empty() {
+        menu.with_header(&tr("mods.none_installed"))
+    } else {
+        menu
+    }
+}
+
+/// This enables the mod at `index` in the mod list of resources, if it is disabled, or disables
+/// it, if it is enabled, and saves the config
+fn toggle_mod(index: usize) {
+    let id = storage::get::<Resources>().mods[index].manifest.id.clone();
+
+    let mut config = storage::get_mut::<Config>();
+
+    if let Some(i) = config.enabled_mods.iter().position(|other| other == &id) {
+        config.enabled_mods.remove(i);
+    } else {
+        config.enabled_mods.push(id);
+    }
+
+    if let Err(err) = config.save(crate::get_config_path()) {
+        println!("WARNING: Failed to save config: {}", err);
+    }
 }
 
 pub async fn show_main_menu() -> MainMenuResult {
@@ -111,9 +222,30 @@ pub async fn show_main_menu() -> MainMenuResult {
 
     let mut player_input = Vec::new();
 
+    // The time that the root menu has been left without input, to start the attract mode
+    let mut idle_timer = 0.0;
+    let mut last_mouse_position = mouse_position();
+
     loop {
         update_gamepad_context(None).unwrap();
 
+        {
+            let is_mouse_moved = mouse_position() != last_mouse_position;
+            last_mouse_position = mouse_position();
+
+            let is_root = matches!(menu_state, MainMenuState::Root(_));
+
+            if !is_root || is_mouse_moved || is_any_input_pressed() {
+                idle_timer = 0.0;
+            } else {
+                idle_timer += get_frame_time();
+
+                if idle_timer >= ATTRACT_MODE_DELAY {
+                    return MainMenuResult::AttractMode;
+                }
+            }
+        }
+
         draw_main_menu_background(true);
 
         {
@@ -133,6 +265,18 @@ pub async fn show_main_menu() -> MainMenuResult {
                 .ui(&mut *root_ui());
         }
 
+        match &menu_state {
+            MainMenuState::Root(_) | MainMenuState::Editor(_) | MainMenuState::Mods { .. } => {
+                draw_button_prompts(&[
+                    PromptAction::Navigate,
+                    PromptAction::Confirm,
+                    PromptAction::Cancel,
+                ])
+            }
+            MainMenuState::Settings(_) => draw_button_prompts(SettingsMenu::PROMPTS),
+            _ => {}
+        }
+
         match menu_state.borrow_mut() {
             MainMenuState::Root(menu_instance) => {
                 if let Some(res) = menu_instance.ui(&mut *root_ui()) {
@@ -140,12 +284,28 @@ pub async fn show_main_menu() -> MainMenuResult {
                         ROOT_OPTION_LOCAL_GAME => {
                             menu_state = MainMenuState::LocalGame;
                         }
+                        ROOT_OPTION_TRAINING => {
+                            menu_state = MainMenuState::Training;
+                        }
                         ROOT_OPTION_EDITOR => {
                             menu_state = MainMenuState::Editor(build_editor_menu());
                         }
+                        ROOT_OPTION_SETTINGS => {
+                            menu_state = MainMenuState::Settings(SettingsMenu::new());
+                        }
+                        ROOT_OPTION_CREDITS => {
+                            show_credits().await;
+                            menu_state = MainMenuState::Root(build_main_menu());
+                        }
                         ROOT_OPTION_RELOAD_RESOURCES => {
                             return MainMenuResult::ReloadResources;
                         }
+                        ROOT_OPTION_MODS => {
+                            menu_state = MainMenuState::Mods {
+                                menu: build_mods_menu(),
+                                is_changed: false,
+                            };
+                        }
                         Menu::CANCEL_INDEX => {
                             return MainMenuResult::Quit;
                         }
@@ -154,12 +314,27 @@ pub async fn show_main_menu() -> MainMenuResult {
                 }
             }
             MainMenuState::LocalGame => {
-                if let Some(res) = local_game_ui(&mut *root_ui(), &mut player_input) {
+                if let Some(res) = local_game_ui(&mut *root_ui(), &mut player_input, 2) {
                     match res.into_usize() {
                         LOCAL_GAME_OPTION_SUBMIT => {
                             return MainMenuResult::LocalGame(player_input.clone());
                         }
                         Menu::CANCEL_INDEX => {
+                            player_input.clear();
+                            menu_state = MainMenuState::Root(build_main_menu());
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            MainMenuState::Training => {
+                if let Some(res) = local_game_ui(&mut *root_ui(), &mut player_input, 1) {
+                    match res.into_usize() {
+                        LOCAL_GAME_OPTION_SUBMIT => {
+                            return MainMenuResult::Training(player_input[0]);
+                        }
+                        Menu::CANCEL_INDEX => {
+                            player_input.clear();
                             menu_state = MainMenuState::Root(build_main_menu());
                         }
                         _ => {}
@@ -191,17 +366,50 @@ pub async fn show_main_menu() -> MainMenuResult {
                     }
                 }
             }
-            MainMenuState::Settings => {
-                unreachable!("Settings is not implemented yet");
+            MainMenuState::Settings(settings_menu) => {
+                if let Some(res) = settings_menu.ui(&mut *root_ui()) {
+                    if res.is_cancel() {
+                        menu_state = MainMenuState::Root(build_main_menu());
+                    }
+                }
+            }
+            MainMenuState::Mods { menu, is_changed } => {
+                if let Some(res) = menu.ui(&mut *root_ui()) {
+                    if res.is_cancel() {
+                        if *is_changed {
+                            return MainMenuResult::ReloadResources;
+                        }
+
+                        menu_state = MainMenuState::Root(build_main_menu());
+                    } else {
+                        toggle_mod(res.into_usize());
+
+                        menu_state = MainMenuState::Mods {
+                            menu: build_mods_menu(),
+                            is_changed: true,
+                        };
+                    }
+                }
             }
         }
 
+        draw_toasts();
+
+        update_music();
+
         next_frame().await;
     }
 }
 
-fn local_game_ui(ui: &mut ui::Ui, player_input: &mut Vec<GameInputScheme>) -> Option<MenuResult> {
-    if player_input.len() == 2 {
+/// This lets `player_cnt` players join, each on their own device slot, and submits when all of
+/// them have joined. A keyboard slot is taken by pressing the jump binding of that side of the
+/// keyboard, or enter for the first free keyboard slot, and a gamepad is taken by pressing start.
+fn local_game_ui(
+    ui: &mut ui::Ui,
+    player_input: &mut Vec<GameInputScheme>,
+    player_cnt: usize,
+) -> Option<MenuResult> {
+    if player_input.len() == player_cnt {
         return Some(LOCAL_GAME_OPTION_SUBMIT.into());
     } else {
         let gamepad_context = storage::get::<GamepadContext>();
@@ -212,12 +420,20 @@ fn local_game_ui(ui: &mut ui::Ui, player_input: &mut Vec<GameInputScheme>) -> Op
         }
     }
 
-    if player_input.len() < 2 {
-        if is_key_pressed(KeyCode::Enter) {
-            if !player_input.contains(&GameInputScheme::KeyboardLeft) {
-                player_input.push(GameInputScheme::KeyboardLeft);
-            } else {
-                player_input.push(GameInputScheme::KeyboardRight);
+    if player_input.len() < player_cnt {
+        let is_enter_pressed = is_key_pressed(KeyCode::Enter);
+        let mut is_enter_used = false;
+
+        for slot in GameInputScheme::KEYBOARD_SLOTS {
+            if player_input.contains(slot) || player_input.len() >= player_cnt {
+                continue;
+            }
+
+            let is_first_free = is_enter_pressed && !is_enter_used;
+
+            if is_first_free || is_action_pressed(*slot, InputAction::Jump) {
+                is_enter_used = is_enter_used || is_first_free;
+                player_input.push(*slot);
             }
         }
 
@@ -225,6 +441,7 @@ fn local_game_ui(ui: &mut ui::Ui, player_input: &mut Vec<GameInputScheme>) -> Op
         for (ix, gamepad) in gamepad_context.gamepads() {
             if gamepad.digital_inputs.activated(fishsticks::Button::Start)
                 && !player_input.contains(&GameInputScheme::Gamepad(ix))
+                && player_input.len() < player_cnt
             {
                 player_input.push(GameInputScheme::Gamepad(ix));
             }
@@ -240,30 +457,30 @@ fn local_game_ui(ui: &mut ui::Ui, player_input: &mut Vec<GameInputScheme>) -> Op
             ui.push_skin(&gui_resources.skins.menu);
         }
 
-        {
-            let position = vec2(12.0, 12.0);
-
-            if !player_input.is_empty() {
-                ui.label(position, "Player 1: READY");
-            } else {
-                ui.label(position, "Player 1: press START or ENTER");
-            }
-        }
-
-        {
-            let position = vec2(12.0, 44.0);
-
-            if player_input.len() > 1 {
-                ui.label(position, "Player 2: READY");
+        for i in 0..player_cnt {
+            let position = vec2(12.0, 12.0 + i as f32 * 32.0);
+            let player_number = (i + 1).to_string();
+
+            if let Some(input_scheme) = player_input.get(i) {
+                ui.label(
+                    position,
+                    &tr_args(
+                        "local_game.player_ready",
+                        &[&player_number, &input_scheme.get_name()],
+                    ),
+                );
             } else {
-                ui.label(position, "Player 2: press START or ENTER");
+                ui.label(
+                    position,
+                    &tr_args("local_game.player_join", &[&player_number]),
+                );
             }
         }
 
         {
             let position = vec2(12.0, 108.0);
 
-            ui.label(position, "Press B or ESC to cancel");
+            ui.label(position, &tr("local_game.cancel"));
         }
 
         ui.pop_skin();
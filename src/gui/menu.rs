@@ -4,13 +4,12 @@ use macroquad::{
     ui::{widgets, Id, Ui},
 };
 
-use fishsticks::{Axis, Button};
-
 use super::{
-    GuiResources, Panel, BUTTON_FONT_SIZE, BUTTON_MARGIN_V, WINDOW_MARGIN_H, WINDOW_MARGIN_V,
+    FocusNavigator, GuiResources, Panel, BUTTON_FONT_SIZE, BUTTON_MARGIN_V, WINDOW_MARGIN_H,
+    WINDOW_MARGIN_V,
 };
 
-use crate::{is_gamepad_btn_pressed, GamepadContext};
+use crate::localization::tr;
 
 #[derive(Debug, Copy, Clone)]
 pub enum MenuPosition {
@@ -94,10 +93,10 @@ pub struct Menu {
     entries: Vec<MenuEntry>,
     has_cancel_button: bool,
     cancel_entry_title_override: Option<String>,
-    current_selection: Option<usize>,
+    /// The focused entry, in navigation order, which is the entries that are not pulled down,
+    /// followed by those that are
+    navigator: FocusNavigator,
     last_mouse_position: Vec2,
-    up_grace_timer: f32,
-    down_grace_timer: f32,
     is_first_draw: bool,
 }
 
@@ -105,13 +104,12 @@ impl Menu {
     pub const CANCEL_INDEX: usize = 99999;
 
     const HEADER_MARGIN: f32 = 16.0;
-    const CANCEL_TITLE: &'static str = "Cancel";
+    /// The string key of the title of the cancel entry
+    const CANCEL_TITLE: &'static str = "menu.cancel";
 
     pub const ENTRY_HEIGHT: f32 = (BUTTON_MARGIN_V * 2.0) + BUTTON_FONT_SIZE;
     pub const ENTRY_MARGIN: f32 = 4.0;
 
-    const NAVIGATION_GRACE_TIME: f32 = 0.25;
-
     pub fn new(id: Id, width: f32, entries: &[MenuEntry]) -> Self {
         Menu {
             id,
@@ -122,10 +120,8 @@ impl Menu {
             entries: entries.to_vec(),
             has_cancel_button: false,
             cancel_entry_title_override: None,
-            current_selection: None,
+            navigator: FocusNavigator::default(),
             last_mouse_position: Vec2::ZERO,
-            up_grace_timer: Self::NAVIGATION_GRACE_TIME,
-            down_grace_timer: Self::NAVIGATION_GRACE_TIME,
             is_first_draw: true,
         }
     }
@@ -153,8 +149,8 @@ impl Menu {
         }
     }
 
-    /// This adds a cancel entry to the menu. The title of this entry will be `Self::CANCEL_TITLE`
-    /// if no override is specified
+    /// This adds a cancel entry to the menu. The title of this entry will be the translation of
+    /// `Self::CANCEL_TITLE` if no override is specified
     pub fn with_cancel_button(self, title_override: Option<&str>) -> Self {
         for entry in &self.entries {
             assert_ne!(
@@ -177,12 +173,6 @@ impl Menu {
     pub fn ui(&mut self, ui: &mut Ui) -> Option<MenuResult> {
         let mut res = None;
 
-        {
-            let dt = get_frame_time();
-            self.up_grace_timer += dt;
-            self.down_grace_timer += dt;
-        }
-
         {
             let gui_resources = storage::get::<GuiResources>();
             ui.push_skin(&gui_resources.skins.menu);
@@ -194,7 +184,7 @@ impl Menu {
         };
 
         if mouse_position != self.last_mouse_position {
-            self.current_selection = None;
+            self.navigator.set_focus(None);
         }
 
         self.last_mouse_position = mouse_position;
@@ -205,7 +195,7 @@ impl Menu {
             let title = self
                 .cancel_entry_title_override
                 .clone()
-                .unwrap_or_else(|| Self::CANCEL_TITLE.to_string());
+                .unwrap_or_else(|| tr(Self::CANCEL_TITLE));
 
             entries.push(MenuEntry {
                 index: Self::CANCEL_INDEX,
@@ -215,7 +205,16 @@ impl Menu {
             })
         }
 
-        let (should_confirm, should_cancel) = self.update_input();
+        let focusable = entries
+            .iter()
+            .filter(|entry| !entry.is_pulled_down)
+            .chain(entries.iter().filter(|entry| entry.is_pulled_down))
+            .map(|entry| !entry.is_disabled)
+            .collect::<Vec<_>>();
+
+        let (should_confirm, should_cancel) = self.update_input(&focusable);
+
+        let current_selection = self.navigator.get_focus();
 
         let header_height = if let Some(header) = &self.header {
             let gui_resources = storage::get::<GuiResources>();
@@ -281,12 +280,11 @@ impl Menu {
                 }
             }
 
-            if should_confirm && self.current_selection.is_none() {
-                let mut entry = top_entries.first();
-
-                if entry.is_none() {
-                    entry = bottom_entries.first();
-                }
+            if should_confirm && current_selection.is_none() {
+                let entry = top_entries
+                    .iter()
+                    .chain(bottom_entries.iter())
+                    .find(|entry| !entry.is_disabled);
 
                 if let Some(entry) = entry {
                     res = Some(entry.index.into());
@@ -301,11 +299,7 @@ impl Menu {
                         vec2(0.0, 0.0)
                     };
 
-                let is_selected = if let Some(current_selection) = self.current_selection {
-                    current_selection == i
-                } else {
-                    false
-                };
+                let is_selected = current_selection == Some(i);
 
                 {
                     let gui_resources = storage::get::<GuiResources>();
@@ -358,7 +352,7 @@ impl Menu {
                 );
 
                 let mut is_selected = false;
-                if let Some(current_selection) = self.current_selection {
+                if let Some(current_selection) = current_selection {
                     if current_selection >= top_entries.len() {
                         is_selected = current_selection - top_entries.len() == i
                     }
@@ -398,74 +392,28 @@ impl Menu {
         res
     }
 
-    fn update_input(&mut self) -> (bool, bool) {
-        if self.is_first_draw {
-            (false, false)
-        } else {
-            let gamepad_context = storage::get::<GamepadContext>();
-
-            let mut selection = self.current_selection.map(|s| s as i32);
-
-            let mut gamepad_up = false;
-            let mut gamepad_down = false;
+    /// This moves the focus with the navigation input of this frame, skipping entries that are
+    /// not `focusable`, and returns whether the focused entry should be confirmed and whether the
+    /// menu should be cancelled
+    fn update_input(&mut self, focusable: &[bool]) -> (bool, bool) {
+        let input = self.navigator.update_input();
 
-            for (_, gamepad) in gamepad_context.gamepads() {
-                gamepad_up = gamepad_up
-                    || gamepad.digital_inputs.activated(Button::DPadUp)
-                    || gamepad.analog_inputs.digital_value(Axis::LeftY) < 0.0;
-                gamepad_down = gamepad_down
-                    || gamepad.digital_inputs.activated(Button::DPadDown)
-                    || gamepad.analog_inputs.digital_value(Axis::LeftY) > 0.0;
-            }
-
-            if self.up_grace_timer >= Self::NAVIGATION_GRACE_TIME
-                && (gamepad_up || is_key_down(KeyCode::Up) || is_key_down(KeyCode::W))
-            {
-                self.up_grace_timer = 0.0;
-
-                selection = if let Some(selection) = selection {
-                    Some(selection - 1)
-                } else {
-                    Some(0)
-                };
-            } else if self.down_grace_timer >= Self::NAVIGATION_GRACE_TIME
-                && (gamepad_down || is_key_down(KeyCode::Down) || is_key_down(KeyCode::S))
-            {
-                self.down_grace_timer = 0.0;
-
-                selection = if let Some(selection) = selection {
-                    Some(selection + 1)
-                } else {
-                    Some(0)
-                };
-            }
-
-            if let Some(selection) = selection {
-                let mut entries_cnt = self.entries.len();
-                if self.has_cancel_button {
-                    entries_cnt += 1;
-                }
-
-                let selection = if selection < 0 {
-                    if entries_cnt > 0 {
-                        entries_cnt - 1
-                    } else {
-                        0
-                    }
-                } else {
-                    selection as usize % entries_cnt
-                };
-
-                self.current_selection = Some(selection);
-            }
-
-            let should_confirm = is_gamepad_btn_pressed(Some(&gamepad_context), Button::A)
-                || is_key_pressed(KeyCode::Enter);
+        if self.is_first_draw {
+            // Focus the first entry, so that the menu can be used with a gamepad right away
+            self.navigator
+                .move_focus(0, focusable.len(), |i| focusable[i]);
 
-            let should_cancel = is_gamepad_btn_pressed(Some(&gamepad_context), Button::B)
-                || is_key_pressed(KeyCode::Escape);
+            return (false, false);
+        }
 
-            (should_confirm, should_cancel)
+        if input.up {
+            self.navigator
+                .move_focus(-1, focusable.len(), |i| focusable[i]);
+        } else if input.down {
+            self.navigator
+                .move_focus(1, focusable.len(), |i| focusable[i]);
         }
+
+        (input.confirm, input.cancel)
     }
 }
@@ -1,13 +1,18 @@
 mod background;
 mod checkbox;
 mod create_map;
+mod credits;
 mod game_menu;
+mod join_game;
 mod main_menu;
 mod menu;
+mod navigation;
 mod panel;
 mod select_character;
 mod select_map;
+mod settings;
 mod style;
+mod toasts;
 
 use macroquad::prelude::*;
 
@@ -19,15 +24,20 @@ pub use style::{
 pub use background::{draw_main_menu_background, Background};
 pub use checkbox::Checkbox;
 pub use create_map::show_create_map_menu;
+pub use credits::{show_credits, Credits};
 pub use game_menu::{
-    close_game_menu, draw_game_menu, is_game_menu_open, open_game_menu, toggle_game_menu,
-    GAME_MENU_RESULT_MAIN_MENU, GAME_MENU_RESULT_QUIT,
+    close_game_menu, draw_game_menu, is_game_menu_open, open_game_menu, open_game_menu_ex,
+    toggle_game_menu, GAME_MENU_RESULT_MAIN_MENU, GAME_MENU_RESULT_QUIT, GAME_MENU_RESULT_RESTART,
 };
+pub use join_game::draw_join_game_panel;
 pub use main_menu::{show_main_menu, MainMenuResult};
 pub use menu::{Menu, MenuEntry, MenuResult};
+pub use navigation::{draw_button_prompts, is_any_input_pressed, FocusNavigator, PromptAction};
 pub use panel::Panel;
 pub use select_character::show_select_characters_menu;
 pub use select_map::show_select_map_menu;
+pub use settings::SettingsMenu;
+pub use toasts::{draw_toasts, show_toast, ToastOverlay, ToastPriority};
 
 pub const NO_COLOR: Color = Color::new(0.0, 0.0, 0.0, 0.0);
 
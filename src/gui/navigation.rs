@@ -0,0 +1,345 @@
+//! Focus based navigation for menus, so that every menu can be used with a gamepad, or a keyboard,
+//! as well as with a mouse. Menus keep a `FocusNavigator`, which moves the focus between their
+//! entries with the d-pad, the left stick or the arrow keys, wrapping around at the ends, and
+//! repeats movement while a direction is held.
+//!
+//! Where several players navigate at the same time, like when joining a match in progress, each
+//! player has their own `FocusNavigator`, which collects the input of their device slot only.
+//!
+//! The device that was used last is tracked, so that button prompts can be drawn with the
+//! bindings of the device that the player is actually using.
+
+use macroquad::{color, experimental::collections::storage, prelude::*};
+
+use fishsticks::{Axis, Button};
+
+use crate::{
+    game::{collect_input, is_action_pressed, GameInputScheme, InputAction},
+    localization::tr,
+    text::{draw_aligned_text, HorizontalAlignment, VerticalAlignment},
+    GamepadContext,
+};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum InputDevice {
+    Keyboard,
+    Gamepad,
+}
+
+static mut ACTIVE_INPUT_DEVICE: InputDevice = InputDevice::Keyboard;
+
+/// Returns the device that was used last, for navigation or otherwise
+pub fn get_active_input_device() -> InputDevice {
+    unsafe { ACTIVE_INPUT_DEVICE }
+}
+
+/// The buttons that will make a gamepad the active input device, when pressed
+const GAMEPAD_BUTTONS: &[Button] = &[
+    Button::A,
+    Button::B,
+    Button::X,
+    Button::Y,
+    Button::DPadUp,
+    Button::DPadDown,
+    Button::DPadLeft,
+    Button::DPadRight,
+    Button::Start,
+];
+
+/// Returns the device that a key or button was pressed on this frame, if any, where mouse buttons
+/// count as the keyboard and moving a stick counts as the gamepad
+fn get_pressed_input_device(gamepad_context: &GamepadContext) -> Option<InputDevice> {
+    let mut device = None;
+
+    if get_last_key_pressed().is_some()
+        || is_mouse_button_pressed(MouseButton::Left)
+        || is_mouse_button_pressed(MouseButton::Right)
+    {
+        device = Some(InputDevice::Keyboard);
+    }
+
+    for (_, gamepad) in gamepad_context.gamepads() {
+        let is_button_pressed = GAMEPAD_BUTTONS
+            .iter()
+            .any(|btn| gamepad.digital_inputs.just_activated(*btn));
+
+        let is_stick_moved = gamepad
+            .analog_inputs
+            .just_activated_digital(Axis::LeftX)
+            .is_some()
+            || gamepad
+                .analog_inputs
+                .just_activated_digital(Axis::LeftY)
+                .is_some();
+
+        if is_button_pressed || is_stick_moved {
+            device = Some(InputDevice::Gamepad);
+        }
+    }
+
+    device
+}
+
+/// Returns `true` if a key, mouse button or gamepad button was pressed this frame, or a stick was
+/// moved
+pub fn is_any_input_pressed() -> bool {
+    let gamepad_context = storage::get::<GamepadContext>();
+    get_pressed_input_device(&gamepad_context).is_some()
+}
+
+fn update_active_input_device(gamepad_context: &GamepadContext) {
+    if let Some(device) = get_pressed_input_device(gamepad_context) {
+        unsafe { ACTIVE_INPUT_DEVICE = device };
+    }
+}
+
+/// The navigation input of a single frame, from the keyboard and all gamepads. Directions are
+/// `true` on the frame they are pressed, and repeatedly while they are held, if the input was
+/// collected through a `FocusNavigator`.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct NavigationInput {
+    pub up: bool,
+    pub down: bool,
+    pub left: bool,
+    pub right: bool,
+    pub confirm: bool,
+    pub cancel: bool,
+}
+
+impl NavigationInput {
+    /// Returns the directions that are currently held, in the order up, down, left, right, and
+    /// whether confirm and cancel were pressed this frame
+    fn collect_held(gamepad_context: &GamepadContext) -> ([bool; 4], bool, bool) {
+        let mut held = [
+            is_key_down(KeyCode::Up) || is_key_down(KeyCode::W),
+            is_key_down(KeyCode::Down) || is_key_down(KeyCode::S),
+            is_key_down(KeyCode::Left) || is_key_down(KeyCode::A),
+            is_key_down(KeyCode::Right) || is_key_down(KeyCode::D),
+        ];
+
+        let mut confirm = is_key_pressed(KeyCode::Enter);
+        let mut cancel = is_key_pressed(KeyCode::Escape);
+
+        for (_, gamepad) in gamepad_context.gamepads() {
+            let x = gamepad.analog_inputs.digital_value(Axis::LeftX);
+            let y = gamepad.analog_inputs.digital_value(Axis::LeftY);
+
+            held[0] |= gamepad.digital_inputs.activated(Button::DPadUp) || y < 0.0;
+            held[1] |= gamepad.digital_inputs.activated(Button::DPadDown) || y > 0.0;
+            held[2] |= gamepad.digital_inputs.activated(Button::DPadLeft) || x < 0.0;
+            held[3] |= gamepad.digital_inputs.activated(Button::DPadRight) || x > 0.0;
+
+            confirm |= gamepad.digital_inputs.just_activated(Button::A);
+            cancel |= gamepad.digital_inputs.just_activated(Button::B);
+        }
+
+        (held, confirm, cancel)
+    }
+
+    /// Returns the directions that are currently held, and whether confirm and cancel were
+    /// pressed this frame, on the device slot of `scheme`. This uses the input bindings of the
+    /// slot, so that players navigate with the keys or buttons they play with, where jump
+    /// confirms and fire cancels. Up is never held, as it is bound to jump.
+    fn collect_held_for_scheme(scheme: GameInputScheme) -> ([bool; 4], bool, bool) {
+        let input = collect_input(scheme);
+
+        let held = [false, input.down, input.left, input.right];
+
+        let confirm = is_action_pressed(scheme, InputAction::Jump);
+        let cancel = is_action_pressed(scheme, InputAction::Fire);
+
+        (held, confirm, cancel)
+    }
+}
+
+/// This keeps track of the focused entry of a menu, and of held directions, for repeated
+/// movement. Entries are identified by their index, in navigation order.
+#[derive(Debug, Default, Clone)]
+pub struct FocusNavigator {
+    focus: Option<usize>,
+    /// The time until movement is repeated, for each direction, in the order up, down, left,
+    /// right, or `None` if the direction is not held
+    repeat_timers: [Option<f32>; 4],
+}
+
+impl FocusNavigator {
+    /// The time a direction must be held, before movement is repeated
+    const REPEAT_DELAY: f32 = 0.4;
+    /// The time between repeated movement, while a direction is held
+    const REPEAT_INTERVAL: f32 = 0.1;
+
+    pub fn new(focus: Option<usize>) -> Self {
+        FocusNavigator {
+            focus,
+            repeat_timers: [None; 4],
+        }
+    }
+
+    pub fn get_focus(&self) -> Option<usize> {
+        self.focus
+    }
+
+    pub fn set_focus(&mut self, focus: Option<usize>) {
+        self.focus = focus;
+    }
+
+    /// This collects the navigation input of this frame, with repeated directions applied, and
+    /// updates the active input device. This should be called once per frame.
+    pub fn update_input(&mut self) -> NavigationInput {
+        let gamepad_context = storage::get::<GamepadContext>();
+
+        update_active_input_device(&gamepad_context);
+
+        let (held, confirm, cancel) = NavigationInput::collect_held(&gamepad_context);
+
+        self.apply_repeat(held, confirm, cancel)
+    }
+
+    /// This collects the navigation input of this frame from the device slot of `scheme` only,
+    /// with repeated directions applied. This should be called once per frame.
+    pub fn update_scheme_input(&mut self, scheme: GameInputScheme) -> NavigationInput {
+        let (held, confirm, cancel) = NavigationInput::collect_held_for_scheme(scheme);
+
+        self.apply_repeat(held, confirm, cancel)
+    }
+
+    fn apply_repeat(&mut self, held: [bool; 4], confirm: bool, cancel: bool) -> NavigationInput {
+        let dt = get_frame_time();
+        let mut pressed = [false; 4];
+
+        for (i, is_held) in held.iter().enumerate() {
+            let timer = &mut self.repeat_timers[i];
+
+            if !*is_held {
+                *timer = None;
+                continue;
+            }
+
+            match timer {
+                None => {
+                    pressed[i] = true;
+                    *timer = Some(Self::REPEAT_DELAY);
+                }
+                Some(time) => {
+                    *time -= dt;
+
+                    if *time <= 0.0 {
+                        pressed[i] = true;
+                        *time += Self::REPEAT_INTERVAL;
+                    }
+                }
+            }
+        }
+
+        NavigationInput {
+            up: pressed[0],
+            down: pressed[1],
+            left: pressed[2],
+            right: pressed[3],
+            confirm,
+            cancel,
+        }
+    }
+
+    /// This moves the focus by `step` entries, wrapping around at the ends and skipping entries
+    /// for which `is_focusable` returns `false`. If nothing is focused, the first focusable entry
+    /// will be focused, regardless of direction.
+    pub fn move_focus<F: Fn(usize) -> bool>(&mut self, step: i32, cnt: usize, is_focusable: F) {
+        if cnt == 0 {
+            self.focus = None;
+            return;
+        }
+
+        let mut i = match self.focus {
+            Some(focus) => focus.min(cnt - 1) as i32,
+            None => {
+                self.focus = (0..cnt).find(|i| is_focusable(*i));
+                return;
+            }
+        };
+
+        for _ in 0..cnt {
+            i = (i + step).rem_euclid(cnt as i32);
+
+            if is_focusable(i as usize) {
+                self.focus = Some(i as usize);
+                return;
+            }
+        }
+    }
+}
+
+/// The actions that button prompts can be drawn for
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PromptAction {
+    Navigate,
+    Change,
+    Confirm,
+    Cancel,
+}
+
+impl PromptAction {
+    /// Returns the binding of the action, for `device`
+    pub fn get_binding(&self, device: InputDevice) -> &'static str {
+        match device {
+            InputDevice::Keyboard => match self {
+                PromptAction::Navigate => "Up/Down",
+                PromptAction::Change => "Left/Right",
+                PromptAction::Confirm => "Enter",
+                PromptAction::Cancel => "Esc",
+            },
+            InputDevice::Gamepad => match self {
+                PromptAction::Navigate => "D-Pad",
+                PromptAction::Change => "D-Pad",
+                PromptAction::Confirm => "A",
+                PromptAction::Cancel => "B",
+            },
+        }
+    }
+
+    fn get_title(&self) -> String {
+        let key = match self {
+            PromptAction::Navigate => "prompt.navigate",
+            PromptAction::Change => "prompt.change",
+            PromptAction::Confirm => "prompt.confirm",
+            PromptAction::Cancel => "prompt.cancel",
+        };
+
+        tr(key)
+    }
+}
+
+const PROMPT_FONT_SIZE: u16 = 16;
+const PROMPT_MARGIN: f32 = 16.0;
+/// The separator between prompts
+const PROMPT_SEPARATOR: &str = "    ";
+
+/// This draws a line of button prompts, like "[A] Confirm", at the bottom center of the screen,
+/// with the bindings of the active input device
+pub fn draw_button_prompts(actions: &[PromptAction]) {
+    let device = get_active_input_device();
+
+    let prompts = actions
+        .iter()
+        .map(|action| format!("[{}] {}", action.get_binding(device), action.get_title()))
+        .collect::<Vec<_>>();
+
+    let text = prompts.join(PROMPT_SEPARATOR);
+
+    push_camera_state();
+    set_default_camera();
+
+    draw_aligned_text(
+        &text,
+        vec2(screen_width() / 2.0, screen_height() - PROMPT_MARGIN),
+        HorizontalAlignment::Center,
+        VerticalAlignment::Bottom,
+        TextParams {
+            font_size: PROMPT_FONT_SIZE,
+            color: color::WHITE,
+            ..Default::default()
+        },
+    );
+
+    pop_camera_state();
+}
@@ -4,14 +4,20 @@ use macroquad::ui::{hash, root_ui, widgets};
 
 use fishsticks::{Axis, Button, GamepadContext};
 
+use crate::audio::update_music;
 use crate::components::AnimationPlayer;
 use crate::gui::{
-    draw_main_menu_background, GuiResources, Panel, BUTTON_FONT_SIZE, BUTTON_MARGIN_H,
+    draw_main_menu_background, draw_toasts, GuiResources, Panel, BUTTON_FONT_SIZE, BUTTON_MARGIN_H,
     WINDOW_BG_COLOR,
 };
 use crate::input::update_gamepad_context;
+use crate::localization::tr_args;
 use crate::player::PlayerCharacterParams;
-use crate::{GameInputScheme, Resources};
+use crate::{
+    collect_input,
+    game::{is_action_pressed, InputAction},
+    GameInputScheme, Resources,
+};
 
 const SECTION_WIDTH: f32 = 300.0;
 const SECTION_HEIGHT: f32 = 400.0;
@@ -92,17 +98,14 @@ pub async fn show_select_characters_menu(
                 let can_navigate = navigation_grace_timers[i] >= NAVIGATION_GRACE_TIME;
 
                 match *input_scheme {
-                    GameInputScheme::KeyboardRight => {
-                        should_navigate_left = can_navigate && is_key_down(KeyCode::Left);
-                        should_navigate_right = can_navigate && is_key_down(KeyCode::Right);
-                        should_confirm =
-                            is_key_pressed(KeyCode::L) || is_key_pressed(KeyCode::Enter);
-                    }
-                    GameInputScheme::KeyboardLeft => {
-                        should_navigate_left = can_navigate && is_key_down(KeyCode::A);
-                        should_navigate_right = can_navigate && is_key_down(KeyCode::D);
-                        should_confirm =
-                            is_key_pressed(KeyCode::V) || is_key_pressed(KeyCode::LeftControl);
+                    GameInputScheme::KeyboardLeft | GameInputScheme::KeyboardRight => {
+                        let input = collect_input(*input_scheme);
+
+                        should_navigate_left = can_navigate && input.left;
+                        should_navigate_right = can_navigate && input.right;
+                        should_confirm = is_action_pressed(*input_scheme, InputAction::Fire)
+                            || (*input_scheme == GameInputScheme::KeyboardRight
+                                && is_key_pressed(KeyCode::Enter));
                     }
                     GameInputScheme::Gamepad(gamepad_id) => {
                         let gamepad_context = storage::get::<GamepadContext>();
@@ -123,12 +126,12 @@ pub async fn show_select_characters_menu(
                 }
 
                 Panel::new(hash!("section", i), section_size, section_position)
-                    .with_title(&format!("Player {}", i + 1), true)
+                    .with_title(&tr_args("player.name", &[&(i + 1).to_string()]), true)
                     .with_background_color(WINDOW_BG_COLOR)
                     .ui(&mut *root_ui(), |ui, inner_size| {
                         let animation_player = &mut animation_players[i];
 
-                        animation_player.update();
+                        animation_player.update(get_frame_time());
 
                         // TODO: Calculate scale from a fixed target size, based on ui layout
                         animation_player.set_scale(2.0);
@@ -234,6 +237,10 @@ pub async fn show_select_characters_menu(
 
         root_ui().pop_skin();
 
+        draw_toasts();
+
+        update_music();
+
         next_frame().await;
     }
 
@@ -4,9 +4,12 @@ use macroquad::{
     ui::{self, root_ui, widgets},
 };
 
-use super::GuiResources;
+use super::{draw_button_prompts, draw_toasts, FocusNavigator, GuiResources, PromptAction};
 
-use crate::{resources::MapResource, GamepadContext, Resources};
+use crate::{
+    audio::update_music, input::update_gamepad_context, localization::tr_args,
+    resources::MapResource, GamepadContext, Resources,
+};
 
 use crate::gui::{draw_main_menu_background, WINDOW_MARGIN_H, WINDOW_MARGIN_V};
 
@@ -21,58 +24,38 @@ pub async fn show_select_map_menu() -> MapResource {
 
     let mut old_mouse_position = mouse_position();
 
+    // This is only used for its input, as the focus is kept in `hovered`, to support paging
+    let mut navigator = FocusNavigator::default();
+
     // skip a frame to let Enter be unpressed from the previous screen
     next_frame().await;
 
     loop {
         draw_main_menu_background(false);
 
-        let gui_resources = storage::get::<GuiResources>();
-        let mut gamepad_system = storage::get_mut::<GamepadContext>();
+        let _ = update_gamepad_context(None);
 
-        let _ = gamepad_system.update();
+        let input = navigator.update_input();
 
-        let mut up = is_key_pressed(KeyCode::Up) || is_key_pressed(KeyCode::W);
-        let mut down = is_key_pressed(KeyCode::Down) || is_key_pressed(KeyCode::S);
-        let mut right = is_key_pressed(KeyCode::Right) || is_key_pressed(KeyCode::D);
-        let mut left = is_key_pressed(KeyCode::Left) || is_key_pressed(KeyCode::A);
-        let mut start = is_key_pressed(KeyCode::Enter);
+        let (up, down, left, right) = (input.up, input.down, input.left, input.right);
+
+        let start = input.confirm || {
+            let gamepad_context = storage::get::<GamepadContext>();
+            gamepad_context.gamepads().any(|(_, gamepad)| {
+                gamepad
+                    .digital_inputs
+                    .just_activated(fishsticks::Button::Start)
+            })
+        };
 
         let (page_up, page_down) = {
             let mouse_wheel = mouse_wheel();
             (mouse_wheel.1 > 0.0, mouse_wheel.1 < 0.0)
         };
 
-        for (_, gamepad) in gamepad_system.gamepads() {
-            use fishsticks::{Axis, Button};
-
-            up |= gamepad.digital_inputs.just_activated(Button::DPadUp)
-                || matches!(
-                    gamepad.analog_inputs.just_activated_digital(Axis::LeftY),
-                    Some(value) if value < 0.0
-                );
-
-            down |= gamepad.digital_inputs.just_activated(Button::DPadDown)
-                || matches!(
-                    gamepad.analog_inputs.just_activated_digital(Axis::LeftY),
-                    Some(value) if value > 0.0
-                );
-
-            left |= gamepad.digital_inputs.just_activated(Button::DPadLeft)
-                || matches!(
-                    gamepad.analog_inputs.just_activated_digital(Axis::LeftX),
-                    Some(value) if value < 0.0
-                );
-
-            right |= gamepad.digital_inputs.just_activated(Button::DPadRight)
-                || matches!(
-                    gamepad.analog_inputs.just_activated_digital(Axis::LeftX),
-                    Some(value) if value > 0.0
-                );
-
-            start |= gamepad.digital_inputs.just_activated(Button::A)
-                || gamepad.digital_inputs.just_activated(Button::Start);
-        }
+        draw_button_prompts(&[PromptAction::Navigate, PromptAction::Confirm]);
+
+        let gui_resources = storage::get::<GuiResources>();
 
         let resources = storage::get::<Resources>();
         let map_cnt = resources.maps.len();
@@ -174,7 +157,10 @@ pub async fn show_select_map_menu() -> MapResource {
 
             {
                 if page_cnt > 1 {
-                    let pagination_label = format!("page {}/{}", current_page + 1, page_cnt);
+                    let pagination_label = tr_args(
+                        "select_map.page",
+                        &[&(current_page + 1).to_string(), &page_cnt.to_string()],
+                    );
 
                     let label_size = root_ui().calc_size(&pagination_label);
                     let label_position =
@@ -234,6 +220,10 @@ pub async fn show_select_map_menu() -> MapResource {
 
         old_mouse_position = mouse_position();
 
+        draw_toasts();
+
+        update_music();
+
         next_frame().await;
     }
 }
@@ -0,0 +1,557 @@
+use macroquad::{
+    experimental::collections::storage,
+    prelude::*,
+    ui::{hash, widgets, Ui},
+};
+
+use super::{
+    navigation::NavigationInput, Checkbox, FocusNavigator, GuiResources, Menu, MenuResult, Panel,
+    PromptAction,
+};
+
+use crate::{
+    audio::AudioBus,
+    game::{InputAction, InputBinding, InputBindings, InputProfile, TouchControlsConfig},
+    input::is_gamepad_btn_pressed,
+    localization::{self, get_available_languages, tr},
+    Config, GamepadContext,
+};
+
+const SETTINGS_MENU_WIDTH: f32 = 450.0;
+const SETTINGS_MENU_HEIGHT: f32 = 700.0;
+/// The minimum margin between the menu and the top and bottom of the screen
+const SETTINGS_MENU_MARGIN: f32 = 16.0;
+
+/// The change in volume and camera shake for each press of left or right
+const VOLUME_STEP: f32 = 0.1;
+/// The change in resolution scale for each press of left or right
+const RESOLUTION_SCALE_STEP: f32 = 0.25;
+
+/// The binding profiles that can be changed in the settings menu
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum BindingProfile {
+    KeyboardLeft,
+    KeyboardRight,
+    Gamepad,
+}
+
+impl BindingProfile {
+    const ALL: &'static [BindingProfile] = &[
+        BindingProfile::KeyboardLeft,
+        BindingProfile::KeyboardRight,
+        BindingProfile::Gamepad,
+    ];
+
+    fn get_title(&self) -> String {
+        match self {
+            BindingProfile::KeyboardLeft => tr("settings.controls_keyboard_left"),
+            BindingProfile::KeyboardRight => tr("settings.controls_keyboard_right"),
+            BindingProfile::Gamepad => tr("settings.controls_gamepad"),
+        }
+    }
+
+    fn get_profile_mut<'a>(&self, config: &'a mut Config) -> &'a mut InputProfile {
+        match self {
+            BindingProfile::KeyboardLeft => &mut config.input_bindings.keyboard_left,
+            BindingProfile::KeyboardRight => &mut config.input_bindings.keyboard_right,
+            BindingProfile::Gamepad => &mut config.input_bindings.gamepad,
+        }
+    }
+
+    /// Returns a binding for the key or button that was pressed this frame, on the device of
+    /// this profile, if any
+    fn poll_binding(&self) -> Option<InputBinding> {
+        match self {
+            BindingProfile::Gamepad => {
+                let gamepad_context = storage::get::<GamepadContext>();
+                InputBinding::poll_gamepads(&gamepad_context)
+            }
+            _ => InputBinding::poll_keyboard(),
+        }
+    }
+}
+
+/// The settings that can be selected and changed with up, down, left and right
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum SettingsEntry {
+    Fullscreen,
+    Vsync,
+    ResolutionScale,
+    Volume(AudioBus),
+    CameraShake,
+    ReduceFlashing,
+    Language,
+    OffscreenIndicators,
+    Minimap,
+    Nameplates,
+    GamepadDeadZone,
+    TouchControlsScale,
+    TouchControlsOpacity,
+    Binding(BindingProfile, InputAction),
+    Toggle(InputAction),
+    ResetBindings,
+}
+
+impl SettingsEntry {
+    fn all() -> Vec<SettingsEntry> {
+        let mut res = vec![
+            SettingsEntry::Fullscreen,
+            SettingsEntry::Vsync,
+            SettingsEntry::ResolutionScale,
+        ];
+
+        res.extend(AudioBus::ALL.iter().map(|bus| SettingsEntry::Volume(*bus)));
+
+        res.push(SettingsEntry::CameraShake);
+        res.push(SettingsEntry::ReduceFlashing);
+        res.push(SettingsEntry::Language);
+        res.push(SettingsEntry::OffscreenIndicators);
+        res.push(SettingsEntry::Minimap);
+        res.push(SettingsEntry::Nameplates);
+        res.push(SettingsEntry::GamepadDeadZone);
+        res.push(SettingsEntry::TouchControlsScale);
+        res.push(SettingsEntry::TouchControlsOpacity);
+
+        for profile in BindingProfile::ALL {
+            res.extend(
+                InputAction::ALL
+                    .iter()
+                    .map(|action| SettingsEntry::Binding(*profile, *action)),
+            );
+        }
+
+        res.extend(
+            InputAction::TOGGLEABLE
+                .iter()
+                .map(|action| SettingsEntry::Toggle(*action)),
+        );
+
+        res.push(SettingsEntry::ResetBindings);
+
+        res
+    }
+
+    /// This changes the setting in `config` by one step, in the direction of `step`, which
+    /// should be `-1.0` or `1.0`. Toggles are flipped, regardless of direction.
+    fn change(&self, config: &mut Config, step: f32) {
+        match *self {
+            SettingsEntry::Fullscreen => config.fullscreen = !config.fullscreen,
+            SettingsEntry::Vsync => config.vsync = !config.vsync,
+            SettingsEntry::ResolutionScale => {
+                config.resolution_scale = (config.resolution_scale + step * RESOLUTION_SCALE_STEP)
+                    .clamp(Config::MIN_RESOLUTION_SCALE, Config::MAX_RESOLUTION_SCALE)
+            }
+            SettingsEntry::Volume(bus) => {
+                let volume = config.volume.get(bus) + step * VOLUME_STEP;
+                config.volume.set(bus, volume);
+            }
+            SettingsEntry::CameraShake => {
+                config.camera_shake = (config.camera_shake + step * VOLUME_STEP).clamp(0.0, 1.0)
+            }
+            SettingsEntry::ReduceFlashing => config.reduce_flashing = !config.reduce_flashing,
+            SettingsEntry::Language => {
+                let languages = get_available_languages();
+                let cnt = languages.len() as i32;
+
+                if cnt > 0 {
+                    let current = languages
+                        .iter()
+                        .position(|(id, _)| *id == config.language)
+                        .unwrap_or(0) as i32;
+
+                    let i = (current + step as i32 + cnt) % cnt;
+                    config.language = languages[i as usize].0.clone();
+                }
+            }
+            SettingsEntry::OffscreenIndicators => {
+                config.offscreen_indicators = !config.offscreen_indicators
+            }
+            SettingsEntry::Minimap => config.minimap = !config.minimap,
+            SettingsEntry::Nameplates => config.nameplates = !config.nameplates,
+            SettingsEntry::GamepadDeadZone => {
+                config.gamepad_dead_zone = (config.gamepad_dead_zone + step * VOLUME_STEP)
+                    .clamp(0.0, Config::MAX_GAMEPAD_DEAD_ZONE)
+            }
+            SettingsEntry::TouchControlsScale => {
+                let touch_controls = &mut config.touch_controls;
+                touch_controls.scale = (touch_controls.scale + step * VOLUME_STEP).clamp(
+                    TouchControlsConfig::MIN_SCALE,
+                    TouchControlsConfig::MAX_SCALE,
+                )
+            }
+            SettingsEntry::TouchControlsOpacity => {
+                let touch_controls = &mut config.touch_controls;
+                touch_controls.opacity = (touch_controls.opacity + step * VOLUME_STEP)
+                    .clamp(TouchControlsConfig::MIN_OPACITY, 1.0)
+            }
+            SettingsEntry::Toggle(action) => {
+                let bindings = &mut config.input_bindings;
+                bindings.set_toggled(action, !bindings.is_toggled(action));
+            }
+            // Bindings are changed by confirming the entry and pressing a key or button
+            SettingsEntry::Binding(..) | SettingsEntry::ResetBindings => {}
+        }
+    }
+}
+
+/// This applies the video settings of `config` that differ from `previous` to the window and
+/// switches to the selected language
+fn apply_settings(config: &Config, previous: &Config) {
+    if config.language != previous.language {
+        localization::set_language(&config.language);
+    }
+
+    if config.fullscreen != previous.fullscreen {
+        set_fullscreen(config.fullscreen);
+    }
+
+    let is_resized = !config.fullscreen
+        && (config.fullscreen != previous.fullscreen
+            || config.resolution_scale != previous.resolution_scale);
+
+    if is_resized {
+        let (width, height) = config.get_window_size();
+        request_new_screen_size(width as f32, height as f32);
+    }
+}
+
+/// The settings menu, which holds video, audio and accessibility settings, as well as the
+/// bindings of each input scheme.
+/// Settings can be changed with the mouse, or by focusing a setting with up and down and
+/// changing it with left and right. Bindings are changed by confirming them and then pressing
+/// the new key or button. Changes are applied immediately and the config is saved when the menu
+/// is closed.
+pub struct SettingsMenu {
+    entries: Vec<SettingsEntry>,
+    navigator: FocusNavigator,
+    /// This is set while waiting for the new key or button of a binding
+    rebinding: Option<(BindingProfile, InputAction)>,
+}
+
+impl SettingsMenu {
+    /// The button prompts that should be drawn while the menu is open
+    pub const PROMPTS: &'static [PromptAction] = &[
+        PromptAction::Navigate,
+        PromptAction::Change,
+        PromptAction::Confirm,
+        PromptAction::Cancel,
+    ];
+
+    pub fn new() -> Self {
+        SettingsMenu {
+            entries: SettingsEntry::all(),
+            navigator: FocusNavigator::new(Some(0)),
+            rebinding: None,
+        }
+    }
+
+    /// This binds the key or button that was pressed this frame, if any, to the action that is
+    /// being rebound. Escape, or start on a gamepad, cancels the rebinding.
+    fn update_rebinding(&mut self) {
+        if let Some((profile, action)) = self.rebinding {
+            if is_key_pressed(KeyCode::Escape)
+                || is_gamepad_btn_pressed(None, fishsticks::Button::Start)
+            {
+                self.rebinding = None;
+            } else if let Some(binding) = profile.poll_binding() {
+                let mut config = storage::get_mut::<Config>();
+                profile.get_profile_mut(&mut config).rebind(action, binding);
+
+                self.rebinding = None;
+            }
+        }
+    }
+
+    /// This starts rebinding, or resets all bindings, if the entry is a binding entry
+    fn confirm_entry(&mut self, entry: SettingsEntry) {
+        match entry {
+            SettingsEntry::Binding(profile, action) => self.rebinding = Some((profile, action)),
+            SettingsEntry::ResetBindings => {
+                storage::get_mut::<Config>().input_bindings = InputBindings::default();
+            }
+            _ => {}
+        }
+    }
+
+    fn close() {
+        let config = storage::get::<Config>();
+
+        if let Err(err) = config.save(crate::get_config_path()) {
+            println!("WARNING: Failed to save config: {}", err);
+        }
+    }
+
+    fn get_label(&self, entry: SettingsEntry, title: &str) -> String {
+        let focus = self.navigator.get_focus();
+
+        if focus.map(|i| self.entries[i]) == Some(entry) {
+            format!("> {}", title)
+        } else {
+            title.to_string()
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) -> Option<MenuResult> {
+        let input = if self.rebinding.is_some() {
+            self.update_rebinding();
+            NavigationInput::default()
+        } else {
+            self.navigator.update_input()
+        };
+
+        if input.cancel {
+            Self::close();
+            return Some(Menu::CANCEL_INDEX.into());
+        }
+
+        let entry_cnt = self.entries.len();
+
+        if input.up {
+            self.navigator.move_focus(-1, entry_cnt, |_| true);
+        } else if input.down {
+            self.navigator.move_focus(1, entry_cnt, |_| true);
+        }
+
+        if input.confirm {
+            if let Some(i) = self.navigator.get_focus() {
+                self.confirm_entry(self.entries[i]);
+            }
+        }
+
+        let mut config = storage::get_mut::<Config>();
+        let previous = config.clone();
+
+        // Binding entries are buttons, so this holds the entry that was clicked, if any, to be
+        // confirmed after the config is released
+        let mut clicked = None;
+
+        if input.left || input.right {
+            if let Some(i) = self.navigator.get_focus() {
+                let step = if input.left { -1.0 } else { 1.0 };
+                self.entries[i].change(&mut config, step);
+            }
+        }
+
+        let mut res = None;
+
+        let size = vec2(
+            SETTINGS_MENU_WIDTH,
+            SETTINGS_MENU_HEIGHT.min(screen_height() - SETTINGS_MENU_MARGIN * 2.0),
+        );
+        let position = (vec2(screen_width(), screen_height()) - size) / 2.0;
+
+        Panel::new(hash!(), size, position)
+            .with_title(&tr("settings.title"), true)
+            .ui(ui, |ui, _| {
+                {
+                    let gui_resources = storage::get::<GuiResources>();
+                    ui.push_skin(&gui_resources.skins.menu);
+                }
+
+                ui.label(None, &tr("settings.video"));
+
+                let label = self.get_label(SettingsEntry::Fullscreen, &tr("settings.fullscreen"));
+                Checkbox::new(hash!("settings_fullscreen"), None, &label)
+                    .ui(ui, &mut config.fullscreen);
+
+                let label = self.get_label(SettingsEntry::Vsync, &tr("settings.vsync"));
+                Checkbox::new(hash!("settings_vsync"), None, &label).ui(ui, &mut config.vsync);
+
+                let label = self.get_label(
+                    SettingsEntry::ResolutionScale,
+                    &tr("settings.resolution_scale"),
+                );
+                widgets::Slider::new(
+                    hash!("settings_resolution_scale"),
+                    Config::MIN_RESOLUTION_SCALE..Config::MAX_RESOLUTION_SCALE,
+                )
+                .label(&label)
+                .ui(ui, &mut config.resolution_scale);
+
+                ui.separator();
+                ui.label(None, &tr("settings.audio"));
+
+                for (i, bus) in AudioBus::ALL.iter().enumerate() {
+                    let label = self.get_label(SettingsEntry::Volume(*bus), &bus.get_title());
+
+                    let mut volume = config.volume.get(*bus);
+
+                    widgets::Slider::new(hash!("settings_volume", i), 0.0..1.0)
+                        .label(&label)
+                        .ui(ui, &mut volume);
+
+                    config.volume.set(*bus, volume);
+                }
+
+                ui.separator();
+                ui.label(None, &tr("settings.accessibility"));
+
+                let label =
+                    self.get_label(SettingsEntry::CameraShake, &tr("settings.camera_shake"));
+                widgets::Slider::new(hash!("settings_camera_shake"), 0.0..1.0)
+                    .label(&label)
+                    .ui(ui, &mut config.camera_shake);
+
+                let label = self.get_label(
+                    SettingsEntry::ReduceFlashing,
+                    &tr("settings.reduce_flashing"),
+                );
+                Checkbox::new(hash!("settings_reduce_flashing"), None, &label)
+                    .ui(ui, &mut config.reduce_flashing);
+
+                ui.separator();
+                ui.label(None, &tr("settings.interface"));
+
+                {
+                    let languages = get_available_languages();
+                    let names = languages
+                        .iter()
+                        .map(|(_, name)| name.as_str())
+                        .collect::<Vec<_>>();
+
+                    let mut selection = languages
+                        .iter()
+                        .position(|(id, _)| *id == config.language)
+                        .unwrap_or(0);
+
+                    let label = self.get_label(SettingsEntry::Language, &tr("settings.language"));
+                    widgets::ComboBox::new(hash!("settings_language"), &names)
+                        .label(&label)
+                        .ui(ui, &mut selection);
+
+                    if let Some((id, _)) = languages.get(selection) {
+                        config.language = id.clone();
+                    }
+                }
+
+                let label = self.get_label(
+                    SettingsEntry::OffscreenIndicators,
+                    &tr("settings.offscreen_indicators"),
+                );
+                Checkbox::new(hash!("settings_offscreen_indicators"), None, &label)
+                    .ui(ui, &mut config.offscreen_indicators);
+
+                let label = self.get_label(SettingsEntry::Minimap, &tr("settings.minimap"));
+                Checkbox::new(hash!("settings_minimap"), None, &label).ui(ui, &mut config.minimap);
+
+                let label = self.get_label(SettingsEntry::Nameplates, &tr("settings.nameplates"));
+                Checkbox::new(hash!("settings_nameplates"), None, &label)
+                    .ui(ui, &mut config.nameplates);
+
+                ui.separator();
+                ui.label(None, &tr("settings.gamepad"));
+
+                let label = self.get_label(
+                    SettingsEntry::GamepadDeadZone,
+                    &tr("settings.gamepad_dead_zone"),
+                );
+                widgets::Slider::new(
+                    hash!("settings_gamepad_dead_zone"),
+                    0.0..Config::MAX_GAMEPAD_DEAD_ZONE,
+                )
+                .label(&label)
+                .ui(ui, &mut config.gamepad_dead_zone);
+
+                ui.separator();
+                ui.label(None, &tr("settings.touch_controls"));
+
+                let label = self.get_label(
+                    SettingsEntry::TouchControlsScale,
+                    &tr("settings.touch_controls_scale"),
+                );
+                widgets::Slider::new(
+                    hash!("settings_touch_controls_scale"),
+                    TouchControlsConfig::MIN_SCALE..TouchControlsConfig::MAX_SCALE,
+                )
+                .label(&label)
+                .ui(ui, &mut config.touch_controls.scale);
+
+                let label = self.get_label(
+                    SettingsEntry::TouchControlsOpacity,
+                    &tr("settings.touch_controls_opacity"),
+                );
+                widgets::Slider::new(
+                    hash!("settings_touch_controls_opacity"),
+                    TouchControlsConfig::MIN_OPACITY..1.0,
+                )
+                .label(&label)
+                .ui(ui, &mut config.touch_controls.opacity);
+
+                for profile in BindingProfile::ALL {
+                    ui.separator();
+                    ui.label(None, &profile.get_title());
+
+                    for action in InputAction::ALL {
+                        let entry = SettingsEntry::Binding(*profile, *action);
+
+                        let bindings = if self.rebinding == Some((*profile, *action)) {
+                            tr("settings.press_binding")
+                        } else {
+                            profile
+                                .get_profile_mut(&mut config)
+                                .get_binding_names(*action)
+                        };
+
+                        let label =
+                            self.get_label(entry, &format!("{}: {}", action.get_title(), bindings));
+
+                        if widgets::Button::new(label.as_str()).ui(ui) {
+                            clicked = self.entries.iter().position(|other| *other == entry);
+                        }
+                    }
+                }
+
+                ui.separator();
+                ui.label(None, &tr("settings.toggled_actions"));
+
+                for action in InputAction::TOGGLEABLE {
+                    let mut is_toggled = config.input_bindings.is_toggled(*action);
+
+                    let label = self.get_label(SettingsEntry::Toggle(*action), &action.get_title());
+                    Checkbox::new(hash!("settings_toggle", action), None, &label)
+                        .ui(ui, &mut is_toggled);
+
+                    config.input_bindings.set_toggled(*action, is_toggled);
+                }
+
+                ui.separator();
+
+                let label =
+                    self.get_label(SettingsEntry::ResetBindings, &tr("settings.reset_bindings"));
+                if widgets::Button::new(label.as_str()).ui(ui) {
+                    clicked = self
+                        .entries
+                        .iter()
+                        .position(|other| *other == SettingsEntry::ResetBindings);
+                }
+
+                ui.separator();
+
+                if widgets::Button::new(tr("settings.back").as_str()).ui(ui) {
+                    res = Some(Menu::CANCEL_INDEX.into());
+                }
+
+                ui.pop_skin();
+            });
+
+        apply_settings(&config, &previous);
+        drop(config);
+
+        if let Some(i) = clicked {
+            self.navigator.set_focus(Some(i));
+            self.confirm_entry(self.entries[i]);
+        }
+
+        if res.is_some() {
+            Self::close();
+        }
+
+        res
+    }
+}
+
+impl Default for SettingsMenu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
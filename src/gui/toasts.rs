@@ -0,0 +1,212 @@
+//! Toasts are short notifications, like a player joining or a map being saved, that are stacked in
+//! the top right corner of the screen and disappear after a while. They can be shown from any
+//! subsystem, with `show_toast`, and they are kept across scene changes.
+//!
+//! Only a few toasts are visible at once. Any others are queued, by priority, until there is room,
+//! and high priority toasts will push out visible toasts of lower priority, if the stack is full.
+//!
+//! Toasts are drawn by the `ToastOverlay` node in games and in the editor, and by calling
+//! `draw_toasts` directly in menus.
+
+use macroquad::{
+    color,
+    experimental::scene::{Node, RefMut},
+    prelude::*,
+};
+
+use crate::text::{draw_aligned_text, HorizontalAlignment, VerticalAlignment};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub enum ToastPriority {
+    Low,
+    Normal,
+    High,
+}
+
+impl ToastPriority {
+    /// The duration of toasts of this priority, if no duration is specified
+    fn default_duration(&self) -> f32 {
+        match self {
+            ToastPriority::Low => 2.0,
+            ToastPriority::Normal => 3.0,
+            ToastPriority::High => 5.0,
+        }
+    }
+
+    fn get_color(&self) -> Color {
+        match self {
+            ToastPriority::Low => color::LIGHTGRAY,
+            ToastPriority::Normal => color::WHITE,
+            ToastPriority::High => color::ORANGE,
+        }
+    }
+}
+
+struct Toast {
+    text: String,
+    priority: ToastPriority,
+    duration: f32,
+    timer: f32,
+}
+
+impl Toast {
+    /// Returns the opacity of the toast, as it fades in and out
+    fn get_opacity(&self) -> f32 {
+        let time_left = self.duration - self.timer;
+        (self.timer.min(time_left) / FADE_TIME).clamp(0.0, 1.0)
+    }
+}
+
+/// The maximum amount of toasts that are visible at once
+const MAX_VISIBLE_CNT: usize = 4;
+
+const FADE_TIME: f32 = 0.25;
+
+const WIDTH: f32 = 320.0;
+const HEIGHT: f32 = 32.0;
+const SPACING: f32 = 8.0;
+const MARGIN: f32 = 16.0;
+const FONT_SIZE: u16 = 16;
+
+const BACKGROUND_COLOR: Color = Color {
+    r: 0.0,
+    g: 0.0,
+    b: 0.0,
+    a: 0.75,
+};
+
+static mut VISIBLE_TOASTS: Option<Vec<Toast>> = None;
+
+static mut QUEUED_TOASTS: Option<Vec<Toast>> = None;
+
+unsafe fn get_visible_toasts() -> &'static mut Vec<Toast> {
+    VISIBLE_TOASTS.get_or_insert(Vec::new())
+}
+
+unsafe fn get_queued_toasts() -> &'static mut Vec<Toast> {
+    QUEUED_TOASTS.get_or_insert(Vec::new())
+}
+
+/// This shows a toast for the default duration of `priority`
+pub fn show_toast(text: &str, priority: ToastPriority) {
+    show_toast_ex(text, priority, priority.default_duration());
+}
+
+/// This shows a toast for `duration` seconds
+pub fn show_toast_ex(text: &str, priority: ToastPriority, duration: f32) {
+    let toast = Toast {
+        text: text.to_string(),
+        priority,
+        duration,
+        timer: 0.0,
+    };
+
+    let queue = unsafe { get_queued_toasts() };
+
+    // The queue is kept in order of priority, with toasts of the same priority in the order they
+    // were added
+    let i = queue
+        .iter()
+        .position(|other| other.priority < priority)
+        .unwrap_or(queue.len());
+
+    queue.insert(i, toast);
+}
+
+fn update_toasts(dt: f32) {
+    let visible = unsafe { get_visible_toasts() };
+    let queue = unsafe { get_queued_toasts() };
+
+    for toast in visible.iter_mut() {
+        toast.timer += dt;
+    }
+
+    visible.retain(|toast| toast.timer < toast.duration);
+
+    while !queue.is_empty() {
+        if visible.len() >= MAX_VISIBLE_CNT {
+            // A full stack only makes room for toasts of higher priority, by dropping the visible
+            // toast of lowest priority that has been shown the longest
+            let lowest = visible
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| {
+                    a.priority
+                        .cmp(&b.priority)
+                        .then(b.timer.partial_cmp(&a.timer).unwrap())
+                })
+                .map(|(i, toast)| (i, toast.priority));
+
+            match lowest {
+                Some((i, priority)) if priority < queue[0].priority => {
+                    visible.remove(i);
+                }
+                _ => break,
+            }
+        }
+
+        visible.push(queue.remove(0));
+    }
+}
+
+/// This updates and draws the visible toasts. It should be called once per frame, in screen space.
+pub fn draw_toasts() {
+    update_toasts(get_frame_time());
+
+    let visible = unsafe { get_visible_toasts() };
+
+    let x = screen_width() - WIDTH - MARGIN;
+    let mut y = MARGIN;
+
+    for toast in visible.iter() {
+        let opacity = toast.get_opacity();
+
+        let mut background_color = BACKGROUND_COLOR;
+        background_color.a *= opacity;
+
+        draw_rectangle(x, y, WIDTH, HEIGHT, background_color);
+
+        let mut text_color = toast.priority.get_color();
+        text_color.a *= opacity;
+
+        draw_aligned_text(
+            &toast.text,
+            vec2(x + SPACING, y + HEIGHT / 2.0),
+            HorizontalAlignment::Left,
+            VerticalAlignment::Center,
+            TextParams {
+                font_size: FONT_SIZE,
+                color: text_color,
+                ..Default::default()
+            },
+        );
+
+        y += HEIGHT + SPACING;
+    }
+}
+
+/// This draws toasts on top of a scene
+pub struct ToastOverlay;
+
+impl ToastOverlay {
+    pub fn new() -> Self {
+        ToastOverlay
+    }
+}
+
+impl Default for ToastOverlay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Node for ToastOverlay {
+    fn draw(_: RefMut<Self>) {
+        push_camera_state();
+        set_default_camera();
+
+        draw_toasts();
+
+        pop_camera_state();
+    }
+}
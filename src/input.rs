@@ -1,18 +1,61 @@
 use macroquad::experimental::collections::storage;
 
-use crate::{GamepadContext, Result};
+use fishsticks::GamepadId;
 
+use crate::{
+    gui::{show_toast, ToastPriority},
+    localization::tr,
+    GamepadContext, Result,
+};
+
+/// The gamepads that were connected after the last update, to detect gamepads that are connected
+/// or disconnected at runtime. This is `None` before the first update, so that the gamepads that
+/// are connected at startup are not reported.
+static mut CONNECTED_GAMEPADS: Option<Vec<GamepadId>> = None;
+
+/// This updates the gamepad context and shows a toast for every gamepad that was connected or
+/// disconnected since the last update. This should be called once per frame.
 pub fn update_gamepad_context(context: Option<&mut GamepadContext>) -> Result<()> {
     if let Some(context) = context {
         context.update()?;
+        update_connected_gamepads(context);
     } else {
         let mut context = storage::get_mut::<GamepadContext>();
         context.update()?;
+        update_connected_gamepads(&context);
     }
 
     Ok(())
 }
 
+fn update_connected_gamepads(context: &GamepadContext) {
+    let current = context.gamepads().map(|(ix, _)| ix).collect::<Vec<_>>();
+
+    if let Some(previous) = unsafe { &CONNECTED_GAMEPADS } {
+        if current.iter().any(|ix| !previous.contains(ix)) {
+            show_toast(&tr("toast.gamepad_connected"), ToastPriority::Low);
+        }
+
+        if previous.iter().any(|ix| !current.contains(ix)) {
+            show_toast(&tr("toast.gamepad_disconnected"), ToastPriority::Low);
+        }
+    }
+
+    unsafe { CONNECTED_GAMEPADS = Some(current) };
+}
+
+/// This applies a dead zone to the value of an analog axis, between `-1.0` and `1.0`, so that
+/// values within the dead zone are zero, and values outside of it are scaled to the full range
+pub fn apply_dead_zone(value: f32, dead_zone: f32) -> f32 {
+    let magnitude = value.abs();
+
+    if magnitude <= dead_zone || dead_zone >= 1.0 {
+        0.0
+    } else {
+        value.signum() * ((magnitude - dead_zone) / (1.0 - dead_zone)).min(1.0)
+    }
+}
+
 pub fn is_gamepad_btn_pressed(context: Option<&GamepadContext>, btn: fishsticks::Button) -> bool {
     let check = |context: &GamepadContext| -> bool {
         for (_, gamepad) in context.gamepads() {
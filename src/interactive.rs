@@ -0,0 +1,575 @@
+//! Interactive objects are environment objects, placed in a map, that players can interact with.
+//! Buttons and pressure plates signal all objects with a `link_id` that is among their `targets`,
+//! while they are active. A door will be open while it is signaled and a launch pad with a
+//! `link_id` will only launch players while it is signaled. Launch pads without a `link_id` are
+//! always active. Breakable crates will drop an item from a loot table, when they are destroyed.
+//! The links, as well as the configuration of each object, are stored in the properties of the
+//! map objects, so that they can be edited in the map editor.
+
+use std::collections::HashSet;
+
+use macroquad::{
+    color,
+    experimental::{
+        collections::storage,
+        scene::{self, HandleUntyped, Node, RefMut},
+    },
+    prelude::*,
+};
+
+use macroquad_platformer::Solid;
+
+use crate::{
+    audio::play_sound_at,
+    capabilities::{NetworkReplicate, PhysicsObject, PhysicsObjectTrait},
+    components::{Sprite, SpriteParams},
+    effects::active::ExplosionFalloff,
+    game::SIMULATION_DELTA_TIME,
+    json::GenericParam,
+    map::MapObject,
+    GameWorld, Item, ParticleEmitters, Player, Resources,
+};
+
+enum InteractiveObjectKind {
+    /// Toggled by a player standing on it and pressing down
+    Button,
+    /// Active while a player or an item is resting on it
+    PressurePlate,
+    /// A solid that slides up, into the ceiling, while it is signaled
+    Door { solid: Solid, open_offset: f32 },
+    /// Launches players and items that touch it
+    LaunchPad { velocity: Vec2, sprite: Sprite },
+    /// Drops an item from a loot table when it runs out of health
+    BreakableCrate {
+        health: f32,
+        loot_table: String,
+        sprite: Sprite,
+    },
+}
+
+struct InteractiveObject {
+    kind: InteractiveObjectKind,
+    position: Vec2,
+    size: Vec2,
+    link_id: Option<String>,
+    targets: Vec<String>,
+    is_active: bool,
+    cooldown_timer: f32,
+}
+
+impl InteractiveObject {
+    fn get_rect(&self) -> Rect {
+        Rect::new(self.position.x, self.position.y, self.size.x, self.size.y)
+    }
+
+    /// The rect of the top edge of the object, used to check for objects resting on it
+    fn get_trigger_rect(&self) -> Rect {
+        Rect::new(
+            self.position.x,
+            self.position.y - InteractiveObjects::TRIGGER_HEIGHT,
+            self.size.x,
+            InteractiveObjects::TRIGGER_HEIGHT * 2.0,
+        )
+    }
+
+    fn is_signaled(&self, signals: &HashSet<String>) -> bool {
+        self.link_id
+            .as_ref()
+            .map(|link_id| signals.contains(link_id))
+            .unwrap_or(false)
+    }
+
+    fn apply_damage(&mut self, damage: f32) -> bool {
+        if let InteractiveObjectKind::BreakableCrate { health, .. } = &mut self.kind {
+            *health -= damage;
+            return true;
+        }
+
+        false
+    }
+
+    fn is_destroyed(&self) -> bool {
+        if let InteractiveObjectKind::BreakableCrate { health, .. } = &self.kind {
+            return *health <= 0.0;
+        }
+
+        false
+    }
+}
+
+fn get_string_property(object: &MapObject, id: &str) -> Option<String> {
+    object
+        .properties
+        .get(id)
+        .and_then(|param| param.get_value::<String>())
+        .filter(|value| !value.is_empty())
+        .cloned()
+}
+
+#[derive(Default)]
+pub struct InteractiveObjects {
+    objects: Vec<InteractiveObject>,
+}
+
+impl InteractiveObjects {
+    pub const BUTTON_OBJECT_ID: &'static str = "button";
+    pub const PRESSURE_PLATE_OBJECT_ID: &'static str = "pressure_plate";
+    pub const DOOR_OBJECT_ID: &'static str = "door";
+    pub const LAUNCH_PAD_OBJECT_ID: &'static str = "launch_pad";
+    pub const BREAKABLE_CRATE_OBJECT_ID: &'static str = "breakable_crate";
+
+    pub const OBJECT_IDS: &'static [&'static str] = &[
+        Self::BUTTON_OBJECT_ID,
+        Self::PRESSURE_PLATE_OBJECT_ID,
+        Self::DOOR_OBJECT_ID,
+        Self::LAUNCH_PAD_OBJECT_ID,
+        Self::BREAKABLE_CRATE_OBJECT_ID,
+    ];
+
+    /// The id that buttons and pressure plates use to target an object
+    pub const LINK_ID_PROPERTY: &'static str = "link_id";
+    /// A comma separated list of the link ids that a button or pressure plate will signal
+    pub const TARGETS_PROPERTY: &'static str = "targets";
+    /// The size of a door
+    pub const SIZE_PROPERTY: &'static str = "size";
+    /// The velocity that a launch pad will give to players and items
+    pub const LAUNCH_VELOCITY_PROPERTY: &'static str = "launch_velocity";
+    /// The health of a breakable crate
+    pub const HEALTH_PROPERTY: &'static str = "health";
+    /// The id of the loot table that a breakable crate will drop an item from
+    pub const LOOT_TABLE_PROPERTY: &'static str = "loot_table";
+
+    const BUTTON_SIZE: [f32; 2] = [24.0, 8.0];
+    const PRESSURE_PLATE_SIZE: [f32; 2] = [48.0, 6.0];
+    const DEFAULT_DOOR_SIZE: [f32; 2] = [32.0, 96.0];
+    const LAUNCH_PAD_SIZE: [f32; 2] = [31.0, 20.0];
+    const BREAKABLE_CRATE_SIZE: [f32; 2] = [36.0, 31.0];
+
+    const DEFAULT_LAUNCH_VELOCITY: [f32; 2] = [0.0, -1300.0];
+    const DEFAULT_CRATE_HEALTH: f32 = 50.0;
+    const DEFAULT_LOOT_TABLE: &'static str = "default";
+
+    /// The height of the area, above and below the top edge of an object, that is checked for
+    /// players and items resting on it
+    const TRIGGER_HEIGHT: f32 = 4.0;
+    /// The speed at which doors open and close, in pixels per second
+    const DOOR_SPEED: f32 = 240.0;
+    /// The time before a launch pad can launch again
+    const LAUNCH_PAD_COOLDOWN: f32 = 0.25;
+
+    const BUTTON_COLOR: Color = color::RED;
+    const BUTTON_ACTIVE_COLOR: Color = color::GREEN;
+    const PRESSURE_PLATE_COLOR: Color = color::GRAY;
+    const PRESSURE_PLATE_ACTIVE_COLOR: Color = color::LIGHTGRAY;
+    const DOOR_COLOR: Color = color::BROWN;
+
+    const BUTTON_SOUND_EFFECT_ID: &'static str = "pickup";
+    const LAUNCH_SOUND_EFFECT_ID: &'static str = "jump";
+    const BREAK_SOUND_EFFECT_ID: &'static str = "land";
+    const BREAK_PARTICLE_EFFECT_ID: &'static str = "crate_debris";
+
+    /// This will instantiate the interactive objects of a map. The collision world of the
+    /// `GameWorld` is required for doors, so it must be stored before this is called.
+    pub fn new(map_objects: &[MapObject]) -> Self {
+        let mut objects = Vec::new();
+
+        for object in map_objects {
+            let kind = match object.id.as_str() {
+                Self::BUTTON_OBJECT_ID => {
+                    Some((InteractiveObjectKind::Button, Self::BUTTON_SIZE.into()))
+                }
+                Self::PRESSURE_PLATE_OBJECT_ID => Some((
+                    InteractiveObjectKind::PressurePlate,
+                    Self::PRESSURE_PLATE_SIZE.into(),
+                )),
+                Self::DOOR_OBJECT_ID => {
+                    let size = object
+                        .properties
+                        .get(Self::SIZE_PROPERTY)
+                        .and_then(|param| param.get_value::<Vec2>())
+                        .copied()
+                        .unwrap_or_else(|| Self::DEFAULT_DOOR_SIZE.into());
+
+                    let solid = {
+                        let mut world = storage::get_mut::<GameWorld>();
+                        world.collision_world.add_solid(
+                            object.position,
+                            size.x as i32,
+                            size.y as i32,
+                        )
+                    };
+
+                    Some((
+                        InteractiveObjectKind::Door {
+                            solid,
+                            open_offset: 0.0,
+                        },
+                        size,
+                    ))
+                }
+                Self::LAUNCH_PAD_OBJECT_ID => {
+                    let velocity = object
+                        .properties
+                        .get(Self::LAUNCH_VELOCITY_PROPERTY)
+                        .and_then(|param| param.get_value::<Vec2>())
+                        .copied()
+                        .unwrap_or_else(|| Self::DEFAULT_LAUNCH_VELOCITY.into());
+
+                    let sprite = Sprite::new(SpriteParams {
+                        texture_id: "sproinger".to_string(),
+                        ..Default::default()
+                    });
+
+                    Some((
+                        InteractiveObjectKind::LaunchPad { velocity, sprite },
+                        Self::LAUNCH_PAD_SIZE.into(),
+                    ))
+                }
+                Self::BREAKABLE_CRATE_OBJECT_ID => {
+                    let health = object
+                        .properties
+                        .get(Self::HEALTH_PROPERTY)
+                        .and_then(GenericParam::to_f32)
+                        .unwrap_or(Self::DEFAULT_CRATE_HEALTH);
+
+                    let loot_table = get_string_property(object, Self::LOOT_TABLE_PROPERTY)
+                        .unwrap_or_else(|| Self::DEFAULT_LOOT_TABLE.to_string());
+
+                    let sprite = Sprite::new(SpriteParams {
+                        texture_id: "crate".to_string(),
+                        ..Default::default()
+                    });
+
+                    Some((
+                        InteractiveObjectKind::BreakableCrate {
+                            health,
+                            loot_table,
+                            sprite,
+                        },
+                        Self::BREAKABLE_CRATE_SIZE.into(),
+                    ))
+                }
+                _ => None,
+            };
+
+            if let Some((kind, size)) = kind {
+                let link_id = get_string_property(object, Self::LINK_ID_PROPERTY);
+
+                let targets = get_string_property(object, Self::TARGETS_PROPERTY)
+                    .map(|targets| {
+                        targets
+                            .split(',')
+                            .map(|target| target.trim().to_string())
+                            .filter(|target| !target.is_empty())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+
+                objects.push(InteractiveObject {
+                    kind,
+                    position: object.position,
+                    size,
+                    link_id,
+                    targets,
+                    is_active: false,
+                    cooldown_timer: 0.0,
+                });
+            } else {
+                println!("WARNING: Invalid environment object id '{}'", &object.id);
+            }
+        }
+
+        InteractiveObjects { objects }
+    }
+
+    /// Returns `true` if `id` is the id of an interactive environment object
+    pub fn is_interactive_object(id: &str) -> bool {
+        Self::OBJECT_IDS.contains(&id)
+    }
+
+    /// This checks for a projectile hit at `point`, damaging any breakable crate that is hit.
+    /// Returns `true` if the projectile was stopped by a crate.
+    pub fn check_projectile_hit(&mut self, point: Vec2, damage: f32) -> bool {
+        for object in &mut self.objects {
+            if object.get_rect().contains(point) && object.apply_damage(damage) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// This applies the damage of an explosion to all breakable crates within its radius
+    pub fn apply_explosion_damage(
+        &mut self,
+        circle: &Circle,
+        damage: f32,
+        falloff: ExplosionFalloff,
+    ) {
+        for object in &mut self.objects {
+            let rect = object.get_rect();
+
+            if circle.overlaps_rect(&rect) {
+                let distance = if circle.r > 0.0 {
+                    circle.point().distance(rect.point() + rect.size() / 2.0) / circle.r
+                } else {
+                    0.0
+                };
+
+                object.apply_damage(damage * falloff.get_factor(distance));
+            }
+        }
+    }
+
+    fn update_button(object: &mut InteractiveObject) {
+        let rect = object.get_trigger_rect();
+
+        let is_pressed = scene::find_nodes_by_type::<Player>().any(|player| {
+            !player.is_dead
+                && player.input.down
+                && !player.last_frame_input.down
+                && rect.overlaps(&player.get_collider_rect())
+        });
+
+        if is_pressed {
+            object.is_active = !object.is_active;
+
+            let resources = storage::get::<Resources>();
+            play_sound_at(
+                &resources.sounds[Self::BUTTON_SOUND_EFFECT_ID],
+                object.position,
+            );
+        }
+    }
+
+    fn update_pressure_plate(object: &mut InteractiveObject) {
+        let rect = object.get_trigger_rect();
+
+        let is_pressed = scene::find_nodes_with::<PhysicsObject>()
+            .filter(|physics_object| physics_object.active())
+            .any(|physics_object| rect.overlaps(&physics_object.collider()));
+
+        if is_pressed && !object.is_active {
+            let resources = storage::get::<Resources>();
+            play_sound_at(
+                &resources.sounds[Self::BUTTON_SOUND_EFFECT_ID],
+                object.position,
+            );
+        }
+
+        object.is_active = is_pressed;
+    }
+
+    fn update_door(object: &mut InteractiveObject, signals: &HashSet<String>, dt: f32) {
+        object.is_active = object.is_signaled(signals);
+
+        if let InteractiveObjectKind::Door { solid, open_offset } = &mut object.kind {
+            let target_offset = if object.is_active { object.size.y } else { 0.0 };
+
+            let delta =
+                (target_offset - *open_offset).clamp(-Self::DOOR_SPEED * dt, Self::DOOR_SPEED * dt);
+
+            if delta != 0.0 {
+                *open_offset += delta;
+
+                let mut world = storage::get_mut::<GameWorld>();
+                world.collision_world.solid_move(*solid, 0.0, -delta);
+            }
+        }
+    }
+
+    fn update_launch_pad(object: &mut InteractiveObject, signals: &HashSet<String>, dt: f32) {
+        object.is_active = object.link_id.is_none() || object.is_signaled(signals);
+
+        if object.cooldown_timer > 0.0 {
+            object.cooldown_timer -= dt;
+            return;
+        }
+
+        if !object.is_active {
+            return;
+        }
+
+        if let InteractiveObjectKind::LaunchPad { velocity, .. } = &object.kind {
+            let rect = object.get_trigger_rect();
+
+            let mut is_launched = false;
+
+            for physics_object in
+                scene::find_nodes_with::<PhysicsObject>().filter(|obj| obj.active())
+            {
+                if rect.overlaps(&physics_object.collider()) {
+                    if velocity.x != 0.0 {
+                        physics_object.set_speed_x(velocity.x);
+                    }
+
+                    physics_object.set_speed_y(velocity.y);
+
+                    is_launched = true;
+                }
+            }
+
+            if is_launched {
+                object.cooldown_timer = Self::LAUNCH_PAD_COOLDOWN;
+
+                let resources = storage::get::<Resources>();
+                play_sound_at(
+                    &resources.sounds[Self::LAUNCH_SOUND_EFFECT_ID],
+                    object.position,
+                );
+            }
+        }
+    }
+
+    fn on_crate_destroyed(object: InteractiveObject) {
+        let center = object.position + object.size / 2.0;
+
+        if let InteractiveObjectKind::BreakableCrate { loot_table, .. } = object.kind {
+            let params = {
+                let resources = storage::get::<Resources>();
+
+                resources
+                    .loot_tables
+                    .get(&loot_table)
+                    .and_then(|loot_table| loot_table.pick_item(&resources))
+                    .and_then(|item_id| resources.items.get(&item_id).cloned())
+            };
+
+            if let Some(params) = params {
+                let position = center - params.collider_size.as_f32() / 2.0;
+
+                let mut item = Item::new(position, params);
+                item.body.is_bobbing = true;
+
+                scene::add_node(item);
+            } else {
+                println!(
+                    "WARNING: Breakable crate failed to drop an item from loot table '{}'",
+                    &loot_table
+                );
+            }
+        }
+
+        {
+            let mut particles = scene::find_node_by_type::<ParticleEmitters>().unwrap();
+            particles.spawn(Self::BREAK_PARTICLE_EFFECT_ID, center);
+        }
+
+        let resources = storage::get::<Resources>();
+        play_sound_at(&resources.sounds[Self::BREAK_SOUND_EFFECT_ID], center);
+    }
+
+    fn network_update(mut node: RefMut<Self>) {
+        let dt = SIMULATION_DELTA_TIME;
+
+        for object in &mut node.objects {
+            match object.kind {
+                InteractiveObjectKind::Button => Self::update_button(object),
+                InteractiveObjectKind::PressurePlate => Self::update_pressure_plate(object),
+                _ => {}
+            }
+        }
+
+        let signals = node
+            .objects
+            .iter()
+            .filter(|object| object.is_active)
+            .filter(|object| {
+                matches!(
+                    object.kind,
+                    InteractiveObjectKind::Button | InteractiveObjectKind::PressurePlate
+                )
+            })
+            .flat_map(|object| object.targets.iter().cloned())
+            .collect::<HashSet<_>>();
+
+        for object in &mut node.objects {
+            match object.kind {
+                InteractiveObjectKind::Door { .. } => Self::update_door(object, &signals, dt),
+                InteractiveObjectKind::LaunchPad { .. } => {
+                    Self::update_launch_pad(object, &signals, dt)
+                }
+                _ => {}
+            }
+        }
+
+        let mut i = 0;
+        while i < node.objects.len() {
+            if node.objects[i].is_destroyed() {
+                let object = node.objects.remove(i);
+                Self::on_crate_destroyed(object);
+                continue;
+            }
+
+            i += 1;
+        }
+    }
+
+    fn network_capabilities() -> NetworkReplicate {
+        fn network_update(handle: HandleUntyped) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<InteractiveObjects>();
+            InteractiveObjects::network_update(node);
+        }
+
+        NetworkReplicate { network_update }
+    }
+}
+
+impl Node for InteractiveObjects {
+    fn ready(mut node: RefMut<Self>) {
+        node.provides(Self::network_capabilities());
+    }
+
+    fn draw(node: RefMut<Self>) {
+        for object in &node.objects {
+            let rect = object.get_rect();
+
+            match &object.kind {
+                InteractiveObjectKind::Button => {
+                    let color = if object.is_active {
+                        Self::BUTTON_ACTIVE_COLOR
+                    } else {
+                        Self::BUTTON_COLOR
+                    };
+
+                    draw_rectangle(rect.x, rect.y, rect.w, rect.h, color);
+                }
+                InteractiveObjectKind::PressurePlate => {
+                    let (y, h, color) = if object.is_active {
+                        (
+                            rect.y + rect.h / 2.0,
+                            rect.h / 2.0,
+                            Self::PRESSURE_PLATE_ACTIVE_COLOR,
+                        )
+                    } else {
+                        (rect.y, rect.h, Self::PRESSURE_PLATE_COLOR)
+                    };
+
+                    draw_rectangle(rect.x, y, rect.w, h, color);
+                }
+                InteractiveObjectKind::Door { open_offset, .. } => {
+                    draw_rectangle(
+                        rect.x,
+                        rect.y - open_offset,
+                        rect.w,
+                        rect.h,
+                        Self::DOOR_COLOR,
+                    );
+                }
+                InteractiveObjectKind::LaunchPad { sprite, .. } => {
+                    sprite.draw(object.position, 0.0, false, false);
+                }
+                InteractiveObjectKind::BreakableCrate { sprite, .. } => {
+                    sprite.draw(object.position, 0.0, false, false);
+                }
+            }
+
+            #[cfg(debug_assertions)]
+            if crate::debug::is_debug_draw_enabled() {
+                draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 2.0, color::YELLOW);
+            }
+        }
+    }
+}
@@ -5,10 +5,142 @@ use serde::{Deserialize, Serialize};
 use crate::{
     components::{AnimationParams, AnimationPlayer},
     json::{self, OneOrMany},
+    symbol::Symbol,
     Player,
 };
 
-use crate::effects::{active_effect_coroutine, AnyEffectParams};
+use crate::effects::{instantiate_active_effect, AnyEffectParams};
+
+/// The equipment slots of a player. Only one item can be equipped in each slot, so equipping an
+/// item in an occupied slot will drop the item previously equipped in it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EquipmentSlot {
+    Head,
+    Back,
+    Feet,
+}
+
+/// This holds factors that are applied to the base stats of a player, while an item is equipped.
+/// If a player has several items equipped, the factors of all the items are multiplied.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct PlayerStatModifiers {
+    #[serde(default = "PlayerStatModifiers::default_factor")]
+    pub move_speed_factor: f32,
+    #[serde(default = "PlayerStatModifiers::default_factor")]
+    pub jump_force_factor: f32,
+    #[serde(default = "PlayerStatModifiers::default_factor")]
+    pub float_gravity_factor: f32,
+}
+
+impl PlayerStatModifiers {
+    pub fn default_factor() -> f32 {
+        1.0
+    }
+
+    /// Returns the modifiers resulting from applying both `self` and `other`
+    pub fn combine(&self, other: &PlayerStatModifiers) -> Self {
+        PlayerStatModifiers {
+            move_speed_factor: self.move_speed_factor * other.move_speed_factor,
+            jump_force_factor: self.jump_force_factor * other.jump_force_factor,
+            float_gravity_factor: self.float_gravity_factor * other.float_gravity_factor,
+        }
+    }
+}
+
+impl Default for PlayerStatModifiers {
+    fn default() -> Self {
+        PlayerStatModifiers {
+            move_speed_factor: 1.0,
+            jump_force_factor: 1.0,
+            float_gravity_factor: 1.0,
+        }
+    }
+}
+
+/// Abilities granted to a player, while an item is equipped
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EquipmentAbility {
+    /// Holding jump while in the air will thrust the player upwards, until the fuel runs out.
+    /// Fuel is replenished while the player is on the ground.
+    Jetpack {
+        /// The upwards acceleration applied while thrusting
+        force: f32,
+        /// The maximum duration, in seconds, of continuous thrust
+        fuel: f32,
+        /// The amount of fuel replenished per second, while on the ground
+        #[serde(default = "EquipmentAbility::default_recharge_rate")]
+        recharge_rate: f32,
+        /// This can specify the id of a particle effect that will be spawned, below the player,
+        /// while thrusting
+        #[serde(
+            default,
+            rename = "particle_effect",
+            skip_serializing_if = "Option::is_none"
+        )]
+        particle_effect_id: Option<String>,
+    },
+    /// The player will be able to jump while in the air, `jumps` times, before landing again
+    AirJump {
+        #[serde(default = "EquipmentAbility::default_air_jumps")]
+        jumps: u32,
+    },
+}
+
+impl EquipmentAbility {
+    pub fn default_recharge_rate() -> f32 {
+        1.0
+    }
+
+    pub fn default_air_jumps() -> u32 {
+        1
+    }
+}
+
+/// An instance of an `EquipmentAbility`, holding its state
+pub struct EquippedAbility {
+    pub kind: EquipmentAbility,
+    /// The remaining fuel of a jetpack
+    pub fuel: f32,
+    /// The remaining air jumps
+    pub jumps_left: u32,
+    /// Time since particles were last spawned, used to throttle jetpack particles
+    pub particle_timer: f32,
+}
+
+impl EquippedAbility {
+    pub fn new(kind: EquipmentAbility) -> Self {
+        let (fuel, jumps_left) = match &kind {
+            EquipmentAbility::Jetpack { fuel, .. } => (*fuel, 0),
+            EquipmentAbility::AirJump { jumps } => (0.0, *jumps),
+        };
+
+        EquippedAbility {
+            kind,
+            fuel,
+            jumps_left,
+            particle_timer: 0.0,
+        }
+    }
+
+    /// This will replenish fuel and air jumps. Should be called every frame the player is on the
+    /// ground.
+    pub fn replenish(&mut self, dt: f32) {
+        match &self.kind {
+            EquipmentAbility::Jetpack {
+                fuel,
+                recharge_rate,
+                ..
+            } => {
+                self.fuel = (self.fuel + recharge_rate * dt).min(*fuel);
+            }
+            EquipmentAbility::AirJump { jumps } => {
+                self.jumps_left = *jumps;
+            }
+        }
+    }
+}
 
 /// This holds the parameters used when constructing an `EquippedItem`
 #[derive(Clone, Serialize, Deserialize)]
@@ -30,11 +162,29 @@ pub struct EquippedItemParams {
     /// If this is true the item will be dropped if the player holding it dies
     #[serde(default)]
     pub is_dropped_on_death: bool,
+    /// The slot the item occupies when equipped. Items without a slot can be equipped alongside
+    /// any other items.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slot: Option<EquipmentSlot>,
+    /// Factors applied to the stats of the player, while the item is equipped
+    #[serde(default)]
+    pub stat_modifiers: PlayerStatModifiers,
+    /// Abilities granted to the player, while the item is equipped
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub abilities: Vec<EquipmentAbility>,
+    /// If this is true, the animation of the item will follow the animation of the player,
+    /// frame by frame, when it has an animation with the same id as the one played by the player
+    #[serde(default, skip_serializing_if = "json::is_false")]
+    pub is_animation_synced: bool,
 }
 
 #[allow(dead_code)]
 pub struct EquippedItem {
     pub id: String,
+    pub slot: Option<EquipmentSlot>,
+    pub stat_modifiers: PlayerStatModifiers,
+    pub abilities: Vec<EquippedAbility>,
+    is_animation_synced: bool,
     mount_offset: Vec2,
     pub sprite_animation: Option<AnimationPlayer>,
     duration: Option<f32>,
@@ -48,7 +198,7 @@ impl EquippedItem {
         for params in params.effects.into_vec() {
             match params {
                 AnyEffectParams::Active(params) => {
-                    active_effect_coroutine(player.handle(), player.body.position, params);
+                    instantiate_active_effect(player.handle(), player.body.position, params);
                 }
                 AnyEffectParams::Passive(params) => {
                     player.add_passive_effect(Some(id), params);
@@ -58,8 +208,18 @@ impl EquippedItem {
 
         let sprite_animation = params.animation.map(AnimationPlayer::new);
 
+        let abilities = params
+            .abilities
+            .into_iter()
+            .map(EquippedAbility::new)
+            .collect();
+
         EquippedItem {
             id: id.to_string(),
+            slot: params.slot,
+            stat_modifiers: params.stat_modifiers,
+            abilities,
+            is_animation_synced: params.is_animation_synced,
             mount_offset: params.mount_offset,
             sprite_animation,
             duration: params.duration,
@@ -70,12 +230,27 @@ impl EquippedItem {
 
     pub fn update(&mut self, dt: f32) {
         if let Some(sprite) = &mut self.sprite_animation {
-            sprite.update();
+            sprite.update(dt);
         }
 
         self.duration_timer += dt;
     }
 
+    /// This will make the item's sprite track the animation of the player, if the item is
+    /// animation synced and has an animation with the same id as the one currently played by the
+    /// player.
+    pub fn sync_animation(&mut self, animation_id: Symbol, frame: u32) {
+        if !self.is_animation_synced {
+            return;
+        }
+
+        if let Some(sprite) = &mut self.sprite_animation {
+            if let Some(frames) = sprite.set_animation(animation_id).map(|a| a.frames) {
+                sprite.set_frame((frame % frames.max(1)) as usize);
+            }
+        }
+    }
+
     pub fn draw(&self, position: Vec2, rotation: f32, flip_x: bool, flip_y: bool) {
         if let Some(sprite) = &self.sprite_animation {
             let size = sprite.get_size();
@@ -0,0 +1,140 @@
+//! Loot tables decide which items are spawned by item spawners that reference them, and how
+//! often. The chance of an entry being picked is its weight, multiplied by the weight of the
+//! rarity tier of its item, so rarity can be tuned for a whole table without touching each entry.
+//! Loot tables are defined globally, in the `loot_tables` resource file, and can be replaced on a
+//! per-map basis, by adding a `loot_table_overrides` property to the map, mapping the ids of
+//! tables to the ids of the tables that should replace them.
+
+use std::collections::HashMap;
+
+use macroquad::rand;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{json::GenericParam, map::Map, Resources};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemRarity {
+    Common,
+    Uncommon,
+    Rare,
+    Epic,
+    Legendary,
+}
+
+impl ItemRarity {
+    /// The weight used for rarity tiers that are not specified in a loot table
+    pub fn default_weight(&self) -> f32 {
+        match self {
+            Self::Common => 100.0,
+            Self::Uncommon => 50.0,
+            Self::Rare => 20.0,
+            Self::Epic => 8.0,
+            Self::Legendary => 2.0,
+        }
+    }
+
+    pub fn is_common(&self) -> bool {
+        *self == Self::Common
+    }
+}
+
+impl Default for ItemRarity {
+    fn default() -> Self {
+        Self::Common
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LootTableEntry {
+    #[serde(rename = "item")]
+    pub item_id: String,
+    #[serde(default = "LootTableEntry::default_weight")]
+    pub weight: f32,
+}
+
+impl LootTableEntry {
+    pub fn default_weight() -> f32 {
+        1.0
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LootTable {
+    pub id: String,
+    /// This can specify the weights of rarity tiers, overriding the default weight of a tier
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub rarity_weights: HashMap<ItemRarity, f32>,
+    pub entries: Vec<LootTableEntry>,
+}
+
+impl LootTable {
+    /// The id of the map property that holds loot table overrides
+    pub const OVERRIDES_MAP_PROPERTY: &'static str = "loot_table_overrides";
+
+    pub fn get_rarity_weight(&self, rarity: ItemRarity) -> f32 {
+        self.rarity_weights
+            .get(&rarity)
+            .copied()
+            .unwrap_or_else(|| rarity.default_weight())
+    }
+
+    /// Returns the ids of the items in the table, along with their weights, adjusted for rarity.
+    /// Entries with an item id that is not found in `Resources` will be excluded.
+    pub fn get_weighted_items(&self, resources: &Resources) -> Vec<(String, f32)> {
+        self.entries
+            .iter()
+            .filter_map(|entry| {
+                if let Some(params) = resources.items.get(&entry.item_id) {
+                    let weight = entry.weight * self.get_rarity_weight(params.rarity);
+                    Some((entry.item_id.clone(), weight))
+                } else {
+                    println!(
+                        "WARNING: Invalid item id '{}' in loot table '{}'",
+                        &entry.item_id, &self.id
+                    );
+                    None
+                }
+            })
+            .filter(|(_, weight)| *weight > 0.0)
+            .collect()
+    }
+
+    /// This will pick the id of an item in the table at random, using the weights returned by
+    /// `get_weighted_items`
+    pub fn pick_item(&self, resources: &Resources) -> Option<String> {
+        let items = self.get_weighted_items(resources);
+
+        let total = items.iter().map(|(_, weight)| *weight).sum::<f32>();
+        let mut value = rand::gen_range(0.0, total);
+
+        for (id, weight) in &items {
+            if value < *weight {
+                return Some(id.clone());
+            }
+
+            value -= weight;
+        }
+
+        items.last().map(|(id, _)| id.clone())
+    }
+
+    /// Returns the loot table overrides of a map, mapping ids of loot tables to the ids of the
+    /// loot tables that should be used in their place.
+    pub fn get_overrides(map: &Map) -> HashMap<String, String> {
+        let mut res = HashMap::new();
+
+        if let Some(GenericParam::HashMap(overrides)) =
+            map.properties.get(Self::OVERRIDES_MAP_PROPERTY)
+        {
+            for (id, param) in overrides {
+                if let GenericParam::String(override_id) = param {
+                    res.insert(id.clone(), override_id.clone());
+                }
+            }
+        }
+
+        res
+    }
+}
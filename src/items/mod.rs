@@ -4,7 +4,7 @@
 use macroquad::{
     experimental::{
         collections::storage,
-        scene::{HandleUntyped, Node, RefMut},
+        scene::{self, HandleUntyped, Node, RefMut},
     },
     prelude::*,
 };
@@ -14,18 +14,35 @@ use serde::{Deserialize, Serialize};
 use crate::{
     capabilities::{NetworkReplicate, PhysicsObject},
     components::{PhysicsBody, Sprite, SpriteParams},
-    json, GameWorld,
+    console, formaterr,
+    game::SIMULATION_DELTA_TIME,
+    json,
+    math::IsZero,
+    physics::CollisionLayers,
+    GameCamera, GameWorld, Player, Resources,
 };
 
 mod weapons;
-pub use weapons::{Weapon, WeaponAnimationParams, WeaponParams};
+pub use weapons::{
+    Weapon, WeaponAnimationParams, WeaponChargeParams, WeaponDurabilityParams, WeaponParams,
+    WeaponSkinParams, WeaponSlot, WeaponSpreadParams,
+};
 
 mod equipped;
-pub use equipped::{EquippedItem, EquippedItemParams};
+pub use equipped::{
+    EquipmentAbility, EquipmentSlot, EquippedAbility, EquippedItem, EquippedItemParams,
+    PlayerStatModifiers,
+};
 
 mod sproinger;
 pub use sproinger::Sproinger;
 
+mod spawner;
+pub use spawner::{ItemSpawnerParams, ItemSpawners};
+
+mod loot;
+pub use loot::{ItemRarity, LootTable, LootTableEntry};
+
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ItemKind {
@@ -57,37 +74,110 @@ pub struct ItemParams {
     pub sprite: SpriteParams,
     #[serde(with = "json::uvec2_def")]
     pub collider_size: UVec2,
+    /// The rarity tier of the item, used to weigh it in loot tables
+    #[serde(default, skip_serializing_if = "ItemRarity::is_common")]
+    pub rarity: ItemRarity,
+    /// When a player is standing over several items, the item with the highest priority will be
+    /// picked up. Of items with the same priority, the one closest to the player is picked up.
+    #[serde(default, skip_serializing_if = "i32::is_zero")]
+    pub pickup_priority: i32,
+    /// If this is specified, the item will be pulled towards the closest player within this
+    /// radius. This should be used for small pickups.
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    pub magnet_radius: f32,
+    /// The mass of the item. When the item is thrown, the damage dealt on impact is its mass,
+    /// multiplied by its speed.
+    #[serde(default = "ItemParams::default_mass")]
+    pub mass: f32,
+    /// The fraction of the item's velocity that is retained, when it bounces off of a tile
+    #[serde(default = "ItemParams::default_bouncyness")]
+    pub bouncyness: f32,
+    /// The factor of gravity that pushes the item up, when it is in water. If this is above one,
+    /// the item will float.
+    #[serde(default = "ItemParams::default_buoyancy")]
+    pub buoyancy: f32,
+    /// The layers that the item interacts with, when it is dropped or thrown. If this does not
+    /// contain `players`, a thrown item will not hit players.
+    #[serde(default, skip_serializing_if = "CollisionLayers::is_all")]
+    pub collision_mask: CollisionLayers,
     #[serde(default)]
     pub is_network_ready: bool,
+    /// The path of a script that implements callbacks for the item, relative to the assets
+    /// directory. See the `scripting` module for the callbacks and the functions available.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<String>,
+}
+
+impl ItemParams {
+    pub fn default_mass() -> f32 {
+        1.0
+    }
+
+    pub fn default_bouncyness() -> f32 {
+        0.3
+    }
+
+    pub fn default_buoyancy() -> f32 {
+        2.0
+    }
 }
 
 pub struct Item {
     pub id: String,
     pub kind: ItemKind,
     pub body: PhysicsBody,
+    pub pickup_priority: i32,
+    magnet_radius: f32,
+    mass: f32,
+    /// This holds the id of the player that threw the item, while it is flying fast enough to
+    /// deal damage on impact
+    pub thrown_by: Option<u8>,
+    /// The id of the skin of a weapon that was dropped, so that it is kept when it is picked up
+    pub skin_id: Option<String>,
     sprite: Sprite,
 }
 
 impl Item {
+    /// The horizontal acceleration applied to an item, towards a player, by magnetism
+    const MAGNET_FORCE: f32 = 900.0;
+    /// The maximum horizontal speed of an item that is pulled by magnetism
+    const MAGNET_MAX_SPEED: f32 = 250.0;
+    /// The minimum speed of a thrown item for it to deal damage on impact
+    const MIN_IMPACT_SPEED: f32 = 250.0;
+    /// The damage dealt on impact is the item's mass, multiplied by its speed and this factor
+    const IMPACT_DAMAGE_FACTOR: f32 = 0.05;
+    /// The distance above a player that items given with the `give` console command are spawned
+    const CONSOLE_SPAWN_OFFSET: f32 = 32.0;
     pub fn new(position: Vec2, params: ItemParams) -> Self {
         let mut world = storage::get_mut::<GameWorld>();
 
-        let body = PhysicsBody::new(
+        let mut body = PhysicsBody::new(
             &mut world.collision_world,
             position,
             0.0,
             params.collider_size.as_f32(),
-            true,
+            false,
             true,
             None,
         );
 
+        body.bouncyness = params.bouncyness;
+        body.mass = params.mass;
+        body.buoyancy = params.buoyancy;
+        body.collision_layer = CollisionLayers::ITEMS;
+        body.collision_mask = params.collision_mask;
+
         let sprite = Sprite::new(params.sprite);
 
         Item {
             id: params.id,
             kind: params.kind,
             body,
+            pickup_priority: params.pickup_priority,
+            magnet_radius: params.magnet_radius,
+            mass: params.mass,
+            thrown_by: None,
+            skin_id: None,
             sprite,
         }
     }
@@ -122,7 +212,72 @@ impl Item {
         }
     }
 
+    fn update_magnetism(&mut self, dt: f32) {
+        let center = self.body.position + self.body.size / 2.0;
+
+        let target = scene::find_nodes_by_type::<Player>()
+            .filter(|player| !player.is_dead)
+            .map(|player| player.body.position + player.body.size / 2.0)
+            .filter(|position| position.distance(center) <= self.magnet_radius)
+            .min_by(|a, b| {
+                a.distance(center)
+                    .partial_cmp(&b.distance(center))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+
+        if let Some(target) = target {
+            let direction = (target.x - center.x).signum();
+
+            self.body.velocity.x = (self.body.velocity.x + direction * Self::MAGNET_FORCE * dt)
+                .clamp(-Self::MAGNET_MAX_SPEED, Self::MAGNET_MAX_SPEED);
+        }
+    }
+
+    /// This will damage the first player, other than the thrower, that a thrown item hits, if it
+    /// is flying fast enough
+    fn update_impact(&mut self) {
+        if let Some(thrower_id) = self.thrown_by {
+            let speed = self.body.velocity.length();
+
+            if speed < Self::MIN_IMPACT_SPEED {
+                self.thrown_by = None;
+                return;
+            }
+
+            let collider = self.body.get_collider_rect();
+
+            let hit = scene::find_nodes_by_type::<Player>()
+                .find(|player| {
+                    player.id != thrower_id
+                        && !player.is_dead
+                        && self.body.can_collide_with(&player.body)
+                        && collider.overlaps(&player.get_collider_rect())
+                })
+                .map(|player| player.handle());
+
+            if let Some(handle) = hit {
+                let thrower = scene::find_nodes_by_type::<Player>()
+                    .find(|player| player.id == thrower_id)
+                    .map(|player| player.handle());
+
+                let is_from_right = self.body.velocity.x < 0.0;
+                let damage = self.mass * speed * Self::IMPACT_DAMAGE_FACTOR;
+
+                Player::on_receive_damage(handle, is_from_right, thrower, damage);
+
+                self.body.velocity.x *= -self.body.bouncyness;
+                self.thrown_by = None;
+            }
+        }
+    }
+
     fn network_update(mut node: RefMut<Self>) {
+        if node.magnet_radius > 0.0 {
+            node.update_magnetism(SIMULATION_DELTA_TIME);
+        }
+
+        node.update_impact();
+
         node.body.update();
     }
 
@@ -143,13 +298,45 @@ impl Node for Item {
     }
 
     fn draw(node: RefMut<Self>) {
-        node.sprite
-            .draw(node.body.position, node.body.rotation, false, false);
+        let position = node.body.get_render_position();
+
+        if !GameCamera::is_in_view(&node.sprite.get_rect(position)) {
+            return;
+        }
+
+        node.sprite.draw(position, node.body.rotation, false, false);
 
         #[cfg(debug_assertions)]
-        node.sprite.debug_draw(node.body.position);
+        node.sprite.debug_draw(position);
 
         #[cfg(debug_assertions)]
         node.body.debug_draw();
     }
 }
+
+pub fn register_console_commands() {
+    console::register("give", |args| {
+        let (item_id, player_id) = match args {
+            [item_id] => (*item_id, Ok(0)),
+            [item_id, player_id] => (*item_id, player_id.parse::<u8>()),
+            _ => return Err(formaterr!("Usage: give <item_id> [player_id]")),
+        };
+
+        let player_id = player_id.map_err(|_| formaterr!("Usage: give <item_id> [player_id]"))?;
+
+        let params = storage::get::<Resources>()
+            .items
+            .get(item_id)
+            .cloned()
+            .ok_or_else(|| formaterr!("Invalid item id '{}'", item_id))?;
+
+        let position = scene::find_nodes_by_type::<Player>()
+            .find(|player| player.id == player_id)
+            .map(|player| player.body.position - vec2(0.0, Item::CONSOLE_SPAWN_OFFSET))
+            .ok_or_else(|| formaterr!("Invalid player id '{}'", player_id))?;
+
+        scene::add_node(Item::new(position, params));
+
+        Ok(format!("Spawned '{}' at player {}", item_id, player_id + 1))
+    });
+}
@@ -0,0 +1,316 @@
+//! Item spawners are item objects, placed in a map, that spawn items at their position.
+//! The configuration of a spawner is stored in the properties of its map object, so that it can
+//! be edited in the map editor. An item object without any spawner properties will spawn the item
+//! specified by the object's id once, at the start of a match.
+//! A spawner can also reference a `LootTable`, in which case the items of the table will be used
+//! in place of the spawner's own items.
+
+use std::collections::HashMap;
+
+use macroquad::{
+    experimental::{
+        collections::storage,
+        scene::{Handle, HandleUntyped, Node, RefMut},
+    },
+    prelude::*,
+};
+
+use crate::{
+    capabilities::NetworkReplicate,
+    game::SIMULATION_DELTA_TIME,
+    json::GenericParam,
+    map::{MapObject, MapProperty},
+    Item, Resources,
+};
+
+#[derive(Debug, Clone)]
+pub struct ItemSpawnerParams {
+    /// The ids of the items that can be spawned, along with their weights. The chance of an item
+    /// being spawned is its weight, divided by the sum of all weights.
+    pub items: Vec<(String, f32)>,
+    /// This can specify the id of a loot table that will be used instead of `items`
+    pub loot_table: Option<String>,
+    /// The delay before the first item is spawned
+    pub initial_delay: f32,
+    /// The interval between an item being spawned, or removed, and a new item being spawned.
+    /// If this is `None`, only one item will be spawned.
+    pub respawn_interval: Option<f32>,
+    /// The maximum amount of items, spawned by the spawner, that can exist at the same time
+    pub max_items: u32,
+}
+
+impl ItemSpawnerParams {
+    pub const ITEMS_PROPERTY: &'static str = "spawner_items";
+    pub const LOOT_TABLE_PROPERTY: &'static str = "spawner_loot_table";
+    pub const INITIAL_DELAY_PROPERTY: &'static str = "spawner_initial_delay";
+    pub const RESPAWN_INTERVAL_PROPERTY: &'static str = "spawner_respawn_interval";
+    pub const MAX_ITEMS_PROPERTY: &'static str = "spawner_max_items";
+
+    const DEFAULT_MAX_ITEMS: u32 = 1;
+
+    /// This reads the spawner configuration from the properties of a map object
+    pub fn from_object(object: &MapObject) -> Self {
+        let mut items = object
+            .properties
+            .get(Self::ITEMS_PROPERTY)
+            .and_then(|param| {
+                if let GenericParam::HashMap(weights) = param {
+                    let items = weights
+                        .iter()
+                        .filter_map(|(id, weight)| {
+                            weight.to_f32().map(|weight| (id.clone(), weight))
+                        })
+                        .filter(|(_, weight)| *weight > 0.0)
+                        .collect::<Vec<_>>();
+
+                    Some(items)
+                } else {
+                    None
+                }
+            })
+            .filter(|items| !items.is_empty())
+            .unwrap_or_else(|| vec![(object.id.clone(), 1.0)]);
+
+        // Sort by id, so that spawns are not affected by the iteration order of the map
+        items.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let loot_table = object
+            .properties
+            .get(Self::LOOT_TABLE_PROPERTY)
+            .and_then(|param| {
+                if let GenericParam::String(id) = param {
+                    Some(id.clone())
+                } else {
+                    None
+                }
+            })
+            .filter(|id| !id.is_empty());
+
+        let initial_delay = object
+            .properties
+            .get(Self::INITIAL_DELAY_PROPERTY)
+            .and_then(GenericParam::to_f32)
+            .unwrap_or(0.0);
+
+        let respawn_interval = object
+            .properties
+            .get(Self::RESPAWN_INTERVAL_PROPERTY)
+            .and_then(GenericParam::to_f32);
+
+        let max_items = object
+            .properties
+            .get(Self::MAX_ITEMS_PROPERTY)
+            .and_then(GenericParam::to_f32)
+            .map(|value| value.max(1.0) as u32)
+            .unwrap_or(Self::DEFAULT_MAX_ITEMS);
+
+        ItemSpawnerParams {
+            items,
+            loot_table,
+            initial_delay,
+            respawn_interval,
+            max_items,
+        }
+    }
+
+    /// This writes the spawner configuration to a map of object properties. Values that are
+    /// equal to the defaults will be removed from the properties.
+    pub fn to_properties(&self, object_id: &str, properties: &mut HashMap<String, MapProperty>) {
+        let is_default_items = self.items.len() == 1 && self.items[0].0 == object_id;
+
+        if self.items.is_empty() || is_default_items {
+            properties.remove(Self::ITEMS_PROPERTY);
+        } else {
+            let weights = self
+                .items
+                .iter()
+                .map(|(id, weight)| (id.clone(), GenericParam::Float(*weight)))
+                .collect();
+
+            properties.insert(
+                Self::ITEMS_PROPERTY.to_string(),
+                GenericParam::HashMap(weights),
+            );
+        }
+
+        if let Some(loot_table) = &self.loot_table {
+            properties.insert(
+                Self::LOOT_TABLE_PROPERTY.to_string(),
+                GenericParam::String(loot_table.clone()),
+            );
+        } else {
+            properties.remove(Self::LOOT_TABLE_PROPERTY);
+        }
+
+        if self.initial_delay > 0.0 {
+            properties.insert(
+                Self::INITIAL_DELAY_PROPERTY.to_string(),
+                GenericParam::Float(self.initial_delay),
+            );
+        } else {
+            properties.remove(Self::INITIAL_DELAY_PROPERTY);
+        }
+
+        if let Some(respawn_interval) = self.respawn_interval {
+            properties.insert(
+                Self::RESPAWN_INTERVAL_PROPERTY.to_string(),
+                GenericParam::Float(respawn_interval),
+            );
+        } else {
+            properties.remove(Self::RESPAWN_INTERVAL_PROPERTY);
+        }
+
+        if self.max_items != Self::DEFAULT_MAX_ITEMS {
+            properties.insert(
+                Self::MAX_ITEMS_PROPERTY.to_string(),
+                GenericParam::UInt(self.max_items),
+            );
+        } else {
+            properties.remove(Self::MAX_ITEMS_PROPERTY);
+        }
+    }
+
+    /// This will pick an item id at random, using the weights of the items
+    fn pick_item(&self) -> Option<&str> {
+        let total = self.items.iter().map(|(_, weight)| *weight).sum::<f32>();
+        let mut value = rand::gen_range(0.0, total);
+
+        for (id, weight) in &self.items {
+            if value < *weight {
+                return Some(id);
+            }
+
+            value -= weight;
+        }
+
+        self.items.last().map(|(id, _)| id.as_str())
+    }
+}
+
+struct ItemSpawner {
+    position: Vec2,
+    params: ItemSpawnerParams,
+    timer: f32,
+    spawn_cnt: u32,
+    items: Vec<Handle<Item>>,
+}
+
+#[derive(Default)]
+pub struct ItemSpawners {
+    spawners: Vec<ItemSpawner>,
+    /// The loot table overrides of the current map
+    loot_table_overrides: HashMap<String, String>,
+}
+
+impl ItemSpawners {
+    pub fn new(loot_table_overrides: HashMap<String, String>) -> Self {
+        ItemSpawners {
+            spawners: Vec::new(),
+            loot_table_overrides,
+        }
+    }
+
+    /// This adds a spawner. If the spawner references a loot table, its items will be replaced by
+    /// the items of the table, or of the table that overrides it on the current map.
+    /// Any item ids that are not in `Resources`, or are not network ready if the game is not a
+    /// local game, will be removed and, if none are left, no spawner will be added.
+    pub fn add(&mut self, position: Vec2, mut params: ItemSpawnerParams, is_local_game: bool) {
+        {
+            let resources = storage::get::<Resources>();
+
+            if let Some(loot_table_id) = &params.loot_table {
+                let loot_table_id = self
+                    .loot_table_overrides
+                    .get(loot_table_id)
+                    .unwrap_or(loot_table_id);
+
+                if let Some(loot_table) = resources.loot_tables.get(loot_table_id) {
+                    params.items = loot_table.get_weighted_items(&resources);
+                } else {
+                    println!("WARNING: Invalid loot table id '{}'", loot_table_id);
+                }
+            }
+
+            params.items.retain(|(id, _)| {
+                if let Some(item) = resources.items.get(id) {
+                    item.is_network_ready || is_local_game
+                } else {
+                    println!("WARNING: Invalid item id '{}'", id);
+                    false
+                }
+            });
+        }
+
+        if !params.items.is_empty() {
+            self.spawners.push(ItemSpawner {
+                position,
+                params,
+                timer: 0.0,
+                spawn_cnt: 0,
+                items: Vec::new(),
+            });
+        }
+    }
+
+    fn network_update(mut node: RefMut<Self>) {
+        let dt = SIMULATION_DELTA_TIME;
+
+        for spawner in &mut node.spawners {
+            // Items that have been picked up, or otherwise removed, no longer count
+            spawner
+                .items
+                .retain(|item| scene::try_get_node(*item).is_some());
+
+            if spawner.items.len() as u32 >= spawner.params.max_items {
+                spawner.timer = 0.0;
+                continue;
+            }
+
+            let interval = if spawner.spawn_cnt == 0 {
+                spawner.params.initial_delay
+            } else if let Some(respawn_interval) = spawner.params.respawn_interval {
+                respawn_interval
+            } else {
+                continue;
+            };
+
+            spawner.timer += dt;
+
+            if spawner.timer >= interval {
+                spawner.timer = 0.0;
+
+                let params = spawner.params.pick_item().and_then(|id| {
+                    let resources = storage::get::<Resources>();
+                    resources.items.get(id).cloned()
+                });
+
+                if let Some(params) = params {
+                    let mut item = Item::new(spawner.position, params);
+                    item.body.is_bobbing = true;
+
+                    let item = scene::add_node(item);
+
+                    spawner.items.push(item);
+                    spawner.spawn_cnt += 1;
+                }
+            }
+        }
+    }
+
+    fn network_capabilities() -> NetworkReplicate {
+        fn network_update(handle: HandleUntyped) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<ItemSpawners>();
+            ItemSpawners::network_update(node);
+        }
+
+        NetworkReplicate { network_update }
+    }
+}
+
+impl Node for ItemSpawners {
+    fn ready(mut node: RefMut<Self>) {
+        node.provides(Self::network_capabilities());
+    }
+}
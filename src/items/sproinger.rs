@@ -1,10 +1,8 @@
 use macroquad::{
-    audio::play_sound_once,
     color,
     experimental::{
         animation::{AnimatedSprite, Animation},
         collections::storage,
-        coroutines::{start_coroutine, wait_seconds, Coroutine},
         scene::{self, HandleUntyped, RefMut},
         state_machine::{State, StateMachine},
     },
@@ -12,7 +10,9 @@ use macroquad::{
 };
 
 use crate::{
+    audio::play_sound_at,
     capabilities::{NetworkReplicate, PhysicsObject},
+    game::SIMULATION_DELTA_TIME,
     Resources,
 };
 
@@ -22,6 +22,8 @@ pub struct Sproinger {
     sprite: AnimatedSprite,
     pos: Vec2,
     state_machine: StateMachine<Self>,
+    /// The time that has passed since the sproinger was triggered, while it is jumping
+    jump_timer: f32,
 }
 
 impl Sproinger {
@@ -31,6 +33,11 @@ impl Sproinger {
     pub const TRIGGER_HEIGHT: f32 = 8.0;
     pub const FORCE: f32 = 1100.0;
 
+    /// The duration of each frame of the jump animation
+    const JUMP_FRAME_DURATION: f32 = 0.08;
+    /// The time after the jump animation, before the sproinger can be triggered again
+    const JUMP_COOLDOWN: f32 = 0.5;
+
     const ST_NORMAL: usize = 0;
     const ST_JUMP: usize = 1;
 
@@ -64,17 +71,13 @@ impl Sproinger {
         let mut state_machine = StateMachine::new();
 
         state_machine.add_state(Self::ST_NORMAL, State::new().update(Self::update_normal));
-        state_machine.add_state(
-            Self::ST_JUMP,
-            State::new()
-                .update(Self::update_jump)
-                .coroutine(Self::jump_coroutine),
-        );
+        state_machine.add_state(Self::ST_JUMP, State::new().update(Self::update_jump));
 
         scene::add_node(Sproinger {
             sprite,
             pos,
             state_machine,
+            jump_timer: 0.0,
         })
         .untyped()
     }
@@ -92,46 +95,34 @@ impl Sproinger {
             let intersect = sproinger_rect.intersect(object_collider);
             if intersect.is_some() {
                 let resources = storage::get_mut::<Resources>();
-                let jump_sound = resources.sounds["jump"];
+                let jump_sound = &resources.sounds["jump"];
 
-                play_sound_once(jump_sound);
+                play_sound_at(jump_sound, node.pos);
 
                 physics_object.set_speed_y(-Self::FORCE);
 
+                node.sprite.set_animation(2);
+                node.sprite.set_frame(0);
+
+                node.jump_timer = 0.0;
                 node.state_machine.set_state(Self::ST_JUMP);
             }
         }
     }
 
-    pub fn update_jump(_node: &mut RefMut<Self>, _dt: f32) {}
-
-    fn jump_coroutine(node: &mut RefMut<Self>) -> Coroutine {
-        let node_handle = node.handle();
+    /// This plays the jump animation and returns to the normal state when the cooldown has
+    /// passed, counted in ticks
+    pub fn update_jump(node: &mut RefMut<Self>, _dt: f32) {
+        node.jump_timer += SIMULATION_DELTA_TIME;
 
-        let coroutine = async move {
-            {
-                let mut node = scene::get_node(node_handle);
-                node.sprite.set_animation(2);
-            }
-            for i in 0..2 {
-                {
-                    let mut node = scene::get_node(node_handle);
-                    if node.sprite.current_animation() != 2 {
-                        return;
-                    }
-                    node.sprite.set_frame(i);
-                }
-                wait_seconds(0.08).await;
-            }
-            wait_seconds(0.5).await;
+        let animation_duration = Self::JUMP_FRAME_DURATION * 2.0;
 
-            {
-                let mut node = scene::get_node(node_handle);
-                node.sprite.set_animation(0);
-                node.state_machine.set_state(Self::ST_NORMAL);
-            }
-        };
-        start_coroutine(coroutine)
+        if node.jump_timer >= animation_duration + Self::JUMP_COOLDOWN {
+            node.sprite.set_animation(0);
+            node.state_machine.set_state(Self::ST_NORMAL);
+        } else if node.jump_timer >= Self::JUMP_FRAME_DURATION {
+            node.sprite.set_frame(1);
+        }
     }
 }
 
@@ -359,7 +359,7 @@ impl EruptingVolcano {
 
         // Gravity law: vₜ² = vᵢ² - 2gy
         // We take the negative solution, since we go upwards.
-        let y_speed = -(2. * PhysicsBody::GRAVITY * map_height).sqrt();
+        let y_speed = -(2. * PhysicsBody::get_gravity() * map_height).sqrt();
 
         let x_distance = if item_x >= map_width / 2. {
             map_width - item_x
@@ -369,7 +369,7 @@ impl EruptingVolcano {
 
         // Gravity law: t = (vₜ - vᵢ) / g
         // The 2* factor is due to going up, then down.
-        let time_on_map: f32 = (-y_speed / PhysicsBody::GRAVITY) * 2.;
+        let time_on_map: f32 = (-y_speed / PhysicsBody::get_gravity()) * 2.;
         let max_x_speed = x_distance / time_on_map;
 
         let x_speed = gen_range(0., max_x_speed);
@@ -1,10 +1,9 @@
+use std::f32::consts::PI;
+
 use macroquad::{
-    audio::play_sound_once,
-    audio::Sound,
     experimental::{
         collections::storage,
-        coroutines::{start_coroutine, wait_seconds, Coroutine},
-        scene::Handle,
+        scene::{self, RefMut},
     },
     prelude::*,
 };
@@ -13,10 +12,17 @@ use serde::{Deserialize, Serialize};
 
 use crate::components::{ParticleController, ParticleControllerParams};
 use crate::{
-    components::{AnimationParams, AnimationPlayer},
-    effects::{active_effect_coroutine, ActiveEffectParams},
+    audio::{play_sound_at, play_sound_effect, AudioBus, SoundGroup},
+    components::{AnimationParams, AnimationPlayer, PhysicsBody},
+    cosmetic_rand,
+    cvars::{self, CVarFlags, CVarValue},
+    effects::{instantiate_active_effect, ActiveEffectKind, ActiveEffectParams},
+    game::GameEvent,
     json::{self, OneOrMany},
-    Player, Resources,
+    math::IsZero,
+    player::DamageParams,
+    symbol::Symbol,
+    GameCamera, Lighting, ParticleEmitters, Player, Resources,
 };
 
 /// This holds the parameters for the `AnimationPlayer` components of an equipped `Weapon`.
@@ -41,13 +47,208 @@ pub struct WeaponAnimationParams {
     pub effect: Option<AnimationParams>,
 }
 
+/// This holds the parameters for weapons that are charged by holding the attack button and that
+/// attack when it is released, like bows and harpoons.
+/// The charge is a value between `0.0` and `1.0`, that is used to interpolate between the minimum
+/// and maximum values. If the weapon's animation has an animation with the id `"charge"`, this
+/// will be played while the weapon is charging.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WeaponChargeParams {
+    /// The time it takes to reach full charge
+    pub duration: f32,
+    /// The minimum charge required for the weapon to attack when the attack button is released.
+    /// If the button is released before this is reached, the charge will be cancelled.
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    pub min_charge: f32,
+    /// The damage dealt by the weapon's effects at no charge. If this is not specified, the
+    /// damage of the weapon, or of the individual effects, will be used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_damage: Option<f32>,
+    /// The damage dealt by the weapon's effects at full charge. If this is not specified, the
+    /// damage of the weapon, or of the individual effects, will be used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_damage: Option<f32>,
+    /// The factor applied to the speed and range of projectiles, and to the velocity of triggered
+    /// effects, at no charge
+    #[serde(default = "WeaponChargeParams::default_velocity_factor")]
+    pub min_velocity_factor: f32,
+    /// The factor applied to the speed and range of projectiles, and to the velocity of triggered
+    /// effects, at full charge
+    #[serde(default = "WeaponChargeParams::default_velocity_factor")]
+    pub max_velocity_factor: f32,
+    /// If this is `true`, the time the weapon has been charged will be subtracted from the fuse
+    /// (`timed_trigger`) of any triggered effects, like when cooking a grenade. If a fuse runs out
+    /// while the weapon is charging, the weapon will attack immediately.
+    #[serde(default, skip_serializing_if = "json::is_false")]
+    pub is_cookable: bool,
+    /// If this is `true`, the trajectory of any triggered effects will be drawn while the weapon
+    /// is charging
+    #[serde(default, skip_serializing_if = "json::is_false")]
+    pub should_preview_arc: bool,
+    /// Particle effects that will be active while the weapon is charging
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub particles: Vec<ParticleControllerParams>,
+    /// This can specify an id of a sound effect that is played when the weapon starts charging
+    #[serde(
+        default,
+        rename = "sound_effect",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub sound_effect_id: Option<String>,
+}
+
+impl WeaponChargeParams {
+    const DEFAULT_VELOCITY_FACTOR: f32 = 1.0;
+
+    pub fn default_velocity_factor() -> f32 {
+        Self::DEFAULT_VELOCITY_FACTOR
+    }
+
+    fn get_velocity_factor(&self, charge: f32) -> f32 {
+        self.min_velocity_factor + (self.max_velocity_factor - self.min_velocity_factor) * charge
+    }
+
+    /// This will apply the charge to the parameters of an effect, before it is instantiated.
+    /// `charge_time` is the time the weapon was charged, which is used for cooking.
+    fn apply(&self, charge: f32, charge_time: f32, params: &mut ActiveEffectParams) {
+        let min_damage = self.min_damage.unwrap_or(params.damage);
+        let max_damage = self.max_damage.unwrap_or(params.damage);
+
+        params.damage = min_damage + (max_damage - min_damage) * charge;
+
+        let factor = self.get_velocity_factor(charge);
+
+        match &mut *params.kind {
+            ActiveEffectKind::Projectile { speed, range, .. } => {
+                *speed *= factor;
+                *range *= factor;
+            }
+            ActiveEffectKind::TriggeredEffect { params } => {
+                params.velocity *= factor;
+
+                if self.is_cookable {
+                    if let Some(timed_trigger) = &mut params.timed_trigger {
+                        *timed_trigger = (*timed_trigger - charge_time).max(0.0);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// This holds the parameters of a weapon's dynamic spread. Each attack adds spread, which is
+/// added to the spread of the weapon's projectiles, and the added spread recovers over time, when
+/// the weapon is not used. This makes sustained automatic fire less accurate than single shots.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WeaponSpreadParams {
+    /// The spread, in degrees, added per attack
+    pub growth: f32,
+    /// The maximum added spread, in degrees
+    pub max: f32,
+    /// The amount of added spread, in degrees, recovered per second
+    pub recovery: f32,
+    /// The time after an attack, before the added spread starts to recover
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    pub recovery_delay: f32,
+}
+
+/// The hand that a `Weapon` is held in. A player can hold a one-handed weapon in the off hand,
+/// alongside a one-handed weapon in the main hand, and each hand is fired with its own input.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum WeaponSlot {
+    MainHand,
+    OffHand,
+}
+
+impl WeaponSlot {
+    pub const ALL: [Self; 2] = [Self::MainHand, Self::OffHand];
+}
+
+/// A cosmetic variant of a weapon. A skin only changes the appearance of the weapon, while it is
+/// held, sharing all gameplay stats with the base weapon.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WeaponSkinParams {
+    pub id: String,
+    /// This can specify a texture that will replace the texture of the weapon's main animations.
+    /// It must have the same layout as the texture it replaces.
+    #[serde(default, rename = "texture", skip_serializing_if = "Option::is_none")]
+    pub texture_id: Option<String>,
+    /// This can specify a texture that will replace the texture of the weapon's effect
+    /// animations. It must have the same layout as the texture it replaces.
+    #[serde(
+        default,
+        rename = "effect_texture",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub effect_texture_id: Option<String>,
+    /// This can specify a color to blend with the texture color of the weapon's animations
+    #[serde(
+        default,
+        with = "json::color_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub tint: Option<Color>,
+    /// This can specify a color that the colors of the weapon's particle effects will be
+    /// multiplied by
+    #[serde(
+        default,
+        with = "json::color_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub particle_color: Option<Color>,
+}
+
+/// This holds the parameters of a weapon's durability. Durability is lost when the weapon is used
+/// and, optionally, over time while it is held, and the weapon will break when it runs out.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WeaponDurabilityParams {
+    /// The durability of the weapon, when it is picked up
+    pub max: f32,
+    /// The durability lost per attack
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    pub use_cost: f32,
+    /// The durability lost per second, while the weapon is held
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    pub decay_rate: f32,
+    /// The weapon's sprite is tinted towards this color as it loses durability
+    #[serde(
+        default = "WeaponDurabilityParams::default_worn_tint",
+        with = "json::ColorDef"
+    )]
+    pub worn_tint: Color,
+    /// Effects that will be instantiated at the weapon's position, when it breaks
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub break_effects: Vec<ActiveEffectParams>,
+    /// This can specify the id of a particle effect that is spawned when the weapon breaks
+    #[serde(
+        default,
+        rename = "break_particle_effect",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub break_particle_effect_id: Option<String>,
+    /// This can specify the id of a sound effect that is played when the weapon breaks
+    #[serde(
+        default,
+        rename = "break_sound_effect",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub break_sound_effect_id: Option<String>,
+}
+
+impl WeaponDurabilityParams {
+    pub fn default_worn_tint() -> Color {
+        Color::new(0.55, 0.4, 0.3, 1.0)
+    }
+}
+
 /// This holds parameters specific to the `Weapon` variant of `ItemKind`, used to instantiate a
 /// `Weapon` struct instance, when an `Item` of type `Weapon` is picked up.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct WeaponParams {
     /// This specifies the effects to instantiate when the weapon is used to attack. Can be either
     /// a single `ActiveEffectParams` or a vector of `ActiveEffectParams`.
-    #[serde(alias = "effect")]
+    #[serde(alias = "effect", alias = "on_fire")]
     pub effects: OneOrMany<ActiveEffectParams>,
     /// Particle effects that will be activated when using the weapon
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
@@ -59,6 +260,19 @@ pub struct WeaponParams {
         skip_serializing_if = "Option::is_none"
     )]
     pub sound_effect_id: Option<String>,
+    /// This can specify an id of a sound effect that is played when the hitboxes of the weapon's
+    /// attack animation hit another player. Sound effects for hits by projectiles and colliders
+    /// should be added to their `on_hit` effects, with a `play_sound` effect.
+    #[serde(
+        default,
+        rename = "hit_sound_effect",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub hit_sound_effect_id: Option<String>,
+    /// The string key of the verb used for kills with the weapon in the kill feed, like
+    /// `"kill_feed.harpooned"`. If this is not specified, `"kill_feed.killed"` will be used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kill_verb: Option<String>,
     /// This can specify a maximum amount of weapon uses. If no value is specified, the weapon
     /// will have unlimited uses.
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -66,6 +280,36 @@ pub struct WeaponParams {
     /// If this is set to `true` the weapon will be destroyed when it is out of uses
     #[serde(default)]
     pub is_destroyed_on_depletion: bool,
+    /// If this is set to `true` the player will throw the weapon away when it is out of uses
+    #[serde(default, skip_serializing_if = "json::is_false")]
+    pub is_thrown_on_depletion: bool,
+    /// This can specify the amount of attacks that can be made before the weapon must be
+    /// reloaded. If no value is specified, the weapon will never have to be reloaded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub magazine_size: Option<u32>,
+    /// This specifies the time it takes to reload the weapon, once its magazine is empty.
+    /// If the weapon's animation has an animation with the id `"reload"`, this will be played
+    /// while the weapon is reloading.
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    pub reload_duration: f32,
+    /// This can specify an id of a sound effect that is played when the weapon starts reloading
+    #[serde(
+        default,
+        rename = "reload_sound_effect",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub reload_sound_effect_id: Option<String>,
+    /// If this is specified, the weapon will be charged while the attack button is held and it
+    /// will attack when the button is released.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub charge: Option<WeaponChargeParams>,
+    /// If this is specified, the weapon will lose durability and break when it runs out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub durability: Option<WeaponDurabilityParams>,
+    /// If this is set to `true`, the weapon is small enough to be held in the off hand, alongside
+    /// another one-handed weapon in the main hand
+    #[serde(default, skip_serializing_if = "json::is_false")]
+    pub is_one_handed: bool,
     /// This specifies the offset from the `Player` weapon mount
     #[serde(default, with = "json::vec2_def")]
     pub mount_offset: Vec2,
@@ -84,6 +328,34 @@ pub struct WeaponParams {
     /// attack, when the weapon is activated.
     #[serde(default)]
     pub recoil: f32,
+    /// If this is specified, the spread of the weapon's projectiles will grow with each attack
+    /// and recover over time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spread: Option<WeaponSpreadParams>,
+    /// The magnitude of the camera kick, a short camera shake along the direction of the attack,
+    /// caused by each attack
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    pub camera_kick: f32,
+    /// This specifies the amount of damage dealt by each of the weapon's effects. If no value is
+    /// specified, the damage specified in each of the effects' parameters will be used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub damage: Option<f32>,
+    /// This specifies the knockback applied to players that are hit by the weapon, and survive
+    /// the hit, by its melee swings and by each of its effects. If no value is specified, swings
+    /// apply no knockback and the knockback specified in each of the effects' parameters will be
+    /// used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub knockback: Option<f32>,
+    /// This specifies the amount of simulation frames that both the attacker and the victim will
+    /// be frozen for, when an attack with the weapon lands. This is used to make heavy hits feel
+    /// weighty and, as it is counted in simulation frames, it is deterministic.
+    #[serde(default, skip_serializing_if = "u32::is_zero")]
+    pub hitstop: u32,
+    /// Cosmetic variants of the weapon. When the weapon is picked up, a skin preferred by the
+    /// player's character is used, if one is available, or one is picked at random, including the
+    /// base weapon.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skins: Vec<WeaponSkinParams>,
     /// This holds the parameters for the `AnimationPlayer` components that will be used when
     /// the weapon is equipped by a player. It is flattened into this struct, so when defining
     /// weapons in JSON files, the members of `WeaponAnimationParams` will be treated as members
@@ -100,33 +372,143 @@ impl Default for WeaponParams {
             effects: OneOrMany::Many(Vec::new()),
             particles: Vec::new(),
             sound_effect_id: None,
+            hit_sound_effect_id: None,
+            kill_verb: None,
             uses: None,
             is_destroyed_on_depletion: false,
+            is_thrown_on_depletion: false,
+            magazine_size: None,
+            reload_duration: 0.0,
+            reload_sound_effect_id: None,
+            charge: None,
+            durability: None,
+            is_one_handed: false,
             mount_offset: Vec2::ZERO,
             effect_offset: Vec2::ZERO,
             attack_duration: 0.0,
             cooldown: 0.0,
             recoil: 0.0,
+            spread: None,
+            camera_kick: 0.0,
+            damage: None,
+            knockback: None,
+            hitstop: 0,
+            skins: Vec::new(),
             animation: Default::default(),
         }
     }
 }
 
+impl WeaponParams {
+    pub fn get_skin(&self, id: &str) -> Option<&WeaponSkinParams> {
+        self.skins.iter().find(|skin| skin.id == id)
+    }
+
+    /// This will return the id of the first skin in `preferred` that is available for the
+    /// weapon, if any, or the id of a random skin, if not. `None` is returned if the base weapon
+    /// is picked.
+    pub fn pick_skin(&self, preferred: &[String]) -> Option<String> {
+        if let Some(id) = preferred.iter().find(|id| self.get_skin(id).is_some()) {
+            return Some(id.clone());
+        }
+
+        if self.skins.is_empty() {
+            return None;
+        }
+
+        let i = cosmetic_rand::gen_range(0, self.skins.len() + 1);
+        self.skins.get(i).map(|skin| skin.id.clone())
+    }
+
+    /// This will apply the textures, tint and particle color of `skin` to the parameters
+    fn apply_skin(&mut self, skin: &WeaponSkinParams) {
+        let sprite = &mut self.animation.sprite;
+
+        if let Some(texture_id) = &skin.texture_id {
+            sprite.texture_id = texture_id.clone();
+        }
+
+        if skin.tint.is_some() {
+            sprite.tint = skin.tint;
+        }
+
+        if let Some(effect) = &mut self.animation.effect {
+            if let Some(texture_id) = &skin.effect_texture_id {
+                effect.texture_id = texture_id.clone();
+            }
+
+            if skin.tint.is_some() {
+                effect.tint = skin.tint;
+            }
+        }
+
+        if skin.particle_color.is_some() {
+            let charge_particles = self
+                .charge
+                .iter_mut()
+                .flat_map(|charge| charge.particles.iter_mut());
+
+            for particles in self.particles.iter_mut().chain(charge_particles) {
+                particles.color = skin.particle_color;
+            }
+        }
+    }
+}
+
 pub struct Weapon {
     pub id: String,
+    /// The id of the skin used by the weapon, if any
+    pub skin_id: Option<String>,
     pub particles: Vec<ParticleController>,
-    pub sound_effect: Option<Sound>,
+    pub sound_effect: Option<SoundGroup>,
+    hit_sound_effect: Option<SoundGroup>,
     pub effects: Vec<ActiveEffectParams>,
     pub cooldown: f32,
     pub recoil: f32,
+    pub spread: Option<WeaponSpreadParams>,
+    /// The spread, in degrees, that is currently added to the weapon's projectiles
+    pub added_spread: f32,
+    pub camera_kick: f32,
+    pub damage: Option<f32>,
+    pub knockback: Option<f32>,
+    pub hitstop: u32,
     pub attack_duration: f32,
     pub uses: Option<u32>,
     pub sprite_animation: AnimationPlayer,
     pub effect_animation: Option<AnimationPlayer>,
     pub cooldown_timer: f32,
+    pub is_one_handed: bool,
     mount_offset: Vec2,
     pub effect_offset: Vec2,
+    pub magazine_size: Option<u32>,
+    pub magazine: u32,
+    pub reload_duration: f32,
+    /// This holds the time passed since the weapon started reloading, if it is reloading
+    pub reload_timer: Option<f32>,
+    reload_sound_effect: Option<SoundGroup>,
+    pub charge: Option<WeaponChargeParams>,
+    /// This holds the time passed since the weapon started charging, if it is charging
+    pub charge_timer: Option<f32>,
+    charge_particles: Vec<ParticleController>,
+    charge_sound_effect: Option<SoundGroup>,
+    pub durability_params: Option<WeaponDurabilityParams>,
+    /// The remaining durability, if the weapon has durability
+    pub durability: Option<f32>,
+    /// The tint of the weapon's sprite at full durability
+    base_tint: Color,
+    /// This is `true` while a melee attack, using the hitboxes of the attack animation, is active
+    pub is_swinging: bool,
+    /// This holds the ids of all players hit by the current swing, so that each player can only
+    /// be hit once per swing
+    swing_hits: Vec<u8>,
+    /// The time that has passed since the attack animation of the sprite was started, while it is
+    /// playing
+    animation_timer: Option<f32>,
+    /// The time that has passed since the attack animation of the effect was started, while it is
+    /// playing
+    effect_animation_timer: Option<f32>,
     is_destroyed_on_depletion: bool,
+    is_thrown_on_depletion: bool,
     use_cnt: u32,
 }
 
@@ -147,11 +529,67 @@ impl Weapon {
         a: 0.8,
     };
 
+    const HUD_PROGRESS_BAR_WIDTH: f32 = 32.0;
+    const HUD_PROGRESS_BAR_HEIGHT: f32 = 3.0;
+
     const IDLE_ANIMATION_ID: &'static str = "idle";
     const ATTACK_ANIMATION_ID: &'static str = "attack";
+    const RELOAD_ANIMATION_ID: &'static str = "reload";
+    const CHARGE_ANIMATION_ID: &'static str = "charge";
     const ATTACK_EFFECT_ANIMATION_ID: &'static str = "attack_effect";
 
-    pub fn new(id: &str, params: WeaponParams) -> Self {
+    /// The amount of simulated steps, and the interval between each step, of the trajectory drawn
+    /// when previewing the arc of triggered effects
+    const ARC_PREVIEW_STEPS: u32 = 48;
+    const ARC_PREVIEW_STEP_INTERVAL: f32 = 1.0 / 60.0;
+    /// The amount of steps between each dot drawn in the arc preview
+    const ARC_PREVIEW_DOT_INTERVAL: u32 = 4;
+    const ARC_PREVIEW_COLOR: Color = Color {
+        r: 1.0,
+        g: 1.0,
+        b: 1.0,
+        a: 0.6,
+    };
+
+    /// The length, in frames, and frequency of the camera kick caused by attacks
+    const CAMERA_KICK_LENGTH: i32 = 8;
+    const CAMERA_KICK_FREQUENCY: f32 = 1.0;
+
+    /// The velocity applied to both players, away from each other, when two melee swings clash
+    const CLASH_KNOCKBACK_CVAR: &'static str = "weapons.clash_knockback";
+    const DEFAULT_CLASH_KNOCKBACK: f32 = 400.0;
+    const CLASH_PARTICLE_EFFECT_ID: &'static str = "hit";
+
+    const SHELL_CASING_PARTICLE_EFFECT_ID: &'static str = "shell_casing";
+    const DESTROYED_PARTICLE_EFFECT_ID: &'static str = "weapon_debris";
+
+    /// The light emitted when firing a weapon with projectile effects, on maps with darkness
+    const MUZZLE_FLASH_RADIUS: f32 = 64.0;
+    const MUZZLE_FLASH_DURATION: f32 = 0.1;
+    const MUZZLE_FLASH_COLOR: Color = Color {
+        r: 1.0,
+        g: 0.85,
+        b: 0.5,
+        a: 1.0,
+    };
+    const CLASH_SOUND_EFFECT_ID: &'static str = "sword";
+
+    pub fn register_cvars() {
+        cvars::register(
+            Self::CLASH_KNOCKBACK_CVAR,
+            CVarValue::Float(Self::DEFAULT_CLASH_KNOCKBACK),
+            CVarFlags::CHEAT | CVarFlags::REPLICATED,
+            "The knockback applied to both players when their melee attacks clash",
+        );
+    }
+
+    pub fn new(id: &str, mut params: WeaponParams, skin_id: Option<&str>) -> Self {
+        let skin = skin_id.and_then(|id| params.get_skin(id)).cloned();
+
+        if let Some(skin) = &skin {
+            params.apply_skin(skin);
+        }
+
         let particles = params
             .particles
             .into_iter()
@@ -160,10 +598,48 @@ impl Weapon {
 
         let sound_effect = params.sound_effect_id.as_ref().map(|id| {
             let resources = storage::get::<Resources>();
-            resources.sounds[id]
+            resources.sounds[id].clone()
+        });
+
+        let hit_sound_effect = params.hit_sound_effect_id.as_ref().map(|id| {
+            let resources = storage::get::<Resources>();
+            resources.sounds[id].clone()
+        });
+
+        let reload_sound_effect = params.reload_sound_effect_id.as_ref().map(|id| {
+            let resources = storage::get::<Resources>();
+            resources.sounds[id].clone()
         });
 
+        let (charge_particles, charge_sound_effect) = params
+            .charge
+            .as_ref()
+            .map(|charge| {
+                let particles = charge
+                    .particles
+                    .iter()
+                    .cloned()
+                    .map(ParticleController::new)
+                    .collect();
+
+                let sound_effect = charge.sound_effect_id.as_ref().map(|id| {
+                    let resources = storage::get::<Resources>();
+                    resources.sounds[id].clone()
+                });
+
+                (particles, sound_effect)
+            })
+            .unwrap_or_default();
+
+        let magazine = params
+            .magazine_size
+            .map(|size| params.uses.map_or(size, |uses| size.min(uses)))
+            .unwrap_or(0);
+
         let sprite_animation = AnimationPlayer::new(params.animation.sprite);
+        let base_tint = sprite_animation.get_tint();
+
+        let durability = params.durability.as_ref().map(|durability| durability.max);
 
         let effect_animation = params.animation.effect.map(|params| {
             AnimationPlayer::new(AnimationParams {
@@ -174,19 +650,45 @@ impl Weapon {
 
         Weapon {
             id: id.to_string(),
+            skin_id: skin.map(|skin| skin.id),
             particles,
             sound_effect,
+            hit_sound_effect,
             effects: params.effects.into(),
             cooldown: params.cooldown,
             recoil: params.recoil,
+            spread: params.spread,
+            added_spread: 0.0,
+            camera_kick: params.camera_kick,
+            damage: params.damage,
+            knockback: params.knockback,
+            hitstop: params.hitstop,
             attack_duration: params.attack_duration,
             uses: params.uses,
             sprite_animation,
             effect_animation,
             cooldown_timer: params.cooldown,
+            is_one_handed: params.is_one_handed,
             mount_offset: params.mount_offset,
             effect_offset: params.effect_offset,
+            magazine_size: params.magazine_size,
+            magazine,
+            reload_duration: params.reload_duration,
+            reload_timer: None,
+            reload_sound_effect,
+            charge: params.charge,
+            charge_timer: None,
+            charge_particles,
+            charge_sound_effect,
+            durability_params: params.durability,
+            durability,
+            base_tint,
+            is_swinging: false,
+            swing_hits: Vec::new(),
+            animation_timer: None,
+            effect_animation_timer: None,
             is_destroyed_on_depletion: params.is_destroyed_on_depletion,
+            is_thrown_on_depletion: params.is_thrown_on_depletion,
             use_cnt: 0,
         }
     }
@@ -223,13 +725,244 @@ impl Weapon {
         offset
     }
 
+    /// Returns the amount of uses left, if the weapon has limited uses
+    pub fn remaining_uses(&self) -> Option<u32> {
+        self.uses.map(|uses| uses.saturating_sub(self.use_cnt))
+    }
+
+    pub fn is_depleted(&self) -> bool {
+        self.remaining_uses() == Some(0)
+    }
+
+    pub fn is_reloading(&self) -> bool {
+        self.reload_timer.is_some()
+    }
+
+    pub fn start_reload(&mut self) {
+        if self.magazine_size.is_some() && !self.is_reloading() && !self.is_depleted() {
+            self.reload_timer = Some(0.0);
+
+            if let Some(sound_effect) = &self.reload_sound_effect {
+                play_sound_effect(sound_effect, AudioBus::Sfx, 1.0);
+            }
+
+            if self
+                .sprite_animation
+                .set_animation(Self::RELOAD_ANIMATION_ID)
+                .is_some()
+            {
+                self.sprite_animation.play();
+            }
+        }
+    }
+
+    fn finish_reload(&mut self) {
+        if let Some(magazine_size) = self.magazine_size {
+            self.magazine = self
+                .remaining_uses()
+                .map_or(magazine_size, |remaining| magazine_size.min(remaining));
+        }
+
+        self.reload_timer = None;
+
+        if self
+            .sprite_animation
+            .get_animation(Self::RELOAD_ANIMATION_ID)
+            .is_some()
+        {
+            self.sprite_animation.set_animation(Self::IDLE_ANIMATION_ID);
+            self.sprite_animation.play();
+        }
+    }
+
+    /// Returns `true` if the weapon has durability and it has run out
+    pub fn is_broken(&self) -> bool {
+        self.durability
+            .map(|durability| durability <= 0.0)
+            .unwrap_or(false)
+    }
+
+    /// Returns the remaining durability, between `0.0` and `1.0`
+    pub fn get_durability(&self) -> f32 {
+        match (&self.durability_params, self.durability) {
+            (Some(params), Some(durability)) if params.max > 0.0 => {
+                (durability / params.max).clamp(0.0, 1.0)
+            }
+            _ => 1.0,
+        }
+    }
+
+    /// Returns the remaining and the total amount of ammo, if the weapon has limited ammo. This is
+    /// the magazine, for weapons that reload, or the remaining uses, for weapons that do not.
+    pub fn get_ammo(&self) -> Option<(u32, u32)> {
+        if let Some(magazine_size) = self.magazine_size {
+            Some((self.magazine, magazine_size))
+        } else {
+            self.uses
+                .map(|uses| (uses.saturating_sub(self.use_cnt), uses))
+        }
+    }
+
+    fn reduce_durability(&mut self, amount: f32) {
+        if let Some(durability) = &mut self.durability {
+            *durability = (*durability - amount).max(0.0);
+        }
+
+        if let Some(params) = &self.durability_params {
+            let wear = 1.0 - self.get_durability();
+            let tint = Color::new(
+                self.base_tint.r + (params.worn_tint.r - self.base_tint.r) * wear,
+                self.base_tint.g + (params.worn_tint.g - self.base_tint.g) * wear,
+                self.base_tint.b + (params.worn_tint.b - self.base_tint.b) * wear,
+                self.base_tint.a + (params.worn_tint.a - self.base_tint.a) * wear,
+            );
+
+            self.sprite_animation.set_tint(tint);
+        }
+    }
+
+    pub fn is_chargeable(&self) -> bool {
+        self.charge.is_some()
+    }
+
+    pub fn is_charging(&self) -> bool {
+        self.charge_timer.is_some()
+    }
+
+    /// Returns the current charge, between `0.0` and `1.0`
+    pub fn get_charge(&self) -> f32 {
+        match (&self.charge, self.charge_timer) {
+            (Some(params), Some(timer)) if params.duration > 0.0 => {
+                (timer / params.duration).min(1.0)
+            }
+            (Some(_), Some(_)) => 1.0,
+            _ => 0.0,
+        }
+    }
+
+    /// Returns `true` if the weapon has been charged enough to attack when the charge is released
+    pub fn can_release_charge(&self) -> bool {
+        if let Some(params) = &self.charge {
+            self.is_charging() && self.get_charge() >= params.min_charge
+        } else {
+            false
+        }
+    }
+
+    pub fn start_charge(&mut self) {
+        if self.is_chargeable() && !self.is_charging() {
+            self.charge_timer = Some(0.0);
+
+            for particles in &mut self.charge_particles {
+                particles.activate();
+            }
+
+            if let Some(sound_effect) = &self.charge_sound_effect {
+                play_sound_effect(sound_effect, AudioBus::Sfx, 1.0);
+            }
+
+            if self
+                .sprite_animation
+                .set_animation(Self::CHARGE_ANIMATION_ID)
+                .is_some()
+            {
+                self.sprite_animation.play();
+            }
+        }
+    }
+
+    /// Returns `true` if the weapon is cookable and the shortest fuse of its triggered effects
+    /// has run out while it was charging
+    pub fn is_cooked_off(&self) -> bool {
+        match (&self.charge, self.charge_timer) {
+            (Some(params), Some(timer)) if params.is_cookable => {
+                self.effects.iter().any(|effect| {
+                    if let ActiveEffectKind::TriggeredEffect { params } = &*effect.kind {
+                        params
+                            .timed_trigger
+                            .map(|timed_trigger| timer >= timed_trigger)
+                            .unwrap_or(false)
+                    } else {
+                        false
+                    }
+                })
+            }
+            _ => false,
+        }
+    }
+
+    /// This will stop charging and return the charge that was reached, as well as the time the
+    /// weapon was charged, if the weapon was charging
+    fn release_charge(&mut self) -> Option<(f32, f32)> {
+        if let Some(charge_time) = self.charge_timer {
+            let charge = self.get_charge();
+
+            self.cancel_charge();
+
+            Some((charge, charge_time))
+        } else {
+            None
+        }
+    }
+
+    pub fn cancel_charge(&mut self) {
+        self.charge_timer = None;
+
+        for particles in &mut self.charge_particles {
+            particles.deactivate();
+        }
+
+        if self
+            .sprite_animation
+            .get_animation(Self::CHARGE_ANIMATION_ID)
+            .is_some()
+        {
+            self.sprite_animation.set_animation(Self::IDLE_ANIMATION_ID);
+            self.sprite_animation.play();
+        }
+    }
+
     pub fn update(&mut self, dt: f32) {
         self.cooldown_timer += dt;
 
-        self.sprite_animation.update();
+        if let Some(decay_rate) = self
+            .durability_params
+            .as_ref()
+            .map(|params| params.decay_rate)
+        {
+            if decay_rate > 0.0 {
+                self.reduce_durability(decay_rate * dt);
+            }
+        }
+
+        if let Some(spread) = &self.spread {
+            if self.cooldown_timer >= spread.recovery_delay {
+                self.added_spread = (self.added_spread - spread.recovery * dt).max(0.0);
+            }
+        }
+
+        if let Some(charge_timer) = &mut self.charge_timer {
+            *charge_timer += dt;
+        }
+
+        for particles in &mut self.charge_particles {
+            particles.update(dt);
+        }
+
+        if let Some(reload_timer) = &mut self.reload_timer {
+            *reload_timer += dt;
+
+            if *reload_timer >= self.reload_duration {
+                self.finish_reload();
+            }
+        }
+
+        self.update_attack_animations(dt);
+
+        self.sprite_animation.update(dt);
 
         if let Some(effect_animation) = &mut self.effect_animation {
-            effect_animation.update();
+            effect_animation.update(dt);
         }
 
         for particles in &mut self.particles {
@@ -237,23 +970,116 @@ impl Weapon {
         }
     }
 
-    pub fn draw(&mut self, position: Vec2, rotation: f32, flip_x: bool, flip_y: bool) {
-        let position = position + self.get_mount_offset(flip_x, flip_y);
+    /// This plays the frame sounds of the weapon's animations. `position` should be the position
+    /// of the player holding the weapon.
+    pub fn play_frame_sounds(&mut self, position: Vec2) {
+        self.sprite_animation.play_frame_sounds(position);
 
-        {
-            let mut offset = Vec2::ZERO;
+        if let Some(effect_animation) = &mut self.effect_animation {
+            effect_animation.play_frame_sounds(position);
+        }
+    }
+
+    /// Returns the position of the upper left corner of the weapon's sprite, from the position of
+    /// the player's weapon mount
+    fn get_sprite_position(&self, weapon_mount: Vec2, flip_x: bool, flip_y: bool) -> Vec2 {
+        let mut position = weapon_mount + self.get_mount_offset(flip_x, flip_y);
 
-            let size = self.sprite_animation.get_size();
+        let size = self.sprite_animation.get_size();
 
-            if flip_x {
-                offset.x -= size.x;
+        if flip_x {
+            position.x -= size.x;
+        }
+
+        if flip_y {
+            position.y -= size.y;
+        }
+
+        position
+    }
+
+    /// Returns `true` if the weapon's attack animation has hitboxes, making it a melee weapon
+    pub fn is_melee(&self) -> bool {
+        self.sprite_animation
+            .get_animation(Self::ATTACK_ANIMATION_ID)
+            .map(|animation| !animation.hitboxes.is_empty())
+            .unwrap_or(false)
+    }
+
+    /// Returns the hitboxes that are active on the current frame of the weapon's animation, in
+    /// world space
+    pub fn get_active_hitboxes(&self, weapon_mount: Vec2, flip_x: bool, flip_y: bool) -> Vec<Rect> {
+        let position = self.get_sprite_position(weapon_mount, flip_x, flip_y);
+
+        self.sprite_animation
+            .get_frame_hitboxes(position, flip_x, flip_y)
+    }
+
+    /// This draws the trajectories of the weapon's triggered effects, as they would be if the
+    /// charge was released now. This mirrors the movement of `TriggeredEffects`, including the
+    /// added gravity and drag of bodies that rotate, but it does not account for collisions.
+    fn draw_arc_preview(&self, origin: Vec2, flip_x: bool) {
+        let factor = match &self.charge {
+            Some(params) => params.get_velocity_factor(self.get_charge()),
+            None => return,
+        };
+
+        for effect in &self.effects {
+            if let ActiveEffectKind::TriggeredEffect { params } = &*effect.kind {
+                let mut velocity = params.velocity * factor;
+                if flip_x {
+                    velocity.x = -velocity.x;
+                }
+
+                let gravity = if params.is_rotates {
+                    PhysicsBody::get_gravity() * PhysicsBody::THROWN_GRAVITY_SCALE
+                } else {
+                    PhysicsBody::get_gravity()
+                };
+
+                let mut position = origin;
+
+                for i in 0..Self::ARC_PREVIEW_STEPS {
+                    velocity.y += gravity * Self::ARC_PREVIEW_STEP_INTERVAL;
+
+                    if params.is_rotates {
+                        velocity.x *= 1.0 - PhysicsBody::DEFAULT_FRICTION;
+                    }
+
+                    position += velocity * Self::ARC_PREVIEW_STEP_INTERVAL;
+
+                    if i % Self::ARC_PREVIEW_DOT_INTERVAL == 0 {
+                        draw_circle(position.x, position.y, 2.0, Self::ARC_PREVIEW_COLOR);
+                    }
+                }
             }
+        }
+    }
+
+    pub fn draw(&mut self, position: Vec2, rotation: f32, flip_x: bool, flip_y: bool) {
+        if self.is_charging()
+            && self
+                .charge
+                .as_ref()
+                .map(|params| params.should_preview_arc)
+                .unwrap_or(false)
+        {
+            let origin = position
+                + self.get_mount_offset(flip_x, flip_y)
+                + self.get_effect_offset(flip_x, flip_y);
+
+            self.draw_arc_preview(origin, flip_x);
+        }
 
-            if flip_y {
-                offset.y -= size.y;
+        #[cfg(debug_assertions)]
+        if crate::debug::is_debug_draw_enabled() && self.is_swinging {
+            for rect in self.get_active_hitboxes(position, flip_x, flip_y) {
+                draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 2.0, RED);
             }
+        }
 
-            let position = position + offset;
+        {
+            let position = self.get_sprite_position(position, flip_x, flip_y);
 
             self.sprite_animation
                 .draw(position, rotation, flip_x, flip_y);
@@ -267,60 +1093,108 @@ impl Weapon {
         }
 
         {
-            let position = position + self.get_effect_offset(flip_x, flip_y);
+            let position = position
+                + self.get_mount_offset(flip_x, flip_y)
+                + self.get_effect_offset(flip_x, flip_y);
 
             for particles in &mut self.particles {
                 particles.draw(position, flip_x, flip_y);
             }
+
+            for particles in &mut self.charge_particles {
+                particles.draw(position, flip_x, flip_y);
+            }
         }
     }
 
     pub fn draw_hud(&self, position: Vec2) {
-        if let Some(uses) = self.uses {
+        if let Some(magazine_size) = self.magazine_size {
+            Self::draw_use_count(position, magazine_size, self.magazine);
+
+            if let Some(reload_timer) = self.reload_timer {
+                let progress = if self.reload_duration > 0.0 {
+                    (reload_timer / self.reload_duration).min(1.0)
+                } else {
+                    1.0
+                };
+
+                Self::draw_progress_bar(position, progress);
+            }
+        } else if let Some(uses) = self.uses {
             if !self.is_destroyed_on_depletion || uses > 1 {
-                let remaining = uses - self.use_cnt;
-
-                if uses >= Self::HUD_CONDENSED_USE_COUNT_THRESHOLD {
-                    let x = position.x - ((4.0 * uses as f32) / 2.0);
-
-                    for i in 0..uses {
-                        draw_rectangle(
-                            x + 4.0 * i as f32,
-                            position.y - 12.0,
-                            2.0,
-                            12.0,
-                            if i >= remaining {
-                                Self::HUD_USE_COUNT_COLOR_EMPTY
-                            } else {
-                                Self::HUD_USE_COUNT_COLOR_FULL
-                            },
-                        )
-                    }
+                Self::draw_use_count(position, uses, uses - self.use_cnt);
+            }
+        }
+
+        if self.is_charging() {
+            Self::draw_progress_bar(position, self.get_charge());
+        } else if self.durability.is_some() {
+            Self::draw_progress_bar(position, self.get_durability());
+        }
+    }
+
+    fn draw_progress_bar(position: Vec2, progress: f32) {
+        let x = position.x - Self::HUD_PROGRESS_BAR_WIDTH / 2.0;
+
+        draw_rectangle(
+            x,
+            position.y + 4.0,
+            Self::HUD_PROGRESS_BAR_WIDTH,
+            Self::HUD_PROGRESS_BAR_HEIGHT,
+            Self::HUD_USE_COUNT_COLOR_EMPTY,
+        );
+
+        draw_rectangle(
+            x,
+            position.y + 4.0,
+            Self::HUD_PROGRESS_BAR_WIDTH * progress,
+            Self::HUD_PROGRESS_BAR_HEIGHT,
+            Self::HUD_USE_COUNT_COLOR_FULL,
+        );
+    }
+
+    fn draw_use_count(position: Vec2, total: u32, remaining: u32) {
+        if total >= Self::HUD_CONDENSED_USE_COUNT_THRESHOLD {
+            let x = position.x - ((4.0 * total as f32) / 2.0);
+
+            for i in 0..total {
+                draw_rectangle(
+                    x + 4.0 * i as f32,
+                    position.y - 12.0,
+                    2.0,
+                    12.0,
+                    if i >= remaining {
+                        Self::HUD_USE_COUNT_COLOR_EMPTY
+                    } else {
+                        Self::HUD_USE_COUNT_COLOR_FULL
+                    },
+                )
+            }
+        } else {
+            let x = position.x - (total as f32 * 14.0) / 2.0;
+
+            for i in 0..total {
+                let x = x + 14.0 * i as f32;
+
+                if i >= remaining {
+                    draw_circle_lines(
+                        x,
+                        position.y - 4.0,
+                        4.0,
+                        2.0,
+                        Self::HUD_USE_COUNT_COLOR_EMPTY,
+                    );
                 } else {
-                    let x = position.x - (uses as f32 * 14.0) / 2.0;
-
-                    for i in 0..uses {
-                        let x = x + 14.0 * i as f32;
-
-                        if i >= remaining {
-                            draw_circle_lines(
-                                x,
-                                position.y - 4.0,
-                                4.0,
-                                2.0,
-                                Self::HUD_USE_COUNT_COLOR_EMPTY,
-                            );
-                        } else {
-                            draw_circle(x, position.y - 4.0, 4.0, Self::HUD_USE_COUNT_COLOR_FULL);
-                        };
-                    }
-                }
+                    draw_circle(x, position.y - 4.0, 4.0, Self::HUD_USE_COUNT_COLOR_FULL);
+                };
             }
         }
     }
 
-    fn is_ready(&self) -> bool {
-        if self.cooldown_timer < self.cooldown {
+    pub fn is_ready(&self) -> bool {
+        if self.cooldown_timer < self.cooldown || self.is_reloading() {
+            return false;
+        } else if self.magazine_size.is_some() && self.magazine == 0 {
             return false;
         } else if let Some(uses) = self.uses {
             if self.use_cnt >= uses {
@@ -331,178 +1205,404 @@ impl Weapon {
         true
     }
 
-    fn animation_coroutine(
-        player_handle: Handle<Player>,
-        animation_id: &str,
-        is_effect: bool,
-    ) -> Coroutine {
-        let animation_id = animation_id.to_string();
+    /// This starts the animation `animation_id` of the sprite, or of the effect, if `is_effect`
+    /// is `true`, as an attack animation. Attack animations are played by the simulation, in
+    /// `update`, so that the hitboxes of their frames are active for the same amount of ticks,
+    /// regardless of frame rate.
+    fn play_attack_animation(&mut self, animation_id: &str, is_effect: bool) {
+        if is_effect {
+            if let Some(animation_player) = &mut self.effect_animation {
+                if animation_player.set_animation(animation_id).is_some() {
+                    animation_player.stop();
+                    animation_player.set_frame(0);
+                    animation_player.is_deactivated = false;
+
+                    self.effect_animation_timer = Some(0.0);
+                }
+            }
+        } else if self.sprite_animation.set_animation(animation_id).is_some() {
+            self.sprite_animation.stop();
+            self.sprite_animation.set_frame(0);
 
-        let coroutine = async move {
-            let mut animation = None;
+            self.animation_timer = Some(0.0);
+        }
+    }
 
-            {
-                let player = &mut *scene::get_node(player_handle);
-                if let Some(weapon) = &mut player.weapon {
-                    if is_effect {
-                        if let Some(animation_player) = &mut weapon.effect_animation {
-                            animation = animation_player.set_animation(&animation_id).cloned();
-
-                            if animation.is_some() {
-                                animation_player.stop();
-                                animation_player.is_deactivated = false;
-                            }
-                        }
-                    } else {
-                        animation = weapon
-                            .sprite_animation
-                            .set_animation(&animation_id)
-                            .cloned();
+    /// This sets the frame of an attack animation from `timer`, the time that has passed since it
+    /// was started, and returns `false` if the animation has ended
+    fn advance_attack_animation(animation_player: &mut AnimationPlayer, timer: f32) -> bool {
+        let id = animation_player.get_current_animation_id();
 
-                        if animation.is_some() {
-                            weapon.sprite_animation.stop();
-                        }
-                    }
+        let frame = match animation_player.get_animation(id) {
+            Some(animation) => {
+                let frame = (timer * animation.fps as f32) as u32;
+
+                if frame >= animation.frames {
+                    return false;
                 }
+
+                frame
             }
+            None => return false,
+        };
 
-            if let Some(animation) = animation {
-                let frame_interval = 1.0 / animation.fps as f32;
+        animation_player.set_frame(frame as usize);
 
-                for i in 0..animation.frames as usize {
-                    {
-                        let player = &mut *scene::get_node(player_handle);
-                        if let Some(weapon) = player.weapon.as_mut() {
-                            if is_effect {
-                                let animation_player = weapon.effect_animation.as_mut().unwrap();
+        true
+    }
 
-                                animation_player.set_frame(i);
-                            } else {
-                                weapon.sprite_animation.set_frame(i);
-                            }
-                        }
-                    }
+    /// This advances the attack animations of the weapon by `dt` and ends any that have played
+    /// all of their frames. This is called by `update`.
+    fn update_attack_animations(&mut self, dt: f32) {
+        let sprite_animation = &mut self.sprite_animation;
 
-                    wait_seconds(frame_interval).await;
-                }
+        let is_playing = self.animation_timer.as_mut().map(|timer| {
+            *timer += dt;
+            Self::advance_attack_animation(sprite_animation, *timer)
+        });
 
-                {
-                    let player = &mut *scene::get_node(player_handle);
-                    if let Some(weapon) = player.weapon.as_mut() {
-                        if is_effect {
-                            let animation_player = weapon.effect_animation.as_mut().unwrap();
-                            animation_player.stop();
-                            animation_player.is_deactivated = true;
-                        } else {
-                            weapon
-                                .sprite_animation
-                                .set_animation(Self::IDLE_ANIMATION_ID);
+        if is_playing == Some(false) {
+            self.animation_timer = None;
 
-                            weapon.sprite_animation.play();
-                        }
-                    }
-                }
+            self.sprite_animation.set_animation(Self::IDLE_ANIMATION_ID);
+            self.sprite_animation.play();
+
+            self.is_swinging = false;
+        }
+
+        let effect_animation = &mut self.effect_animation;
+
+        let is_playing = self.effect_animation_timer.as_mut().and_then(|timer| {
+            *timer += dt;
+
+            effect_animation
+                .as_mut()
+                .map(|animation_player| Self::advance_attack_animation(animation_player, *timer))
+        });
+
+        if is_playing != Some(true) && self.effect_animation_timer.is_some() {
+            self.effect_animation_timer = None;
+
+            if let Some(animation_player) = &mut self.effect_animation {
+                animation_player.stop();
+                animation_player.is_deactivated = true;
             }
+        }
+    }
+
+    /// This checks the hitboxes of the current frame of the attack animation against other
+    /// players, if the `Weapon` held in `slot` by `player` is swinging. It is called every tick,
+    /// for as long as the swing is active.
+    /// If the hitboxes overlap those of another player's swing, the swings will clash, ending both
+    /// of them and knocking both players back.
+    pub fn update_swing(player: &mut RefMut<Player>, slot: WeaponSlot) {
+        let player_handle = player.handle();
+
+        let mut clash_with = None;
+
+        let weapon_mount = player.get_weapon_mount_position(slot);
+        let (flip_x, flip_y) = (!player.body.is_facing_right, player.body.is_upside_down);
+        let position = player.body.position;
+
+        let weapon = match player.get_weapon_mut(slot) {
+            Some(weapon) if weapon.is_swinging => weapon,
+            _ => return,
         };
 
-        start_coroutine(coroutine)
-    }
+        let hitboxes = weapon.get_active_hitboxes(weapon_mount, flip_x, flip_y);
 
-    /// This will start a `Coroutine` that performs an attack with the `Weapon` equipped by the
-    /// `Player` fetched with `player_handle`, id one is equipped and ready for use.
-    pub fn attack_coroutine(player_handle: Handle<Player>) -> Coroutine {
-        let coroutine = async move {
-            let is_ready = {
-                let player = &mut *scene::get_node(player_handle);
-                if let Some(weapon) = &player.weapon {
-                    weapon.is_ready()
-                } else {
-                    false
-                }
+        if !hitboxes.is_empty() {
+            let damage = weapon
+                .damage
+                .unwrap_or_else(ActiveEffectParams::default_damage);
+
+            let damage_params = DamageParams {
+                hitstop: weapon.hitstop,
+                knockback: weapon.knockback.unwrap_or_default(),
+                weapon_id: Some(weapon.id.clone()),
+                ..Default::default()
             };
 
-            if is_ready {
-                let mut should_destroy = false;
+            for mut other in scene::find_nodes_by_type::<Player>() {
+                if other.is_dead {
+                    continue;
+                }
 
-                {
-                    let player = &mut *scene::get_node(player_handle);
-                    if let Some(weapon) = player.weapon.as_mut() {
-                        if let Some(uses) = weapon.uses {
-                            weapon.use_cnt += 1;
+                let (other_flip_x, other_flip_y) =
+                    (!other.body.is_facing_right, other.body.is_upside_down);
 
-                            if weapon.is_destroyed_on_depletion && weapon.use_cnt >= uses {
-                                should_destroy = true;
-                            }
-                        }
+                for other_slot in WeaponSlot::ALL {
+                    let other_weapon_mount = other.get_weapon_mount_position(other_slot);
 
-                        weapon.cooldown_timer = 0.0;
+                    let is_clash = match other.get_weapon_mut(other_slot) {
+                        Some(other_weapon) if other_weapon.is_swinging => {
+                            let other_hitboxes = other_weapon.get_active_hitboxes(
+                                other_weapon_mount,
+                                other_flip_x,
+                                other_flip_y,
+                            );
+
+                            let is_clash = hitboxes.iter().any(|rect| {
+                                other_hitboxes.iter().any(|other| rect.overlaps(other))
+                            });
 
-                        if let Some(sound_effect) = weapon.sound_effect {
-                            play_sound_once(sound_effect);
+                            if is_clash {
+                                other_weapon.is_swinging = false;
+                            }
+
+                            is_clash
                         }
+                        _ => false,
+                    };
 
-                        player.body.velocity.x = if player.body.is_facing_right {
-                            -weapon.recoil
+                    if is_clash {
+                        let direction = if other.body.position.x < position.x {
+                            -1.0
                         } else {
-                            weapon.recoil
+                            1.0
                         };
-                    } else {
-                        return;
+
+                        other.body.apply_knockback(vec2(
+                            direction * cvars::get_float(Self::CLASH_KNOCKBACK_CVAR),
+                            0.0,
+                        ));
+
+                        clash_with = Some(other.body.position);
+
+                        break;
                     }
                 }
 
-                {
-                    let player = &mut *scene::get_node(player_handle);
+                if clash_with.is_some() {
+                    break;
+                }
 
-                    let weapon_mount = player.get_weapon_mount_position();
-                    let (flip_x, flip_y) =
-                        (!player.body.is_facing_right, player.body.is_upside_down);
+                if weapon.swing_hits.contains(&other.id) {
+                    continue;
+                }
 
-                    if let Some(weapon) = &mut player.weapon {
-                        for particles in &mut weapon.particles {
-                            particles.activate();
-                        }
+                let collider = other.get_collider_rect();
 
-                        let origin = weapon_mount
-                            + weapon.get_mount_offset(flip_x, flip_y)
-                            + weapon.get_effect_offset(flip_x, flip_y);
+                if hitboxes.iter().any(|rect| rect.overlaps(&collider)) {
+                    weapon.swing_hits.push(other.id);
 
-                        for params in weapon.effects.clone() {
-                            active_effect_coroutine(player_handle, origin, params);
-                        }
+                    if let Some(sound_effect) = &weapon.hit_sound_effect {
+                        play_sound_at(sound_effect, other.body.position);
                     }
-                }
 
-                {
-                    Weapon::animation_coroutine(player_handle, Self::ATTACK_ANIMATION_ID, false);
-                    Weapon::animation_coroutine(
-                        player_handle,
-                        Self::ATTACK_EFFECT_ANIMATION_ID,
-                        true,
+                    let is_from_right = position.x > other.body.position.x;
+
+                    Player::on_receive_damage_ex(
+                        other.handle(),
+                        is_from_right,
+                        Some(player_handle),
+                        damage,
+                        damage_params.clone(),
                     );
                 }
+            }
+        }
 
-                let attack_duration = {
-                    let player = &*scene::get_node(player_handle);
-                    player.weapon.as_ref().map(|weapon| weapon.attack_duration)
-                };
+        if let Some(other_position) = clash_with {
+            weapon.is_swinging = false;
 
-                if let Some(attack_duration) = attack_duration {
-                    wait_seconds(attack_duration).await;
-                }
+            let direction = if position.x < other_position.x {
+                -1.0
+            } else {
+                1.0
+            };
 
-                if should_destroy {
-                    let player = &mut *scene::get_node(player_handle);
-                    player.weapon = None;
-                }
+            player.body.apply_knockback(vec2(
+                direction * cvars::get_float(Self::CLASH_KNOCKBACK_CVAR),
+                0.0,
+            ));
+
+            let resources = storage::get::<Resources>();
+            play_sound_at(&resources.sounds[Self::CLASH_SOUND_EFFECT_ID], position);
+
+            let mut particles = scene::find_node_by_type::<ParticleEmitters>().unwrap();
+            particles.spawn(
+                Self::CLASH_PARTICLE_EFFECT_ID,
+                (position + other_position) / 2.0,
+            );
+        }
+    }
+
+    /// This starts an attack with the `Weapon` held in `slot` by `player`, if one is held and it
+    /// is ready for use, and returns `false` if it is not. The attack is ended by calling
+    /// `finish_attack`, after `attack_duration` has passed, which is counted by the player.
+    pub fn begin_attack(player: &mut RefMut<Player>, slot: WeaponSlot) -> bool {
+        let is_ready = player
+            .get_weapon(slot)
+            .map(Weapon::is_ready)
+            .unwrap_or(false);
+
+        if !is_ready {
+            return false;
+        }
+
+        let player_handle = player.handle();
+        let player_id = player.id;
+        let player_position = player.body.position;
+
+        let (recoil, camera_kick) = {
+            let weapon = player.get_weapon_mut(slot).unwrap();
+
+            if weapon.uses.is_some() {
+                weapon.use_cnt += 1;
             }
 
+            if weapon.magazine_size.is_some() {
+                weapon.magazine -= 1;
+            }
+
+            if let Some(use_cost) = weapon
+                .durability_params
+                .as_ref()
+                .map(|params| params.use_cost)
             {
-                let player = &mut *scene::get_node(player_handle);
-                player.state_machine.set_state(Player::ST_NORMAL);
+                weapon.reduce_durability(use_cost);
+            }
+
+            weapon.cooldown_timer = 0.0;
+
+            if let Some(sound_effect) = &weapon.sound_effect {
+                play_sound_at(sound_effect, player_position);
+            }
+
+            GameEvent::ItemUsed {
+                player_id,
+                item_id: weapon.id.clone(),
             }
+            .dispatch();
+
+            (weapon.recoil, weapon.camera_kick)
         };
 
-        start_coroutine(coroutine)
+        if recoil > 0.0 {
+            let recoil = if player.body.is_facing_right {
+                -recoil
+            } else {
+                recoil
+            };
+
+            player.body.apply_knockback(vec2(recoil, 0.0));
+        }
+
+        if camera_kick > 0.0 {
+            let angle = if player.body.is_facing_right { 0.0 } else { PI };
+
+            if let Some(mut camera) = scene::find_node_by_type::<GameCamera>() {
+                camera.shake_sinusoidal(
+                    camera_kick,
+                    Self::CAMERA_KICK_LENGTH,
+                    Self::CAMERA_KICK_FREQUENCY,
+                    angle,
+                );
+            }
+        }
+
+        let weapon_mount = player.get_weapon_mount_position(slot);
+        let (flip_x, flip_y) = (!player.body.is_facing_right, player.body.is_upside_down);
+
+        let weapon = player.get_weapon_mut(slot).unwrap();
+
+        for particles in &mut weapon.particles {
+            particles.activate();
+        }
+
+        let origin = weapon_mount
+            + weapon.get_mount_offset(flip_x, flip_y)
+            + weapon.get_effect_offset(flip_x, flip_y);
+
+        let is_firearm = weapon
+            .effects
+            .iter()
+            .any(|params| matches!(*params.kind, ActiveEffectKind::Projectile { .. }));
+
+        if is_firearm {
+            Lighting::add_flash(
+                origin,
+                Self::MUZZLE_FLASH_RADIUS,
+                Self::MUZZLE_FLASH_COLOR,
+                Self::MUZZLE_FLASH_DURATION,
+            );
+
+            let mut particles = scene::find_node_by_type::<ParticleEmitters>().unwrap();
+            particles.spawn(Self::SHELL_CASING_PARTICLE_EFFECT_ID, weapon_mount);
+        }
+
+        // Spread added by this attack will only apply to subsequent attacks
+        let added_spread = weapon
+            .spread
+            .as_ref()
+            .map(|spread| (weapon.added_spread + spread.growth).min(spread.max));
+
+        let charge = weapon.release_charge();
+
+        for mut params in weapon.effects.clone() {
+            if let Some(damage) = weapon.damage {
+                params.damage = damage;
+            }
+
+            if let Some(knockback) = weapon.knockback {
+                params.knockback = knockback;
+            }
+
+            if let ActiveEffectKind::Projectile { spread, .. } = &mut *params.kind {
+                *spread += weapon.added_spread;
+            }
+
+            if let (Some(charge_params), Some((charge, charge_time))) = (&weapon.charge, charge) {
+                charge_params.apply(charge, charge_time, &mut params);
+            }
+
+            params.set_weapon_id(&weapon.id);
+
+            instantiate_active_effect(player_handle, origin, params);
+        }
+
+        if let Some(added_spread) = added_spread {
+            weapon.added_spread = added_spread;
+        }
+
+        if weapon.is_melee() {
+            weapon.is_swinging = true;
+            weapon.swing_hits.clear();
+        }
+
+        weapon.play_attack_animation(Self::ATTACK_ANIMATION_ID, false);
+        weapon.play_attack_animation(Self::ATTACK_EFFECT_ANIMATION_ID, true);
+
+        true
+    }
+
+    /// This ends the attack of the `Weapon` held in `slot` by `player`, destroying or throwing the
+    /// weapon, if it was depleted by the attack, or starting a reload, if its magazine is empty
+    pub fn finish_attack(player: &mut RefMut<Player>, slot: WeaponSlot) {
+        let (should_destroy, should_throw) = match player.get_weapon_mut(slot) {
+            Some(weapon) if weapon.is_depleted() => (
+                weapon.is_destroyed_on_depletion,
+                !weapon.is_destroyed_on_depletion && weapon.is_thrown_on_depletion,
+            ),
+            Some(weapon) => {
+                if weapon.magazine_size.is_some() && weapon.magazine == 0 {
+                    weapon.start_reload();
+                }
+
+                (false, false)
+            }
+            None => return,
+        };
+
+        if should_destroy {
+            player.get_weapon_slot_mut(slot).take();
+
+            let position = player.get_weapon_mount_position(slot);
+            let mut particles = scene::find_node_by_type::<ParticleEmitters>().unwrap();
+            particles.spawn(Self::DESTROYED_PARTICLE_EFFECT_ID, position);
+        } else if should_throw {
+            player.drop_weapon(slot, true);
+        }
     }
 }
@@ -0,0 +1,166 @@
+//! Cosmetic work, that has no effect on the simulation, can be split into jobs that run on worker
+//! threads, while the simulation itself stays single-threaded, so that it remains deterministic.
+//!
+//! A parallel stage is started with `for_each_parallel`, which splits a slice between the main
+//! thread and the workers, and it does not return until every item has been processed. This is
+//! the barrier that keeps the order of everything that happens after a stage the same, no matter
+//! how many workers there are, or in what order they finish.
+//!
+//! Jobs must not touch any global state, like `storage`, scene nodes or the global RNG, which is
+//! shared with the simulation. Work that needs to do any of that, like emitting particles or
+//! drawing decals, is done in a separate stage on the main thread, before or after the parallel
+//! stage. For now, only the integration of particles is offloaded, as animations are updated by
+//! the scene nodes that own them, and audio is already mixed on its own thread by the backend.
+//!
+//! The workers are started the first time they are needed and then kept alive, waiting for jobs,
+//! for the rest of the game, so that a parallel stage does not have to spawn any threads.
+
+use std::{
+    any::Any,
+    mem,
+    panic::{self, AssertUnwindSafe},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+use crate::cvars::{self, CVarFlags, CVarValue};
+
+/// The amount of worker threads used for parallel stages, where `0` picks the amount from the
+/// available parallelism, and `1` runs all stages on the main thread
+pub const WORKER_CNT_CVAR: &str = "jobs.worker_count";
+
+/// The maximum amount of threads, including the main thread, that will be picked automatically
+const MAX_AUTO_WORKER_CNT: usize = 8;
+
+pub fn register_cvars() {
+    cvars::register(
+        WORKER_CNT_CVAR,
+        CVarValue::Int(0),
+        CVarFlags::ARCHIVE,
+        "The amount of threads used for cosmetic work, like particles (0 = auto)",
+    );
+}
+
+/// Returns the amount of threads, including the main thread, that parallel stages are split over
+pub fn get_worker_cnt() -> usize {
+    if cfg!(target_family = "wasm") {
+        return 1;
+    }
+
+    match cvars::get_int(WORKER_CNT_CVAR) {
+        cnt if cnt > 0 => cnt as usize,
+        _ => std::thread::available_parallelism()
+            .map(|cnt| cnt.get().min(MAX_AUTO_WORKER_CNT))
+            .unwrap_or(1),
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// The result of a job, which holds the payload of the panic, if the job panicked
+type JobResult = thread::Result<()>;
+
+struct WorkerPool {
+    job_senders: Vec<Sender<Job>>,
+    result_sender: Sender<JobResult>,
+    result_receiver: Receiver<JobResult>,
+}
+
+impl WorkerPool {
+    fn new() -> Self {
+        let (result_sender, result_receiver) = mpsc::channel();
+
+        WorkerPool {
+            job_senders: Vec::new(),
+            result_sender,
+            result_receiver,
+        }
+    }
+
+    /// This starts workers until there are at least `cnt` of them
+    fn reserve(&mut self, cnt: usize) {
+        while self.job_senders.len() < cnt {
+            let (job_sender, job_receiver) = mpsc::channel::<Job>();
+            let result_sender = self.result_sender.clone();
+
+            thread::Builder::new()
+                .name(format!("worker {}", self.job_senders.len()))
+                .spawn(move || {
+                    for job in job_receiver {
+                        let res = panic::catch_unwind(AssertUnwindSafe(job));
+
+                        if result_sender.send(res).is_err() {
+                            break;
+                        }
+                    }
+                })
+                .unwrap();
+
+            self.job_senders.push(job_sender);
+        }
+    }
+}
+
+static mut WORKER_POOL: Option<WorkerPool> = None;
+
+unsafe fn get_worker_pool() -> &'static mut WorkerPool {
+    WORKER_POOL.get_or_insert_with(WorkerPool::new)
+}
+
+/// This calls `f` for every item in `items`, split over the main thread and worker threads, and
+/// returns once every item has been processed. Items are processed in order on each thread, but
+/// the order between threads is not defined, so `f` must only modify the item it is given.
+///
+/// Items are only split off to a worker in chunks of at least `min_chunk_size` items, as handing
+/// a job to a worker costs more than processing a few small items on the main thread.
+pub fn for_each_parallel<T, F>(items: &mut [T], min_chunk_size: usize, f: F)
+where
+    T: Send,
+    F: Fn(&mut T) + Sync,
+{
+    let min_chunk_size = min_chunk_size.max(1);
+    let thread_cnt = get_worker_cnt().min(items.len() / min_chunk_size);
+
+    if thread_cnt <= 1 {
+        items.iter_mut().for_each(f);
+        return;
+    }
+
+    let chunk_size = (items.len() + thread_cnt - 1) / thread_cnt;
+    let f = &f;
+
+    let pool = unsafe { get_worker_pool() };
+    pool.reserve(thread_cnt - 1);
+
+    let mut chunks = items.chunks_mut(chunk_size);
+
+    // The first chunk is processed on the main thread, in stead of waiting for the workers
+    let main_chunk = chunks.next().unwrap();
+
+    let mut job_cnt = 0;
+
+    for (chunk, job_sender) in chunks.zip(&pool.job_senders) {
+        let job: Box<dyn FnOnce() + Send + '_> = Box::new(move || chunk.iter_mut().for_each(f));
+
+        // SAFETY: The job borrows `items` and `f`, which outlive it, as this does not return, or
+        // unwind, before the result of every job has been received
+        let job: Job = unsafe { mem::transmute(job) };
+
+        job_sender.send(job).unwrap();
+        job_cnt += 1;
+    }
+
+    let main_res = panic::catch_unwind(AssertUnwindSafe(|| main_chunk.iter_mut().for_each(f)));
+
+    let mut panic_payload: Option<Box<dyn Any + Send>> = main_res.err();
+
+    for _ in 0..job_cnt {
+        if let Err(payload) = pool.result_receiver.recv().unwrap() {
+            panic_payload.get_or_insert(payload);
+        }
+    }
+
+    if let Some(payload) = panic_payload {
+        panic::resume_unwind(payload);
+    }
+}
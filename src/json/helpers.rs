@@ -127,6 +127,17 @@ impl GenericParam {
     pub fn get_value<T: GenericParamType>(&self) -> Option<&T> {
         T::from_param(self)
     }
+
+    /// Returns the value of any numeric variant as a `f32`. This is useful for values that are
+    /// edited as text, where a whole number will be parsed as an integer.
+    pub fn to_f32(&self) -> Option<f32> {
+        match self {
+            Self::Float(value) => Some(*value),
+            Self::Int(value) => Some(*value as f32),
+            Self::UInt(value) => Some(*value as f32),
+            _ => None,
+        }
+    }
 }
 
 pub trait GenericParamType: Clone {
@@ -1,17 +1,19 @@
 pub mod tiled;
 
-use std::{collections::HashMap, iter::FromIterator};
+use std::{collections::HashMap, convert::TryFrom, iter::FromIterator};
 
 use macroquad::prelude::*;
 
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    error::Error,
     json,
     map::{
         Map, MapBackgroundLayer, MapLayer, MapLayerKind, MapObject, MapProperty, MapTile,
-        MapTileset,
+        MapTileset, TileChunks,
     },
+    symbol::Symbol,
 };
 
 pub use tiled::TiledMap;
@@ -111,8 +113,16 @@ impl From<Map> for MapDef {
     }
 }
 
-impl From<MapDef> for Map {
-    fn from(def: MapDef) -> Self {
+impl TryFrom<MapDef> for Map {
+    type Error = Error;
+
+    fn try_from(def: MapDef) -> Result<Self, Self::Error> {
+        // The texture ids of tilesets are interned up front, so that a hash collision fails the
+        // deserialization of the map, in stead of aliasing the textures of two tilesets
+        for tileset in &def.tilesets {
+            Symbol::intern(&tileset.texture_id)?;
+        }
+
         let tilesets = HashMap::from_iter(
             def.tilesets
                 .clone()
@@ -150,7 +160,7 @@ impl From<MapDef> for Map {
                                 let tile = MapTile {
                                     tile_id,
                                     tileset_id: tileset.id.clone(),
-                                    texture_id: tileset.texture_id.clone(),
+                                    texture_id: Symbol::of(&tileset.texture_id),
                                     texture_coords: tileset.get_texture_coords(tile_id),
                                     attributes,
                                 };
@@ -167,7 +177,7 @@ impl From<MapDef> for Map {
                         tile
                     }
                 })
-                .collect();
+                .collect::<Vec<_>>();
 
             let objects = layer.objects.clone().unwrap_or_default();
 
@@ -176,7 +186,7 @@ impl From<MapDef> for Map {
                 kind: layer.kind,
                 has_collision: layer.has_collision,
                 grid_size: def.grid_size,
-                tiles,
+                tiles: TileChunks::from_tiles(def.grid_size, tiles),
                 objects,
                 is_visible: layer.is_visible,
                 properties: layer.properties.clone(),
@@ -185,7 +195,7 @@ impl From<MapDef> for Map {
             (layer.id.clone(), layer)
         }));
 
-        Map {
+        Ok(Map {
             background_color: def.background_color,
             background_layers: def.background_layers,
             world_offset: def.world_offset,
@@ -196,7 +206,7 @@ impl From<MapDef> for Map {
             draw_order,
             properties: def.properties,
             spawn_points: def.spawn_points,
-        }
+        })
     }
 }
 
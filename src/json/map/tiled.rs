@@ -6,8 +6,13 @@ use serde::{Deserialize, Serialize};
 
 use crate::map::MapObjectKind;
 use crate::{
-    map::{Map, MapLayer, MapLayerKind, MapObject, MapProperty, MapTile, MapTileset},
+    map::{
+        Map, MapLayer, MapLayerKind, MapObject, MapProperty, MapTile, MapTileAnimation, MapTileset,
+        TileChunks,
+    },
     math::color_from_hex_string,
+    symbol::Symbol,
+    Result,
 };
 
 const SPAWN_POINT_MAP_OBJECT_TYPE: &str = "spawn_point";
@@ -44,8 +49,17 @@ pub struct TiledObject {
 #[derive(Debug, Clone, Deserialize)]
 pub struct TiledTileAttribute {
     pub id: u32,
-    #[serde(rename = "type")]
+    #[serde(default, rename = "type")]
     pub attribute: String,
+    #[serde(default)]
+    pub animation: Option<Vec<TiledAnimationFrame>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TiledAnimationFrame {
+    pub tileid: u32,
+    /// The duration of the frame, in milliseconds
+    pub duration: u32,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -115,7 +129,7 @@ impl TiledMap {
     pub const COLLISION_LAYER_PROP: &'static str = "collision";
     pub const TEXTURE_ID_PROP: &'static str = "texture_id";
 
-    pub fn into_map(self) -> Map {
+    pub fn into_map(self) -> Result<Map> {
         let background_color = if let Some(background_color) = self.backgroundcolor {
             color_from_hex_string(&background_color)
         } else {
@@ -140,13 +154,38 @@ impl TiledMap {
             );
 
             let mut tile_attributes: HashMap<u32, Vec<String>> = HashMap::new();
+            let mut tile_animations = HashMap::new();
             if let Some(tiled_tile_attributes) = tiled_tileset.tile_attributes.as_ref() {
                 for tiled_attr in tiled_tile_attributes {
-                    tile_attributes.entry(tiled_attr.id).or_default();
-                    tile_attributes
-                        .get_mut(&tiled_attr.id)
-                        .unwrap()
-                        .push(tiled_attr.attribute.clone());
+                    if !tiled_attr.attribute.is_empty() {
+                        tile_attributes.entry(tiled_attr.id).or_default();
+                        tile_attributes
+                            .get_mut(&tiled_attr.id)
+                            .unwrap()
+                            .push(tiled_attr.attribute.clone());
+                    }
+
+                    // Tiled stores a duration for each frame, but as we use a fixed frame rate,
+                    // the average duration of the frames is used
+                    if let Some(tiled_frames) = tiled_attr.animation.as_ref() {
+                        if !tiled_frames.is_empty() {
+                            let total_duration: u32 =
+                                tiled_frames.iter().map(|frame| frame.duration).sum();
+
+                            let fps = if total_duration > 0 {
+                                tiled_frames.len() as f32 * 1000.0 / total_duration as f32
+                            } else {
+                                MapTileAnimation::default_fps()
+                            };
+
+                            let animation = MapTileAnimation {
+                                frames: tiled_frames.iter().map(|frame| frame.tileid).collect(),
+                                fps,
+                            };
+
+                            tile_animations.insert(tiled_attr.id, animation);
+                        }
+                    }
                 }
             }
 
@@ -192,12 +231,17 @@ impl TiledMap {
                 tile_subdivisions,
                 autotile_mask,
                 tile_attributes,
+                tile_animations,
                 properties,
             };
 
             tilesets.insert(tiled_tileset.name, tileset);
         }
 
+        for tileset in tilesets.values() {
+            Symbol::intern(&tileset.texture_id)?;
+        }
+
         let mut spawn_points = Vec::new();
         let mut layers = HashMap::new();
         let mut draw_order = Vec::new();
@@ -228,7 +272,7 @@ impl TiledMap {
                     let tile = MapTile {
                         tile_id,
                         tileset_id: tileset.id.clone(),
-                        texture_id: tileset.texture_id.clone(),
+                        texture_id: Symbol::of(&tileset.texture_id),
                         texture_coords: tileset.get_texture_coords(tile_id),
                         attributes,
                     };
@@ -298,7 +342,7 @@ impl TiledMap {
                 kind,
                 has_collision,
                 grid_size,
-                tiles,
+                tiles: TileChunks::from_tiles(grid_size, tiles),
                 objects,
                 is_visible: tiled_layer.visible,
                 properties,
@@ -318,7 +362,7 @@ impl TiledMap {
             }
         }
 
-        Map {
+        Ok(Map {
             background_color,
             background_layers: Vec::new(),
             world_offset: Vec2::ZERO,
@@ -329,7 +373,7 @@ impl TiledMap {
             draw_order,
             properties,
             spawn_points,
-        }
+        })
     }
 }
 
@@ -0,0 +1,338 @@
+//! A simple 2D lighting system, for cave and night themed maps. A map that has a `darkness`
+//! property, between `0.0` and `1.0`, will be darkened by that amount, except where there is light.
+//! Lights are placed in a map as environment objects, with an optional radius and color, and every
+//! player also emits a small light. Short lived lights, like muzzle flashes and explosions, are
+//! added with `Lighting::add_flash`.
+//!
+//! Every frame, all the lights are drawn additively to a light map, which is then multiplied with
+//! everything that has been drawn before it. As nodes are drawn in the order they were added to the
+//! scene, the lighting node should be added after all the nodes that should be affected by it.
+
+use macroquad::{
+    experimental::{
+        collections::storage,
+        scene::{self, RefMut},
+    },
+    material::{gl_use_default_material, gl_use_material, load_material, Material, MaterialParams},
+    miniquad::{BlendFactor, BlendState, BlendValue, Equation, PipelineParams},
+    prelude::*,
+};
+
+use crate::{
+    cvars::{self, CVarFlags, CVarValue},
+    json::GenericParam,
+    map::MapObject,
+    profiler::ProfileScope,
+    Config, GameCamera, Map, Player,
+};
+
+const VERTEX_SHADER: &str = r#"#version 100
+attribute vec3 position;
+attribute vec2 texcoord;
+attribute vec4 color0;
+
+varying lowp vec2 uv;
+varying lowp vec4 color;
+
+uniform mat4 Model;
+uniform mat4 Projection;
+
+void main() {
+    gl_Position = Projection * Model * vec4(position, 1);
+    color = color0 / 255.0;
+    uv = texcoord;
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"#version 100
+varying lowp vec4 color;
+varying lowp vec2 uv;
+
+uniform sampler2D Texture;
+
+void main() {
+    gl_FragColor = color * texture2D(Texture, uv);
+}
+"#;
+
+#[derive(Debug, Clone)]
+pub struct Light {
+    pub position: Vec2,
+    pub radius: f32,
+    pub color: Color,
+}
+
+/// A light that fades out over its duration
+struct Flash {
+    light: Light,
+    duration: f32,
+    timer: f32,
+}
+
+pub struct Lighting {
+    darkness: f32,
+    lights: Vec<Light>,
+    flashes: Vec<Flash>,
+    light_map: RenderTarget,
+    light_texture: Texture2D,
+    /// Lights are added together on the light map
+    additive_material: Material,
+    /// The light map is multiplied with what has already been drawn
+    multiply_material: Material,
+}
+
+impl Lighting {
+    pub const DARKNESS_MAP_PROPERTY: &'static str = "darkness";
+
+    pub const LIGHT_OBJECT_ID: &'static str = "light";
+    pub const RADIUS_PROPERTY: &'static str = "radius";
+    pub const COLOR_PROPERTY: &'static str = "color";
+
+    const DEFAULT_LIGHT_RADIUS: f32 = 128.0;
+    const DEFAULT_LIGHT_COLOR: Color = Color {
+        r: 1.0,
+        g: 0.9,
+        b: 0.7,
+        a: 1.0,
+    };
+
+    const PLAYER_LIGHT_RADIUS: f32 = 96.0;
+    const PLAYER_LIGHT_COLOR: Color = Color {
+        r: 0.8,
+        g: 0.8,
+        b: 0.8,
+        a: 1.0,
+    };
+
+    /// The size of the texture used for the falloff of each light
+    const LIGHT_TEXTURE_SIZE: u16 = 64;
+    /// The light map is rendered at a fraction of the screen resolution, as it is blurry anyway
+    const LIGHT_MAP_SCALE_CVAR: &'static str = "render.light_map_scale";
+    const DEFAULT_LIGHT_MAP_SCALE: f32 = 0.5;
+
+    /// This will return `None` if the map has no darkness, in which case lighting is disabled
+    pub fn register_cvars() {
+        cvars::register(
+            Self::LIGHT_MAP_SCALE_CVAR,
+            CVarValue::Float(Self::DEFAULT_LIGHT_MAP_SCALE),
+            CVarFlags::ARCHIVE,
+            "The resolution of the light map, as a fraction of the screen resolution, between 0.1 and 1.0",
+        );
+    }
+
+    pub fn new(map: &Map, map_objects: &[MapObject]) -> Option<Self> {
+        let darkness = Self::get_darkness(map);
+        if darkness <= 0.0 {
+            return None;
+        }
+
+        let lights = map_objects
+            .iter()
+            .filter(|object| object.id == Self::LIGHT_OBJECT_ID)
+            .map(|object| {
+                let radius = object
+                    .properties
+                    .get(Self::RADIUS_PROPERTY)
+                    .and_then(|param| param.to_f32())
+                    .unwrap_or(Self::DEFAULT_LIGHT_RADIUS);
+
+                let color = object
+                    .properties
+                    .get(Self::COLOR_PROPERTY)
+                    .and_then(|param| param.get_value::<Color>())
+                    .copied()
+                    .unwrap_or(Self::DEFAULT_LIGHT_COLOR);
+
+                Light {
+                    position: object.position,
+                    radius,
+                    color,
+                }
+            })
+            .collect();
+
+        let light_map_scale = cvars::get_float(Self::LIGHT_MAP_SCALE_CVAR).clamp(0.1, 1.0);
+
+        let light_map = render_target(
+            (screen_width() * light_map_scale) as u32,
+            (screen_height() * light_map_scale) as u32,
+        );
+        light_map.texture.set_filter(FilterMode::Linear);
+
+        let light_texture = Self::create_light_texture();
+
+        let additive_material = Self::create_material(BlendState::new(
+            Equation::Add,
+            BlendFactor::Value(BlendValue::SourceAlpha),
+            BlendFactor::One,
+        ));
+
+        let multiply_material = Self::create_material(BlendState::new(
+            Equation::Add,
+            BlendFactor::Value(BlendValue::DestinationColor),
+            BlendFactor::Zero,
+        ));
+
+        Some(Lighting {
+            darkness,
+            lights,
+            flashes: Vec::new(),
+            light_map,
+            light_texture,
+            additive_material,
+            multiply_material,
+        })
+    }
+
+    /// Returns the darkness set in the properties of `map`, if any
+    pub fn get_darkness(map: &Map) -> f32 {
+        map.properties
+            .get(Self::DARKNESS_MAP_PROPERTY)
+            .and_then(GenericParam::to_f32)
+            .map(|darkness| darkness.clamp(0.0, 1.0))
+            .unwrap_or(0.0)
+    }
+
+    /// This adds a light that fades out over `duration`, like a muzzle flash or an explosion.
+    /// This does nothing if the current map has no darkness.
+    pub fn add_flash(position: Vec2, radius: f32, color: Color, duration: f32) {
+        if storage::get::<Config>().reduce_flashing {
+            return;
+        }
+
+        if let Some(mut lighting) = scene::find_node_by_type::<Lighting>() {
+            lighting.flashes.push(Flash {
+                light: Light {
+                    position,
+                    radius,
+                    color,
+                },
+                duration,
+                timer: 0.0,
+            });
+        }
+    }
+
+    fn create_light_texture() -> Texture2D {
+        let size = Self::LIGHT_TEXTURE_SIZE;
+        let mut image = Image::gen_image_color(size, size, BLANK);
+
+        let center = vec2(size as f32, size as f32) / 2.0;
+        for y in 0..size as u32 {
+            for x in 0..size as u32 {
+                let distance = (vec2(x as f32, y as f32) + 0.5).distance(center) / center.x;
+                let intensity = (1.0 - distance).clamp(0.0, 1.0);
+
+                image.set_pixel(x, y, Color::new(1.0, 1.0, 1.0, intensity * intensity));
+            }
+        }
+
+        let texture = Texture2D::from_image(&image);
+        texture.set_filter(FilterMode::Linear);
+
+        texture
+    }
+
+    fn create_material(color_blend: BlendState) -> Material {
+        load_material(
+            VERTEX_SHADER,
+            FRAGMENT_SHADER,
+            MaterialParams {
+                pipeline_params: PipelineParams {
+                    color_blend: Some(color_blend),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    }
+
+    fn draw_light(&self, light: &Light, intensity: f32) {
+        let size = light.radius * 2.0;
+        let color = Color::new(light.color.r, light.color.g, light.color.b, intensity);
+
+        draw_texture_ex(
+            self.light_texture,
+            light.position.x - light.radius,
+            light.position.y - light.radius,
+            color,
+            DrawTextureParams {
+                dest_size: Some(vec2(size, size)),
+                ..Default::default()
+            },
+        );
+    }
+}
+
+impl scene::Node for Lighting {
+    fn update(mut node: RefMut<Self>) {
+        let dt = get_frame_time();
+
+        for flash in &mut node.flashes {
+            flash.timer += dt;
+        }
+
+        node.flashes.retain(|flash| flash.timer < flash.duration);
+    }
+
+    fn draw(node: RefMut<Self>) {
+        let _scope = ProfileScope::new("draw lighting");
+
+        let camera = match scene::find_node_by_type::<GameCamera>() {
+            Some(camera) => camera.get_macroquad_camera(),
+            None => return,
+        };
+
+        set_camera(&Camera2D {
+            render_target: Some(node.light_map),
+            ..camera
+        });
+
+        let ambient = 1.0 - node.darkness;
+        clear_background(Color::new(ambient, ambient, ambient, 1.0));
+
+        gl_use_material(node.additive_material);
+
+        for light in &node.lights {
+            node.draw_light(light, 1.0);
+        }
+
+        for player in scene::find_nodes_by_type::<Player>() {
+            let collider = player.body.get_collider_rect();
+
+            node.draw_light(
+                &Light {
+                    position: collider.point() + collider.size() / 2.0,
+                    radius: Self::PLAYER_LIGHT_RADIUS,
+                    color: Self::PLAYER_LIGHT_COLOR,
+                },
+                1.0,
+            );
+        }
+
+        for flash in &node.flashes {
+            node.draw_light(&flash.light, 1.0 - flash.timer / flash.duration);
+        }
+
+        set_default_camera();
+
+        gl_use_material(node.multiply_material);
+
+        draw_texture_ex(
+            node.light_map.texture,
+            0.0,
+            0.0,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(screen_width(), screen_height())),
+                flip_y: true,
+                ..Default::default()
+            },
+        );
+
+        gl_use_default_material();
+
+        set_camera(&camera);
+    }
+}
@@ -0,0 +1,162 @@
+//! A simple string table localization system. Every language is a JSON file, holding an object
+//! that maps string keys to translated strings, and the languages are listed in the
+//! `localization` resource file. User facing text should always be looked up with `tr`, or with
+//! `tr_args`, for strings with arguments, in stead of being hard-coded.
+//!
+//! Strings that are missing from the current language fall back to the default language, and
+//! keys that are missing from both are returned as is, so unfinished translations are still
+//! usable. The pseudo-locale, which is available in debug builds, transforms every string, so
+//! that any text that is not passed through `tr` stands out.
+
+use std::collections::HashMap;
+
+use macroquad::experimental::collections::storage;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Resources;
+
+/// The language used when no language is configured, and for strings that are missing from the
+/// current language
+pub const DEFAULT_LANGUAGE_ID: &str = "en";
+
+/// The id of the pseudo-locale, which is not listed in the resource file
+pub const PSEUDO_LANGUAGE_ID: &str = "pseudo";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LanguageMetadata {
+    pub id: String,
+    /// The name of the language, in the language itself
+    pub name: String,
+    pub path: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Language {
+    pub id: String,
+    pub name: String,
+    pub strings: HashMap<String, String>,
+}
+
+static mut CURRENT_LANGUAGE: Option<String> = None;
+
+/// Returns the id of the current language
+pub fn get_language() -> String {
+    unsafe { CURRENT_LANGUAGE.clone() }.unwrap_or_else(|| DEFAULT_LANGUAGE_ID.to_string())
+}
+
+/// This sets the current language. Strings will be looked up in the new language immediately,
+/// but text that has been cached, like the entries of an open menu, will not change until it is
+/// rebuilt.
+pub fn set_language(id: &str) {
+    unsafe { CURRENT_LANGUAGE = Some(id.to_string()) };
+}
+
+/// Returns the ids and names of all the languages that can be selected
+pub fn get_available_languages() -> Vec<(String, String)> {
+    let resources = storage::get::<Resources>();
+
+    #[allow(unused_mut)]
+    let mut res = resources
+        .languages
+        .iter()
+        .map(|language| (language.id.clone(), language.name.clone()))
+        .collect::<Vec<_>>();
+
+    #[cfg(debug_assertions)]
+    res.push((PSEUDO_LANGUAGE_ID.to_string(), "Pseudo-locale".to_string()));
+
+    res
+}
+
+/// Returns the string for `key` in the current language
+pub fn tr(key: &str) -> String {
+    let current = get_language();
+    let is_pseudo = current == PSEUDO_LANGUAGE_ID;
+
+    let res = {
+        let resources = match storage::try_get::<Resources>() {
+            Some(resources) => resources,
+            None => return key.to_string(),
+        };
+
+        let lookup = |id: &str| {
+            resources
+                .languages
+                .iter()
+                .find(|language| language.id == id)
+                .and_then(|language| language.strings.get(key))
+                .cloned()
+        };
+
+        lookup(&current)
+            .or_else(|| lookup(DEFAULT_LANGUAGE_ID))
+            .unwrap_or_else(|| key.to_string())
+    };
+
+    if is_pseudo {
+        to_pseudo_locale(&res)
+    } else {
+        res
+    }
+}
+
+/// Returns the string for `key` in the current language, with every `{n}` replaced by the `n`th
+/// of `args`
+pub fn tr_args(key: &str, args: &[&str]) -> String {
+    let mut res = tr(key);
+
+    for (i, arg) in args.iter().enumerate() {
+        res = res.replace(&format!("{{{}}}", i), arg);
+    }
+
+    res
+}
+
+/// This replaces letters with accented versions, pads the string and wraps it in brackets, so
+/// that localized text can be told apart from hard-coded text, and so that layouts can be checked
+/// for longer translations. Argument placeholders are left untouched.
+fn to_pseudo_locale(str: &str) -> String {
+    let mut res = String::with_capacity(str.len() * 2);
+    let mut is_in_placeholder = false;
+
+    res.push('[');
+
+    for c in str.chars() {
+        match c {
+            '{' => is_in_placeholder = true,
+            '}' => is_in_placeholder = false,
+            _ => {}
+        }
+
+        let c = if is_in_placeholder {
+            c
+        } else {
+            match c {
+                'a' => 'á',
+                'e' => 'é',
+                'i' => 'í',
+                'o' => 'ó',
+                'u' => 'ú',
+                'A' => 'Á',
+                'E' => 'É',
+                'I' => 'Í',
+                'O' => 'Ó',
+                'U' => 'Ú',
+                'c' => 'ç',
+                'n' => 'ñ',
+                _ => c,
+            }
+        };
+
+        res.push(c);
+    }
+
+    for _ in 0..(str.chars().count() / 3) {
+        res.push('~');
+    }
+
+    res.push(']');
+
+    res
+}
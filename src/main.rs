@@ -1,32 +1,49 @@
 use fishsticks::GamepadContext;
 
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use macroquad::{experimental::collections::storage, prelude::*};
 
+pub mod audio;
 mod capabilities;
 pub mod components;
 pub mod config;
+pub mod console;
+pub mod cosmetic_rand;
+pub mod cvars;
 mod decoration;
 pub mod editor;
 mod gui;
+mod interactive;
 mod items;
 pub mod json;
+mod lighting;
+pub mod localization;
 pub mod map;
 pub mod math;
+pub mod mods;
+pub mod mutators;
 mod noise;
 pub mod resources;
+pub mod scripting;
+pub mod symbol;
 pub mod text;
+mod weather;
 #[macro_use]
 pub mod error;
 #[cfg(debug_assertions)]
 pub mod debug;
 pub mod effects;
 pub mod events;
+pub mod frame_alloc;
 pub mod game;
+pub mod jobs;
 pub mod particles;
+pub mod physics;
 pub mod player;
+pub mod pool;
+pub mod profiler;
 
 pub mod input;
 
@@ -44,23 +61,35 @@ pub use items::{EquippedItem, Item, Sproinger, Weapon};
 pub use events::{dispatch_application_event, ApplicationEvent};
 
 pub use game::{
-    collect_input, create_game_scene, start_music, stop_music, GameCamera, GameInput,
-    GameInputScheme, GameScene, GameWorld, LocalGame,
+    clear_game_events, collect_input, create_game_scene, DamageReadout, GameCamera, GameInput,
+    GameInputScheme, GameScene, GameWorld, LocalGame, TimeScale, TrainingGame,
 };
 
 pub use particles::ParticleEmitters;
 
+pub use lighting::Lighting;
+
+pub use weather::{Weather, WeatherKind};
+
 pub use resources::Resources;
 
 pub use player::{Player, PlayerEventParams};
 
 pub use decoration::Decoration;
 
+pub use interactive::InteractiveObjects;
+
 use crate::effects::passive::init_passive_effects;
-use crate::resources::load_resources;
+use crate::game::{
+    init_game_event_listeners, run_balance_simulation, AttractMode, BalanceSimulationParams,
+    CutscenePlayer, InputPlayback, InputRecorder, InputRecording, ATTRACT_MODE_RECORDING_PATH,
+    RECORD_INPUT_ENV_VAR, REPLAY_ENV_VAR, SIMULATE_ENV_VAR, TITLE_INTRO_CUTSCENE_ID,
+};
+use crate::player::PlayerCharacterParams;
+use crate::resources::{load_resources, MapResource};
 pub use effects::{
-    ActiveEffectCoroutine, ActiveEffectKind, ActiveEffectParams, PassiveEffectInstance,
-    PassiveEffectParams, Projectiles, TriggeredEffects,
+    ActiveEffectCoroutine, ActiveEffectKind, ActiveEffectParams, AreaEffects, Deployables,
+    PassiveEffectInstance, PassiveEffectParams, Projectiles, TriggeredEffects,
 };
 
 pub type CollisionWorld = macroquad_platformer::World;
@@ -70,6 +99,16 @@ const CONFIG_FILE_ENV_VAR: &str = "FISHFIGHT_CONFIG";
 
 const WINDOW_TITLE: &str = "FishFight";
 
+/// Restart the current local match, or training session
+pub fn restart_match() {
+    ApplicationEvent::RestartMatch.dispatch();
+}
+
+/// Start a new local match on the map with the given index in `Resources::maps`
+pub fn next_map(map_index: usize) {
+    ApplicationEvent::NextMap(map_index).dispatch();
+}
+
 /// Exit to main menu
 pub fn exit_to_main_menu() {
     ApplicationEvent::MainMenu.dispatch();
@@ -80,31 +119,299 @@ pub fn quit_to_desktop() {
     ApplicationEvent::Quit.dispatch()
 }
 
+fn register_console_commands() {
+    console::register("load_map", |args| {
+        let name = args.join(" ");
+        if name.is_empty() {
+            return Err(formaterr!("Usage: load_map <name or index>"));
+        }
+
+        let map_cnt = storage::get::<Resources>().maps.len();
+
+        let map_index = name
+            .parse::<usize>()
+            .ok()
+            .filter(|i| *i < map_cnt)
+            .or_else(|| {
+                storage::get::<Resources>()
+                    .maps
+                    .iter()
+                    .position(|map| map.meta.name.eq_ignore_ascii_case(&name))
+            });
+
+        let map_index = map_index.ok_or_else(|| formaterr!("Invalid map '{}'", name))?;
+        next_map(map_index);
+
+        Ok(String::new())
+    });
+
+    console::register("restart", |_| {
+        restart_match();
+        Ok(String::new())
+    });
+}
+
+/// The name of the directory, within the data directory of the user, that user data is stored in
+const USER_DATA_DIR_NAME: &str = "fishfight";
+
+/// Returns the directory that user data, like settings, should be stored in, following the
+/// conventions of the platform, or `None` if it could not be determined from the environment
+#[cfg_attr(debug_assertions, allow(dead_code))]
+pub fn get_user_data_dir() -> Option<PathBuf> {
+    #[cfg(target_os = "windows")]
+    let dir = env::var("APPDATA").ok().map(PathBuf::from);
+    #[cfg(target_os = "macos")]
+    let dir = env::var("HOME")
+        .ok()
+        .map(|home| PathBuf::from(home).join("Library/Application Support"));
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let dir = env::var("XDG_DATA_HOME")
+        .ok()
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var("HOME")
+                .ok()
+                .map(|home| PathBuf::from(home).join(".local/share"))
+        });
+
+    dir.map(|dir| dir.join(USER_DATA_DIR_NAME))
+}
+
+/// Returns the path of the config file, which can be set with the `FISHFIGHT_CONFIG` env var.
+/// Release builds will store it in the user data directory, if one is found, while debug builds
+/// will use the one in the crate root.
+pub fn get_config_path() -> PathBuf {
+    env::var(CONFIG_FILE_ENV_VAR)
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| {
+            #[cfg(debug_assertions)]
+            return PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("config.json");
+            #[cfg(not(debug_assertions))]
+            return get_user_data_dir()
+                .map(|dir| dir.join("config.json"))
+                .unwrap_or_else(|| PathBuf::from("./config.json"));
+        })
+}
+
 fn window_conf() -> Conf {
-    let config = Config::load(
-        env::var(CONFIG_FILE_ENV_VAR)
-            .map(PathBuf::from)
-            .unwrap_or_else(|_| {
-                #[cfg(debug_assertions)]
-                return PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("config.json");
-                #[cfg(not(debug_assertions))]
-                return PathBuf::from("./config.json");
-            }),
-    )
-    .unwrap();
+    let config = Config::load(get_config_path()).unwrap();
 
     storage::store(config.clone());
 
+    let (window_width, window_height) = config.get_window_size();
+
     Conf {
         window_title: WINDOW_TITLE.to_owned(),
         high_dpi: config.high_dpi,
         fullscreen: config.fullscreen,
-        window_width: config.resolution.width,
-        window_height: config.resolution.height,
+        window_width,
+        window_height,
         ..Default::default()
     }
 }
 
+/// This creates the scene of a local match and starts its music and sound recording
+fn start_local_match(
+    map_resource: &MapResource,
+    player_characters: &[PlayerCharacterParams],
+    player_input: &[GameInputScheme],
+) {
+    audio::start_match_music(&map_resource.meta);
+    audio::start_sound_recording();
+
+    // The seed is kept by input recordings, so that the match can be reproduced when played back
+    let seed = (macroquad::miniquad::date::now() * 1000.0) as u64;
+    rand::srand(seed);
+
+    let players = create_game_scene(map_resource.map.clone(), player_characters.to_vec(), true);
+
+    let mut local_game = LocalGame::new(player_input.to_vec(), players, &map_resource.meta.path);
+
+    if let Some(id) = &map_resource.meta.intro_cutscene {
+        let cutscene = storage::get::<Resources>().cutscenes.get(id).cloned();
+
+        match cutscene {
+            Some(cutscene) => {
+                scene::add_node(CutscenePlayer::new(cutscene));
+            }
+            None => println!(
+                "WARNING: Map '{}': Invalid cutscene id '{}'",
+                &map_resource.meta.name, id
+            ),
+        }
+    }
+
+    if let Ok(path) = env::var(RECORD_INPUT_ENV_VAR) {
+        let character_ids = player_characters
+            .iter()
+            .map(|params| params.id.clone())
+            .collect::<Vec<_>>();
+
+        let recording = InputRecording::new(seed, &map_resource.meta.path, &character_ids);
+        local_game = local_game.with_recorder(InputRecorder::new(path, recording));
+    }
+
+    scene::add_node(local_game);
+}
+
+/// This creates the scene of a local match from an input recording and plays it back
+fn start_playback(recording: &InputRecording) -> Result<()> {
+    let (map_resource, player_characters) = {
+        let resources = storage::get::<Resources>();
+
+        let map_resource = resources
+            .maps
+            .iter()
+            .find(|map_resource| map_resource.meta.path == recording.map_path)
+            .cloned()
+            .ok_or_else(|| {
+                formaterr!(
+                    "Input playback: The map '{}' was not found",
+                    &recording.map_path
+                )
+            })?;
+
+        let mut player_characters = Vec::new();
+        for character_id in &recording.player_characters {
+            let params = resources
+                .player_characters
+                .iter()
+                .find(|params| params.id == *character_id)
+                .cloned()
+                .ok_or_else(|| {
+                    formaterr!(
+                        "Input playback: The player character '{}' was not found",
+                        character_id
+                    )
+                })?;
+
+            player_characters.push(params);
+        }
+
+        (map_resource, player_characters)
+    };
+
+    audio::start_match_music(&map_resource.meta);
+    audio::start_sound_playback(recording.sound_events.clone());
+
+    rand::srand(recording.seed);
+
+    let players = create_game_scene(map_resource.map.clone(), player_characters, true);
+
+    // The input schemes are only used for the votes at the end of the match
+    let player_input = (0..players.len())
+        .map(|i| {
+            if i % 2 == 0 {
+                GameInputScheme::KeyboardLeft
+            } else {
+                GameInputScheme::KeyboardRight
+            }
+        })
+        .collect();
+
+    let local_game = LocalGame::new(player_input, players, &map_resource.meta.path)
+        .with_playback(InputPlayback::new(recording.clone()));
+
+    scene::add_node(local_game);
+
+    Ok(())
+}
+
+/// This creates the scene of the attract mode, which plays back the input recording that is
+/// bundled with the assets, or shows a match between two bots on a random map, if there is none
+fn start_attract_mode() -> Result<()> {
+    let recording_path =
+        Path::new(&storage::get::<Resources>().assets_dir).join(ATTRACT_MODE_RECORDING_PATH);
+
+    if let Ok(recording) = InputRecording::load(recording_path) {
+        start_playback(&recording)?;
+    } else {
+        let (map_resource, player_characters) = {
+            let resources = storage::get::<Resources>();
+
+            let map_cnt = resources.maps.len();
+            let character_cnt = resources.player_characters.len();
+
+            if map_cnt == 0 || character_cnt < 2 {
+                return Err(formaterr!(
+                    "Attract mode: There must be at least one map and two player characters"
+                ));
+            }
+
+            rand::srand((macroquad::miniquad::date::now() * 1000.0) as u64);
+
+            let map_resource = resources.maps[rand::gen_range(0, map_cnt)].clone();
+
+            let first = rand::gen_range(0, character_cnt);
+            let second = (first + rand::gen_range(1, character_cnt)) % character_cnt;
+
+            let player_characters = vec![
+                resources.player_characters[first].clone(),
+                resources.player_characters[second].clone(),
+            ];
+
+            (map_resource, player_characters)
+        };
+
+        audio::start_match_music(&map_resource.meta);
+
+        let players = create_game_scene(map_resource.map.clone(), player_characters, true);
+
+        for player in &players {
+            scene::get_node(*player).set_ai_enabled(true);
+        }
+
+        // The input schemes are only used for the votes at the end of the match, which the
+        // attract mode returns to the main menu before
+        let player_input = vec![
+            GameInputScheme::KeyboardLeft,
+            GameInputScheme::KeyboardRight,
+        ];
+
+        scene::add_node(LocalGame::new(
+            player_input,
+            players,
+            &map_resource.meta.path,
+        ));
+    }
+
+    scene::add_node(AttractMode::new());
+
+    Ok(())
+}
+
+/// This creates the scene of a training session, on the training map, with a training dummy as
+/// the second player
+fn start_training(player_character: &PlayerCharacterParams, input_scheme: GameInputScheme) {
+    let (map_resource, dummy_character) = {
+        let resources = storage::get::<Resources>();
+
+        // The dummy uses another character than the player, if there is one, to tell them apart
+        let dummy_character = resources
+            .player_characters
+            .iter()
+            .find(|params| params.id != player_character.id)
+            .unwrap_or(player_character)
+            .clone();
+
+        (resources.training_map.clone(), dummy_character)
+    };
+
+    audio::start_match_music(&map_resource.meta);
+
+    let players = create_game_scene(
+        map_resource.map,
+        vec![player_character.clone(), dummy_character],
+        true,
+    );
+
+    let dummy_id = scene::get_node(players[1]).id;
+
+    scene::add_node(DamageReadout::new(dummy_id));
+    scene::add_node(TrainingGame::new(input_scheme, players[0], players[1]));
+}
+
 #[macroquad::main(window_conf)]
 async fn main() -> Result<()> {
     use events::iter_events;
@@ -116,68 +423,129 @@ async fn main() -> Result<()> {
 
     load_resources(&assets_dir).await;
 
+    localization::set_language(&storage::get::<Config>().language);
+
     {
         let gamepad_system = fishsticks::GamepadContext::init().unwrap();
         storage::store(gamepad_system);
     }
 
+    cvars::init_cvars();
     init_passive_effects();
+    init_game_event_listeners();
+    game::init_game_modes();
+    console::init_console_commands();
 
-    'outer: loop {
-        match gui::show_main_menu().await {
-            MainMenuResult::LocalGame(player_input) => {
-                let player_cnt = player_input.len();
+    // A balance simulation given by env var is run in stead of the game, which quits when done
+    if let Ok(path) = env::var(SIMULATE_ENV_VAR) {
+        let params = BalanceSimulationParams::load(&path)?;
+        run_balance_simulation(&params).await?;
 
-                assert_eq!(
-                    player_cnt, 2,
-                    "Local Game: There should be two player input schemes for this game mode"
-                );
+        return Ok(());
+    }
 
-                let player_characters =
-                    gui::show_select_characters_menu(player_input.clone()).await;
+    // A recording given by env var is played back on startup, in stead of showing the main menu
+    let mut pending_playback = match env::var(REPLAY_ENV_VAR) {
+        Ok(path) => Some(InputRecording::load(path)?),
+        Err(_) => None,
+    };
 
-                assert_eq!(
+    audio::start_music(audio::MENU_MUSIC_ID);
+
+    if pending_playback.is_none() {
+        let has_intro = storage::get::<Resources>()
+            .cutscenes
+            .contains_key(TITLE_INTRO_CUTSCENE_ID);
+
+        if has_intro {
+            game::play_cutscene(TITLE_INTRO_CUTSCENE_ID).await;
+        }
+    }
+
+    'outer: loop {
+        audio::start_music(audio::MENU_MUSIC_ID);
+
+        // The map, characters and input schemes of the current local match, kept for restarts
+        let mut local_match = None;
+        // The character and input scheme of the current training session, kept for restarts
+        let mut training = None;
+        // The recording that is currently played back, kept for restarts
+        let mut playback = None;
+
+        if let Some(recording) = pending_playback.take() {
+            start_playback(&recording)?;
+            playback = Some(recording);
+        } else {
+            match gui::show_main_menu().await {
+                MainMenuResult::LocalGame(player_input) => {
+                    let player_cnt = player_input.len();
+
+                    assert_eq!(
+                        player_cnt, 2,
+                        "Local Game: There should be two player input schemes for this game mode"
+                    );
+
+                    let player_characters =
+                        gui::show_select_characters_menu(player_input.clone()).await;
+
+                    assert_eq!(
                     player_cnt,
                     player_characters.len(),
                     "Local Game: Amount of player character params does not match the amount of players"
                 );
 
-                let map_resource = gui::show_select_map_menu().await;
+                    let map_resource = gui::show_select_map_menu().await;
 
-                let players = create_game_scene(map_resource.map, player_characters, true);
-                scene::add_node(LocalGame::new(player_input, players[0], players[1]));
+                    game::clear_round_wins();
 
-                start_music("fish_tide");
-            }
-            MainMenuResult::Editor {
-                input_scheme,
-                is_new_map,
-            } => {
-                let map_resource = if is_new_map {
-                    let res = gui::show_create_map_menu().await?;
-                    if res.is_none() {
-                        continue 'outer;
-                    }
+                    start_local_match(&map_resource, &player_characters, &player_input);
 
-                    res.unwrap()
-                } else {
-                    gui::show_select_map_menu().await
-                };
+                    local_match = Some((map_resource, player_characters, player_input));
+                }
+                MainMenuResult::Training(input_scheme) => {
+                    let player_characters =
+                        gui::show_select_characters_menu(vec![input_scheme]).await;
 
-                let position = map_resource.map.get_size() * 0.5;
+                    game::clear_round_wins();
 
-                scene::add_node(EditorCamera::new(position));
-                scene::add_node(Editor::new(input_scheme, map_resource));
-            }
-            MainMenuResult::ReloadResources => {
-                let resources = storage::get::<Resources>();
-                load_resources(&resources.assets_dir).await;
-                continue 'outer;
-            }
-            MainMenuResult::Quit => {
-                quit_to_desktop();
-            }
-        };
+                    start_training(&player_characters[0], input_scheme);
+
+                    training = Some((player_characters[0].clone(), input_scheme));
+                }
+                MainMenuResult::Editor {
+                    input_scheme,
+                    is_new_map,
+                } => {
+                    let map_resource = if is_new_map {
+                        let res = gui::show_create_map_menu().await?;
+                        if res.is_none() {
+                            continue 'outer;
+                        }
+
+                        res.unwrap()
+                    } else {
+                        gui::show_select_map_menu().await
+                    };
+
+                    let position = map_resource.map.get_size() * 0.5;
+
+                    scene::add_node(EditorCamera::new(position));
+                    scene::add_node(Editor::new(input_scheme, map_resource));
+                    scene::add_node(gui::ToastOverlay::new());
+                }
+                MainMenuResult::AttractMode => {
+                    start_attract_mode()?;
+                }
+                MainMenuResult::ReloadResources => {
+                    let resources = storage::get::<Resources>();
+                    load_resources(&resources.assets_dir).await;
+                    continue 'outer;
+                }
+                MainMenuResult::Quit => {
+                    quit_to_desktop();
+                }
+            };
+        }
 
         'inner: loop {
             #[allow(clippy::never_loop)]
@@ -187,6 +555,40 @@ async fn main() -> Result<()> {
                         let resources = storage::get::<Resources>();
                         load_resources(&resources.assets_dir).await;
                     }
+                    ApplicationEvent::RestartMatch => {
+                        if let Some((map_resource, player_characters, player_input)) = &local_match
+                        {
+                            scene::clear();
+                            clear_game_events();
+                            audio::stop_sound_event_log();
+
+                            start_local_match(map_resource, player_characters, player_input);
+                        } else if let Some((player_character, input_scheme)) = &training {
+                            scene::clear();
+                            clear_game_events();
+
+                            start_training(player_character, *input_scheme);
+                        } else if let Some(recording) = &playback {
+                            scene::clear();
+                            clear_game_events();
+                            audio::stop_sound_event_log();
+
+                            start_playback(recording)?;
+                        }
+                    }
+                    ApplicationEvent::NextMap(map_index) => {
+                        if let Some((map_resource, player_characters, player_input)) =
+                            &mut local_match
+                        {
+                            scene::clear();
+                            clear_game_events();
+                            audio::stop_sound_event_log();
+
+                            *map_resource = storage::get::<Resources>().maps[map_index].clone();
+
+                            start_local_match(map_resource, player_characters, player_input);
+                        }
+                    }
                     ApplicationEvent::MainMenu => break 'inner,
                     ApplicationEvent::Quit => break 'outer,
                 }
@@ -194,14 +596,20 @@ async fn main() -> Result<()> {
 
             {
                 let mut gamepad_system = storage::get_mut::<GamepadContext>();
-                gamepad_system.update()?;
+                input::update_gamepad_context(Some(&mut gamepad_system))?;
             }
 
+            audio::update_music();
+
+            profiler::end_frame();
+            frame_alloc::end_frame();
+
             next_frame().await;
         }
 
         scene::clear();
-        stop_music();
+        clear_game_events();
+        audio::stop_sound_event_log();
     }
 
     Ok(())
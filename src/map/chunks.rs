@@ -0,0 +1,361 @@
+//! The tiles of a tile layer are stored in square chunks of `CHUNK_SIZE` by `CHUNK_SIZE` tiles,
+//! so that the tiles in an area of the map, like the part that is in view, can be found without
+//! iterating over every tile in the layer.
+//!
+//! Every chunk keeps a mesh of its tiles, with one batch of quads per texture, which is drawn in
+//! stead of drawing its tiles one by one. The mesh is rebuilt, the next time that the chunk is
+//! drawn, whenever a tile in the chunk is changed, like when it is destroyed by an explosion or
+//! placed in the editor, which marks the chunk as dirty. Animated tiles are not part of the mesh,
+//! as their texture coords change over time, so they are drawn individually, every frame.
+
+use std::{
+    cell::RefCell,
+    fmt,
+    ops::{Index, IndexMut},
+};
+
+use macroquad::{
+    color,
+    models::{draw_mesh, Mesh, Vertex},
+    prelude::*,
+};
+
+use crate::{math::URect, symbol::Symbol, Resources};
+
+use super::{Map, MapTile};
+
+/// The width and height, in tiles, of a chunk
+pub const CHUNK_SIZE: u32 = 16;
+
+/// The mesh of the tiles of a chunk, which is rebuilt when the chunk is dirty
+struct ChunkMesh {
+    is_dirty: bool,
+    /// One mesh per texture used by the tiles of the chunk
+    batches: Vec<Mesh>,
+    /// The indices, in the chunk, of the tiles that are animated
+    animated: Vec<usize>,
+}
+
+impl ChunkMesh {
+    fn new() -> Self {
+        ChunkMesh {
+            is_dirty: true,
+            batches: Vec::new(),
+            animated: Vec::new(),
+        }
+    }
+}
+
+/// Meshes are not cloned, as they are rebuilt from the tiles when a cloned chunk is drawn
+impl Clone for ChunkMesh {
+    fn clone(&self) -> Self {
+        ChunkMesh::new()
+    }
+}
+
+impl fmt::Debug for ChunkMesh {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChunkMesh")
+            .field("is_dirty", &self.is_dirty)
+            .field("batches", &self.batches.len())
+            .field("animated", &self.animated)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct TileChunk {
+    /// The tiles of the chunk, row by row. Chunks on the right and bottom edges of a map, that is
+    /// not a multiple of `CHUNK_SIZE` in size, are padded with `None`.
+    tiles: Vec<Option<MapTile>>,
+    mesh: RefCell<ChunkMesh>,
+}
+
+impl TileChunk {
+    fn new() -> Self {
+        TileChunk {
+            tiles: vec![None; (CHUNK_SIZE * CHUNK_SIZE) as usize],
+            mesh: RefCell::new(ChunkMesh::new()),
+        }
+    }
+
+    fn mark_dirty(&mut self) {
+        self.mesh.get_mut().is_dirty = true;
+    }
+
+    fn rebuild_mesh(&self, mesh: &mut ChunkMesh, origin: Vec2, map: &Map, resources: &Resources) {
+        let mut batches: Vec<(Symbol, Mesh)> = Vec::new();
+
+        mesh.animated.clear();
+
+        for (i, tile) in self.tiles.iter().enumerate() {
+            let tile = match tile {
+                Some(tile) => tile,
+                None => continue,
+            };
+
+            let is_animated = map
+                .tilesets
+                .get(&tile.tileset_id)
+                .map(|tileset| tileset.tile_animations.contains_key(&tile.tile_id))
+                .unwrap_or(false);
+
+            if is_animated {
+                mesh.animated.push(i);
+                continue;
+            }
+
+            let texture = resources
+                .textures
+                .get(&tile.texture_id)
+                .unwrap_or_else(|| panic!("No texture with id '{}'!", tile.texture_id))
+                .texture;
+
+            let batch = match batches
+                .iter()
+                .position(|(texture_id, _)| *texture_id == tile.texture_id)
+            {
+                Some(j) => &mut batches[j].1,
+                None => {
+                    batches.push((
+                        tile.texture_id,
+                        Mesh {
+                            vertices: Vec::new(),
+                            indices: Vec::new(),
+                            texture: Some(texture),
+                        },
+                    ));
+
+                    &mut batches.last_mut().unwrap().1
+                }
+            };
+
+            let position = origin + get_local_coords(i).as_f32() * map.tile_size;
+
+            let texture_size = vec2(texture.width(), texture.height());
+            let uv_min = tile.texture_coords / texture_size;
+            let uv_max = (tile.texture_coords + map.tile_size) / texture_size;
+
+            let corners = [
+                (vec2(0.0, 0.0), vec2(uv_min.x, uv_min.y)),
+                (vec2(1.0, 0.0), vec2(uv_max.x, uv_min.y)),
+                (vec2(1.0, 1.0), vec2(uv_max.x, uv_max.y)),
+                (vec2(0.0, 1.0), vec2(uv_min.x, uv_max.y)),
+            ];
+
+            let first = batch.vertices.len() as u16;
+
+            for (corner, uv) in corners.iter() {
+                batch.vertices.push(Vertex {
+                    position: (position + *corner * map.tile_size).extend(0.0),
+                    uv: *uv,
+                    color: color::WHITE,
+                });
+            }
+
+            batch.indices.extend_from_slice(&[
+                first,
+                first + 1,
+                first + 2,
+                first,
+                first + 2,
+                first + 3,
+            ]);
+        }
+
+        mesh.batches = batches.into_iter().map(|(_, batch)| batch).collect();
+        mesh.is_dirty = false;
+    }
+
+    /// This draws the chunk, with its top left corner at `origin`, in world space, rebuilding its
+    /// mesh first, if it is dirty
+    fn draw(&self, origin: Vec2, map: &Map, resources: &Resources) {
+        let mut mesh = self.mesh.borrow_mut();
+
+        if mesh.is_dirty {
+            self.rebuild_mesh(&mut mesh, origin, map, resources);
+        }
+
+        for batch in &mesh.batches {
+            draw_mesh(batch);
+        }
+
+        for &i in &mesh.animated {
+            if let Some(tile) = &self.tiles[i] {
+                let texture_coords = map
+                    .tilesets
+                    .get(&tile.tileset_id)
+                    .and_then(|tileset| tileset.get_animated_texture_coords(tile.tile_id))
+                    .unwrap_or(tile.texture_coords);
+
+                let texture = resources
+                    .textures
+                    .get(&tile.texture_id)
+                    .unwrap_or_else(|| panic!("No texture with id '{}'!", tile.texture_id))
+                    .texture;
+
+                let position = origin + get_local_coords(i).as_f32() * map.tile_size;
+
+                draw_texture_ex(
+                    texture,
+                    position.x,
+                    position.y,
+                    color::WHITE,
+                    DrawTextureParams {
+                        source: Some(Rect::new(
+                            texture_coords.x,
+                            texture_coords.y,
+                            map.tile_size.x,
+                            map.tile_size.y,
+                        )),
+                        dest_size: Some(map.tile_size),
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// Returns the coords, within a chunk, of the tile at index `i` of the chunk
+fn get_local_coords(i: usize) -> UVec2 {
+    uvec2(i as u32 % CHUNK_SIZE, i as u32 / CHUNK_SIZE)
+}
+
+/// The tiles of a tile layer, stored in chunks. Tiles are indexed like they would be in a single
+/// `Vec`, row by row, over the whole grid, so `map.to_index(coords)` gives the index of the tile
+/// at `coords`.
+#[derive(Debug, Clone, Default)]
+pub struct TileChunks {
+    grid_size: UVec2,
+    /// The amount of chunks on each axis
+    chunk_cnt: UVec2,
+    chunks: Vec<TileChunk>,
+}
+
+impl TileChunks {
+    /// This creates an empty grid of `grid_size` tiles
+    pub fn new(grid_size: UVec2) -> Self {
+        let chunk_cnt = uvec2(
+            (grid_size.x + CHUNK_SIZE - 1) / CHUNK_SIZE,
+            (grid_size.y + CHUNK_SIZE - 1) / CHUNK_SIZE,
+        );
+
+        let mut chunks = Vec::new();
+        chunks.resize_with((chunk_cnt.x * chunk_cnt.y) as usize, TileChunk::new);
+
+        TileChunks {
+            grid_size,
+            chunk_cnt,
+            chunks,
+        }
+    }
+
+    /// This creates a grid of `grid_size` tiles from `tiles`, which should be row by row. If
+    /// `tiles` is empty, like for object layers, the grid will be empty as well.
+    pub fn from_tiles(grid_size: UVec2, tiles: Vec<Option<MapTile>>) -> Self {
+        if tiles.is_empty() {
+            return TileChunks::default();
+        }
+
+        let mut res = TileChunks::new(grid_size);
+
+        for (i, tile) in tiles.into_iter().enumerate().take(res.len()) {
+            res[i] = tile;
+        }
+
+        res
+    }
+
+    /// Returns the amount of tiles in the grid
+    pub fn len(&self) -> usize {
+        if self.chunks.is_empty() {
+            0
+        } else {
+            (self.grid_size.x * self.grid_size.y) as usize
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the index of the chunk, and the index within that chunk, of the tile at index `i`
+    fn locate(&self, i: usize) -> Option<(usize, usize)> {
+        if i >= self.len() {
+            return None;
+        }
+
+        let x = i as u32 % self.grid_size.x;
+        let y = i as u32 / self.grid_size.x;
+
+        let chunk = (y / CHUNK_SIZE) * self.chunk_cnt.x + x / CHUNK_SIZE;
+        let local = (y % CHUNK_SIZE) * CHUNK_SIZE + x % CHUNK_SIZE;
+
+        Some((chunk as usize, local as usize))
+    }
+
+    pub fn get(&self, i: usize) -> Option<&Option<MapTile>> {
+        self.locate(i)
+            .map(|(chunk, local)| &self.chunks[chunk].tiles[local])
+    }
+
+    /// Returns the tile at index `i`, marking its chunk as dirty, as the tile may be changed
+    pub fn get_mut(&mut self, i: usize) -> Option<&mut Option<MapTile>> {
+        let (chunk, local) = self.locate(i)?;
+
+        let chunk = &mut self.chunks[chunk];
+        chunk.mark_dirty();
+
+        Some(&mut chunk.tiles[local])
+    }
+
+    /// This replaces the tile at index `i` with `tile` and returns the tile that was replaced
+    pub fn replace(&mut self, i: usize, tile: Option<MapTile>) -> Option<MapTile> {
+        std::mem::replace(&mut self[i], tile)
+    }
+
+    /// Returns all the tiles in the grid, row by row
+    pub fn iter(&self) -> impl Iterator<Item = &Option<MapTile>> {
+        (0..self.len()).map(move |i| &self[i])
+    }
+
+    /// This draws all the chunks that contain any of the tiles in `rect`
+    pub fn draw(&self, rect: URect, map: &Map, resources: &Resources) {
+        if self.is_empty() {
+            return;
+        }
+
+        let min = rect.point() / CHUNK_SIZE;
+        let max = uvec2(
+            (rect.x + rect.w + CHUNK_SIZE - 1) / CHUNK_SIZE,
+            (rect.y + rect.h + CHUNK_SIZE - 1) / CHUNK_SIZE,
+        )
+        .min(self.chunk_cnt);
+
+        for y in min.y..max.y {
+            for x in min.x..max.x {
+                let chunk = &self.chunks[(y * self.chunk_cnt.x + x) as usize];
+                let origin = map.to_position(uvec2(x, y) * CHUNK_SIZE);
+
+                chunk.draw(origin, map, resources);
+            }
+        }
+    }
+}
+
+impl Index<usize> for TileChunks {
+    type Output = Option<MapTile>;
+
+    fn index(&self, i: usize) -> &Self::Output {
+        self.get(i)
+            .unwrap_or_else(|| panic!("Tile index {} is out of bounds", i))
+    }
+}
+
+impl IndexMut<usize> for TileChunks {
+    fn index_mut(&mut self, i: usize) -> &mut Self::Output {
+        self.get_mut(i)
+            .unwrap_or_else(|| panic!("Tile index {} is out of bounds", i))
+    }
+}
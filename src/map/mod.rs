@@ -1,6 +1,10 @@
+mod chunks;
+
+pub use chunks::TileChunks;
+
 use std::{collections::HashMap, path::Path};
 
-use macroquad::{color, experimental::collections::storage, prelude::*};
+use macroquad::{experimental::collections::storage, prelude::*};
 
 use serde::{Deserialize, Serialize};
 
@@ -11,6 +15,7 @@ use crate::{
     editor::gui::combobox::ComboBoxValue,
     json::{self, TiledMap},
     math::URect,
+    symbol::Symbol,
     Resources,
 };
 
@@ -18,14 +23,14 @@ pub type MapProperty = json::GenericParam;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MapBackgroundLayer {
-    pub texture_id: String,
+    pub texture_id: Symbol,
     pub depth: f32,
     #[serde(with = "json::vec2_def")]
     pub offset: Vec2,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(into = "json::MapDef", from = "json::MapDef")]
+#[serde(into = "json::MapDef", try_from = "json::MapDef")]
 pub struct Map {
     #[serde(default = "Map::default_background_color", with = "json::ColorDef")]
     pub background_color: Color,
@@ -50,6 +55,69 @@ pub struct Map {
 impl Map {
     pub const PLATFORM_TILE_ATTRIBUTE: &'static str = "jumpthrough";
 
+    /// Tiles with this attribute will be removed by explosions. This only applies to tiles in
+    /// layers without collision, as the static colliders of the collision world can not be
+    /// modified after it has been created.
+    pub const DESTRUCTIBLE_TILE_ATTRIBUTE: &'static str = "destructible";
+
+    /// Tiles with these attributes, in layers with collision, will move bodies standing on them
+    /// to the left or to the right, at the speed set by the `conveyor_speed` property of their
+    /// tileset
+    pub const CONVEYOR_LEFT_TILE_ATTRIBUTE: &'static str = "conveyor_left";
+    pub const CONVEYOR_RIGHT_TILE_ATTRIBUTE: &'static str = "conveyor_right";
+
+    pub const CONVEYOR_TILE_ATTRIBUTES: &'static [&'static str] = &[
+        Self::CONVEYOR_LEFT_TILE_ATTRIBUTE,
+        Self::CONVEYOR_RIGHT_TILE_ATTRIBUTE,
+    ];
+
+    pub const CONVEYOR_SPEED_TILESET_PROPERTY: &'static str = "conveyor_speed";
+    pub const DEFAULT_CONVEYOR_SPEED: f32 = 120.0;
+
+    /// Tiles with these attributes, in layers with collision, have a surface material that
+    /// affects the bodies that land or stand on them. The strength of each material is set by
+    /// the corresponding property of their tileset.
+    ///
+    /// Bouncy tiles, like trampolines, reflect the vertical velocity of bodies landing on them,
+    /// multiplied by the `bounciness` property.
+    pub const BOUNCY_TILE_ATTRIBUTE: &'static str = "bouncy";
+    /// Ice tiles replace the ground friction of bodies with the `ice_friction` property, and make
+    /// player movement accelerate gradually, in stead of instantly.
+    pub const ICE_TILE_ATTRIBUTE: &'static str = "ice";
+    /// Sticky tiles, like glue, slow down horizontal movement and jumps, by the fraction set by
+    /// the `stickiness` property.
+    pub const STICKY_TILE_ATTRIBUTE: &'static str = "sticky";
+
+    pub const MATERIAL_TILE_ATTRIBUTES: &'static [&'static str] = &[
+        Self::BOUNCY_TILE_ATTRIBUTE,
+        Self::ICE_TILE_ATTRIBUTE,
+        Self::STICKY_TILE_ATTRIBUTE,
+    ];
+
+    /// Tiles with these attributes, in layers with collision, select the sounds played by bodies
+    /// landing or walking on them. A sound effect id with a variant for the surface, like
+    /// `land_metal` for `land`, will be used in stead of the plain id, if it exists.
+    pub const METAL_TILE_ATTRIBUTE: &'static str = "metal";
+    pub const WOOD_TILE_ATTRIBUTE: &'static str = "wood";
+    pub const SAND_TILE_ATTRIBUTE: &'static str = "sand";
+    pub const WATER_TILE_ATTRIBUTE: &'static str = "water";
+
+    pub const SOUND_TILE_ATTRIBUTES: &'static [&'static str] = &[
+        Self::METAL_TILE_ATTRIBUTE,
+        Self::WOOD_TILE_ATTRIBUTE,
+        Self::SAND_TILE_ATTRIBUTE,
+        Self::WATER_TILE_ATTRIBUTE,
+    ];
+
+    pub const BOUNCINESS_TILESET_PROPERTY: &'static str = "bounciness";
+    pub const DEFAULT_BOUNCINESS: f32 = 1.0;
+
+    pub const ICE_FRICTION_TILESET_PROPERTY: &'static str = "ice_friction";
+    pub const DEFAULT_ICE_FRICTION: f32 = 0.005;
+
+    pub const STICKINESS_TILESET_PROPERTY: &'static str = "stickiness";
+    pub const DEFAULT_STICKINESS: f32 = 0.6;
+
     // Padding added to colliders for collision checks since the collision system stops movement
     // before collision is registered, if not.
     pub const COLLIDER_PADDING: f32 = 8.0;
@@ -87,7 +155,7 @@ impl Map {
         let bytes = load_file(&path.to_string_helper()).await?;
         let tiled_map: TiledMap = serde_json::from_slice(&bytes).unwrap();
 
-        let map = tiled_map.into_map();
+        let map = tiled_map.into_map()?;
 
         if let Some(export_path) = export_path {
             map.save(export_path).unwrap();
@@ -121,6 +189,29 @@ impl Map {
         URect::new(p.x, p.y, w, h)
     }
 
+    /// Returns the tiles that are within `rect`, in world space, including tiles that are only
+    /// partially within it, or `None` if `rect` does not overlap the map
+    pub fn get_overlapping_grid_rect(&self, rect: &Rect) -> Option<URect> {
+        let min = ((rect.point() - self.world_offset) / self.tile_size)
+            .floor()
+            .max(Vec2::ZERO);
+
+        let max = ((rect.point() + rect.size() - self.world_offset) / self.tile_size)
+            .ceil()
+            .min(self.grid_size.as_f32());
+
+        if min.x >= max.x || min.y >= max.y {
+            return None;
+        }
+
+        Some(URect::new(
+            min.x as u32,
+            min.y as u32,
+            (max.x - min.x) as u32,
+            (max.y - min.y) as u32,
+        ))
+    }
+
     pub fn to_coords(&self, position: Vec2) -> UVec2 {
         let x = (((position.x - self.world_offset.x) / self.tile_size.x) as u32)
             .clamp(0, self.grid_size.x - 1);
@@ -164,6 +255,60 @@ impl Map {
         MapTileIterator::new(layer, rect)
     }
 
+    /// This removes all destructible tiles that are within `circle`, from all tile layers without
+    /// collision, and returns the center position of each of the removed tiles.
+    pub fn destroy_tiles(&mut self, circle: &Circle) -> Vec<Vec2> {
+        let rect = Rect::new(
+            circle.x - circle.r,
+            circle.y - circle.r,
+            circle.r * 2.0,
+            circle.r * 2.0,
+        );
+
+        let grid = self.to_grid(&rect);
+
+        let mut res = Vec::new();
+
+        for y in grid.y..=grid.y + grid.h {
+            for x in grid.x..=grid.x + grid.w {
+                let coords = uvec2(x, y);
+                let i = self.to_index(coords);
+                let position = self.to_position(coords) + self.tile_size / 2.0;
+
+                if !circle.contains(&position) {
+                    continue;
+                }
+
+                let mut is_destroyed = false;
+
+                for layer in self.layers.values_mut() {
+                    if layer.kind != MapLayerKind::TileLayer || layer.has_collision {
+                        continue;
+                    }
+
+                    let is_destructible = layer.tiles[i]
+                        .as_ref()
+                        .map(|tile| {
+                            tile.attributes
+                                .contains(&Self::DESTRUCTIBLE_TILE_ATTRIBUTE.to_string())
+                        })
+                        .unwrap_or(false);
+
+                    if is_destructible {
+                        layer.tiles[i] = None;
+                        is_destroyed = true;
+                    }
+                }
+
+                if is_destroyed {
+                    res.push(position);
+                }
+            }
+        }
+
+        res
+    }
+
     pub fn get_collisions(&self, collider: &Rect, should_ignore_platforms: bool) -> Vec<Rect> {
         let collider = Rect::new(
             collider.x - Self::COLLIDER_PADDING,
@@ -256,8 +401,8 @@ impl Map {
         let rect = rect.unwrap_or_else(|| URect::new(0, 0, self.grid_size.x, self.grid_size.y));
 
         draw_rectangle(
-            self.world_offset.x,
-            self.world_offset.y,
+            self.world_offset.x + rect.x as f32 * self.tile_size.x,
+            self.world_offset.y + rect.y as f32 * self.tile_size.y,
             rect.w as f32 * self.tile_size.x,
             rect.h as f32 * self.tile_size.y,
             self.background_color,
@@ -304,7 +449,8 @@ impl Map {
         }
     }
 
-    /// This will draw the map
+    /// This will draw the map. If `rect` is specified, only the chunks that contain any of the
+    /// tiles within it are drawn.
     pub fn draw(&self, rect: Option<URect>, should_draw_background: bool) {
         if should_draw_background {
             self.draw_background(rect, false);
@@ -319,34 +465,7 @@ impl Map {
         for layer_id in draw_order {
             if let Some(layer) = self.layers.get(&layer_id) {
                 if layer.is_visible && layer.kind == MapLayerKind::TileLayer {
-                    for (x, y, tile) in self.get_tiles(&layer_id, Some(rect)) {
-                        if let Some(tile) = tile {
-                            let world_position = self.world_offset
-                                + vec2(x as f32 * self.tile_size.x, y as f32 * self.tile_size.y);
-
-                            let texture_entry =
-                                resources.textures.get(&tile.texture_id).unwrap_or_else(|| {
-                                    panic!("No texture with id '{}'!", tile.texture_id)
-                                });
-
-                            draw_texture_ex(
-                                texture_entry.texture,
-                                world_position.x,
-                                world_position.y,
-                                color::WHITE,
-                                DrawTextureParams {
-                                    source: Some(Rect::new(
-                                        tile.texture_coords.x, // + 0.1,
-                                        tile.texture_coords.y, // + 0.1,
-                                        self.tile_size.x,      // - 0.2,
-                                        self.tile_size.y,      // - 0.2,
-                                    )),
-                                    dest_size: Some(vec2(self.tile_size.x, self.tile_size.y)),
-                                    ..Default::default()
-                                },
-                            );
-                        }
-                    }
+                    layer.tiles.draw(rect, self, &resources);
                 }
             }
         }
@@ -457,21 +576,17 @@ impl Default for MapLayerKind {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A layer of a map. Layers are serialized as part of `json::MapDef`, which stores the tiles of
+/// tile layers as a flat list of tile ids.
+#[derive(Debug, Clone)]
 pub struct MapLayer {
     pub id: String,
     pub kind: MapLayerKind,
-    #[serde(default)]
     pub has_collision: bool,
-    #[serde(with = "json::uvec2_def")]
     pub grid_size: UVec2,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
-    pub tiles: Vec<Option<MapTile>>,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tiles: TileChunks,
     pub objects: Vec<MapObject>,
-    #[serde(default)]
     pub is_visible: bool,
-    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub properties: HashMap<String, MapProperty>,
 }
 
@@ -483,8 +598,7 @@ impl MapLayer {
             false
         };
 
-        let mut tiles = Vec::new();
-        tiles.resize((grid_size.x * grid_size.y) as usize, None);
+        let tiles = TileChunks::new(grid_size);
 
         MapLayer {
             id: id.to_string(),
@@ -504,7 +618,7 @@ impl Default for MapLayer {
             has_collision: false,
             kind: MapLayerKind::TileLayer,
             grid_size: UVec2::ZERO,
-            tiles: Vec::new(),
+            tiles: TileChunks::default(),
             objects: Vec::new(),
             is_visible: true,
             properties: HashMap::new(),
@@ -516,13 +630,102 @@ impl Default for MapLayer {
 pub struct MapTile {
     pub tile_id: u32,
     pub tileset_id: String,
-    pub texture_id: String,
+    pub texture_id: Symbol,
     #[serde(with = "json::vec2_def")]
     pub texture_coords: Vec2,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub attributes: Vec<String>,
 }
 
+/// The slope shapes that a tile in a layer with collision can have, set by giving the tile the
+/// corresponding attribute. Slope tiles are not part of the static colliders of the collision
+/// world, as those only support axis-aligned solids, but are resolved separately by the physics
+/// bodies and projectiles.
+///
+/// Gentle slopes span two tiles, so a `GentleRightLow` tile should be followed by a
+/// `GentleRightHigh` tile, to its right, and a `GentleLeftHigh` tile should be followed by a
+/// `GentleLeftLow` tile.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TileSlope {
+    /// A 45 degree slope, rising towards the right
+    Right,
+    /// A 45 degree slope, rising towards the left
+    Left,
+    /// The lower half of a gentle slope rising towards the right
+    GentleRightLow,
+    /// The upper half of a gentle slope rising towards the right
+    GentleRightHigh,
+    /// The upper half of a gentle slope rising towards the left
+    GentleLeftHigh,
+    /// The lower half of a gentle slope rising towards the left
+    GentleLeftLow,
+}
+
+impl TileSlope {
+    pub const RIGHT_ATTRIBUTE: &'static str = "slope_right";
+    pub const LEFT_ATTRIBUTE: &'static str = "slope_left";
+    pub const GENTLE_RIGHT_LOW_ATTRIBUTE: &'static str = "gentle_slope_right_low";
+    pub const GENTLE_RIGHT_HIGH_ATTRIBUTE: &'static str = "gentle_slope_right_high";
+    pub const GENTLE_LEFT_HIGH_ATTRIBUTE: &'static str = "gentle_slope_left_high";
+    pub const GENTLE_LEFT_LOW_ATTRIBUTE: &'static str = "gentle_slope_left_low";
+
+    pub const ATTRIBUTES: &'static [&'static str] = &[
+        Self::RIGHT_ATTRIBUTE,
+        Self::LEFT_ATTRIBUTE,
+        Self::GENTLE_RIGHT_LOW_ATTRIBUTE,
+        Self::GENTLE_RIGHT_HIGH_ATTRIBUTE,
+        Self::GENTLE_LEFT_HIGH_ATTRIBUTE,
+        Self::GENTLE_LEFT_LOW_ATTRIBUTE,
+    ];
+
+    /// Returns the slope specified in `attributes`, if any
+    pub fn from_attributes(attributes: &[String]) -> Option<Self> {
+        attributes
+            .iter()
+            .find_map(|attribute| match attribute.as_str() {
+                Self::RIGHT_ATTRIBUTE => Some(Self::Right),
+                Self::LEFT_ATTRIBUTE => Some(Self::Left),
+                Self::GENTLE_RIGHT_LOW_ATTRIBUTE => Some(Self::GentleRightLow),
+                Self::GENTLE_RIGHT_HIGH_ATTRIBUTE => Some(Self::GentleRightHigh),
+                Self::GENTLE_LEFT_HIGH_ATTRIBUTE => Some(Self::GentleLeftHigh),
+                Self::GENTLE_LEFT_LOW_ATTRIBUTE => Some(Self::GentleLeftLow),
+                _ => None,
+            })
+    }
+
+    /// Returns the height of the surface, as a fraction of the tile height, measured from the
+    /// bottom of the tile, at `x`, which is a fraction of the tile width, measured from the left
+    /// edge of the tile.
+    pub fn get_surface_height(&self, x: f32) -> f32 {
+        let x = x.clamp(0.0, 1.0);
+
+        match self {
+            Self::Right => x,
+            Self::Left => 1.0 - x,
+            Self::GentleRightLow => x * 0.5,
+            Self::GentleRightHigh => 0.5 + x * 0.5,
+            Self::GentleLeftHigh => 1.0 - x * 0.5,
+            Self::GentleLeftLow => 0.5 - x * 0.5,
+        }
+    }
+
+    /// Returns the change in surface height per change in `x`, both as fractions of the tile size
+    pub fn get_gradient(&self) -> f32 {
+        match self {
+            Self::Right => 1.0,
+            Self::Left => -1.0,
+            Self::GentleRightLow | Self::GentleRightHigh => 0.5,
+            Self::GentleLeftHigh | Self::GentleLeftLow => -0.5,
+        }
+    }
+
+    /// Returns the normal of the surface, pointing away from the slope, for tiles of `tile_size`
+    pub fn get_normal(&self, tile_size: Vec2) -> Vec2 {
+        let gradient = self.get_gradient() * tile_size.y / tile_size.x;
+        vec2(-gradient, -1.0).normalize()
+    }
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum MapObjectKind {
@@ -635,6 +838,9 @@ pub struct MapTileset {
     pub autotile_mask: Vec<bool>,
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub tile_attributes: HashMap<u32, Vec<String>>,
+    /// Animations of tiles, by the id of the tile that is placed in the map
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub tile_animations: HashMap<u32, MapTileAnimation>,
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub properties: HashMap<String, MapProperty>,
 }
@@ -672,6 +878,7 @@ impl MapTileset {
             tile_subdivisions,
             autotile_mask,
             tile_attributes: HashMap::new(),
+            tile_animations: HashMap::new(),
             properties: HashMap::new(),
         }
     }
@@ -682,7 +889,44 @@ impl MapTileset {
         vec2(x, y)
     }
 
+    /// Returns the texture coords of the current frame of the animation of `tile_id`, if the
+    /// tile is animated
+    pub fn get_animated_texture_coords(&self, tile_id: u32) -> Option<Vec2> {
+        self.tile_animations
+            .get(&tile_id)
+            .filter(|animation| !animation.frames.is_empty())
+            .map(|animation| {
+                let i = (get_time() * animation.fps as f64) as usize % animation.frames.len();
+                self.get_texture_coords(animation.frames[i])
+            })
+    }
+
+    /// Returns the value of the number property `id` of this tileset, or `default`, if the
+    /// property is not set
+    pub fn get_f32_property(&self, id: &str, default: f32) -> f32 {
+        self.properties
+            .get(id)
+            .and_then(|prop| prop.to_f32())
+            .unwrap_or(default)
+    }
+
     pub fn default_tile_subdivisions() -> UVec2 {
         uvec2(3, 3)
     }
 }
+
+/// An animation of a tile, which is drawn by cycling through the tiles in `frames`, at `fps`. This
+/// is purely visual, so the attributes of the animated tile apply, regardless of the frame.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MapTileAnimation {
+    /// The ids of the tiles used as frames, relative to the first tile of the tileset
+    pub frames: Vec<u32>,
+    #[serde(default = "MapTileAnimation::default_fps")]
+    pub fps: f32,
+}
+
+impl MapTileAnimation {
+    pub fn default_fps() -> f32 {
+        8.0
+    }
+}
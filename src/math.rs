@@ -234,6 +234,12 @@ impl IsZero for f32 {
     }
 }
 
+impl IsZero for i32 {
+    fn is_zero(&self) -> bool {
+        *self == 0
+    }
+}
+
 impl IsZero for u32 {
     fn is_zero(&self) -> bool {
         *self == 0
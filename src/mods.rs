@@ -0,0 +1,362 @@
+//! Mods are directories of assets that are layered on top of the core assets. Every mod has a
+//! manifest, `mod.json`, in its directory, that declares its id, its version, the other mods that
+//! it depends on and which kinds of content it overrides. The content of a mod is loaded from
+//! resource files in its own directory, that have the same format as the core resource files,
+//! and any resources that have the same id as an existing resource will replace it.
+//!
+//! Installed mods are listed in `mods.json`, in the assets directory, by the path of their
+//! directory, and they are enabled and disabled from the mod manager, in the main menu, which
+//! stores the ids of the enabled mods in `Config`. Enabled mods are loaded after the mods that
+//! they depend on, and otherwise in the order that they are listed in.
+
+use std::{cmp::Ordering, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{json::deserialize_json_file, Resources, Result};
+
+/// The kinds of content that a mod can override. Each of these corresponds to a core resource
+/// file, that the mod must provide its own version of, in its directory.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModContent {
+    ParticleEffects,
+    ParticleEmitters,
+    Sounds,
+    Textures,
+    Images,
+    Maps,
+    Items,
+    LootTables,
+    Cutscenes,
+    PlayerCharacters,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModDependency {
+    pub id: String,
+    /// The minimum version of the dependency that is required, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModManifest {
+    pub id: String,
+    pub name: String,
+    /// The version of the mod, as `major.minor.patch`
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<ModDependency>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub content: Vec<ModContent>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ModStatus {
+    Disabled,
+    /// The mod is loaded, at this position in the load order
+    Loaded(usize),
+    /// The mod is enabled, but it was not loaded, as a dependency is not installed, not enabled,
+    /// or too old, or because it failed to load itself
+    MissingDependency(String),
+    /// The mod is enabled, but it was not loaded, as it depends on itself, through other mods
+    DependencyCycle,
+    /// The mod is enabled, but its content failed to load. Any content that was loaded before the
+    /// error occurred will remain.
+    Failed,
+}
+
+#[derive(Debug, Clone)]
+pub struct ModInfo {
+    pub manifest: ModManifest,
+    /// The path of the directory of the mod, relative to the assets directory
+    pub path: String,
+    pub status: ModStatus,
+}
+
+impl ModInfo {
+    pub fn is_enabled(&self) -> bool {
+        self.status != ModStatus::Disabled
+    }
+}
+
+pub const MODS_FILE: &str = "mods";
+pub const MOD_MANIFEST_FILE: &str = "mod";
+
+/// This loads the manifests of all the mods that are listed in the mods file of `assets_dir`.
+/// Mods whose manifest can not be loaded, or has the same id as a mod that is listed before them,
+/// are skipped, with a warning.
+pub async fn load_mod_manifests(assets_dir: &Path) -> Result<Vec<ModInfo>> {
+    let mods_file_path = assets_dir
+        .join(MODS_FILE)
+        .with_extension(Resources::RESOURCE_FILES_EXTENSION);
+
+    let paths: Vec<String> = deserialize_json_file(&mods_file_path).await?;
+
+    let mut res: Vec<ModInfo> = Vec::new();
+
+    for path in paths {
+        let manifest_path = assets_dir
+            .join(&path)
+            .join(MOD_MANIFEST_FILE)
+            .with_extension(Resources::RESOURCE_FILES_EXTENSION);
+
+        let manifest: ModManifest = match deserialize_json_file(&manifest_path).await {
+            Ok(manifest) => manifest,
+            Err(err) => {
+                println!(
+                    "WARNING: Mods: Failed to load the manifest of '{}': {}",
+                    &path, err
+                );
+                continue;
+            }
+        };
+
+        if res.iter().any(|info| info.manifest.id == manifest.id) {
+            println!(
+                "WARNING: Mods: Duplicate mod id '{}' in '{}'",
+                &manifest.id, &path
+            );
+            continue;
+        }
+
+        res.push(ModInfo {
+            manifest,
+            path,
+            status: ModStatus::Disabled,
+        });
+    }
+
+    Ok(res)
+}
+
+/// Compares two versions of the form `major.minor.patch`, number by number. Missing numbers are
+/// considered zero and anything that is not a number is ignored.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    fn parse(version: &str) -> Vec<u32> {
+        let mut res = version
+            .split('.')
+            .map(|part| {
+                part.chars()
+                    .take_while(char::is_ascii_digit)
+                    .collect::<String>()
+                    .parse()
+                    .unwrap_or(0)
+            })
+            .collect::<Vec<_>>();
+
+        while res.last() == Some(&0) {
+            res.pop();
+        }
+
+        res
+    }
+
+    parse(a).cmp(&parse(b))
+}
+
+/// This sets the status of every mod in `mods`, from the ids in `enabled_ids`, and returns the
+/// indices of the mods that should be loaded, in load order. A mod is always loaded after all of
+/// its dependencies, and mods that do not depend on each other are loaded in the order of `mods`.
+pub fn resolve_load_order(mods: &mut [ModInfo], enabled_ids: &[String]) -> Vec<usize> {
+    for info in mods.iter_mut() {
+        info.status = ModStatus::Disabled;
+    }
+
+    let mut is_candidate = mods
+        .iter()
+        .map(|info| enabled_ids.contains(&info.manifest.id))
+        .collect::<Vec<_>>();
+
+    // Mods with unmet dependencies are removed, until all the remaining mods have their
+    // dependencies met, as removing one mod can break the dependencies of another
+    let mut is_changed = true;
+    while is_changed {
+        is_changed = false;
+
+        for i in 0..mods.len() {
+            if !is_candidate[i] {
+                continue;
+            }
+
+            let missing = mods[i]
+                .manifest
+                .dependencies
+                .iter()
+                .find(|dependency| {
+                    let other = mods
+                        .iter()
+                        .enumerate()
+                        .find(|(j, other)| is_candidate[*j] && other.manifest.id == dependency.id);
+
+                    match (other, &dependency.version) {
+                        (None, _) => true,
+                        (Some((_, other)), Some(version)) => {
+                            compare_versions(&other.manifest.version, version) == Ordering::Less
+                        }
+                        (Some(_), None) => false,
+                    }
+                })
+                .map(|dependency| dependency.id.clone());
+
+            if let Some(id) = missing {
+                mods[i].status = ModStatus::MissingDependency(id);
+                is_candidate[i] = false;
+                is_changed = true;
+            }
+        }
+    }
+
+    let mut load_order: Vec<usize> = Vec::new();
+
+    // Every pass loads the first mod, in list order, that has all of its dependencies loaded.
+    // Any mods that remain when no mod can be loaded are part of a dependency cycle.
+    loop {
+        let next = (0..mods.len()).find(|&i| {
+            is_candidate[i]
+                && !load_order.contains(&i)
+                && mods[i].manifest.dependencies.iter().all(|dependency| {
+                    load_order
+                        .iter()
+                        .any(|&j| mods[j].manifest.id == dependency.id)
+                })
+        });
+
+        match next {
+            Some(i) => load_order.push(i),
+            None => break,
+        }
+    }
+
+    for (i, info) in mods.iter_mut().enumerate() {
+        if is_candidate[i] {
+            info.status = match load_order.iter().position(|&j| j == i) {
+                Some(position) => ModStatus::Loaded(position),
+                None => ModStatus::DependencyCycle,
+            };
+        }
+    }
+
+    load_order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_mod(id: &str, version: &str, dependencies: &[(&str, Option<&str>)]) -> ModInfo {
+        ModInfo {
+            manifest: ModManifest {
+                id: id.to_string(),
+                name: id.to_string(),
+                version: version.to_string(),
+                description: None,
+                dependencies: dependencies
+                    .iter()
+                    .map(|(id, version)| ModDependency {
+                        id: id.to_string(),
+                        version: version.map(str::to_string),
+                    })
+                    .collect(),
+                content: Vec::new(),
+            },
+            path: id.to_string(),
+            status: ModStatus::Disabled,
+        }
+    }
+
+    fn enable_all(mods: &[ModInfo]) -> Vec<String> {
+        mods.iter().map(|info| info.manifest.id.clone()).collect()
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("1.2.3", "1.2.3"), Ordering::Equal);
+        assert_eq!(compare_versions("1.2", "1.2.0"), Ordering::Equal);
+        assert_eq!(compare_versions("1.10.0", "1.9.0"), Ordering::Greater);
+        assert_eq!(compare_versions("0.9", "1"), Ordering::Less);
+        assert_eq!(compare_versions("2.0.1-beta", "2.0.1"), Ordering::Equal);
+        assert_eq!(compare_versions("", "0.0.0"), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_load_order() {
+        let mut mods = vec![
+            create_mod("a", "1.0.0", &[("b", None)]),
+            create_mod("b", "1.0.0", &[("c", None)]),
+            create_mod("c", "1.0.0", &[]),
+            create_mod("d", "1.0.0", &[]),
+        ];
+
+        let enabled_ids = enable_all(&mods);
+        let load_order = resolve_load_order(&mut mods, &enabled_ids);
+
+        assert_eq!(load_order, vec![2, 1, 0, 3]);
+        assert_eq!(mods[0].status, ModStatus::Loaded(2));
+        assert_eq!(mods[2].status, ModStatus::Loaded(0));
+    }
+
+    #[test]
+    fn test_transitive_missing_dependency() {
+        let mut mods = vec![
+            create_mod("a", "1.0.0", &[("b", None)]),
+            create_mod("b", "1.0.0", &[("c", None)]),
+            create_mod("c", "1.0.0", &[]),
+        ];
+
+        let enabled_ids = vec!["a".to_string(), "b".to_string()];
+        let load_order = resolve_load_order(&mut mods, &enabled_ids);
+
+        assert!(load_order.is_empty());
+        assert_eq!(
+            mods[0].status,
+            ModStatus::MissingDependency("b".to_string())
+        );
+        assert_eq!(
+            mods[1].status,
+            ModStatus::MissingDependency("c".to_string())
+        );
+        assert_eq!(mods[2].status, ModStatus::Disabled);
+    }
+
+    #[test]
+    fn test_minimum_version() {
+        let mut mods = vec![
+            create_mod("a", "1.0.0", &[("c", Some("1.2"))]),
+            create_mod("b", "1.0.0", &[("c", Some("1.10.0"))]),
+            create_mod("c", "1.9.5", &[]),
+        ];
+
+        let enabled_ids = enable_all(&mods);
+        let load_order = resolve_load_order(&mut mods, &enabled_ids);
+
+        assert_eq!(load_order, vec![2, 0]);
+        assert_eq!(mods[0].status, ModStatus::Loaded(1));
+        assert_eq!(
+            mods[1].status,
+            ModStatus::MissingDependency("c".to_string())
+        );
+    }
+
+    #[test]
+    fn test_dependency_cycle() {
+        let mut mods = vec![
+            create_mod("a", "1.0.0", &[("b", None)]),
+            create_mod("b", "1.0.0", &[("a", None)]),
+            create_mod("c", "1.0.0", &[("a", None)]),
+            create_mod("d", "1.0.0", &[]),
+        ];
+
+        let enabled_ids = enable_all(&mods);
+        let load_order = resolve_load_order(&mut mods, &enabled_ids);
+
+        assert_eq!(load_order, vec![3]);
+        assert_eq!(mods[0].status, ModStatus::DependencyCycle);
+        assert_eq!(mods[1].status, ModStatus::DependencyCycle);
+        assert_eq!(mods[2].status, ModStatus::DependencyCycle);
+        assert_eq!(mods[3].status, ModStatus::Loaded(0));
+    }
+}
@@ -0,0 +1,295 @@
+//! The WebAssembly host of the mutators. This holds the API that is available to mutators, as
+//! imports from the `fishfight` module:
+//!
+//! - `log(ptr: i32, len: i32)` prints the UTF-8 string of `len` bytes at `ptr` in the memory of
+//!   the mutator, which must be exported as `memory`
+//! - `set_gravity_scale(scale: f32)` ignores values that are not finite
+//! - `set_damage_scale(scale: f32)` ignores values that are not finite
+//! - `set_one_hit_kill(is_enabled: i32)`
+//! - `get_player_health(player_id: i32) -> f32` returns `0.0` if the player does not exist
+//! - `get_player_max_health(player_id: i32) -> f32` returns `0.0` if the player does not exist
+//! - `heal_player(player_id: i32, amount: f32)` does nothing if the player is dead
+//!
+//! Any changes to these, or to the hooks, must increment `MUTATOR_API_VERSION`.
+
+use std::fmt::Display;
+
+use wasmi::{
+    Caller, Config, Engine, Extern, Linker, Module, Store, StoreLimits, StoreLimitsBuilder,
+    TypedFunc,
+};
+
+use crate::{
+    error::{Error, ErrorKind, Result},
+    formaterr,
+    mutators::{get_mutator_rules_mut, MutatorRules},
+    player::with_player,
+};
+
+/// The version of the mutator API. Mutators must export a function, `mutator_api_version`, that
+/// returns this.
+pub const MUTATOR_API_VERSION: i32 = 1;
+
+const HOST_MODULE: &str = "fishfight";
+
+/// The amount of fuel that a single hook can consume, before it is aborted
+const FUEL_PER_CALL: u64 = 1_000_000;
+/// The maximum size of the linear memory of a mutator, in bytes
+const MAX_MEMORY_SIZE: usize = 16 * 1024 * 1024;
+const MAX_LOG_LEN: usize = 256;
+
+static mut ENGINE: Option<Engine> = None;
+
+fn get_engine() -> &'static Engine {
+    unsafe {
+        ENGINE.get_or_insert_with(|| {
+            let mut config = Config::default();
+            config.consume_fuel(true);
+
+            Engine::new(&config)
+        })
+    }
+}
+
+fn mutator_error<E: Display>(id: &str, err: E) -> Error {
+    formaterr!(ErrorKind::General, "Mutator '{}': {}", id, err)
+}
+
+struct HostState {
+    id: String,
+    limits: StoreLimits,
+}
+
+fn create_linker(engine: &Engine) -> Result<Linker<HostState>> {
+    let mut linker = Linker::new(engine);
+
+    linker
+        .func_wrap(
+            HOST_MODULE,
+            "log",
+            |caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+                let memory = caller.get_export("memory").and_then(Extern::into_memory);
+
+                if let Some(memory) = memory {
+                    let mut buf = vec![0; (len.max(0) as usize).min(MAX_LOG_LEN)];
+
+                    if memory.read(&caller, ptr as usize, &mut buf).is_ok() {
+                        println!(
+                            "Mutator '{}': {}",
+                            caller.data().id,
+                            String::from_utf8_lossy(&buf)
+                        );
+                    }
+                }
+            },
+        )
+        .map_err(|err| formaterr!(ErrorKind::General, "Mutator host: {}", err))?;
+
+    linker
+        .func_wrap(HOST_MODULE, "set_gravity_scale", |scale: f32| {
+            if !scale.is_finite() {
+                return;
+            }
+
+            let rules = unsafe { get_mutator_rules_mut() };
+            rules.gravity_scale = scale.clamp(
+                MutatorRules::MIN_GRAVITY_SCALE,
+                MutatorRules::MAX_GRAVITY_SCALE,
+            );
+        })
+        .map_err(|err| formaterr!(ErrorKind::General, "Mutator host: {}", err))?;
+
+    linker
+        .func_wrap(HOST_MODULE, "set_damage_scale", |scale: f32| {
+            if !scale.is_finite() {
+                return;
+            }
+
+            let rules = unsafe { get_mutator_rules_mut() };
+            rules.damage_scale = scale.clamp(0.0, MutatorRules::MAX_DAMAGE_SCALE);
+        })
+        .map_err(|err| formaterr!(ErrorKind::General, "Mutator host: {}", err))?;
+
+    linker
+        .func_wrap(HOST_MODULE, "set_one_hit_kill", |is_enabled: i32| {
+            let rules = unsafe { get_mutator_rules_mut() };
+            rules.is_one_hit_kill = is_enabled != 0;
+        })
+        .map_err(|err| formaterr!(ErrorKind::General, "Mutator host: {}", err))?;
+
+    linker
+        .func_wrap(HOST_MODULE, "get_player_health", |player_id: i32| -> f32 {
+            with_player(player_id, 0.0, |player| player.health)
+        })
+        .map_err(|err| formaterr!(ErrorKind::General, "Mutator host: {}", err))?;
+
+    linker
+        .func_wrap(
+            HOST_MODULE,
+            "get_player_max_health",
+            |player_id: i32| -> f32 { with_player(player_id, 0.0, |player| player.max_health) },
+        )
+        .map_err(|err| formaterr!(ErrorKind::General, "Mutator host: {}", err))?;
+
+    linker
+        .func_wrap(HOST_MODULE, "heal_player", |player_id: i32, amount: f32| {
+            with_player(player_id, (), |player| {
+                if !player.is_dead && amount > 0.0 {
+                    player.health = (player.health + amount).min(player.max_health);
+                }
+            })
+        })
+        .map_err(|err| formaterr!(ErrorKind::General, "Mutator host: {}", err))?;
+
+    Ok(linker)
+}
+
+/// This sets the fuel of `store` to `FUEL_PER_CALL`
+fn refuel(store: &mut Store<HostState>) -> Result<()> {
+    let id = store.data().id.clone();
+
+    let remaining = store
+        .consume_fuel(0)
+        .map_err(|err| mutator_error(&id, err))?;
+
+    store
+        .add_fuel(FUEL_PER_CALL.saturating_sub(remaining))
+        .map_err(|err| mutator_error(&id, err))
+}
+
+/// This instantiates the mutator in `bytes` and checks its API version, so that invalid mutators
+/// can be rejected when resources are loaded
+pub fn validate_mutator(id: &str, bytes: &[u8]) -> Result<()> {
+    MutatorInstance::new(id, bytes).map(|_| ())
+}
+
+/// The hooks that mutators can implement
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MutatorHook {
+    RoundStart,
+    Kill {
+        player_id: u8,
+        killed_by: Option<u8>,
+    },
+    Tick {
+        dt: f32,
+    },
+}
+
+pub struct MutatorInstance {
+    id: String,
+    store: Store<HostState>,
+    on_round_start: Option<TypedFunc<(), ()>>,
+    on_kill: Option<TypedFunc<(i32, i32), ()>>,
+    on_tick: Option<TypedFunc<f32, ()>>,
+    /// This is set if the mutator traps, after which none of its hooks will be called
+    is_disabled: bool,
+}
+
+impl MutatorInstance {
+    pub fn new(id: &str, bytes: &[u8]) -> Result<Self> {
+        let engine = get_engine();
+
+        let module = Module::new(engine, bytes).map_err(|err| mutator_error(id, err))?;
+
+        let state = HostState {
+            id: id.to_string(),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(MAX_MEMORY_SIZE)
+                .build(),
+        };
+
+        let mut store = Store::new(engine, state);
+        store.limiter(|state| &mut state.limits);
+
+        refuel(&mut store)?;
+
+        let linker = create_linker(engine)?;
+
+        // Instantiation fails if the module imports anything that is not in the host API
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .and_then(|instance| instance.start(&mut store))
+            .map_err(|err| mutator_error(id, err))?;
+
+        let version = instance
+            .get_typed_func::<(), i32>(&store, "mutator_api_version")
+            .map_err(|err| mutator_error(id, err))?
+            .call(&mut store, ())
+            .map_err(|err| mutator_error(id, err))?;
+
+        if version != MUTATOR_API_VERSION {
+            return Err(formaterr!(
+                ErrorKind::General,
+                "Mutator '{}': Unsupported API version {} (expected {})",
+                id,
+                version,
+                MUTATOR_API_VERSION
+            ));
+        }
+
+        let on_round_start = instance.get_typed_func(&store, "on_round_start").ok();
+        let on_kill = instance.get_typed_func(&store, "on_kill").ok();
+        let on_tick = instance.get_typed_func(&store, "on_tick").ok();
+
+        Ok(MutatorInstance {
+            id: id.to_string(),
+            store,
+            on_round_start,
+            on_kill,
+            on_tick,
+            is_disabled: false,
+        })
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.is_disabled
+    }
+
+    /// This calls `hook`, if it is implemented by the mutator. If the mutator traps, or runs out
+    /// of fuel, it is disabled.
+    pub fn call(&mut self, hook: MutatorHook) {
+        if self.is_disabled {
+            return;
+        }
+
+        if let Err(err) = refuel(&mut self.store) {
+            println!("WARNING: {}", err);
+            self.is_disabled = true;
+            return;
+        }
+
+        let store = &mut self.store;
+
+        let res = match hook {
+            MutatorHook::RoundStart => self
+                .on_round_start
+                .as_ref()
+                .map(|f| f.call(&mut *store, ()).map_err(|err| err.to_string())),
+            MutatorHook::Kill {
+                player_id,
+                killed_by,
+            } => {
+                let killed_by = killed_by.map(|id| id as i32).unwrap_or(-1);
+
+                self.on_kill.as_ref().map(|f| {
+                    f.call(&mut *store, (player_id as i32, killed_by))
+                        .map_err(|err| err.to_string())
+                })
+            }
+            MutatorHook::Tick { dt } => self
+                .on_tick
+                .as_ref()
+                .map(|f| f.call(&mut *store, dt).map_err(|err| err.to_string())),
+        };
+
+        if let Some(Err(err)) = res {
+            println!(
+                "WARNING: Mutator '{}' was disabled, as it failed in {:?}: {}",
+                self.id, hook, err
+            );
+
+            self.is_disabled = true;
+        }
+    }
+}
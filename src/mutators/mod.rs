@@ -0,0 +1,196 @@
+//! Mutators are plugins that change the rules of a match, like low gravity, one-hit kills or
+//! vampire mode. They are WebAssembly modules that are listed in `mutators.json`, in the assets
+//! directory, and they are enabled by adding their ids to the `mutators` of the `Ruleset`.
+//!
+//! A mutator can export any of these hooks:
+//!
+//! - `on_round_start()` is called when a match is started
+//! - `on_kill(player_id: i32, killed_by: i32)` is called when a player is killed. `killed_by` is
+//!   `-1` if the kill can not be attributed to another player.
+//! - `on_tick(dt: f32)` is called on every simulation frame
+//!
+//! Low gravity and one-hit kills are set up in `on_round_start`, through the rules of the host
+//! API, while vampire mode would heal the killer with `heal_player`, in `on_kill`.
+//!
+//! Mutators must also export `mutator_api_version() -> i32`, returning the version of the host
+//! API that they were built against. Mutators built against any other version than
+//! `MUTATOR_API_VERSION` are rejected when resources are loaded, so that the API can be changed
+//! without breaking existing mutators in subtle ways.
+//!
+//! Mutators are sandboxed. They can only import the functions of the host API, from the
+//! `fishfight` module (see `host`), so they have no access to the file system, or anything else
+//! outside of the match, and every hook is limited to a fixed amount of fuel, so that a broken
+//! mutator can not hang the game. A mutator that traps is disabled for the rest of the match.
+
+use macroquad::experimental::{
+    collections::storage,
+    scene::{self, HandleUntyped, Node, RefMut},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    capabilities::NetworkReplicate,
+    game::{GameEvent, SIMULATION_DELTA_TIME},
+    Resources,
+};
+
+mod host;
+
+pub use host::{validate_mutator, MUTATOR_API_VERSION};
+
+use host::{MutatorHook, MutatorInstance};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MutatorMetadata {
+    pub id: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The path of the WebAssembly module, relative to the assets directory
+    pub path: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct MutatorResource {
+    pub meta: MutatorMetadata,
+    /// The WebAssembly module. This is instantiated for every match, so that mutators start out
+    /// with a fresh state.
+    pub bytes: Vec<u8>,
+}
+
+/// The rules that mutators can change through the host API. These are reset when a match is
+/// started.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MutatorRules {
+    /// A factor that gravity is multiplied by
+    pub gravity_scale: f32,
+    /// A factor that all damage is multiplied by
+    pub damage_scale: f32,
+    /// If this is `true`, any damage that is not blocked will kill a player
+    pub is_one_hit_kill: bool,
+}
+
+impl MutatorRules {
+    pub const MIN_GRAVITY_SCALE: f32 = 0.1;
+    pub const MAX_GRAVITY_SCALE: f32 = 4.0;
+
+    pub const MAX_DAMAGE_SCALE: f32 = 10.0;
+
+    /// Returns the damage that a player with `health` should receive, for a hit of `damage`
+    pub fn get_damage(&self, damage: f32, health: f32) -> f32 {
+        let damage = damage * self.damage_scale;
+
+        if self.is_one_hit_kill && damage > 0.0 {
+            damage.max(health)
+        } else {
+            damage
+        }
+    }
+}
+
+impl Default for MutatorRules {
+    fn default() -> Self {
+        MutatorRules {
+            gravity_scale: 1.0,
+            damage_scale: 1.0,
+            is_one_hit_kill: false,
+        }
+    }
+}
+
+static mut MUTATOR_RULES: Option<MutatorRules> = None;
+
+unsafe fn get_mutator_rules_mut() -> &'static mut MutatorRules {
+    MUTATOR_RULES.get_or_insert_with(MutatorRules::default)
+}
+
+/// Returns the rules, as they have been changed by the mutators of the current match
+pub fn get_mutator_rules() -> MutatorRules {
+    unsafe { *get_mutator_rules_mut() }
+}
+
+pub struct Mutators {
+    instances: Vec<MutatorInstance>,
+}
+
+impl Mutators {
+    pub const LISTENER_ID: &'static str = "mutators";
+
+    /// This instantiates the mutators that are enabled in the ruleset and resets the rules
+    pub fn new() -> Self {
+        unsafe { *get_mutator_rules_mut() = MutatorRules::default() };
+
+        let resources = storage::get::<Resources>();
+
+        let mut instances = Vec::new();
+
+        for id in &resources.ruleset.mutators {
+            if let Some(mutator) = resources.mutators.get(id) {
+                match MutatorInstance::new(id, &mutator.bytes) {
+                    Ok(instance) => instances.push(instance),
+                    Err(err) => println!("WARNING: Mutator '{}': {}", id, err),
+                }
+            } else {
+                println!("WARNING: Ruleset: Invalid mutator id '{}'", id);
+            }
+        }
+
+        Mutators { instances }
+    }
+
+    fn call_hook(&mut self, hook: MutatorHook) {
+        for instance in &mut self.instances {
+            instance.call(hook);
+        }
+
+        self.instances.retain(|instance| !instance.is_disabled());
+    }
+
+    pub fn on_game_event(event: &GameEvent) {
+        if let GameEvent::PlayerKilled {
+            player_id,
+            killed_by,
+            ..
+        } = *event
+        {
+            if let Some(mut node) = scene::find_node_by_type::<Mutators>() {
+                node.call_hook(MutatorHook::Kill {
+                    player_id,
+                    killed_by,
+                });
+            }
+        }
+    }
+
+    fn network_update(mut node: RefMut<Self>) {
+        node.call_hook(MutatorHook::Tick {
+            dt: SIMULATION_DELTA_TIME,
+        });
+    }
+
+    fn network_capabilities() -> NetworkReplicate {
+        fn network_update(handle: HandleUntyped) {
+            let node = scene::get_untyped_node(handle)
+                .unwrap()
+                .to_typed::<Mutators>();
+            Mutators::network_update(node);
+        }
+
+        NetworkReplicate { network_update }
+    }
+}
+
+impl Default for Mutators {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Node for Mutators {
+    fn ready(mut node: RefMut<Self>) {
+        node.provides(Self::network_capabilities());
+
+        node.call_hook(MutatorHook::RoundStart);
+    }
+}
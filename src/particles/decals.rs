@@ -0,0 +1,142 @@
+use macroquad::{
+    experimental::{collections::storage, scene},
+    prelude::*,
+};
+
+use crate::{GameCamera, GameWorld};
+
+/// A particle that has settled, and that will be drawn to the decal layer
+#[derive(Debug, Clone)]
+pub struct Decal {
+    pub position: Vec2,
+    pub size: f32,
+    pub color: Color,
+    /// The texture and source rect of the decal. If this is `None`, it is drawn as a circle.
+    pub texture: Option<(Texture2D, Rect)>,
+}
+
+impl Decal {
+    pub fn draw(&self) {
+        match self.texture {
+            Some((texture, source)) => {
+                draw_texture_ex(
+                    texture,
+                    self.position.x - self.size / 2.0,
+                    self.position.y - self.size / 2.0,
+                    self.color,
+                    DrawTextureParams {
+                        dest_size: Some(vec2(self.size, self.size)),
+                        source: Some(source),
+                        ..Default::default()
+                    },
+                );
+            }
+            None => {
+                draw_circle(
+                    self.position.x,
+                    self.position.y,
+                    self.size / 2.0,
+                    self.color,
+                );
+            }
+        }
+    }
+}
+
+/// A texture, covering the whole map, that persistent particles, like shell casings and debris,
+/// are drawn to once they settle. This means that any amount of settled particles cost the same
+/// to draw, every frame, as a single texture.
+///
+/// Decals are queued when added and drawn to the layer the next time it is drawn, as the layer can
+/// only be drawn to while rendering.
+pub struct DecalLayer {
+    rect: Rect,
+    render_target: RenderTarget,
+    pending: Vec<Decal>,
+    is_cleared: bool,
+}
+
+impl DecalLayer {
+    /// The maximum size of the layer, in each dimension. Decals outside of this, on very large
+    /// maps, will be dropped.
+    const MAX_SIZE: f32 = 4096.0;
+
+    pub fn new(map_rect: Rect) -> Self {
+        let rect = Rect::new(
+            map_rect.x,
+            map_rect.y,
+            map_rect.w.min(Self::MAX_SIZE),
+            map_rect.h.min(Self::MAX_SIZE),
+        );
+
+        let render_target = render_target(rect.w as u32, rect.h as u32);
+        render_target.texture.set_filter(FilterMode::Nearest);
+
+        DecalLayer {
+            rect,
+            render_target,
+            pending: Vec::new(),
+            is_cleared: false,
+        }
+    }
+
+    /// This queues `decal` to be drawn to the layer
+    pub fn add(decal: Decal) {
+        if let Some(mut layer) = storage::try_get_mut::<DecalLayer>() {
+            layer.pending.push(decal);
+        }
+    }
+
+    /// This draws any pending decals to the layer and then draws the layer. This should be called
+    /// after the map is drawn.
+    pub fn draw() {
+        let mut layer = storage::get_mut::<DecalLayer>();
+
+        if !layer.is_cleared || !layer.pending.is_empty() {
+            let camera = scene::find_node_by_type::<GameCamera>()
+                .map(|camera| camera.get_macroquad_camera());
+
+            let mut layer_camera = Camera2D::from_display_rect(layer.rect);
+            layer_camera.render_target = Some(layer.render_target);
+
+            set_camera(&layer_camera);
+
+            if !layer.is_cleared {
+                clear_background(BLANK);
+                layer.is_cleared = true;
+            }
+
+            for decal in layer.pending.drain(..) {
+                decal.draw();
+            }
+
+            match camera {
+                Some(camera) => set_camera(&camera),
+                None => set_default_camera(),
+            }
+        }
+
+        draw_texture_ex(
+            layer.render_target.texture,
+            layer.rect.x,
+            layer.rect.y,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(layer.rect.size()),
+                flip_y: true,
+                ..Default::default()
+            },
+        );
+    }
+
+    /// Returns the rect of the map in `world`
+    pub fn get_map_rect(world: &GameWorld) -> Rect {
+        let size = world.map.grid_size.as_f32() * world.map.tile_size;
+        Rect::new(
+            world.map.world_offset.x,
+            world.map.world_offset.y,
+            size.x,
+            size.y,
+        )
+    }
+}
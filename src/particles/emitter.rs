@@ -0,0 +1,458 @@
+//! Data-driven particle emitters, that are declared in JSON and loaded into `Resources`. Unlike the
+//! effects in `particle_effects`, these support textures, size and alpha curves, and continuous
+//! emission over time.
+//!
+//! Emitters can optionally collide with the map, so that debris and shell casings bounce and
+//! settle on the ground and on platforms. This is a point test against the tile grid, so it is
+//! cheap enough for large amounts of particles, but it ignores the size of the particles.
+//!
+//! Colliding emitters can also be marked as persistent, in which case their particles, once
+//! settled, are drawn to the `DecalLayer` and stay there for the rest of the game.
+//!
+//! All the particles of an emitter config are kept in one flat list, and drawn one after the other,
+//! so that every particle that share a texture end up in the same draw call.
+
+use macroquad::{experimental::collections::storage, prelude::*};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Decal, DecalLayer};
+use crate::{
+    cosmetic_rand,
+    game::TileCollision,
+    json,
+    math::{deg_to_rad, rotate_vector, IsZero},
+    symbol::Symbol,
+    GameWorld, Resources,
+};
+
+/// A value that is interpolated linearly over the lifetime of a particle, from `start` to `end`,
+/// through `mid`, at the half-way point, if it is specified
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParticleCurve {
+    pub start: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mid: Option<f32>,
+    pub end: f32,
+}
+
+impl ParticleCurve {
+    /// Returns the value of the curve at `t`, where `t` is between `0.0` and `1.0`
+    pub fn sample(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+
+        match self.mid {
+            Some(mid) if t < 0.5 => self.start + (mid - self.start) * (t * 2.0),
+            Some(mid) => mid + (self.end - mid) * ((t - 0.5) * 2.0),
+            None => self.start + (self.end - self.start) * t,
+        }
+    }
+}
+
+impl Default for ParticleCurve {
+    fn default() -> Self {
+        ParticleCurve {
+            start: 1.0,
+            mid: None,
+            end: 1.0,
+        }
+    }
+}
+
+/// The collision of the particles of an emitter with the map
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParticleCollisionParams {
+    /// The factor of its velocity that a particle bounces back with, when it hits a tile
+    #[serde(default = "ParticleCollisionParams::default_bounciness")]
+    pub bounciness: f32,
+    /// The factor of its horizontal velocity that a particle loses, every time it hits the ground
+    #[serde(default = "ParticleCollisionParams::default_friction")]
+    pub friction: f32,
+    /// If this is `true`, particles will fall through platforms and only land on solid tiles
+    #[serde(default, skip_serializing_if = "json::is_false")]
+    pub ignores_platforms: bool,
+}
+
+impl ParticleCollisionParams {
+    pub fn default_bounciness() -> f32 {
+        0.4
+    }
+
+    pub fn default_friction() -> f32 {
+        0.3
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ParticleEmitterParams {
+    /// The id of a texture that particles will be drawn with. If the texture is a spritesheet,
+    /// the first frame is used. If this is not specified, particles are drawn as circles.
+    #[serde(default, rename = "texture", skip_serializing_if = "Option::is_none")]
+    pub texture_id: Option<Symbol>,
+    /// The amount of particles emitted immediately, when the emitter is spawned
+    #[serde(default, skip_serializing_if = "u32::is_zero")]
+    pub burst: u32,
+    /// The amount of particles emitted per second, for `duration` seconds
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    pub rate: f32,
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    pub duration: f32,
+    /// The time, in seconds, that each particle lives
+    pub lifetime: f32,
+    /// A factor between `0.0` and `1.0` that the lifetime of each particle is randomly reduced by
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    pub lifetime_randomness: f32,
+    /// The center of the cone that particles are emitted in
+    #[serde(
+        default = "ParticleEmitterParams::default_direction",
+        with = "json::vec2_def"
+    )]
+    pub direction: Vec2,
+    /// The width of the cone that particles are emitted in, in degrees
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    pub spread: f32,
+    pub speed: f32,
+    /// A factor between `0.0` and `1.0` that the speed of each particle is randomly reduced by
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    pub speed_randomness: f32,
+    #[serde(
+        default,
+        with = "json::vec2_def",
+        skip_serializing_if = "Vec2::is_zero"
+    )]
+    pub gravity: Vec2,
+    /// The diameter of each particle, which is multiplied by `size_curve`
+    pub size: f32,
+    /// A factor between `0.0` and `1.0` that the size of each particle is randomly reduced by
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    pub size_randomness: f32,
+    #[serde(default)]
+    pub size_curve: ParticleCurve,
+    /// The alpha of each particle is multiplied by this
+    #[serde(default)]
+    pub alpha_curve: ParticleCurve,
+    #[serde(
+        default = "ParticleEmitterParams::default_color",
+        with = "json::ColorDef"
+    )]
+    pub color: Color,
+    /// If this is specified, the color of particles will fade from `color` to this, over their
+    /// lifetime
+    #[serde(
+        default,
+        with = "json::color_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub end_color: Option<Color>,
+    /// If this is specified, particles will collide with the map
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collision: Option<ParticleCollisionParams>,
+    /// If this is `true`, particles that settle will be drawn to the decal layer, instead of
+    /// fading out. This requires `collision` to be specified.
+    #[serde(default, skip_serializing_if = "json::is_false")]
+    pub is_persistent: bool,
+}
+
+impl ParticleEmitterParams {
+    pub fn default_direction() -> Vec2 {
+        vec2(0.0, -1.0)
+    }
+
+    pub fn default_color() -> Color {
+        WHITE
+    }
+}
+
+struct Particle {
+    position: Vec2,
+    velocity: Vec2,
+    gravity: Vec2,
+    tint: Color,
+    size: f32,
+    age: f32,
+    lifetime: f32,
+    /// This is set when a colliding particle has come to rest on the ground, after which it will
+    /// no longer be moved
+    is_settled: bool,
+}
+
+/// An emitter that is still emitting particles, over its `duration`
+struct ActiveEmitter {
+    position: Vec2,
+    force: Vec2,
+    tint: Color,
+    timer: f32,
+    emission_timer: f32,
+}
+
+/// All the active emitters, and their particles, that use the same emitter config
+pub struct ParticleEmitterBatch {
+    params: ParticleEmitterParams,
+    emitters: Vec<ActiveEmitter>,
+    particles: Vec<Particle>,
+    /// The particles that are due to be emitted by the active emitters, on this update. This is
+    /// kept between updates, so that its buffer is reused.
+    emissions: Vec<(Vec2, Vec2, Color)>,
+}
+
+impl ParticleEmitterBatch {
+    /// A colliding particle that would bounce off the ground slower than this will settle instead
+    const SETTLE_SPEED: f32 = 20.0;
+
+    /// The maximum amount of particles of a persistent emitter config that can be in motion at
+    /// once. Any particles emitted beyond this are discarded.
+    const MAX_PERSISTENT_PARTICLES: usize = 128;
+
+    pub fn new(params: ParticleEmitterParams) -> Self {
+        ParticleEmitterBatch {
+            params,
+            emitters: Vec::new(),
+            particles: Vec::new(),
+            emissions: Vec::new(),
+        }
+    }
+
+    /// This spawns an emitter at `position`, where `force` is added to the gravity of its
+    /// particles and the colors of the particles are multiplied by `tint`
+    pub fn spawn(&mut self, position: Vec2, force: Vec2, tint: Color) {
+        for _ in 0..self.params.burst {
+            self.emit(position, force, tint);
+        }
+
+        if self.params.rate > 0.0 && self.params.duration > 0.0 {
+            self.emitters.push(ActiveEmitter {
+                position,
+                force,
+                tint,
+                timer: 0.0,
+                emission_timer: 0.0,
+            });
+        }
+    }
+
+    fn emit(&mut self, position: Vec2, force: Vec2, tint: Color) {
+        let params = &self.params;
+
+        if params.is_persistent && self.particles.len() >= Self::MAX_PERSISTENT_PARTICLES {
+            return;
+        }
+
+        let half_spread = deg_to_rad(params.spread) / 2.0;
+        let direction = rotate_vector(
+            params.direction.normalize_or_zero(),
+            cosmetic_rand::gen_range(-half_spread, half_spread),
+        );
+
+        let speed = params.speed * (1.0 - cosmetic_rand::gen_range(0.0, params.speed_randomness));
+        let size = params.size * (1.0 - cosmetic_rand::gen_range(0.0, params.size_randomness));
+        let lifetime =
+            params.lifetime * (1.0 - cosmetic_rand::gen_range(0.0, params.lifetime_randomness));
+
+        self.particles.push(Particle {
+            position,
+            velocity: direction * speed,
+            gravity: params.gravity + force,
+            tint,
+            size,
+            age: 0.0,
+            lifetime,
+            is_settled: false,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.emitters.is_empty() && self.particles.is_empty()
+    }
+
+    pub fn has_collision(&self) -> bool {
+        self.params.collision.is_some()
+    }
+
+    /// This advances the active emitters and emits their particles. Emission uses the cosmetic
+    /// RNG, so it does not affect the simulation, no matter how many particles are emitted.
+    pub fn update_emitters(&mut self, dt: f32) {
+        let interval = 1.0 / self.params.rate;
+        let duration = self.params.duration;
+
+        let mut emissions = std::mem::take(&mut self.emissions);
+
+        for emitter in &mut self.emitters {
+            emitter.timer += dt;
+            emitter.emission_timer += dt;
+
+            while emitter.emission_timer >= interval {
+                emitter.emission_timer -= interval;
+                emissions.push((emitter.position, emitter.force, emitter.tint));
+            }
+        }
+
+        self.emitters.retain(|emitter| emitter.timer < duration);
+
+        for (position, force, tint) in emissions.drain(..) {
+            self.emit(position, force, tint);
+        }
+
+        self.emissions = emissions;
+    }
+
+    /// This moves the particles of a batch without collision. This only touches the batch
+    /// itself, so it can be called on a worker thread.
+    pub fn update_particles(&mut self, dt: f32) {
+        debug_assert!(
+            !self.has_collision(),
+            "ParticleEmitterBatch: Colliding particles must be updated with the game world"
+        );
+
+        for particle in &mut self.particles {
+            particle.velocity += particle.gravity * dt;
+            particle.position += particle.velocity * dt;
+            particle.age += dt;
+        }
+    }
+
+    /// This moves the particles of a batch with collision, against the tiles of `world`
+    pub fn update_colliding_particles(&mut self, dt: f32, world: &GameWorld) {
+        if let Some(collision) = &self.params.collision {
+            for particle in &mut self.particles {
+                if !particle.is_settled {
+                    Self::move_and_collide(particle, collision, world, dt);
+                }
+
+                particle.age += dt;
+            }
+        }
+    }
+
+    /// This removes expired particles and, for persistent batches, draws settled particles to the
+    /// decal layer. This must be called on the main thread, after the particles have been moved.
+    pub fn remove_expired_particles(&mut self) {
+        if self.params.is_persistent {
+            let texture = self.get_texture();
+
+            for particle in self.particles.iter().filter(|particle| particle.is_settled) {
+                DecalLayer::add(self.get_decal(particle, texture));
+            }
+
+            self.particles.retain(|particle| !particle.is_settled);
+        }
+
+        self.particles
+            .retain(|particle| particle.age < particle.lifetime);
+    }
+
+    /// This moves `particle` one axis at a time, bouncing it off any tile it would move into
+    fn move_and_collide(
+        particle: &mut Particle,
+        collision: &ParticleCollisionParams,
+        world: &GameWorld,
+        dt: f32,
+    ) {
+        particle.velocity += particle.gravity * dt;
+
+        let next = particle.position + vec2(particle.velocity.x * dt, 0.0);
+        if Self::is_blocked(world, particle.position, next, collision) {
+            particle.velocity.x = -particle.velocity.x * collision.bounciness;
+        } else {
+            particle.position = next;
+        }
+
+        let next = particle.position + vec2(0.0, particle.velocity.y * dt);
+        if Self::is_blocked(world, particle.position, next, collision) {
+            if particle.velocity.y > 0.0 {
+                particle.velocity.x *= 1.0 - collision.friction;
+
+                if particle.velocity.y * collision.bounciness < Self::SETTLE_SPEED {
+                    particle.velocity = Vec2::ZERO;
+                    particle.is_settled = true;
+                    return;
+                }
+            }
+
+            particle.velocity.y = -particle.velocity.y * collision.bounciness;
+        } else {
+            particle.position = next;
+        }
+    }
+
+    /// Returns `true` if a particle moving from `from` to `to` would hit a tile at `to`.
+    /// Platforms are only hit when moving down onto them, from above.
+    fn is_blocked(
+        world: &GameWorld,
+        from: Vec2,
+        to: Vec2,
+        collision: &ParticleCollisionParams,
+    ) -> bool {
+        match world.get_tile_collision_at(to) {
+            TileCollision::Empty => false,
+            TileCollision::Solid => true,
+            TileCollision::Platform => {
+                !collision.ignores_platforms
+                    && to.y > from.y
+                    && world.get_tile_collision_at(from) != TileCollision::Platform
+            }
+            TileCollision::Slope(_) => world
+                .get_slope_surface_y(to)
+                .map(|surface_y| to.y >= surface_y)
+                .unwrap_or(false),
+        }
+    }
+
+    /// Returns the texture and the source rect that particles are drawn with, if any
+    fn get_texture(&self) -> Option<(Texture2D, Rect)> {
+        self.params.texture_id.as_ref().map(|texture_id| {
+            let resources = storage::get::<Resources>();
+            let res = resources.textures.get(texture_id).unwrap_or_else(|| {
+                panic!("ParticleEmitterBatch: Invalid texture ID '{}'", texture_id)
+            });
+
+            let frame_size = res
+                .meta
+                .sprite_size
+                .map(|size| size.as_f32())
+                .unwrap_or(res.meta.size);
+
+            (res.texture, Rect::new(0.0, 0.0, frame_size.x, frame_size.y))
+        })
+    }
+
+    /// Returns `particle` as it should be drawn, at its current age
+    fn get_decal(&self, particle: &Particle, texture: Option<(Texture2D, Rect)>) -> Decal {
+        let params = &self.params;
+
+        let t = if particle.lifetime > 0.0 {
+            particle.age / particle.lifetime
+        } else {
+            1.0
+        };
+
+        let color = match params.end_color {
+            Some(end_color) => Color::new(
+                params.color.r + (end_color.r - params.color.r) * t,
+                params.color.g + (end_color.g - params.color.g) * t,
+                params.color.b + (end_color.b - params.color.b) * t,
+                params.color.a + (end_color.a - params.color.a) * t,
+            ),
+            None => params.color,
+        };
+
+        Decal {
+            position: particle.position,
+            size: particle.size * params.size_curve.sample(t),
+            color: Color::new(
+                color.r * particle.tint.r,
+                color.g * particle.tint.g,
+                color.b * particle.tint.b,
+                color.a * particle.tint.a * params.alpha_curve.sample(t),
+            ),
+            texture,
+        }
+    }
+
+    pub fn draw(&self) {
+        let texture = self.get_texture();
+
+        for particle in &self.particles {
+            self.get_decal(particle, texture).draw();
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+
+mod decals;
+mod emitter;
+
+pub use decals::{Decal, DecalLayer};
+pub use emitter::{
+    ParticleCollisionParams, ParticleCurve, ParticleEmitterBatch, ParticleEmitterParams,
+};
+
+use macroquad::{
+    experimental::{
+        collections::storage,
+        scene::{self, RefMut},
+    },
+    prelude::*,
+};
+
+use ff_particles::EmittersCache;
+
+use crate::{jobs, physics::ForceVolumes, profiler::ProfileScope, GameWorld, Resources, TimeScale};
+
+#[derive(Default)]
+pub struct ParticleEmitters {
+    pub emitters: HashMap<String, EmittersCache>,
+    pub batches: HashMap<String, ParticleEmitterBatch>,
+}
+
+impl ParticleEmitters {
+    /// The step that the force of force volumes is rounded to, when applied to particles
+    const FORCE_STEP: f32 = 50.0;
+    /// The minimum amount of batches that are moved by a worker, as a parallel stage is only
+    /// worth it when there are enough particles to move
+    const MIN_BATCHES_PER_JOB: usize = 4;
+
+    pub fn new() -> Self {
+        ParticleEmitters {
+            emitters: HashMap::new(),
+            batches: HashMap::new(),
+        }
+    }
+
+    pub fn spawn(&mut self, id: &str, position: Vec2) {
+        self.spawn_emitter(id, position, None);
+    }
+
+    /// This will spawn the particle effect with the id `id`, with the colors of the effect
+    /// multiplied by `color`. A separate emitter is cached for each color that is used.
+    pub fn spawn_tinted(&mut self, id: &str, position: Vec2, color: Color) {
+        self.spawn_emitter(id, position, Some(color));
+    }
+
+    /// Particle effects spawned inside a force volume will have the force of the volume added to
+    /// their gravity. The force is rounded, so that only a limited amount of emitters is cached
+    /// for each effect, even if the force of the volume falls off with distance.
+    ///
+    /// If `id` is the id of one of the emitter configs in `Resources::particle_emitters`, that
+    /// will be used, before any of the effects in `Resources::particle_effects`.
+    fn spawn_emitter(&mut self, id: &str, position: Vec2, color: Option<Color>) {
+        let force = (ForceVolumes::get_force(position) * ForceVolumes::PARTICLE_FORCE_FACTOR
+            / Self::FORCE_STEP)
+            .round()
+            * Self::FORCE_STEP;
+
+        {
+            let resources = storage::get::<Resources>();
+            if let Some(params) = resources.particle_emitters.get(id) {
+                let batch = self
+                    .batches
+                    .entry(id.to_string())
+                    .or_insert_with(|| ParticleEmitterBatch::new(params.clone()));
+
+                batch.spawn(position, force, color.unwrap_or(WHITE));
+
+                return;
+            }
+        }
+
+        let mut key = id.to_string();
+
+        if let Some(color) = color {
+            key = format!("{}#{:?}", key, color);
+        }
+
+        if force != Vec2::ZERO {
+            key = format!("{}@{:?}", key, force);
+        }
+
+        if !self.emitters.contains_key(&key) {
+            let resources = storage::get::<Resources>();
+            let mut cfg = resources.particle_effects.get(id).cloned().unwrap();
+
+            if let Some(color) = color {
+                let colors = &mut cfg.colors_curve;
+                for curve_color in [&mut colors.start, &mut colors.mid, &mut colors.end] {
+                    *curve_color = Color::new(
+                        curve_color.r * color.r,
+                        curve_color.g * color.g,
+                        curve_color.b * color.b,
+                        curve_color.a * color.a,
+                    );
+                }
+            }
+
+            cfg.gravity += force;
+
+            self.emitters.insert(key.clone(), EmittersCache::new(cfg));
+        }
+
+        let emitter = self.emitters.get_mut(&key).unwrap();
+        emitter.spawn(position);
+    }
+}
+
+impl scene::Node for ParticleEmitters {
+    fn update(mut node: RefMut<Self>) {
+        // Particles slow down with the simulation, when the game is in slow motion
+        let dt = TimeScale::get_frame_time();
+
+        // Emitters are advanced before any particles are moved, so that new particles are moved
+        // in the same frame as they are emitted
+        for batch in node.batches.values_mut() {
+            batch.update_emitters(dt);
+        }
+
+        let mut batches = Vec::with_capacity(node.batches.len());
+
+        {
+            let world = storage::get::<GameWorld>();
+
+            for batch in node.batches.values_mut() {
+                if batch.is_empty() {
+                    continue;
+                }
+
+                // The game world can not be shared between threads, so colliding particles are
+                // moved on the main thread
+                if batch.has_collision() {
+                    batch.update_colliding_particles(dt, &world);
+                } else {
+                    batches.push(batch);
+                }
+            }
+        }
+
+        jobs::for_each_parallel(&mut batches, Self::MIN_BATCHES_PER_JOB, |batch| {
+            batch.update_particles(dt)
+        });
+
+        for batch in node.batches.values_mut() {
+            batch.remove_expired_particles();
+        }
+    }
+
+    fn draw(mut node: RefMut<Self>) {
+        let _scope = ProfileScope::new("draw particles");
+
+        for emitter in node.emitters.values_mut() {
+            emitter.draw();
+        }
+
+        for batch in node.batches.values() {
+            batch.draw();
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use std::ops::BitOr;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// A set of collision layers. Every physics body has a layer, which is what the body is, and a
+/// mask, which holds the layers that the body will interact with. Two bodies will only interact if
+/// the mask of each of them contains the layer of the other.
+///
+/// In JSON, this is declared as a list of layer names, like `["solids", "players"]`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct CollisionLayers(u8);
+
+impl CollisionLayers {
+    pub const NONE: Self = Self(0);
+    /// Solid tiles and slopes
+    pub const SOLIDS: Self = Self(1);
+    /// Platforms, which can be jumped through from below
+    pub const PLATFORMS: Self = Self(1 << 1);
+    pub const PLAYERS: Self = Self(1 << 2);
+    pub const PROJECTILES: Self = Self(1 << 3);
+    pub const ITEMS: Self = Self(1 << 4);
+    /// Things that harm players, like mines and turrets
+    pub const HAZARDS: Self = Self(1 << 5);
+
+    pub const ALL: Self = Self(0b111111);
+
+    const NAMES: &'static [(&'static str, Self)] = &[
+        ("solids", Self::SOLIDS),
+        ("platforms", Self::PLATFORMS),
+        ("players", Self::PLAYERS),
+        ("projectiles", Self::PROJECTILES),
+        ("items", Self::ITEMS),
+        ("hazards", Self::HAZARDS),
+    ];
+
+    /// Returns `true` if all the layers in `other` are also in `self`
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns `true` if any of the layers in `other` are also in `self`
+    pub fn intersects(&self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// Returns `true` if the masks of two objects both contain the layer of the other object
+    pub fn can_collide(
+        layer: CollisionLayers,
+        mask: CollisionLayers,
+        other_layer: CollisionLayers,
+        other_mask: CollisionLayers,
+    ) -> bool {
+        mask.intersects(other_layer) && other_mask.intersects(layer)
+    }
+
+    pub fn is_all(&self) -> bool {
+        *self == Self::ALL
+    }
+}
+
+impl Default for CollisionLayers {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl BitOr for CollisionLayers {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl Serialize for CollisionLayers {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let names: Vec<&str> = Self::NAMES
+            .iter()
+            .filter(|(_, layer)| self.contains(*layer))
+            .map(|(name, _)| *name)
+            .collect();
+
+        names.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CollisionLayers {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let names = Vec::<String>::deserialize(deserializer)?;
+
+        let mut res = Self::NONE;
+        for name in names {
+            let layer = Self::NAMES
+                .iter()
+                .find(|(layer_name, _)| *layer_name == name)
+                .map(|(_, layer)| *layer)
+                .ok_or_else(|| de::Error::custom(format!("Invalid collision layer '{}'", name)))?;
+
+            res = res | layer;
+        }
+
+        Ok(res)
+    }
+}
@@ -0,0 +1,210 @@
+//! A debug overlay that draws the collision tiles of the map, the contact points of physics bodies
+//! and the ray and shape casts done during the last simulation tick. The colliders and velocities
+//! of the bodies themselves are drawn by `PhysicsBody::debug_draw`, when the overlay is enabled.
+//!
+//! The overlay is toggled with `debug::toggle_physics_debug_draw`, and nothing is recorded while
+//! it is disabled.
+
+use macroquad::{experimental::collections::storage, prelude::*};
+
+use crate::{game::TileCollision, GameWorld};
+
+struct DebugCast {
+    origin: Vec2,
+    end: Vec2,
+    hit: Option<Vec2>,
+}
+
+struct DebugContact {
+    point: Vec2,
+    normal: Vec2,
+}
+
+#[derive(Default)]
+pub struct PhysicsDebugOverlay {
+    casts: Vec<DebugCast>,
+    contacts: Vec<DebugContact>,
+}
+
+impl PhysicsDebugOverlay {
+    const TILE_COLOR: Color = Color {
+        r: 1.0,
+        g: 0.4,
+        b: 0.0,
+        a: 0.6,
+    };
+
+    const PLATFORM_COLOR: Color = Color {
+        r: 0.2,
+        g: 0.8,
+        b: 1.0,
+        a: 0.8,
+    };
+
+    const CAST_COLOR: Color = Color {
+        r: 1.0,
+        g: 1.0,
+        b: 0.0,
+        a: 0.6,
+    };
+
+    const CONTACT_COLOR: Color = Color {
+        r: 1.0,
+        g: 0.0,
+        b: 1.0,
+        a: 1.0,
+    };
+
+    /// The length of the normals drawn at contact points
+    const NORMAL_LENGTH: f32 = 12.0;
+
+    pub fn new() -> Self {
+        PhysicsDebugOverlay {
+            casts: Vec::new(),
+            contacts: Vec::new(),
+        }
+    }
+
+    pub fn is_enabled() -> bool {
+        crate::debug::is_physics_debug_draw_enabled()
+    }
+
+    /// This clears everything recorded during the previous tick. This should be called at the
+    /// start of every simulation tick.
+    pub fn clear() {
+        let mut overlay = storage::get_mut::<PhysicsDebugOverlay>();
+        overlay.casts.clear();
+        overlay.contacts.clear();
+    }
+
+    /// This records a ray or shape cast from `origin` along `ray`, with the position of the hit,
+    /// if anything was hit
+    pub fn add_cast(origin: Vec2, ray: Vec2, hit: Option<Vec2>) {
+        if Self::is_enabled() {
+            let mut overlay = storage::get_mut::<PhysicsDebugOverlay>();
+            overlay.casts.push(DebugCast {
+                origin,
+                end: origin + ray,
+                hit,
+            });
+        }
+    }
+
+    /// This records a contact between a physics body and the map, at `point`, where `normal` is
+    /// the normal of the surface that was hit
+    pub fn add_contact(point: Vec2, normal: Vec2) {
+        if Self::is_enabled() {
+            let mut overlay = storage::get_mut::<PhysicsDebugOverlay>();
+            overlay.contacts.push(DebugContact { point, normal });
+        }
+    }
+
+    pub fn draw() {
+        if !Self::is_enabled() {
+            return;
+        }
+
+        Self::draw_tiles();
+
+        let overlay = storage::get::<PhysicsDebugOverlay>();
+
+        for cast in &overlay.casts {
+            let end = cast.hit.unwrap_or(cast.end);
+            draw_line(
+                cast.origin.x,
+                cast.origin.y,
+                end.x,
+                end.y,
+                1.0,
+                Self::CAST_COLOR,
+            );
+
+            if let Some(hit) = cast.hit {
+                draw_circle_lines(hit.x, hit.y, 3.0, 1.0, Self::CAST_COLOR);
+            }
+        }
+
+        for contact in &overlay.contacts {
+            let end = contact.point + contact.normal * Self::NORMAL_LENGTH;
+
+            draw_circle(contact.point.x, contact.point.y, 2.0, Self::CONTACT_COLOR);
+            draw_line(
+                contact.point.x,
+                contact.point.y,
+                end.x,
+                end.y,
+                1.0,
+                Self::CONTACT_COLOR,
+            );
+        }
+    }
+
+    /// This draws the outlines of solid tiles, the surfaces of slopes and the surfaces of
+    /// platforms, with an arrow pointing in the direction they can be passed through
+    fn draw_tiles() {
+        let world = storage::get::<GameWorld>();
+        let tile_size = world.map.tile_size;
+
+        for y in 0..world.map.grid_size.y as i32 {
+            for x in 0..world.map.grid_size.x as i32 {
+                let position = world.map.world_offset + vec2(x as f32, y as f32) * tile_size;
+
+                match world.get_tile_collision(x, y) {
+                    TileCollision::Empty => {}
+                    TileCollision::Solid => {
+                        draw_rectangle_lines(
+                            position.x,
+                            position.y,
+                            tile_size.x,
+                            tile_size.y,
+                            1.0,
+                            Self::TILE_COLOR,
+                        );
+                    }
+                    TileCollision::Platform => {
+                        draw_line(
+                            position.x,
+                            position.y,
+                            position.x + tile_size.x,
+                            position.y,
+                            2.0,
+                            Self::PLATFORM_COLOR,
+                        );
+
+                        // Platforms can only be passed through from below
+                        let center = position + vec2(tile_size.x / 2.0, 0.0);
+                        let head = center - vec2(0.0, tile_size.y / 4.0);
+                        draw_line(
+                            center.x,
+                            center.y,
+                            head.x,
+                            head.y,
+                            1.0,
+                            Self::PLATFORM_COLOR,
+                        );
+                        draw_triangle(
+                            head - vec2(3.0, 0.0),
+                            head + vec2(3.0, 0.0),
+                            head - vec2(0.0, 4.0),
+                            Self::PLATFORM_COLOR,
+                        );
+                    }
+                    TileCollision::Slope(slope) => {
+                        let bottom = position.y + tile_size.y;
+                        let left_y = bottom - slope.get_surface_height(0.0) * tile_size.y;
+                        let right_y = bottom - slope.get_surface_height(1.0) * tile_size.y;
+
+                        draw_line(
+                            position.x,
+                            left_y,
+                            position.x + tile_size.x,
+                            right_y,
+                            2.0,
+                            Self::TILE_COLOR,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! Force volumes are environment objects, placed in a map, that push physics bodies and particles
+//! inside them, like fans, updrafts and underwater currents. The size, force and falloff of each
+//! volume is stored in the properties of its map object, so that they can be edited in the map
+//! editor.
+//!
+//! A force volume can also be marked as water, in which case buoyant bodies, like most items, will
+//! float at the top of it. A water volume without any force should have its force set to zero,
+//! as the default force is an updraft.
+
+use macroquad::{experimental::collections::storage, prelude::*};
+
+use crate::{effects::active::ExplosionFalloff, json::GenericParam, map::MapObject};
+
+/// A rect that applies `force`, as an acceleration, to everything inside it. The force falls off
+/// along its direction, from the edge of the rect that the force is coming from, to the opposite
+/// edge, so that a fan at the bottom of a volume with an upwards force is strongest at the bottom.
+#[derive(Debug, Clone)]
+pub struct ForceVolume {
+    pub rect: Rect,
+    pub force: Vec2,
+    pub falloff: ExplosionFalloff,
+    /// If this is `true`, buoyant bodies will float at the top of the volume
+    pub is_water: bool,
+}
+
+impl ForceVolume {
+    /// Returns the force applied at `point`, which will be zero if the point is outside the volume
+    pub fn get_force_at(&self, point: Vec2) -> Vec2 {
+        if !self.rect.contains(point) {
+            return Vec2::ZERO;
+        }
+
+        let direction = self.force.normalize_or_zero();
+        let extent = direction.x.abs() * self.rect.w + direction.y.abs() * self.rect.h;
+
+        if extent <= 0.0 {
+            return self.force;
+        }
+
+        let center = self.rect.point() + self.rect.size() / 2.0;
+        let start = center - direction * (extent / 2.0);
+
+        let distance = (point - start).dot(direction) / extent;
+
+        self.force * self.falloff.get_factor(distance)
+    }
+}
+
+#[derive(Default)]
+pub struct ForceVolumes {
+    volumes: Vec<ForceVolume>,
+}
+
+impl ForceVolumes {
+    pub const OBJECT_ID: &'static str = "force_volume";
+
+    pub const SIZE_PROPERTY: &'static str = "size";
+    pub const FORCE_PROPERTY: &'static str = "force";
+    pub const FALLOFF_PROPERTY: &'static str = "falloff";
+    pub const WATER_PROPERTY: &'static str = "water";
+
+    const DEFAULT_SIZE: (f32, f32) = (128.0, 128.0);
+    const DEFAULT_FORCE: (f32, f32) = (0.0, -2400.0);
+
+    /// The factor of the force of a volume that is applied to the particles of particle effects
+    /// that are spawned inside it
+    pub const PARTICLE_FORCE_FACTOR: f32 = 0.25;
+
+    /// This will instantiate the force volumes among `map_objects`, ignoring all other objects
+    pub fn new(map_objects: &[MapObject]) -> Self {
+        let volumes = map_objects
+            .iter()
+            .filter(|object| object.id == Self::OBJECT_ID)
+            .map(|object| {
+                let size = object
+                    .properties
+                    .get(Self::SIZE_PROPERTY)
+                    .and_then(|param| param.get_value::<Vec2>())
+                    .copied()
+                    .unwrap_or_else(|| Self::DEFAULT_SIZE.into());
+
+                let force = object
+                    .properties
+                    .get(Self::FORCE_PROPERTY)
+                    .and_then(|param| param.get_value::<Vec2>())
+                    .copied()
+                    .unwrap_or_else(|| Self::DEFAULT_FORCE.into());
+
+                let falloff = object
+                    .properties
+                    .get(Self::FALLOFF_PROPERTY)
+                    .and_then(|param| param.get_value::<String>())
+                    .map(|value| Self::parse_falloff(value.as_str()))
+                    .unwrap_or_default();
+
+                let is_water = object
+                    .properties
+                    .get(Self::WATER_PROPERTY)
+                    .and_then(|param| param.get_value::<bool>())
+                    .copied()
+                    .unwrap_or(false);
+
+                ForceVolume {
+                    rect: Rect::new(object.position.x, object.position.y, size.x, size.y),
+                    force,
+                    falloff,
+                    is_water,
+                }
+            })
+            .collect();
+
+        ForceVolumes { volumes }
+    }
+
+    fn parse_falloff(value: &str) -> ExplosionFalloff {
+        match value {
+            "linear" => ExplosionFalloff::Linear,
+            "quadratic" => ExplosionFalloff::Quadratic,
+            "inverse_quadratic" => ExplosionFalloff::InverseQuadratic,
+            _ => ExplosionFalloff::None,
+        }
+    }
+
+    /// Returns the sum of the forces of all volumes that contain `point`
+    pub fn get_force(point: Vec2) -> Vec2 {
+        storage::get::<ForceVolumes>()
+            .volumes
+            .iter()
+            .fold(Vec2::ZERO, |force, volume| {
+                force + volume.get_force_at(point)
+            })
+    }
+
+    /// Returns the y position of the surface of the water at `point`, if `point` is inside a water
+    /// volume. If several water volumes contain the point, the highest surface is returned.
+    pub fn get_water_surface(point: Vec2) -> Option<f32> {
+        storage::get::<ForceVolumes>()
+            .volumes
+            .iter()
+            .filter(|volume| volume.is_water && volume.rect.contains(point))
+            .map(|volume| volume.rect.y)
+            .fold(None, |res: Option<f32>, y| {
+                Some(res.map(|res| res.min(y)).unwrap_or(y))
+            })
+    }
+
+    #[cfg(debug_assertions)]
+    pub fn debug_draw() {
+        if crate::debug::is_debug_draw_enabled() {
+            for volume in &storage::get::<ForceVolumes>().volumes {
+                let rect = volume.rect;
+                let color = if volume.is_water { BLUE } else { SKYBLUE };
+                draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 2.0, color);
+
+                let center = rect.point() + rect.size() / 2.0;
+                let end = center + volume.force.normalize_or_zero() * rect.w.min(rect.h) / 2.0;
+                draw_line(center.x, center.y, end.x, end.y, 2.0, color);
+            }
+        }
+    }
+}
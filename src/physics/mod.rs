@@ -0,0 +1,117 @@
+//! Collision queries against the map and the entities of the game world.
+//!
+//! `raycast` and `shapecast` can be used for things like hitscan weapons, grapples and line of
+//! sight checks, and will hit both collision tiles and the colliders of players and items.
+//!
+//! `Rope` is a distance constraint, used by things like grappling hooks, that is applied to a
+//! `PhysicsBody` after it has been updated.
+//!
+//! `CollisionLayers` are used as the layer and mask of physics bodies, projectiles and other
+//! objects, to decide which of them interact with each other, like items that pass through
+//! players or projectiles that ignore hazards.
+//!
+//! `PhysicsDebugOverlay`, which is only available in debug builds, draws collision tiles, contact
+//! points and the casts done during the last tick, on top of the map.
+//!
+//! `ForceVolumes` holds the wind and current volumes of the map, which are applied to all physics
+//! bodies, in `PhysicsBody::update`, as well as to particle effects spawned inside them.
+//!
+//! The positions of players and items are indexed in a `SpatialHash` once per simulation frame,
+//! by `EntityIndex::rebuild`, before the nodes are updated. Queries against the index only work
+//! as a broad-phase, as entities will move during the frame, so the colliders of the returned
+//! entities should always be checked again, after the nodes have been fetched.
+
+use macroquad::{
+    experimental::{
+        collections::storage,
+        scene::{self, Handle},
+    },
+    prelude::*,
+};
+
+mod collision_layers;
+#[cfg(debug_assertions)]
+mod debug_overlay;
+mod force_volumes;
+mod queries;
+mod rope;
+mod spatial_hash;
+
+pub use collision_layers::CollisionLayers;
+#[cfg(debug_assertions)]
+pub use debug_overlay::PhysicsDebugOverlay;
+pub use force_volumes::{ForceVolume, ForceVolumes};
+pub use queries::{raycast, shapecast, QueryEntity, QueryHit, QueryMask};
+pub use rope::Rope;
+pub use spatial_hash::SpatialHash;
+
+use crate::{Item, Player};
+
+pub struct EntityIndex {
+    players: SpatialHash<Handle<Player>>,
+    items: SpatialHash<Handle<Item>>,
+}
+
+impl EntityIndex {
+    const CELL_SIZE: f32 = 128.0;
+
+    /// The rects of entities are padded by this, when indexed, to account for any movement
+    /// during the frame
+    const PADDING: f32 = 32.0;
+
+    pub fn new() -> Self {
+        EntityIndex {
+            players: SpatialHash::new(Self::CELL_SIZE),
+            items: SpatialHash::new(Self::CELL_SIZE),
+        }
+    }
+
+    /// This will rebuild the index from the current positions of all players and items. This
+    /// should be called once per simulation frame, before the nodes are updated.
+    pub fn rebuild() {
+        let mut index = storage::get_mut::<EntityIndex>();
+
+        index.players.clear();
+        index.items.clear();
+
+        for player in scene::find_nodes_by_type::<Player>() {
+            let rect = Self::pad_rect(player.get_collider_rect());
+            index.players.insert(rect, player.handle());
+        }
+
+        for item in scene::find_nodes_by_type::<Item>() {
+            let rect = Self::pad_rect(item.body.get_collider_rect());
+            index.items.insert(rect, item.handle());
+        }
+    }
+
+    /// Returns the handles of all players that might overlap `rect`
+    pub fn get_players(rect: &Rect) -> Vec<Handle<Player>> {
+        storage::get::<EntityIndex>().players.query(rect)
+    }
+
+    /// Returns the handles of all players that might contain `point`
+    pub fn get_players_at(point: Vec2) -> Vec<Handle<Player>> {
+        storage::get::<EntityIndex>().players.query_point(point)
+    }
+
+    /// Returns the handles of all items that might overlap `rect`
+    pub fn get_items(rect: &Rect) -> Vec<Handle<Item>> {
+        storage::get::<EntityIndex>().items.query(rect)
+    }
+
+    fn pad_rect(rect: Rect) -> Rect {
+        Rect::new(
+            rect.x - Self::PADDING,
+            rect.y - Self::PADDING,
+            rect.w + Self::PADDING * 2.0,
+            rect.h + Self::PADDING * 2.0,
+        )
+    }
+}
+
+impl Default for EntityIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
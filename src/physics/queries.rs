@@ -0,0 +1,420 @@
+use std::ops::BitOr;
+
+use macroquad::{
+    experimental::{
+        collections::storage,
+        scene::{self, Handle},
+    },
+    prelude::*,
+};
+
+use crate::{game::TileCollision, map::TileSlope, GameWorld, Item, Player};
+
+use super::EntityIndex;
+
+#[cfg(debug_assertions)]
+use super::PhysicsDebugOverlay;
+
+/// This selects what a query will hit. Masks can be combined with `|`, like
+/// `QueryMask::TILES | QueryMask::PLAYERS`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct QueryMask(u8);
+
+#[allow(dead_code)]
+impl QueryMask {
+    /// Solid tiles and slopes
+    pub const TILES: Self = Self(1);
+    /// Platforms, which will only be hit from above
+    pub const PLATFORMS: Self = Self(1 << 1);
+    pub const PLAYERS: Self = Self(1 << 2);
+    pub const ITEMS: Self = Self(1 << 3);
+
+    pub const ALL: Self = Self(0b1111);
+
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for QueryMask {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// The entity hit by a query
+#[allow(dead_code)]
+#[derive(Copy, Clone)]
+pub enum QueryEntity {
+    Player(Handle<Player>),
+    Item(Handle<Item>),
+}
+
+#[allow(dead_code)]
+#[derive(Copy, Clone)]
+pub struct QueryHit {
+    /// The position of the hit. For shape casts, this is the position of the center of the shape
+    /// at the time of the hit.
+    pub point: Vec2,
+    /// The normal of the surface that was hit
+    pub normal: Vec2,
+    /// The fraction of the ray or cast that was traveled before the hit, from zero to one
+    pub fraction: f32,
+    /// The entity that was hit, if any. If this is `None` a tile was hit.
+    pub entity: Option<QueryEntity>,
+}
+
+/// Casts a ray from `origin` along `ray`, where the length of `ray` is the length of the cast,
+/// and returns the first hit on anything selected by `mask`, if any.
+///
+/// As entities are fetched from the scene, any node that is borrowed when this is called, like the
+/// player that is casting the ray, will be excluded from the query.
+pub fn raycast(origin: Vec2, ray: Vec2, mask: QueryMask) -> Option<QueryHit> {
+    let mut res = None;
+
+    if mask.contains(QueryMask::TILES) || mask.contains(QueryMask::PLATFORMS) {
+        let world = storage::get::<GameWorld>();
+        res = raycast_tiles(&world, origin, ray, mask);
+    }
+
+    let bounds = get_cast_bounds(&Rect::new(origin.x, origin.y, 0.0, 0.0), ray);
+
+    if let Some(hit) = cast_entities(&bounds, origin, ray, Vec2::ZERO, mask) {
+        if res.map(|res| hit.fraction < res.fraction).unwrap_or(true) {
+            res = Some(hit);
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    PhysicsDebugOverlay::add_cast(origin, ray, res.map(|hit| hit.point));
+
+    res
+}
+
+/// Casts `rect` along `ray`, where the length of `ray` is the length of the cast, and returns the
+/// first hit on anything selected by `mask`, if any.
+///
+/// Slope tiles are treated as if they were solid tiles, by shape casts.
+#[allow(dead_code)]
+pub fn shapecast(rect: &Rect, ray: Vec2, mask: QueryMask) -> Option<QueryHit> {
+    let half_size = rect.size() / 2.0;
+    let origin = rect.point() + half_size;
+
+    let bounds = get_cast_bounds(rect, ray);
+
+    let mut res: Option<QueryHit> = None;
+
+    if mask.contains(QueryMask::TILES) || mask.contains(QueryMask::PLATFORMS) {
+        let world = storage::get::<GameWorld>();
+        let tile_size = world.map.tile_size;
+
+        let min = ((bounds.point() - world.map.world_offset) / tile_size).floor();
+        let max = ((bounds.point() + bounds.size() - world.map.world_offset) / tile_size).floor();
+
+        for y in min.y as i32..=max.y as i32 {
+            for x in min.x as i32..=max.x as i32 {
+                let collision = world.get_tile_collision(x, y);
+
+                let is_platform = collision == TileCollision::Platform;
+
+                let is_hit = match collision {
+                    TileCollision::Empty => false,
+                    TileCollision::Platform => mask.contains(QueryMask::PLATFORMS),
+                    _ => mask.contains(QueryMask::TILES),
+                };
+
+                if !is_hit {
+                    continue;
+                }
+
+                let position = world.map.world_offset + vec2(x as f32, y as f32) * tile_size;
+                let tile_rect = expand_rect(
+                    &Rect::new(position.x, position.y, tile_size.x, tile_size.y),
+                    half_size,
+                );
+
+                if let Some((fraction, normal)) = ray_vs_rect(origin, ray, &tile_rect) {
+                    if is_platform && normal.y >= 0.0 {
+                        continue;
+                    }
+
+                    if res.map(|res| fraction < res.fraction).unwrap_or(true) {
+                        res = Some(QueryHit {
+                            point: origin + ray * fraction,
+                            normal,
+                            fraction,
+                            entity: None,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(hit) = cast_entities(&bounds, origin, ray, half_size, mask) {
+        if res.map(|res| hit.fraction < res.fraction).unwrap_or(true) {
+            res = Some(hit);
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    PhysicsDebugOverlay::add_cast(origin, ray, res.map(|hit| hit.point));
+
+    res
+}
+
+/// This traverses the tiles along the ray, from `origin`, in order, and returns the first hit
+fn raycast_tiles(world: &GameWorld, origin: Vec2, ray: Vec2, mask: QueryMask) -> Option<QueryHit> {
+    let tile_size = world.map.tile_size;
+    let start = origin - world.map.world_offset;
+
+    let mut x = (start.x / tile_size.x).floor() as i32;
+    let mut y = (start.y / tile_size.y).floor() as i32;
+
+    let step_x = ray.x.signum() as i32;
+    let step_y = ray.y.signum() as i32;
+
+    let get_t_max = |cell: i32, start: f32, ray: f32, size: f32| {
+        if ray > 0.0 {
+            ((cell + 1) as f32 * size - start) / ray
+        } else if ray < 0.0 {
+            (cell as f32 * size - start) / ray
+        } else {
+            f32::INFINITY
+        }
+    };
+
+    let mut t_max_x = get_t_max(x, start.x, ray.x, tile_size.x);
+    let mut t_max_y = get_t_max(y, start.y, ray.y, tile_size.y);
+
+    let t_delta_x = if ray.x != 0.0 {
+        tile_size.x / ray.x.abs()
+    } else {
+        f32::INFINITY
+    };
+
+    let t_delta_y = if ray.y != 0.0 {
+        tile_size.y / ray.y.abs()
+    } else {
+        f32::INFINITY
+    };
+
+    // The fraction of the ray where it entered the current tile, and the normal of the edge it
+    // entered through
+    let mut t_entry = 0.0;
+    let mut entry_normal = -ray.normalize_or_zero();
+
+    let is_hitting_tiles = mask.contains(QueryMask::TILES);
+    let is_hitting_platforms = mask.contains(QueryMask::PLATFORMS);
+
+    while t_entry <= 1.0 {
+        let t_exit = t_max_x.min(t_max_y).min(1.0);
+
+        let hit = match world.get_tile_collision(x, y) {
+            TileCollision::Solid if is_hitting_tiles => Some((t_entry, entry_normal)),
+            TileCollision::Platform
+                if is_hitting_platforms && t_entry > 0.0 && entry_normal.y < 0.0 =>
+            {
+                Some((t_entry, entry_normal))
+            }
+            TileCollision::Slope(slope) if is_hitting_tiles => {
+                let tile_position = world.map.world_offset + vec2(x as f32, y as f32) * tile_size;
+                let tile_rect =
+                    Rect::new(tile_position.x, tile_position.y, tile_size.x, tile_size.y);
+
+                ray_vs_slope(
+                    origin,
+                    ray,
+                    &tile_rect,
+                    slope,
+                    t_entry,
+                    t_exit,
+                    entry_normal,
+                )
+            }
+            _ => None,
+        };
+
+        if let Some((fraction, normal)) = hit {
+            return Some(QueryHit {
+                point: origin + ray * fraction,
+                normal,
+                fraction,
+                entity: None,
+            });
+        }
+
+        if t_max_x < t_max_y {
+            t_entry = t_max_x;
+            t_max_x += t_delta_x;
+            x += step_x;
+            entry_normal = vec2(-step_x as f32, 0.0);
+        } else {
+            t_entry = t_max_y;
+            t_max_y += t_delta_y;
+            y += step_y;
+            entry_normal = vec2(0.0, -step_y as f32);
+        }
+
+        if t_entry.is_infinite() {
+            break;
+        }
+    }
+
+    None
+}
+
+/// Checks for a hit with the surface of a slope, for the part of the ray that is inside the slope
+/// tile, between `t_entry` and `t_exit`
+fn ray_vs_slope(
+    origin: Vec2,
+    ray: Vec2,
+    tile_rect: &Rect,
+    slope: TileSlope,
+    t_entry: f32,
+    t_exit: f32,
+    entry_normal: Vec2,
+) -> Option<(f32, Vec2)> {
+    let get_surface_y = |x: f32| {
+        let x = (x - tile_rect.x) / tile_rect.w;
+        tile_rect.y + tile_rect.h - slope.get_surface_height(x) * tile_rect.h
+    };
+
+    // If the ray enters the tile below the surface, it hits the edge of the tile
+    let entry = origin + ray * t_entry;
+    if entry.y >= get_surface_y(entry.x) {
+        return Some((t_entry, entry_normal));
+    }
+
+    // The surface is the line `y = a + b * x`
+    let b = -slope.get_gradient() * tile_rect.h / tile_rect.w;
+    let a = get_surface_y(tile_rect.x) - b * tile_rect.x;
+
+    let denominator = ray.y - b * ray.x;
+    if denominator == 0.0 {
+        return None;
+    }
+
+    let t = (a + b * origin.x - origin.y) / denominator;
+    if t >= t_entry && t <= t_exit {
+        Some((t, slope.get_normal(tile_rect.size())))
+    } else {
+        None
+    }
+}
+
+fn cast_entities(
+    bounds: &Rect,
+    origin: Vec2,
+    ray: Vec2,
+    half_size: Vec2,
+    mask: QueryMask,
+) -> Option<QueryHit> {
+    let mut res: Option<QueryHit> = None;
+
+    let mut check_hit = |collider: &Rect, entity: QueryEntity| {
+        let collider = expand_rect(collider, half_size);
+        if let Some((fraction, normal)) = ray_vs_rect(origin, ray, &collider) {
+            if res.map(|res| fraction < res.fraction).unwrap_or(true) {
+                res = Some(QueryHit {
+                    point: origin + ray * fraction,
+                    normal,
+                    fraction,
+                    entity: Some(entity),
+                });
+            }
+        }
+    };
+
+    if mask.contains(QueryMask::PLAYERS) {
+        for player in EntityIndex::get_players(bounds)
+            .into_iter()
+            .filter_map(scene::try_get_node)
+        {
+            check_hit(
+                &player.get_collider_rect(),
+                QueryEntity::Player(player.handle()),
+            );
+        }
+    }
+
+    if mask.contains(QueryMask::ITEMS) {
+        for item in EntityIndex::get_items(bounds)
+            .into_iter()
+            .filter_map(scene::try_get_node)
+        {
+            check_hit(
+                &item.body.get_collider_rect(),
+                QueryEntity::Item(item.handle()),
+            );
+        }
+    }
+
+    res
+}
+
+/// Returns the fraction of `ray` where it enters `rect`, along with the normal of the edge it
+/// enters through, if it hits it. If `origin` is inside of `rect` the fraction will be zero.
+fn ray_vs_rect(origin: Vec2, ray: Vec2, rect: &Rect) -> Option<(f32, Vec2)> {
+    if rect.contains(origin) {
+        return Some((0.0, -ray.normalize_or_zero()));
+    }
+
+    let mut t_min = 0.0f32;
+    let mut t_max = 1.0f32;
+    let mut normal = Vec2::ZERO;
+
+    let axes = [
+        (origin.x, ray.x, rect.x, rect.x + rect.w, vec2(1.0, 0.0)),
+        (origin.y, ray.y, rect.y, rect.y + rect.h, vec2(0.0, 1.0)),
+    ];
+
+    for (origin, ray, min, max, axis) in axes {
+        if ray == 0.0 {
+            if origin < min || origin > max {
+                return None;
+            }
+        } else {
+            let mut t1 = (min - origin) / ray;
+            let mut t2 = (max - origin) / ray;
+            let mut axis_normal = -axis;
+
+            if t1 > t2 {
+                std::mem::swap(&mut t1, &mut t2);
+                axis_normal = axis;
+            }
+
+            if t1 > t_min {
+                t_min = t1;
+                normal = axis_normal;
+            }
+
+            t_max = t_max.min(t2);
+
+            if t_min > t_max {
+                return None;
+            }
+        }
+    }
+
+    Some((t_min, normal))
+}
+
+/// Returns the bounding rect of `rect` moved along all of `ray`
+fn get_cast_bounds(rect: &Rect, ray: Vec2) -> Rect {
+    let min = rect.point().min(rect.point() + ray);
+    let max = (rect.point() + rect.size()).max(rect.point() + rect.size() + ray);
+
+    Rect::new(min.x, min.y, max.x - min.x, max.y - min.y)
+}
+
+fn expand_rect(rect: &Rect, half_size: Vec2) -> Rect {
+    Rect::new(
+        rect.x - half_size.x,
+        rect.y - half_size.y,
+        rect.w + half_size.x * 2.0,
+        rect.h + half_size.y * 2.0,
+    )
+}
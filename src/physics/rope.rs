@@ -0,0 +1,104 @@
+use macroquad::prelude::*;
+
+use crate::components::PhysicsBody;
+
+/// A single distance constraint between a fixed anchor and the center of a physics body. The rope
+/// only pulls, so the body can move freely within `length` of the anchor, and when the rope is
+/// taut, any velocity away from the anchor is removed, leaving only the velocity tangential to
+/// the rope, which makes the body swing around the anchor with the momentum it already has.
+#[derive(Debug, Clone)]
+pub struct Rope {
+    pub anchor: Vec2,
+    pub length: f32,
+    pub min_length: f32,
+    pub max_length: f32,
+}
+
+impl Rope {
+    pub fn new(anchor: Vec2, length: f32, min_length: f32, max_length: f32) -> Self {
+        Rope {
+            anchor,
+            length: length.clamp(min_length, max_length),
+            min_length,
+            max_length,
+        }
+    }
+
+    /// This changes the length of the rope by `delta`, where a negative value will reel the rope
+    /// in and a positive value will reel it out
+    pub fn reel(&mut self, delta: f32) {
+        self.length = (self.length + delta).clamp(self.min_length, self.max_length);
+    }
+
+    /// Returns `true` if a body with its center at `point` would be held back by the rope
+    pub fn is_taut(&self, point: Vec2) -> bool {
+        point.distance(self.anchor) >= self.length
+    }
+
+    /// This moves `body` back within the length of the rope, if it has moved past it, and removes
+    /// its velocity away from the anchor. This should be called after the body has been updated.
+    pub fn apply(&self, body: &mut PhysicsBody) {
+        let collider = body.get_collider_rect();
+        let center = collider.point() + collider.size() / 2.0;
+
+        let delta = center - self.anchor;
+        let distance = delta.length();
+
+        if distance > self.length && distance > 0.0 {
+            let normal = delta / distance;
+
+            body.translate(-normal * (distance - self.length));
+
+            let radial_speed = body.velocity.dot(normal);
+            if radial_speed > 0.0 {
+                body.velocity -= normal * radial_speed;
+            }
+        }
+    }
+
+    /// This draws the rope from `from` to the anchor. If a texture is specified, it will be tiled
+    /// along the rope, with the width of the texture as the length of each segment. If not, the
+    /// rope is drawn as a line of `color`.
+    pub fn draw(&self, from: Vec2, texture: Option<Texture2D>, color: Color) {
+        let delta = self.anchor - from;
+        let length = delta.length();
+
+        if length <= 0.0 {
+            return;
+        }
+
+        match texture {
+            Some(texture) => {
+                let rotation = delta.y.atan2(delta.x);
+                let direction = delta / length;
+
+                let segment_size = vec2(texture.width(), texture.height());
+
+                let mut offset = 0.0;
+                while offset < length {
+                    let segment_length = segment_size.x.min(length - offset);
+                    let position = from + direction * offset;
+
+                    draw_texture_ex(
+                        texture,
+                        position.x,
+                        position.y - segment_size.y / 2.0,
+                        WHITE,
+                        DrawTextureParams {
+                            dest_size: Some(vec2(segment_length, segment_size.y)),
+                            source: Some(Rect::new(0.0, 0.0, segment_length, segment_size.y)),
+                            rotation,
+                            pivot: Some(position),
+                            ..Default::default()
+                        },
+                    );
+
+                    offset += segment_size.x;
+                }
+            }
+            None => {
+                draw_line(from.x, from.y, self.anchor.x, self.anchor.y, 2.0, color);
+            }
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use macroquad::prelude::*;
+
+/// A uniform grid that is used as a broad-phase for collision queries. Each value is inserted
+/// with a rect and stored in every cell that the rect overlaps, so a query only has to check the
+/// values in the cells overlapped by the query rect, in stead of every value.
+pub struct SpatialHash<T> {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    entries: Vec<(Rect, T)>,
+}
+
+impl<T: Copy> SpatialHash<T> {
+    pub fn new(cell_size: f32) -> Self {
+        SpatialHash {
+            cell_size,
+            cells: HashMap::new(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// This removes all values, while keeping the allocated cells, so that rebuilding the hash
+    /// every frame does not allocate once it is warmed up
+    pub fn clear(&mut self) {
+        for cell in self.cells.values_mut() {
+            cell.clear();
+        }
+
+        self.entries.clear();
+    }
+
+    pub fn insert(&mut self, rect: Rect, value: T) {
+        let i = self.entries.len();
+        self.entries.push((rect, value));
+
+        let (min, max) = self.get_cell_range(&rect);
+        for y in min.1..=max.1 {
+            for x in min.0..=max.0 {
+                self.cells.entry((x, y)).or_insert_with(Vec::new).push(i);
+            }
+        }
+    }
+
+    /// Returns all the values that were inserted with a rect that overlaps `rect`
+    pub fn query(&self, rect: &Rect) -> Vec<T> {
+        let mut indices = Vec::new();
+
+        let (min, max) = self.get_cell_range(rect);
+        for y in min.1..=max.1 {
+            for x in min.0..=max.0 {
+                if let Some(cell) = self.cells.get(&(x, y)) {
+                    indices.extend_from_slice(cell);
+                }
+            }
+        }
+
+        indices.sort_unstable();
+        indices.dedup();
+
+        indices
+            .into_iter()
+            .filter_map(|i| {
+                let (entry_rect, value) = &self.entries[i];
+                if entry_rect.overlaps(rect) {
+                    Some(*value)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Returns all the values that were inserted with a rect that contains `point`
+    pub fn query_point(&self, point: Vec2) -> Vec<T> {
+        let cell = self.get_cell(point);
+
+        self.cells
+            .get(&cell)
+            .map(|cell| {
+                cell.iter()
+                    .filter_map(|i| {
+                        let (entry_rect, value) = &self.entries[*i];
+                        if entry_rect.contains(point) {
+                            Some(*value)
+                        } else {
+                            None
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn get_cell(&self, point: Vec2) -> (i32, i32) {
+        (
+            (point.x / self.cell_size).floor() as i32,
+            (point.y / self.cell_size).floor() as i32,
+        )
+    }
+
+    fn get_cell_range(&self, rect: &Rect) -> ((i32, i32), (i32, i32)) {
+        let min = self.get_cell(rect.point());
+        let max = self.get_cell(rect.point() + rect.size());
+
+        (min, max)
+    }
+}
@@ -2,10 +2,15 @@ use macroquad::{
     experimental::{collections::storage, scene},
     math::vec2,
     rand,
-    time::get_frame_time,
 };
 
-use crate::{items::Item, player::GameInput, GameWorld, Player};
+use crate::{
+    game::SIMULATION_DELTA_TIME,
+    items::{Item, WeaponSlot},
+    physics::{self, QueryMask},
+    player::GameInput,
+    GameWorld, Player,
+};
 
 pub struct Ai {
     jump_cooldown: f32,
@@ -103,23 +108,31 @@ impl Ai {
             self.throw_cooldown = 1.;
         }
 
-        if player.body.position.distance(foe.body.position) <= 100. || rand::gen_range(0, 180) == 5
+        let is_foe_visible = {
+            let origin = player.body.position + player.body.size / 2.0;
+            let target = foe.body.position + foe.body.size / 2.0;
+            physics::raycast(origin, target - origin, QueryMask::TILES).is_none()
+        };
+
+        if player.body.position.distance(foe.body.position) <= 100.
+            || (is_foe_visible && rand::gen_range(0, 180) == 5)
         {
             //
             if player.state_machine.state() == Player::ST_NORMAL && player.weapon.is_some() {
+                player.attack_slot = WeaponSlot::MainHand;
                 player.state_machine.set_state(Player::ST_ATTACK);
             }
         }
 
         if self.jump_cooldown >= 0. {
-            self.jump_cooldown -= get_frame_time();
+            self.jump_cooldown -= SIMULATION_DELTA_TIME;
         }
         if self.throw_cooldown >= 0. {
-            self.throw_cooldown -= get_frame_time();
+            self.throw_cooldown -= SIMULATION_DELTA_TIME;
         }
 
         if self.keep_direction_timeout >= 0. {
-            self.keep_direction_timeout -= get_frame_time();
+            self.keep_direction_timeout -= SIMULATION_DELTA_TIME;
         }
 
         if self.throw_cooldown <= 0.0 {
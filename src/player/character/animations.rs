@@ -5,6 +5,7 @@ use serde::{Deserialize, Serialize};
 use crate::components::{Animation, AnimationParams};
 use crate::json;
 use crate::player::Player;
+use crate::symbol::Symbol;
 
 /// This is used in stead of `AnimationParams`, as we have different data requirements, in the case
 /// of a player character, compared to most other use cases. We want to have a default animation
@@ -75,71 +76,85 @@ pub struct PlayerAnimations {
 impl PlayerAnimations {
     pub fn default_idle_animation() -> Animation {
         Animation {
-            id: Player::IDLE_ANIMATION_ID.to_string(),
+            id: Symbol::new(Player::IDLE_ANIMATION_ID),
             row: 0,
             frames: 7,
             fps: 12,
             is_looping: true,
+            hitboxes: Vec::new(),
+            sounds: Vec::new(),
         }
     }
 
     pub fn default_move_animation() -> Animation {
         Animation {
-            id: Player::MOVE_ANIMATION_ID.to_string(),
+            id: Symbol::new(Player::MOVE_ANIMATION_ID),
             row: 1,
             frames: 6,
             fps: 10,
             is_looping: true,
+            hitboxes: Vec::new(),
+            sounds: Vec::new(),
         }
     }
 
     pub fn default_jump_animation() -> Animation {
         Animation {
-            id: Player::JUMP_ANIMATION_ID.to_string(),
+            id: Symbol::new(Player::JUMP_ANIMATION_ID),
             row: 2,
             frames: 1,
             fps: 5,
             is_looping: false,
+            hitboxes: Vec::new(),
+            sounds: Vec::new(),
         }
     }
 
     pub fn default_fall_animation() -> Animation {
         Animation {
-            id: Player::FALL_ANIMATION_ID.to_string(),
+            id: Symbol::new(Player::FALL_ANIMATION_ID),
             row: 3,
             frames: 1,
             fps: 8,
             is_looping: true,
+            hitboxes: Vec::new(),
+            sounds: Vec::new(),
         }
     }
 
     pub fn default_crouch_animation() -> Animation {
         Animation {
-            id: Player::CROUCH_ANIMATION_ID.to_string(),
+            id: Symbol::new(Player::CROUCH_ANIMATION_ID),
             row: 4,
             frames: 1,
             fps: 8,
             is_looping: false,
+            hitboxes: Vec::new(),
+            sounds: Vec::new(),
         }
     }
 
     pub fn default_death_back_animation() -> Animation {
         Animation {
-            id: Player::DEATH_BACK_ANIMATION_ID.to_string(),
+            id: Symbol::new(Player::DEATH_BACK_ANIMATION_ID),
             row: 5,
             frames: 7,
             fps: 10,
             is_looping: false,
+            hitboxes: Vec::new(),
+            sounds: Vec::new(),
         }
     }
 
     pub fn default_death_face_animation() -> Animation {
         Animation {
-            id: Player::DEATH_FACE_ANIMATION_ID.to_string(),
+            id: Symbol::new(Player::DEATH_FACE_ANIMATION_ID),
             row: 6,
             frames: 7,
             fps: 10,
             is_looping: false,
+            hitboxes: Vec::new(),
+            sounds: Vec::new(),
         }
     }
 }
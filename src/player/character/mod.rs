@@ -7,7 +7,7 @@ use macroquad::prelude::*;
 
 use serde::{Deserialize, Serialize};
 
-use crate::json;
+use crate::{components::TrailParams, json};
 
 mod animations;
 
@@ -28,6 +28,10 @@ pub struct PlayerCharacterParams {
     /// `PlayerCharacterParams` entry.
     #[serde(flatten)]
     pub animation: PlayerAnimationParams,
+    /// The id of the texture used for the portrait of the player character, in the match HUD.
+    /// If this is not specified, the first frame of the player character's texture is used.
+    #[serde(default, rename = "portrait", skip_serializing_if = "Option::is_none")]
+    pub portrait_texture_id: Option<String>,
     /// The size of the players collider.
     /// This should, in general, be smaller than the sprite size
     #[serde(
@@ -61,9 +65,38 @@ pub struct PlayerCharacterParams {
     /// This is the slide duration of the player character
     #[serde(default = "PlayerCharacterParams::default_slide_duration")]
     pub slide_duration: f32,
+    /// The path of a script that implements the unique abilities of the player character, if any,
+    /// relative to the assets directory (see `scripting::ability`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ability_script: Option<String>,
     /// This is the float gravity factor of the player character
     #[serde(default = "PlayerCharacterParams::default_float_gravity_factor")]
     pub float_gravity_factor: f32,
+    /// This is the time, in seconds, that the player character can still jump after walking off
+    /// a ledge (coyote time)
+    #[serde(default = "PlayerCharacterParams::default_coyote_time")]
+    pub coyote_time: f32,
+    /// This is the time, in seconds, that a jump pressed in the air is buffered for, so that the
+    /// player character will jump if it lands within it
+    #[serde(default = "PlayerCharacterParams::default_jump_buffer_time")]
+    pub jump_buffer_time: f32,
+    /// This is the mass of the player character, which divides the knockback and impulses it
+    /// receives, so that heavier characters are pushed around less
+    #[serde(default = "PlayerCharacterParams::default_mass")]
+    pub mass: f32,
+    /// This is the amount of damage the player character can receive before it dies. The default
+    /// amount of damage dealt by effects is equal to the default max health, so, unless either is
+    /// changed, any hit will be lethal.
+    #[serde(default = "PlayerCharacterParams::default_max_health")]
+    pub max_health: f32,
+    /// The ids of the weapon skins preferred by the player character, in order of preference.
+    /// When a weapon is picked up, the first of these that is available for the weapon is used.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub weapon_skins: Vec<String>,
+    /// The trail drawn behind the player character while it is sliding. Set this to `null` to
+    /// disable the trail.
+    #[serde(default = "PlayerCharacterParams::default_slide_trail")]
+    pub slide_trail: Option<TrailParams>,
 }
 
 impl PlayerCharacterParams {
@@ -75,6 +108,12 @@ impl PlayerCharacterParams {
     const DEFAULT_SLIDE_SPEED_FACTOR: f32 = 3.0;
     const DEFAULT_SLIDE_DURATION: f32 = 0.1;
     const DEFAULT_FLOAT_GRAVITY_FACTOR: f32 = 0.5;
+    const DEFAULT_COYOTE_TIME: f32 = 0.15;
+    const DEFAULT_JUMP_BUFFER_TIME: f32 = 0.1;
+
+    pub const DEFAULT_MAX_HEALTH: f32 = 100.0;
+
+    const DEFAULT_MASS: f32 = 1.0;
 
     const DEFAULT_COLLIDER_WIDTH: f32 = 20.0;
     const DEFAULT_COLLIDER_HEIGHT: f32 = 54.0;
@@ -110,6 +149,26 @@ impl PlayerCharacterParams {
         Self::DEFAULT_FLOAT_GRAVITY_FACTOR
     }
 
+    pub fn default_coyote_time() -> f32 {
+        Self::DEFAULT_COYOTE_TIME
+    }
+
+    pub fn default_jump_buffer_time() -> f32 {
+        Self::DEFAULT_JUMP_BUFFER_TIME
+    }
+
+    pub fn default_mass() -> f32 {
+        Self::DEFAULT_MASS
+    }
+
+    pub fn default_max_health() -> f32 {
+        Self::DEFAULT_MAX_HEALTH
+    }
+
+    pub fn default_slide_trail() -> Option<TrailParams> {
+        Some(TrailParams::default())
+    }
+
     pub fn default_collider_size() -> Vec2 {
         vec2(Self::DEFAULT_COLLIDER_WIDTH, Self::DEFAULT_COLLIDER_HEIGHT)
     }
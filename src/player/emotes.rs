@@ -0,0 +1,133 @@
+//! Cosmetic effects that are spawned as a reaction to gameplay events, like a skull puff when a
+//! player dies, a star burst when a player scores a kill and a combo counter over players that
+//! score several kills in quick succession.
+//! These are driven entirely by `GameEvent`s, so they will look the same in local and networked
+//! play.
+
+use std::collections::HashMap;
+
+use macroquad::{
+    experimental::scene::{self, Handle, Node, RefMut},
+    prelude::*,
+};
+
+use crate::{
+    game::GameEvent,
+    text::{draw_aligned_text, HorizontalAlignment, VerticalAlignment},
+    ParticleEmitters, Player,
+};
+
+struct Combo {
+    player: Handle<Player>,
+    count: u32,
+    timer: f32,
+}
+
+#[derive(Default)]
+pub struct PlayerEmotes {
+    combos: HashMap<u8, Combo>,
+}
+
+impl PlayerEmotes {
+    pub const LISTENER_ID: &'static str = "player_emotes";
+
+    pub const DEATH_PARTICLE_EFFECT_ID: &'static str = "skull_puff";
+    pub const KILL_PARTICLE_EFFECT_ID: &'static str = "star_burst";
+
+    /// The maximum time between two kills, for them to count towards the same combo
+    const COMBO_WINDOW: f32 = 2.5;
+
+    const COMBO_Y_OFFSET: f32 = -24.0;
+    const COMBO_FONT_SIZE: u16 = 24;
+    const COMBO_COLOR: Color = Color {
+        r: 1.0,
+        g: 0.85,
+        b: 0.2,
+        a: 1.0,
+    };
+
+    pub fn new() -> Self {
+        PlayerEmotes {
+            combos: HashMap::new(),
+        }
+    }
+
+    pub fn on_game_event(event: &GameEvent) {
+        if let Some(mut node) = scene::find_node_by_type::<PlayerEmotes>() {
+            node.handle_event(event);
+        }
+    }
+
+    fn handle_event(&mut self, event: &GameEvent) {
+        if let GameEvent::PlayerKilled {
+            player_id,
+            position,
+            killed_by,
+            ..
+        } = *event
+        {
+            let mut particles = scene::find_node_by_type::<ParticleEmitters>().unwrap();
+
+            particles.spawn(Self::DEATH_PARTICLE_EFFECT_ID, position);
+
+            // A dead player loses any combo in progress
+            self.combos.remove(&player_id);
+
+            if let Some(killed_by) = killed_by {
+                let killer = scene::find_nodes_by_type::<Player>().find(|p| p.id == killed_by);
+
+                if let Some(killer) = killer {
+                    particles.spawn(Self::KILL_PARTICLE_EFFECT_ID, killer.body.position);
+
+                    let combo = self.combos.entry(killed_by).or_insert(Combo {
+                        player: killer.handle(),
+                        count: 0,
+                        timer: 0.0,
+                    });
+
+                    combo.count += 1;
+                    combo.timer = 0.0;
+                }
+            }
+        }
+    }
+}
+
+impl Node for PlayerEmotes {
+    fn update(mut node: RefMut<Self>) {
+        let dt = get_frame_time();
+
+        for combo in node.combos.values_mut() {
+            combo.timer += dt;
+        }
+
+        node.combos
+            .retain(|_, combo| combo.timer < Self::COMBO_WINDOW);
+    }
+
+    fn draw(node: RefMut<Self>) {
+        for combo in node.combos.values() {
+            if combo.count > 1 {
+                if let Some(player) = scene::try_get_node(combo.player) {
+                    let position = player.body.position + vec2(0.0, Self::COMBO_Y_OFFSET);
+
+                    // Fade out as the combo window runs out
+                    let mut color = Self::COMBO_COLOR;
+                    color.a = 1.0 - (combo.timer / Self::COMBO_WINDOW);
+
+                    draw_aligned_text(
+                        &format!("x{}", combo.count),
+                        position,
+                        HorizontalAlignment::Center,
+                        VerticalAlignment::Bottom,
+                        TextParams {
+                            font_size: Self::COMBO_FONT_SIZE,
+                            color,
+                            ..Default::default()
+                        },
+                    );
+                }
+            }
+        }
+    }
+}
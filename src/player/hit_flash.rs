@@ -0,0 +1,155 @@
+//! A standardized hit reaction, where a damaged player flashes white and is briefly outlined.
+//! This is driven by the `PlayerDamaged` game event, so every source of damage gives the same
+//! feedback, and all players are drawn with the same, shared material while flashing.
+
+use macroquad::{
+    experimental::{collections::storage, scene},
+    material::{gl_use_default_material, gl_use_material, load_material, Material, MaterialParams},
+    prelude::*,
+};
+
+use crate::{game::GameEvent, Config, Player};
+
+const VERTEX_SHADER: &str = r#"#version 100
+attribute vec3 position;
+attribute vec2 texcoord;
+attribute vec4 color0;
+
+varying lowp vec2 uv;
+varying lowp vec4 color;
+
+uniform mat4 Model;
+uniform mat4 Projection;
+
+void main() {
+    gl_Position = Projection * Model * vec4(position, 1);
+    color = color0 / 255.0;
+    uv = texcoord;
+}
+"#;
+
+/// Mixes the texture towards white by `Flash` and draws an outline, in `OutlineColor`, on any
+/// transparent pixel that borders an opaque one
+const FRAGMENT_SHADER: &str = r#"#version 100
+precision lowp float;
+
+varying vec4 color;
+varying vec2 uv;
+
+uniform sampler2D Texture;
+uniform float Flash;
+uniform vec4 OutlineColor;
+uniform vec2 TexelSize;
+
+void main() {
+    vec4 texel = texture2D(Texture, uv) * color;
+
+    if (texel.a < 0.5) {
+        float neighbors = texture2D(Texture, uv + vec2(TexelSize.x, 0.0)).a
+            + texture2D(Texture, uv - vec2(TexelSize.x, 0.0)).a
+            + texture2D(Texture, uv + vec2(0.0, TexelSize.y)).a
+            + texture2D(Texture, uv - vec2(0.0, TexelSize.y)).a;
+
+        if (neighbors > 0.5) {
+            gl_FragColor = OutlineColor;
+            return;
+        }
+    }
+
+    gl_FragColor = vec4(mix(texel.rgb, vec3(1.0), Flash), texel.a);
+}
+"#;
+
+pub struct HitFlash {
+    material: Material,
+}
+
+impl HitFlash {
+    pub const LISTENER_ID: &'static str = "hit_flash";
+
+    /// The duration of the whole hit reaction
+    pub const DURATION: f32 = 0.2;
+    /// The part of the duration where the player is flashing white. The outline remains for the
+    /// whole duration.
+    const FLASH_DURATION: f32 = 0.08;
+
+    const OUTLINE_COLOR: Color = Color {
+        r: 1.0,
+        g: 0.25,
+        b: 0.2,
+        a: 1.0,
+    };
+
+    pub fn new() -> Self {
+        let material = load_material(
+            VERTEX_SHADER,
+            FRAGMENT_SHADER,
+            MaterialParams {
+                uniforms: vec![
+                    ("Flash".to_string(), UniformType::Float1),
+                    ("OutlineColor".to_string(), UniformType::Float4),
+                    ("TexelSize".to_string(), UniformType::Float2),
+                ],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        HitFlash { material }
+    }
+
+    pub fn on_game_event(event: &GameEvent) {
+        if let GameEvent::PlayerDamaged { player_id, .. } = *event {
+            if storage::get::<Config>().reduce_flashing {
+                return;
+            }
+
+            for mut player in scene::find_nodes_by_type::<Player>() {
+                if player.id == player_id {
+                    player.hit_flash_timer = Self::DURATION;
+                }
+            }
+        }
+    }
+
+    /// This sets the shared hit flash material for anything drawn with `texture`, until `end` is
+    /// called. `timer` is the remaining time of the hit reaction, counting down from `DURATION`.
+    pub fn begin(texture: Texture2D, timer: f32) {
+        let hit_flash = storage::get::<HitFlash>();
+
+        let elapsed = Self::DURATION - timer;
+        let flash = (1.0 - elapsed / Self::FLASH_DURATION).clamp(0.0, 1.0);
+        let outline_alpha = (timer / Self::DURATION).clamp(0.0, 1.0);
+
+        let outline_color = Color {
+            a: outline_alpha,
+            ..Self::OUTLINE_COLOR
+        };
+
+        hit_flash.material.set_uniform("Flash", flash);
+        hit_flash.material.set_uniform(
+            "OutlineColor",
+            (
+                outline_color.r,
+                outline_color.g,
+                outline_color.b,
+                outline_color.a,
+            ),
+        );
+        hit_flash
+            .material
+            .set_uniform("TexelSize", (1.0 / texture.width(), 1.0 / texture.height()));
+
+        gl_use_material(hit_flash.material);
+    }
+
+    pub fn end() {
+        gl_use_default_material();
+    }
+}
+
+impl Default for HitFlash {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,10 +1,9 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, convert::TryInto};
 
 use macroquad::{
-    audio::{self, play_sound_once},
     experimental::{
         collections::storage,
-        coroutines::{start_coroutine, wait_seconds, Coroutine},
+        coroutines::{start_coroutine, Coroutine},
         scene::{self, Handle, HandleUntyped, RefMut},
         state_machine::{State, StateMachine},
     },
@@ -12,33 +11,67 @@ use macroquad::{
 };
 
 use crate::{
+    audio::{play_sound_at, play_sound_at_ex},
     capabilities::{NetworkReplicate, PhysicsObject},
     components::PhysicsBody,
-    items::{Item, ItemKind, Weapon},
-    GameInput, GameWorld, ParticleEmitters, PassiveEffectInstance, PassiveEffectParams, Resources,
+    cvars::{self, CVarFlags, CVarValue},
+    effects::{
+        instantiate_active_effect, passive::coroutines::get_passive_effect_coroutine,
+        ActiveEffectKind, GrapplingHook,
+    },
+    game::{EntityTimeScale, GameEvent, SIMULATION_DELTA_TIME},
+    items::{Item, ItemKind, Weapon, WeaponSlot},
+    mutators::get_mutator_rules,
+    physics::{CollisionLayers, EntityIndex},
+    profiler::ProfileScope,
+    scripting::{self, AbilityCallback},
+    symbol::Symbol,
+    Config, GameInput, GameWorld, ParticleEmitters, PassiveEffectInstance, PassiveEffectParams,
+    Resources,
 };
 
-use crate::components::AnimationPlayer;
+use crate::components::{AnimationPlayer, Trail};
 
-use crate::items::EquippedItem;
+use crate::items::{EquipmentAbility, EquipmentSlot, EquippedItem, PlayerStatModifiers};
 
 mod ai;
 mod character;
+mod emotes;
 mod events;
+mod hit_flash;
 
 pub use character::PlayerCharacterParams;
+pub use emotes::PlayerEmotes;
 pub use events::{PlayerEvent, PlayerEventParams};
+pub use hit_flash::HitFlash;
 
 pub struct Player {
     pub id: u8,
+    /// The id of the player character params that the player was created from
+    pub character_id: String,
 
     pub body: PhysicsBody,
     animation_player: AnimationPlayer,
 
     pub is_dead: bool,
+    /// The time that has passed since the player died, while the player is dead
+    death_timer: f32,
+    /// This is `true` if the player died inside the map, in stead of by falling off of it
+    is_dead_inside_map: bool,
+
+    pub health: f32,
+    pub max_health: f32,
 
     pub weapon: Option<Weapon>,
+    /// A one-handed weapon held alongside a one-handed weapon in the main hand
+    pub off_hand_weapon: Option<Weapon>,
+    /// The slot of the weapon used by the current attack or charge
+    pub attack_slot: WeaponSlot,
+    /// The time that has passed since the current attack started, while the player is attacking
+    attack_timer: Option<f32>,
     pub equipped_items: HashMap<String, EquippedItem>,
+    /// This holds the rope of a grappling hook, while one is attached
+    pub grappling_hook: Option<GrapplingHook>,
 
     pub passive_effects: HashMap<String, PassiveEffectInstance>,
 
@@ -46,7 +79,11 @@ pub struct Player {
     pub last_frame_input: GameInput,
     pub pick_grace_timer: f32,
 
+    /// The time the player can still jump, after walking off a ledge (coyote time)
     jump_grace_timer: f32,
+    /// The time a jump pressed in the air is remembered for, so that the player will jump when
+    /// landing within it
+    jump_buffer_timer: f32,
     jump_frames_left: i32,
 
     was_floating: bool,
@@ -69,11 +106,22 @@ pub struct Player {
     pub legs_threshold: f32,
 
     pub weapon_mount: Vec2,
+    /// The ids of the weapon skins preferred by the player's character
+    pub weapon_skins: Vec<String>,
     pub jump_force: f32,
     pub move_speed: f32,
     pub slide_speed_factor: f32,
     pub slide_duration: f32,
+    /// The time that has passed since the current slide started, while the player is sliding
+    slide_timer: f32,
     pub float_gravity_factor: f32,
+    pub coyote_time: f32,
+    pub jump_buffer_time: f32,
+
+    /// The stats of the player character, before any modifiers from equipped items are applied
+    base_jump_force: f32,
+    base_move_speed: f32,
+    base_float_gravity_factor: f32,
 
     pub back_armor: i32,
     pub can_head_boink: bool,
@@ -81,7 +129,42 @@ pub struct Player {
     pub last_collisions: Vec<u8>,
     pub current_collisions: Vec<u8>,
 
-    is_hit_from_right: bool,
+    /// The amount of simulation frames the player will remain frozen, as a result of a heavy hit
+    pub hitstop_frames: u32,
+    /// The time scale of the player, which will make the player skip ticks, when below `1.0`
+    pub time_scale: EntityTimeScale,
+
+    /// The trail drawn behind the player while sliding, if the character has one
+    slide_trail: Option<Trail>,
+
+    /// The remaining time of the hit reaction, after the player has received damage
+    hit_flash_timer: f32,
+
+    /// The time that has passed since the player last gave any input
+    pub idle_timer: f32,
+    /// This is set when the player has not given any input for longer than the AFK timeout,
+    /// specified in `Config`. A player that is AFK will not receive damage. Players that are
+    /// controlled by the AI are never AFK.
+    pub is_afk: bool,
+}
+
+/// This calls `f` with the player with the id `id`, if there is one, and returns its result, or
+/// `default` if there is no such player. This is used by the APIs of scripts and mutators, that
+/// identify players by their id.
+pub fn with_player<I, T, F>(id: I, default: T, f: F) -> T
+where
+    I: TryInto<u8>,
+    F: FnOnce(&mut Player) -> T,
+{
+    let id = match id.try_into() {
+        Ok(id) => id,
+        Err(_) => return default,
+    };
+
+    scene::find_nodes_by_type::<Player>()
+        .find(|player| player.id == id)
+        .map(|mut player| f(&mut *player))
+        .unwrap_or(default)
 }
 
 impl Player {
@@ -91,19 +174,64 @@ impl Player {
     pub const ST_SLIDE: usize = 3;
     pub const ST_INCAPACITATED: usize = 4;
     pub const ST_AFTERMATCH: usize = 5;
+    pub const ST_CHARGE: usize = 6;
 
     pub const JUMP_HEIGHT_CONTROL_FRAMES: i32 = 8;
     pub const JUMP_RELEASE_GRAVITY_INCREASE: f32 = 35.0;
 
-    pub const JUMP_GRACE_TIME: f32 = 0.15;
+    /// The interval at which jetpack particles are spawned, while thrusting
+    pub const JETPACK_PARTICLE_INTERVAL: f32 = 0.05;
     pub const PICK_GRACE_TIME: f32 = 0.30;
 
+    /// Damage that will kill a player, regardless of remaining health
+    pub const LETHAL_DAMAGE: f32 = f32::MAX;
+
     pub const INCAPACITATED_BREAK_FACTOR: f32 = 0.9;
     pub const INCAPACITATED_STOP_THRESHOLD: f32 = 20.0;
 
-    const ITEM_THROW_FORCE: f32 = 600.0;
+    /// The time a player that died inside the map is given to take off from the ground
+    const DEATH_TAKE_OFF_DURATION: f32 = 0.1;
+    /// The time a player that died inside the map lies still, after taking off
+    const DEATH_LIE_DURATION: f32 = 0.5;
+    const RESPAWN_DELAY: f32 = 0.5;
+
+    const ITEM_THROW_FORCE_CVAR: &'static str = "player.item_throw_force";
+    const DEFAULT_ITEM_THROW_FORCE: f32 = 600.0;
 
     const WEAPON_HUD_Y_OFFSET: f32 = -16.0;
+    const OFF_HAND_WEAPON_HUD_Y_OFFSET: f32 = -32.0;
+
+    /// The offset of the off-hand weapon mount, relative to the weapon mount, when facing right
+    const OFF_HAND_MOUNT_OFFSET_X: f32 = -12.0;
+    const OFF_HAND_MOUNT_OFFSET_Y: f32 = 4.0;
+
+    /// The colors used to tell players apart, in the HUD, by player id
+    const COLORS: [Color; 4] = [
+        Color {
+            r: 0.9,
+            g: 0.3,
+            b: 0.25,
+            a: 1.0,
+        },
+        Color {
+            r: 0.25,
+            g: 0.55,
+            b: 0.95,
+            a: 1.0,
+        },
+        Color {
+            r: 0.35,
+            g: 0.8,
+            b: 0.3,
+            a: 1.0,
+        },
+        Color {
+            r: 0.95,
+            g: 0.8,
+            b: 0.2,
+            a: 1.0,
+        },
+    ];
 
     pub const IDLE_ANIMATION_ID: &'static str = "idle";
     pub const MOVE_ANIMATION_ID: &'static str = "move";
@@ -113,6 +241,19 @@ impl Player {
     pub const DEATH_BACK_ANIMATION_ID: &'static str = "death_back";
     pub const DEATH_FACE_ANIMATION_ID: &'static str = "death_face";
 
+    /// The id of the sound effect played when landing. Variants for tile surfaces, like
+    /// `land_metal`, will be used on those surfaces, if they exist.
+    pub const LAND_SOUND_EFFECT_ID: &'static str = "land";
+
+    pub fn register_cvars() {
+        cvars::register(
+            Self::ITEM_THROW_FORCE_CVAR,
+            CVarValue::Float(Self::DEFAULT_ITEM_THROW_FORCE),
+            CVarFlags::CHEAT | CVarFlags::REPLICATED,
+            "The speed that thrown items are given, on top of the velocity of the thrower",
+        );
+    }
+
     pub fn new(player_id: u8, params: PlayerCharacterParams) -> Player {
         let spawn_point = {
             let world = storage::get_mut::<GameWorld>();
@@ -122,17 +263,11 @@ impl Player {
         let mut state_machine = StateMachine::new();
         state_machine.add_state(Self::ST_NORMAL, State::new().update(Self::update_normal));
 
-        state_machine.add_state(
-            Self::ST_DEATH,
-            State::new().coroutine(Self::death_coroutine),
-        );
+        state_machine.add_state(Self::ST_DEATH, State::new().update(Self::update_death));
 
-        state_machine.add_state(
-            Self::ST_ATTACK,
-            State::new()
-                .update(Self::update_attack)
-                .coroutine(Self::attack_coroutine),
-        );
+        state_machine.add_state(Self::ST_ATTACK, State::new().update(Self::update_attack));
+
+        state_machine.add_state(Self::ST_CHARGE, State::new().update(Self::update_charge));
 
         state_machine.add_state(
             Self::ST_INCAPACITATED,
@@ -146,12 +281,7 @@ impl Player {
             State::new().update(Self::update_aftermatch),
         );
 
-        state_machine.add_state(
-            Self::ST_SLIDE,
-            State::new()
-                .update(Self::update_slide)
-                .coroutine(Self::slide_coroutine),
-        );
+        state_machine.add_state(Self::ST_SLIDE, State::new().update(Self::update_slide));
 
         let body = {
             let mut world = storage::get_mut::<GameWorld>();
@@ -159,7 +289,7 @@ impl Player {
             let size = vec2(params.collider_size.x, params.collider_size.y);
             let collider_offset = vec2(-params.collider_size.x / 2.0, 0.0);
 
-            PhysicsBody::new(
+            let mut body = PhysicsBody::new(
                 &mut world.collision_world,
                 spawn_point,
                 0.0,
@@ -167,17 +297,28 @@ impl Player {
                 false,
                 false,
                 collider_offset,
-            )
-        };
+            );
 
-        let is_hit_from_right = body.is_facing_right;
+            body.mass = params.mass;
+            body.collision_layer = CollisionLayers::PLAYERS;
+
+            body
+        };
 
         let animation_player = AnimationPlayer::new(params.animation.into());
 
         Player {
             id: player_id,
+            character_id: params.id,
             is_dead: false,
+            death_timer: 0.0,
+            is_dead_inside_map: false,
+            health: params.max_health,
+            max_health: params.max_health,
             weapon: None,
+            off_hand_weapon: None,
+            attack_slot: WeaponSlot::MainHand,
+            attack_timer: None,
             equipped_items: HashMap::new(),
             passive_effects: HashMap::new(),
             input: Default::default(),
@@ -186,6 +327,7 @@ impl Player {
             body,
             animation_player,
             jump_grace_timer: 0.,
+            jump_buffer_timer: 0.,
             jump_frames_left: 0,
             floating: false,
             was_floating: false,
@@ -201,19 +343,43 @@ impl Player {
             head_threshold: params.head_threshold,
             legs_threshold: params.legs_threshold,
             weapon_mount: params.weapon_mount,
+            weapon_skins: params.weapon_skins,
             jump_force: params.jump_force,
             move_speed: params.move_speed,
             slide_speed_factor: params.slide_speed_factor,
             slide_duration: params.slide_duration,
+            slide_timer: 0.0,
             float_gravity_factor: params.float_gravity_factor,
+            coyote_time: params.coyote_time,
+            jump_buffer_time: params.jump_buffer_time,
+            base_jump_force: params.jump_force,
+            base_move_speed: params.move_speed,
+            base_float_gravity_factor: params.float_gravity_factor,
             incapacitation_duration: 0.0,
             last_collisions: Vec::new(),
             current_collisions: Vec::new(),
-            is_hit_from_right,
+            hitstop_frames: 0,
+            time_scale: EntityTimeScale::new(),
+            slide_trail: params.slide_trail.map(Trail::new),
+            hit_flash_timer: 0.0,
+            grappling_hook: None,
+            idle_timer: 0.0,
+            is_afk: false,
         }
     }
 
+    pub fn get_color(&self) -> Color {
+        Self::get_color_by_id(self.id)
+    }
+
+    /// Returns the color of the player with `player_id`, for when the player node is not at hand
+    pub fn get_color_by_id(player_id: u8) -> Color {
+        Self::COLORS[player_id as usize % Self::COLORS.len()]
+    }
+
     pub fn set_animation(&mut self, id: &str) {
+        let id = Symbol::of(id);
+
         self.animation_player.set_animation(id);
         for item in self.equipped_items.values_mut() {
             if let Some(animation_player) = &mut item.sprite_animation {
@@ -235,8 +401,73 @@ impl Player {
         self.passive_effects.insert(effect.id.clone(), effect);
     }
 
-    pub fn drop_weapon(&mut self, is_thrown: bool) {
-        if let Some(weapon) = self.weapon.take() {
+    pub fn get_weapon(&self, slot: WeaponSlot) -> Option<&Weapon> {
+        match slot {
+            WeaponSlot::MainHand => self.weapon.as_ref(),
+            WeaponSlot::OffHand => self.off_hand_weapon.as_ref(),
+        }
+    }
+
+    pub fn get_weapon_mut(&mut self, slot: WeaponSlot) -> Option<&mut Weapon> {
+        self.get_weapon_slot_mut(slot).as_mut()
+    }
+
+    pub fn get_weapon_slot_mut(&mut self, slot: WeaponSlot) -> &mut Option<Weapon> {
+        match slot {
+            WeaponSlot::MainHand => &mut self.weapon,
+            WeaponSlot::OffHand => &mut self.off_hand_weapon,
+        }
+    }
+
+    /// Returns the direction that the player is aiming in, if free aim is enabled in the ruleset
+    /// and the player is aiming
+    pub fn get_aim_direction(&self) -> Option<Vec2> {
+        if self.input.aim != Vec2::ZERO && storage::get::<Resources>().ruleset.free_aim {
+            Some(self.input.aim)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the rotation of the sprite of a ranged weapon that is aimed, relative to the
+    /// direction that the player is facing. Aim that points behind the player is mirrored, as
+    /// the player only turns around when firing.
+    fn get_aim_rotation(&self) -> f32 {
+        match self.get_aim_direction() {
+            Some(aim) => {
+                let angle = aim.y.atan2(aim.x.abs());
+
+                if self.body.is_facing_right {
+                    angle
+                } else {
+                    -angle
+                }
+            }
+            None => 0.0,
+        }
+    }
+
+    /// Returns `true` if the fire input of `slot` is held down
+    fn is_firing(&self, slot: WeaponSlot) -> bool {
+        match slot {
+            WeaponSlot::MainHand => self.input.fire,
+            WeaponSlot::OffHand => self.input.fire_off_hand,
+        }
+    }
+
+    pub fn drop_weapon(&mut self, slot: WeaponSlot, is_thrown: bool) {
+        if let Some(weapon) = self.get_weapon_slot_mut(slot).take() {
+            // An attached grappling hook can only be released by firing it again, so it is
+            // released when the weapon that fired it is dropped
+            let has_grappling_hook = weapon
+                .effects
+                .iter()
+                .any(|params| matches!(*params.kind, ActiveEffectKind::GrapplingHook { .. }));
+
+            if has_grappling_hook {
+                self.grappling_hook = None;
+            }
+
             let params = {
                 let resources = storage::get::<Resources>();
                 resources
@@ -249,46 +480,155 @@ impl Player {
             let mut item = Item::new(self.body.position, params);
 
             if is_thrown {
-                item.body.velocity = self.body.facing_dir() * Self::ITEM_THROW_FORCE;
+                item.body.velocity = self.body.facing_dir()
+                    * cvars::get_float(Self::ITEM_THROW_FORCE_CVAR)
+                    + self.body.velocity;
+                item.thrown_by = Some(self.id);
             }
 
+            item.skin_id = weapon.skin_id;
+
             scene::add_node(item);
         }
     }
 
-    pub fn pick_up_weapon(&mut self, weapon: Weapon) {
-        let resources = storage::get::<Resources>();
-        let sound = resources.sounds["pickup"];
+    /// This will destroy the weapon held in `slot`, instantiating its break effects, if any
+    pub fn break_weapon(&mut self, handle: Handle<Player>, slot: WeaponSlot) {
+        if let Some(weapon) = self.get_weapon_slot_mut(slot).take() {
+            if let Some(params) = weapon.durability_params {
+                let position = self.get_weapon_mount_position(slot);
 
-        play_sound_once(sound);
+                for effect in params.break_effects {
+                    instantiate_active_effect(handle, position, effect);
+                }
 
-        self.drop_weapon(false);
+                if let Some(particle_effect_id) = &params.break_particle_effect_id {
+                    let mut particles = scene::find_node_by_type::<ParticleEmitters>().unwrap();
+                    particles.spawn(particle_effect_id, position);
+                }
 
-        self.weapon = Some(weapon);
+                if let Some(sound_effect_id) = &params.break_sound_effect_id {
+                    let resources = storage::get::<Resources>();
+                    play_sound_at(&resources.sounds[sound_effect_id], position);
+                }
+            }
+        }
+    }
+
+    pub fn pick_up_weapon(&mut self, weapon: Weapon) {
+        GameEvent::ItemPickedUp {
+            player_id: self.id,
+            item_id: weapon.id.clone(),
+            position: self.body.position,
+        }
+        .dispatch();
+
+        let is_dual_wielding = weapon.is_one_handed
+            && self.off_hand_weapon.is_none()
+            && self
+                .weapon
+                .as_ref()
+                .map(|weapon| weapon.is_one_handed)
+                .unwrap_or(false);
+
+        if is_dual_wielding {
+            self.off_hand_weapon = Some(weapon);
+        } else {
+            self.drop_weapon(WeaponSlot::MainHand, false);
+
+            if !weapon.is_one_handed {
+                self.drop_weapon(WeaponSlot::OffHand, false);
+            }
+
+            self.weapon = Some(weapon);
+        }
     }
 
     pub fn pick_up_equipped_item(&mut self, equipped_item: EquippedItem) {
-        let resources = storage::get::<Resources>();
-        let sound = resources.sounds["pickup"];
-        play_sound_once(sound);
+        GameEvent::ItemPickedUp {
+            player_id: self.id,
+            item_id: equipped_item.id.clone(),
+            position: self.body.position,
+        }
+        .dispatch();
+
+        if let Some(slot) = equipped_item.slot {
+            let previous_id = self
+                .get_equipped_item_in_slot(slot)
+                .filter(|item| item.id != equipped_item.id)
+                .map(|item| item.id.clone());
+
+            if let Some(previous_id) = previous_id {
+                self.drop_equipped_item(&previous_id);
+            }
+        }
 
         self.equipped_items
             .insert(equipped_item.id.clone(), equipped_item);
+
+        self.update_stats();
+    }
+
+    /// Returns the item equipped in `slot`, if any
+    pub fn get_equipped_item_in_slot(&self, slot: EquipmentSlot) -> Option<&EquippedItem> {
+        self.equipped_items
+            .values()
+            .find(|item| item.slot == Some(slot))
+    }
+
+    /// This will unequip the item with the specified id, removing any passive effects that were
+    /// added by it, and spawn it in the world, at the player's position.
+    pub fn drop_equipped_item(&mut self, id: &str) {
+        if let Some(item) = self.equipped_items.remove(id) {
+            self.passive_effects
+                .retain(|_, effect| effect.item_id.as_deref() != Some(id));
+
+            let params = {
+                let resources = storage::get::<Resources>();
+                resources.items.get(&item.id).cloned()
+            };
+
+            if let Some(params) = params {
+                scene::add_node(Item::new(self.body.position, params));
+            }
+
+            self.update_stats();
+        }
     }
 
-    pub fn get_weapon_mount_position(&self) -> Vec2 {
+    /// This will apply the stat modifiers of all equipped items to the base stats of the player
+    pub fn update_stats(&mut self) {
+        let modifiers = self
+            .equipped_items
+            .values()
+            .fold(PlayerStatModifiers::default(), |res, item| {
+                res.combine(&item.stat_modifiers)
+            });
+
+        self.move_speed = self.base_move_speed * modifiers.move_speed_factor;
+        self.jump_force = self.base_jump_force * modifiers.jump_force_factor;
+        self.float_gravity_factor = self.base_float_gravity_factor * modifiers.float_gravity_factor;
+    }
+
+    pub fn get_weapon_mount_position(&self, slot: WeaponSlot) -> Vec2 {
+        let mut weapon_mount = self.weapon_mount;
+
+        if slot == WeaponSlot::OffHand {
+            weapon_mount += vec2(Self::OFF_HAND_MOUNT_OFFSET_X, Self::OFF_HAND_MOUNT_OFFSET_Y);
+        }
+
         let mut offset = Vec2::ZERO;
 
         if self.body.is_facing_right {
-            offset.x = self.weapon_mount.x;
+            offset.x = weapon_mount.x;
         } else {
-            offset.x = -self.weapon_mount.x;
+            offset.x = -weapon_mount.x;
         }
 
         if self.body.is_upside_down {
-            offset.y = -self.weapon_mount.y;
+            offset.y = -weapon_mount.y;
         } else {
-            offset.y = self.weapon_mount.y;
+            offset.y = weapon_mount.y;
         }
 
         let size = self.animation_player.get_size();
@@ -306,31 +646,62 @@ impl Player {
 
     pub fn jump(&mut self) {
         let resources = storage::get::<Resources>();
-        let jump_sound = resources.sounds["jump"];
+        let jump_sound = &resources.sounds["jump"];
 
-        self.body.velocity.y = -self.jump_force;
-        self.jump_frames_left = Self::JUMP_HEIGHT_CONTROL_FRAMES;
+        // Jumping off a sticky surface is weaker, and can not be extended by holding jump
+        let stickiness = self.body.surface.stickiness;
+        self.body.velocity.y = -self.jump_force * (1.0 - stickiness);
+        self.jump_frames_left = if stickiness > 0.0 {
+            0
+        } else {
+            Self::JUMP_HEIGHT_CONTROL_FRAMES
+        };
 
-        audio::play_sound(
-            jump_sound,
-            audio::PlaySoundParams {
-                looped: false,
-                volume: 0.6,
-            },
-        );
+        play_sound_at_ex(jump_sound, self.body.position, 0.6);
+
+        scripting::run_ability_callback(&self.character_id, self.id, AbilityCallback::Jump);
     }
 
     fn slide(&mut self) {
+        self.body.velocity.x = if self.body.is_facing_right {
+            self.move_speed * self.slide_speed_factor
+        } else {
+            -self.move_speed * self.slide_speed_factor
+        };
+
+        self.set_animation(Self::CROUCH_ANIMATION_ID);
+
+        self.slide_timer = 0.0;
         self.state_machine.set_state(Self::ST_SLIDE);
+
+        scripting::run_ability_callback(&self.character_id, self.id, AbilityCallback::Slide);
     }
 
     // This should only be used under special circumstances, when you want to override a damage
-    // blocking effect, for example. To give damage to a player, use `Player::on_receive_damage`
-    pub fn kill(&mut self, is_from_right: bool) {
-        if self.state_machine.state() != Self::ST_DEATH {
-            self.is_hit_from_right = is_from_right;
+    // blocking effect, for example. To give damage to a player, use `Player::on_receive_damage`.
+    // The kill is credited to `killed_by` and to the weapon `weapon_id`, if they are specified.
+    pub fn kill(
+        &mut self,
+        is_from_right: bool,
+        killed_by: Option<Handle<Player>>,
+        weapon_id: Option<String>,
+    ) {
+        if !self.is_dead {
+            self.is_dead = true;
+
+            GameEvent::PlayerKilled {
+                player_id: self.id,
+                position: self.body.position,
+                killed_by: killed_by
+                    .and_then(scene::try_get_node)
+                    .map(|player| player.id)
+                    .filter(|id| *id != self.id),
+                weapon_id,
+            }
+            .dispatch();
 
-            self.drop_weapon(false);
+            self.drop_weapon(WeaponSlot::MainHand, false);
+            self.drop_weapon(WeaponSlot::OffHand, false);
 
             {
                 let position = self.body.position;
@@ -345,21 +716,62 @@ impl Player {
 
             self.passive_effects.clear();
 
+            self.grappling_hook = None;
+
+            let map_bottom = {
+                let world = storage::get::<GameWorld>();
+                world.map.grid_size.y as f32 * world.map.tile_size.y
+            };
+
+            // A player that died inside the map takes off from the ground, before it lands, while
+            // one that fell off the map is stopped immediately
+            self.is_dead_inside_map = self.body.position.y < map_bottom;
+
+            self.body.velocity = if self.is_dead_inside_map {
+                vec2(if is_from_right { -300.0 } else { 300.0 }, -150.0)
+            } else {
+                vec2(0.0, 0.0)
+            };
+
+            self.body.has_gravity = true;
+
+            if self.body.is_facing_right && is_from_right {
+                self.set_animation(Self::DEATH_BACK_ANIMATION_ID);
+            } else {
+                self.set_animation(Self::DEATH_FACE_ANIMATION_ID);
+            }
+
+            self.animation_player.restart();
+
+            self.death_timer = 0.0;
             self.state_machine.set_state(Self::ST_DEATH);
 
             {
                 let resources = storage::get::<Resources>();
-                let sound = resources.sounds["death"];
-                play_sound_once(sound);
+                let sound = &resources.sounds["death"];
+                play_sound_at(sound, self.body.position);
             }
+
+            scripting::run_ability_callback(&self.character_id, self.id, AbilityCallback::Death);
         }
     }
 
+    /// This will freeze the player for the specified amount of simulation frames. If the player is
+    /// already frozen, the longest of the two durations will be used.
+    pub fn apply_hitstop(&mut self, frames: u32) {
+        self.hitstop_frames = self.hitstop_frames.max(frames);
+    }
+
     pub fn apply_input(&mut self, input: GameInput) {
         self.last_frame_input = self.input;
         self.input = input;
     }
 
+    /// This lets the AI control the player, in stead of the input that is applied to it
+    pub fn set_ai_enabled(&mut self, is_enabled: bool) {
+        self.ai_enabled = is_enabled;
+    }
+
     #[allow(dead_code)]
     pub fn incapacitate(&mut self, duration: f32, should_stop: bool, should_fall: bool) {
         if should_stop {
@@ -394,173 +806,235 @@ impl Player {
         start_coroutine(coroutine)
     }
 
-    fn death_coroutine(node: &mut RefMut<Player>) -> Coroutine {
-        let handle = node.handle();
+    /// This moves a dead player to a spawn point and brings it back to life, when the respawn
+    /// delay has passed, counted in ticks. A player that died inside the map is given some time to
+    /// take off from the ground, before it is stopped, and then some time to lie still.
+    fn update_death(node: &mut RefMut<Player>, _dt: f32) {
+        let last_timer = node.death_timer;
+        let death_timer = last_timer + SIMULATION_DELTA_TIME;
+        node.death_timer = death_timer;
 
-        let map_bottom = {
-            let world = storage::get::<GameWorld>();
-
-            world.map.grid_size.y as f32 * world.map.tile_size.y
-        } as f32;
+        let respawn_delay = if node.is_dead_inside_map {
+            let has_passed = |time: f32| last_timer < time && death_timer >= time;
 
-        let coroutine = async move {
+            if has_passed(Self::DEATH_TAKE_OFF_DURATION)
+                || has_passed(Self::DEATH_TAKE_OFF_DURATION + Self::DEATH_LIE_DURATION)
             {
-                let mut node = scene::get_node(handle);
-                node.body.velocity.x = 300.;
+                node.body.velocity = vec2(0.0, 0.0);
+            }
 
-                if node.is_hit_from_right {
-                    node.body.velocity.x *= -1.;
-                }
+            Self::DEATH_TAKE_OFF_DURATION + Self::DEATH_LIE_DURATION + Self::RESPAWN_DELAY
+        } else {
+            Self::RESPAWN_DELAY
+        };
 
-                node.body.velocity.y = -150.;
-                node.body.has_gravity = true;
+        if death_timer < respawn_delay {
+            return;
+        }
 
-                node.is_dead = true;
+        if node.can_head_boink {
+            // Shoes::spawn(this.body.pos);
+            node.can_head_boink = false;
+        }
 
-                if node.body.is_facing_right && node.is_hit_from_right {
-                    node.set_animation(Self::DEATH_BACK_ANIMATION_ID);
-                } else {
-                    node.set_animation(Self::DEATH_FACE_ANIMATION_ID);
-                }
+        let spawn_point = {
+            let world = storage::get::<GameWorld>();
+            world.get_random_spawn_point()
+        };
 
-                node.animation_player.restart();
+        node.body.set_position(spawn_point);
 
-                // let mut score_counter = scene::get_node(node.score_counter);
-                // score_counter.count_loss(node.controller_id)
-            }
-
-            let is_out_of_bounds = {
-                let node = scene::get_node(handle);
-                node.body.position.y < map_bottom
-            };
+        // in deathmatch we can just get back to normal after death
+        node.state_machine.set_state(Self::ST_NORMAL);
+        node.is_dead = false;
+        node.health = node.max_health;
+    }
 
-            if is_out_of_bounds {
-                // give some take for a dead fish to take off the ground
-                wait_seconds(0.1).await;
+    fn update_incapacitated(node: &mut RefMut<Player>, _dt: f32) {
+        node.incapacitation_timer += SIMULATION_DELTA_TIME;
+        if node.incapacitation_timer >= node.incapacitation_duration {
+            node.incapacitation_timer = 0.0;
+            node.incapacitation_duration = 0.0;
+            node.state_machine.set_state(Player::ST_NORMAL);
+        }
+    }
 
-                // wait until it lands (or fall down the map)
-                let mut should_continue = false;
-                while should_continue {
-                    next_frame().await;
+    /// This starts an attack with the weapon in `attack_slot`, when the state is entered, and
+    /// ends it when the attack duration of the weapon has passed, counted in ticks
+    fn update_attack(node: &mut RefMut<Player>, _dt: f32) {
+        node.body.velocity.x *= 0.9;
 
-                    should_continue = {
-                        let node = scene::get_node(handle);
-                        !(node.body.is_on_ground || node.body.position.y > map_bottom)
-                    };
-                }
+        let slot = node.attack_slot;
 
-                {
-                    let mut node = scene::get_node(handle);
-                    //node.set_animation(Self::DEATH_ALT_ANIMATION_ID);
-                    node.body.velocity = vec2(0., 0.);
+        let attack_timer = match node.attack_timer {
+            Some(timer) => timer + SIMULATION_DELTA_TIME,
+            None => {
+                if !Weapon::begin_attack(node, slot) {
+                    node.state_machine.set_state(Self::ST_NORMAL);
+                    return;
                 }
 
-                wait_seconds(0.5).await;
-            }
-
-            {
-                let mut node = scene::get_node(handle);
-
-                //node.animation_player.stop();
-                node.body.velocity = vec2(0., 0.);
+                0.0
             }
+        };
 
-            wait_seconds(0.5).await;
+        let attack_duration = node
+            .get_weapon(slot)
+            .map(|weapon| weapon.attack_duration)
+            .unwrap_or(0.0);
 
-            let mut node = scene::get_node(handle);
+        if attack_timer >= attack_duration {
+            Weapon::finish_attack(node, slot);
 
-            if node.can_head_boink {
-                // Shoes::spawn(this.body.pos);
-                node.can_head_boink = false;
-            }
-
-            node.body.position = {
-                let world = storage::get_mut::<GameWorld>();
-                world.get_random_spawn_point()
-            };
+            node.attack_timer = None;
+            node.state_machine.set_state(Self::ST_NORMAL);
+        } else {
+            node.attack_timer = Some(attack_timer);
+        }
+    }
 
-            // node.animation_player.play();
+    fn update_charge(node: &mut RefMut<Player>, _dt: f32) {
+        node.body.velocity.x *= 0.9;
 
-            // in deathmatch we can just get back to normal after death
-            {
-                let mut world = storage::get_mut::<GameWorld>();
+        let slot = node.attack_slot;
+        let is_released = !node.is_firing(slot);
 
-                node.state_machine.set_state(Self::ST_NORMAL);
-                node.is_dead = false;
-                world
-                    .collision_world
-                    .set_actor_position(node.body.collider, node.body.position);
+        if let Some(weapon) = node.get_weapon_mut(slot) {
+            if weapon.is_cooked_off() {
+                node.state_machine.set_state(Self::ST_ATTACK);
+            } else if is_released {
+                if weapon.can_release_charge() {
+                    node.state_machine.set_state(Self::ST_ATTACK);
+                } else {
+                    weapon.cancel_charge();
+                    node.state_machine.set_state(Self::ST_NORMAL);
+                }
             }
-        };
-
-        start_coroutine(coroutine)
+        } else {
+            node.state_machine.set_state(Self::ST_NORMAL);
+        }
     }
 
-    fn attack_coroutine(node: &mut RefMut<Player>) -> Coroutine {
-        Weapon::attack_coroutine(node.handle())
+    fn update_aftermatch(node: &mut RefMut<Player>, _dt: f32) {
+        node.body.velocity.x = 0.0;
     }
 
-    fn update_incapacitated(node: &mut RefMut<Player>, dt: f32) {
-        node.incapacitation_timer += dt;
-        if node.incapacitation_timer >= node.incapacitation_duration {
-            node.incapacitation_timer = 0.0;
-            node.incapacitation_duration = 0.0;
-            node.state_machine.set_state(Player::ST_NORMAL);
+    fn update_slide(node: &mut RefMut<Player>, _dt: f32) {
+        node.slide_timer += SIMULATION_DELTA_TIME;
+        if node.slide_timer >= node.slide_duration {
+            node.state_machine.set_state(Self::ST_NORMAL);
         }
     }
 
-    fn update_attack(node: &mut RefMut<Player>, _dt: f32) {
-        node.body.velocity.x *= 0.9;
-    }
+    fn update_equipment_abilities(node: &mut RefMut<Player>, dt: f32) {
+        let is_on_ground = node.body.is_on_ground;
+        let is_jump_pressed = node.input.jump && !node.last_frame_input.jump;
+        // Abilities should not be used on the same jump press that made the player leave the ground
+        let can_use_abilities = !is_on_ground && node.jump_grace_timer <= 0.0;
 
-    fn update_aftermatch(node: &mut RefMut<Player>, _dt: f32) {
-        node.body.velocity.x = 0.0;
-    }
+        let mut thrust = 0.0;
+        let mut should_air_jump = false;
+        let mut particle_effects = Vec::new();
 
-    fn update_slide(_node: &mut RefMut<Player>, _dt: f32) {}
+        for item in node.equipped_items.values_mut() {
+            for ability in &mut item.abilities {
+                if is_on_ground {
+                    ability.replenish(dt);
+                    continue;
+                }
 
-    fn slide_coroutine(node: &mut RefMut<Player>) -> Coroutine {
-        let handle = node.handle();
+                match &ability.kind {
+                    EquipmentAbility::Jetpack {
+                        force,
+                        particle_effect_id,
+                        ..
+                    } => {
+                        if can_use_abilities && node.input.jump && ability.fuel > 0.0 {
+                            ability.fuel = (ability.fuel - dt).max(0.0);
+                            thrust += force;
+
+                            ability.particle_timer += dt;
+                            if ability.particle_timer >= Self::JETPACK_PARTICLE_INTERVAL {
+                                ability.particle_timer = 0.0;
+
+                                if let Some(particle_effect_id) = particle_effect_id {
+                                    particle_effects.push(particle_effect_id.clone());
+                                }
+                            }
+                        }
+                    }
+                    EquipmentAbility::AirJump { .. } => {
+                        if can_use_abilities
+                            && is_jump_pressed
+                            && !should_air_jump
+                            && ability.jumps_left > 0
+                        {
+                            ability.jumps_left -= 1;
+                            should_air_jump = true;
+                        }
+                    }
+                }
+            }
+        }
 
-        let coroutine = async move {
-            let slide_duration = {
-                let mut node = scene::get_node(handle);
-                node.body.velocity.x = if node.body.is_facing_right {
-                    node.move_speed * node.slide_speed_factor
-                } else {
-                    -node.move_speed * node.slide_speed_factor
-                };
+        if should_air_jump {
+            // The press was used by the air jump, so it should not also jump on landing
+            node.jump_buffer_timer = 0.0;
+            node.floating = false;
+            node.jump();
 
-                node.set_animation(Self::CROUCH_ANIMATION_ID);
+            scripting::run_ability_callback(&node.character_id, node.id, AbilityCallback::AirJump);
+        }
 
-                node.slide_duration
-            };
+        if thrust > 0.0 {
+            node.floating = false;
 
-            wait_seconds(slide_duration).await;
+            // Gravity is countered, so that `force` is the resulting upwards acceleration
+            node.body.velocity.y -= (thrust + PhysicsBody::get_gravity()) * dt;
 
-            {
-                let mut node = scene::get_node(handle);
-                node.state_machine.set_state(Self::ST_NORMAL);
-            }
-        };
+            let max_speed = node.jump_force;
+            node.body.velocity.y = node.body.velocity.y.clamp(-max_speed, max_speed);
 
-        start_coroutine(coroutine)
+            let mut particles = scene::find_node_by_type::<ParticleEmitters>().unwrap();
+            for particle_effect_id in particle_effects {
+                particles.spawn(&particle_effect_id, node.body.position);
+            }
+        }
     }
 
     fn update_normal(node: &mut RefMut<Player>, _dt: f32) {
+        // The state machine passes the render frame time, so this is not used
+        let dt = SIMULATION_DELTA_TIME;
+
         if node.remote_control {
             return;
         }
 
         #[cfg(debug_assertions)]
         if is_key_pressed(KeyCode::Y) {
-            Player::on_receive_damage(node.handle(), true, None);
+            Player::on_receive_damage(node.handle(), true, None, Self::LETHAL_DAMAGE);
         }
 
         //let node = &mut **node;
 
+        // On slippery surfaces, like ice, the player will accelerate and decelerate gradually
+        let traction = node.body.get_traction();
+
         if node.is_crouched {
-            node.body.velocity.x = 0.0;
+            node.body.velocity.x -= node.body.velocity.x * traction;
+
+            if node.input.right {
+                node.body.is_facing_right = true;
+            } else if node.input.left {
+                node.body.is_facing_right = false;
+            }
+        } else if node.grappling_hook.is_some() && !node.body.is_on_ground {
+            // While swinging from a grappling hook, horizontal input accelerates the swing, in
+            // stead of setting the velocity, so that momentum is kept
+            let node = &mut **node;
+            if let Some(grappling_hook) = &mut node.grappling_hook {
+                grappling_hook.update(&mut node.body, &node.input, dt);
+            }
 
             if node.input.right {
                 node.body.is_facing_right = true;
@@ -569,14 +1043,21 @@ impl Player {
             }
         } else {
             //
-            if node.input.right {
-                node.body.velocity.x = node.move_speed;
+            let target_velocity = if node.input.right {
                 node.body.is_facing_right = true;
+                node.move_speed
             } else if node.input.left {
-                node.body.velocity.x = -node.move_speed;
                 node.body.is_facing_right = false;
+                -node.move_speed
             } else {
-                node.body.velocity.x = 0.;
+                0.
+            };
+
+            node.body.velocity.x += (target_velocity - node.body.velocity.x) * traction;
+
+            let node = &mut **node;
+            if let Some(grappling_hook) = &mut node.grappling_hook {
+                grappling_hook.update(&mut node.body, &node.input, dt);
             }
         }
 
@@ -593,9 +1074,15 @@ impl Player {
         if node.body.is_on_ground && !node.body.was_on_ground_last_frame {
             {
                 let resources = storage::get::<Resources>();
-                let land_sound = resources.sounds["land"];
 
-                play_sound_once(land_sound);
+                if let Some(land_sound) = node
+                    .body
+                    .surface
+                    .sound
+                    .get_sound(&resources.sounds, Self::LAND_SOUND_EFFECT_ID)
+                {
+                    play_sound_at(land_sound, node.body.position);
+                }
             }
         }
 
@@ -642,6 +1129,16 @@ impl Player {
             node.body.velocity.y *= node.float_gravity_factor;
         }
 
+        let is_jump_pressed = node.input.jump && !node.last_frame_input.jump;
+
+        if is_jump_pressed && node.jump_grace_timer <= 0.0 {
+            node.jump_buffer_timer = node.jump_buffer_time;
+        } else if node.jump_buffer_timer > 0.0 {
+            node.jump_buffer_timer -= dt;
+        }
+
+        Self::update_equipment_abilities(node, dt);
+
         node.is_crouched = node.body.is_on_ground && node.input.down;
 
         if node.body.is_on_ground {
@@ -656,29 +1153,61 @@ impl Player {
             node.body.descent();
         }
 
-        if !node.input.down
-            && node.input.jump
-            && !node.last_frame_input.jump
-            && node.jump_grace_timer > 0.
+        let is_jump_buffered = node.body.is_on_ground && node.jump_buffer_timer > 0.0;
+
+        if !node.input.down && ((is_jump_pressed && node.jump_grace_timer > 0.) || is_jump_buffered)
         {
             node.jump_grace_timer = 0.0;
+            node.jump_buffer_timer = 0.0;
 
             node.jump();
         }
 
         if node.weapon.is_none() && node.pick_grace_timer > 0. {
-            node.pick_grace_timer -= get_frame_time();
+            node.pick_grace_timer -= dt;
         }
 
         if node.input.pickup && !node.last_frame_input.pickup {
-            if node.weapon.is_some() {
-                node.drop_weapon(true);
+            let can_pick_up_weapon = node.weapon.is_some() || node.pick_grace_timer <= 0.0;
+
+            let candidate = node
+                .get_pickup_candidates(|item| match &item.kind {
+                    ItemKind::Weapon { .. } => can_pick_up_weapon,
+                    // Equipment for a free slot is picked up automatically, so only equipment that
+                    // will be swapped with an equipped item is picked up on input
+                    ItemKind::EquippedItem { .. } => !node.can_auto_equip(item),
+                })
+                .into_iter()
+                .next();
+
+            if let Some(handle) = candidate {
+                let mut item = scene::get_node(handle);
+
+                match &item.kind {
+                    ItemKind::Weapon { params } => {
+                        let skin_id = item
+                            .skin_id
+                            .clone()
+                            .or_else(|| params.pick_skin(&node.weapon_skins));
+
+                        let weapon = Weapon::new(&item.id, params.clone(), skin_id.as_deref());
+                        node.pick_up_weapon(weapon);
+                    }
+                    ItemKind::EquippedItem { params } => {
+                        let equipment = EquippedItem::new(&item.id, params.clone(), node);
+                        node.pick_up_equipped_item(equipment);
+                    }
+                }
+
+                item.delete();
+            } else if node.weapon.is_some() {
+                node.drop_weapon(WeaponSlot::MainHand, true);
 
                 {
                     let resources = storage::get::<Resources>();
-                    let throw_sound = resources.sounds["throw"];
+                    let throw_sound = &resources.sounds["throw"];
 
-                    play_sound_once(throw_sound);
+                    play_sound_at(throw_sound, node.body.position);
                 }
 
                 // set a grace time for picking up the weapon again
@@ -690,44 +1219,117 @@ impl Player {
                 // floating it looks less cool than if its stop floating and
                 // falls, but idk
                 node.floating = false;
-            } else if node.pick_grace_timer <= 0.0 {
-                for item in scene::find_nodes_by_type::<Item>() {
-                    if let ItemKind::Weapon { params } = &item.kind {
-                        if node
-                            .get_collider_rect()
-                            .overlaps(&item.body.get_collider_rect())
-                        {
-                            let weapon = Weapon::new(&item.id, params.clone());
-                            node.pick_up_weapon(weapon);
-                            item.delete();
-                            break;
-                        }
-                    }
+            }
+        }
+
+        for slot in WeaponSlot::ALL {
+            // A charge may have been interrupted by the player being knocked out of the charge state
+            if let Some(weapon) = node.get_weapon_mut(slot) {
+                if weapon.is_charging() {
+                    weapon.cancel_charge();
                 }
             }
         }
 
-        if node.input.fire {
-            //
-            if node.weapon.is_some() {
-                node.state_machine.set_state(Self::ST_ATTACK);
+        for slot in WeaponSlot::ALL {
+            if !node.is_firing(slot) {
+                continue;
+            }
+
+            let next_state = match node.get_weapon_mut(slot) {
+                Some(weapon) if weapon.is_chargeable() => {
+                    if weapon.is_ready() {
+                        weapon.start_charge();
+                        Some(Self::ST_CHARGE)
+                    } else {
+                        None
+                    }
+                }
+                Some(_) => Some(Self::ST_ATTACK),
+                None => None,
+            };
+
+            if let Some(next_state) = next_state {
+                // With free aim, the player turns around to fire at anything behind them
+                if let Some(aim) = node.get_aim_direction() {
+                    if aim.x != 0.0 {
+                        node.body.is_facing_right = aim.x > 0.0;
+                    }
+                }
+
+                node.attack_slot = slot;
+                node.state_machine.set_state(next_state);
                 node.floating = false;
+
+                break;
             }
         }
 
-        for item in scene::find_nodes_by_type::<Item>() {
+        for handle in node.get_pickup_candidates(|item| node.can_auto_equip(item)) {
+            let mut item = scene::get_node(handle);
+
+            // A preferred item may have taken the slot of this one
+            if !node.can_auto_equip(&item) {
+                continue;
+            }
+
             if let ItemKind::EquippedItem { params } = &item.kind {
-                if node
-                    .get_collider_rect()
-                    .overlaps(&item.body.get_collider_rect())
-                {
-                    let equipment = EquippedItem::new(&item.id, params.clone(), node);
+                let equipment = EquippedItem::new(&item.id, params.clone(), node);
+                node.pick_up_equipped_item(equipment);
+            }
 
-                    node.pick_up_equipped_item(equipment);
-                    item.delete();
-                }
+            item.delete();
+        }
+    }
+
+    /// Returns `true` if `item` is equipment that will be equipped automatically, when the player
+    /// overlaps it. This is the case if the item does not occupy a slot or if its slot is free.
+    fn can_auto_equip(&self, item: &Item) -> bool {
+        if let ItemKind::EquippedItem { params } = &item.kind {
+            params
+                .slot
+                .and_then(|slot| self.get_equipped_item_in_slot(slot))
+                .map(|equipped| equipped.id == item.id)
+                .unwrap_or(true)
+        } else {
+            false
+        }
+    }
+
+    /// Returns the handles of the items, overlapping the player and accepted by `filter`, in the
+    /// order they should be picked up. Items with a higher pickup priority are preferred and, of
+    /// items with the same priority, the items closest to the player are preferred.
+    fn get_pickup_candidates<F: Fn(&Item) -> bool>(&self, filter: F) -> Vec<Handle<Item>> {
+        let collider = self.get_collider_rect();
+        let center = collider.point() + collider.size() / 2.0;
+
+        let mut candidates = Vec::new();
+
+        for item in EntityIndex::get_items(&collider)
+            .into_iter()
+            .filter_map(scene::try_get_node)
+        {
+            let item_collider = item.body.get_collider_rect();
+
+            if collider.overlaps(&item_collider) && filter(&item) {
+                let distance = center.distance(item_collider.point() + item_collider.size() / 2.0);
+
+                candidates.push((item.handle(), item.pickup_priority, distance));
             }
         }
+
+        candidates.sort_by(|(_, a_priority, a_distance), (_, b_priority, b_distance)| {
+            b_priority.cmp(a_priority).then(
+                a_distance
+                    .partial_cmp(b_distance)
+                    .unwrap_or(std::cmp::Ordering::Equal),
+            )
+        });
+
+        candidates
+            .into_iter()
+            .map(|(handle, _, _)| handle)
+            .collect()
     }
 
     pub fn get_collider_rect(&self) -> Rect {
@@ -744,6 +1346,15 @@ impl Player {
     }
 
     fn network_update(mut node: RefMut<Self>) {
+        if node.hitstop_frames > 0 {
+            node.hitstop_frames -= 1;
+            return;
+        }
+
+        if !node.time_scale.tick() {
+            return;
+        }
+
         // Break incapacitated
         if node.state_machine.state() == Player::ST_INCAPACITATED && node.body.velocity.x != 0.0 {
             if node.body.velocity.x > Player::INCAPACITATED_STOP_THRESHOLD
@@ -761,20 +1372,95 @@ impl Player {
             // }
         }
 
-        node.animation_player.update();
+        node.animation_player.update(SIMULATION_DELTA_TIME);
 
-        let dt = get_frame_time();
+        {
+            // Footsteps and other frame sounds use the variants for the surface under the player
+            let (position, surface) = (node.body.position, node.body.surface.sound);
+            node.animation_player
+                .play_frame_sounds_on(position, surface);
+        }
+
+        {
+            let collider = node.get_collider_rect();
+            let center = collider.point() + collider.size() / 2.0;
+            let is_sliding = node.state_machine.state() == Self::ST_SLIDE;
 
-        if let Some(weapon) = &mut node.weapon {
-            weapon.update(dt);
+            if let Some(trail) = &mut node.slide_trail {
+                if is_sliding {
+                    trail.add_point(center);
+                } else {
+                    trail.shrink();
+                }
+            }
+        }
+
+        let dt = SIMULATION_DELTA_TIME;
+
+        // The input of AI players is only set further down, so they are never considered AFK
+        if !node.ai_enabled && node.input.is_idle() {
+            node.idle_timer += dt;
+
+            let afk_timeout = storage::get::<Config>().afk_timeout;
+            if afk_timeout > 0.0 && node.idle_timer >= afk_timeout {
+                node.is_afk = true;
+            }
+        } else {
+            node.idle_timer = 0.0;
+            node.is_afk = false;
+        }
+
+        for slot in WeaponSlot::ALL {
+            let position = node.body.position;
+
+            // The hitboxes of a swing are checked before the attack animation is advanced, so
+            // that the first frame of the animation is checked as well
+            if node
+                .get_weapon(slot)
+                .map(|weapon| weapon.is_swinging)
+                .unwrap_or(false)
+            {
+                Weapon::update_swing(&mut node, slot);
+            }
+
+            if let Some(weapon) = node.get_weapon_mut(slot) {
+                weapon.update(dt);
+                weapon.play_frame_sounds(position);
+            }
+
+            if node
+                .get_weapon(slot)
+                .map(Weapon::is_broken)
+                .unwrap_or(false)
+            {
+                let handle = node.handle();
+                node.break_weapon(handle, slot);
+            }
+        }
+
+        // If the main hand weapon is lost, the off-hand weapon is moved to the main hand
+        if node.weapon.is_none() && node.state_machine.state() == Self::ST_NORMAL {
+            node.weapon = node.off_hand_weapon.take();
         }
 
         for item in node.equipped_items.values_mut() {
             item.update(dt);
         }
 
+        {
+            let animation_id = node.animation_player.get_current_animation_id();
+            let frame = node.animation_player.get_current_frame();
+
+            for item in node.equipped_items.values_mut() {
+                item.sync_animation(animation_id, frame);
+            }
+        }
+
         node.equipped_items.retain(|_, item| !item.is_depleted());
 
+        // Items may also be removed by their effects, so this is done every frame
+        node.update_stats();
+
         for effect in node.passive_effects.values_mut() {
             effect.update(dt);
         }
@@ -798,7 +1484,7 @@ impl Player {
         };
 
         if node.body.position.y > map_bottom {
-            Player::on_receive_damage(node.handle(), false, None);
+            Player::on_receive_damage(node.handle(), false, None, Self::LETHAL_DAMAGE);
         }
 
         if node.input.jump {
@@ -831,12 +1517,21 @@ impl Player {
             let node = &mut *node;
 
             if node.body.is_on_ground && !node.input.jump {
-                node.jump_grace_timer = Self::JUMP_GRACE_TIME;
+                node.jump_grace_timer = node.coyote_time;
             } else if node.jump_grace_timer > 0. {
-                node.jump_grace_timer -= get_frame_time();
+                node.jump_grace_timer -= SIMULATION_DELTA_TIME;
             }
 
             node.body.update();
+
+            if let Some(grappling_hook) = &node.grappling_hook {
+                grappling_hook.rope.apply(&mut node.body);
+            }
+        }
+
+        // Players that are squeezed into a solid, like by a closing door, are killed
+        if node.body.is_crushed && !node.is_dead {
+            Player::on_receive_damage(node.handle(), false, None, Self::LETHAL_DAMAGE);
         }
 
         {
@@ -861,13 +1556,14 @@ impl Player {
                 let is_overlapping = hitbox.overlaps(&other_hitbox);
                 if is_overlapping && hitbox.y + 60.0 < other_hitbox.y + node.head_threshold {
                     let resources = storage::get::<Resources>();
-                    let jump_sound = resources.sounds["jump"];
+                    let jump_sound = &resources.sounds["jump"];
 
-                    play_sound_once(jump_sound);
+                    play_sound_at(jump_sound, node.body.position);
                     Player::on_receive_damage(
                         player.handle(),
                         !node.body.is_facing_right,
                         Some(node.handle()),
+                        Self::LETHAL_DAMAGE,
                     );
                 }
             }
@@ -895,13 +1591,21 @@ impl Player {
             node.body.is_upside_down = !node.body.is_upside_down;
         }
 
+        // An attack that was interrupted, like by the death of the player, is abandoned, so that
+        // the next attack starts from the beginning
+        if node.state_machine.state() != Self::ST_ATTACK {
+            node.attack_timer = None;
+        }
+
         StateMachine::update_detached(node, |node| &mut node.state_machine);
     }
 
     fn draw_player(&self) {
         let size = self.animation_player.get_size();
 
-        let mut position = self.body.position;
+        let render_position = self.body.get_render_position();
+
+        let mut position = render_position;
 
         position.x -= size.x / 2.0;
 
@@ -910,6 +1614,12 @@ impl Player {
             position.y -= size.y - collider_size.y;
         }
 
+        let is_hit_flashing = self.hit_flash_timer > 0.0;
+
+        if is_hit_flashing {
+            HitFlash::begin(self.animation_player.get_texture(), self.hit_flash_timer);
+        }
+
         self.animation_player.draw(
             position,
             self.body.rotation,
@@ -917,8 +1627,12 @@ impl Player {
             self.body.is_upside_down,
         );
 
+        if is_hit_flashing {
+            HitFlash::end();
+        }
+
         for equipped in self.equipped_items.values() {
-            let mut position = self.body.position;
+            let mut position = render_position;
             position.y -= size.y - self.body.size.y;
 
             if self.body.is_upside_down {
@@ -940,20 +1654,38 @@ impl Player {
         self.body.debug_draw();
     }
 
-    fn draw_weapon(&mut self) {
-        let position = self.get_weapon_mount_position();
-        if let Some(weapon) = &mut self.weapon {
-            weapon.draw(
-                position,
-                self.body.rotation,
-                !self.body.is_facing_right,
-                self.body.is_upside_down,
-            );
+    fn draw_weapon(&mut self, slot: WeaponSlot) {
+        let render_position = self.body.get_render_position();
+
+        let position =
+            self.get_weapon_mount_position(slot) + (render_position - self.body.position);
+
+        let hud_y_offset = match slot {
+            WeaponSlot::MainHand => Self::WEAPON_HUD_Y_OFFSET,
+            WeaponSlot::OffHand => Self::OFF_HAND_WEAPON_HUD_Y_OFFSET,
+        };
+
+        let (rotation, flip_x, flip_y) = (
+            self.body.rotation,
+            !self.body.is_facing_right,
+            self.body.is_upside_down,
+        );
+
+        let aim_rotation = self.get_aim_rotation();
+
+        let mut hud_position = render_position;
+        hud_position.y += hud_y_offset;
+
+        if let Some(weapon) = self.get_weapon_mut(slot) {
+            let rotation = if weapon.is_melee() {
+                rotation
+            } else {
+                rotation + aim_rotation
+            };
 
-            let mut position = self.body.position;
-            position.y += Self::WEAPON_HUD_Y_OFFSET;
+            weapon.draw(position, rotation, flip_x, flip_y);
 
-            weapon.draw_hud(position);
+            weapon.draw_hud(hud_position);
         }
     }
 }
@@ -965,16 +1697,42 @@ impl scene::Node for Player {
     }
 
     fn draw(mut node: RefMut<Self>) {
+        let _scope = ProfileScope::new("draw players");
+
+        if let Some(grappling_hook) = &node.grappling_hook {
+            let collider = node.get_collider_rect();
+            let center = collider.point() + collider.size() / 2.0;
+
+            grappling_hook.draw(center + (node.body.get_render_position() - node.body.position));
+        }
+
+        if let Some(trail) = &node.slide_trail {
+            if !trail.is_empty() {
+                let collider = node.get_collider_rect();
+                let center = collider.point() + collider.size() / 2.0;
+
+                trail.draw(center + (node.body.get_render_position() - node.body.position));
+            }
+        }
+
+        // The off-hand weapon is held on the other side of the body, so it is drawn on the
+        // opposite layer of the main hand weapon
         if node.body.is_facing_right {
+            node.draw_weapon(WeaponSlot::OffHand);
             node.draw_player();
-            node.draw_weapon();
+            node.draw_weapon(WeaponSlot::MainHand);
         } else {
-            node.draw_weapon();
+            node.draw_weapon(WeaponSlot::MainHand);
             node.draw_player();
+            node.draw_weapon(WeaponSlot::OffHand);
         }
     }
 
     fn update(mut node: RefMut<Self>) {
+        if node.hit_flash_timer > 0.0 {
+            node.hit_flash_timer -= get_frame_time();
+        }
+
         if is_key_pressed(KeyCode::Key0) && node.id == 0 {
             node.ai_enabled ^= true;
         }
@@ -1033,90 +1791,336 @@ impl Player {
     }
 }
 
+/// The optional parameters of damage, that can be passed to `Player::on_receive_damage_ex`
+#[derive(Debug, Default, Clone)]
+pub struct DamageParams {
+    /// The hitstop, in ticks, that is applied to both the damaged player and the player that dealt
+    /// the damage, if the damage is not blocked
+    pub hitstop: u32,
+    /// The knockback applied to the damaged player, away from the source of the damage, if the
+    /// damage is not blocked and the player survives it
+    pub knockback: f32,
+    /// The id of the weapon that dealt the damage, if any, which a kill is credited to
+    pub weapon_id: Option<String>,
+    /// This is `true` for damage dealt by scripts, which does not call `on_hit` on item scripts,
+    /// so that a script that deals damage in `on_hit` does not call itself again
+    pub is_from_script: bool,
+}
+
+/// Something that happens to a player, like damage, a collision, or a call to an ability script or
+/// a passive effect, that is queued while nodes are updated and applied by
+/// `Player::apply_pending_interactions`
+enum PendingInteraction {
+    ReceiveDamage {
+        player_handle: Handle<Player>,
+        is_from_right: bool,
+        damage_from: Option<Handle<Player>>,
+        damage: f32,
+        params: DamageParams,
+    },
+    GiveDamage {
+        player_handle: Handle<Player>,
+        damage_to: Handle<Player>,
+        is_damage_blocked: bool,
+        hitstop: u32,
+    },
+    Collision {
+        player_handle: Handle<Player>,
+        collision_with: Handle<Player>,
+        is_new: bool,
+    },
+    AbilityCallback {
+        character_id: String,
+        player_id: u8,
+        callback: AbilityCallback,
+    },
+    PassiveEffect {
+        coroutine_id: String,
+        instance_id: String,
+        item_id: Option<String>,
+        player_handle: Handle<Player>,
+        event_params: PlayerEventParams,
+    },
+}
+
+static mut PENDING_INTERACTIONS: Option<Vec<PendingInteraction>> = None;
+
+unsafe fn get_pending_interactions() -> &'static mut Vec<PendingInteraction> {
+    PENDING_INTERACTIONS.get_or_insert_with(Vec::new)
+}
+
 impl Player {
     pub fn on_receive_damage(
         player_handle: Handle<Player>,
         is_from_right: bool,
         damage_from: Option<Handle<Player>>,
-    ) -> Coroutine {
-        let coroutine = async move {
-            if let Some(mut node) = scene::try_get_node(player_handle) {
-                if node.state_machine.state() != Self::ST_DEATH {
-                    let position = node.body.position;
+        damage: f32,
+    ) {
+        Self::on_receive_damage_ex(
+            player_handle,
+            is_from_right,
+            damage_from,
+            damage,
+            DamageParams::default(),
+        );
+    }
 
-                    let mut is_damage_blocked = false;
+    /// This queues damage for the player. The damage is applied in the current tick, by
+    /// `Player::apply_pending_interactions`, as the damaged player, or the player that dealt the
+    /// damage, might be borrowed when this is called.
+    pub fn on_receive_damage_ex(
+        player_handle: Handle<Player>,
+        is_from_right: bool,
+        damage_from: Option<Handle<Player>>,
+        damage: f32,
+        params: DamageParams,
+    ) {
+        unsafe { get_pending_interactions() }.push(PendingInteraction::ReceiveDamage {
+            player_handle,
+            is_from_right,
+            damage_from,
+            damage,
+            params,
+        });
+    }
 
-                    for effect in node.passive_effects.values_mut() {
-                        if effect.blocks_damage
-                            && effect.events.contains(&PlayerEvent::ReceiveDamage)
-                        {
-                            is_damage_blocked = true;
-                        }
+    pub fn on_give_damage(
+        player_handle: Handle<Player>,
+        damage_to: Handle<Player>,
+        is_damage_blocked: bool,
+        hitstop: u32,
+    ) {
+        unsafe { get_pending_interactions() }.push(PendingInteraction::GiveDamage {
+            player_handle,
+            damage_to,
+            is_damage_blocked,
+            hitstop,
+        });
+    }
+
+    pub fn on_collision(
+        player_handle: Handle<Player>,
+        collision_with: Handle<Player>,
+        is_new: bool,
+    ) {
+        unsafe { get_pending_interactions() }.push(PendingInteraction::Collision {
+            player_handle,
+            collision_with,
+            is_new,
+        });
+    }
 
-                        let params = PlayerEventParams::ReceiveDamage {
-                            is_from_right,
-                            damage_from,
-                            is_damage_blocked,
-                        };
+    /// This queues a call of `callback` on the ability script of the character `character_id`
+    pub fn on_ability_callback(character_id: &str, player_id: u8, callback: AbilityCallback) {
+        unsafe { get_pending_interactions() }.push(PendingInteraction::AbilityCallback {
+            character_id: character_id.to_string(),
+            player_id,
+            callback,
+        });
+    }
 
-                        effect.on_player_event(player_handle, position, params);
-                    }
+    /// This queues a call to the passive effect coroutine `coroutine_id`, for a player event
+    pub fn on_passive_effect_event(
+        coroutine_id: &str,
+        instance_id: &str,
+        item_id: Option<&str>,
+        player_handle: Handle<Player>,
+        event_params: PlayerEventParams,
+    ) {
+        unsafe { get_pending_interactions() }.push(PendingInteraction::PassiveEffect {
+            coroutine_id: coroutine_id.to_string(),
+            instance_id: instance_id.to_string(),
+            item_id: item_id.map(|id| id.to_string()),
+            player_handle,
+            event_params,
+        });
+    }
 
-                    if !is_damage_blocked {
-                        node.kill(is_from_right);
-                    }
+    /// This applies all queued damage, collisions, ability callbacks and passive effect events. It is called in the
+    /// simulation tick, after all nodes have been updated, so that no player is borrowed. Any
+    /// interactions that are queued while this runs, like the damage given by a player that
+    /// received damage, are applied as well, before this returns.
+    pub fn apply_pending_interactions() {
+        loop {
+            let interactions = std::mem::take(unsafe { get_pending_interactions() });
 
-                    if let Some(damage_from) = damage_from {
-                        Player::on_give_damage(damage_from, player_handle, is_damage_blocked);
+            if interactions.is_empty() {
+                break;
+            }
+
+            for interaction in interactions {
+                match interaction {
+                    PendingInteraction::ReceiveDamage {
+                        player_handle,
+                        is_from_right,
+                        damage_from,
+                        damage,
+                        params,
+                    } => Self::apply_damage(
+                        player_handle,
+                        is_from_right,
+                        damage_from,
+                        damage,
+                        params,
+                    ),
+                    PendingInteraction::GiveDamage {
+                        player_handle,
+                        damage_to,
+                        is_damage_blocked,
+                        hitstop,
+                    } => Self::apply_give_damage(
+                        player_handle,
+                        damage_to,
+                        is_damage_blocked,
+                        hitstop,
+                    ),
+                    PendingInteraction::Collision {
+                        player_handle,
+                        collision_with,
+                        is_new,
+                    } => {
+                        if let Some(mut node) = scene::try_get_node(player_handle) {
+                            let position = node.body.position;
+
+                            for effect in node.passive_effects.values_mut() {
+                                let params = PlayerEventParams::Collision {
+                                    is_new,
+                                    collision_with,
+                                };
+                                effect.on_player_event(player_handle, position, params);
+                            }
+                        }
+                    }
+                    PendingInteraction::AbilityCallback {
+                        character_id,
+                        player_id,
+                        callback,
+                    } => scripting::call_ability_callback(&character_id, player_id, callback),
+                    PendingInteraction::PassiveEffect {
+                        coroutine_id,
+                        instance_id,
+                        item_id,
+                        player_handle,
+                        event_params,
+                    } => {
+                        let coroutine = get_passive_effect_coroutine(&coroutine_id);
+                        coroutine(
+                            &instance_id,
+                            item_id.as_deref(),
+                            player_handle,
+                            event_params,
+                        );
                     }
                 }
             }
-        };
+        }
+    }
 
-        start_coroutine(coroutine)
+    /// This removes all queued interactions that have not been applied yet. It should be called
+    /// whenever a new game scene is created.
+    pub fn clear_pending_interactions() {
+        unsafe { get_pending_interactions() }.clear();
     }
 
-    pub fn on_give_damage(
+    fn apply_damage(
         player_handle: Handle<Player>,
-        damage_to: Handle<Player>,
-        is_damage_blocked: bool,
-    ) -> Coroutine {
-        let coroutine = async move {
-            if let Some(mut node) = scene::try_get_node(player_handle) {
-                let position = node.body.position;
+        is_from_right: bool,
+        damage_from: Option<Handle<Player>>,
+        damage: f32,
+        params: DamageParams,
+    ) {
+        let is_damage_blocked = {
+            let mut node = match scene::try_get_node(player_handle) {
+                Some(node) => node,
+                None => return,
+            };
 
-                for effect in node.passive_effects.values_mut() {
-                    let params = PlayerEventParams::GiveDamage {
-                        damage_to,
-                        is_damage_blocked,
-                    };
-                    effect.on_player_event(player_handle, position, params);
+            if node.is_dead {
+                return;
+            }
+
+            let position = node.body.position;
+
+            let mut is_damage_blocked = node.is_afk;
+
+            for effect in node.passive_effects.values_mut() {
+                if effect.blocks_damage && effect.events.contains(&PlayerEvent::ReceiveDamage) {
+                    is_damage_blocked = true;
                 }
+
+                let params = PlayerEventParams::ReceiveDamage {
+                    is_from_right,
+                    damage_from,
+                    is_damage_blocked,
+                };
+
+                effect.on_player_event(player_handle, position, params);
             }
+
+            if !is_damage_blocked {
+                let damage = get_mutator_rules().get_damage(damage, node.health);
+
+                GameEvent::PlayerDamaged {
+                    player_id: node.id,
+                    position,
+                    damage,
+                    damaged_by: damage_from
+                        .and_then(scene::try_get_node)
+                        .map(|player| player.id)
+                        .filter(|id| *id != node.id),
+                    weapon_id: params.weapon_id.clone(),
+                    is_from_script: params.is_from_script,
+                }
+                .dispatch();
+
+                node.health -= damage;
+
+                if node.health <= 0.0 {
+                    node.kill(is_from_right, damage_from, params.weapon_id.clone());
+                } else if params.knockback > 0.0 {
+                    let direction = if is_from_right { -1.0 } else { 1.0 };
+                    node.body
+                        .apply_knockback(vec2(direction * params.knockback, 0.0));
+                }
+            }
+
+            is_damage_blocked
         };
 
-        start_coroutine(coroutine)
+        if let Some(damage_from) = damage_from {
+            Self::on_give_damage(
+                damage_from,
+                player_handle,
+                is_damage_blocked,
+                params.hitstop,
+            );
+        }
     }
 
-    pub fn on_collision(
+    fn apply_give_damage(
         player_handle: Handle<Player>,
-        collision_with: Handle<Player>,
-        is_new: bool,
-    ) -> Coroutine {
-        let coroutine = async move {
-            if let Some(mut node) = scene::try_get_node(player_handle) {
-                let position = node.body.position;
+        damage_to: Handle<Player>,
+        is_damage_blocked: bool,
+        hitstop: u32,
+    ) {
+        if let Some(mut node) = scene::try_get_node(player_handle) {
+            let position = node.body.position;
 
-                for effect in node.passive_effects.values_mut() {
-                    let params = PlayerEventParams::Collision {
-                        is_new,
-                        collision_with,
-                    };
-                    effect.on_player_event(player_handle, position, params);
+            if !is_damage_blocked && hitstop > 0 {
+                node.apply_hitstop(hitstop);
+
+                if let Some(mut other) = scene::try_get_node(damage_to) {
+                    other.apply_hitstop(hitstop);
                 }
             }
-        };
 
-        start_coroutine(coroutine)
+            for effect in node.passive_effects.values_mut() {
+                let params = PlayerEventParams::GiveDamage {
+                    damage_to,
+                    is_damage_blocked,
+                };
+                effect.on_player_event(player_handle, position, params);
+            }
+        }
     }
 }
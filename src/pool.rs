@@ -0,0 +1,197 @@
+//! Pools of short-lived entities, like projectiles and popups. A pool keeps the values of despawned
+//! entities in their slots, so that the next entity that is spawned can reuse their heap buffers,
+//! like the `Vec`s of particle controllers of a projectile, in stead of allocating new ones. Once
+//! a pool has grown to the amount of entities that are alive at the peak of a match, spawning and
+//! despawning entities will not allocate at all.
+//!
+//! Entities are referred to by `PoolHandle`, which holds the generation of the slot that the
+//! entity was spawned in. The generation is incremented when the entity is despawned, so that a
+//! handle to a despawned entity will never refer to another entity that reuses its slot.
+//!
+//! Short-lived data that owns no heap buffers and is never referred to, like the particles of a
+//! particle emitter, is better kept in a plain `Vec`, which does not allocate either, once it has
+//! grown to its peak size.
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct PoolHandle {
+    index: u32,
+    generation: u32,
+}
+
+#[derive(Debug, Clone)]
+struct PoolSlot<T> {
+    value: T,
+    generation: u32,
+    is_alive: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Pool<T> {
+    slots: Vec<PoolSlot<T>>,
+    /// The indices of the slots that are not alive, in the order that they will be reused in
+    free: Vec<u32>,
+}
+
+#[allow(dead_code)]
+impl<T: Default> Pool<T> {
+    /// This creates a pool with `capacity` default values, so that the first `capacity` entities
+    /// that are spawned do not have to grow the pool
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut slots = Vec::with_capacity(capacity);
+        slots.resize_with(capacity, || PoolSlot {
+            value: T::default(),
+            generation: 0,
+            is_alive: false,
+        });
+
+        // Slots are popped from the back, so the free list is reversed to reuse the first slot
+        // first
+        let free = (0..capacity as u32).rev().collect();
+
+        Pool { slots, free }
+    }
+
+    /// This spawns an entity, in a free slot if there is one, and in a new slot otherwise. The
+    /// value in the slot is passed to `init` to be reset, which means that it holds the state of
+    /// the entity that was last despawned from the slot, or the default value, if the slot is new.
+    pub fn spawn_with<F: FnOnce(&mut T)>(&mut self, init: F) -> PoolHandle {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push(PoolSlot {
+                    value: T::default(),
+                    generation: 0,
+                    is_alive: false,
+                });
+
+                self.slots.len() as u32 - 1
+            }
+        };
+
+        let slot = &mut self.slots[index as usize];
+        slot.is_alive = true;
+
+        init(&mut slot.value);
+
+        PoolHandle {
+            index,
+            generation: slot.generation,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl<T> Pool<T> {
+    /// This despawns the entity of `handle`. Its value is kept for reuse, so any resources that
+    /// should be released right away, should be released before this is called.
+    /// Returns `false` if the entity has already been despawned.
+    pub fn despawn(&mut self, handle: PoolHandle) -> bool {
+        match self.slots.get_mut(handle.index as usize) {
+            Some(slot) if slot.is_alive && slot.generation == handle.generation => {
+                slot.is_alive = false;
+                slot.generation = slot.generation.wrapping_add(1);
+
+                self.free.push(handle.index);
+
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn get(&self, handle: PoolHandle) -> Option<&T> {
+        self.slots
+            .get(handle.index as usize)
+            .filter(|slot| slot.is_alive && slot.generation == handle.generation)
+            .map(|slot| &slot.value)
+    }
+
+    pub fn get_mut(&mut self, handle: PoolHandle) -> Option<&mut T> {
+        self.slots
+            .get_mut(handle.index as usize)
+            .filter(|slot| slot.is_alive && slot.generation == handle.generation)
+            .map(|slot| &mut slot.value)
+    }
+
+    /// Returns the amount of entities that are alive
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.slots
+            .iter()
+            .filter(|slot| slot.is_alive)
+            .map(|slot| &slot.value)
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.slots
+            .iter_mut()
+            .filter(|slot| slot.is_alive)
+            .map(|slot| &mut slot.value)
+    }
+
+    /// This despawns every entity for which `f` returns `false`
+    pub fn retain<F: FnMut(&mut T) -> bool>(&mut self, mut f: F) {
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            if slot.is_alive && !f(&mut slot.value) {
+                slot.is_alive = false;
+                slot.generation = slot.generation.wrapping_add(1);
+
+                self.free.push(index as u32);
+            }
+        }
+    }
+
+    /// This despawns all entities
+    pub fn clear(&mut self) {
+        self.retain(|_| false);
+    }
+}
+
+impl<T: Default> Default for Pool<T> {
+    fn default() -> Self {
+        Self::with_capacity(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_reuses_despawned_slot() {
+        let mut pool: Pool<Vec<u32>> = Pool::with_capacity(1);
+
+        let first = pool.spawn_with(|value| value.push(1));
+        assert!(pool.despawn(first));
+
+        let second = pool.spawn_with(|value| {
+            assert_eq!(*value, vec![1]);
+            value.clear();
+        });
+
+        assert_eq!(pool.len(), 1);
+        assert_eq!(pool.slots.len(), 1);
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_pool_stale_handle() {
+        let mut pool: Pool<u32> = Pool::default();
+
+        let first = pool.spawn_with(|value| *value = 1);
+        pool.retain(|_| false);
+
+        let second = pool.spawn_with(|value| *value = 2);
+
+        assert_eq!(pool.get(first), None);
+        assert!(!pool.despawn(first));
+        assert_eq!(pool.get(second), Some(&2));
+    }
+}
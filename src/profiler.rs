@@ -0,0 +1,537 @@
+//! The profiler measures the time spent in named scopes, like the simulation tick, physics and
+//! drawing, so that performance problems, on big maps in particular, can be narrowed down to the
+//! subsystem that causes them. A scope is measured by creating a `ProfileScope` at its start,
+//! which is ended when it is dropped, and scopes that are created while another scope is active
+//! are nested in it:
+//!
+//! ```ignore
+//! let _scope = ProfileScope::new("physics");
+//! ```
+//!
+//! Nothing is recorded while the profiler is disabled, so scopes are cheap enough to leave in hot
+//! code. While it is enabled, the `ProfilerOverlay` draws a graph of the duration of the most
+//! recent frames, broken down by top level scope, as well as a flame graph of the last frame. It
+//! is toggled with F4, or with the `profiler` console command.
+//!
+//! The legend of the overlay also shows the estimated GPU memory used by textures, which can be
+//! limited by evicting map textures, with the `resources.texture_eviction_maps` cvar.
+//!
+//! The scopes can also be recorded to a trace file, in the Chrome tracing format, with
+//! `profiler trace start` and `profiler trace stop [path]`. These files can be opened in
+//! `chrome://tracing`, or in Perfetto, to inspect individual frames.
+
+use std::{collections::VecDeque, fs};
+
+use macroquad::{
+    color,
+    experimental::{
+        collections::storage,
+        scene::{Node, RefMut},
+    },
+    prelude::*,
+};
+
+use serde::Serialize;
+
+use crate::{
+    console, formaterr, frame_alloc,
+    text::{draw_aligned_text, HorizontalAlignment, VerticalAlignment},
+    Resources, Result,
+};
+
+/// The default path that traces are written to, if no path is given to `profiler trace stop`
+pub const DEFAULT_TRACE_PATH: &str = "profiler_trace.json";
+
+/// The amount of frames that are kept for the graph of the overlay
+const FRAME_HISTORY_LEN: usize = 180;
+
+/// A scope that was measured on the current, or a previous, frame. Times are in seconds.
+#[derive(Debug, Clone)]
+struct ScopeSample {
+    name: &'static str,
+    depth: u32,
+    start: f64,
+    /// This is `None` until the scope has ended
+    duration: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+struct FrameSample {
+    start: f64,
+    duration: f64,
+    scopes: Vec<ScopeSample>,
+}
+
+impl FrameSample {
+    /// Returns the total duration of the top level scopes named `name`
+    fn get_total(&self, name: &str) -> f64 {
+        self.scopes
+            .iter()
+            .filter(|scope| scope.depth == 0 && scope.name == name)
+            .filter_map(|scope| scope.duration)
+            .sum()
+    }
+}
+
+/// An event in the Chrome tracing format. Times are in microseconds.
+#[derive(Debug, Clone, Serialize)]
+struct TraceEvent {
+    name: &'static str,
+    ph: &'static str,
+    ts: f64,
+    dur: f64,
+    pid: u32,
+    tid: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Trace {
+    trace_events: Vec<TraceEvent>,
+}
+
+#[derive(Debug, Default)]
+struct Profiler {
+    is_enabled: bool,
+    frame_start: f64,
+    depth: u32,
+    scopes: Vec<ScopeSample>,
+    history: VecDeque<FrameSample>,
+    /// The events that have been recorded since the trace was started, if one is being recorded
+    trace: Option<Vec<TraceEvent>>,
+}
+
+impl Profiler {
+    fn is_recording(&self) -> bool {
+        self.is_enabled || self.trace.is_some()
+    }
+}
+
+static mut PROFILER: Option<Profiler> = None;
+
+unsafe fn get_profiler() -> &'static mut Profiler {
+    PROFILER.get_or_insert_with(Profiler::default)
+}
+
+pub fn is_profiler_enabled() -> bool {
+    unsafe { get_profiler() }.is_enabled
+}
+
+pub fn toggle_profiler() {
+    let profiler = unsafe { get_profiler() };
+
+    profiler.is_enabled = !profiler.is_enabled;
+
+    if !profiler.is_enabled {
+        profiler.history.clear();
+    }
+}
+
+/// This starts recording a trace. Any trace that is already being recorded is discarded.
+pub fn start_trace() {
+    unsafe { get_profiler() }.trace = Some(Vec::new());
+}
+
+/// This stops the trace that is being recorded and writes it to `path`
+pub fn stop_trace(path: &str) -> Result<usize> {
+    let trace_events = unsafe { get_profiler() }
+        .trace
+        .take()
+        .ok_or_else(|| formaterr!("Profiler: No trace is being recorded"))?;
+
+    let event_cnt = trace_events.len();
+
+    let json = serde_json::to_string(&Trace { trace_events })?;
+    fs::write(path, json)?;
+
+    Ok(event_cnt)
+}
+
+/// This ends the current frame and starts the next one. It should be called once per frame, at
+/// the same point of the main loop, so that everything between two calls is attributed to one
+/// frame.
+pub fn end_frame() {
+    let profiler = unsafe { get_profiler() };
+
+    let now = get_time();
+
+    let scopes = std::mem::take(&mut profiler.scopes);
+
+    if profiler.is_recording() {
+        let scopes = scopes
+            .into_iter()
+            .filter(|scope| scope.duration.is_some())
+            .collect::<Vec<_>>();
+
+        if let Some(trace) = &mut profiler.trace {
+            for scope in &scopes {
+                trace.push(TraceEvent {
+                    name: scope.name,
+                    ph: "X",
+                    ts: scope.start * 1_000_000.0,
+                    dur: scope.duration.unwrap() * 1_000_000.0,
+                    pid: 0,
+                    tid: 0,
+                });
+            }
+
+            trace.push(TraceEvent {
+                name: "frame",
+                ph: "X",
+                ts: profiler.frame_start * 1_000_000.0,
+                dur: (now - profiler.frame_start) * 1_000_000.0,
+                pid: 0,
+                tid: 1,
+            });
+        }
+
+        if profiler.is_enabled {
+            if profiler.history.len() >= FRAME_HISTORY_LEN {
+                profiler.history.pop_front();
+            }
+
+            profiler.history.push_back(FrameSample {
+                start: profiler.frame_start,
+                duration: now - profiler.frame_start,
+                scopes,
+            });
+        }
+    }
+
+    profiler.frame_start = now;
+    profiler.depth = 0;
+}
+
+/// A scope that is measured from its creation until it is dropped. Use `let _scope = ...`, as
+/// `let _ = ...` drops it immediately.
+pub struct ProfileScope {
+    index: Option<usize>,
+}
+
+impl ProfileScope {
+    pub fn new(name: &'static str) -> Self {
+        let profiler = unsafe { get_profiler() };
+
+        if !profiler.is_recording() {
+            return ProfileScope { index: None };
+        }
+
+        let index = profiler.scopes.len();
+
+        profiler.scopes.push(ScopeSample {
+            name,
+            depth: profiler.depth,
+            start: get_time(),
+            duration: None,
+        });
+
+        profiler.depth += 1;
+
+        ProfileScope { index: Some(index) }
+    }
+}
+
+impl Drop for ProfileScope {
+    fn drop(&mut self) {
+        if let Some(index) = self.index {
+            let profiler = unsafe { get_profiler() };
+
+            // The scope was discarded if the frame ended while it was active
+            if let Some(scope) = profiler.scopes.get_mut(index) {
+                scope.duration = Some(get_time() - scope.start);
+                profiler.depth = scope.depth;
+            }
+        }
+    }
+}
+
+/// Returns a color for the scope `name`, which is the same every frame
+fn get_scope_color(name: &str) -> Color {
+    const COLORS: &[Color] = &[
+        color::SKYBLUE,
+        color::ORANGE,
+        color::LIME,
+        color::PINK,
+        color::GOLD,
+        color::VIOLET,
+        color::BEIGE,
+        color::RED,
+    ];
+
+    let hash = name.bytes().fold(0usize, |hash, b| {
+        hash.wrapping_mul(31).wrapping_add(b as usize)
+    });
+
+    COLORS[hash % COLORS.len()]
+}
+
+/// The overlay that draws the frame graph and the flame graph of the profiler, while it is
+/// enabled
+pub struct ProfilerOverlay;
+
+impl ProfilerOverlay {
+    const TOGGLE_KEY: KeyCode = KeyCode::F4;
+
+    const MARGIN: f32 = 12.0;
+    const GRAPH_HEIGHT: f32 = 120.0;
+    const BAR_WIDTH: f32 = 2.0;
+    const FLAME_ROW_HEIGHT: f32 = 14.0;
+    const FONT_SIZE: u16 = 14;
+    const LINE_HEIGHT: f32 = 16.0;
+
+    /// The frame duration, in seconds, that corresponds to the top of the graph
+    const GRAPH_MAX_DURATION: f64 = 1.0 / 30.0;
+    /// The frame duration, in seconds, that a horizontal line is drawn at, as a reference
+    const TARGET_DURATION: f64 = 1.0 / 60.0;
+
+    pub fn new() -> Self {
+        ProfilerOverlay
+    }
+
+    fn draw_text(text: &str, position: Vec2, color: Color) {
+        draw_aligned_text(
+            text,
+            position,
+            HorizontalAlignment::Left,
+            VerticalAlignment::Top,
+            TextParams {
+                font_size: Self::FONT_SIZE,
+                color,
+                ..Default::default()
+            },
+        );
+    }
+
+    /// This draws a bar for every frame in the history, with the top level scopes stacked on top
+    /// of each other, and returns the names of the top level scopes, in the order that they were
+    /// first seen in
+    fn draw_frame_graph(history: &VecDeque<FrameSample>, position: Vec2) -> Vec<&'static str> {
+        let width = FRAME_HISTORY_LEN as f32 * Self::BAR_WIDTH;
+        let scale = Self::GRAPH_HEIGHT / Self::GRAPH_MAX_DURATION as f32;
+        let bottom = position.y + Self::GRAPH_HEIGHT;
+
+        draw_rectangle(
+            position.x,
+            position.y,
+            width,
+            Self::GRAPH_HEIGHT,
+            Color::new(0.0, 0.0, 0.0, 0.6),
+        );
+
+        let mut names = Vec::new();
+
+        for (i, frame) in history.iter().enumerate() {
+            let x = position.x + i as f32 * Self::BAR_WIDTH;
+
+            let frame_height = (frame.duration as f32 * scale).min(Self::GRAPH_HEIGHT);
+            draw_rectangle(
+                x,
+                bottom - frame_height,
+                Self::BAR_WIDTH,
+                frame_height,
+                color::DARKGRAY,
+            );
+
+            let mut y = bottom;
+            for scope in frame.scopes.iter().filter(|scope| scope.depth == 0) {
+                if !names.contains(&scope.name) {
+                    names.push(scope.name);
+                }
+
+                let height = scope.duration.unwrap_or(0.0) as f32 * scale;
+                let height = height.min(y - position.y);
+
+                draw_rectangle(
+                    x,
+                    y - height,
+                    Self::BAR_WIDTH,
+                    height,
+                    get_scope_color(scope.name),
+                );
+
+                y -= height;
+            }
+        }
+
+        let target_y = bottom - Self::TARGET_DURATION as f32 * scale;
+        draw_line(
+            position.x,
+            target_y,
+            position.x + width,
+            target_y,
+            1.0,
+            color::WHITE,
+        );
+
+        names
+    }
+
+    /// This draws the scopes of `frame` as a flame graph, with nested scopes below the scope that
+    /// they are nested in, and returns the height of the graph
+    fn draw_flame_graph(frame: &FrameSample, position: Vec2, width: f32) -> f32 {
+        let mut max_depth = 0;
+
+        for scope in &frame.scopes {
+            let duration = match scope.duration {
+                Some(duration) => duration,
+                None => continue,
+            };
+
+            let x = position.x + ((scope.start - frame.start) / frame.duration) as f32 * width;
+            let y = position.y + scope.depth as f32 * Self::FLAME_ROW_HEIGHT;
+            let w = ((duration / frame.duration) as f32 * width).max(1.0);
+
+            draw_rectangle(
+                x,
+                y,
+                w,
+                Self::FLAME_ROW_HEIGHT - 1.0,
+                get_scope_color(scope.name),
+            );
+
+            // Names are only drawn on scopes that are wide enough to fit some of them
+            if w > 40.0 {
+                Self::draw_text(scope.name, vec2(x + 2.0, y), color::BLACK);
+            }
+
+            max_depth = max_depth.max(scope.depth + 1);
+        }
+
+        max_depth as f32 * Self::FLAME_ROW_HEIGHT
+    }
+}
+
+impl Default for ProfilerOverlay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Node for ProfilerOverlay {
+    fn update(_: RefMut<Self>) {
+        if is_key_pressed(Self::TOGGLE_KEY) {
+            toggle_profiler();
+        }
+    }
+
+    fn draw(_: RefMut<Self>) {
+        let profiler = unsafe { get_profiler() };
+
+        if !profiler.is_enabled {
+            return;
+        }
+
+        let last_frame = match profiler.history.back() {
+            Some(frame) => frame,
+            None => return,
+        };
+
+        push_camera_state();
+        set_default_camera();
+
+        let position = vec2(Self::MARGIN, Self::MARGIN);
+        let width = FRAME_HISTORY_LEN as f32 * Self::BAR_WIDTH;
+
+        let names = Self::draw_frame_graph(&profiler.history, position);
+
+        let flame_position = position + vec2(0.0, Self::GRAPH_HEIGHT + Self::MARGIN);
+        let flame_height = Self::draw_flame_graph(last_frame, flame_position, width);
+
+        // The legend lists the average duration of every top level scope, over the history
+        let frame_cnt = profiler.history.len() as f64;
+        let average_frame = profiler
+            .history
+            .iter()
+            .map(|frame| frame.duration)
+            .sum::<f64>()
+            / frame_cnt;
+
+        let mut y = flame_position.y + flame_height + Self::MARGIN;
+
+        Self::draw_text(
+            &format!("frame: {:.2} ms", average_frame * 1000.0),
+            vec2(position.x, y),
+            color::WHITE,
+        );
+
+        {
+            let resources = storage::get::<Resources>();
+
+            let memory_usage = resources.get_texture_memory_usage() as f32 / (1024.0 * 1024.0);
+            let evicted_cnt = resources.get_evicted_texture_cnt();
+
+            y += Self::LINE_HEIGHT;
+
+            Self::draw_text(
+                &format!("textures: {:.1} MB ({} evicted)", memory_usage, evicted_cnt),
+                vec2(position.x, y),
+                color::WHITE,
+            );
+        }
+
+        {
+            let stats = frame_alloc::get_last_frame_stats();
+
+            y += Self::LINE_HEIGHT;
+
+            Self::draw_text(
+                &format!(
+                    "frame buffers: {} taken, {:.1} KB pooled",
+                    stats.taken_cnt,
+                    stats.pooled_capacity as f32 / 1024.0
+                ),
+                vec2(position.x, y),
+                color::WHITE,
+            );
+        }
+
+        for name in names {
+            y += Self::LINE_HEIGHT;
+
+            let average = profiler
+                .history
+                .iter()
+                .map(|frame| frame.get_total(name))
+                .sum::<f64>()
+                / frame_cnt;
+
+            Self::draw_text(
+                &format!("{}: {:.2} ms", name, average * 1000.0),
+                vec2(position.x, y),
+                get_scope_color(name),
+            );
+        }
+
+        if profiler.trace.is_some() {
+            y += Self::LINE_HEIGHT;
+            Self::draw_text("recording trace", vec2(position.x, y), color::RED);
+        }
+
+        pop_camera_state();
+    }
+}
+
+pub fn register_console_commands() {
+    console::register("profiler", |args| match args {
+        [] => {
+            toggle_profiler();
+            Ok(format!("Profiler: {}", is_profiler_enabled()))
+        }
+        ["trace", "start"] => {
+            start_trace();
+            Ok("Profiler: Recording trace".to_string())
+        }
+        ["trace", "stop"] | ["trace", "stop", _] => {
+            let path = args.get(2).copied().unwrap_or(DEFAULT_TRACE_PATH);
+            let event_cnt = stop_trace(path)?;
+
+            Ok(format!(
+                "Profiler: Wrote {} events to '{}'",
+                event_cnt, path
+            ))
+        }
+        _ => Err(formaterr!(
+            "Usage: profiler [trace start | trace stop [path]]"
+        )),
+    });
+}
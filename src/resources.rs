@@ -1,7 +1,11 @@
-use std::{collections::HashMap, fs, path::Path};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
 
 use macroquad::{
-    audio::{load_sound, Sound},
+    audio::load_sound,
     experimental::{collections::storage, coroutines::start_coroutine},
     prelude::*,
 };
@@ -10,17 +14,28 @@ use ff_particles::EmitterConfig;
 
 use serde::{Deserialize, Serialize};
 
-use crate::gui::GuiResources;
+use crate::gui::{Credits, GuiResources};
 use crate::{
+    audio::{MusicStem, MusicTrack, SoundGroup},
+    cvars::{self, CVarFlags, CVarValue},
     error::{ErrorKind, Result},
     formaterr,
-    items::ItemParams,
-    json::{self, deserialize_json_file},
+    game::{AnnouncerRule, Cutscene, Ruleset},
+    items::{ItemParams, LootTable},
+    json::{self, deserialize_json_file, OneOrMany},
+    localization::{Language, LanguageMetadata},
     map::Map,
+    math::IsZero,
+    mods::{load_mod_manifests, resolve_load_order, ModContent, ModInfo, ModStatus},
+    mutators::{validate_mutator, MutatorMetadata, MutatorResource},
+    particles::ParticleEmitterParams,
+    scripting::{AbilityScript, GameModeScript, ItemScript},
+    symbol::{Symbol, SymbolMap},
 };
 
 use crate::player::PlayerCharacterParams;
 use crate::text::ToStringHelper;
+use crate::Config;
 
 #[derive(Serialize, Deserialize)]
 struct ParticleEffectMetadata {
@@ -28,10 +43,47 @@ struct ParticleEffectMetadata {
     path: String,
 }
 
+#[derive(Serialize, Deserialize)]
+struct GameModeMetadata {
+    id: String,
+    /// The path of the script that implements the game mode
+    script: String,
+}
+
 #[derive(Serialize, Deserialize)]
 struct SoundMetadata {
+    id: String,
+    /// This can be a single path or a list of paths, in which case a random one of the
+    /// variations will be played, every time the sound is played
+    path: OneOrMany<String>,
+    /// The maximum amount the volume is randomly lowered by, every time the sound is played
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    volume_jitter: f32,
+    /// The maximum amount the pitch is randomly changed by, every time the sound is played
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    pitch_jitter: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MusicMetadata {
     id: String,
     path: String,
+    /// An intro section, that is played once before the track at `path` is looped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    intro_path: Option<String>,
+    /// The duration of the intro section, in seconds
+    #[serde(default, skip_serializing_if = "f32::is_zero")]
+    intro_duration: f32,
+    /// Stems that are layered on top of the main loop, as the music intensity rises
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    stems: Vec<MusicStemMetadata>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MusicStemMetadata {
+    path: String,
+    /// The music intensity, between `0.0` and `1.0`, at which the stem becomes audible
+    intensity: f32,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
@@ -64,6 +116,58 @@ pub struct TextureMetadata {
 pub struct TextureResource {
     pub texture: Texture2D,
     pub meta: TextureMetadata,
+    /// The path that the texture was loaded from, so that it can be reloaded after it has been
+    /// evicted
+    pub file_path: PathBuf,
+    /// The value of `Resources::started_map_cnt` when the texture was last used by a map, or
+    /// `None` if it has not been used by a map. Only textures that have been used by maps can be
+    /// evicted.
+    pub last_used_by_map: Option<u32>,
+    /// This is `true` if the texture is only ever used by maps, so that it can be evicted when no
+    /// recent map has used it. See `is_map_exclusive_texture`.
+    pub is_map_exclusive: bool,
+    pub is_evicted: bool,
+}
+
+impl TextureResource {
+    /// Returns the estimated amount of GPU memory, in bytes, used by the texture
+    pub fn get_memory_usage(&self) -> usize {
+        if self.is_evicted {
+            0
+        } else {
+            get_texture_memory_usage(self.texture)
+        }
+    }
+}
+
+/// The tileset and background textures that are also used outside of maps, by the menu background
+/// and by decorations, both in game and in the editor
+const NON_MAP_TEXTURE_IDS: &[&str] = &[
+    "background_01",
+    "background_02",
+    "background_03",
+    "background_04",
+    "default_decorations",
+];
+
+/// Returns `true` if the texture described by `meta` is only used by the tilesets and backgrounds
+/// of maps. Textures of any other kind are used by items, characters, particles and the UI, which
+/// hold on to them for as long as the game runs, so they are never evicted.
+fn is_map_exclusive_texture(meta: &TextureMetadata) -> bool {
+    let is_map_kind = matches!(
+        meta.kind,
+        Some(TextureKind::Tileset) | Some(TextureKind::Background)
+    );
+
+    is_map_kind && !NON_MAP_TEXTURE_IDS.contains(&meta.id.as_str())
+}
+
+/// Returns the estimated amount of GPU memory, in bytes, used by `texture`. Macroquad decodes
+/// every image file to RGBA8, at 4 bytes per pixel, before it is uploaded, and it does not
+/// generate mipmaps, so this is the size of the texture data. Padding and other overhead of the
+/// driver is not included.
+fn get_texture_memory_usage(texture: Texture2D) -> usize {
+    texture.width() as usize * texture.height() as usize * 4
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -91,6 +195,17 @@ pub struct MapMetadata {
     pub is_tiled_map: bool,
     #[serde(default, skip_serializing_if = "json::is_false")]
     pub is_user_map: bool,
+    /// The id of the music track played during a match on the map. If this is not specified,
+    /// the default match track is played.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub music: Option<String>,
+    /// The id of the music track played during overtime, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overtime_music: Option<String>,
+    /// The id of a cutscene that is played before the first round on the map starts, like a
+    /// flyover of the map
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub intro_cutscene: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -104,24 +219,62 @@ pub struct Resources {
     pub assets_dir: String,
 
     pub particle_effects: HashMap<String, EmitterConfig>,
-    pub sounds: HashMap<String, Sound>,
-    pub music: HashMap<String, Sound>,
-    pub textures: HashMap<String, TextureResource>,
+    pub particle_emitters: HashMap<String, ParticleEmitterParams>,
+    pub sounds: HashMap<String, SoundGroup>,
+    pub music: HashMap<String, MusicTrack>,
+    pub textures: SymbolMap<TextureResource>,
     pub images: HashMap<String, ImageResource>,
     pub maps: Vec<MapResource>,
-    pub items: HashMap<String, ItemParams>,
+    /// The map of training mode. This is kept separate from `maps`, so that it will not show up
+    /// in map selection, or in map votes.
+    pub training_map: MapResource,
+    pub items: SymbolMap<ItemParams>,
+    /// The compiled scripts of the items that have one, by item id
+    pub item_scripts: HashMap<String, ItemScript>,
+    pub loot_tables: HashMap<String, LootTable>,
     pub player_characters: Vec<PlayerCharacterParams>,
+    /// The compiled ability scripts of the player characters that have one, by character id
+    pub ability_scripts: HashMap<String, AbilityScript>,
+    pub announcer_rules: Vec<AnnouncerRule>,
+    pub cutscenes: HashMap<String, Cutscene>,
+    pub ruleset: Ruleset,
+    /// The scripted game modes, by id
+    pub game_mode_scripts: HashMap<String, GameModeScript>,
+    /// The mutators that can be enabled in the ruleset, by id
+    pub mutators: HashMap<String, MutatorResource>,
+    pub credits: Credits,
+    pub languages: Vec<Language>,
+    /// All installed mods, with their status, in the order they are listed in the mods file
+    pub mods: Vec<ModInfo>,
+    /// The amount of maps that have been started, in the game or in the editor, since the
+    /// resources were loaded. This is used to find the textures that have not been used for a
+    /// while, when texture eviction is enabled.
+    pub started_map_cnt: u32,
 }
 
 impl Resources {
     pub const PARTICLE_EFFECTS_DIR: &'static str = "particle_effects";
+    pub const PARTICLE_EMITTERS_FILE: &'static str = "particle_emitters";
     pub const SOUNDS_FILE: &'static str = "sounds";
     pub const MUSIC_FILE: &'static str = "music";
     pub const TEXTURES_FILE: &'static str = "textures";
     pub const IMAGES_FILE: &'static str = "images";
     pub const MAPS_FILE: &'static str = "maps";
+
+    /// The amount of maps after which a map texture that has not been used is evicted, where `0`
+    /// disables eviction
+    pub const TEXTURE_EVICTION_CVAR: &'static str = "resources.texture_eviction_maps";
+    pub const TRAINING_MAP_FILE: &'static str = "training_map";
     pub const ITEMS_FILE: &'static str = "items";
+    pub const LOOT_TABLES_FILE: &'static str = "loot_tables";
     pub const PLAYER_CHARACTERS_FILE: &'static str = "player_characters";
+    pub const ANNOUNCER_FILE: &'static str = "announcer";
+    pub const CUTSCENES_FILE: &'static str = "cutscenes";
+    pub const RULESET_FILE: &'static str = "ruleset";
+    pub const MUTATORS_FILE: &'static str = "mutators";
+    pub const GAME_MODES_FILE: &'static str = "game_modes";
+    pub const CREDITS_FILE: &'static str = "credits";
+    pub const LOCALIZATION_FILE: &'static str = "localization";
 
     pub const RESOURCE_FILES_EXTENSION: &'static str = "json";
 
@@ -136,191 +289,709 @@ impl Resources {
         let assets_dir_path = Path::new(assets_dir);
 
         let mut particle_effects = HashMap::new();
+        Self::load_particle_effects(assets_dir_path, &mut particle_effects).await?;
+
+        let mut particle_emitters = HashMap::new();
+        Self::load_particle_emitters(assets_dir_path, &mut particle_emitters).await?;
+
+        let mut sounds = HashMap::new();
+        Self::load_sounds(assets_dir_path, &mut sounds).await?;
+
+        let mut music = HashMap::new();
 
         {
-            let particle_effects_file_path = assets_dir_path
-                .join(Self::PARTICLE_EFFECTS_DIR)
+            let music_file_path = assets_dir_path
+                .join(Self::MUSIC_FILE)
                 .with_extension(Self::RESOURCE_FILES_EXTENSION);
 
-            let metadata: Vec<ParticleEffectMetadata> =
-                deserialize_json_file(&particle_effects_file_path).await?;
+            let metadata: Vec<MusicMetadata> = deserialize_json_file(&music_file_path).await?;
 
             for meta in metadata {
-                let file_path = assets_dir_path.join(&meta.path);
+                let file_path = assets_dir_path.join(meta.path);
+
+                let sound = load_sound(&file_path.to_string_helper()).await?;
 
-                let cfg: EmitterConfig = deserialize_json_file(&file_path).await?;
+                let intro = if let Some(intro_path) = meta.intro_path {
+                    let file_path = assets_dir_path.join(intro_path);
 
-                particle_effects.insert(meta.id, cfg);
+                    let intro = load_sound(&file_path.to_string_helper()).await?;
+
+                    Some((intro, meta.intro_duration))
+                } else {
+                    None
+                };
+
+                let mut stems = Vec::new();
+
+                for stem in meta.stems {
+                    let file_path = assets_dir_path.join(stem.path);
+
+                    let sound = load_sound(&file_path.to_string_helper()).await?;
+
+                    stems.push(MusicStem {
+                        sound,
+                        intensity: stem.intensity,
+                    });
+                }
+
+                music.insert(
+                    meta.id,
+                    MusicTrack {
+                        sound,
+                        intro,
+                        stems,
+                    },
+                );
             }
         }
 
-        let mut sounds = HashMap::new();
+        let mut textures = SymbolMap::new();
+        Self::load_textures(assets_dir_path, &mut textures).await?;
 
-        {
-            let sounds_file_path = assets_dir_path
-                .join(Self::SOUNDS_FILE)
+        let mut images = HashMap::new();
+        Self::load_images(assets_dir_path, &mut images).await?;
+
+        let mut maps = Vec::new();
+        Self::load_maps(assets_dir_path, &mut maps).await?;
+
+        let training_map = {
+            let path = assets_dir_path
+                .join(Self::TRAINING_MAP_FILE)
                 .with_extension(Self::RESOURCE_FILES_EXTENSION);
 
-            let metadata: Vec<SoundMetadata> = deserialize_json_file(&sounds_file_path).await?;
+            let meta: MapMetadata = deserialize_json_file(&path).await?;
 
-            for meta in metadata {
-                let file_path = assets_dir_path.join(meta.path);
+            let map_path = assets_dir_path.join(&meta.path);
+            let preview_path = assets_dir_path.join(&meta.preview_path);
 
-                let sound = load_sound(&file_path.to_string_helper()).await?;
+            let map = if meta.is_tiled_map {
+                Map::load_tiled(map_path, None).await?
+            } else {
+                Map::load(map_path).await?
+            };
+
+            let preview = load_texture(&preview_path.to_string_helper()).await?;
+
+            MapResource { map, preview, meta }
+        };
+
+        let mut items = SymbolMap::new();
+        let mut item_scripts = HashMap::new();
+        Self::load_items(assets_dir_path, &mut items, &mut item_scripts).await?;
+
+        let mut loot_tables = HashMap::new();
+        Self::load_loot_tables(assets_dir_path, &mut loot_tables).await?;
+
+        let mut player_characters = Vec::new();
+        let mut ability_scripts = HashMap::new();
+        Self::load_player_characters(
+            assets_dir_path,
+            &mut player_characters,
+            &mut ability_scripts,
+        )
+        .await?;
+
+        let announcer_rules = {
+            let path = assets_dir_path
+                .join(Self::ANNOUNCER_FILE)
+                .with_extension(Self::RESOURCE_FILES_EXTENSION);
+
+            deserialize_json_file(&path).await?
+        };
+
+        let mut cutscenes = HashMap::new();
+        Self::load_cutscenes(assets_dir_path, &mut cutscenes).await?;
+
+        let ruleset = {
+            let path = assets_dir_path
+                .join(Self::RULESET_FILE)
+                .with_extension(Self::RESOURCE_FILES_EXTENSION);
 
-                sounds.insert(meta.id, sound);
+            deserialize_json_file(&path).await?
+        };
+
+        let mut game_mode_scripts = HashMap::new();
+
+        {
+            let game_modes_file_path = assets_dir_path
+                .join(Self::GAME_MODES_FILE)
+                .with_extension(Self::RESOURCE_FILES_EXTENSION);
+
+            let metadata: Vec<GameModeMetadata> =
+                deserialize_json_file(&game_modes_file_path).await?;
+
+            for meta in metadata {
+                let script_path = assets_dir_path.join(&meta.script);
+                let source = load_string(&script_path.to_string_helper()).await?;
+
+                let script = GameModeScript::compile(&source).map_err(|err| {
+                    formaterr!(
+                        ErrorKind::Parsing,
+                        "Game mode '{}': Failed to compile script: {}",
+                        &meta.id,
+                        err
+                    )
+                })?;
+
+                game_mode_scripts.insert(meta.id, script);
             }
         }
 
-        let mut music = HashMap::new();
+        let mut mutators = HashMap::new();
 
         {
-            let music_file_path = assets_dir_path
-                .join(Self::MUSIC_FILE)
+            let mutators_file_path = assets_dir_path
+                .join(Self::MUTATORS_FILE)
                 .with_extension(Self::RESOURCE_FILES_EXTENSION);
 
-            let metadata: Vec<SoundMetadata> = deserialize_json_file(&music_file_path).await?;
+            let metadata: Vec<MutatorMetadata> = deserialize_json_file(&mutators_file_path).await?;
 
             for meta in metadata {
-                let file_path = assets_dir_path.join(meta.path);
+                let path = assets_dir_path.join(&meta.path);
+                let bytes = load_file(&path.to_string_helper()).await?;
 
-                let sound = load_sound(&file_path.to_string_helper()).await?;
+                validate_mutator(&meta.id, &bytes)?;
 
-                music.insert(meta.id, sound);
+                mutators.insert(meta.id.clone(), MutatorResource { meta, bytes });
             }
         }
 
-        let mut textures = HashMap::new();
+        let credits = {
+            let path = assets_dir_path
+                .join(Self::CREDITS_FILE)
+                .with_extension(Self::RESOURCE_FILES_EXTENSION);
+
+            deserialize_json_file(&path).await?
+        };
+
+        let mut languages = Vec::new();
 
         {
-            let textures_file_path = assets_dir_path
-                .join(Self::TEXTURES_FILE)
+            let localization_file_path = assets_dir_path
+                .join(Self::LOCALIZATION_FILE)
                 .with_extension(Self::RESOURCE_FILES_EXTENSION);
 
-            let metadata: Vec<TextureMetadata> = deserialize_json_file(&textures_file_path).await?;
+            let metadata: Vec<LanguageMetadata> =
+                deserialize_json_file(&localization_file_path).await?;
 
             for meta in metadata {
                 let file_path = assets_dir_path.join(&meta.path);
 
-                let texture = load_texture(&file_path.to_string_helper()).await?;
-                texture.set_filter(meta.filter_mode);
+                let strings: HashMap<String, String> = deserialize_json_file(&file_path).await?;
 
-                let sprite_size = {
-                    let val = meta
-                        .sprite_size
-                        .unwrap_or_else(|| vec2(texture.width(), texture.height()).as_u32());
+                languages.push(Language {
+                    id: meta.id,
+                    name: meta.name,
+                    strings,
+                });
+            }
+        }
 
-                    Some(val)
-                };
+        #[allow(clippy::inconsistent_struct_constructor)]
+        let mut resources = Resources {
+            assets_dir: assets_dir.to_string(),
+            particle_effects,
+            particle_emitters,
+            sounds,
+            music,
+            textures,
+            images,
+            maps,
+            training_map,
+            items,
+            item_scripts,
+            loot_tables,
+            player_characters,
+            ability_scripts,
+            announcer_rules,
+            cutscenes,
+            ruleset,
+            game_mode_scripts,
+            mutators,
+            credits,
+            languages,
+            mods: Vec::new(),
+            started_map_cnt: 0,
+        };
 
-                let size = vec2(texture.width(), texture.height());
+        resources.load_mods().await?;
 
-                let key = meta.id.clone();
+        Ok(resources)
+    }
 
-                let meta = TextureMetadata {
-                    sprite_size,
-                    size,
-                    ..meta
-                };
+    /// This loads the manifests of all installed mods and then loads the content of the enabled
+    /// mods on top of the resources that are already loaded, in load order. A mod that fails to
+    /// load will not stop the game from loading, as it can be disabled again from the mod manager.
+    async fn load_mods(&mut self) -> Result<()> {
+        let assets_dir_path = Path::new(&self.assets_dir).to_path_buf();
 
-                let res = TextureResource { texture, meta };
+        let mut mods = load_mod_manifests(&assets_dir_path).await?;
 
-                textures.insert(key, res);
+        let enabled_ids = storage::get::<Config>().enabled_mods.clone();
+
+        for i in resolve_load_order(&mut mods, &enabled_ids) {
+            // Dependencies are loaded first, so a dependency that is not loaded by now has failed,
+            // or has a dependency of its own that failed
+            let failed_dependency = mods[i]
+                .manifest
+                .dependencies
+                .iter()
+                .find(|dependency| {
+                    !mods.iter().any(|other| {
+                        other.manifest.id == dependency.id
+                            && matches!(other.status, ModStatus::Loaded(_))
+                    })
+                })
+                .map(|dependency| dependency.id.clone());
+
+            if let Some(id) = failed_dependency {
+                mods[i].status = ModStatus::MissingDependency(id);
+                continue;
+            }
+
+            let dir = assets_dir_path.join(&mods[i].path);
+
+            if let Err(err) = self.load_mod_content(&dir, &mods[i].manifest.content).await {
+                println!(
+                    "WARNING: Mod '{}' failed to load: {}",
+                    &mods[i].manifest.id, err
+                );
+
+                mods[i].status = ModStatus::Failed;
             }
         }
 
-        let mut images = HashMap::new();
+        self.mods = mods;
 
-        {
-            let images_file_path = assets_dir_path
-                .join(Self::IMAGES_FILE)
-                .with_extension(Self::RESOURCE_FILES_EXTENSION);
+        Ok(())
+    }
+
+    /// This loads the resource files of the kinds in `content`, from the mod directory `dir`,
+    /// replacing any existing resources with the same ids
+    async fn load_mod_content(&mut self, dir: &Path, content: &[ModContent]) -> Result<()> {
+        for content in content {
+            match content {
+                ModContent::ParticleEffects => {
+                    Self::load_particle_effects(dir, &mut self.particle_effects).await?
+                }
+                ModContent::ParticleEmitters => {
+                    Self::load_particle_emitters(dir, &mut self.particle_emitters).await?
+                }
+                ModContent::Sounds => Self::load_sounds(dir, &mut self.sounds).await?,
+                ModContent::Textures => Self::load_textures(dir, &mut self.textures).await?,
+                ModContent::Images => Self::load_images(dir, &mut self.images).await?,
+                ModContent::Maps => Self::load_maps(dir, &mut self.maps).await?,
+                ModContent::Items => {
+                    Self::load_items(dir, &mut self.items, &mut self.item_scripts).await?
+                }
+                ModContent::Cutscenes => Self::load_cutscenes(dir, &mut self.cutscenes).await?,
+                ModContent::LootTables => {
+                    Self::load_loot_tables(dir, &mut self.loot_tables).await?
+                }
+                ModContent::PlayerCharacters => {
+                    Self::load_player_characters(
+                        dir,
+                        &mut self.player_characters,
+                        &mut self.ability_scripts,
+                    )
+                    .await?
+                }
+            }
+        }
 
-            let metadata: Vec<ImageMetadata> = deserialize_json_file(&images_file_path).await?;
+        Ok(())
+    }
 
-            for meta in metadata {
-                let file_path = assets_dir_path.join(&meta.path);
+    async fn load_particle_effects(
+        dir: &Path,
+        particle_effects: &mut HashMap<String, EmitterConfig>,
+    ) -> Result<()> {
+        let particle_effects_file_path = dir
+            .join(Self::PARTICLE_EFFECTS_DIR)
+            .with_extension(Self::RESOURCE_FILES_EXTENSION);
 
-                let image = load_image(&file_path.to_string_helper()).await?;
+        let metadata: Vec<ParticleEffectMetadata> =
+            deserialize_json_file(&particle_effects_file_path).await?;
 
-                let key = meta.id.clone();
+        for meta in metadata {
+            let file_path = dir.join(&meta.path);
 
-                let meta = ImageMetadata {
-                    size: vec2(image.width() as f32, image.height() as f32),
-                    ..meta
-                };
+            let cfg: EmitterConfig = deserialize_json_file(&file_path).await?;
+
+            particle_effects.insert(meta.id, cfg);
+        }
 
-                let res = ImageResource { image, meta };
+        Ok(())
+    }
+
+    async fn load_particle_emitters(
+        dir: &Path,
+        particle_emitters: &mut HashMap<String, ParticleEmitterParams>,
+    ) -> Result<()> {
+        let particle_emitters_file_path = dir
+            .join(Self::PARTICLE_EMITTERS_FILE)
+            .with_extension(Self::RESOURCE_FILES_EXTENSION);
+
+        let metadata: Vec<ParticleEffectMetadata> =
+            deserialize_json_file(&particle_emitters_file_path).await?;
+
+        for meta in metadata {
+            let file_path = dir.join(&meta.path);
+
+            let params: ParticleEmitterParams = deserialize_json_file(&file_path).await?;
+
+            particle_emitters.insert(meta.id, params);
+        }
 
-                images.insert(key, res);
+        Ok(())
+    }
+
+    async fn load_sounds(dir: &Path, sounds: &mut HashMap<String, SoundGroup>) -> Result<()> {
+        let sounds_file_path = dir
+            .join(Self::SOUNDS_FILE)
+            .with_extension(Self::RESOURCE_FILES_EXTENSION);
+
+        let metadata: Vec<SoundMetadata> = deserialize_json_file(&sounds_file_path).await?;
+
+        for meta in metadata {
+            let mut variations = Vec::new();
+
+            for path in meta.path.into_vec() {
+                let file_path = dir.join(path);
+
+                let sound = load_sound(&file_path.to_string_helper()).await?;
+
+                variations.push(sound);
             }
+
+            let sound =
+                SoundGroup::new(&meta.id, variations, meta.volume_jitter, meta.pitch_jitter);
+
+            sounds.insert(meta.id, sound);
         }
 
-        let mut maps = Vec::new();
+        Ok(())
+    }
 
-        {
-            let maps_file_path = assets_dir_path
-                .join(Self::MAPS_FILE)
-                .with_extension(Self::RESOURCE_FILES_EXTENSION);
+    async fn load_textures(dir: &Path, textures: &mut SymbolMap<TextureResource>) -> Result<()> {
+        let textures_file_path = dir
+            .join(Self::TEXTURES_FILE)
+            .with_extension(Self::RESOURCE_FILES_EXTENSION);
 
-            let metadata: Vec<MapMetadata> = deserialize_json_file(&maps_file_path).await?;
+        let metadata: Vec<TextureMetadata> = deserialize_json_file(&textures_file_path).await?;
 
-            for meta in metadata {
-                let map_path = assets_dir_path.join(&meta.path);
-                let preview_path = assets_dir_path.join(&meta.preview_path);
+        for meta in metadata {
+            let file_path = dir.join(&meta.path);
 
-                let map = if meta.is_tiled_map {
-                    Map::load_tiled(map_path, None).await?
-                } else {
-                    Map::load(map_path).await?
-                };
+            let texture = load_texture(&file_path.to_string_helper()).await?;
+            texture.set_filter(meta.filter_mode);
+
+            let sprite_size = {
+                let val = meta
+                    .sprite_size
+                    .unwrap_or_else(|| vec2(texture.width(), texture.height()).as_u32());
+
+                Some(val)
+            };
+
+            let size = vec2(texture.width(), texture.height());
+
+            let key = meta.id.clone();
+
+            let meta = TextureMetadata {
+                sprite_size,
+                size,
+                ..meta
+            };
+
+            let is_map_exclusive = is_map_exclusive_texture(&meta);
+
+            let res = TextureResource {
+                texture,
+                meta,
+                file_path,
+                last_used_by_map: None,
+                is_map_exclusive,
+                is_evicted: false,
+            };
+
+            // This fails on a hash collision, so that `insert` can not alias the ids
+            Symbol::intern(&key)?;
+
+            textures.insert(&key, res);
+        }
+
+        Ok(())
+    }
+
+    async fn load_images(dir: &Path, images: &mut HashMap<String, ImageResource>) -> Result<()> {
+        let images_file_path = dir
+            .join(Self::IMAGES_FILE)
+            .with_extension(Self::RESOURCE_FILES_EXTENSION);
+
+        let metadata: Vec<ImageMetadata> = deserialize_json_file(&images_file_path).await?;
+
+        for meta in metadata {
+            let file_path = dir.join(&meta.path);
+
+            let image = load_image(&file_path.to_string_helper()).await?;
+
+            let key = meta.id.clone();
+
+            let meta = ImageMetadata {
+                size: vec2(image.width() as f32, image.height() as f32),
+                ..meta
+            };
+
+            let res = ImageResource { image, meta };
+
+            images.insert(key, res);
+        }
+
+        Ok(())
+    }
+
+    /// Maps do not have ids, so the maps of mods are added to the map list, in stead of replacing
+    /// any existing maps
+    async fn load_maps(dir: &Path, maps: &mut Vec<MapResource>) -> Result<()> {
+        let maps_file_path = dir
+            .join(Self::MAPS_FILE)
+            .with_extension(Self::RESOURCE_FILES_EXTENSION);
 
-                let preview = load_texture(&preview_path.to_string_helper()).await?;
+        let metadata: Vec<MapMetadata> = deserialize_json_file(&maps_file_path).await?;
 
-                let res = MapResource { map, preview, meta };
+        for meta in metadata {
+            let map_path = dir.join(&meta.path);
+            let preview_path = dir.join(&meta.preview_path);
 
-                maps.push(res)
+            let map = if meta.is_tiled_map {
+                Map::load_tiled(map_path, None).await?
+            } else {
+                Map::load(map_path).await?
+            };
+
+            let preview = load_texture(&preview_path.to_string_helper()).await?;
+
+            let res = MapResource { map, preview, meta };
+
+            maps.push(res)
+        }
+
+        Ok(())
+    }
+
+    async fn load_items(
+        dir: &Path,
+        items: &mut SymbolMap<ItemParams>,
+        item_scripts: &mut HashMap<String, ItemScript>,
+    ) -> Result<()> {
+        let items_file_path = dir
+            .join(Self::ITEMS_FILE)
+            .with_extension(Self::RESOURCE_FILES_EXTENSION);
+
+        let item_paths: Vec<String> = deserialize_json_file(&items_file_path).await?;
+
+        for path in item_paths {
+            let path = dir.join(&path);
+
+            let params: ItemParams = deserialize_json_file(&path).await?;
+
+            // A script of an item that is replaced should not be kept for the new item
+            item_scripts.remove(&params.id);
+
+            if let Some(script_path) = &params.script {
+                let script_path = dir.join(script_path);
+                let source = load_string(&script_path.to_string_helper()).await?;
+
+                let script = ItemScript::compile(&source).map_err(|err| {
+                    formaterr!(
+                        ErrorKind::Parsing,
+                        "Item '{}': Failed to compile script: {}",
+                        &params.id,
+                        err
+                    )
+                })?;
+
+                item_scripts.insert(params.id.clone(), script);
             }
+
+            let id = params.id.clone();
+
+            Symbol::intern(&id)?;
+
+            items.insert(&id, params);
         }
 
-        let mut items = HashMap::new();
+        Ok(())
+    }
 
-        {
-            let items_file_path = assets_dir_path
-                .join(Self::ITEMS_FILE)
-                .with_extension(Self::RESOURCE_FILES_EXTENSION);
+    async fn load_loot_tables(
+        dir: &Path,
+        loot_tables: &mut HashMap<String, LootTable>,
+    ) -> Result<()> {
+        let loot_tables_file_path = dir
+            .join(Self::LOOT_TABLES_FILE)
+            .with_extension(Self::RESOURCE_FILES_EXTENSION);
+
+        let tables: Vec<LootTable> = deserialize_json_file(&loot_tables_file_path).await?;
+
+        for table in tables {
+            loot_tables.insert(table.id.clone(), table);
+        }
+
+        Ok(())
+    }
+
+    async fn load_cutscenes(dir: &Path, cutscenes: &mut HashMap<String, Cutscene>) -> Result<()> {
+        let cutscenes_file_path = dir
+            .join(Self::CUTSCENES_FILE)
+            .with_extension(Self::RESOURCE_FILES_EXTENSION);
+
+        let metadata: Vec<ParticleEffectMetadata> =
+            deserialize_json_file(&cutscenes_file_path).await?;
+
+        for meta in metadata {
+            let file_path = dir.join(&meta.path);
+
+            let cutscene: Cutscene = deserialize_json_file(&file_path).await?;
 
-            let item_paths: Vec<String> = deserialize_json_file(&items_file_path).await?;
+            cutscenes.insert(meta.id, cutscene);
+        }
+
+        Ok(())
+    }
+
+    async fn load_player_characters(
+        dir: &Path,
+        player_characters: &mut Vec<PlayerCharacterParams>,
+        ability_scripts: &mut HashMap<String, AbilityScript>,
+    ) -> Result<()> {
+        let path = dir
+            .join(Self::PLAYER_CHARACTERS_FILE)
+            .with_extension(Self::RESOURCE_FILES_EXTENSION);
+
+        let characters: Vec<PlayerCharacterParams> = deserialize_json_file(&path).await?;
+
+        for character in characters {
+            ability_scripts.remove(&character.id);
+
+            if let Some(script_path) = &character.ability_script {
+                let script_path = dir.join(script_path);
+                let source = load_string(&script_path.to_string_helper()).await?;
 
-            for path in item_paths {
-                let path = assets_dir_path.join(&path);
+                let script = AbilityScript::compile(&source).map_err(|err| {
+                    formaterr!(
+                        ErrorKind::Parsing,
+                        "Player character '{}': Failed to compile ability script: {}",
+                        &character.id,
+                        err
+                    )
+                })?;
 
-                let params: ItemParams = deserialize_json_file(&path).await?;
+                ability_scripts.insert(character.id.clone(), script);
+            }
+
+            let existing = player_characters
+                .iter_mut()
+                .find(|other| other.id == character.id);
 
-                items.insert(params.id.clone(), params);
+            if let Some(existing) = existing {
+                *existing = character;
+            } else {
+                player_characters.push(character);
             }
         }
 
-        let player_characters = {
-            let path = assets_dir_path
-                .join(Self::PLAYER_CHARACTERS_FILE)
-                .with_extension(Self::RESOURCE_FILES_EXTENSION);
+        Ok(())
+    }
 
-            deserialize_json_file(&path).await?
+    pub fn register_cvars() {
+        cvars::register(
+            Self::TEXTURE_EVICTION_CVAR,
+            CVarValue::Int(0),
+            CVarFlags::ARCHIVE,
+            "Unloads map textures that have not been used for this many maps (0 = disabled)",
+        );
+    }
+
+    /// Returns the estimated amount of GPU memory, in bytes, used by all loaded textures,
+    /// including map previews
+    pub fn get_texture_memory_usage(&self) -> usize {
+        let textures = self
+            .textures
+            .values()
+            .map(|res| res.get_memory_usage())
+            .sum::<usize>();
+
+        let previews = self
+            .maps
+            .iter()
+            .chain(std::iter::once(&self.training_map))
+            .map(|res| get_texture_memory_usage(res.preview))
+            .sum::<usize>();
+
+        textures + previews
+    }
+
+    pub fn get_evicted_texture_cnt(&self) -> usize {
+        self.textures.values().filter(|res| res.is_evicted).count()
+    }
+
+    /// This should be called whenever a map is started, before it is drawn. Any evicted textures
+    /// that are used by `map` are reloaded and, if texture eviction is enabled, textures that
+    /// have not been used by any of the maps started since are evicted.
+    ///
+    /// Only textures that are exclusive to the tilesets and backgrounds of maps are ever evicted,
+    /// as those are the textures that accumulate, over long sessions with many custom maps.
+    /// Textures are reloaded from disk, so eviction is not available on WASM.
+    pub fn prepare_map_textures(&mut self, map: &Map) {
+        self.started_map_cnt += 1;
+
+        let texture_ids = map
+            .tilesets
+            .values()
+            .map(|tileset| Symbol::of(&tileset.texture_id))
+            .chain(map.background_layers.iter().map(|layer| layer.texture_id));
+
+        for texture_id in texture_ids {
+            if let Some(res) = self.textures.get_mut(texture_id) {
+                reload_evicted_texture(res);
+
+                res.last_used_by_map = Some(self.started_map_cnt);
+            }
+        }
+
+        let max_unused_map_cnt = if cfg!(target_family = "wasm") {
+            0
+        } else {
+            cvars::get_int(Self::TEXTURE_EVICTION_CVAR).max(0) as u32
         };
 
-        #[allow(clippy::inconsistent_struct_constructor)]
-        Ok(Resources {
-            assets_dir: assets_dir.to_string(),
-            particle_effects,
-            sounds,
-            music,
-            textures,
-            images,
-            maps,
-            items,
-            player_characters,
-        })
+        if max_unused_map_cnt == 0 {
+            return;
+        }
+
+        for res in self
+            .textures
+            .values_mut()
+            .filter(|res| res.is_map_exclusive)
+        {
+            if let Some(last_used) = res.last_used_by_map {
+                if !res.is_evicted && self.started_map_cnt - last_used >= max_unused_map_cnt {
+                    res.texture.delete();
+                    res.is_evicted = true;
+                }
+            }
+        }
+    }
+
+    /// This should be called whenever the editor opens a map. The editor can add any tileset or
+    /// background to the map, so all evicted textures are reloaded, and nothing is evicted until
+    /// the next map is started in game.
+    pub fn prepare_editor_textures(&mut self) {
+        for res in self.textures.values_mut() {
+            reload_evicted_texture(res);
+        }
     }
 
     pub fn create_map(
@@ -347,6 +1018,9 @@ impl Resources {
             preview_path,
             is_tiled_map: false,
             is_user_map: true,
+            music: None,
+            overtime_music: None,
+            intro_cutscene: None,
         };
 
         let map = Map::new(tile_size, grid_size);
@@ -418,6 +1092,31 @@ impl Resources {
     }
 }
 
+/// This reloads the texture of `res` if it has been evicted, and prints a warning if that fails
+fn reload_evicted_texture(res: &mut TextureResource) {
+    if res.is_evicted {
+        if let Err(err) = reload_texture(res) {
+            println!(
+                "WARNING: Resources: Failed to reload texture '{}': {}",
+                res.meta.id, err
+            );
+        }
+    }
+}
+
+/// This reloads the texture of `res` from its file, after it has been evicted
+fn reload_texture(res: &mut TextureResource) -> Result<()> {
+    let bytes = fs::read(&res.file_path)?;
+
+    let texture = Texture2D::from_file_with_format(&bytes, None);
+    texture.set_filter(res.meta.filter_mode);
+
+    res.texture = texture;
+    res.is_evicted = false;
+
+    Ok(())
+}
+
 pub fn map_name_to_filename(name: &str) -> String {
     name.replace(' ', "_").replace('.', "_").to_lowercase()
 }
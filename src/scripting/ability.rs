@@ -0,0 +1,97 @@
+//! Ability scripts give player characters unique abilities, like a puff of smoke on double jumps,
+//! an ink cloud when sliding, or an explosion on death, as content rather than as engine features.
+//! A script is attached to a character by the `ability_script` field of its
+//! `PlayerCharacterParams` and it can implement any of these callbacks:
+//!
+//! - `on_jump(player)` is called when the player jumps, from the ground or in the air
+//! - `on_air_jump(player)` is called when the player jumps in the air
+//! - `on_slide(player)` is called when the player starts a slide
+//! - `on_death(player)` is called when the player is killed
+//!
+//! Ability scripts have access to the same API as item scripts.
+
+use macroquad::experimental::collections::storage;
+
+use rhai::{Dynamic, Scope, AST, INT};
+
+use crate::{Player, Resources, Result};
+
+use super::{get_script_engine, has_function};
+
+/// The callbacks that ability scripts can implement
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AbilityCallback {
+    Jump,
+    AirJump,
+    Slide,
+    Death,
+}
+
+impl AbilityCallback {
+    fn get_function_name(&self) -> &'static str {
+        match self {
+            AbilityCallback::Jump => "on_jump",
+            AbilityCallback::AirJump => "on_air_jump",
+            AbilityCallback::Slide => "on_slide",
+            AbilityCallback::Death => "on_death",
+        }
+    }
+}
+
+/// A compiled ability script
+#[derive(Debug, Clone)]
+pub struct AbilityScript {
+    ast: AST,
+}
+
+impl AbilityScript {
+    pub fn compile(source: &str) -> Result<Self> {
+        let ast = get_script_engine().compile(source)?;
+        Ok(AbilityScript { ast })
+    }
+
+    /// This calls `callback`, if it is implemented by the script. Errors are printed, in stead of
+    /// returned, as a broken script should not stop the game.
+    fn call(&self, character_id: &str, player_id: u8, callback: AbilityCallback) {
+        let name = callback.get_function_name();
+
+        if !has_function(&self.ast, name, 1) {
+            return;
+        }
+
+        let res = get_script_engine().call_fn::<Dynamic>(
+            &mut Scope::new(),
+            &self.ast,
+            name,
+            (player_id as INT,),
+        );
+
+        if let Err(err) = res {
+            println!(
+                "WARNING: Ability script of '{}' failed in '{}': {}",
+                character_id, name, err
+            );
+        }
+    }
+}
+
+/// This calls `callback` on the ability script of the character with the id `character_id`, if it
+/// has one. The call is queued and run later in the same tick, by
+/// `Player::apply_pending_interactions`, as the player that triggers the callback is borrowed when
+/// this is called.
+pub fn run_ability_callback(character_id: &str, player_id: u8, callback: AbilityCallback) {
+    Player::on_ability_callback(character_id, player_id, callback);
+}
+
+/// This calls `callback` on the ability script immediately. It must only be called when no nodes
+/// are borrowed.
+pub fn call_ability_callback(character_id: &str, player_id: u8, callback: AbilityCallback) {
+    let script = storage::get::<Resources>()
+        .ability_scripts
+        .get(character_id)
+        .cloned();
+
+    if let Some(script) = script {
+        script.call(character_id, player_id, callback);
+    }
+}
@@ -0,0 +1,165 @@
+//! The functions that are available to item and ability scripts. Players are identified by their id, and
+//! functions that are given the id of a player that does not exist do nothing, or return a
+//! default value, so that a script can not crash the game.
+
+use macroquad::{
+    experimental::{collections::storage, scene},
+    prelude::*,
+};
+
+use rhai::{Engine, FLOAT, INT};
+
+use crate::{
+    audio::play_sound_at,
+    effects::active::{explode_ex, ExplosionFalloff, ExplosionParams},
+    game::GameCamera,
+    player::{with_player, DamageParams},
+    ParticleEmitters, Player, Resources,
+};
+
+/// The knockback, at the origin, of explosions created by scripts
+const EXPLOSION_KNOCKBACK: f32 = 600.0;
+
+pub fn register(engine: &mut Engine) {
+    register_physics(engine);
+    register_effects(engine);
+    register_sounds(engine);
+}
+
+fn register_physics(engine: &mut Engine) {
+    engine.register_fn("position_x", |player: INT| -> FLOAT {
+        with_player(player, 0.0, |player| player.body.position.x as FLOAT)
+    });
+
+    engine.register_fn("position_y", |player: INT| -> FLOAT {
+        with_player(player, 0.0, |player| player.body.position.y as FLOAT)
+    });
+
+    engine.register_fn("velocity_x", |player: INT| -> FLOAT {
+        with_player(player, 0.0, |player| player.body.velocity.x as FLOAT)
+    });
+
+    engine.register_fn("velocity_y", |player: INT| -> FLOAT {
+        with_player(player, 0.0, |player| player.body.velocity.y as FLOAT)
+    });
+
+    engine.register_fn("set_velocity", |player: INT, x: FLOAT, y: FLOAT| {
+        with_player(player, (), |player| {
+            player.body.velocity = vec2(x as f32, y as f32);
+        })
+    });
+
+    engine.register_fn("apply_knockback", |player: INT, x: FLOAT, y: FLOAT| {
+        with_player(player, (), |player| {
+            player.body.apply_knockback(vec2(x as f32, y as f32));
+        })
+    });
+
+    engine.register_fn("is_facing_right", |player: INT| -> bool {
+        with_player(player, false, |player| player.body.is_facing_right)
+    });
+
+    engine.register_fn("is_on_ground", |player: INT| -> bool {
+        with_player(player, false, |player| player.body.is_on_ground)
+    });
+}
+
+fn register_effects(engine: &mut Engine) {
+    engine.register_fn(
+        "damage_player",
+        |target: INT, damage: FLOAT, attacker: INT| {
+            let target = with_player(target, None, |player| {
+                Some((player.handle(), player.body.position))
+            });
+
+            let attacker = with_player(attacker, None, |player| {
+                Some((player.handle(), player.body.position))
+            });
+
+            if let Some((target_handle, target_position)) = target {
+                let is_from_right = attacker
+                    .map(|(_, position)| position.x > target_position.x)
+                    .unwrap_or(false);
+
+                Player::on_receive_damage_ex(
+                    target_handle,
+                    is_from_right,
+                    attacker.map(|(handle, _)| handle),
+                    damage as f32,
+                    DamageParams {
+                        is_from_script: true,
+                        ..Default::default()
+                    },
+                );
+            }
+        },
+    );
+
+    engine.register_fn("spawn_particles", |id: &str, x: FLOAT, y: FLOAT| {
+        let is_valid = {
+            let resources = storage::get::<Resources>();
+            resources.particle_emitters.contains_key(id)
+                || resources.particle_effects.contains_key(id)
+        };
+
+        if !is_valid {
+            println!("WARNING: Item script: Invalid particle effect id '{}'", id);
+            return;
+        }
+
+        if let Some(mut particles) = scene::find_node_by_type::<ParticleEmitters>() {
+            particles.spawn(id, vec2(x as f32, y as f32));
+        }
+    });
+
+    engine.register_fn(
+        "explode",
+        |x: FLOAT, y: FLOAT, radius: FLOAT, damage: FLOAT, owner: INT| {
+            let owner = with_player(owner, None, |player| Some(player.handle()));
+
+            if let Some(owner) = owner {
+                let params = ExplosionParams {
+                    radius: radius as f32,
+                    damage_falloff: ExplosionFalloff::Linear,
+                    knockback: EXPLOSION_KNOCKBACK,
+                    knockback_falloff: ExplosionFalloff::Linear,
+                    ignores_line_of_sight: false,
+                    camera_shake: 0.0,
+                    particle_effect_id: None,
+                    debris_particle_effect_id: None,
+                };
+
+                let damage_params = DamageParams {
+                    is_from_script: true,
+                    ..Default::default()
+                };
+
+                explode_ex(
+                    owner,
+                    vec2(x as f32, y as f32),
+                    damage as f32,
+                    &params,
+                    damage_params,
+                );
+            }
+        },
+    );
+
+    engine.register_fn("shake_camera", |magnitude: FLOAT| {
+        if let Some(mut camera) = scene::find_node_by_type::<GameCamera>() {
+            camera.add_trauma(magnitude as f32);
+        }
+    });
+}
+
+fn register_sounds(engine: &mut Engine) {
+    engine.register_fn("play_sound", |id: &str, x: FLOAT, y: FLOAT| {
+        let resources = storage::get::<Resources>();
+
+        if let Some(sound) = resources.sounds.get(id) {
+            play_sound_at(sound, vec2(x as f32, y as f32));
+        } else {
+            println!("WARNING: Item script: Invalid sound id '{}'", id);
+        }
+    });
+}
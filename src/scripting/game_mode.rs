@@ -0,0 +1,183 @@
+//! Scripted game modes are listed in `game_modes.json`, in the assets directory, and they are
+//! picked, like any other game mode, by the `game_mode` of the `Ruleset`. A script can implement
+//! any of these functions:
+//!
+//! - `setup(players)` is called when the match is started, with an array of player ids
+//! - `on_kill(player, killed_by)` is called when a player is killed. `killed_by` is `-1` if the
+//!   kill can not be attributed to another player.
+//! - `on_damage(player, damaged_by, damage)` is called when a player receives damage
+//! - `on_pickup(player, item)` is called when a player picks up an item
+//! - `on_tick(dt)` is called on every simulation frame
+//! - `check_win()` returns the id of the player that has won the match, or `-1`
+//! - `hud_text()` returns a string that is drawn at the top of the screen
+//!
+//! The state of the mode is kept in an object map that is bound to `this` in all the functions,
+//! so that a king of the hill mode can, for example, count the time each player has held the hill
+//! in `this.hill_time`, in `on_tick`.
+
+use macroquad::{color, prelude::*};
+
+use rhai::{Array, Dynamic, Map, Scope, AST, FLOAT, INT};
+
+use crate::{
+    game::{GameEvent, GameMode, MatchStats},
+    text::{draw_aligned_text, HorizontalAlignment, VerticalAlignment},
+    Result,
+};
+
+use super::{get_script_engine, has_function};
+
+/// A compiled game mode script
+#[derive(Debug, Clone)]
+pub struct GameModeScript {
+    ast: AST,
+}
+
+impl GameModeScript {
+    pub fn compile(source: &str) -> Result<Self> {
+        let ast = get_script_engine().compile(source)?;
+        Ok(GameModeScript { ast })
+    }
+}
+
+pub struct ScriptedGameMode {
+    id: String,
+    ast: AST,
+    /// The state of the mode, which is bound to `this` in the functions of the script
+    state: Dynamic,
+}
+
+impl ScriptedGameMode {
+    const HUD_FONT_SIZE: u16 = 24;
+    const HUD_MARGIN: f32 = 16.0;
+
+    pub fn new(id: &str, script: &GameModeScript) -> Self {
+        ScriptedGameMode {
+            id: id.to_string(),
+            ast: script.ast.clone(),
+            state: Dynamic::from(Map::new()),
+        }
+    }
+
+    /// This calls the function named `name`, if it is implemented by the script, and returns its
+    /// result. Errors are printed, in stead of returned, as a broken script should not stop the
+    /// game.
+    fn call(&mut self, name: &str, mut args: Vec<Dynamic>) -> Option<Dynamic> {
+        if !has_function(&self.ast, name, args.len()) {
+            return None;
+        }
+
+        let res = get_script_engine().call_fn_raw(
+            &mut Scope::new(),
+            &self.ast,
+            false,
+            false,
+            name,
+            Some(&mut self.state),
+            &mut args,
+        );
+
+        match res {
+            Ok(value) => Some(value),
+            Err(err) => {
+                println!(
+                    "WARNING: Game mode script '{}' failed in '{}': {}",
+                    &self.id, name, err
+                );
+
+                None
+            }
+        }
+    }
+}
+
+impl GameMode for ScriptedGameMode {
+    fn setup(&mut self, player_ids: &[u8]) {
+        let players = player_ids
+            .iter()
+            .map(|id| Dynamic::from(*id as INT))
+            .collect::<Array>();
+
+        self.call("setup", vec![Dynamic::from(players)]);
+    }
+
+    fn on_event(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::PlayerKilled {
+                player_id,
+                killed_by,
+                ..
+            } => {
+                let killed_by = killed_by.map(|id| id as INT).unwrap_or(-1);
+
+                self.call(
+                    "on_kill",
+                    vec![Dynamic::from(*player_id as INT), Dynamic::from(killed_by)],
+                );
+            }
+            GameEvent::PlayerDamaged {
+                player_id,
+                damage,
+                damaged_by,
+                ..
+            } => {
+                let damaged_by = damaged_by.map(|id| id as INT).unwrap_or(-1);
+
+                self.call(
+                    "on_damage",
+                    vec![
+                        Dynamic::from(*player_id as INT),
+                        Dynamic::from(damaged_by),
+                        Dynamic::from(*damage as FLOAT),
+                    ],
+                );
+            }
+            GameEvent::ItemPickedUp {
+                player_id, item_id, ..
+            } => {
+                self.call(
+                    "on_pickup",
+                    vec![
+                        Dynamic::from(*player_id as INT),
+                        Dynamic::from(item_id.clone()),
+                    ],
+                );
+            }
+            _ => {}
+        }
+    }
+
+    fn update(&mut self, dt: f32) {
+        self.call("on_tick", vec![Dynamic::from(dt as FLOAT)]);
+    }
+
+    fn check_win_condition(&mut self, _match_stats: &MatchStats) -> Option<u8> {
+        self.call("check_win", Vec::new())
+            .and_then(|res| res.as_int().ok())
+            .filter(|id| *id >= 0 && *id <= u8::MAX as INT)
+            .map(|id| id as u8)
+    }
+
+    fn hud_overlay(&mut self) {
+        let text = self
+            .call("hud_text", Vec::new())
+            .map(|res| res.to_string())
+            .unwrap_or_default();
+
+        if text.is_empty() {
+            return;
+        }
+
+        draw_aligned_text(
+            &text,
+            vec2(screen_width() / 2.0, Self::HUD_MARGIN),
+            HorizontalAlignment::Center,
+            VerticalAlignment::Top,
+            TextParams {
+                font_size: Self::HUD_FONT_SIZE,
+                color: color::WHITE,
+                ..Default::default()
+            },
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! Item scripts let custom items define their behavior in Rhai script files, without recompiling
+//! the game. A script is attached to an item by the `script` field of its `ItemParams` and it is
+//! compiled when resources are loaded. It can implement any of these callbacks:
+//!
+//! - `on_equip(player, item)` is called when a player picks up the item
+//! - `on_use(player, item)` is called when a player attacks with the item, if it is a weapon
+//! - `on_hit(player, target, item)` is called when a player damages another player, while holding
+//!   or having equipped the item, unless the damage was dealt by a script
+//!
+//! Item callbacks are called by the listener of the game events that trigger them, so they are run
+//! within the simulation tick, after the nodes have been updated, and the scripting API can
+//! access any player, including the one that triggered the callback.
+//!
+//! Players are passed to scripts by their id and items by their id. Scripts can not access
+//! anything but the functions registered in `api`, which cover physics, effects and sounds, and
+//! the engine limits the amount of operations a single call can run, so that a broken script can
+//! not hang the game.
+//!
+//! Game modes and character abilities can also be implemented in scripts, which is documented in
+//! `game_mode` and `ability`.
+
+use macroquad::experimental::{collections::storage, scene};
+
+use rhai::{Dynamic, Engine, Scope, AST, INT};
+
+use crate::{game::GameEvent, Player, Resources, Result};
+
+mod ability;
+mod api;
+mod game_mode;
+
+pub use ability::{call_ability_callback, run_ability_callback, AbilityCallback, AbilityScript};
+pub use game_mode::{GameModeScript, ScriptedGameMode};
+
+/// The maximum amount of operations that a single callback can run, before it is aborted
+const MAX_OPERATIONS: u64 = 100_000;
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_STRING_SIZE: usize = 1024;
+const MAX_ARRAY_SIZE: usize = 1024;
+
+static mut SCRIPT_ENGINE: Option<Engine> = None;
+
+fn get_script_engine() -> &'static Engine {
+    unsafe { SCRIPT_ENGINE.get_or_insert_with(create_script_engine) }
+}
+
+fn create_script_engine() -> Engine {
+    let mut engine = Engine::new();
+
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(MAX_CALL_LEVELS);
+    engine.set_max_string_size(MAX_STRING_SIZE);
+    engine.set_max_array_size(MAX_ARRAY_SIZE);
+
+    api::register(&mut engine);
+
+    engine
+}
+
+/// Returns `true` if `ast` has a function named `name`, that takes `param_cnt` parameters
+fn has_function(ast: &AST, name: &str, param_cnt: usize) -> bool {
+    ast.iter_functions()
+        .any(|f| f.name == name && f.params.len() == param_cnt)
+}
+
+/// The callbacks that item scripts can implement
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ItemCallback {
+    Equip,
+    Use,
+    /// The holder of the item damaged the player with the id `target`
+    Hit {
+        target: u8,
+    },
+}
+
+impl ItemCallback {
+    fn get_function_name(&self) -> &'static str {
+        match self {
+            ItemCallback::Equip => "on_equip",
+            ItemCallback::Use => "on_use",
+            ItemCallback::Hit { .. } => "on_hit",
+        }
+    }
+}
+
+/// A compiled item script
+#[derive(Debug, Clone)]
+pub struct ItemScript {
+    ast: AST,
+}
+
+impl ItemScript {
+    pub fn compile(source: &str) -> Result<Self> {
+        let ast = get_script_engine().compile(source)?;
+        Ok(ItemScript { ast })
+    }
+
+    fn has_function(&self, name: &str, param_cnt: usize) -> bool {
+        has_function(&self.ast, name, param_cnt)
+    }
+
+    /// This calls `callback`, if it is implemented by the script. Errors are printed, in stead of
+    /// returned, as a broken script should not stop the game.
+    fn call(&self, item_id: &str, player_id: u8, callback: ItemCallback) {
+        let name = callback.get_function_name();
+
+        let player = player_id as INT;
+        let item = item_id.to_string();
+
+        let res = match callback {
+            ItemCallback::Equip | ItemCallback::Use => {
+                if !self.has_function(name, 2) {
+                    return;
+                }
+
+                get_script_engine().call_fn::<Dynamic>(
+                    &mut Scope::new(),
+                    &self.ast,
+                    name,
+                    (player, item),
+                )
+            }
+            ItemCallback::Hit { target } => {
+                if !self.has_function(name, 3) {
+                    return;
+                }
+
+                get_script_engine().call_fn::<Dynamic>(
+                    &mut Scope::new(),
+                    &self.ast,
+                    name,
+                    (player, target as INT, item),
+                )
+            }
+        };
+
+        if let Err(err) = res {
+            println!(
+                "WARNING: Item script of '{}' failed in '{}': {}",
+                item_id, name, err
+            );
+        }
+    }
+}
+
+/// This calls `callback` on the script of the item with the id `item_id`, if it has one. It must
+/// only be called by the game event listener, when no nodes are borrowed.
+fn run_item_callback(item_id: &str, player_id: u8, callback: ItemCallback) {
+    let script = storage::get::<Resources>()
+        .item_scripts
+        .get(item_id)
+        .cloned();
+
+    if let Some(script) = script {
+        script.call(item_id, player_id, callback);
+    }
+}
+
+pub const LISTENER_ID: &str = "item_scripts";
+
+/// This calls `on_equip` on the script of items that are picked up, `on_use` on the script of
+/// weapons that are attacked with, and `on_hit` on the scripts of the items held, or equipped, by
+/// a player that damages another player
+pub fn on_game_event(event: &GameEvent) {
+    if let GameEvent::ItemPickedUp {
+        player_id, item_id, ..
+    } = event
+    {
+        run_item_callback(item_id, *player_id, ItemCallback::Equip);
+    }
+
+    if let GameEvent::ItemUsed { player_id, item_id } = event {
+        run_item_callback(item_id, *player_id, ItemCallback::Use);
+    }
+
+    if let GameEvent::PlayerDamaged {
+        player_id,
+        damaged_by: Some(damaged_by),
+        is_from_script: false,
+        ..
+    } = *event
+    {
+        if player_id == damaged_by {
+            return;
+        }
+
+        let item_ids = scene::find_nodes_by_type::<Player>()
+            .find(|player| player.id == damaged_by)
+            .map(|player| {
+                let mut item_ids = player.equipped_items.keys().cloned().collect::<Vec<_>>();
+
+                for weapon in [&player.weapon, &player.off_hand_weapon]
+                    .iter()
+                    .copied()
+                    .flatten()
+                {
+                    item_ids.push(weapon.id.clone());
+                }
+
+                item_ids
+            })
+            .unwrap_or_default();
+
+        for item_id in item_ids {
+            run_item_callback(
+                &item_id,
+                damaged_by,
+                ItemCallback::Hit { target: player_id },
+            );
+        }
+    }
+}
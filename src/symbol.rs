@@ -0,0 +1,307 @@
+//! Symbols are interned ids, like the ids of animations, textures and items, that are compared and
+//! hashed as a single integer, in stead of as a string, so that they can be looked up every frame
+//! without any string comparisons or string hashing.
+//!
+//! The value of a symbol is a hash of its id, which can be computed at compile time, so ids that
+//! are known up front, like the ids of the animations of players, can be declared as consts with
+//! `Symbol::of`. The strings of ids are only needed to print them, so they are kept in a global
+//! lookup table, that ids are added to when they are interned with `Symbol::new`. Symbols are
+//! interned when they are deserialized, so all ids that are declared in assets are in the table.
+//!
+//! Two different ids can, in theory, have the same hash. Ids that are loaded from assets are
+//! interned with `Symbol::intern`, or by deserialization, which fail if the hash of an id collides
+//! with that of another interned id, so that the assets can not be loaded with two ids aliased.
+//!
+//! Converting a `&str` into a symbol, with `From`, only hashes it. This is how ids are looked up,
+//! so the ids that are looked up like this should have been interned when the assets were loaded,
+//! or a symbol that is printed, like in a warning about an invalid id, will print as its hash.
+//!
+//! Symbols are serialized as strings, so they can replace `String` ids in any of the JSON formats
+//! of the game, without changing the files.
+
+use std::{
+    collections::{hash_map, HashMap},
+    fmt,
+    hash::{BuildHasherDefault, Hasher},
+};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::ErrorKind;
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Symbol(u64);
+
+static mut SYMBOL_TABLE: Option<HashMap<u64, &'static str>> = None;
+
+unsafe fn get_symbol_table() -> &'static mut HashMap<u64, &'static str> {
+    SYMBOL_TABLE.get_or_insert_with(HashMap::new)
+}
+
+impl Symbol {
+    /// This interns `id` and returns its symbol. The string of an id is only stored once, no
+    /// matter how many times it is interned. If the hash of `id` collides with that of another
+    /// interned id, a warning is printed and the symbol will print as the id that was interned
+    /// first. This is for ids that are declared in code or created in the editor, and ids that
+    /// are loaded from assets should be interned with `intern` in stead.
+    pub fn new(id: &str) -> Self {
+        Self::try_new(id).unwrap_or_else(|existing| {
+            println!(
+                "WARNING: Symbol: The ids '{}' and '{}' have the same hash",
+                existing, id
+            );
+
+            Self::of(id)
+        })
+    }
+
+    /// This interns `id` and returns its symbol, or the id that has already been interned with the
+    /// same hash, if there is a collision
+    pub fn try_new(id: &str) -> Result<Self, &'static str> {
+        let res = Self::of(id);
+
+        let table = unsafe { get_symbol_table() };
+
+        match table.get(&res.0) {
+            Some(existing) if *existing != id => return Err(existing),
+            Some(_) => {}
+            None => {
+                table.insert(res.0, Box::leak(id.to_string().into_boxed_str()));
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// This interns `id` and returns its symbol, or a parsing error if its hash collides with
+    /// that of another interned id
+    pub fn intern(id: &str) -> crate::Result<Self> {
+        Self::try_new(id).map_err(|existing| {
+            formaterr!(
+                ErrorKind::Parsing,
+                "Symbol: The ids '{}' and '{}' have the same hash",
+                existing,
+                id
+            )
+        })
+    }
+
+    /// Returns the symbol of `id`, without interning it. This can be used in consts, and to look
+    /// up ids, but the symbol can only be converted back to a string if `id` has been interned.
+    pub const fn of(id: &str) -> Self {
+        // FNV-1a
+        let bytes = id.as_bytes();
+
+        let mut hash = 0xcbf2_9ce4_8422_2325u64;
+        let mut i = 0;
+
+        while i < bytes.len() {
+            hash ^= bytes[i] as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+            i += 1;
+        }
+
+        Symbol(hash)
+    }
+
+    /// Returns the id of the symbol, or `None` if it has not been interned
+    pub fn as_str(&self) -> Option<&'static str> {
+        unsafe { get_symbol_table() }.get(&self.0).copied()
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(id: &str) -> Self {
+        Symbol::of(id)
+    }
+}
+
+impl From<&String> for Symbol {
+    fn from(id: &String) -> Self {
+        Symbol::of(id)
+    }
+}
+
+impl From<&Symbol> for Symbol {
+    fn from(symbol: &Symbol) -> Self {
+        *symbol
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        *self == Symbol::of(other)
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        *self == Symbol::of(other)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.as_str() {
+            Some(id) => write!(f, "{}", id),
+            None => write!(f, "#{:016x}", self.0),
+        }
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Symbol({})", self)
+    }
+}
+
+impl Serialize for Symbol {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.as_str() {
+            Some(id) => serializer.serialize_str(id),
+            None => Err(serde::ser::Error::custom(format!(
+                "The symbol {} has not been interned",
+                self
+            ))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct SymbolVisitor;
+
+        impl<'de> de::Visitor<'de> for SymbolVisitor {
+            type Value = Symbol;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a string id")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Symbol::intern(value).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(SymbolVisitor)
+    }
+}
+
+/// Symbols are already hashes, so they are used as is, by maps of symbols, in stead of being
+/// hashed again
+#[derive(Default)]
+pub struct SymbolHasher(u64);
+
+impl Hasher for SymbolHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 << 8) | *byte as u64;
+        }
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.0 = value;
+    }
+}
+
+/// A map of values by symbol. Any id that can be converted into a symbol, like a `&str`, can be
+/// used to look up values, which only interns the ids of the values that are inserted.
+#[derive(Debug, Clone)]
+pub struct SymbolMap<V> {
+    map: HashMap<Symbol, V, BuildHasherDefault<SymbolHasher>>,
+}
+
+#[allow(dead_code)]
+impl<V> SymbolMap<V> {
+    pub fn new() -> Self {
+        SymbolMap {
+            map: HashMap::default(),
+        }
+    }
+
+    /// This inserts `value` as `id`, interning `id`, and returns any value that was replaced
+    pub fn insert(&mut self, id: &str, value: V) -> Option<V> {
+        self.map.insert(Symbol::new(id), value)
+    }
+
+    pub fn remove<K: Into<Symbol>>(&mut self, id: K) -> Option<V> {
+        self.map.remove(&id.into())
+    }
+
+    pub fn get<K: Into<Symbol>>(&self, id: K) -> Option<&V> {
+        self.map.get(&id.into())
+    }
+
+    pub fn get_mut<K: Into<Symbol>>(&mut self, id: K) -> Option<&mut V> {
+        self.map.get_mut(&id.into())
+    }
+
+    pub fn contains_key<K: Into<Symbol>>(&self, id: K) -> bool {
+        self.map.contains_key(&id.into())
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn iter(&self) -> hash_map::Iter<'_, Symbol, V> {
+        self.map.iter()
+    }
+
+    pub fn keys(&self) -> hash_map::Keys<'_, Symbol, V> {
+        self.map.keys()
+    }
+
+    pub fn values(&self) -> hash_map::Values<'_, Symbol, V> {
+        self.map.values()
+    }
+
+    pub fn values_mut(&mut self) -> hash_map::ValuesMut<'_, Symbol, V> {
+        self.map.values_mut()
+    }
+}
+
+impl<V> Default for SymbolMap<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbol_lookup() {
+        const IDLE: Symbol = Symbol::of("idle");
+
+        assert_eq!(IDLE.as_str(), None);
+        assert_eq!(Symbol::new("idle"), IDLE);
+        assert_eq!(IDLE.as_str(), Some("idle"));
+        assert_ne!(Symbol::of("move"), IDLE);
+
+        let mut map = SymbolMap::new();
+        map.insert("idle", 1);
+
+        assert_eq!(map.get("idle"), Some(&1));
+        assert_eq!(map.get(IDLE), Some(&1));
+        assert_eq!(map.get("move"), None);
+    }
+}
@@ -0,0 +1,274 @@
+//! A weather layer, that fills the view of the camera with rain, snow or underwater bubbles. The
+//! kind of weather is set with the `weather` property of a map, and its intensity, between `0.0`
+//! and `1.0`, with the `weather_intensity` property.
+//!
+//! If the `weather_wind` property of the map is `true`, weather particles will drift with the wind
+//! of the map and be pushed by force volumes.
+//!
+//! Weather particles are purely cosmetic and are kept within the view of the camera, wrapping
+//! around its edges, so their amount does not depend on the size of the map.
+
+use macroquad::{
+    experimental::scene::{self, RefMut},
+    prelude::*,
+};
+
+use crate::{
+    cosmetic_rand, json::GenericParam, physics::ForceVolumes, AreaEffects, GameCamera, Map,
+};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum WeatherKind {
+    Rain,
+    Snow,
+    Bubbles,
+}
+
+impl WeatherKind {
+    /// The amount of particles at full intensity
+    fn get_max_particles(&self) -> usize {
+        match self {
+            Self::Rain => 400,
+            Self::Snow => 250,
+            Self::Bubbles => 80,
+        }
+    }
+
+    fn get_velocity(&self) -> Vec2 {
+        match self {
+            Self::Rain => vec2(-60.0, 700.0),
+            Self::Snow => vec2(0.0, 60.0),
+            Self::Bubbles => vec2(0.0, -50.0),
+        }
+    }
+
+    /// The amplitude of the horizontal sway of particles
+    fn get_sway(&self) -> f32 {
+        match self {
+            Self::Rain => 0.0,
+            Self::Snow => 20.0,
+            Self::Bubbles => 12.0,
+        }
+    }
+
+    fn get_size_range(&self) -> (f32, f32) {
+        match self {
+            Self::Rain => (8.0, 14.0),
+            Self::Snow => (1.5, 3.0),
+            Self::Bubbles => (2.0, 5.0),
+        }
+    }
+
+    fn get_color(&self) -> Color {
+        match self {
+            Self::Rain => Color::new(0.7, 0.8, 1.0, 0.5),
+            Self::Snow => Color::new(1.0, 1.0, 1.0, 0.8),
+            Self::Bubbles => Color::new(0.8, 0.95, 1.0, 0.6),
+        }
+    }
+
+    fn from_str(value: &str) -> Option<Self> {
+        match value {
+            "rain" => Some(Self::Rain),
+            "snow" => Some(Self::Snow),
+            "bubbles" => Some(Self::Bubbles),
+            _ => None,
+        }
+    }
+}
+
+struct WeatherParticle {
+    position: Vec2,
+    velocity: Vec2,
+    size: f32,
+    /// A random offset for the sway of the particle, so that particles do not sway in unison
+    phase: f32,
+}
+
+pub struct Weather {
+    pub kind: WeatherKind,
+    /// The current intensity, which moves towards `target_intensity` over time
+    intensity: f32,
+    target_intensity: f32,
+    is_affected_by_wind: bool,
+    wind: Vec2,
+    particles: Vec<WeatherParticle>,
+}
+
+impl Weather {
+    pub const WEATHER_MAP_PROPERTY: &'static str = "weather";
+    pub const INTENSITY_MAP_PROPERTY: &'static str = "weather_intensity";
+    pub const WIND_MAP_PROPERTY: &'static str = "weather_wind";
+
+    const DEFAULT_INTENSITY: f32 = 0.5;
+
+    /// The change in intensity per second, when the intensity is changed
+    const INTENSITY_CHANGE_SPEED: f32 = 0.2;
+
+    /// The factor of the force of force volumes that is applied to weather particles
+    const FORCE_FACTOR: f32 = 0.1;
+
+    /// This will return `None` if no weather is set in the properties of `map`
+    pub fn new(map: &Map) -> Option<Self> {
+        let kind = map
+            .properties
+            .get(Self::WEATHER_MAP_PROPERTY)
+            .and_then(|param| param.get_value::<String>())
+            .and_then(|value| WeatherKind::from_str(value))?;
+
+        let intensity = map
+            .properties
+            .get(Self::INTENSITY_MAP_PROPERTY)
+            .and_then(GenericParam::to_f32)
+            .unwrap_or(Self::DEFAULT_INTENSITY)
+            .clamp(0.0, 1.0);
+
+        let is_affected_by_wind = map
+            .properties
+            .get(Self::WIND_MAP_PROPERTY)
+            .and_then(|param| param.get_value::<bool>())
+            .copied()
+            .unwrap_or(false);
+
+        let wind = if is_affected_by_wind {
+            AreaEffects::get_wind(map)
+        } else {
+            Vec2::ZERO
+        };
+
+        Some(Weather {
+            kind,
+            intensity,
+            target_intensity: intensity,
+            is_affected_by_wind,
+            wind,
+            particles: Vec::new(),
+        })
+    }
+
+    pub fn get_intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    /// This changes the intensity of the weather, which will fade gradually from the current
+    /// intensity to `intensity`
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.target_intensity = intensity.clamp(0.0, 1.0);
+    }
+
+    fn spawn_particle(&self, view: &Rect, is_initial: bool) -> WeatherParticle {
+        let (min_size, max_size) = self.kind.get_size_range();
+
+        let x = cosmetic_rand::gen_range(view.x, view.x + view.w);
+        let y = if is_initial {
+            cosmetic_rand::gen_range(view.y, view.y + view.h)
+        } else if self.kind.get_velocity().y < 0.0 {
+            view.y + view.h
+        } else {
+            view.y
+        };
+
+        WeatherParticle {
+            position: vec2(x, y),
+            velocity: self.kind.get_velocity() * cosmetic_rand::gen_range(0.8, 1.2),
+            size: cosmetic_rand::gen_range(min_size, max_size),
+            phase: cosmetic_rand::gen_range(0.0, std::f32::consts::PI * 2.0),
+        }
+    }
+
+    /// Returns the rect in the world that is visible through `camera`
+    fn get_view_rect(camera: &Camera2D) -> Rect {
+        let min = camera.screen_to_world(vec2(0.0, 0.0));
+        let max = camera.screen_to_world(vec2(screen_width(), screen_height()));
+
+        Rect::new(
+            min.x.min(max.x),
+            min.y.min(max.y),
+            (max.x - min.x).abs(),
+            (max.y - min.y).abs(),
+        )
+    }
+}
+
+impl scene::Node for Weather {
+    fn update(mut node: RefMut<Self>) {
+        let dt = get_frame_time();
+
+        let view = match scene::find_node_by_type::<GameCamera>() {
+            Some(camera) => Self::get_view_rect(&camera.get_macroquad_camera()),
+            None => return,
+        };
+
+        if node.intensity != node.target_intensity {
+            let delta = node.target_intensity - node.intensity;
+            let step = Self::INTENSITY_CHANGE_SPEED * dt;
+
+            node.intensity += delta.clamp(-step, step);
+        }
+
+        let target_amount = (node.kind.get_max_particles() as f32 * node.intensity) as usize;
+
+        let is_initial = node.particles.is_empty();
+        while node.particles.len() < target_amount {
+            let particle = node.spawn_particle(&view, is_initial);
+            node.particles.push(particle);
+        }
+
+        node.particles.truncate(target_amount);
+
+        let base_velocity = node.kind.get_velocity();
+        let sway = node.kind.get_sway();
+        let time = get_time() as f32;
+
+        let node = &mut *node;
+        for particle in &mut node.particles {
+            let mut velocity = particle.velocity + node.wind;
+
+            if node.is_affected_by_wind {
+                particle.velocity +=
+                    ForceVolumes::get_force(particle.position) * Self::FORCE_FACTOR * dt;
+
+                // Particles slowly return to their base velocity, after being pushed
+                particle.velocity = particle.velocity.lerp(base_velocity, dt);
+            }
+
+            velocity.x += (time * 2.0 + particle.phase).sin() * sway;
+
+            particle.position += velocity * dt;
+
+            // Particles that leave the view wrap around to the opposite edge
+            if particle.position.x < view.x {
+                particle.position.x += view.w;
+            } else if particle.position.x > view.x + view.w {
+                particle.position.x -= view.w;
+            }
+
+            if particle.position.y < view.y {
+                particle.position.y += view.h;
+            } else if particle.position.y > view.y + view.h {
+                particle.position.y -= view.h;
+            }
+        }
+    }
+
+    fn draw(node: RefMut<Self>) {
+        let color = node.kind.get_color();
+
+        for particle in &node.particles {
+            let position = particle.position;
+
+            match node.kind {
+                WeatherKind::Rain => {
+                    let end = position - particle.velocity.normalize_or_zero() * particle.size;
+                    draw_line(position.x, position.y, end.x, end.y, 1.0, color);
+                }
+                WeatherKind::Snow => {
+                    draw_circle(position.x, position.y, particle.size, color);
+                }
+                WeatherKind::Bubbles => {
+                    draw_circle_lines(position.x, position.y, particle.size, 1.0, color);
+                }
+            }
+        }
+    }
+}